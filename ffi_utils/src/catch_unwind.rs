@@ -17,28 +17,50 @@
 
 use super::{ErrorCode, FfiResult};
 use super::callback::{Callback, CallbackArgs};
+use std::any::Any;
 use std::fmt::{Debug, Display};
+use std::io;
 use std::os::raw::c_void;
 use std::panic::{self, AssertUnwindSafe};
+use std::thread;
+
+/// Error produced when a panic is caught at the FFI boundary. Carries the panic message, if
+/// there was one.
+#[derive(Debug)]
+pub struct Panic(pub String);
+
+impl Panic {
+    fn from_payload(payload: Box<Any + Send>) -> Self {
+        let msg = if let Some(msg) = payload.downcast_ref::<&str>() {
+            (*msg).to_owned()
+        } else if let Some(msg) = payload.downcast_ref::<String>() {
+            msg.clone()
+        } else {
+            "panic".to_owned()
+        };
+
+        Panic(msg)
+    }
+}
 
-fn catch_unwind_result<'a, F, T, E>(f: F) -> Result<T, E>
+fn catch_unwind_result<F, T, E>(f: F) -> Result<T, E>
 where
     F: FnOnce() -> Result<T, E>,
-    E: Debug + From<&'a str>,
+    E: Debug + From<Panic>,
 {
     match panic::catch_unwind(AssertUnwindSafe(f)) {
-        Err(_) => Err(E::from("panic")),
+        Err(payload) => Err(E::from(Panic::from_payload(payload))),
         Ok(result) => result,
     }
 }
 
 /// Catch panics. On error call the callback.
-pub fn catch_unwind_cb<'a, U, C, F, E>(user_data: U, cb: C, f: F)
+pub fn catch_unwind_cb<U, C, F, E>(user_data: U, cb: C, f: F)
 where
     U: Into<*mut c_void>,
     C: Callback + Copy,
     F: FnOnce() -> Result<(), E>,
-    E: Debug + Display + ErrorCode + From<&'a str>,
+    E: Debug + Display + ErrorCode + From<Panic>,
 {
     if let Err(err) = catch_unwind_result(f) {
         let (error_code, description) = ffi_result!(Err::<(), E>(err));
@@ -50,6 +72,25 @@ where
     }
 }
 
+/// Run `f` on a new thread and return immediately, for operations which would
+/// otherwise block the calling thread. Panics and errors are reported to the
+/// callback as in `catch_unwind_cb`, also if the thread can't be spawned.
+pub fn spawn_cb<U, C, F, E>(user_data: U, cb: C, f: F)
+where
+    U: Into<*mut c_void> + Copy + Send + 'static,
+    C: Callback + Copy + Send + 'static,
+    F: FnOnce() -> Result<(), E> + Send + 'static,
+    E: Debug + Display + ErrorCode + From<Panic> + From<io::Error>,
+{
+    let res = thread::Builder::new()
+        .name("FFI worker".to_owned())
+        .spawn(move || catch_unwind_cb(user_data, cb, f));
+
+    if let Err(err) = res {
+        catch_unwind_cb(user_data, cb, || -> Result<(), E> { Err(E::from(err)) });
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -64,10 +105,19 @@ mod tests {
             panic!("simulated panic");
         });
 
-        assert!(res.is_err());
+        assert_eq!(unwrap!(res.err()).0, "simulated panic");
         assert!(did_unwind);
     }
 
+    #[test]
+    fn panic_with_formatted_message() {
+        let res = catch_unwind_result(|| -> Result<(), TestError> {
+            panic!("simulated panic {}", 42);
+        });
+
+        assert_eq!(unwrap!(res.err()).0, "simulated panic 42");
+    }
+
     #[test]
     fn panic_inside_catch_unwind_cb() {
         extern "C" fn cb(user_data: *mut c_void, result: *const FfiResult) {
@@ -93,6 +143,20 @@ mod tests {
         assert!(did_unwind);
     }
 
+    #[test]
+    fn panic_inside_spawn_cb() {
+        use OpaqueCtx;
+        use test_utils::call_0;
+
+        let res = call_0(|user_data, cb| {
+            spawn_cb(OpaqueCtx(user_data), cb, || -> Result<(), TestError> {
+                panic!("simulated panic");
+            })
+        });
+
+        assert_eq!(res, Err(-1));
+    }
+
     // Calls a callback on drop.
     struct DropProbe<F: FnOnce()>(Option<F>);
 
@@ -112,11 +176,17 @@ mod tests {
 
     // Dummy error type for testing.
     #[derive(Debug)]
-    struct TestError;
+    struct TestError(String);
+
+    impl From<Panic> for TestError {
+        fn from(panic: Panic) -> Self {
+            TestError(panic.0)
+        }
+    }
 
-    impl<'a> From<&'a str> for TestError {
-        fn from(_: &'a str) -> Self {
-            TestError
+    impl From<io::Error> for TestError {
+        fn from(err: io::Error) -> Self {
+            TestError(format!("{}", err))
         }
     }
 
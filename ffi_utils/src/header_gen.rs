@@ -92,7 +92,7 @@ pub fn gen_headers_custom_code(
 }
 
 // Parse given root file (such as lib.rs) and return all `pub use`d modules starting with `ffi::`.
-fn parse_root(fname: &str) -> io::Result<Vec<String>> {
+pub(crate) fn parse_root(fname: &str) -> io::Result<Vec<String>> {
     let contents = read_file_str(fname)?;
     let mut modules = Vec::new();
     let mut found = false;
@@ -141,7 +141,7 @@ fn file_contents_replace(fname: &str) -> io::Result<()> {
 }
 
 // Reads a file and returns its contents in a string.
-fn read_file_str(fname: &str) -> io::Result<String> {
+pub(crate) fn read_file_str(fname: &str) -> io::Result<String> {
     // Open the path in read-only mode
     let mut file = File::open(fname)?;
 
@@ -152,7 +152,7 @@ fn read_file_str(fname: &str) -> io::Result<String> {
 }
 
 // Writes a string to a file.
-fn write_file_str(fname: &str, contents: &str) -> io::Result<()> {
+pub(crate) fn write_file_str(fname: &str, contents: &str) -> io::Result<()> {
     // Open a file in write-only mode
     let mut file = File::create(fname)?;
 
@@ -0,0 +1,227 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Helpers for exposing the C API to Java through JNI.
+//!
+//! Java callbacks are objects implementing an interface with a single `call` method. To pass
+//! one to an FFI function, wrap it in a `JniCallback`, turn it into the user data with
+//! `into_user_data` and pass the trampoline matching the callback signature, e.g.
+//! `result_cb` for `extern "C" fn(user_data: *mut c_void, result: *const FfiResult)`.
+//! The trampolines call `call` with the error code and description followed by the
+//! callback values, and release the Java object afterwards.
+//!
+//! Callbacks are usually fired from the event loop thread, which isn't known to the JVM.
+//! Such threads are attached for the duration of the call and detached again afterwards.
+
+use super::FfiResult;
+use jni::{JNIEnv, JavaVM};
+use jni::errors::Result as JniResult;
+use jni::objects::{GlobalRef, JObject, JString, JValue};
+use jni::sys::{jbyteArray, jlong};
+use std::ffi::CStr;
+use std::fmt::Display;
+use std::os::raw::c_void;
+use std::slice;
+
+/// Java object receiving the result of an FFI call.
+pub struct JniCallback {
+    vm: JavaVM,
+    obj: GlobalRef,
+}
+
+impl JniCallback {
+    /// Wraps `obj` so it can outlive the current JNI call.
+    pub fn new(env: &JNIEnv, obj: JObject) -> JniResult<Self> {
+        Ok(JniCallback {
+            vm: env.get_java_vm()?,
+            obj: env.new_global_ref(obj)?,
+        })
+    }
+
+    /// Converts the callback into user data for the trampolines in this module.
+    pub fn into_user_data(self) -> *mut c_void {
+        Box::into_raw(Box::new(self)) as *mut c_void
+    }
+
+    /// Takes back the callback passed as user data.
+    pub unsafe fn from_user_data(user_data: *mut c_void) -> Box<Self> {
+        Box::from_raw(user_data as *mut Self)
+    }
+
+    /// Runs `f` with the JNI environment of the current thread, attaching the thread
+    /// if necessary. Java exceptions thrown by the callback are logged and cleared.
+    pub fn call<F>(&self, f: F)
+    where
+        F: FnOnce(&JNIEnv, JObject) -> JniResult<()>,
+    {
+        let obj = self.obj.as_obj();
+        let res = with_env(&self.vm, |env| {
+            let res = f(env, obj);
+            if env.exception_check()? {
+                env.exception_describe()?;
+                env.exception_clear()?;
+            }
+            res
+        });
+        if let Err(err) = res {
+            warn!("Failed to call into Java: {:?}", err);
+        }
+    }
+
+    /// Same as `call`, but also releases the Java object while the thread is attached.
+    pub fn call_once<F>(self, f: F)
+    where
+        F: FnOnce(&JNIEnv, JObject) -> JniResult<()>,
+    {
+        self.call(f);
+        let JniCallback { vm, obj } = self;
+        let res = with_env(&vm, move |_| {
+            drop(obj);
+            Ok(())
+        });
+        if let Err(err) = res {
+            warn!("Failed to release Java callback: {:?}", err);
+        }
+    }
+}
+
+// Callbacks are fired on the calling Java thread as well (e.g. when the input is invalid),
+// which must not be detached afterwards.
+fn with_env<F>(vm: &JavaVM, f: F) -> JniResult<()>
+where
+    F: FnOnce(&JNIEnv) -> JniResult<()>,
+{
+    if let Ok(env) = vm.get_env() {
+        return f(&env);
+    }
+    let guard = vm.attach_current_thread()?;
+    f(&guard)
+}
+
+/// Converts a Java string.
+pub fn from_jstring(env: &JNIEnv, s: JString) -> JniResult<String> {
+    Ok(env.get_string(s)?.into())
+}
+
+/// Copies the contents of a Java byte array. `null` is converted to an empty vector.
+pub fn from_jbyte_array(env: &JNIEnv, array: jbyteArray) -> JniResult<Vec<u8>> {
+    if array.is_null() {
+        Ok(Vec::new())
+    } else {
+        env.convert_byte_array(array)
+    }
+}
+
+/// Throws `java.lang.IllegalArgumentException` with the given error as the message.
+pub fn throw_illegal_argument<E: Display>(env: &JNIEnv, err: E) {
+    if let Err(err) = env.throw_new("java/lang/IllegalArgumentException", err.to_string()) {
+        warn!("Failed to throw Java exception: {:?}", err);
+    }
+}
+
+/// Trampoline calling `void call(int errorCode, String description)`.
+pub extern "C" fn result_cb(user_data: *mut c_void, result: *const FfiResult) {
+    unsafe {
+        complete(user_data, result, |env, obj, code, description| {
+            call_void(env, obj, "(ILjava/lang/String;)V", &[code, description])
+        })
+    }
+}
+
+/// Trampoline calling `void call(int errorCode, String description, long value)`.
+pub extern "C" fn result_u64_cb(user_data: *mut c_void, result: *const FfiResult, value: u64) {
+    unsafe {
+        complete(user_data, result, |env, obj, code, description| {
+            call_void(
+                env,
+                obj,
+                "(ILjava/lang/String;J)V",
+                &[code, description, JValue::Long(value as jlong)],
+            )
+        })
+    }
+}
+
+/// Same as `result_u64_cb`, for `usize` values.
+pub extern "C" fn result_usize_cb(user_data: *mut c_void, result: *const FfiResult, value: usize) {
+    result_u64_cb(user_data, result, value as u64)
+}
+
+/// Same as `result_u64_cb`, passing a pointer as the `long` value.
+pub extern "C" fn result_ptr_cb<T>(user_data: *mut c_void, result: *const FfiResult, ptr: *mut T) {
+    result_u64_cb(user_data, result, ptr as usize as u64)
+}
+
+/// Trampoline calling `void call(int errorCode, String description, byte[] value)`.
+pub extern "C" fn result_bytes_cb(
+    user_data: *mut c_void,
+    result: *const FfiResult,
+    ptr: *const u8,
+    len: usize,
+) {
+    unsafe {
+        let bytes = if ptr.is_null() {
+            &[][..]
+        } else {
+            slice::from_raw_parts(ptr, len)
+        };
+        complete(user_data, result, |env, obj, code, description| {
+            let array = JObject::from(env.byte_array_from_slice(bytes)?);
+            call_void(
+                env,
+                obj,
+                "(ILjava/lang/String;[B)V",
+                &[code, description, JValue::Object(array)],
+            )
+        })
+    }
+}
+
+/// Trampoline calling `void call()`, for notifiers which can fire repeatedly. The
+/// callback is not released.
+pub extern "C" fn notify_cb(user_data: *mut c_void) {
+    let callback = user_data as *const JniCallback;
+    unsafe {
+        (*callback).call(|env, obj| call_void(env, obj, "()V", &[]));
+    }
+}
+
+unsafe fn complete<F>(user_data: *mut c_void, result: *const FfiResult, f: F)
+where
+    F: FnOnce(&JNIEnv, JObject, JValue, JValue) -> JniResult<()>,
+{
+    let callback = JniCallback::from_user_data(user_data);
+    let error_code = (*result).error_code;
+    let description = if (*result).description.is_null() {
+        None
+    } else {
+        Some(CStr::from_ptr((*result).description).to_string_lossy().into_owned())
+    };
+
+    callback.call_once(|env, obj| {
+        let description = match description {
+            Some(description) => JObject::from(env.new_string(description)?),
+            None => JObject::null(),
+        };
+        f(env, obj, JValue::Int(error_code), JValue::Object(description))
+    });
+}
+
+fn call_void(env: &JNIEnv, obj: JObject, sig: &str, args: &[JValue]) -> JniResult<()> {
+    let _ = env.call_method(obj, "call", sig, args)?;
+    Ok(())
+}
@@ -0,0 +1,51 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Per-thread record of the most recent error reported through FFI.
+
+use std::cell::RefCell;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<String>> = RefCell::new(None);
+}
+
+/// Records the description of an error reported on the current thread.
+pub fn set_last_error(description: String) {
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(description));
+}
+
+/// Returns the description of the most recent error reported on the current
+/// thread, if any.
+pub fn last_error() -> Option<String> {
+    LAST_ERROR.with(|last| last.borrow().clone())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn per_thread() {
+        set_last_error("first".to_owned());
+        set_last_error("second".to_owned());
+        assert_eq!(last_error(), Some("second".to_owned()));
+
+        let other = unwrap!(thread::spawn(last_error).join());
+        assert_eq!(other, None);
+    }
+}
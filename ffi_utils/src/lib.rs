@@ -42,6 +42,9 @@
 #![cfg_attr(feature="cargo-clippy", allow(implicit_hasher, too_many_arguments, use_debug))]
 
 extern crate base64;
+/// Re-exported for the JNI wrappers of dependent crates.
+#[cfg(feature = "jni")]
+pub extern crate jni;
 #[macro_use]
 extern crate log;
 #[macro_use]
@@ -53,6 +56,8 @@ extern crate unwrap;
 mod macros;
 mod b64;
 mod catch_unwind;
+mod last_error;
+mod ptr;
 mod repr_c;
 mod vec;
 
@@ -60,12 +65,18 @@ pub mod callback;
 pub mod test_utils;
 pub mod string;
 pub mod header_gen;
+#[cfg(feature = "jni")]
+pub mod java;
+pub mod napi_gen;
 
 pub use self::b64::{base64_decode, base64_encode};
-pub use self::catch_unwind::catch_unwind_cb;
+pub use self::catch_unwind::{Panic, catch_unwind_cb, spawn_cb};
+pub use self::last_error::{last_error, set_last_error};
+pub use self::ptr::{NullPointer, ptr_as_mut, ptr_as_ref};
 pub use self::repr_c::ReprC;
-pub use self::string::{StringError, from_c_str};
-pub use self::vec::{SafePtr, vec_clone_from_raw_parts, vec_into_raw_parts};
+pub use self::string::{FfiString, StringError, from_c_str, from_c_wstr};
+pub use self::vec::{BorrowedSlice, FfiBuffer, SafePtr, vec_clone_from_raw_parts,
+                    vec_into_raw_parts};
 use std::os::raw::{c_char, c_void};
 
 /// Type that holds opaque user data handed into FFI functions
@@ -22,6 +22,7 @@ macro_rules! ffi_error {
     ($error:expr) => {{
         let err_code = ffi_error_code!($error);
         let err_desc = format!("{}", $error);
+        $crate::set_last_error(err_desc.clone());
         (err_code, unwrap!(::std::ffi::CString::new(err_desc)))
     }}
 }
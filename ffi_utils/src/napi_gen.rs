@@ -0,0 +1,880 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Generation of Node.js N-API bindings in build scripts.
+//!
+//! The bindings are generated from the same FFI modules as the C headers (see `header_gen`),
+//! so they can't drift from the C API. Every exported function is wrapped in an N-API function
+//! taking `Buffer`s for byte arrays, strings for C strings and externals for opaque pointers.
+//! Functions reporting their result through an `o_cb` callback return a `Promise` instead,
+//! which resolves to the callback's values (a single value or an object keyed by parameter
+//! name) or rejects with an `Error` carrying the error `code`.
+//!
+//! Functions whose parameters have no JS counterpart, such as `repr(C)` structures or
+//! additional callbacks, are skipped and listed at the top of the generated file.
+//!
+//! The output is a C source file to be compiled by `node-gyp` together with the generated
+//! header and linked against the library. It requires N-API version 4.
+
+use super::header_gen::{parse_root, read_file_str, write_file_str};
+use std::fmt::Write;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Generate the N-API bindings for the current project. Should be called from a build script.
+/// `opaque_types` are the types which are only ever passed around by pointer (e.g. `App`).
+pub fn gen_bindings(
+    header_name: &str,
+    output_directory: &str,
+    root_file: &str,
+    opaque_types: &[&str],
+) -> io::Result<()> {
+    let src_dir = Path::new(root_file).parent().unwrap_or_else(|| Path::new(""));
+    let mut modules = parse_root(root_file)?;
+    modules.insert(0, "ffi".to_owned());
+
+    let mut functions = Vec::new();
+    let mut skipped = Vec::new();
+
+    for module in modules {
+        let module_path = src_dir.join(module.replace("::", "/"));
+        let source = read_file_str(&module_path.with_extension("rs").to_string_lossy())
+            .or_else(|_| read_file_str(&module_path.join("mod.rs").to_string_lossy()))?;
+
+        for raw in parse_functions(&source) {
+            match Function::from_raw(&raw, opaque_types) {
+                Ok(function) => functions.push(function),
+                Err(reason) => skipped.push(format!("{}: {}", raw.name, reason)),
+            }
+        }
+    }
+
+    fs::create_dir_all(output_directory)?;
+    let path = format!("{}{}_napi.c", output_directory, header_name);
+    write_file_str(&path, &gen_source(header_name, &functions, &skipped))
+}
+
+// Exported function as written in the source.
+#[derive(Debug)]
+struct RawFunction {
+    name: String,
+    params: Vec<(String, String)>,
+    ret: Option<String>,
+}
+
+// Type of a value crossing the boundary.
+#[derive(Clone, Debug, PartialEq)]
+enum Type {
+    Bool,
+    // Integer, converted to a JS number. Holds the C type.
+    Number(String),
+    // `*const c_char`.
+    Str,
+    // `*const u8` followed by its `usize` length.
+    Bytes,
+    // Pointer to an opaque type. Holds the C type.
+    Opaque(String),
+}
+
+#[derive(Debug, PartialEq)]
+struct Param {
+    name: String,
+    ty: Type,
+}
+
+#[derive(Debug, PartialEq)]
+enum Kind {
+    // Returns the result directly.
+    Sync(Option<Type>),
+    // Reports the result through `o_cb`, called with these parameters after the `FfiResult`.
+    Async(Vec<Param>),
+}
+
+#[derive(Debug, PartialEq)]
+struct Function {
+    name: String,
+    params: Vec<Param>,
+    kind: Kind,
+}
+
+impl Function {
+    fn from_raw(raw: &RawFunction, opaque_types: &[&str]) -> Result<Self, String> {
+        let mut params = &raw.params[..];
+        let mut kind = Kind::Sync(match raw.ret {
+            Some(ref ret) => Some(convert_type(ret, opaque_types)?),
+            None => None,
+        });
+
+        if let Some((&(ref name, ref ty), rest)) = params.split_last() {
+            if name == "o_cb" {
+                let cb_params = parse_callback(ty).ok_or_else(|| "invalid `o_cb`".to_owned())?;
+                match (rest.last(), cb_params.get(0), cb_params.get(1)) {
+                    (Some(&(ref user_data, ref user_data_ty)),
+                     Some(&(_, ref cb_user_data_ty)),
+                     Some(&(_, ref result_ty)))
+                        if user_data == "user_data" && user_data_ty == "*mut c_void" &&
+                               cb_user_data_ty == "*mut c_void" &&
+                               result_ty == "*const FfiResult" => (),
+                    _ => return Err("`o_cb` doesn't report an `FfiResult`".to_owned()),
+                }
+                kind = Kind::Async(convert_params(&cb_params[2..], opaque_types)?);
+                params = &rest[..rest.len() - 1];
+            }
+        }
+
+        Ok(Function {
+            name: raw.name.clone(),
+            params: convert_params(params, opaque_types)?,
+            kind,
+        })
+    }
+}
+
+fn convert_params(
+    params: &[(String, String)],
+    opaque_types: &[&str],
+) -> Result<Vec<Param>, String> {
+    let mut result = Vec::new();
+    let mut iter = params.iter().peekable();
+
+    while let Some(&(ref name, ref ty)) = iter.next() {
+        let is_bytes = ty == "*const u8" &&
+            iter.peek().map_or(false, |&&(ref len_name, ref len_ty)| {
+                *len_name == format!("{}_len", name) && len_ty == "usize"
+            });
+        let ty = if is_bytes {
+            let _ = iter.next();
+            Type::Bytes
+        } else {
+            convert_type(ty, opaque_types)?
+        };
+        result.push(Param {
+            name: name.clone(),
+            ty,
+        });
+    }
+
+    Ok(result)
+}
+
+fn convert_type(ty: &str, opaque_types: &[&str]) -> Result<Type, String> {
+    Ok(match ty {
+        "bool" => Type::Bool,
+        "u8" => Type::Number("uint8_t".to_owned()),
+        "u16" => Type::Number("uint16_t".to_owned()),
+        "u32" => Type::Number("uint32_t".to_owned()),
+        "u64" => Type::Number("uint64_t".to_owned()),
+        "i32" => Type::Number("int32_t".to_owned()),
+        "i64" => Type::Number("int64_t".to_owned()),
+        "usize" => Type::Number("uintptr_t".to_owned()),
+        "*const c_char" => Type::Str,
+        // Object handles are all aliases of `u64`.
+        _ if ty.ends_with("Handle") && is_ident(ty) => Type::Number(ty.to_owned()),
+        _ => {
+            let pointee = if ty.starts_with("*const ") {
+                Some(("const ", &ty[7..]))
+            } else if ty.starts_with("*mut ") {
+                Some(("", &ty[5..]))
+            } else {
+                None
+            };
+            match pointee {
+                Some((qualifier, name)) if opaque_types.contains(&name) => {
+                    Type::Opaque(format!("{}{}*", qualifier, name))
+                }
+                _ => return Err(format!("unsupported type `{}`", ty)),
+            }
+        }
+    })
+}
+
+fn is_ident(s: &str) -> bool {
+    s.chars().all(|c| c.is_alphanumeric() || c == '_')
+}
+
+// Find all exported functions in the source.
+fn parse_functions(source: &str) -> Vec<RawFunction> {
+    let mut functions = Vec::new();
+    let mut rest = source;
+
+    loop {
+        let start = match (
+            rest.find("pub unsafe extern \"C\" fn "),
+            rest.find("pub extern \"C\" fn "),
+        ) {
+            (Some(a), Some(b)) if a < b => a + 25,
+            (Some(a), None) => a + 25,
+            (_, Some(b)) => b + 18,
+            (None, None) => break,
+        };
+        rest = &rest[start..];
+
+        let open = match rest.find('(') {
+            Some(open) => open,
+            None => break,
+        };
+        let name = rest[..open].trim().to_owned();
+        let close = match matching_paren(rest, open) {
+            Some(close) => close,
+            None => break,
+        };
+        let params = split_top_level(&rest[open + 1..close])
+            .into_iter()
+            .filter_map(|param| {
+                let colon = param.find(':')?;
+                Some((
+                    param[..colon].trim().to_owned(),
+                    normalise(&param[colon + 1..]),
+                ))
+            })
+            .collect();
+
+        rest = &rest[close + 1..];
+        let body = rest.find('{').unwrap_or_else(|| rest.len());
+        let signature_rest = rest[..body].trim();
+        let ret = if signature_rest.starts_with("->") {
+            Some(normalise(&signature_rest[2..]))
+        } else {
+            None
+        };
+
+        functions.push(RawFunction { name, params, ret });
+    }
+
+    functions
+}
+
+// Parse the parameters of an `extern "C" fn(...)` type.
+fn parse_callback(ty: &str) -> Option<Vec<(String, String)>> {
+    let prefix = "extern \"C\" fn(";
+    if !ty.starts_with(prefix) || !ty.ends_with(')') {
+        return None;
+    }
+    split_top_level(&ty[prefix.len()..ty.len() - 1])
+        .into_iter()
+        .map(|param| {
+            let colon = param.find(':')?;
+            Some((
+                param[..colon].trim().to_owned(),
+                normalise(&param[colon + 1..]),
+            ))
+        })
+        .collect()
+}
+
+fn matching_paren(s: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in s[open..].char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + i);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
+
+// Split at commas which aren't nested in brackets, dropping empty items.
+fn split_top_level(s: &str) -> Vec<&str> {
+    let mut items = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+
+    for (i, c) in s.char_indices() {
+        match c {
+            '(' | '<' | '[' => depth += 1,
+            ')' | '>' | ']' => depth -= 1,
+            ',' if depth == 0 => {
+                items.push(&s[start..i]);
+                start = i + 1;
+            }
+            _ => (),
+        }
+    }
+    items.push(&s[start..]);
+
+    items
+        .into_iter()
+        .filter(|item| !item.trim().is_empty())
+        .collect()
+}
+
+// Collapse whitespace, so types compare equal regardless of formatting.
+fn normalise(ty: &str) -> String {
+    let ty = ty.split_whitespace().collect::<Vec<_>>().join(" ");
+    ty.replace("( ", "(").replace(" )", ")").replace(",)", ")")
+}
+
+fn c_params(param: &Param) -> Vec<String> {
+    match param.ty {
+        Type::Bool => vec![format!("bool {}", param.name)],
+        Type::Number(ref c_type) |
+        Type::Opaque(ref c_type) => vec![format!("{} {}", c_type, param.name)],
+        Type::Str => vec![format!("const char* {}", param.name)],
+        Type::Bytes => {
+            vec![
+                format!("const uint8_t* {}", param.name),
+                format!("uintptr_t {}_len", param.name),
+            ]
+        }
+    }
+}
+
+// Statement converting `expr` of the given type into the JS value `var`.
+fn to_js(ty: &Type, expr: &str, var: &str) -> String {
+    match *ty {
+        Type::Bool => format!("napi_get_boolean(env, {}, &{});", expr, var),
+        Type::Number(_) => format!("napi_create_int64(env, (int64_t) {}, &{});", expr, var),
+        Type::Str => format!("safe_napi_create_string(env, {}, &{});", expr, var),
+        Type::Bytes => {
+            format!(
+                "napi_create_buffer_copy(env, {0}_len, {0}, NULL, &{1});",
+                expr,
+                var
+            )
+        }
+        Type::Opaque(_) => {
+            format!(
+                "napi_create_external(env, (void*) {}, NULL, NULL, &{});",
+                expr,
+                var
+            )
+        }
+    }
+}
+
+fn gen_source(header_name: &str, functions: &[Function], skipped: &[String]) -> String {
+    let mut out = String::new();
+
+    out.push_str("/* Generated by ffi_utils::napi_gen. Do not edit. */\n");
+    if !skipped.is_empty() {
+        out.push_str("\n/* Not exported:\n");
+        for reason in skipped {
+            let _ = writeln!(out, " *   {}", reason);
+        }
+        out.push_str(" */\n");
+    }
+    out.push_str(&PRELUDE.replace("{header}", header_name));
+
+    for function in functions {
+        gen_function(&mut out, function);
+    }
+
+    out.push_str(
+        "\nstatic napi_value safe_napi_init(napi_env env, napi_value exports) {\n    \
+         napi_property_descriptor properties[] = {\n",
+    );
+    for function in functions {
+        let _ = writeln!(
+            out,
+            "        {{ \"{0}\", NULL, {0}_napi, NULL, NULL, NULL, napi_default, NULL }},",
+            function.name
+        );
+    }
+    out.push_str(
+        "    };\n    napi_define_properties(env, exports, \
+         sizeof(properties) / sizeof(properties[0]), properties);\n    \
+         return exports;\n}\n\nNAPI_MODULE(NODE_GYP_MODULE_NAME, safe_napi_init)\n",
+    );
+
+    out
+}
+
+fn gen_function(out: &mut String, function: &Function) {
+    let name = &function.name;
+
+    if let Kind::Async(ref results) = function.kind {
+        // Copy of the callback parameters, which are only valid during the callback.
+        let _ = writeln!(
+            out,
+            "\ntypedef struct {{\n    int32_t error_code;\n    char* description;"
+        );
+        for result in results {
+            let _ = match result.ty {
+                Type::Bool => writeln!(out, "    bool {};", result.name),
+                Type::Number(ref c_type) => writeln!(out, "    {} {};", c_type, result.name),
+                Type::Str => writeln!(out, "    char* {};", result.name),
+                Type::Bytes => {
+                    writeln!(
+                        out,
+                        "    uint8_t* {0};\n    uintptr_t {0}_len;",
+                        result.name
+                    )
+                }
+                Type::Opaque(_) => writeln!(out, "    void* {};", result.name),
+            };
+        }
+        let _ = writeln!(out, "}} {}_result;", name);
+
+        let mut cb_params = vec![
+            "void* user_data".to_owned(),
+            "const FfiResult* result".to_owned(),
+        ];
+        for result in results {
+            cb_params.extend(c_params(result));
+        }
+        let _ = writeln!(
+            out,
+            "\nstatic void {0}_cb({1}) {{\n    \
+             {0}_result* res = calloc(1, sizeof(*res));\n    \
+             res->error_code = result->error_code;\n    \
+             res->description = safe_napi_strdup(result->description);",
+            name,
+            cb_params.join(", ")
+        );
+        for result in results {
+            let _ = match result.ty {
+                Type::Str => {
+                    writeln!(
+                        out,
+                        "    res->{0} = safe_napi_strdup({0});",
+                        result.name
+                    )
+                }
+                Type::Bytes => {
+                    writeln!(
+                        out,
+                        "    res->{0} = safe_napi_memdup({0}, {0}_len);\n    \
+                         res->{0}_len = {0}_len;",
+                        result.name
+                    )
+                }
+                Type::Opaque(_) => writeln!(out, "    res->{0} = (void*) {0};", result.name),
+                _ => writeln!(out, "    res->{0} = {0};", result.name),
+            };
+        }
+        let _ = writeln!(
+            out,
+            "    safe_napi_call_complete((SafeNapiCall*) user_data, res);\n}}"
+        );
+
+        let _ = writeln!(
+            out,
+            "\nstatic void {0}_js(napi_env env, napi_value js_cb, void* context, \
+             void* data) {{\n    \
+             {0}_result* res = data;\n    \
+             napi_value value = NULL;\n    \
+             (void) js_cb;\n    \
+             if (env != NULL && res->error_code == 0) {{",
+            name
+        );
+        match results.len() {
+            0 => out.push_str("        napi_get_undefined(env, &value);\n"),
+            1 => {
+                let _ = writeln!(
+                    out,
+                    "        {}",
+                    to_js(&results[0].ty, &format!("res->{}", results[0].name), "value")
+                );
+            }
+            _ => {
+                out.push_str(
+                    "        napi_value field;\n        napi_create_object(env, &value);\n",
+                );
+                for result in results {
+                    let _ = writeln!(
+                        out,
+                        "        {}\n        \
+                         napi_set_named_property(env, value, \"{}\", field);",
+                        to_js(&result.ty, &format!("res->{}", result.name), "field"),
+                        result.name
+                    );
+                }
+            }
+        }
+        out.push_str(
+            "    }\n    safe_napi_call_settle(env, (SafeNapiCall*) context, \
+             res->error_code, res->description, value);\n    free(res->description);\n",
+        );
+        for result in results {
+            if result.ty == Type::Str || result.ty == Type::Bytes {
+                let _ = writeln!(out, "    free(res->{});", result.name);
+            }
+        }
+        out.push_str("    free(res);\n}\n");
+    }
+
+    // The wrapper itself.
+    let argc = function.params.len();
+    let _ = writeln!(
+        out,
+        "\nstatic napi_value {}_napi(napi_env env, napi_callback_info info) {{\n    \
+         size_t argc = {};\n    \
+         napi_value argv[{}];\n    \
+         napi_value ret = NULL;",
+        name,
+        argc,
+        argc.max(1)
+    );
+    for param in &function.params {
+        let _ = match param.ty {
+            Type::Bool => writeln!(out, "    bool {};", param.name),
+            Type::Number(_) => writeln!(out, "    int64_t {};", param.name),
+            Type::Str => writeln!(out, "    char* {};", param.name),
+            Type::Bytes => {
+                writeln!(
+                    out,
+                    "    const uint8_t* {0};\n    uintptr_t {0}_len;",
+                    param.name
+                )
+            }
+            Type::Opaque(_) => writeln!(out, "    void* {};", param.name),
+        };
+    }
+    out.push_str("    napi_get_cb_info(env, info, &argc, argv, NULL, NULL);\n");
+    if argc > 0 {
+        let _ = writeln!(
+            out,
+            "    if (argc < {0}) {{\n        \
+             napi_throw_type_error(env, NULL, \"{1} expects {0} argument(s)\");\n        \
+             return NULL;\n    }}",
+            argc,
+            name
+        );
+    }
+    for (i, param) in function.params.iter().enumerate() {
+        let _ = match param.ty {
+            Type::Bool => {
+                writeln!(out, "    napi_get_value_bool(env, argv[{}], &{});", i, param.name)
+            }
+            Type::Number(_) => {
+                writeln!(out, "    napi_get_value_int64(env, argv[{}], &{});", i, param.name)
+            }
+            Type::Str => {
+                writeln!(out, "    {} = safe_napi_get_string(env, argv[{}]);", param.name, i)
+            }
+            Type::Bytes => {
+                writeln!(
+                    out,
+                    "    {0} = safe_napi_get_buffer(env, argv[{1}], &{0}_len);",
+                    param.name,
+                    i
+                )
+            }
+            Type::Opaque(_) => {
+                writeln!(out, "    {} = safe_napi_get_external(env, argv[{}]);", param.name, i)
+            }
+        };
+    }
+
+    let mut args: Vec<String> = Vec::new();
+    for param in &function.params {
+        match param.ty {
+            Type::Bytes => {
+                args.push(param.name.clone());
+                args.push(format!("{}_len", param.name));
+            }
+            Type::Number(ref c_type) => args.push(format!("({}) {}", c_type, param.name)),
+            _ => args.push(param.name.clone()),
+        }
+    }
+
+    match function.kind {
+        Kind::Async(_) => {
+            args.push(format!(
+                "safe_napi_call_new(env, &ret, \"{0}\", {0}_js)",
+                name
+            ));
+            args.push(format!("{}_cb", name));
+            let _ = writeln!(out, "    {}({});", name, args.join(", "));
+        }
+        Kind::Sync(None) => {
+            let _ = writeln!(
+                out,
+                "    {}({});\n    napi_get_undefined(env, &ret);",
+                name,
+                args.join(", ")
+            );
+        }
+        Kind::Sync(Some(ref ty)) => {
+            let c_type = match *ty {
+                Type::Bool => "bool".to_owned(),
+                Type::Number(ref c_type) |
+                Type::Opaque(ref c_type) => c_type.clone(),
+                Type::Str => "const char*".to_owned(),
+                Type::Bytes => unreachable!(),
+            };
+            let _ = writeln!(
+                out,
+                "    {{\n        {} value = {}({});\n        {}\n    }}",
+                c_type,
+                name,
+                args.join(", "),
+                to_js(ty, "value", "ret")
+            );
+        }
+    }
+
+    for param in &function.params {
+        if param.ty == Type::Str {
+            let _ = writeln!(out, "    free({});", param.name);
+        }
+    }
+    out.push_str("    return ret;\n}\n");
+}
+
+const PRELUDE: &str = r#"
+#define NAPI_VERSION 4
+#include <node_api.h>
+#include <stdbool.h>
+#include <stdint.h>
+#include <stdlib.h>
+#include <string.h>
+
+/* Defined in `ffi_utils`, so it's not part of the generated header. */
+typedef struct FfiResult {
+    int32_t error_code;
+    const char* description;
+} FfiResult;
+
+#include "{header}.h"
+
+/* Pending call of a function reporting its result through a callback. */
+typedef struct {
+    napi_deferred deferred;
+    napi_threadsafe_function tsfn;
+} SafeNapiCall;
+
+static char* safe_napi_strdup(const char* s) {
+    char* copy;
+    size_t len;
+    if (s == NULL) {
+        return NULL;
+    }
+    len = strlen(s) + 1;
+    copy = malloc(len);
+    memcpy(copy, s, len);
+    return copy;
+}
+
+static uint8_t* safe_napi_memdup(const uint8_t* data, uintptr_t len) {
+    uint8_t* copy = malloc(len > 0 ? len : 1);
+    if (len > 0) {
+        memcpy(copy, data, len);
+    }
+    return copy;
+}
+
+static bool safe_napi_is_nullish(napi_env env, napi_value value) {
+    napi_valuetype type;
+    napi_typeof(env, value, &type);
+    return type == napi_null || type == napi_undefined;
+}
+
+/* `null` and `undefined` are passed as NULL. The result has to be freed. */
+static char* safe_napi_get_string(napi_env env, napi_value value) {
+    char* s;
+    size_t len;
+    if (safe_napi_is_nullish(env, value)) {
+        return NULL;
+    }
+    napi_get_value_string_utf8(env, value, NULL, 0, &len);
+    s = malloc(len + 1);
+    napi_get_value_string_utf8(env, value, s, len + 1, &len);
+    return s;
+}
+
+static const uint8_t* safe_napi_get_buffer(napi_env env, napi_value value, uintptr_t* len) {
+    void* data = NULL;
+    size_t size = 0;
+    if (!safe_napi_is_nullish(env, value)) {
+        napi_get_buffer_info(env, value, &data, &size);
+    }
+    *len = size;
+    return data;
+}
+
+static void* safe_napi_get_external(napi_env env, napi_value value) {
+    void* data = NULL;
+    if (!safe_napi_is_nullish(env, value)) {
+        napi_get_value_external(env, value, &data);
+    }
+    return data;
+}
+
+static void safe_napi_create_string(napi_env env, const char* s, napi_value* result) {
+    if (s == NULL) {
+        napi_get_null(env, result);
+    } else {
+        napi_create_string_utf8(env, s, NAPI_AUTO_LENGTH, result);
+    }
+}
+
+/* Creates the promise returned to JS. The result is passed as the FFI user data. */
+static SafeNapiCall* safe_napi_call_new(napi_env env,
+                                        napi_value* promise,
+                                        const char* name,
+                                        napi_threadsafe_function_call_js call_js) {
+    SafeNapiCall* call = malloc(sizeof(*call));
+    napi_value resource_name;
+    napi_create_promise(env, &call->deferred, promise);
+    napi_create_string_utf8(env, name, NAPI_AUTO_LENGTH, &resource_name);
+    napi_create_threadsafe_function(env, NULL, NULL, resource_name, 0, 1, NULL, NULL, call,
+                                    call_js, &call->tsfn);
+    return call;
+}
+
+/* Called from the FFI callback, possibly on another thread. */
+static void safe_napi_call_complete(SafeNapiCall* call, void* data) {
+    napi_call_threadsafe_function(call->tsfn, data, napi_tsfn_blocking);
+    napi_release_threadsafe_function(call->tsfn, napi_tsfn_release);
+}
+
+/* Settles the promise on the JS thread and frees the call. */
+static void safe_napi_call_settle(napi_env env,
+                                  SafeNapiCall* call,
+                                  int32_t error_code,
+                                  const char* description,
+                                  napi_value value) {
+    if (env != NULL) {
+        if (error_code == 0) {
+            napi_resolve_deferred(env, call->deferred, value);
+        } else {
+            napi_value message, code, error;
+            napi_create_string_utf8(env, description != NULL ? description : "",
+                                    NAPI_AUTO_LENGTH, &message);
+            napi_create_error(env, NULL, message, &error);
+            napi_create_int32(env, error_code, &code);
+            napi_set_named_property(env, error, "code", code);
+            napi_reject_deferred(env, call->deferred, error);
+        }
+    }
+    free(call);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = r#"
+/// Get the entry value at the given key.
+#[no_mangle]
+pub unsafe extern "C" fn mdata_entries_get(
+    app: *const App,
+    entries_h: MDataEntriesHandle,
+    key: *const u8,
+    key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        content: *const u8,
+                        content_len: usize,
+                        version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || { unimplemented!() })
+}
+
+#[no_mangle]
+pub extern "C" fn is_mock_build() -> bool {
+    cfg!(feature = "use-mock-routing")
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn enc_generate_key_pair(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, key: *const PubKey),
+) {
+}
+"#;
+
+    // Test that exported functions are parsed and converted.
+    #[test]
+    fn parse_and_convert() {
+        let raw = parse_functions(SOURCE);
+        assert_eq!(raw.len(), 3);
+        assert_eq!(raw[0].name, "mdata_entries_get");
+        assert_eq!(raw[0].params.len(), 6);
+        assert_eq!(raw[1].ret, Some("bool".to_owned()));
+
+        let get = unwrap!(Function::from_raw(&raw[0], &["App"]));
+        assert_eq!(
+            get,
+            Function {
+                name: "mdata_entries_get".to_owned(),
+                params: vec![
+                    Param {
+                        name: "app".to_owned(),
+                        ty: Type::Opaque("const App*".to_owned()),
+                    },
+                    Param {
+                        name: "entries_h".to_owned(),
+                        ty: Type::Number("MDataEntriesHandle".to_owned()),
+                    },
+                    Param {
+                        name: "key".to_owned(),
+                        ty: Type::Bytes,
+                    },
+                ],
+                kind: Kind::Async(vec![
+                    Param {
+                        name: "content".to_owned(),
+                        ty: Type::Bytes,
+                    },
+                    Param {
+                        name: "version".to_owned(),
+                        ty: Type::Number("uint64_t".to_owned()),
+                    },
+                ]),
+            }
+        );
+
+        let is_mock = unwrap!(Function::from_raw(&raw[1], &["App"]));
+        assert_eq!(is_mock.kind, Kind::Sync(Some(Type::Bool)));
+
+        // Structures can't be converted.
+        assert!(Function::from_raw(&raw[2], &["App"]).is_err());
+        // Neither can pointers to types which aren't declared opaque.
+        assert!(Function::from_raw(&raw[0], &[]).is_err());
+    }
+
+    // Test that the generated source wraps the functions and lists the skipped ones.
+    #[test]
+    fn generate() {
+        let functions: Vec<_> = parse_functions(SOURCE)
+            .iter()
+            .filter_map(|raw| Function::from_raw(raw, &["App"]).ok())
+            .collect();
+        let source = gen_source(
+            "safe_app",
+            &functions,
+            &["enc_generate_key_pair: unsupported type `*const PubKey`".to_owned()],
+        );
+
+        assert!(source.contains("#include \"safe_app.h\""));
+        assert!(source.contains(" *   enc_generate_key_pair: unsupported type"));
+        assert!(source.contains(
+            "static void mdata_entries_get_cb(void* user_data, const FfiResult* result, \
+             const uint8_t* content, uintptr_t content_len, uint64_t version)",
+        ));
+        assert!(source.contains(
+            "mdata_entries_get(app, (MDataEntriesHandle) entries_h, key, key_len, \
+             safe_napi_call_new(env, &ret, \"mdata_entries_get\", mdata_entries_get_js), \
+             mdata_entries_get_cb);",
+        ));
+        assert!(source.contains("bool value = is_mock_build();"));
+        assert!(source.contains("{ \"is_mock_build\", NULL, is_mock_build_napi,"));
+        assert!(!source.contains("enc_generate_key_pair_napi"));
+    }
+}
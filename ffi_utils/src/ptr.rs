@@ -0,0 +1,73 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::fmt::{self, Display, Formatter};
+
+/// Error returned when a null pointer is passed where a valid one is required.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NullPointer;
+
+impl Display for NullPointer {
+    fn fmt(&self, formatter: &mut Formatter) -> fmt::Result {
+        write!(formatter, "Null pointer")
+    }
+}
+
+/// Converts a raw pointer into a reference, failing if it is null.
+///
+/// This only guards against null pointers. Any other invalid pointer is still
+/// undefined behaviour, so the caller must otherwise uphold the same guarantees as
+/// for dereferencing `ptr` directly.
+pub unsafe fn ptr_as_ref<'a, T>(ptr: *const T) -> Result<&'a T, NullPointer> {
+    if ptr.is_null() {
+        Err(NullPointer)
+    } else {
+        Ok(&*ptr)
+    }
+}
+
+/// Converts a raw pointer into a mutable reference, failing if it is null.
+///
+/// The same caveats as for `ptr_as_ref` apply.
+pub unsafe fn ptr_as_mut<'a, T>(ptr: *mut T) -> Result<&'a mut T, NullPointer> {
+    if ptr.is_null() {
+        Err(NullPointer)
+    } else {
+        Ok(&mut *ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ptr;
+
+    #[test]
+    fn null_pointers() {
+        let mut value = 42;
+
+        unsafe {
+            assert_eq!(ptr_as_ref(ptr::null::<i32>()).err(), Some(NullPointer));
+            assert_eq!(ptr_as_mut(ptr::null_mut::<i32>()).err(), Some(NullPointer));
+
+            assert_eq!(*unwrap!(ptr_as_ref(&value as *const i32)), 42);
+            *unwrap!(ptr_as_mut(&mut value as *mut i32)) = 43;
+        }
+
+        assert_eq!(value, 43);
+    }
+}
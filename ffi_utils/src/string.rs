@@ -19,9 +19,12 @@
 
 use repr_c::ReprC;
 use std::error::Error;
-use std::ffi::{CStr, IntoStringError, NulError};
+use std::ffi::{CStr, CString, IntoStringError, NulError};
 use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
 use std::str::Utf8Error;
+use std::string::FromUtf16Error;
 
 impl ReprC for String {
     type C = *const c_char;
@@ -36,11 +39,51 @@ impl ReprC for String {
     }
 }
 
+/// Owned, nul-terminated string handed over through FFI. Null if there is no
+/// string. The caller has to give it back by passing it to the free function of
+/// the library which returned it.
+#[repr(C)]
+pub struct FfiString {
+    /// Pointer to the nul-terminated data, or null.
+    pub ptr: *mut c_char,
+}
+
+impl FfiString {
+    /// Takes ownership of the string. Interior nul bytes are dropped, as they can't
+    /// be represented in a C string.
+    pub fn from_string(s: String) -> Self {
+        let c_string = CString::new(s).unwrap_or_else(|err| {
+            let mut bytes = err.into_vec();
+            bytes.retain(|byte| *byte != 0);
+            unwrap!(CString::new(bytes))
+        });
+
+        FfiString { ptr: c_string.into_raw() }
+    }
+
+    /// Null string.
+    pub fn null() -> Self {
+        FfiString { ptr: ptr::null_mut() }
+    }
+
+    /// Turns the string back into the `String` it was created from. Returns `None`
+    /// for a null string.
+    pub unsafe fn into_string(self) -> Option<String> {
+        if self.ptr.is_null() {
+            None
+        } else {
+            Some(CString::from_raw(self.ptr).to_string_lossy().into_owned())
+        }
+    }
+}
+
 /// Error type for strings
 #[derive(Serialize, Deserialize, Debug, Eq, PartialEq)]
 pub enum StringError {
     /// UTF8 error
     Utf8(String),
+    /// UTF16 error
+    Utf16(String),
     /// Null error
     Null(String),
     /// IntoString error
@@ -53,6 +96,12 @@ impl From<Utf8Error> for StringError {
     }
 }
 
+impl From<FromUtf16Error> for StringError {
+    fn from(e: FromUtf16Error) -> Self {
+        StringError::Utf16(e.description().to_owned())
+    }
+}
+
 impl From<NulError> for StringError {
     fn from(e: NulError) -> Self {
         StringError::Null(e.description().to_owned())
@@ -76,3 +125,48 @@ pub unsafe fn from_c_str(ptr: *const c_char) -> Result<String, StringError> {
     }
     Ok(CStr::from_ptr(ptr).to_str()?.to_owned())
 }
+
+/// Copies memory from a provided pointer to a nul-terminated UTF-16 string, as passed
+/// by Windows wide-string (`wchar_t`) APIs, and allocates a new `String`.
+pub unsafe fn from_c_wstr(ptr: *const u16) -> Result<String, StringError> {
+    if ptr.is_null() {
+        return Err(StringError::Null(
+            "String could not be constructed from C null pointer"
+                .to_owned(),
+        ));
+    }
+
+    let mut len = 0;
+    while *ptr.offset(len as isize) != 0 {
+        len += 1;
+    }
+
+    Ok(String::from_utf16(slice::from_raw_parts(ptr, len))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that wide strings are decoded, including characters outside the BMP.
+    #[test]
+    fn wide_strings() {
+        let input = "za\u{17c}\u{f3}\u{142}\u{107} \u{1f600}";
+        let mut wide: Vec<u16> = input.encode_utf16().collect();
+        wide.push(0);
+
+        assert_eq!(unwrap!(unsafe { from_c_wstr(wide.as_ptr()) }), input);
+        assert_eq!(unwrap!(unsafe { from_c_wstr([0u16].as_ptr()) }), "");
+
+        // Unpaired surrogate.
+        match unsafe { from_c_wstr([0xd800u16, 0].as_ptr()) } {
+            Err(StringError::Utf16(_)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        match unsafe { from_c_wstr(ptr::null()) } {
+            Err(StringError::Null(_)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
@@ -15,6 +15,7 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use callback::CallbackArgs;
 use std::mem;
 use std::ptr;
 use std::slice;
@@ -58,3 +59,86 @@ pub fn vec_into_raw_parts<T>(mut v: Vec<T>) -> (*mut T, usize, usize) {
     mem::forget(v);
     (ptr, len, cap)
 }
+
+/// Byte buffer owned by Rust and lent to the caller without copying it. The
+/// caller has to give it back by passing it to the free function of the library
+/// which returned it.
+#[repr(C)]
+pub struct FfiBuffer {
+    /// Pointer to the data.
+    pub ptr: *mut u8,
+    /// Length of the data.
+    pub len: usize,
+    /// Capacity of the allocation, needed to free it.
+    pub cap: usize,
+}
+
+impl FfiBuffer {
+    /// Takes ownership of the vector's memory without copying it.
+    pub fn from_vec(mut v: Vec<u8>) -> Self {
+        let buffer = FfiBuffer {
+            ptr: v.as_mut_ptr(),
+            len: v.len(),
+            cap: v.capacity(),
+        };
+        mem::forget(v);
+        buffer
+    }
+
+    /// Null buffer, passed to callbacks when reporting an error.
+    pub fn null() -> Self {
+        FfiBuffer {
+            ptr: ptr::null_mut(),
+            len: 0,
+            cap: 0,
+        }
+    }
+
+    /// Returns `true` if this is a null buffer.
+    pub fn is_null(&self) -> bool {
+        self.ptr.is_null()
+    }
+
+    /// Turns the buffer back into the vector it was created from. Returns an
+    /// empty vector for a null buffer.
+    pub unsafe fn into_vec(self) -> Vec<u8> {
+        if self.ptr.is_null() {
+            Vec::new()
+        } else {
+            Vec::from_raw_parts(self.ptr, self.len, self.cap)
+        }
+    }
+}
+
+impl CallbackArgs for FfiBuffer {
+    fn default() -> Self {
+        FfiBuffer::null()
+    }
+}
+
+/// Slice of caller-owned memory passed to an asynchronous operation without
+/// copying it. The caller has to keep the memory valid and unchanged until the
+/// operation calls back.
+#[derive(Clone, Copy)]
+pub struct BorrowedSlice {
+    ptr: *const u8,
+    len: usize,
+}
+
+unsafe impl Send for BorrowedSlice {}
+
+impl BorrowedSlice {
+    /// Borrows `len` bytes starting at `ptr`.
+    pub unsafe fn new(ptr: *const u8, len: usize) -> Self {
+        BorrowedSlice { ptr, len }
+    }
+
+    /// The borrowed bytes.
+    pub unsafe fn as_slice(&self) -> &[u8] {
+        if self.len == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+}
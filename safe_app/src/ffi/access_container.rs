@@ -16,12 +16,14 @@
 // relating to use of the SAFE Network Software.
 
 use {App, AppError};
+use ffi::helper::send_with_user_data;
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb, from_c_str};
 use futures::Future;
 use safe_core::FutureExt;
 use safe_core::ffi::MDataInfo as FfiMDataInfo;
-use safe_core::ffi::ipc::req::ContainerPermissions as FfiContainerPermissions;
-use safe_core::ipc::req::containers_into_vec;
+use safe_core::ffi::ipc::req::{ContainerPermissions as FfiContainerPermissions,
+                               PermissionSet as FfiPermissionSet};
+use safe_core::ipc::req::{container_perms_into_repr_c, containers_into_vec};
 use std::os::raw::{c_char, c_void};
 
 /// Fetch access info from the network.
@@ -36,7 +38,7 @@ pub unsafe extern "C" fn access_container_refresh_access_info(
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             context
                 .refresh_access_info(client)
                 .then(move |res| {
@@ -64,7 +66,7 @@ pub unsafe extern "C" fn access_container_fetch(
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             context
                 .get_access_info(client)
                 .and_then(move |containers| {
@@ -104,7 +106,7 @@ pub unsafe extern "C" fn access_container_get_container_mdata_info(
         let user_data = OpaqueCtx(user_data);
         let name = from_c_str(name)?;
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             context
                 .get_access_info(client)
                 .map(move |mut containers| if let Some((mdata_info, _)) =
@@ -124,15 +126,90 @@ pub unsafe extern "C" fn access_container_get_container_mdata_info(
     })
 }
 
+/// Retrieve the permissions the app has been granted for the given container,
+/// as recorded in the access info. Useful to check whether an action is allowed
+/// before attempting it.
+///
+/// Callback parameters: user data, error code, permission set
+#[no_mangle]
+pub unsafe extern "C" fn app_container_permissions(
+    app: *const App,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        perms: *const FfiPermissionSet),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            context
+                .get_access_info(client)
+                .map(move |containers| if let Some(&(_, ref perms)) =
+                    containers.get(&name)
+                {
+                    let perms = container_perms_into_repr_c(perms);
+                    o_cb(user_data.0, FFI_RESULT_OK, &perms);
+                } else {
+                    call_result_cb!(Err::<(), _>(AppError::NoSuchContainer), user_data, o_cb);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Retrieve `MDataInfo` of the app's own container, creating the container if this is
+/// its first use, as the authenticator only reserves it. Fails with
+/// `ERR_NO_SUCH_CONTAINER` if the app hasn't been authorised with `app_container`.
+///
+/// Callback parameters: user data, error code, mdata info handle
+#[no_mangle]
+pub unsafe extern "C" fn app_container_info(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        mdata_info: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, context| {
+            context
+                .get_app_container_info(client)
+                .map(move |mdata_info| {
+                    let mdata_info = mdata_info.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &mdata_info);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 #[cfg(test)]
 mod tests {
+    use App;
     use errors::AppError;
+    use ffi::{app_reconnect, app_refresh_access_info};
     use ffi::access_container::*;
     use ffi_utils::{ReprC, from_c_str};
     use ffi_utils::test_utils::{call_0, call_1, call_vec};
+    use safe_authenticator::ffi::ipc::encode_containers_resp;
+    use safe_authenticator::test_utils as authenticator;
     use safe_core::{DIR_TAG, MDataInfo};
     use safe_core::ffi::ipc::req::ContainerPermissions as FfiContainerPermissions;
-    use safe_core::ipc::req::{Permission, container_perms_from_repr_c};
+    use safe_core::ipc::gen_req_id;
+    use safe_core::ipc::req::{ContainersReq, Permission, container_perms_from_repr_c};
     use safe_core::ipc::req::ContainerPermissions;
     use std::collections::HashMap;
     use std::ffi::CString;
@@ -177,6 +254,186 @@ mod tests {
         });
     }
 
+    // Test that reconnecting also refreshes the access info.
+    #[test]
+    fn reconnect_refreshes_access_info() {
+        let mut container_permissions = HashMap::new();
+        let _ = container_permissions.insert("_videos".to_string(), btree_set![Permission::Read]);
+
+        let mut app = create_app_by_req(&create_auth_req_with_access(container_permissions));
+
+        run(&app, move |_client, context| {
+            let reg = Rc::clone(unwrap!(context.as_registered()));
+            assert!(reg.access_info.borrow().is_empty());
+            Ok(())
+        });
+
+        unsafe { unwrap!(call_0(|ud, cb| app_reconnect(&mut app, ud, cb))) }
+
+        run(&app, move |_client, context| {
+            let reg = Rc::clone(unwrap!(context.as_registered()));
+            assert!(reg.access_info.borrow().contains_key("_videos"));
+            Ok(())
+        });
+    }
+
+    // Test that containers granted to a running app become available after refreshing.
+    // 1. Authorise an app with access to `_videos` and fetch its access info.
+    // 2. Grant it access to `_downloads` through a containers request.
+    // 3. The cached access info should still be the old one.
+    // 4. After refreshing, `_downloads` should be listed.
+    #[test]
+    fn refresh_after_containers_req() {
+        let auth = authenticator::create_account_and_login();
+
+        let mut container_permissions = HashMap::new();
+        let _ = container_permissions.insert("_videos".to_string(), btree_set![Permission::Read]);
+        let auth_req = create_auth_req_with_access(container_permissions);
+        let auth_granted = unwrap!(authenticator::register_app(&auth, &auth_req));
+        let app = unwrap!(App::registered(
+            auth_req.app.id.clone(),
+            auth_granted,
+            || (),
+        ));
+
+        let perms: Vec<PermSet> =
+            unsafe { unwrap!(call_vec(|ud, cb| access_container_fetch(&app, ud, cb))) };
+        assert!(!perms.iter().any(|perm| perm.0 == "_downloads"));
+
+        let cont_req = ContainersReq {
+            app: auth_req.app.clone(),
+            containers: {
+                let mut containers = HashMap::new();
+                let _ = containers.insert("_downloads".to_string(), btree_set![Permission::Read]);
+                containers
+            },
+        };
+        let _: String = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                let cont_req = unwrap!(cont_req.into_repr_c());
+                encode_containers_resp(&auth, &cont_req, gen_req_id(), true, ud, cb)
+            }))
+        };
+
+        let perms: Vec<PermSet> =
+            unsafe { unwrap!(call_vec(|ud, cb| access_container_fetch(&app, ud, cb))) };
+        assert!(!perms.iter().any(|perm| perm.0 == "_downloads"));
+
+        let perms: Vec<PermSet> =
+            unsafe { unwrap!(call_vec(|ud, cb| app_refresh_access_info(&app, ud, cb))) };
+        let perms: HashMap<_, _> = perms.into_iter().map(|val| (val.0, val.1)).collect();
+        assert_eq!(perms["_videos"], btree_set![Permission::Read]);
+        assert_eq!(perms["_downloads"], btree_set![Permission::Read]);
+
+        let perms: Vec<PermSet> =
+            unsafe { unwrap!(call_vec(|ud, cb| access_container_fetch(&app, ud, cb))) };
+        assert!(perms.iter().any(|perm| perm.0 == "_downloads"));
+    }
+
+    // Test getting the permissions granted for a container.
+    #[test]
+    fn get_container_permissions() {
+        use errors::ERR_NO_SUCH_CONTAINER;
+        use safe_core::ffi::ipc::req::PermissionSet as FfiPermissionSet;
+
+        let mut container_permissions = HashMap::new();
+        let _ = container_permissions.insert(
+            "_videos".to_string(),
+            btree_set![Permission::Read, Permission::Insert],
+        );
+        let app = create_app_by_req(&create_auth_req_with_access(container_permissions));
+
+        let videos_str = unwrap!(CString::new("_videos"));
+        let perms: FfiPermissionSet = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                app_container_permissions(&app, videos_str.as_ptr(), ud, cb)
+            }))
+        };
+        assert_eq!(
+            unwrap!(container_perms_from_repr_c(perms)),
+            btree_set![Permission::Read, Permission::Insert]
+        );
+
+        let music_str = unwrap!(CString::new("_music"));
+        let res: Result<FfiPermissionSet, i32> = unsafe {
+            call_1(|ud, cb| {
+                app_container_permissions(&app, music_str.as_ptr(), ud, cb)
+            })
+        };
+        match res {
+            Err(ERR_NO_SUCH_CONTAINER) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
+    // Test getting the app's own container.
+    #[test]
+    fn get_app_container_info() {
+        use errors::ERR_NO_SUCH_CONTAINER;
+        use futures::Future;
+        use routing::{Action, ClientError, User};
+        use safe_core::{CoreError, app_container_name};
+        use test_utils::create_app;
+
+        let mut container_permissions = HashMap::new();
+        let _ = container_permissions.insert("_videos".to_string(), btree_set![Permission::Read]);
+        let app = create_app_by_req(&create_auth_req_with_access(container_permissions));
+
+        // The container is only reserved by the authenticator.
+        let (name, tag) = run(&app, |client, context| {
+            let reg = Rc::clone(unwrap!(context.as_registered()));
+            let app_id = reg.app_id.clone();
+            let c2 = client.clone();
+
+            context
+                .refresh_access_info(client)
+                .map(move |_| {
+                    let access_info = reg.access_info.borrow();
+                    let &(ref info, _) = unwrap!(access_info.get(&app_container_name(&app_id)));
+                    (info.name, info.type_tag)
+                })
+                .and_then(move |(name, tag)| {
+                    c2.get_mdata_version(name, tag).then(move |res| {
+                        match res {
+                            Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => (),
+                            x => panic!("Unexpected {:?}", x),
+                        }
+                        Ok((name, tag))
+                    })
+                })
+        });
+
+        // Getting it creates it, with full access for the app.
+        let md_info: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| app_container_info(&app, ud, cb))) };
+        assert_eq!(md_info.type_tag, DIR_TAG);
+        assert!(md_info.enc_info.is_some());
+        assert_eq!((md_info.name, md_info.type_tag), (name, tag));
+
+        let perms = run(&app, move |client, _| {
+            let sign_pk = unwrap!(client.public_signing_key());
+            client
+                .list_mdata_user_permissions(name, tag, User::Key(sign_pk))
+                .map_err(AppError::from)
+        });
+        assert_eq!(perms.is_allowed(Action::Insert), Some(true));
+        assert_eq!(perms.is_allowed(Action::ManagePermissions), Some(true));
+
+        // Getting it again reuses it.
+        let md_info2: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| app_container_info(&app, ud, cb))) };
+        assert_eq!(md_info2.name, name);
+
+        // An app authorised without `app_container` doesn't have one.
+        let app = create_app();
+        let res: Result<MDataInfo, i32> =
+            unsafe { call_1(|ud, cb| app_container_info(&app, ud, cb)) };
+        match res {
+            Err(ERR_NO_SUCH_CONTAINER) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
     // Test getting info about access containers and their mutable data.
     #[test]
     fn get_access_info() {
@@ -0,0 +1,346 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use safe_core::{FutureExt, MDataInfo, append_log};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use safe_core::ffi::arrays::SignPublicKey;
+use std::os::raw::c_void;
+
+/// FFI object representing an entry of an append-only log.
+#[repr(C)]
+pub struct LogEntry {
+    /// Entry content.
+    pub content: *const u8,
+    /// Entry content length.
+    pub content_len: usize,
+}
+
+/// Create a new empty append-only log owned by the app.
+///
+/// Callback parameters: user data, error code, log mdata info
+#[no_mangle]
+pub unsafe extern "C" fn append_log_create(
+    app: *const App,
+    private: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        log: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::create(client, private)
+                .map(move |log| {
+                    let log = log.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &log);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Append an entry to the log.
+///
+/// Callback parameters: user data, error code, index of the entry
+#[no_mangle]
+pub unsafe extern "C" fn append_log_append(
+    app: *const App,
+    log: *const FfiMDataInfo,
+    entry: *const u8,
+    entry_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, index: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let log = MDataInfo::clone_from_repr_c(log)?;
+        let entry = vec_clone_from_raw_parts(entry, entry_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::append(client, &log, entry)
+                .map(move |index| o_cb(user_data.0, FFI_RESULT_OK, index))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the number of entries in the log.
+///
+/// Callback parameters: user data, error code, number of entries
+#[no_mangle]
+pub unsafe extern "C" fn append_log_len(
+    app: *const App,
+    log: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, len: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let log = MDataInfo::clone_from_repr_c(log)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::len(client, &log)
+                .map(move |len| o_cb(user_data.0, FFI_RESULT_OK, len))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the version of the log, incremented by every append.
+///
+/// Callback parameters: user data, error code, version
+#[no_mangle]
+pub unsafe extern "C" fn append_log_version(
+    app: *const App,
+    log: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let log = MDataInfo::clone_from_repr_c(log)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::stats(client, &log)
+                .map(move |stats| o_cb(user_data.0, FFI_RESULT_OK, stats.version))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the public signing keys of the owners of the log.
+///
+/// Callback parameters: user data, error code, keys vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn append_log_owners(
+    app: *const App,
+    log: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        owners: *const SignPublicKey,
+                        owners_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let log = MDataInfo::clone_from_repr_c(log)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::stats(client, &log)
+                .map(move |stats| {
+                    let owners: Vec<SignPublicKey> = stats.owners.iter().map(|key| key.0).collect();
+                    o_cb(user_data.0, FFI_RESULT_OK, owners.as_safe_ptr(), owners.len());
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the approximate serialised size of the log, in bytes, and the number of entries of
+/// its `MutableData`. Appends start failing once either reaches the limit of the network, so
+/// new entries should go to a new log before that.
+///
+/// Callback parameters: user data, error code, size, number of `MutableData` entries
+#[no_mangle]
+pub unsafe extern "C" fn append_log_size(
+    app: *const App,
+    log: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        size: u64,
+                        mdata_entries: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let log = MDataInfo::clone_from_repr_c(log)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::stats(client, &log)
+                .map(move |stats| {
+                    o_cb(user_data.0, FFI_RESULT_OK, stats.size, stats.mdata_entries)
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Read the log entries with indices from `from` (inclusive) to `to` (exclusive).
+///
+/// Callback parameters: user data, error code, entries vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn append_log_read_range(
+    app: *const App,
+    log: *const FfiMDataInfo,
+    from: u64,
+    to: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        entries: *const LogEntry,
+                        entries_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let log = MDataInfo::clone_from_repr_c(log)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            append_log::read_range(client, &log, from, to)
+                .map(move |entries| {
+                    let ffi_entries: Vec<_> = entries
+                        .iter()
+                        .map(|entry| {
+                            LogEntry {
+                                content: entry.as_safe_ptr(),
+                                content_len: entry.len(),
+                            }
+                        })
+                        .collect();
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        ffi_entries.as_safe_ptr(),
+                        ffi_entries.len(),
+                    );
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{call_1, call_2, call_vec, send_via_user_data,
+                                sender_as_user_data};
+    use std::slice;
+    use std::sync::mpsc;
+    use test_utils::{create_app, run_now};
+
+    // Test appending to and reading from a log through the FFI.
+    #[test]
+    fn append_read() {
+        let app = create_app();
+
+        let log: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| append_log_create(&app, true, ud, cb))) };
+        let log = log.into_repr_c();
+
+        for (i, entry) in [b"one", b"two"].iter().enumerate() {
+            let index: u64 = unsafe {
+                unwrap!(call_1(|ud, cb| {
+                    append_log_append(&app, &log, entry.as_ptr(), entry.len(), ud, cb)
+                }))
+            };
+            assert_eq!(index, i as u64);
+        }
+
+        let len: u64 = unsafe { unwrap!(call_1(|ud, cb| append_log_len(&app, &log, ud, cb))) };
+        assert_eq!(len, 2);
+
+        let entries = unsafe { read_range(&app, &log, 1, 10) };
+        assert_eq!(entries, vec![b"two".to_vec()]);
+
+        let version: u64 =
+            unsafe { unwrap!(call_1(|ud, cb| append_log_version(&app, &log, ud, cb))) };
+        assert_eq!(version, 2);
+
+        let owners: Vec<SignPublicKey> =
+            unsafe { unwrap!(call_vec(|ud, cb| append_log_owners(&app, &log, ud, cb))) };
+        let owner_key = unwrap!(run_now(&app, |client, _| client.owner_key()));
+        assert_eq!(owners, vec![owner_key.0]);
+
+        let (size, mdata_entries): (u64, u64) =
+            unsafe { unwrap!(call_2(|ud, cb| append_log_size(&app, &log, ud, cb))) };
+        assert!(size > 0);
+        assert_eq!(mdata_entries, 1);
+    }
+
+    unsafe fn read_range(app: &App, log: &FfiMDataInfo, from: u64, to: u64) -> Vec<Vec<u8>> {
+        extern "C" fn read_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            entries: *const LogEntry,
+            entries_len: usize,
+        ) {
+            unsafe {
+                let result: Result<Vec<_>, i32> = if (*res).error_code == 0 {
+                    Ok(
+                        slice::from_raw_parts(entries, entries_len)
+                            .iter()
+                            .map(|entry| {
+                                vec_clone_from_raw_parts(entry.content, entry.content_len)
+                            })
+                            .collect(),
+                    )
+                } else {
+                    Err((*res).error_code)
+                };
+
+                send_via_user_data(user_data, result);
+            }
+        }
+
+        let (tx, rx) = mpsc::channel::<Result<Vec<Vec<u8>>, i32>>();
+        let mut ud = Default::default();
+        append_log_read_range(
+            app,
+            log,
+            from,
+            to,
+            sender_as_user_data(&tx, &mut ud),
+            read_cb,
+        );
+        unwrap!(unwrap!(rx.recv()))
+    }
+}
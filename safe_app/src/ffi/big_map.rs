@@ -0,0 +1,276 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use safe_core::{FutureExt, MDataInfo, big_map};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use safe_core::ffi::ipc::resp::MDataKey as FfiMDataKey;
+use safe_core::ipc::resp::MDataKey;
+use std::os::raw::c_void;
+
+/// Create a new empty big map. Entries of a private map are encrypted.
+///
+/// Callback parameters: user data, error code, big map mdata info
+#[no_mangle]
+pub unsafe extern "C" fn big_map_create(
+    app: *const App,
+    private: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        map: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            big_map::create(client, private)
+                .map(move |map| {
+                    let map = map.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &map);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Store a value in the big map, replacing the current value of the key if any.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn big_map_put(
+    app: *const App,
+    map: *const FfiMDataInfo,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let map = MDataInfo::clone_from_repr_c(map)?;
+        let key = vec_clone_from_raw_parts(key, key_len);
+        let value = vec_clone_from_raw_parts(value, value_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            big_map::put(client, &map, &key, &value)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the value stored under the key in the big map.
+///
+/// Callback parameters: user data, error code, content, content length, entry version
+#[no_mangle]
+pub unsafe extern "C" fn big_map_get(
+    app: *const App,
+    map: *const FfiMDataInfo,
+    key: *const u8,
+    key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        content: *const u8,
+                        content_len: usize,
+                        version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let map = MDataInfo::clone_from_repr_c(map)?;
+        let key = vec_clone_from_raw_parts(key, key_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            big_map::get(client, &map, &key)
+                .map(move |value| {
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        value.content.as_safe_ptr(),
+                        value.content.len(),
+                        value.entry_version,
+                    );
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Delete the value stored under the key in the big map.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn big_map_delete(
+    app: *const App,
+    map: *const FfiMDataInfo,
+    key: *const u8,
+    key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let map = MDataInfo::clone_from_repr_c(map)?;
+        let key = vec_clone_from_raw_parts(key, key_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            big_map::delete(client, &map, &key)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get the list of all keys in the big map.
+///
+/// Callback parameters: user data, error code, vector of keys, vector size
+#[no_mangle]
+pub unsafe extern "C" fn big_map_list_keys(
+    app: *const App,
+    map: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        keys: *const FfiMDataKey,
+                        len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let map = MDataInfo::clone_from_repr_c(map)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            big_map::list(client, &map)
+                .map(move |entries| {
+                    let keys: Vec<_> = entries
+                        .into_iter()
+                        .map(|(key, _)| MDataKey::from_routing(key))
+                        .collect();
+                    let repr_c: Vec<_> = keys.iter().map(MDataKey::as_repr_c).collect();
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        repr_c.as_safe_ptr(),
+                        repr_c.len(),
+                    );
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ERR_NO_SUCH_ENTRY;
+    use ffi_utils::test_utils::{call_0, call_1, call_vec, send_via_user_data,
+                                sender_as_user_data};
+    use std::sync::mpsc;
+    use test_utils::create_app;
+
+    // Test the big map operations through the FFI.
+    #[test]
+    fn put_get_delete() {
+        let app = create_app();
+
+        let map: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| big_map_create(&app, true, ud, cb))) };
+        let map = map.into_repr_c();
+
+        for key in &[b"one", b"two"] {
+            unsafe {
+                unwrap!(call_0(|ud, cb| {
+                    big_map_put(&app, &map, key.as_ptr(), key.len(), key.as_ptr(), 3, ud, cb)
+                }))
+            };
+        }
+
+        let mut keys: Vec<MDataKey> =
+            unsafe { unwrap!(call_vec(|ud, cb| big_map_list_keys(&app, &map, ud, cb))) };
+        keys.sort_by(|a, b| a.val.cmp(&b.val));
+        let keys: Vec<_> = keys.into_iter().map(|key| key.val).collect();
+        assert_eq!(keys, vec![b"one".to_vec(), b"two".to_vec()]);
+
+        assert_eq!(unsafe { get(&app, &map, b"one") }, Ok((b"one".to_vec(), 0)));
+
+        unsafe {
+            unwrap!(call_0(
+                |ud, cb| big_map_delete(&app, &map, b"one".as_ptr(), 3, ud, cb),
+            ))
+        };
+        assert_eq!(unsafe { get(&app, &map, b"one") }, Err(ERR_NO_SUCH_ENTRY));
+    }
+
+    unsafe fn get(app: &App, map: &FfiMDataInfo, key: &[u8]) -> Result<(Vec<u8>, u64), i32> {
+        extern "C" fn get_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            content: *const u8,
+            content_len: usize,
+            version: u64,
+        ) {
+            unsafe {
+                let result = if (*res).error_code == 0 {
+                    Ok((vec_clone_from_raw_parts(content, content_len), version))
+                } else {
+                    Err((*res).error_code)
+                };
+
+                send_via_user_data(user_data, result);
+            }
+        }
+
+        let (tx, rx) = mpsc::channel::<Result<(Vec<u8>, u64), i32>>();
+        let mut ud = Default::default();
+        big_map_get(
+            app,
+            map,
+            key.as_ptr(),
+            key.len(),
+            sender_as_user_data(&tx, &mut ud),
+            get_cb,
+        );
+        unwrap!(rx.recv())
+    }
+}
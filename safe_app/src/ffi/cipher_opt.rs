@@ -17,15 +17,17 @@
 
 use {App, AppContext};
 use errors::AppError;
+use ffi::helper::send_with_user_data;
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use object_cache::{CipherOptHandle, EncryptPubKeyHandle};
 use rust_sodium::crypto::{box_, sealedbox, secretbox};
-use safe_core::{Client, CoreError};
+use safe_core::{Client, CoreError, KeyOp};
+use safe_core::utils::compression;
 use std::os::raw::c_void;
 
 /// Cipher Options
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum CipherOpt {
     /// No encryption
     PlainText,
@@ -36,6 +38,10 @@ pub enum CipherOpt {
         /// PublicKey of the peer to whom we want to encrypt
         peer_encrypt_key: box_::PublicKey,
     },
+    /// Compress the plain text before encrypting it with the inner option. Content which
+    /// doesn't shrink is left uncompressed. Content is compressed at most once, even if the
+    /// inner option is compressing too.
+    Compressed(Box<CipherOpt>),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -46,29 +52,40 @@ enum WireFormat {
         cipher_text: Vec<u8>,
     },
     Asymmetric(Vec<u8>),
+    // Plain text compressed before being sealed. Can't be nested.
+    Compressed(Sealed),
 }
 
-impl CipherOpt {
-    /// Encrypt plain text
-    pub fn encrypt(&self, plain_text: &[u8], app_ctx: &AppContext) -> Result<Vec<u8>, AppError> {
-        match *self {
-            CipherOpt::PlainText => Ok(serialise(&WireFormat::Plain(plain_text.to_owned()))?),
-            CipherOpt::Symmetric => {
-                let nonce = secretbox::gen_nonce();
-                let cipher_text = secretbox::seal(plain_text, &nonce, app_ctx.sym_enc_key()?);
-                let wire_format = WireFormat::Symmetric {
+#[derive(Debug, Clone, Serialize, Deserialize)]
+enum Sealed {
+    Plain(Vec<u8>),
+    Symmetric {
+        nonce: secretbox::Nonce,
+        cipher_text: Vec<u8>,
+    },
+    Asymmetric(Vec<u8>),
+}
+
+impl From<Sealed> for WireFormat {
+    fn from(sealed: Sealed) -> WireFormat {
+        match sealed {
+            Sealed::Plain(plain_text) => WireFormat::Plain(plain_text),
+            Sealed::Symmetric { nonce, cipher_text } => {
+                WireFormat::Symmetric {
                     nonce: nonce,
                     cipher_text: cipher_text,
-                };
-
-                Ok(serialise(&wire_format)?)
-            }
-            CipherOpt::Asymmetric { ref peer_encrypt_key } => {
-                let cipher_text = sealedbox::seal(plain_text, peer_encrypt_key);
-                Ok(serialise(&WireFormat::Asymmetric(cipher_text))?)
+                }
             }
+            Sealed::Asymmetric(cipher_text) => WireFormat::Asymmetric(cipher_text),
         }
     }
+}
+
+impl CipherOpt {
+    /// Encrypt plain text
+    pub fn encrypt(&self, plain_text: &[u8], app_ctx: &AppContext) -> Result<Vec<u8>, AppError> {
+        Ok(serialise(&self.seal(plain_text, app_ctx)?)?)
+    }
 
     /// Decrypt something encrypted by CipherOpt::encrypt()
     pub fn decrypt(
@@ -80,17 +97,81 @@ impl CipherOpt {
             return Ok(Vec::new());
         }
 
-        match deserialise::<WireFormat>(cipher_text)? {
-            WireFormat::Plain(plain_text) => Ok(plain_text),
-            WireFormat::Symmetric { nonce, cipher_text } => {
-                Ok(secretbox::open(&cipher_text, &nonce, app_ctx.sym_enc_key()?)
-                    .map_err(|()| CoreError::SymmetricDecipherFailure)?)
+        open(deserialise::<WireFormat>(cipher_text)?, app_ctx, client)
+    }
+
+    fn seal(&self, plain_text: &[u8], app_ctx: &AppContext) -> Result<WireFormat, AppError> {
+        match *self {
+            CipherOpt::Compressed(_) => {
+                let compressed = compression::compress(plain_text)?;
+                if compressed.len() < plain_text.len() {
+                    Ok(WireFormat::Compressed(self.seal_only(&compressed, app_ctx)?))
+                } else {
+                    Ok(self.seal_only(plain_text, app_ctx)?.into())
+                }
             }
-            WireFormat::Asymmetric(cipher_text) => {
-                let (asym_pk, asym_sk) = client.encryption_keypair()?;
-                Ok(sealedbox::open(&cipher_text, &asym_pk, &asym_sk)
-                    .map_err(|()| CoreError::AsymmetricDecipherFailure)?)
+            _ => Ok(self.seal_only(plain_text, app_ctx)?.into()),
+        }
+    }
+
+    // Seals the content without compressing it, whether this option is compressing or not.
+    fn seal_only(&self, plain_text: &[u8], app_ctx: &AppContext) -> Result<Sealed, AppError> {
+        match *self {
+            CipherOpt::PlainText => Ok(Sealed::Plain(plain_text.to_owned())),
+            CipherOpt::Symmetric => {
+                let nonce = secretbox::gen_nonce();
+                let cipher_text = secretbox::seal(plain_text, &nonce, app_ctx.sym_enc_key()?);
+
+                Ok(Sealed::Symmetric {
+                    nonce: nonce,
+                    cipher_text: cipher_text,
+                })
             }
+            CipherOpt::Asymmetric { ref peer_encrypt_key } => {
+                let cipher_text = sealedbox::seal(plain_text, peer_encrypt_key);
+                Ok(Sealed::Asymmetric(cipher_text))
+            }
+            CipherOpt::Compressed(ref inner) => inner.seal_only(plain_text, app_ctx),
+        }
+    }
+}
+
+fn open(
+    wire_format: WireFormat,
+    app_ctx: &AppContext,
+    client: &Client<AppContext>,
+) -> Result<Vec<u8>, AppError> {
+    match wire_format {
+        WireFormat::Plain(plain_text) => Ok(plain_text),
+        WireFormat::Symmetric { nonce, cipher_text } => {
+            open_sealed(Sealed::Symmetric { nonce, cipher_text }, app_ctx, client)
+        }
+        WireFormat::Asymmetric(cipher_text) => {
+            open_sealed(Sealed::Asymmetric(cipher_text), app_ctx, client)
+        }
+        WireFormat::Compressed(sealed) => {
+            let compressed = open_sealed(sealed, app_ctx, client)?;
+            Ok(compression::decompress(&compressed)?)
+        }
+    }
+}
+
+fn open_sealed(
+    sealed: Sealed,
+    app_ctx: &AppContext,
+    client: &Client<AppContext>,
+) -> Result<Vec<u8>, AppError> {
+    match sealed {
+        Sealed::Plain(plain_text) => Ok(plain_text),
+        Sealed::Symmetric { nonce, cipher_text } => {
+            Ok(secretbox::open(&cipher_text, &nonce, app_ctx.sym_enc_key()?)
+                .map_err(|()| CoreError::SymmetricDecipherFailure)?)
+        }
+        Sealed::Asymmetric(cipher_text) => {
+            client.record_key_usage(KeyOp::Decrypt, None);
+            let (asym_pk, asym_sk) = client.encryption_keypair()?;
+            Ok(sealedbox::open(&cipher_text, &asym_pk, &asym_sk)
+                .map_err(|()| CoreError::AsymmetricDecipherFailure)?)
         }
     }
 }
@@ -109,7 +190,7 @@ pub unsafe extern "C" fn cipher_opt_new_plaintext(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let handle = context.object_cache().insert_cipher_opt(
                 CipherOpt::PlainText,
             );
@@ -132,7 +213,7 @@ pub unsafe extern "C" fn cipher_opt_new_symmetric(
 ) {
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let handle = context.object_cache().insert_cipher_opt(
                 CipherOpt::Symmetric,
             );
@@ -157,7 +238,7 @@ pub unsafe extern "C" fn cipher_opt_new_asymmetric(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk = try_cb!(
                 context.object_cache().get_encrypt_key(peer_encrypt_key_h),
                 user_data,
@@ -174,6 +255,37 @@ pub unsafe extern "C" fn cipher_opt_new_asymmetric(
     });
 }
 
+/// Construct `CipherOpt::Compressed` handle wrapping the given cipher opt, which stays
+/// valid and has to be freed separately.
+///
+/// Callback parameters: user data, error code, cipher opt handle
+#[no_mangle]
+pub unsafe extern "C" fn cipher_opt_new_compressed(
+    app: *const App,
+    inner_h: CipherOptHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        handle: CipherOptHandle),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || {
+        send_with_user_data(app, user_data, move |_, context| {
+            let inner = try_cb!(
+                context.object_cache().get_cipher_opt(inner_h),
+                user_data,
+                o_cb
+            ).clone();
+            let handle = context.object_cache().insert_cipher_opt(
+                CipherOpt::Compressed(Box::new(inner)),
+            );
+            o_cb(user_data.0, FFI_RESULT_OK, handle);
+            None
+        })
+    });
+}
+
 /// Free `CipherOpt` handle.
 ///
 /// Callback parameters: user data, error code
@@ -187,7 +299,7 @@ pub unsafe extern "C" fn cipher_opt_free(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let res = context.object_cache().remove_cipher_opt(handle);
             call_result_cb!(res, user_data, o_cb);
             None
@@ -324,6 +436,46 @@ mod tests {
         });
     }
 
+    // Test compressed symmetric encryption and decryption.
+    #[test]
+    fn app_0_to_app_0_compressed() {
+        let app_0 = create_app();
+
+        let sym_handle: CipherOptHandle =
+            unsafe { unwrap!(call_1(|ud, cb| cipher_opt_new_symmetric(&app_0, ud, cb))) };
+        let cipher_opt_handle: CipherOptHandle = unsafe {
+            unwrap!(call_1(
+                |ud, cb| cipher_opt_new_compressed(&app_0, sym_handle, ud, cb),
+            ))
+        };
+        assert_free(&app_0, sym_handle, 0);
+
+        let text: Vec<u8> = b"<p>hello</p>".iter().cycle().take(1200).cloned().collect();
+        let random = unwrap!(utils::generate_random_vector::<u8>(10));
+
+        run_now(&app_0, move |client, context| {
+            let cipher_opt = unwrap!(context.object_cache().get_cipher_opt(cipher_opt_handle));
+
+            let cipher_text = unwrap!(cipher_opt.encrypt(&text, context));
+            assert!(cipher_text.len() < text.len());
+            assert!(decrypt_and_check(client, context, &cipher_text, &text));
+
+            // Incompressible content is stored as is.
+            let cipher_text = unwrap!(cipher_opt.encrypt(&random, context));
+            assert!(decrypt_and_check(client, context, &cipher_text, &random));
+
+            // Nested options compress only once.
+            let nested = CipherOpt::Compressed(Box::new(cipher_opt.clone()));
+            let cipher_text = unwrap!(nested.encrypt(&text, context));
+            match unwrap!(deserialise::<WireFormat>(&cipher_text)) {
+                WireFormat::Compressed(Sealed::Symmetric { .. }) => (),
+                x => panic!("Unexpected {:?}", x),
+            }
+            assert!(decrypt_and_check(client, context, &cipher_text, &text));
+        });
+        assert_free(&app_0, cipher_opt_handle, 0);
+    }
+
     // Test creating and freeing the different possible cipher option handles.
     #[test]
     fn create_and_free() {
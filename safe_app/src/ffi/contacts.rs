@@ -0,0 +1,356 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb, from_c_str};
+use futures::Future;
+use object_cache::{EncryptPubKeyHandle, NULL_OBJECT_HANDLE, SignPubKeyHandle};
+use safe_core::{FutureExt, MDataInfo, contacts};
+use safe_core::contacts::Contact;
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::ptr;
+
+/// FFI object representing an entry of the address book.
+#[repr(C)]
+pub struct FfiContact {
+    /// Alias of the contact.
+    pub alias: *const c_char,
+    /// Public name of the contact, or null if it has none.
+    pub public_name: *const c_char,
+}
+
+/// Add a contact to the address book stored in the `_contacts` container,
+/// replacing the one already stored under `alias`, if any. `public_name` may be
+/// null and either key handle may be `NULL_OBJECT_HANDLE`, but the contact needs
+/// at least one of them.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn contacts_add(
+    app: *const App,
+    contacts: *const FfiMDataInfo,
+    alias: *const c_char,
+    public_name: *const c_char,
+    enc_key_h: EncryptPubKeyHandle,
+    sign_key_h: SignPubKeyHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let contacts = MDataInfo::clone_from_repr_c(contacts)?;
+        let alias = from_c_str(alias)?;
+        let public_name = if public_name.is_null() {
+            None
+        } else {
+            Some(from_c_str(public_name)?)
+        };
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let enc_key = if enc_key_h == NULL_OBJECT_HANDLE {
+                None
+            } else {
+                Some(*try_cb!(
+                    context.object_cache().get_encrypt_key(enc_key_h),
+                    user_data,
+                    o_cb
+                ))
+            };
+            let sign_key = if sign_key_h == NULL_OBJECT_HANDLE {
+                None
+            } else {
+                Some(*try_cb!(
+                    context.object_cache().get_pub_sign_key(sign_key_h),
+                    user_data,
+                    o_cb
+                ))
+            };
+            let contact = Contact {
+                public_name,
+                enc_key,
+                sign_key,
+            };
+
+            contacts::add(client, &contacts, &alias, &contact)
+                .map(move |_| o_cb(user_data.0, FFI_RESULT_OK))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Look up the public keys of the contact stored under `alias`. A key the
+/// contact has no record of is returned as `NULL_OBJECT_HANDLE`.
+///
+/// Callback parameters: user data, error code, encryption key handle, sign key handle
+#[no_mangle]
+pub unsafe extern "C" fn contacts_get_keys(
+    app: *const App,
+    contacts: *const FfiMDataInfo,
+    alias: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        enc_key_h: EncryptPubKeyHandle,
+                        sign_key_h: SignPubKeyHandle),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let contacts = MDataInfo::clone_from_repr_c(contacts)?;
+        let alias = from_c_str(alias)?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let context = context.clone();
+
+            contacts::get(client, &contacts, &alias)
+                .map(move |contact| {
+                    let cache = context.object_cache();
+                    let enc_key_h = contact.enc_key.map_or(NULL_OBJECT_HANDLE, |key| {
+                        cache.insert_encrypt_key(key)
+                    });
+                    let sign_key_h = contact.sign_key.map_or(NULL_OBJECT_HANDLE, |key| {
+                        cache.insert_pub_sign_key(key)
+                    });
+                    o_cb(user_data.0, FFI_RESULT_OK, enc_key_h, sign_key_h);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// List all contacts in the address book.
+///
+/// Callback parameters: user data, error code, contacts vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn contacts_list(
+    app: *const App,
+    contacts: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        contacts: *const FfiContact,
+                        contacts_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let contacts = MDataInfo::clone_from_repr_c(contacts)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            contacts::list(client, &contacts)
+                .map_err(AppError::from)
+                .and_then(|contacts| {
+                    let mut names = Vec::with_capacity(contacts.len());
+                    for (alias, contact) in contacts {
+                        let public_name = match contact.public_name {
+                            Some(name) => Some(CString::new(name)?),
+                            None => None,
+                        };
+                        names.push((CString::new(alias)?, public_name));
+                    }
+                    Ok(names)
+                })
+                .map(move |names| {
+                    let ffi_contacts: Vec<_> = names
+                        .iter()
+                        .map(|&(ref alias, ref public_name)| {
+                            FfiContact {
+                                alias: alias.as_ptr(),
+                                public_name: public_name.as_ref().map_or(
+                                    ptr::null(),
+                                    |name| name.as_ptr(),
+                                ),
+                            }
+                        })
+                        .collect();
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        ffi_contacts.as_safe_ptr(),
+                        ffi_contacts.len(),
+                    );
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Remove the contact stored under `alias` from the address book.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn contacts_remove(
+    app: *const App,
+    contacts: *const FfiMDataInfo,
+    alias: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let contacts = MDataInfo::clone_from_repr_c(contacts)?;
+        let alias = from_c_str(alias)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            contacts::remove(client, &contacts, &alias)
+                .map(move |_| o_cb(user_data.0, FFI_RESULT_OK))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ERR_NO_SUCH_ENTRY;
+    use ffi_utils::test_utils::{call_0, call_2, call_vec};
+    use rust_sodium::crypto::box_;
+    use safe_core::ipc::Permission;
+    use std::collections::HashMap;
+    use test_utils::{create_app_by_req, create_auth_req_with_access, run, run_now};
+
+    #[derive(Debug, PartialEq)]
+    struct Entry(String, Option<String>);
+
+    impl ReprC for Entry {
+        type C = *const FfiContact;
+        type Error = AppError;
+
+        unsafe fn clone_from_repr_c(repr_c: *const FfiContact) -> Result<Self, Self::Error> {
+            let public_name = if (*repr_c).public_name.is_null() {
+                None
+            } else {
+                Some(from_c_str((*repr_c).public_name)?)
+            };
+            Ok(Entry(from_c_str((*repr_c).alias)?, public_name))
+        }
+    }
+
+    // Test managing the address book through the FFI.
+    #[test]
+    fn add_get_list_remove() {
+        let mut access = HashMap::new();
+        let _ = access.insert(
+            contacts::CONTACTS_CONTAINER.to_string(),
+            btree_set![
+                Permission::Read,
+                Permission::Insert,
+                Permission::Update,
+                Permission::Delete,
+            ],
+        );
+        let app = create_app_by_req(&create_auth_req_with_access(access));
+
+        let contacts = run(&app, |client, context| {
+            context.get_access_info(client).map(|mut containers| {
+                unwrap!(containers.remove(contacts::CONTACTS_CONTAINER)).0
+            })
+        });
+        let contacts = contacts.into_repr_c();
+
+        let (enc_key, _) = box_::gen_keypair();
+        let enc_key_h = run_now(&app, move |_, context| {
+            context.object_cache().insert_encrypt_key(enc_key)
+        });
+
+        let alice = unwrap!(CString::new("alice"));
+        let bob = unwrap!(CString::new("bob"));
+        let bob_name = unwrap!(CString::new("bobby"));
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                contacts_add(
+                    &app,
+                    &contacts,
+                    alice.as_ptr(),
+                    ptr::null(),
+                    enc_key_h,
+                    NULL_OBJECT_HANDLE,
+                    ud,
+                    cb,
+                )
+            }));
+            unwrap!(call_0(|ud, cb| {
+                contacts_add(
+                    &app,
+                    &contacts,
+                    bob.as_ptr(),
+                    bob_name.as_ptr(),
+                    NULL_OBJECT_HANDLE,
+                    NULL_OBJECT_HANDLE,
+                    ud,
+                    cb,
+                )
+            }));
+        }
+
+        let (enc_key_h, sign_key_h): (EncryptPubKeyHandle, SignPubKeyHandle) = unsafe {
+            unwrap!(call_2(|ud, cb| {
+                contacts_get_keys(&app, &contacts, alice.as_ptr(), ud, cb)
+            }))
+        };
+        assert_eq!(sign_key_h, NULL_OBJECT_HANDLE);
+        let fetched = run_now(&app, move |_, context| {
+            *unwrap!(context.object_cache().get_encrypt_key(enc_key_h))
+        });
+        assert_eq!(fetched, enc_key);
+
+        let entries: Vec<Entry> =
+            unsafe { unwrap!(call_vec(|ud, cb| contacts_list(&app, &contacts, ud, cb))) };
+        assert_eq!(
+            entries,
+            vec![
+                Entry("alice".to_string(), None),
+                Entry("bob".to_string(), Some("bobby".to_string())),
+            ]
+        );
+
+        unsafe {
+            unwrap!(call_0(
+                |ud, cb| contacts_remove(&app, &contacts, alice.as_ptr(), ud, cb),
+            ))
+        };
+        let res: Result<(EncryptPubKeyHandle, SignPubKeyHandle), _> = unsafe {
+            call_2(|ud, cb| {
+                contacts_get_keys(&app, &contacts, alice.as_ptr(), ud, cb)
+            })
+        };
+        match res {
+            Err(ERR_NO_SUCH_ENTRY) => (),
+            Err(x) => panic!("Unexpected error {:?}", x),
+            Ok(_) => panic!("Unexpected success"),
+        }
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb};
+use futures::Future;
+use safe_core::FutureExt;
+use safe_core::cost::{self, Operation};
+use std::os::raw::c_void;
+
+/// Operations whose cost can be estimated.
+#[repr(C)]
+pub enum MutationOp {
+    /// Storing data as `ImmutableData`.
+    PutIData,
+    /// Writing a file and inserting it into a directory.
+    FileUpload,
+    /// Creating a directory without linking it anywhere.
+    CreateDir,
+    /// Creating a directory linked into its parent.
+    CreateSubDir,
+    /// Putting a new `MutableData`.
+    PutMData,
+    /// Mutating the entries of a `MutableData` in one request.
+    MutateMDataEntries,
+}
+
+/// Estimates the number of mutations the operation will consume from the account
+/// balance. `size` is the size of the content in bytes for `PutIData` and `FileUpload`,
+/// and is ignored otherwise.
+///
+/// Callback parameters: user data, error code, number of mutations
+#[no_mangle]
+pub unsafe extern "C" fn estimate_mutations(
+    op: MutationOp,
+    size: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, mutations: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
+        let mutations = cost::estimate_mutations(&operation(op, size));
+        o_cb(user_data, FFI_RESULT_OK, mutations);
+        Ok(())
+    })
+}
+
+/// Checks the account owning the app has at least `mutations` mutations available.
+/// Fails with `ERR_LOW_BALANCE` otherwise.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_check_balance(
+    app: *const App,
+    mutations: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            cost::check_balance(client, mutations)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+fn operation(op: MutationOp, size: u64) -> Operation {
+    match op {
+        MutationOp::PutIData => Operation::PutIData { size: size },
+        MutationOp::FileUpload => Operation::FileUpload { size: size },
+        MutationOp::CreateDir => Operation::CreateDir,
+        MutationOp::CreateSubDir => Operation::CreateSubDir,
+        MutationOp::PutMData => Operation::PutMData,
+        MutationOp::MutateMDataEntries => Operation::MutateMDataEntries,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{call_0, call_1};
+    use safe_core::ffi::error_codes::ERR_LOW_BALANCE;
+    use test_utils::create_app;
+
+    // Test estimating the cost of operations and checking the balance against it.
+    #[test]
+    fn estimate_and_check() {
+        let mutations: u64 = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                estimate_mutations(MutationOp::FileUpload, 10_000, ud, cb)
+            }))
+        };
+        assert_eq!(mutations, 5);
+
+        let mutations: u64 = unsafe {
+            unwrap!(call_1(
+                |ud, cb| estimate_mutations(MutationOp::CreateSubDir, 0, ud, cb),
+            ))
+        };
+        assert_eq!(mutations, 3);
+
+        let app = create_app();
+        unsafe {
+            unwrap!(call_0(|ud, cb| app_check_balance(&app, mutations, ud, cb)));
+
+            match call_0(|ud, cb| app_check_balance(&app, u64::max_value(), ud, cb)) {
+                Err(ERR_LOW_BALANCE) => (),
+                x => panic!("Unexpected {:?}", x),
+            }
+        }
+    }
+}
@@ -17,16 +17,19 @@
 
 use App;
 use errors::AppError;
-use ffi::helper::send_sync;
+use ffi::helper::{send_sync, send_with_user_data};
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb,
                 vec_clone_from_raw_parts};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use object_cache::{EncryptPubKeyHandle, EncryptSecKeyHandle, NULL_OBJECT_HANDLE, SignPubKeyHandle,
                    SignSecKeyHandle};
 use rust_sodium::crypto::{box_, sealedbox, sign};
+use safe_core::KeyOp;
 use safe_core::crypto::{shared_box, shared_sign};
-use safe_core::ffi::arrays::{AsymNonce, AsymPublicKey, AsymSecretKey, SignPublicKey, SignSecretKey};
+use safe_core::ffi::arrays::{AsymNonce, AsymPublicKey, AsymSecretKey, Signature, SignPublicKey,
+                             SignSecretKey, XorNameArray};
 use std::os::raw::c_void;
+use std::ptr;
 use std::slice;
 use tiny_keccak::sha3_256;
 
@@ -69,7 +72,7 @@ pub unsafe extern "C" fn sign_generate_key_pair(
         let (ourpk, oursk) = shared_sign::gen_keypair();
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk_h = context.object_cache().insert_pub_sign_key(ourpk);
             let sk_h = context.object_cache().insert_sec_sign_key(oursk);
 
@@ -231,7 +234,7 @@ pub unsafe extern "C" fn enc_generate_key_pair(
         let (ourpk, oursk) = shared_box::gen_keypair();
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk_h = context.object_cache().insert_encrypt_key(ourpk);
             let sk_h = context.object_cache().insert_secret_key(oursk);
 
@@ -378,8 +381,9 @@ pub unsafe extern "C" fn sign(
         let user_data = OpaqueCtx(user_data);
         let plaintext = vec_clone_from_raw_parts(data, len);
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let sign_sk = if sign_sk_h == SIGN_WITH_APP {
+                client.record_key_usage(KeyOp::Sign, None);
                 try_cb!(
                     client.secret_signing_key().map_err(AppError::from),
                     user_data,
@@ -427,7 +431,7 @@ pub unsafe extern "C" fn verify(
         let user_data = OpaqueCtx(user_data);
         let signed = vec_clone_from_raw_parts(signed_data, len);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let sign_pk = try_cb!(
                 context.object_cache().get_pub_sign_key(sign_pk_h),
                 user_data,
@@ -449,6 +453,76 @@ pub unsafe extern "C" fn verify(
     })
 }
 
+/// Creates a detached signature of arbitrary data using the app's own secret sign key.
+///
+/// Callback parameters: user data, error code, signature
+#[no_mangle]
+pub unsafe extern "C" fn app_sign(
+    app: *const App,
+    data: *const u8,
+    len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        signature: *const Signature),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let data = vec_clone_from_raw_parts(data, len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            client.record_key_usage(KeyOp::Sign, None);
+            let sign_sk = try_cb!(
+                client.secret_signing_key().map_err(AppError::from),
+                user_data,
+                o_cb
+            );
+
+            let signature = sign::sign_detached(&data, &sign_sk);
+            o_cb(user_data.0, FFI_RESULT_OK, &signature.0);
+
+            None
+        })
+    })
+}
+
+/// Verifies a detached signature created by `app_sign` using a given public sign key.
+/// Returns an error if the signature is not valid.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_verify(
+    app: *const App,
+    data: *const u8,
+    len: usize,
+    signature: *const Signature,
+    sign_pk_h: SignPubKeyHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let data = vec_clone_from_raw_parts(data, len);
+        let signature = sign::Signature(*signature);
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let sign_pk = try_cb!(
+                context.object_cache().get_pub_sign_key(sign_pk_h),
+                user_data,
+                o_cb
+            );
+
+            if sign::verify_detached(&signature, &data, &sign_pk) {
+                o_cb(user_data.0, FFI_RESULT_OK);
+            } else {
+                call_result_cb!(Err::<(), _>(AppError::EncodeDecodeError), user_data, o_cb);
+            }
+
+            None
+        })
+    })
+}
+
 /// Encrypts arbitrary data using a given key pair.
 /// You should provide a recipient's public key and a sender's secret key.
 ///
@@ -470,7 +544,7 @@ pub unsafe extern "C" fn encrypt(
         let user_data = OpaqueCtx(user_data);
         let plaintext = vec_clone_from_raw_parts(data, len);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk = try_cb!(
                 context.object_cache().get_encrypt_key(pk_h),
                 user_data,
@@ -515,7 +589,7 @@ pub unsafe extern "C" fn decrypt(
         let user_data = OpaqueCtx(user_data);
         let encrypted_text = vec_clone_from_raw_parts(data, len);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk = try_cb!(
                 context.object_cache().get_encrypt_key(pk_h),
                 user_data,
@@ -545,6 +619,111 @@ pub unsafe extern "C" fn decrypt(
     })
 }
 
+/// Encrypts arbitrary data for a recipient using the app's own secret encryption key.
+/// The app's public key is included in the result, so the recipient can decrypt it
+/// with `app_decrypt` without knowing the sender in advance.
+///
+/// Callback parameters: user data, error code, ciphertext vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn app_encrypt(
+    app: *const App,
+    data: *const u8,
+    len: usize,
+    recipient_pk_h: EncryptPubKeyHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        ciphertext: *const u8,
+                        ciphertext_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let plaintext = vec_clone_from_raw_parts(data, len);
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let recipient_pk = *try_cb!(
+                context.object_cache().get_encrypt_key(recipient_pk_h),
+                user_data,
+                o_cb
+            );
+            let app_pk = try_cb!(
+                client.public_encryption_key().map_err(AppError::from),
+                user_data,
+                o_cb
+            );
+            let app_sk = try_cb!(
+                client.secret_encryption_key().map_err(AppError::from),
+                user_data,
+                o_cb
+            );
+
+            let nonce = box_::gen_nonce();
+            let ciphertext = box_::seal(&plaintext, &nonce, &recipient_pk, &app_sk);
+
+            match serialise(&(app_pk, nonce, ciphertext)) {
+                Ok(result) => o_cb(user_data.0, FFI_RESULT_OK, result.as_ptr(), result.len()),
+                res @ Err(..) => {
+                    call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
+                }
+            }
+
+            None
+        })
+    })
+}
+
+/// Decrypts data produced by `app_encrypt` using the app's own secret encryption key.
+/// Returns an error if the data wasn't encrypted for this app.
+///
+/// Callback parameters: user data, error code, plaintext vector, vector size,
+/// sender's public encryption key
+#[no_mangle]
+pub unsafe extern "C" fn app_decrypt(
+    app: *const App,
+    data: *const u8,
+    len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        plaintext: *const u8,
+                        plaintext_len: usize,
+                        sender_pk: *const AsymPublicKey),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let encrypted_text = vec_clone_from_raw_parts(data, len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            client.record_key_usage(KeyOp::Decrypt, None);
+            let app_sk = try_cb!(
+                client.secret_encryption_key().map_err(AppError::from),
+                user_data,
+                o_cb
+            );
+
+            let (sender_pk, nonce, ciphertext) = try_cb!(
+                deserialise::<(box_::PublicKey, box_::Nonce, Vec<u8>)>(&encrypted_text)
+                    .map_err(AppError::from),
+                user_data,
+                o_cb
+            );
+            let plaintext =
+                try_cb!(box_::open(&ciphertext, &nonce, &sender_pk, &app_sk)
+                        .map_err(|()| AppError::EncodeDecodeError), user_data, o_cb);
+
+            o_cb(
+                user_data.0,
+                FFI_RESULT_OK,
+                plaintext.as_ptr(),
+                plaintext.len(),
+                &sender_pk.0,
+            );
+
+            None
+        })
+    })
+}
+
 /// Encrypts arbitrary data for a single recipient.
 /// You should provide a recipient's public key.
 ///
@@ -565,7 +744,7 @@ pub unsafe extern "C" fn encrypt_sealed_box(
         let plaintext = vec_clone_from_raw_parts(data, len);
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk = *try_cb!(
                 context.object_cache().get_encrypt_key(pk_h),
                 user_data,
@@ -606,7 +785,7 @@ pub unsafe extern "C" fn decrypt_sealed_box(
         let user_data = OpaqueCtx(user_data);
         let plaintext = vec_clone_from_raw_parts(data, len);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let pk = try_cb!(
                 context.object_cache().get_encrypt_key(pk_h),
                 user_data,
@@ -670,12 +849,48 @@ pub unsafe extern "C" fn generate_nonce(
     });
 }
 
+/// Set the function to call each time one of the app's secret keys is used to sign or
+/// decrypt, which includes signing each mutation request the app sends. It's called on the
+/// app's event loop with `hook_data`, which must stay valid until the hook is replaced or the
+/// app is freed, the operation (0 for signing, 1 for decryption) and the name of the data
+/// involved, or null if it wasn't network data. Pass a null hook to remove it.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_set_key_usage_hook(
+    app: *const App,
+    hook_data: *mut c_void,
+    o_hook: Option<extern "C" fn(hook_data: *mut c_void, op: u32, data_id: *const XorNameArray)>,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let hook_data = OpaqueCtx(hook_data);
+
+    catch_unwind_cb(user_data, o_cb, || {
+        send_sync(app, user_data, o_cb, move |client, _| {
+            match o_hook {
+                Some(o_hook) => {
+                    client.set_key_usage_hook(move |op, data_id| {
+                        let data_id = data_id.as_ref().map_or(ptr::null(), |name| {
+                            &name.0 as *const XorNameArray
+                        });
+                        o_hook(hook_data.0, op as u32, data_id)
+                    })
+                }
+                None => client.remove_key_usage_hook(),
+            }
+            Ok(())
+        })
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ffi_utils::test_utils::{call_0, call_1, call_2, call_vec_u8};
     use rust_sodium::crypto::box_;
-    use safe_core::arrays::{AsymNonce, AsymPublicKey, SignPublicKey, SignSecretKey};
+    use safe_core::arrays::{AsymNonce, AsymPublicKey, Signature, SignPublicKey, SignSecretKey};
+    use std::sync::Mutex;
     use test_utils::{create_app, run_now};
 
     // Test signing and verifying messages between apps.
@@ -735,6 +950,40 @@ mod tests {
         assert_eq!(&verified, data);
     }
 
+    // Test creating and verifying detached signatures with the app's sign key.
+    #[test]
+    fn app_sign_verify() {
+        let app1 = create_app();
+        let app2 = create_app();
+
+        let data = b"hi there";
+        let signature: Signature =
+            unsafe { unwrap!(call_1(|ud, cb| app_sign(&app1, data.as_ptr(), data.len(), ud, cb))) };
+
+        // Copying app1 pubkey to app2 object cache
+        let app1_pk_h = unsafe { unwrap!(call_1(|ud, cb| app_pub_sign_key(&app1, ud, cb))) };
+        let pk_raw: SignPublicKey =
+            unsafe { unwrap!(call_1(|ud, cb| sign_pub_key_get(&app1, app1_pk_h, ud, cb))) };
+        let app2_pk_h =
+            unsafe { unwrap!(call_1(|ud, cb| sign_pub_key_new(&app2, &pk_raw, ud, cb))) };
+
+        // Verifying the signature in app2
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                app_verify(&app2, data.as_ptr(), data.len(), &signature, app2_pk_h, ud, cb)
+            }))
+        };
+
+        // A signature over different data must not verify
+        let other = b"hi there!";
+        let res = unsafe {
+            call_0(|ud, cb| {
+                app_verify(&app2, other.as_ptr(), other.len(), &signature, app2_pk_h, ud, cb)
+            })
+        };
+        assert!(res.is_err());
+    }
+
     // Test encrypting and decrypting messages between apps.
     #[test]
     fn encrypt_decrypt() {
@@ -792,6 +1041,82 @@ mod tests {
         assert_eq!(&decrypted, data);
     }
 
+    // Test encrypting and decrypting messages with the apps' own keys.
+    #[test]
+    fn app_encrypt_decrypt() {
+        use ffi_utils::test_utils::{UserData, send_via_user_data, sender_as_user_data};
+        use std::sync::mpsc;
+
+        let app1 = create_app();
+        let app2 = create_app();
+
+        // Copying app2 pubkey to app1 object cache
+        let app2_pk_h = unsafe { unwrap!(call_1(|ud, cb| app_pub_enc_key(&app2, ud, cb))) };
+        let app2_pk: AsymPublicKey =
+            unsafe { unwrap!(call_1(|ud, cb| enc_pub_key_get(&app2, app2_pk_h, ud, cb))) };
+        let app1_pk2_h =
+            unsafe { unwrap!(call_1(|ud, cb| enc_pub_key_new(&app1, &app2_pk, ud, cb))) };
+
+        let app1_pk_h = unsafe { unwrap!(call_1(|ud, cb| app_pub_enc_key(&app1, ud, cb))) };
+        let app1_pk: AsymPublicKey =
+            unsafe { unwrap!(call_1(|ud, cb| enc_pub_key_get(&app1, app1_pk_h, ud, cb))) };
+
+        // Trying to encrypt a message for app2 from app1
+        let data = b"hi there";
+        let encrypted = unsafe {
+            unwrap!(call_vec_u8(|ud, cb| {
+                app_encrypt(&app1, data.as_ptr(), data.len(), app1_pk2_h, ud, cb)
+            }))
+        };
+
+        // Trying to decrypt the message in app2, which should also reveal the sender
+        let (tx, rx) = mpsc::channel::<Result<(Vec<u8>, AsymPublicKey), i32>>();
+        let mut ud = UserData::default();
+        unsafe {
+            app_decrypt(
+                &app2,
+                encrypted.as_ptr(),
+                encrypted.len(),
+                sender_as_user_data(&tx, &mut ud),
+                decrypt_cb,
+            )
+        };
+        let (decrypted, sender_pk) = unwrap!(unwrap!(rx.recv()));
+        assert_eq!(&decrypted, data);
+        assert_eq!(sender_pk, app1_pk);
+
+        // app1 can't decrypt the message, as it's not meant for it
+        let (tx, rx) = mpsc::channel::<Result<(Vec<u8>, AsymPublicKey), i32>>();
+        let mut ud = UserData::default();
+        unsafe {
+            app_decrypt(
+                &app1,
+                encrypted.as_ptr(),
+                encrypted.len(),
+                sender_as_user_data(&tx, &mut ud),
+                decrypt_cb,
+            )
+        };
+        assert!(unwrap!(rx.recv()).is_err());
+
+        extern "C" fn decrypt_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            plaintext: *const u8,
+            plaintext_len: usize,
+            sender_pk: *const AsymPublicKey,
+        ) {
+            unsafe {
+                let result = if (*res).error_code == 0 {
+                    Ok((slice::from_raw_parts(plaintext, plaintext_len).to_vec(), *sender_pk))
+                } else {
+                    Err((*res).error_code)
+                };
+                send_via_user_data(user_data, result);
+            }
+        }
+    }
+
     // Test encrypting and decrypting sealed box messages between apps.
     #[test]
     fn encrypt_decrypt_sealed() {
@@ -1035,4 +1360,39 @@ mod tests {
 
         assert_eq!(sha3.len(), 256 / 8);
     }
+
+    // Test that signing with the app's key is reported to the key usage hook until it's
+    // removed.
+    #[test]
+    fn key_usage_hook() {
+        extern "C" fn record(hook_data: *mut c_void, op: u32, data_id: *const XorNameArray) {
+            let uses = unsafe { &*(hook_data as *const Mutex<Vec<(u32, bool)>>) };
+            unwrap!(uses.lock()).push((op, data_id.is_null()));
+        }
+
+        let app = create_app();
+        let uses = Mutex::new(Vec::new());
+        let hook_data = &uses as *const _ as *mut c_void;
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                app_set_key_usage_hook(&app, hook_data, Some(record), ud, cb)
+            }))
+        };
+
+        let data = b"hi there";
+        let _: Signature =
+            unsafe { unwrap!(call_1(|ud, cb| app_sign(&app, data.as_ptr(), data.len(), ud, cb))) };
+        assert_eq!(*unwrap!(uses.lock()), vec![(KeyOp::Sign as u32, true)]);
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                app_set_key_usage_hook(&app, hook_data, None, ud, cb)
+            }))
+        };
+
+        let _: Signature =
+            unsafe { unwrap!(call_1(|ud, cb| app_sign(&app, data.as_ptr(), data.len(), ud, cb))) };
+        assert_eq!(unwrap!(uses.lock()).len(), 1);
+    }
 }
@@ -0,0 +1,105 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str};
+use futures::Future;
+use safe_core::{FutureExt, dns};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use std::os::raw::{c_char, c_void};
+
+/// Resolve a `public_name/service` path (e.g. `myname/blog`) to the public
+/// directory serving it. The service part may be omitted, in which case `www`
+/// is resolved. Works with unregistered apps too.
+///
+/// Callback parameters: user data, error code, mdata info
+#[no_mangle]
+pub unsafe extern "C" fn dns_resolve(
+    app: *const App,
+    path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        mdata_info: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let path = from_c_str(path)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            dns::resolve_path(client, &path)
+                .map(move |info| {
+                    let info = info.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &info);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ERR_NO_SUCH_ENTRY;
+    use ffi_utils::test_utils::call_1;
+    use safe_authenticator::AuthError;
+    use safe_authenticator::test_utils as authenticator;
+    use safe_core::{DIR_TAG, MDataInfo};
+    use safe_core::utils::generate_random_string;
+    use std::ffi::CString;
+    use test_utils::create_app;
+
+    // Test resolving public names through the FFI.
+    #[test]
+    fn resolve() {
+        let auth = authenticator::create_account_and_login();
+        let public_name = unwrap!(generate_random_string(10));
+        let target = unwrap!(MDataInfo::random_public(DIR_TAG));
+
+        let name = public_name.clone();
+        let target2 = target.clone();
+        authenticator::run(&auth, move |client| {
+            let c2 = client.clone();
+            dns::register(client, &name)
+                .and_then(move |_| dns::set_service(&c2, &name, "blog", &target2))
+                .map_err(AuthError::from)
+        });
+
+        let app = create_app();
+
+        let path = unwrap!(CString::new(format!("{}/blog", public_name)));
+        let info: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| dns_resolve(&app, path.as_ptr(), ud, cb))) };
+        assert_eq!(info.name, target.name);
+        assert_eq!(info.type_tag, target.type_tag);
+
+        // `www` hasn't been attached.
+        let path = unwrap!(CString::new(public_name));
+        let res: Result<MDataInfo, _> =
+            unsafe { call_1(|ud, cb| dns_resolve(&app, path.as_ptr(), ud, cb)) };
+        match res {
+            Err(ERR_NO_SUCH_ENTRY) => (),
+            Err(x) => panic!("Unexpected error {:?}", x),
+            Ok(_) => panic!("Unexpected success"),
+        }
+    }
+}
@@ -18,12 +18,15 @@
 use App;
 use AppContext;
 use errors::AppError;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx};
+use ffi::UserDataDestructor;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ptr_as_ref};
 use ffi_utils::callback::Callback;
 use futures::Future;
 use safe_core::{Client, FutureExt};
 use std::fmt::Debug;
 use std::os::raw::c_void;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 // Convenience wrapper around `App::send` which automatically handles the callback
 // boilerplate.
@@ -40,7 +43,7 @@ where
 {
     let user_data = OpaqueCtx(user_data);
 
-    (*app).send(move |client, context| {
+    send_with_user_data(app, user_data, move |client, context| {
         match f(client, context) {
             Ok(args) => o_cb.call(user_data.0, FFI_RESULT_OK, args),
             res @ Err(..) => {
@@ -68,7 +71,7 @@ where
 {
     let user_data = OpaqueCtx(user_data);
 
-    (*app).send(move |client, context| {
+    send_with_user_data(app, user_data, move |client, context| {
         f(client, context)
             .map(move |args| o_cb.call(user_data.0, FFI_RESULT_OK, args))
             .map_err(AppError::from)
@@ -79,3 +82,74 @@ where
             .into()
     })
 }
+
+// Wrapper around `App::send` for operations reporting to a callback with the given
+// user data. If the app has a user data destructor, it's invoked with the user data
+// when the operation gets dropped before completing, so the callback is never called.
+pub unsafe fn send_with_user_data<F>(
+    app: *const App,
+    user_data: OpaqueCtx,
+    f: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(&Client<AppContext>, &AppContext) -> Option<Box<Future<Item = (), Error = ()>>>
+        + Send
+        + 'static,
+{
+    let app = ptr_as_ref(app)?;
+    let guard = Arc::new(UserDataGuard {
+        user_data,
+        destructor: app.user_data_destructor(),
+        completed: AtomicBool::new(false),
+    });
+    let guard2 = Arc::clone(&guard);
+
+    let res = app.send(move |client, context| match f(client, context) {
+        Some(fut) => Some(
+            fut.then(move |res| {
+                guard2.complete();
+                res
+            }).into_box(),
+        ),
+        None => {
+            guard2.complete();
+            None
+        }
+    });
+
+    // The callback is going to be called with the error.
+    if res.is_err() {
+        guard.complete();
+    }
+
+    res
+}
+
+// Invokes the destructor once the last reference to it is dropped, unless the
+// operation completed.
+struct UserDataGuard {
+    user_data: OpaqueCtx,
+    destructor: Option<UserDataDestructor>,
+    completed: AtomicBool,
+}
+
+// The user data pointer is never dereferenced, only handed back to the host.
+unsafe impl Sync for UserDataGuard {}
+
+impl UserDataGuard {
+    fn complete(&self) {
+        self.completed.store(true, Ordering::SeqCst);
+    }
+}
+
+impl Drop for UserDataGuard {
+    fn drop(&mut self) {
+        if self.completed.load(Ordering::SeqCst) {
+            return;
+        }
+
+        if let Some(destructor) = self.destructor {
+            destructor(self.user_data.0);
+        }
+    }
+}
@@ -0,0 +1,81 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use super::AppError;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, catch_unwind_cb};
+use safe_core::host_routing;
+use std::os::raw::c_void;
+use std::slice;
+
+/// Registers the transport used by apps built with the `use-host-routing` feature.
+/// Has to be called before the app is registered or connected unregistered,
+/// otherwise connecting fails.
+///
+/// `o_send` is called from the app's thread with a serialised `HostRequest` which
+/// the host has to get to the network. The message is only valid for the duration
+/// of the call. Responses are passed back through `app_deliver_host_response`.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_set_host_transport(
+    user_data: *mut c_void,
+    o_send: extern "C" fn(user_data: *mut c_void, message: *const u8, message_len: usize),
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
+        host_routing::set_transport(user_data, o_send);
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
+
+/// Delivers a serialised `HostResponse` received from the network to the app which
+/// sent the request.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_deliver_host_response(
+    message: *const u8,
+    message_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
+        let message = slice::from_raw_parts(message, message_len);
+        host_routing::deliver(message)?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
+
+/// Tells the app with the given client ID (as found in its `HostRequest`s) that the
+/// host has lost the connection to the network. The app's disconnect notifier is
+/// called in response.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_host_disconnected(
+    client_id: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
+        host_routing::disconnect(client_id);
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
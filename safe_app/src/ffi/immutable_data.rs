@@ -18,7 +18,9 @@
 use super::cipher_opt::CipherOpt;
 use App;
 use errors::AppError;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, vec_clone_from_raw_parts};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{BorrowedSlice, FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb,
+                vec_clone_from_raw_parts};
 use futures::Future;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use object_cache::{CipherOptHandle, SelfEncryptorReaderHandle, SelfEncryptorWriterHandle};
@@ -27,12 +29,22 @@ use safe_core::{FutureExt, SelfEncryptionStorage, immutable_data};
 use safe_core::ffi::arrays::XorNameArray;
 use self_encryption::{SelfEncryptor, SequentialEncryptor};
 use std::os::raw::c_void;
+use std::slice;
 
 /// Handle of a Self Encryptor Writer object
 pub type SEWriterHandle = SelfEncryptorWriterHandle;
 /// Handle of a Self Encryptor Reader object
 pub type SEReaderHandle = SelfEncryptorReaderHandle;
 
+/// FFI object representing the content of an `ImmutableData`.
+#[repr(C)]
+pub struct IDataContent {
+    /// Content.
+    pub content: *const u8,
+    /// Content length.
+    pub content_len: usize,
+}
+
 /// Get a Self Encryptor.
 ///
 /// Callback parameters: user data, error code, SE handle
@@ -47,7 +59,7 @@ pub unsafe extern "C" fn idata_new_self_encryptor(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let se_storage = SelfEncryptionStorage::new(client.clone());
             let context = context.clone();
 
@@ -84,7 +96,7 @@ pub unsafe extern "C" fn idata_write_to_self_encryptor(
     catch_unwind_cb(user_data, o_cb, || {
         let data_slice = vec_clone_from_raw_parts(data, data_len);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let fut = {
                 match context.object_cache().get_se_writer(se_h) {
                     Ok(writer) => writer.write(&data_slice),
@@ -105,6 +117,46 @@ pub unsafe extern "C" fn idata_write_to_self_encryptor(
     });
 }
 
+/// Write to Self Encryptor, like `idata_write_to_self_encryptor`, but without
+/// copying the data. The caller has to keep `data` valid and unchanged until
+/// `o_cb` is called.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn idata_write_to_self_encryptor_borrowed(
+    app: *const App,
+    se_h: SEWriterHandle,
+    data: *const u8,
+    data_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || {
+        let data = BorrowedSlice::new(data, data_len);
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let fut = {
+                match context.object_cache().get_se_writer(se_h) {
+                    Ok(writer) => writer.write(data.as_slice()),
+                    res @ Err(..) => {
+                        call_result_cb!(res, user_data, o_cb);
+                        return None;
+                    }
+                }
+            };
+            let fut = fut.map_err(AppError::from)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box();
+            Some(fut)
+        })
+    });
+}
+
 /// Close Self Encryptor and free the Self Encryptor Writer handle.
 ///
 /// Callback parameters: user data, error code, xor name
@@ -121,7 +173,7 @@ pub unsafe extern "C" fn idata_close_self_encryptor(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let client2 = client.clone();
             let client3 = client.clone();
             let context2 = context.clone();
@@ -185,7 +237,7 @@ pub unsafe extern "C" fn idata_fetch_self_encryptor(
         let user_data = OpaqueCtx(user_data);
         let name = XorName(*name);
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let client2 = client.clone();
             let client3 = client.clone();
             let context2 = context.clone();
@@ -233,7 +285,7 @@ pub unsafe extern "C" fn idata_serialised_size(
     catch_unwind_cb(user_data, o_cb, || {
         let name = XorName(*name);
 
-        (*app).send(move |client, _| {
+        send_with_user_data(app, user_data, move |client, _| {
             client
                 .get_idata(name)
                 .map(move |idata| {
@@ -248,6 +300,58 @@ pub unsafe extern "C" fn idata_serialised_size(
     });
 }
 
+/// Get the raw content of several `ImmutableData` at once. The data are fetched
+/// concurrently and returned in the order of `names`.
+///
+/// Callback parameters: user data, error code, contents vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn idata_get_many(
+    app: *const App,
+    names: *const XorNameArray,
+    names_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        contents: *const IDataContent,
+                        contents_len: usize),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || {
+        let names: Vec<_> = slice::from_raw_parts(names, names_len)
+            .iter()
+            .map(|name| XorName(*name))
+            .collect();
+
+        send_with_user_data(app, user_data, move |client, _| {
+            client
+                .get_idata_many(names)
+                .map(move |data| {
+                    let contents: Vec<_> = data.iter()
+                        .map(|idata| {
+                            IDataContent {
+                                content: idata.value().as_safe_ptr(),
+                                content_len: idata.value().len(),
+                            }
+                        })
+                        .collect();
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        contents.as_safe_ptr(),
+                        contents.len(),
+                    );
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(AppError::from(e)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    });
+}
+
 /// Get data size from Self Encryptor.
 ///
 /// Callback parameters: user data, error code, size
@@ -261,7 +365,7 @@ pub unsafe extern "C" fn idata_size(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             match context.object_cache().get_se_reader(se_h) {
                 Ok(se) => {
                     o_cb(user_data.0, FFI_RESULT_OK, se.len());
@@ -293,7 +397,7 @@ pub unsafe extern "C" fn idata_read_from_self_encryptor(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let se = match context.object_cache().get_se_reader(se_h) {
                 Ok(r) => r,
                 res @ Err(..) => {
@@ -339,7 +443,7 @@ pub unsafe extern "C" fn idata_self_encryptor_writer_free(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let res = context.object_cache().remove_se_writer(handle);
             call_result_cb!(res, user_data, o_cb);
             None
@@ -360,7 +464,7 @@ pub unsafe extern "C" fn idata_self_encryptor_reader_free(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let res = context.object_cache().remove_se_reader(handle);
             call_result_cb!(res, user_data, o_cb);
             None
@@ -374,9 +478,13 @@ mod tests {
     use errors::AppError;
     use ffi::cipher_opt::*;
     use ffi_utils::ErrorCode;
-    use ffi_utils::test_utils::{call_0, call_1, call_vec_u8};
+    use ffi_utils::test_utils::{call_0, call_1, call_vec_u8, send_via_user_data,
+                                sender_as_user_data};
+    use futures::future;
+    use routing::ImmutableData;
     use safe_core::utils;
-    use test_utils::create_app;
+    use std::sync::mpsc;
+    use test_utils::{create_app, run};
 
     // Test immutable data operations.
     #[test]
@@ -471,4 +579,69 @@ mod tests {
             unwrap!(call_0(|ud, cb| cipher_opt_free(&app, cipher_opt_h, ud, cb)));
         }
     }
+
+    // Test fetching several immutable data at once.
+    #[test]
+    fn get_many() {
+        let app = create_app();
+
+        let data: Vec<_> = (0..3)
+            .map(|_| ImmutableData::new(unwrap!(utils::generate_random_vector::<u8>(100))))
+            .collect();
+        let data2 = data.clone();
+        run(&app, move |client, _| {
+            let puts: Vec<_> = data2
+                .into_iter()
+                .map(|idata| client.put_idata(idata))
+                .collect();
+            future::join_all(puts).map(|_| ()).map_err(AppError::from)
+        });
+
+        // Reverse the order to check it's preserved.
+        let names: Vec<XorNameArray> = data.iter().rev().map(|idata| idata.name().0).collect();
+        let contents = unsafe { get_many_contents(&app, &names) };
+        let expected: Vec<_> = data.iter().rev().map(|idata| idata.value().clone()).collect();
+        assert_eq!(contents, Ok(expected));
+
+        // Fails when any of the data doesn't exist.
+        let names = [names[0], [0; 32]];
+        assert!(unsafe { get_many_contents(&app, &names) }.is_err());
+    }
+
+    unsafe fn get_many_contents(app: &App, names: &[XorNameArray]) -> Result<Vec<Vec<u8>>, i32> {
+        extern "C" fn get_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            contents: *const IDataContent,
+            contents_len: usize,
+        ) {
+            unsafe {
+                let result: Result<Vec<_>, i32> = if (*res).error_code == 0 {
+                    Ok(
+                        slice::from_raw_parts(contents, contents_len)
+                            .iter()
+                            .map(|content| {
+                                vec_clone_from_raw_parts(content.content, content.content_len)
+                            })
+                            .collect(),
+                    )
+                } else {
+                    Err((*res).error_code)
+                };
+
+                send_via_user_data(user_data, result);
+            }
+        }
+
+        let (tx, rx) = mpsc::channel::<Result<Vec<Vec<u8>>, i32>>();
+        let mut ud = Default::default();
+        idata_get_many(
+            app,
+            names.as_ptr(),
+            names.len(),
+            sender_as_user_data(&tx, &mut ud),
+            get_cb,
+        );
+        unwrap!(rx.recv())
+    }
 }
@@ -0,0 +1,309 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use object_cache::{EncryptPubKeyHandle, EncryptSecKeyHandle};
+use safe_core::{FutureExt, MDataInfo, inbox};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use std::os::raw::c_void;
+
+/// FFI object representing a message in an inbox.
+#[repr(C)]
+pub struct InboxMessage {
+    /// Message ID.
+    pub id: *const u8,
+    /// Message ID length.
+    pub id_len: usize,
+    /// Message content.
+    pub content: *const u8,
+    /// Message content length.
+    pub content_len: usize,
+    /// Time the message was sent at, in seconds since the Unix epoch.
+    pub sent_at: u64,
+}
+
+/// Create a new inbox owned by the app. Anyone can send messages into it,
+/// but only the app can delete them.
+///
+/// Callback parameters: user data, error code, inbox mdata info
+#[no_mangle]
+pub unsafe extern "C" fn inbox_create(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        inbox: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            inbox::create(client)
+                .map(move |inbox| {
+                    let inbox = inbox.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &inbox);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Send a message into the inbox, encrypted for the recipient's public
+/// encryption key.
+///
+/// Callback parameters: user data, error code, message ID, message ID length
+#[no_mangle]
+pub unsafe extern "C" fn inbox_send(
+    app: *const App,
+    inbox: *const FfiMDataInfo,
+    recipient_h: EncryptPubKeyHandle,
+    content: *const u8,
+    content_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        id: *const u8,
+                        id_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let inbox = MDataInfo::clone_from_repr_c(inbox)?;
+        let content = vec_clone_from_raw_parts(content, content_len);
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let recipient = *try_cb!(
+                context.object_cache().get_encrypt_key(recipient_h),
+                user_data,
+                o_cb
+            );
+
+            inbox::send(client, &inbox, &recipient, content)
+                .map(move |id| {
+                    o_cb(user_data.0, FFI_RESULT_OK, id.as_safe_ptr(), id.len());
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// List messages in the inbox which can be decrypted with the given key pair,
+/// oldest first.
+///
+/// Callback parameters: user data, error code, messages vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn inbox_list(
+    app: *const App,
+    inbox: *const FfiMDataInfo,
+    pk_h: EncryptPubKeyHandle,
+    sk_h: EncryptSecKeyHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        messages: *const InboxMessage,
+                        messages_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let inbox = MDataInfo::clone_from_repr_c(inbox)?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let pk = *try_cb!(
+                context.object_cache().get_encrypt_key(pk_h),
+                user_data,
+                o_cb
+            );
+            let sk = try_cb!(context.object_cache().get_secret_key(sk_h), user_data, o_cb)
+                .clone();
+
+            inbox::list(client, &inbox, &pk, &sk)
+                .map(move |messages| {
+                    let ffi_messages: Vec<_> = messages
+                        .iter()
+                        .map(|&(ref id, ref message)| {
+                            InboxMessage {
+                                id: id.as_safe_ptr(),
+                                id_len: id.len(),
+                                content: message.content.as_safe_ptr(),
+                                content_len: message.content.len(),
+                                sent_at: message.sent_at,
+                            }
+                        })
+                        .collect();
+
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        ffi_messages.as_safe_ptr(),
+                        ffi_messages.len(),
+                    );
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Delete the message with the given ID from the inbox.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn inbox_delete(
+    app: *const App,
+    inbox: *const FfiMDataInfo,
+    id: *const u8,
+    id_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let inbox = MDataInfo::clone_from_repr_c(inbox)?;
+        let id = vec_clone_from_raw_parts(id, id_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            inbox::delete(client, &inbox, id)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{call_0, call_1, call_vec_u8, send_via_user_data,
+                                sender_as_user_data};
+    use safe_core::crypto::shared_box;
+    use std::slice;
+    use std::sync::mpsc;
+    use test_utils::{create_app, run_now};
+
+    // Test sending a message from one app to another app's inbox.
+    #[test]
+    fn send_list_delete() {
+        let owner = create_app();
+        let sender = create_app();
+
+        let (pk, sk) = shared_box::gen_keypair();
+        let (pk_h, sk_h) = run_now(&owner, move |_, context| {
+            (
+                context.object_cache().insert_encrypt_key(pk),
+                context.object_cache().insert_secret_key(sk),
+            )
+        });
+        let recipient_h = run_now(&sender, move |_, context| {
+            context.object_cache().insert_encrypt_key(pk)
+        });
+
+        let inbox: MDataInfo = unsafe { unwrap!(call_1(|ud, cb| inbox_create(&owner, ud, cb))) };
+        let inbox = inbox.into_repr_c();
+
+        let id = unsafe {
+            unwrap!(call_vec_u8(|ud, cb| {
+                inbox_send(&sender, &inbox, recipient_h, b"hi".as_ptr(), 2, ud, cb)
+            }))
+        };
+
+        let messages = unsafe { list(&owner, &inbox, pk_h, sk_h) };
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, id);
+        assert_eq!(messages[0].1, b"hi");
+
+        // Only the owner may delete messages.
+        let res = unsafe {
+            call_0(|ud, cb| inbox_delete(&sender, &inbox, id.as_ptr(), id.len(), ud, cb))
+        };
+        assert!(res.is_err());
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                inbox_delete(&owner, &inbox, id.as_ptr(), id.len(), ud, cb)
+            }))
+        };
+
+        let messages = unsafe { list(&owner, &inbox, pk_h, sk_h) };
+        assert!(messages.is_empty());
+    }
+
+    unsafe fn list(
+        app: &App,
+        inbox: &FfiMDataInfo,
+        pk_h: EncryptPubKeyHandle,
+        sk_h: EncryptSecKeyHandle,
+    ) -> Vec<(Vec<u8>, Vec<u8>)> {
+        extern "C" fn list_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            messages: *const InboxMessage,
+            messages_len: usize,
+        ) {
+            unsafe {
+                let result: Result<Vec<_>, i32> = if (*res).error_code == 0 {
+                    Ok(
+                        slice::from_raw_parts(messages, messages_len)
+                            .iter()
+                            .map(|message| {
+                                (
+                                    vec_clone_from_raw_parts(message.id, message.id_len),
+                                    vec_clone_from_raw_parts(
+                                        message.content,
+                                        message.content_len,
+                                    ),
+                                )
+                            })
+                            .collect(),
+                    )
+                } else {
+                    Err((*res).error_code)
+                };
+
+                send_via_user_data(user_data, result);
+            }
+        }
+
+        let (tx, rx) = mpsc::channel::<Result<Vec<(Vec<u8>, Vec<u8>)>, i32>>();
+        let mut ud = Default::default();
+        inbox_list(
+            app,
+            inbox,
+            pk_h,
+            sk_h,
+            sender_as_user_data(&tx, &mut ud),
+            list_cb,
+        );
+        unwrap!(unwrap!(rx.recv()))
+    }
+}
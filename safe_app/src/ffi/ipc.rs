@@ -17,17 +17,24 @@
 
 //! App-related IPC utilities.
 
+use {App, AppContext};
 use errors::AppError;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, ReprC, catch_unwind_cb, from_c_str,
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, catch_unwind_cb, from_c_str,
                 vec_clone_from_raw_parts};
 use maidsafe_utilities::serialisation::serialise;
-use safe_core::ffi::ipc::req::{AuthReq as FfiAuthReq, ContainersReq as FfiContainersReq,
+use safe_core::ffi::ipc::req::{AuthReq as FfiAuthReq, CombinedReq as FfiCombinedReq,
+                               ContainersReq as FfiContainersReq,
                                ShareMDataReq as FfiShareMDataReq};
 use safe_core::ffi::ipc::resp::AuthGranted as FfiAuthGranted;
-use safe_core::ipc::{self, AuthReq, ContainersReq, IpcError, IpcMsg, IpcReq, IpcResp,
-                     ShareMDataReq};
+use safe_core::ipc::{self, AuthReq, CombinedReq, ContainersReq, IpcError, IpcMsg, IpcReq,
+                     IpcResp, ShareMDataReq};
+use safe_core::ipc::socket;
+use safe_core::ipc::uri;
 use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
+use std::thread;
 
 /// Encode `AuthReq`.
 ///
@@ -51,7 +58,9 @@ pub unsafe extern "C" fn encode_auth_req(
     })
 }
 
-/// Encode `ContainersReq`.
+/// Encode `ContainersReq`. The request isn't authenticated with the app's keys, so
+/// authenticators reject it unless they allow legacy requests. Registered apps
+/// should use `app_encode_containers_req` instead.
 ///
 /// Callback parameters: user data, error code, request id, encoded request
 #[no_mangle]
@@ -96,7 +105,9 @@ pub unsafe extern "C" fn encode_unregistered_req(
     })
 }
 
-/// Encode `ShareMDataReq`.
+/// Encode `ShareMDataReq`. The request isn't authenticated with the app's keys, so
+/// authenticators reject it unless they allow legacy requests. Registered apps
+/// should use `app_encode_share_mdata_req` instead.
 ///
 /// Callback parameters: user data, error code, request id, encoded request
 #[no_mangle]
@@ -118,11 +129,104 @@ pub unsafe extern "C" fn encode_share_mdata_req(
     })
 }
 
+/// Encode `CombinedReq`, asking for authorisation, container permissions and
+/// shared access to mutable data in one go. The response is passed to the
+/// `o_auth` callback of `decode_ipc_msg`, like a response to an `AuthReq`.
+///
+/// Callback parameters: user data, error code, request id, encoded request
+#[no_mangle]
+pub unsafe extern "C" fn encode_combined_req(
+    req: *const FfiCombinedReq,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        req_id: u32,
+                        encoded: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let req_id = ipc::gen_req_id();
+        let req = CombinedReq::clone_from_repr_c(req)?;
+
+        let encoded = encode_ipc(req_id, IpcReq::Combined(req))?;
+        o_cb(user_data, FFI_RESULT_OK, req_id, encoded.as_ptr());
+        Ok(())
+    })
+}
+
+/// Encode `ContainersReq` of a registered app, authenticating it with the app's keys.
+///
+/// Callback parameters: user data, error code, request id, encoded request
+#[no_mangle]
+pub unsafe extern "C" fn app_encode_containers_req(
+    app: *const App,
+    req: *const FfiContainersReq,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        req_id: u32,
+                        encoded: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        let req = ContainersReq::clone_from_repr_c(req)?;
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let req_id = ipc::gen_req_id();
+            let encoded = try_cb!(
+                encode_ipc_with_mac(req_id, IpcReq::Containers(req), context),
+                user_data,
+                o_cb
+            );
+            o_cb(user_data.0, FFI_RESULT_OK, req_id, encoded.as_ptr());
+            None
+        })
+    })
+}
+
+/// Encode `ShareMDataReq` of a registered app, authenticating it with the app's keys.
+///
+/// Callback parameters: user data, error code, request id, encoded request
+#[no_mangle]
+pub unsafe extern "C" fn app_encode_share_mdata_req(
+    app: *const App,
+    req: *const FfiShareMDataReq,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        req_id: u32,
+                        encoded: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        let req = ShareMDataReq::clone_from_repr_c(req)?;
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let req_id = ipc::gen_req_id();
+            let encoded = try_cb!(
+                encode_ipc_with_mac(req_id, IpcReq::ShareMData(req), context),
+                user_data,
+                o_cb
+            );
+            o_cb(user_data.0, FFI_RESULT_OK, req_id, encoded.as_ptr());
+            None
+        })
+    })
+}
+
 fn encode_ipc(req_id: u32, req: IpcReq) -> Result<CString, AppError> {
     let encoded = ipc::encode_msg(&IpcMsg::Req { req_id, req })?;
     Ok(CString::new(encoded)?)
 }
 
+fn encode_ipc_with_mac(
+    req_id: u32,
+    req: IpcReq,
+    context: &AppContext,
+) -> Result<CString, AppError> {
+    let encoded = ipc::encode_msg_with_mac(&IpcMsg::Req { req_id, req }, context.sym_enc_key()?)?;
+    Ok(CString::new(encoded)?)
+}
+
 /// Decode IPC message.
 #[no_mangle]
 pub unsafe extern "C" fn decode_ipc_msg(
@@ -150,6 +254,10 @@ pub unsafe extern "C" fn decode_ipc_msg(
             IpcMsg::Resp {
                 resp: IpcResp::Auth(res),
                 req_id,
+            } |
+            IpcMsg::Resp {
+                resp: IpcResp::Combined(res),
+                req_id,
             } => {
                 match res {
                     Ok(auth_granted) => {
@@ -248,24 +356,96 @@ pub unsafe extern "C" fn decode_ipc_msg(
     })
 }
 
+/// Wraps an encoded IPC request into a `safe-auth://` URI to be opened by the
+/// authenticator.
+///
+/// Callback parameters: user data, error code, URI
+#[no_mangle]
+pub unsafe extern "C" fn app_ipc_auth_uri(
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, uri: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let msg = from_c_str(msg)?;
+        let uri = CString::new(uri::to_auth_uri(&msg))?;
+        o_cb(user_data, FFI_RESULT_OK, uri.as_ptr());
+        Ok(())
+    })
+}
+
+/// Extracts the encoded IPC message from a URI received from the authenticator,
+/// ready to be passed to `decode_ipc_msg`.
+///
+/// Callback parameters: user data, error code, encoded message
+#[no_mangle]
+pub unsafe extern "C" fn app_ipc_uri_payload(
+    uri: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, msg: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let uri = from_c_str(uri)?;
+        let (_, payload) = uri::parse_uri(&uri)?;
+        let payload = CString::new(payload)?;
+        o_cb(user_data, FFI_RESULT_OK, payload.as_ptr());
+        Ok(())
+    })
+}
+
+/// Sends an encoded IPC request to the authenticator over a local socket at
+/// `path`, or at the default location if `path` is null, instead of opening a
+/// `safe-auth://` URI. `o_cb` is called from a separate thread once the
+/// authenticator responds, with the encoded response to pass to `decode_ipc_msg`.
+///
+/// Callback parameters: user data, error code, encoded response
+#[no_mangle]
+pub unsafe extern "C" fn app_ipc_socket_send(
+    path: *const c_char,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, resp: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AppError> {
+        let path = if path.is_null() {
+            socket::default_socket_path()?
+        } else {
+            PathBuf::from(from_c_str(path)?)
+        };
+        let msg = from_c_str(msg)?;
+
+        let _ = thread::spawn(move || {
+            catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AppError> {
+                let resp = CString::new(socket::send_msg(&path, &msg)?)?;
+                o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+                Ok(())
+            })
+        });
+
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use ffi_utils::ReprC;
-    use ffi_utils::test_utils::call_2;
+    use ffi_utils::test_utils::{call_1, call_2};
     use rand;
     use routing::{Action, PermissionSet};
     use rust_sodium::crypto::secretbox;
     use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
     use safe_core::ffi::ipc::resp::AuthGranted as FfiAuthGranted;
     use safe_core::ipc::{self, AccessContInfo, AccessContainerEntry, AppKeys, AuthGranted,
-                         AuthReq, BootstrapConfig, ContainersReq, IpcMsg, IpcReq, IpcResp,
-                         Permission, ShareMData, ShareMDataReq};
+                         AuthReq, BootstrapConfig, CombinedReq, ContainersReq, IpcMsg, IpcReq,
+                         IpcResp, Permission, ShareMData, ShareMDataReq};
     use safe_core::utils;
     use std::collections::HashMap;
     use std::ffi::CString;
     use std::os::raw::c_void;
-    use test_utils::gen_app_exchange_info;
+    use test_utils::{create_app, gen_app_exchange_info, run_now};
 
     // Test encoding and decoding authorisation requests.
     #[test]
@@ -330,6 +510,33 @@ mod tests {
         assert_eq!(decoded_req, req);
     }
 
+    // Test that containers requests of registered apps are authenticated with
+    // the app's keys.
+    #[test]
+    fn app_encode_containers_req_basics() {
+        let app = create_app();
+        let enc_key = run_now(&app, |_, context| unwrap!(context.sym_enc_key()).clone());
+
+        let req = ContainersReq {
+            app: gen_app_exchange_info(),
+            containers: HashMap::new(),
+        };
+        let req_c = unwrap!(req.clone().into_repr_c());
+
+        let (req_id, encoded): (u32, String) =
+            unsafe { unwrap!(call_2(|ud, cb| app_encode_containers_req(&app, &req_c, ud, cb))) };
+
+        let decoded = unwrap!(ipc::decode_msg_with_details(&encoded));
+        assert_eq!(
+            decoded.msg,
+            IpcMsg::Req {
+                req_id,
+                req: IpcReq::Containers(req),
+            }
+        );
+        assert!(unwrap!(decoded.mac).verify(&enc_key));
+    }
+
     // Test encoding and decoding unregistered requests.
     #[test]
     fn encode_unregistered_req_basics() {
@@ -391,6 +598,104 @@ mod tests {
         assert_eq!(decoded_req, req);
     }
 
+    // Test encoding and decoding combined requests.
+    #[test]
+    fn encode_combined_req_basics() {
+        let mut containers = HashMap::new();
+        let _ = containers.insert("_public".to_owned(), btree_set![Permission::Read]);
+
+        let req = CombinedReq {
+            auth: AuthReq {
+                app: gen_app_exchange_info(),
+                app_container: true,
+                containers: containers,
+            },
+            mdata: vec![
+                ShareMData {
+                    type_tag: rand::random(),
+                    name: rand::random(),
+                    perms: PermissionSet::new().allow(Action::Insert),
+                },
+            ],
+        };
+
+        let req_c = unwrap!(req.clone().into_repr_c());
+
+        let (req_id, encoded): (u32, String) =
+            unsafe { unwrap!(call_2(|ud, cb| encode_combined_req(&req_c, ud, cb))) };
+
+        // Decode it and verify it's the same we encoded.
+        let msg = unwrap!(ipc::decode_msg(&encoded));
+
+        let (decoded_req_id, decoded_req) = match msg {
+            IpcMsg::Req {
+                req_id,
+                req: IpcReq::Combined(req),
+            } => (req_id, req),
+            x => panic!("Unexpected {:?}", x),
+        };
+
+        assert_eq!(decoded_req_id, req_id);
+        assert_eq!(decoded_req, req);
+    }
+
+    // Test wrapping an encoded request into a URI and extracting it back.
+    #[test]
+    fn ipc_uri() {
+        let msg = IpcMsg::Revoked { app_id: "net.maidsafe.test".to_owned() };
+        let encoded = unwrap!(ipc::encode_msg(&msg));
+        let encoded_c = unwrap!(CString::new(encoded.clone()));
+
+        let uri: String = unsafe {
+            unwrap!(call_1(
+                |ud, cb| app_ipc_auth_uri(encoded_c.as_ptr(), ud, cb),
+            ))
+        };
+        assert_eq!(uri, format!("safe-auth://{}", encoded));
+
+        let uri = unwrap!(uri::encode_app_uri("net.maidsafe.test", &msg));
+        let uri = unwrap!(CString::new(uri));
+        let payload: String = unsafe {
+            unwrap!(call_1(|ud, cb| app_ipc_uri_payload(uri.as_ptr(), ud, cb)))
+        };
+        assert_eq!(unwrap!(ipc::decode_msg(&payload)), msg);
+    }
+
+    // Test sending a request to the authenticator over a local socket.
+    #[test]
+    fn ipc_socket_send() {
+        use safe_core::ipc::socket::IpcListener;
+        use std::sync::mpsc;
+
+        let path = unwrap!(socket::socket_path(
+            &format!("safe_app_ipc_{}", rand::random::<u64>()),
+        ));
+        let path_c = unwrap!(CString::new(unwrap!(path.to_str())));
+
+        let (tx, rx) = mpsc::channel();
+        let listener = unwrap!(IpcListener::start(
+            path.clone(),
+            move |id, msg| unwrap!(tx.send((id, msg))),
+        ));
+
+        let msg = unwrap!(CString::new("request"));
+        let (resp_tx, resp_rx) = mpsc::channel();
+        let _ = ::std::thread::spawn(move || {
+            let resp: String = unsafe {
+                unwrap!(call_1(|ud, cb| {
+                    app_ipc_socket_send(path_c.as_ptr(), msg.as_ptr(), ud, cb)
+                }))
+            };
+            unwrap!(resp_tx.send(resp));
+        });
+
+        let (id, msg) = unwrap!(rx.recv());
+        assert_eq!(msg, "request");
+        unwrap!(listener.respond(id, "response"));
+
+        assert_eq!(unwrap!(resp_rx.recv()), "response");
+    }
+
     // Test that `decode_ipc_msg` calls the `o_auth` callback.
     #[test]
     fn decode_ipc_msg_with_auth_granted() {
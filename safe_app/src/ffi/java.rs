@@ -0,0 +1,223 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! JNI wrappers of the C API, exported as the native methods of
+//! `net.maidsafe.safe_app.NativeBindings`.
+//!
+//! Callbacks are Java objects with a `call` method taking the error code and description,
+//! followed by the result values: `long` for handles, sizes and the app pointer, `byte[]`
+//! for byte arrays. Disconnect listeners have a `call()` method with no parameters.
+//! Invalid arguments are reported by throwing `IllegalArgumentException`.
+
+#![allow(non_snake_case)]
+
+use App;
+use ffi::{app_free, app_reconnect, app_set_additional_search_path, app_unregistered};
+use ffi::mutable_data::entries::{mdata_entries_free, mdata_entries_insert, mdata_entries_len,
+                                 mdata_entries_new};
+use ffi_utils::FfiResult;
+use ffi_utils::java::{self, JniCallback, notify_cb, result_cb, result_u64_cb, result_usize_cb};
+use ffi_utils::jni::JNIEnv;
+use ffi_utils::jni::objects::{JClass, JObject, JString};
+use ffi_utils::jni::sys::{jbyteArray, jlong};
+use object_cache::MDataEntriesHandle;
+use std::ffi::CString;
+use std::os::raw::c_void;
+use std::sync::{Mutex, PoisonError};
+
+// Returns from the JNI function after throwing `IllegalArgumentException` on error.
+macro_rules! jni_try {
+    ($env:expr, $res:expr) => {
+        match $res {
+            Ok(value) => value,
+            Err(err) => return java::throw_illegal_argument(&$env, err),
+        }
+    }
+}
+
+/// Calls `app_unregistered`. `on_disconnect` lives as long as the app, so it's only
+/// released if connecting fails.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_appUnregistered(
+    env: JNIEnv,
+    _class: JClass,
+    bootstrap_config: jbyteArray,
+    on_disconnect: JObject,
+    cb: JObject,
+) {
+    let bootstrap_config = jni_try!(env, java::from_jbyte_array(&env, bootstrap_config));
+    let callbacks = ConnectCallbacks {
+        on_disconnect: jni_try!(env, JniCallback::new(&env, on_disconnect)),
+        on_connect: Mutex::new(Some(jni_try!(env, JniCallback::new(&env, cb)))),
+    };
+    let user_data = Box::into_raw(Box::new(callbacks)) as *mut c_void;
+
+    app_unregistered(
+        bootstrap_config.as_ptr(),
+        bootstrap_config.len(),
+        user_data,
+        disconnect_cb,
+        connect_cb,
+    );
+}
+
+/// Calls `app_reconnect`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_appReconnect(
+    env: JNIEnv,
+    _class: JClass,
+    app: jlong,
+    cb: JObject,
+) {
+    let user_data = jni_try!(env, JniCallback::new(&env, cb)).into_user_data();
+    app_reconnect(app as *mut App, user_data, result_cb);
+}
+
+/// Calls `app_free`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_appFree(
+    _env: JNIEnv,
+    _class: JClass,
+    app: jlong,
+) {
+    app_free(app as *mut App);
+}
+
+/// Calls `app_set_additional_search_path`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_appSetAdditionalSearchPath(
+    env: JNIEnv,
+    _class: JClass,
+    new_path: JString,
+    cb: JObject,
+) {
+    let new_path = jni_try!(env, java::from_jstring(&env, new_path));
+    let new_path = jni_try!(env, CString::new(new_path));
+    let user_data = jni_try!(env, JniCallback::new(&env, cb)).into_user_data();
+    app_set_additional_search_path(new_path.as_ptr(), user_data, result_cb);
+}
+
+/// Calls `mdata_entries_new`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_mdataEntriesNew(
+    env: JNIEnv,
+    _class: JClass,
+    app: jlong,
+    cb: JObject,
+) {
+    let user_data = jni_try!(env, JniCallback::new(&env, cb)).into_user_data();
+    mdata_entries_new(app as *const App, user_data, result_u64_cb);
+}
+
+/// Calls `mdata_entries_insert`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_mdataEntriesInsert(
+    env: JNIEnv,
+    _class: JClass,
+    app: jlong,
+    entries_h: jlong,
+    key: jbyteArray,
+    value: jbyteArray,
+    cb: JObject,
+) {
+    let key = jni_try!(env, java::from_jbyte_array(&env, key));
+    let value = jni_try!(env, java::from_jbyte_array(&env, value));
+    let user_data = jni_try!(env, JniCallback::new(&env, cb)).into_user_data();
+    mdata_entries_insert(
+        app as *const App,
+        entries_h as MDataEntriesHandle,
+        key.as_ptr(),
+        key.len(),
+        value.as_ptr(),
+        value.len(),
+        user_data,
+        result_cb,
+    );
+}
+
+/// Calls `mdata_entries_len`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_mdataEntriesLen(
+    env: JNIEnv,
+    _class: JClass,
+    app: jlong,
+    entries_h: jlong,
+    cb: JObject,
+) {
+    let user_data = jni_try!(env, JniCallback::new(&env, cb)).into_user_data();
+    mdata_entries_len(
+        app as *const App,
+        entries_h as MDataEntriesHandle,
+        user_data,
+        result_usize_cb,
+    );
+}
+
+/// Calls `mdata_entries_free`.
+#[no_mangle]
+pub unsafe extern "system" fn Java_net_maidsafe_safe_1app_NativeBindings_mdataEntriesFree(
+    env: JNIEnv,
+    _class: JClass,
+    app: jlong,
+    entries_h: jlong,
+    cb: JObject,
+) {
+    let user_data = jni_try!(env, JniCallback::new(&env, cb)).into_user_data();
+    mdata_entries_free(
+        app as *const App,
+        entries_h as MDataEntriesHandle,
+        user_data,
+        result_cb,
+    );
+}
+
+// `app_unregistered` passes the same user data to both of its callbacks.
+struct ConnectCallbacks {
+    on_disconnect: JniCallback,
+    on_connect: Mutex<Option<JniCallback>>,
+}
+
+extern "C" fn disconnect_cb(user_data: *mut c_void) {
+    let callbacks = user_data as *const ConnectCallbacks;
+    unsafe {
+        notify_cb(&(*callbacks).on_disconnect as *const JniCallback as *mut c_void);
+    }
+}
+
+extern "C" fn connect_cb(user_data: *mut c_void, result: *const FfiResult, app: *mut App) {
+    unsafe {
+        let failed = (*result).error_code != 0;
+        let on_connect = {
+            let callbacks = user_data as *const ConnectCallbacks;
+            (*callbacks)
+                .on_connect
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .take()
+        };
+        if let Some(on_connect) = on_connect {
+            result_u64_cb(
+                on_connect.into_user_data(),
+                result,
+                app as usize as u64,
+            );
+        }
+        if failed {
+            drop(Box::from_raw(user_data as *mut ConnectCallbacks));
+        }
+    }
+}
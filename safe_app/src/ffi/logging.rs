@@ -21,12 +21,18 @@ use super::AppError;
 use config_file_handler::FileHandler;
 use ffi_utils::{FFI_RESULT_OK, FfiResult, catch_unwind_cb, from_c_str};
 use maidsafe_utilities::log;
+use safe_core::config_handler::client_config;
+use safe_core::ffi::logging::init_callback_logger;
+use safe_core::utils::logging::{self, DEFAULT_LOG_FILE_NAME, LogLevels};
 use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::str::FromStr;
 
 /// This function should be called to enable logging to a file.
 /// If `output_file_name_override` is provided, then this path will be used for
 /// the log output file.
+/// The `log_levels` client setting, if set, replaces the levels from `log.toml`.
 ///
 /// Callback parameters: user data, error code
 #[no_mangle]
@@ -36,12 +42,60 @@ pub unsafe extern "C" fn app_init_logging(
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
-        if output_file_name_override.is_null() {
-            log::init(false)?;
+        init_logging(output_file_name_override, ptr::null())?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
+
+/// Same as `app_init_logging`, but if `log_levels` is provided, it replaces the
+/// levels from `log.toml` and the `log_levels` client setting. It's a comma-separated
+/// list of `level` or `module=level` items, e.g. `"warn,safe_core=debug"`.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_init_logging_with_levels(
+    output_file_name_override: *const c_char,
+    log_levels: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
+        init_logging(output_file_name_override, log_levels)?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
+
+/// This function can be called instead of `app_init_logging` to pass log records
+/// to the host application, e.g. to merge them into its own logs. `log_levels` has
+/// the same format as in `app_init_logging_with_levels`; if neither it nor the
+/// `log_levels` client setting is provided only errors are passed on. `o_log` is called
+/// with the same user data for every record, possibly from different threads, and gets
+/// the level (1 = error up to 5 = trace), module path and message.
+/// Only one logger can be set up per process, so this fails if logging has already
+/// been initialised.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_init_log_callback(
+    log_levels: *const c_char,
+    user_data: *mut c_void,
+    o_log: extern "C" fn(user_data: *mut c_void,
+                         level: u32,
+                         module: *const c_char,
+                         message: *const c_char),
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AppError> {
+        let levels = if log_levels.is_null() {
+            let levels = client_config().log_levels.unwrap_or_default();
+            LogLevels::from_str(&levels)?
         } else {
-            let output_file_name_override = from_c_str(output_file_name_override)?;
-            log::init_with_output_file(false, output_file_name_override)?;
-        }
+            LogLevels::from_str(&from_c_str(log_levels)?)?
+        };
+        init_callback_logger(levels, user_data, o_log)?;
+
         o_cb(user_data, FFI_RESULT_OK);
         Ok(())
     });
@@ -80,6 +134,34 @@ pub unsafe extern "C" fn app_output_log_path(
     })
 }
 
+unsafe fn init_logging(
+    output_file_name_override: *const c_char,
+    log_levels: *const c_char,
+) -> Result<(), AppError> {
+    let log_levels = if log_levels.is_null() {
+        client_config().log_levels
+    } else {
+        Some(from_c_str(log_levels)?)
+    };
+    if let Some(log_levels) = log_levels {
+        let levels = LogLevels::from_str(&log_levels)?;
+        let output_file_name = if output_file_name_override.is_null() {
+            DEFAULT_LOG_FILE_NAME.to_owned()
+        } else {
+            from_c_str(output_file_name_override)?
+        };
+        logging::write_log_config(&levels, &output_file_name)?;
+    }
+
+    if output_file_name_override.is_null() {
+        log::init(false)?;
+    } else {
+        let output_file_name_override = from_c_str(output_file_name_override)?;
+        log::init_with_output_file(false, output_file_name_override)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -117,10 +199,11 @@ mod tests {
         let log_file_path = unwrap!(CString::new(unwrap!(
             current_exe_path.clone().into_os_string().into_string()
         )));
+        let log_levels = unwrap!(CString::new("error"));
         unsafe {
-            unwrap!(call_0(
-                |ud, cb| app_init_logging(log_file_path.as_ptr(), ud, cb),
-            ));
+            unwrap!(call_0(|ud, cb| {
+                app_init_logging_with_levels(log_file_path.as_ptr(), log_levels.as_ptr(), ud, cb)
+            }));
         }
 
         let debug_msg = "This is a sample debug message".to_owned();
@@ -15,8 +15,11 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use App;
 use errors::AppError;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, ReprC, SafePtr, catch_unwind_cb};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb,
+                vec_clone_from_raw_parts};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use routing::XorName;
 use rust_sodium::crypto::secretbox;
@@ -55,6 +58,37 @@ pub unsafe extern "C" fn mdata_info_new_private(
     })
 }
 
+/// Derive encrypted mdata info from the app's keys and a label. The same app
+/// always gets the same mdata info for the same label, so it can re-find its own
+/// private data without keeping a record of where it's stored.
+///
+/// Callback parameters: user data, error code, mdata info handle
+#[no_mangle]
+pub unsafe extern "C" fn app_derive_mdata_info(
+    app: *const App,
+    label: *const u8,
+    label_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        mdata_info: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        let label = vec_clone_from_raw_parts(label, label_len);
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let sym_enc_key = try_cb!(context.sym_enc_key(), user_data, o_cb);
+
+            let info = MDataInfo::derive_private(&sym_enc_key.0, &label);
+            let info = info.into_repr_c();
+
+            o_cb(user_data.0, FFI_RESULT_OK, &info);
+            None
+        })
+    })
+}
+
 /// Create random, non-encrypted mdata info.
 ///
 /// Callback parameters: user data, error code, mdata info handle
@@ -239,6 +273,33 @@ mod tests {
     use rust_sodium::crypto::secretbox;
     use safe_core::MDataInfo;
     use safe_core::crypto::shared_secretbox;
+    use test_utils::create_app;
+
+    // Test deriving mdata info from the app's keys.
+    #[test]
+    fn derive_from_app() {
+        let app1 = create_app();
+        let app2 = create_app();
+        let label = b"my data";
+
+        let derive = |app: &App, label: &[u8]| -> MDataInfo {
+            unsafe {
+                unwrap!(call_1(|ud, cb| {
+                    app_derive_mdata_info(app, label.as_ptr(), label.len(), ud, cb)
+                }))
+            }
+        };
+
+        let info = derive(&app1, label);
+        assert!(info.enc_info.is_some());
+
+        // The same app and label give the same info.
+        assert_eq!(derive(&app1, label), info);
+
+        // Another label or another app give different info.
+        assert_ne!(derive(&app1, b"other data").name, info.name);
+        assert_ne!(derive(&app2, label).name, info.name);
+    }
 
     // Test creating non-encrypted mdata info.
     #[test]
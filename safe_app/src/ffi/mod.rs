@@ -21,12 +21,30 @@
 
 /// Access container.
 pub mod access_container;
+/// Append-only logs.
+pub mod append_log;
+/// Sharded key-value store.
+pub mod big_map;
 /// Cipher Options.
 pub mod cipher_opt;
+/// Address book.
+pub mod contacts;
+/// Estimates of the mutations operations consume.
+pub mod cost;
+/// Public name resolution.
+pub mod dns;
+/// Network transport provided by the host.
+#[cfg(feature = "use-host-routing")]
+pub mod host_routing;
 /// Low level manipulation of `ImmutableData`.
 pub mod immutable_data;
+/// Inboxes for receiving encrypted messages.
+pub mod inbox;
 /// IPC utilities.
 pub mod ipc;
+/// JNI wrappers for Android.
+#[cfg(feature = "jni")]
+pub mod java;
 /// Logging operations.
 pub mod logging;
 /// `MDataInfo` operations.
@@ -37,6 +55,18 @@ pub mod crypto;
 pub mod mutable_data;
 /// NFS API.
 pub mod nfs;
+/// Handle-leak diagnostics.
+pub mod object_cache;
+/// Client-side search indices.
+pub mod search;
+/// App settings documents.
+pub mod settings;
+/// Notification topics.
+pub mod topic;
+/// Ledger of `ImmutableData` uploads.
+pub mod upload_ledger;
+/// Fetching of `safe://` URLs.
+pub mod web;
 /// Testing utilities.
 #[cfg(any(test, feature = "testing"))]
 pub mod test_utils;
@@ -45,19 +75,30 @@ mod helper;
 #[cfg(test)]
 mod tests;
 
-use super::App;
+use super::{App, AppContext};
 use super::errors::AppError;
 use config_file_handler;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, catch_unwind_cb, from_c_str};
+use ffi::helper::{send_sync, send_with_user_data};
+use ffi_utils::{FFI_RESULT_OK, FfiBuffer, FfiResult, FfiString, OpaqueCtx, ReprC, SafePtr,
+                catch_unwind_cb, from_c_str, from_c_wstr, last_error, ptr_as_ref, spawn_cb};
 use futures::Future;
 use maidsafe_utilities::serialisation::deserialise;
 use safe_core::{self, FutureExt};
 use safe_core::ffi::AccountInfo as FfiAccountInfo;
+use safe_core::ffi::error_codes::error_code_description_ptr;
+use safe_core::ffi::ipc::req::ContainerPermissions as FfiContainerPermissions;
 use safe_core::ffi::ipc::resp::AuthGranted as FfiAuthGranted;
+use safe_core::ffi::{NetworkStats as FfiNetworkStats, RequestStats as FfiRequestStats};
 use safe_core::ipc::{AuthGranted, BootstrapConfig};
+use safe_core::ipc::req::containers_into_vec;
 use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::{c_char, c_void};
 use std::slice;
+use std::time::Duration;
+
+/// Function invoked with the user data of an operation which will never call its
+/// callback.
+pub type UserDataDestructor = extern "C" fn(user_data: *mut c_void);
 
 /// Create unregistered app.
 /// The `user_data` parameter corresponds to the first parameter of the
@@ -76,25 +117,68 @@ pub unsafe extern "C" fn app_unregistered(
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
         let user_data = OpaqueCtx(user_data);
+        let config = bootstrap_config_from_raw(bootstrap_config, bootstrap_config_len)?;
+        unregistered(config, user_data, o_disconnect_notifier_cb, o_cb)
+    })
+}
 
-        let config = if bootstrap_config_len == 0 || bootstrap_config.is_null() {
-            None
-        } else {
-            let config_serialised = slice::from_raw_parts(bootstrap_config, bootstrap_config_len);
-            Some(deserialise::<BootstrapConfig>(config_serialised)?)
-        };
-
-        let app = App::unregistered(move || o_disconnect_notifier_cb(user_data.0), config)?;
+/// Same as `app_unregistered`, but connects to the network on a background
+/// thread and returns immediately.
+///
+/// Callback parameters: user data, error code, app
+#[no_mangle]
+pub unsafe extern "C" fn app_unregistered_async(
+    bootstrap_config: *const u8,
+    bootstrap_config_len: usize,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        app: *mut App),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        let config = bootstrap_config_from_raw(bootstrap_config, bootstrap_config_len)?;
 
-        o_cb(user_data.0, FFI_RESULT_OK, Box::into_raw(Box::new(app)));
+        spawn_cb(user_data, o_cb, move || {
+            unregistered(config, user_data, o_disconnect_notifier_cb, o_cb)
+        });
 
         Ok(())
     })
 }
 
+unsafe fn bootstrap_config_from_raw(
+    bootstrap_config: *const u8,
+    bootstrap_config_len: usize,
+) -> Result<Option<BootstrapConfig>, AppError> {
+    if bootstrap_config_len == 0 || bootstrap_config.is_null() {
+        Ok(None)
+    } else {
+        let config_serialised = slice::from_raw_parts(bootstrap_config, bootstrap_config_len);
+        Ok(Some(deserialise::<BootstrapConfig>(config_serialised)?))
+    }
+}
+
+fn unregistered(
+    config: Option<BootstrapConfig>,
+    user_data: OpaqueCtx,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        app: *mut App),
+) -> Result<(), AppError> {
+    let app = App::unregistered(move || o_disconnect_notifier_cb(user_data.0), config)?;
+
+    o_cb(user_data.0, FFI_RESULT_OK, Box::into_raw(Box::new(app)));
+
+    Ok(())
+}
+
 /// Create a registered app.
 /// The `user_data` parameter corresponds to the first parameter of the
 /// `o_cb` and `o_disconnect_notifier_cb` callbacks.
+/// Apps authorised with a scope pass their scoped ID, `<app id>#<scope>`, as `app_id`.
 ///
 /// Callback parameters: user data, error code, app
 #[no_mangle]
@@ -112,18 +196,73 @@ pub unsafe extern "C" fn app_registered(
         let app_id = from_c_str(app_id)?;
         let auth_granted = AuthGranted::clone_from_repr_c(auth_granted)?;
 
-        let app = App::registered(app_id, auth_granted, move || {
-            o_disconnect_notifier_cb(user_data.0)
-        })?;
+        registered(
+            app_id,
+            auth_granted,
+            user_data,
+            o_disconnect_notifier_cb,
+            o_cb,
+        )
+    })
+}
 
-        o_cb(user_data.0, FFI_RESULT_OK, Box::into_raw(Box::new(app)));
+/// Same as `app_registered`, but connects to the network on a background
+/// thread and returns immediately.
+///
+/// Callback parameters: user data, error code, app
+#[no_mangle]
+pub unsafe extern "C" fn app_registered_async(
+    app_id: *const c_char,
+    auth_granted: *const FfiAuthGranted,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        app: *mut App),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        let app_id = from_c_str(app_id)?;
+        let auth_granted = AuthGranted::clone_from_repr_c(auth_granted)?;
+
+        spawn_cb(user_data, o_cb, move || {
+            registered(
+                app_id,
+                auth_granted,
+                user_data,
+                o_disconnect_notifier_cb,
+                o_cb,
+            )
+        });
 
         Ok(())
     })
 }
 
+fn registered(
+    app_id: String,
+    auth_granted: AuthGranted,
+    user_data: OpaqueCtx,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        app: *mut App),
+) -> Result<(), AppError> {
+    let app = App::registered(app_id, auth_granted, move || {
+        o_disconnect_notifier_cb(user_data.0)
+    })?;
+
+    o_cb(user_data.0, FFI_RESULT_OK, Box::into_raw(Box::new(app)));
+
+    Ok(())
+}
+
 /// Try to restore a failed connection with the network.
 ///
+/// For registered apps the access container entry is fetched again as well,
+/// as permissions might have changed while the app was disconnected. Existing
+/// object handles stay valid.
+///
 /// Callback parameters: user data, error code
 #[no_mangle]
 pub unsafe extern "C" fn app_reconnect(
@@ -133,20 +272,97 @@ pub unsafe extern "C" fn app_reconnect(
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
         let user_data = OpaqueCtx(user_data);
-        (*app).send(move |client, _| {
+        send_with_user_data(app, user_data, move |client, context| {
             try_cb!(
                 client.restart_routing().map_err(AppError::from),
                 user_data.0,
                 o_cb
             );
-            o_cb(user_data.0, FFI_RESULT_OK);
-            None
+
+            if let AppContext::Unregistered(_) = *context {
+                o_cb(user_data.0, FFI_RESULT_OK);
+                return None;
+            }
+
+            context
+                .refresh_access_info(client)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Fetch the app's access container entry again, replacing the cached one, so that
+/// containers granted by the authenticator while the app is running can be used
+/// without restarting the app.
+///
+/// Callback parameters: user data, error code, container permissions vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn app_refresh_access_info(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        container_perms: *const FfiContainerPermissions,
+                        container_perms_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        send_with_user_data(app, user_data, move |client, context| {
+            let c2 = client.clone();
+            let context2 = context.clone();
+
+            context
+                .refresh_access_info(client)
+                .and_then(move |_| context2.get_access_info(&c2))
+                .and_then(move |containers| {
+                    let ffi_containers = containers_into_vec(containers.into_iter().map(
+                        |(key, (_, value))| (key, value),
+                    ))?;
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        ffi_containers.as_safe_ptr(),
+                        ffi_containers.len(),
+                    );
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Drop the app's cached access container entry, so that it's fetched again the next
+/// time it's needed. Unregistered apps get `ERR_OPERATION_FORBIDDEN`.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_invalidate_access_info(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        send_sync(app, user_data, o_cb, |_, context| {
+            context.invalidate_access_info()
         })
     })
 }
 
 /// Get the account usage statistics (mutations done and mutations available).
 ///
+/// The statistics are those of the account which owns the app, so apps can check
+/// whether they have enough mutations left before starting a large upload.
+/// Unregistered apps get `ERR_OPERATION_FORBIDDEN`.
+///
 /// Callback parameters: user data, error code, account info
 #[no_mangle]
 pub unsafe extern "C" fn app_account_info(
@@ -158,7 +374,7 @@ pub unsafe extern "C" fn app_account_info(
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
         let user_data = OpaqueCtx(user_data);
-        (*app).send(move |client, _| {
+        send_with_user_data(app, user_data, move |client, _| {
             client
                 .get_account_info()
                 .map(move |acc_info| {
@@ -177,6 +393,68 @@ pub unsafe extern "C" fn app_account_info(
     })
 }
 
+/// Get the statistics of the traffic between the app and the network: bytes sent and
+/// received, request counts by kind of request and a round trip time estimate.
+///
+/// The `requests` array and the strings it points to are only valid for the duration of
+/// the callback.
+///
+/// Callback parameters: user data, error code, network stats
+#[no_mangle]
+pub unsafe extern "C" fn client_network_stats(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        stats: *const FfiNetworkStats),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        send_with_user_data(app, user_data, move |client, _| {
+            let stats = client.network_stats();
+
+            let kinds = try_cb!(
+                stats
+                    .requests
+                    .keys()
+                    .map(|kind| CString::new(*kind))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(AppError::from),
+                user_data,
+                o_cb
+            );
+            let requests: Vec<_> = stats
+                .requests
+                .values()
+                .zip(&kinds)
+                .map(|(counts, kind)| {
+                    FfiRequestStats {
+                        kind: kind.as_ptr(),
+                        sent: counts.sent,
+                        failed: counts.failed,
+                    }
+                })
+                .collect();
+            let rtt_ms = stats.rtt.map_or(0, |rtt| {
+                rtt.as_secs() * 1000 + u64::from(rtt.subsec_nanos() / 1_000_000)
+            });
+
+            let ffi_stats = FfiNetworkStats {
+                bytes_sent: stats.bytes_sent,
+                bytes_received: stats.bytes_received,
+                requests_sent: stats.requests_sent(),
+                requests_failed: stats.requests_failed(),
+                rtt_ms,
+                requests: requests.as_safe_ptr(),
+                requests_len: requests.len(),
+            };
+            o_cb(user_data.0, FFI_RESULT_OK, &ffi_stats);
+
+            None
+        })
+    })
+}
+
 /// Returns the expected name for the application executable without an extension
 #[no_mangle]
 pub unsafe extern "C" fn app_exe_file_stem(
@@ -218,13 +496,145 @@ pub unsafe extern "C" fn app_set_additional_search_path(
     });
 }
 
+/// Same as `app_set_additional_search_path`, but takes the path as a nul-terminated
+/// UTF-16 string.
+#[no_mangle]
+pub unsafe extern "C" fn app_set_additional_search_path_w(
+    new_path: *const u16,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let new_path = from_c_wstr(new_path)?;
+        config_file_handler::set_additional_search_path(OsStr::new(&new_path));
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
+
+/// Set the function to invoke with the `user_data` of operations which are
+/// dropped without ever calling their callback, e.g. because the app is freed
+/// or shut down while they are pending. It's invoked at most once for each
+/// operation, and never for operations which call their callback, so the host
+/// can release the resources tied to `user_data` in one of the two places.
+/// Pass a null function to remove it. Only affects operations started after
+/// this call.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_set_user_data_destructor(
+    app: *const App,
+    destructor: Option<UserDataDestructor>,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        ptr_as_ref(app)?.set_user_data_destructor(destructor);
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
 /// Discard and clean up the previously allocated app instance.
 /// Use this only if the app is obtained from one of the auth
 /// functions in this crate. Using `app` after a call to this
 /// function is undefined behaviour.
 #[no_mangle]
 pub unsafe extern "C" fn app_free(app: *mut App) {
-    let _ = Box::from_raw(app);
+    if !app.is_null() {
+        let _ = Box::from_raw(app);
+    }
+}
+
+/// Gracefully shut down and clean up the previously allocated app instance.
+/// Operations already sent to the app are given up to `timeout_ms`
+/// milliseconds to finish. Those still waiting for the network then have
+/// their callbacks invoked with an "operation aborted" error. Using `app`
+/// after a call to this function is undefined behaviour.
+///
+/// Callback parameters: user data, error code, number of operations
+/// abandoned because they didn't finish even so
+#[no_mangle]
+pub unsafe extern "C" fn app_shutdown(
+    app: *mut App,
+    timeout_ms: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        abandoned: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let _ = ptr_as_ref(app)?;
+        let app = *Box::from_raw(app);
+        let abandoned = app.shutdown(Duration::from_millis(timeout_ms))?;
+        o_cb(user_data, FFI_RESULT_OK, abandoned as u64);
+        Ok(())
+    })
+}
+
+/// Same as `app_shutdown`, but waits for the pending operations on a background
+/// thread and returns immediately.
+///
+/// Callback parameters: user data, error code, number of abandoned operations
+#[no_mangle]
+pub unsafe extern "C" fn app_shutdown_async(
+    app: *mut App,
+    timeout_ms: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        abandoned: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+        let _ = ptr_as_ref(app)?;
+        let app = *Box::from_raw(app);
+
+        spawn_cb(user_data, o_cb, move || -> Result<_, AppError> {
+            let abandoned = app.shutdown(Duration::from_millis(timeout_ms))?;
+            o_cb(user_data.0, FFI_RESULT_OK, abandoned as u64);
+            Ok(())
+        });
+
+        Ok(())
+    })
+}
+
+/// Release a buffer handed over by one of the functions of this crate which
+/// return `FfiBuffer`. Using the buffer after a call to this function is
+/// undefined behaviour. Null buffers, as passed to callbacks reporting an error,
+/// are ignored.
+#[no_mangle]
+pub unsafe extern "C" fn app_buffer_free(buffer: FfiBuffer) {
+    if buffer.is_null() {
+        return;
+    }
+    let _ = buffer.into_vec();
+}
+
+/// Returns the description of the most recent error reported on the calling
+/// thread, or a null string if there was none. Errors of asynchronous
+/// operations are reported on the thread their callback runs on, so this should
+/// be called from within the callback. The string has to be released with
+/// `app_string_free`.
+#[no_mangle]
+pub extern "C" fn app_last_error_message() -> FfiString {
+    last_error().map_or_else(FfiString::null, FfiString::from_string)
+}
+
+/// Release a string handed over by one of the functions of this crate which
+/// return `FfiString`. Using the string after a call to this function is
+/// undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn app_string_free(s: FfiString) {
+    let _ = s.into_string();
+}
+
+/// Returns the static description of the given error code, or null if the code
+/// is unknown. The string must not be freed.
+#[no_mangle]
+pub extern "C" fn app_error_code_description(code: i32) -> *const c_char {
+    error_code_description_ptr(code)
 }
 
 /// Resets the object cache. Removes all objects currently in the object cache
@@ -237,7 +647,7 @@ pub unsafe extern "C" fn app_reset_object_cache(
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
         let user_data = OpaqueCtx(user_data);
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             context.object_cache().reset();
             o_cb(user_data.0, FFI_RESULT_OK);
             None
@@ -19,7 +19,7 @@
 
 use App;
 use errors::AppError;
-use ffi::helper::send_sync;
+use ffi::helper::{send_sync, send_with_user_data};
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb,
                 vec_clone_from_raw_parts};
 use ffi_utils::callback::Callback;
@@ -117,7 +117,7 @@ pub unsafe extern "C" fn mdata_entries_get(
         let user_data = OpaqueCtx(user_data);
         let key = vec_clone_from_raw_parts(key, key_len);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let entries = context.object_cache().get_mdata_entries(entries_h);
             let entries = try_cb!(entries, user_data, o_cb);
 
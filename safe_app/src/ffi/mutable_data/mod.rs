@@ -19,20 +19,21 @@ pub mod entry_actions;
 pub mod entries;
 pub mod permissions;
 pub mod metadata;
+pub mod multisig;
 mod helper;
 #[cfg(test)]
 mod tests;
 
 use App;
 use errors::AppError;
-use ffi::helper::send;
+use ffi::helper::{send, send_with_user_data};
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb,
                 vec_clone_from_raw_parts};
 use futures::Future;
 use object_cache::{MDataEntriesHandle, MDataEntryActionsHandle, MDataPermissionsHandle,
                    NULL_OBJECT_HANDLE, SignPubKeyHandle};
 use routing::MutableData;
-use safe_core::{CoreError, FutureExt, MDataInfo};
+use safe_core::{CoreError, FutureExt, MDataInfo, entry_acl, mdata_value};
 use safe_core::ffi::MDataInfo as FfiMDataInfo;
 use safe_core::ffi::ipc::req::PermissionSet as FfiPermissionSet;
 use safe_core::ffi::ipc::resp::MDataKey as FfiMDataKey;
@@ -71,7 +72,7 @@ pub unsafe extern "C" fn mdata_put(
         let info = MDataInfo::clone_from_repr_c(info)?;
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let owner_key = try_cb!(client.owner_key().map_err(AppError::from), user_data, o_cb);
 
             let permissions = if permissions_h != PERMISSIONS_EMPTY {
@@ -193,7 +194,7 @@ pub unsafe extern "C" fn mdata_get_value(
         let key = vec_clone_from_raw_parts(key, key_len);
         let info = MDataInfo::clone_from_repr_c(info)?;
 
-        (*app).send(move |client, _| {
+        send_with_user_data(app, user_data, move |client, _| {
             client
                 .get_mdata_value(info.name, info.type_tag, key)
                 .and_then(move |value| Ok((value.content, value.entry_version)))
@@ -216,6 +217,85 @@ pub unsafe extern "C" fn mdata_get_value(
     })
 }
 
+/// Store a value under the key, replacing the current value if there is one.
+/// Values too large to fit in an entry are stored as immutable data and the
+/// entry only points to them. The key and the value are encrypted according to
+/// `info`. Values stored this way have to be read using `mdata_get_wrapped_value`.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn mdata_put_wrapped_value(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    key: *const u8,
+    key_len: usize,
+    value: *const u8,
+    value_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let key = vec_clone_from_raw_parts(key, key_len);
+        let value = vec_clone_from_raw_parts(value, value_len);
+        let info = MDataInfo::clone_from_repr_c(info)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            mdata_value::put(client, &info, &key, value)
+                .map_err(AppError::from)
+                .then(move |result| {
+                    call_result_cb!(result, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get a value stored by `mdata_put_wrapped_value`, fetching it from immutable
+/// data if it didn't fit in the entry. `key` is the plain, unencrypted key.
+///
+/// Callback parameters: user data, error code, content, content length, entry version
+#[no_mangle]
+pub unsafe extern "C" fn mdata_get_wrapped_value(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    key: *const u8,
+    key_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        content: *const u8,
+                        content_len: usize,
+                        version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let key = vec_clone_from_raw_parts(key, key_len);
+        let info = MDataInfo::clone_from_repr_c(info)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            mdata_value::get(client, &info, &key)
+                .map(move |value| {
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        value.content.as_safe_ptr(),
+                        value.content.len(),
+                        value.entry_version,
+                    );
+                })
+                .map_err(AppError::from)
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Get complete list of entries in the mutable data.
 ///
 /// Callback parameters: user data, error code, entries handle
@@ -263,7 +343,7 @@ pub unsafe extern "C" fn mdata_list_keys(
     catch_unwind_cb(user_data, o_cb, || {
         let info = MDataInfo::clone_from_repr_c(info)?;
 
-        (*app).send(move |client, _context| {
+        send_with_user_data(app, user_data, move |client, _context| {
             client
                 .list_mdata_keys(info.name, info.type_tag)
                 .map_err(AppError::from)
@@ -311,7 +391,7 @@ pub unsafe extern "C" fn mdata_list_values(
     catch_unwind_cb(user_data, o_cb, || {
         let info = MDataInfo::clone_from_repr_c(info)?;
 
-        (*app).send(move |client, _context| {
+        send_with_user_data(app, user_data, move |client, _context| {
             client
                 .list_mdata_values(info.name, info.type_tag)
                 .map_err(AppError::from)
@@ -356,7 +436,7 @@ pub unsafe extern "C" fn mdata_mutate_entries(
         let user_data = OpaqueCtx(user_data);
         let info = MDataInfo::clone_from_repr_c(info)?;
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let actions = try_cb!(
                 context.object_cache().get_mdata_entry_actions(actions_h),
                 user_data,
@@ -376,6 +456,42 @@ pub unsafe extern "C" fn mdata_mutate_entries(
     })
 }
 
+/// Same as `mdata_mutate_entries`, but first checks the actions against the entry-level
+/// ACL of the mutable data (see `safe_core::entry_acl`). Fails with
+/// `ERR_ENTRY_ACCESS_DENIED` if the app isn't allowed to modify some of the entries.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn mdata_mutate_entries_checked(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    actions_h: MDataEntryActionsHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let info = MDataInfo::clone_from_repr_c(info)?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let actions = try_cb!(
+                context.object_cache().get_mdata_entry_actions(actions_h),
+                user_data,
+                o_cb
+            );
+
+            entry_acl::mutate_entries(client, &info, actions.clone())
+                .map_err(AppError::from)
+                .then(move |result| {
+                    call_result_cb!(result, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Get list of all permissions set on the mutable data
 ///
 /// Callback parameters: user data, error code, permission handle
@@ -422,7 +538,7 @@ pub unsafe extern "C" fn mdata_list_user_permissions(
         let user_data = OpaqueCtx(user_data);
         let info = MDataInfo::clone_from_repr_c(info)?;
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let user = try_cb!(
                 helper::get_user(context.object_cache(), user_h),
                 user_data,
@@ -465,7 +581,7 @@ pub unsafe extern "C" fn mdata_set_user_permissions(
         let info = MDataInfo::clone_from_repr_c(info)?;
         let permission_set = *permission_set;
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let user = try_cb!(
                 helper::get_user(context.object_cache(), user_h),
                 user_data,
@@ -504,7 +620,7 @@ pub unsafe extern "C" fn mdata_del_user_permissions(
         let user_data = OpaqueCtx(user_data);
         let info = MDataInfo::clone_from_repr_c(info)?;
 
-        (*app).send(move |client, context| {
+        send_with_user_data(app, user_data, move |client, context| {
             let user = try_cb!(
                 helper::get_user(context.object_cache(), user_h),
                 user_data,
@@ -0,0 +1,309 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! FFI for mutable data owned jointly by several keys.
+//!
+//! Owner-level requests are exchanged between the owners in their serialised form: one of
+//! them creates the request, each of the others signs it with `mdata_owner_request_sign` and
+//! the last one submits it with `mdata_owner_request_submit`.
+//!
+//! The signatures are advisory: they're checked by `mdata_owner_request_submit` only, while
+//! the network accepts owner-level requests from any single owner. They don't protect the
+//! data from an owner who doesn't follow the protocol.
+
+use {App, AppContext};
+use errors::AppError;
+use ffi::helper::send_with_user_data;
+use ffi::mutable_data::{ENTRIES_EMPTY, PERMISSIONS_EMPTY, helper};
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use object_cache::{MDataEntriesHandle, MDataPermissionsHandle, SignPubKeyHandle,
+                   SignSecKeyHandle};
+use safe_core::{FutureExt, MDataInfo, multisig};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use safe_core::ffi::ipc::req::PermissionSet as FfiPermissionSet;
+use safe_core::ipc::req::permission_set_clone_from_repr_c;
+use safe_core::multisig::{OwnerOp, OwnerRequest};
+use std::collections::BTreeSet;
+use std::os::raw::c_void;
+use std::slice;
+
+/// Create new mutable data owned by the app's owner together with `co_owners` and put it on
+/// the network.
+///
+/// `co_owners` is an array of handles to the public signing keys of the other owners.
+/// `permissions_h` and `entries_h` are as in `mdata_put`.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn mdata_put_multisig(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    permissions_h: MDataPermissionsHandle,
+    entries_h: MDataEntriesHandle,
+    co_owners: *const SignPubKeyHandle,
+    co_owners_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let info = MDataInfo::clone_from_repr_c(info)?;
+        let co_owner_handles = slice::from_raw_parts(co_owners, co_owners_len).to_vec();
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let mut co_owners = BTreeSet::new();
+            for handle in co_owner_handles {
+                let key = try_cb!(context.object_cache().get_pub_sign_key(handle), user_data, o_cb);
+                let _ = co_owners.insert(*key);
+            }
+
+            let permissions = if permissions_h != PERMISSIONS_EMPTY {
+                try_cb!(
+                    helper::get_permissions(context.object_cache(), permissions_h),
+                    user_data,
+                    o_cb
+                )
+            } else {
+                Default::default()
+            };
+
+            let entries = if entries_h != ENTRIES_EMPTY {
+                try_cb!(
+                    context.object_cache().get_mdata_entries(entries_h),
+                    user_data,
+                    o_cb
+                ).clone()
+            } else {
+                Default::default()
+            };
+
+            multisig::put_mdata(
+                client,
+                info.name,
+                info.type_tag,
+                permissions,
+                entries,
+                co_owners,
+            ).map_err(AppError::from)
+                .then(move |result| {
+                    call_result_cb!(result, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Create a request to set the permissions of the given user on jointly owned mutable data.
+/// The request is not signed by anyone yet.
+///
+/// User is either handle to a signing key or `USER_ANYONE`.
+///
+/// Callback parameters: user data, error code, serialised request, request size
+#[no_mangle]
+pub unsafe extern "C" fn mdata_owner_request_set_user_permissions(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    user_h: SignPubKeyHandle,
+    permission_set: *const FfiPermissionSet,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        request: *const u8,
+                        request_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let info = MDataInfo::clone_from_repr_c(info)?;
+        let permission_set = *permission_set;
+
+        new_request(app, info, version, user_data, o_cb, move |context| {
+            Ok(OwnerOp::SetUserPermissions {
+                user: helper::get_user(context.object_cache(), user_h)?,
+                permissions: permission_set_clone_from_repr_c(&permission_set)?,
+            })
+        })
+    })
+}
+
+/// Create a request to delete the permissions of the given user on jointly owned mutable data.
+/// The request is not signed by anyone yet.
+///
+/// User is either handle to a signing key or `USER_ANYONE`.
+///
+/// Callback parameters: user data, error code, serialised request, request size
+#[no_mangle]
+pub unsafe extern "C" fn mdata_owner_request_del_user_permissions(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    user_h: SignPubKeyHandle,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        request: *const u8,
+                        request_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let info = MDataInfo::clone_from_repr_c(info)?;
+
+        new_request(app, info, version, user_data, o_cb, move |context| {
+            Ok(OwnerOp::DelUserPermissions {
+                user: helper::get_user(context.object_cache(), user_h)?,
+            })
+        })
+    })
+}
+
+/// Create a request to transfer jointly owned mutable data to a single new owner.
+/// The request is not signed by anyone yet.
+///
+/// Callback parameters: user data, error code, serialised request, request size
+#[no_mangle]
+pub unsafe extern "C" fn mdata_owner_request_change_owner(
+    app: *const App,
+    info: *const FfiMDataInfo,
+    new_owner_h: SignPubKeyHandle,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        request: *const u8,
+                        request_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let info = MDataInfo::clone_from_repr_c(info)?;
+
+        new_request(app, info, version, user_data, o_cb, move |context| {
+            let new_owner = *context.object_cache().get_pub_sign_key(new_owner_h)?;
+            Ok(OwnerOp::ChangeOwner { new_owner })
+        })
+    })
+}
+
+/// Sign a serialised owner request with the given key pair of one of the owners.
+///
+/// Callback parameters: user data, error code, serialised signed request, request size
+#[no_mangle]
+pub unsafe extern "C" fn mdata_owner_request_sign(
+    app: *const App,
+    request: *const u8,
+    request_len: usize,
+    sign_pk_h: SignPubKeyHandle,
+    sign_sk_h: SignSecKeyHandle,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        request: *const u8,
+                        request_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let request = vec_clone_from_raw_parts(request, request_len);
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let res = deserialise::<OwnerRequest>(&request)
+                .map_err(AppError::from)
+                .and_then(|mut request| {
+                    let public_key = *context.object_cache().get_pub_sign_key(sign_pk_h)?;
+                    let secret_key = context.object_cache().get_sec_sign_key(sign_sk_h)?;
+                    request.sign(public_key, &secret_key)?;
+                    Ok(serialise(&request)?)
+                });
+            let request = try_cb!(res, user_data, o_cb);
+
+            o_cb(
+                user_data.0,
+                FFI_RESULT_OK,
+                request.as_safe_ptr(),
+                request.len(),
+            );
+
+            None
+        })
+    })
+}
+
+/// Submit a serialised owner request to the network. Fails with
+/// `ERR_MISSING_OWNER_SIGNATURES` unless all the owners other than the app's owner have
+/// signed it. The check is client-side only and isn't enforced by the network.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn mdata_owner_request_submit(
+    app: *const App,
+    request: *const u8,
+    request_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let request = deserialise::<OwnerRequest>(&vec_clone_from_raw_parts(request, request_len))?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            multisig::submit(client, request)
+                .map_err(AppError::from)
+                .then(move |result| {
+                    call_result_cb!(result, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+// Builds an unsigned request from the operation returned by `f` and passes it serialised to
+// the callback.
+unsafe fn new_request<F>(
+    app: *const App,
+    info: MDataInfo,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        request: *const u8,
+                        request_len: usize),
+    f: F,
+) -> Result<(), AppError>
+where
+    F: FnOnce(&AppContext) -> Result<OwnerOp, AppError> + Send + 'static,
+{
+    let user_data = OpaqueCtx(user_data);
+
+    send_with_user_data(app, user_data, move |_, context| {
+        let res = f(context).and_then(|op| {
+            let request = OwnerRequest::new(info.name, info.type_tag, version, op);
+            Ok(serialise(&request)?)
+        });
+        let request = try_cb!(res, user_data, o_cb);
+
+        o_cb(
+            user_data.0,
+            FFI_RESULT_OK,
+            request.as_safe_ptr(),
+            request.len(),
+        );
+
+        None
+    })
+}
@@ -19,7 +19,7 @@
 
 use App;
 use errors::AppError;
-use ffi::helper::send_sync;
+use ffi::helper::{send_sync, send_with_user_data};
 use ffi::mutable_data::helper;
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb};
 use object_cache::{MDataPermissionsHandle, NULL_OBJECT_HANDLE, SignPubKeyHandle};
@@ -123,7 +123,7 @@ pub unsafe extern "C" fn mdata_permissions_get(
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let permissions = try_cb!(
                 context.object_cache().get_mdata_permissions(permissions_h),
                 user_data,
@@ -166,7 +166,7 @@ pub unsafe extern "C" fn mdata_list_permission_sets(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data, o_cb, || {
-        (*app).send(move |_, context| {
+        send_with_user_data(app, user_data, move |_, context| {
             let permissions = try_cb!(
                 context.object_cache().get_mdata_permissions(permissions_h),
                 user_data,
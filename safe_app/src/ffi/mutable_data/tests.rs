@@ -15,16 +15,19 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use errors::{ERR_ACCESS_DENIED, ERR_INVALID_SUCCESSOR, ERR_NO_SUCH_ENTRY, ERR_NO_SUCH_KEY};
+use errors::{ERR_ACCESS_DENIED, ERR_INVALID_SUCCESSOR, ERR_MISSING_OWNER_SIGNATURES,
+             ERR_NO_SUCH_ENTRY, ERR_NO_SUCH_KEY};
+use ffi::crypto::sign_generate_key_pair;
 use ffi::mdata_info::*;
 use ffi::mutable_data::*;
 use ffi::mutable_data::entries::*;
 use ffi::mutable_data::entry_actions::*;
+use ffi::mutable_data::multisig::*;
 use ffi::mutable_data::permissions::*;
 use ffi_utils::{FfiResult, vec_clone_from_raw_parts};
-use ffi_utils::test_utils::{call_0, call_1, call_vec, call_vec_u8, send_via_user_data,
+use ffi_utils::test_utils::{call_0, call_1, call_2, call_vec, call_vec_u8, send_via_user_data,
                             sender_as_user_data};
-use object_cache::MDataPermissionsHandle;
+use object_cache::{MDataPermissionsHandle, SignPubKeyHandle, SignSecKeyHandle};
 use permissions::UserPermissionSet;
 use routing::{Action, PermissionSet};
 use safe_core::ffi::ipc::req::PermissionSet as FfiPermissionSet;
@@ -562,3 +565,173 @@ fn entries_crud_ffi() {
         }
     }
 }
+
+// Test storing values which overflow into immutable data.
+#[test]
+fn wrapped_values_ffi() {
+    let app = create_app();
+
+    let perm_set = PermissionSet::new().allow(Action::Insert).allow(
+        Action::Update,
+    );
+    let perms_h: MDataPermissionsHandle =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_permissions_new(&app, ud, cb))) };
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            mdata_permissions_insert(
+                &app,
+                perms_h,
+                USER_ANYONE,
+                &permission_set_into_repr_c(perm_set),
+                ud,
+                cb,
+            )
+        }))
+    }
+
+    let md_info: MDataInfo =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_info_random_private(10_000, ud, cb))) };
+    let md_info = md_info.into_repr_c();
+    unsafe {
+        unwrap!(call_0(
+            |ud, cb| mdata_put(&app, &md_info, perms_h, ENTRIES_EMPTY, ud, cb),
+        ))
+    };
+
+    let small = b"small".to_vec();
+    let large = vec![1u8; mdata_value::INLINE_VALUE_MAX_LEN * 2];
+
+    for (version, value) in vec![small, large].into_iter().enumerate() {
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                mdata_put_wrapped_value(
+                    &app,
+                    &md_info,
+                    b"key".as_ptr(),
+                    3,
+                    value.as_ptr(),
+                    value.len(),
+                    ud,
+                    cb,
+                )
+            }))
+        };
+
+        let (tx, rx) = mpsc::channel::<Result<(Vec<u8>, u64), i32>>();
+        let mut ud = Default::default();
+        unsafe {
+            mdata_get_wrapped_value(
+                &app,
+                &md_info,
+                b"key".as_ptr(),
+                3,
+                sender_as_user_data(&tx, &mut ud),
+                get_value_cb,
+            )
+        };
+
+        let (content, entry_version) = unwrap!(unwrap!(rx.recv()));
+        assert_eq!(content, value);
+        assert_eq!(entry_version, version as u64);
+    }
+
+    extern "C" fn get_value_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        val: *const u8,
+        len: usize,
+        version: u64,
+    ) {
+        unsafe {
+            let result: Result<(Vec<u8>, u64), i32> = if (*res).error_code == 0 {
+                Ok((vec_clone_from_raw_parts(val, len), version))
+            } else {
+                Err((*res).error_code)
+            };
+
+            send_via_user_data(user_data, result);
+        }
+    }
+}
+
+// Jointly owned MD: owner-level requests only go through once the co-owner has signed them.
+#[test]
+fn multisig_ffi() {
+    let app = create_app();
+
+    let (co_owner_pk_h, co_owner_sk_h): (SignPubKeyHandle, SignSecKeyHandle) =
+        unsafe { unwrap!(call_2(|ud, cb| sign_generate_key_pair(&app, ud, cb))) };
+
+    let md_info: MDataInfo =
+        unsafe { unwrap!(call_1(|ud, cb| mdata_info_random_public(10_000, ud, cb))) };
+    let md_info = md_info.into_repr_c();
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            mdata_put_multisig(
+                &app,
+                &md_info,
+                PERMISSIONS_EMPTY,
+                ENTRIES_EMPTY,
+                &co_owner_pk_h,
+                1,
+                ud,
+                cb,
+            )
+        }))
+    };
+
+    let perm_set = permission_set_into_repr_c(PermissionSet::new().allow(Action::Insert));
+    let request = unsafe {
+        unwrap!(call_vec_u8(|ud, cb| {
+            mdata_owner_request_set_user_permissions(
+                &app,
+                &md_info,
+                USER_ANYONE,
+                &perm_set,
+                1,
+                ud,
+                cb,
+            )
+        }))
+    };
+
+    // Not signed by the co-owner yet.
+    let res = unsafe {
+        call_0(|ud, cb| {
+            mdata_owner_request_submit(&app, request.as_ptr(), request.len(), ud, cb)
+        })
+    };
+    match res {
+        Err(ERR_MISSING_OWNER_SIGNATURES) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    let request = unsafe {
+        unwrap!(call_vec_u8(|ud, cb| {
+            mdata_owner_request_sign(
+                &app,
+                request.as_ptr(),
+                request.len(),
+                co_owner_pk_h,
+                co_owner_sk_h,
+                ud,
+                cb,
+            )
+        }))
+    };
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            mdata_owner_request_submit(&app, request.as_ptr(), request.len(), ud, cb)
+        }))
+    };
+
+    let perm_set: FfiPermissionSet = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            mdata_list_user_permissions(&app, &md_info, USER_ANYONE, ud, cb)
+        }))
+    };
+    let perm_set = unwrap!(permission_set_clone_from_repr_c(&perm_set));
+    assert_eq!(perm_set.is_allowed(Action::Insert), Some(true));
+}
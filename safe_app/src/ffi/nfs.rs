@@ -17,18 +17,26 @@
 
 use {App, AppContext};
 use errors::AppError;
-use ffi::helper::send;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb, from_c_str,
-                vec_clone_from_raw_parts};
+use ffi::helper::{send, send_with_user_data};
+use ffi_utils::{BorrowedSlice, FFI_RESULT_OK, FfiBuffer, FfiResult, OpaqueCtx, ReprC, SafePtr,
+                catch_unwind_cb, from_c_str, from_c_wstr};
 use futures::Future;
-use futures::future::{self, Either};
+use futures::future::{self, Either, Loop};
 use object_cache::FileContextHandle;
-use safe_core::{FutureExt, MDataInfo};
+use safe_core::{Fetched, FutureExt, MDataInfo};
 use safe_core::ffi::MDataInfo as FfiMDataInfo;
-use safe_core::ffi::nfs::File;
-use safe_core::nfs::{Mode, Reader, Writer, file_helper};
+use safe_core::ffi::nfs::{DirEntry, DirTreeNode, File};
+use safe_core::nfs::{Mode, Reader, Writer, fetch_dir_metadata, fetch_tree, file_helper,
+                     for_each_dir_entry, get_dir, get_dir_if_changed, resolve_dir,
+                     update_dir_metadata};
+use safe_core::nfs::DirEntry as NativeDirEntry;
 use safe_core::nfs::File as NativeFile;
+use safe_core::utils::buffer_pool;
+use std::fs;
+use std::io::Read;
 use std::os::raw::{c_char, c_void};
+use std::ptr;
+use std::slice;
 
 /// Holds context for file operations, depending on the mode.
 pub struct FileContext {
@@ -43,35 +51,217 @@ pub static OPEN_MODE_OVERWRITE: u64 = 1;
 pub static OPEN_MODE_APPEND: u64 = 2;
 /// Open file to read.
 pub static OPEN_MODE_READ: u64 = 4;
+/// Compresses new content before encrypting it. Used together with `OPEN_MODE_OVERWRITE`.
+pub static OPEN_MODE_COMPRESS: u64 = 8;
 /// Read entire contents of a file.
 pub static FILE_READ_TO_END: u64 = 0;
+/// Size of the chunks `file_upload_from_path` reads local files in.
+pub const UPLOAD_CHUNK_SIZE: usize = 1024 * 1024;
 
-/// Retrieve file with the given name, and its version, from the directory.
+/// List the files and sub-directories of the directory. The entries are valid only
+/// during the callback.
 ///
-/// Callback parameters: user data, error code, file, version
+/// Callback parameters: user data, error code, entries vector, vector size
 #[no_mangle]
-pub unsafe extern "C" fn dir_fetch_file(
+pub unsafe extern "C" fn dir_list(
     app: *const App,
     parent_info: *const FfiMDataInfo,
-    file_name: *const c_char,
     user_data: *mut c_void,
     o_cb: extern "C" fn(user_data: *mut c_void,
                         result: *const FfiResult,
-                        file: *const File,
-                        version: u64),
+                        entries: *const DirEntry,
+                        entries_len: usize),
 ) {
     catch_unwind_cb(user_data, o_cb, || {
         let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
-        let file_name = from_c_str(file_name)?;
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |client, _| {
-            file_helper::fetch(client.clone(), parent_info, file_name)
-                .map(move |(version, file)| {
-                    let ffi_file = file.into_repr_c();
-                    o_cb(user_data.0, FFI_RESULT_OK, &ffi_file, version)
+        send_with_user_data(app, user_data, move |client, _| {
+            get_dir(client, &parent_info)
+                .map_err(AppError::from)
+                .and_then(move |response| {
+                    let entries = response
+                        .entries
+                        .into_iter()
+                        .map(|entry| entry.into_repr_c())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    o_cb(user_data.0, FFI_RESULT_OK, entries.as_safe_ptr(), entries.len());
+                    Ok(())
                 })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Same as `dir_list`, unless the directory is still at `known_version`, in which case
+/// `modified` is false and no entries are passed. Otherwise `version` is the one to pass
+/// next time. Pass 0 to always get the listing.
+///
+/// Callback parameters: user data, error code, modified flag, version, entries vector,
+/// vector size
+#[no_mangle]
+pub unsafe extern "C" fn dir_fetch_if_changed(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    known_version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        modified: bool,
+                        version: u64,
+                        entries: *const DirEntry,
+                        entries_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            get_dir_if_changed(client, &parent_info, known_version)
+                .map_err(AppError::from)
+                .and_then(move |fetched| {
+                    match fetched {
+                        Fetched::NotModified => {
+                            o_cb(user_data.0, FFI_RESULT_OK, false, known_version, ptr::null(), 0)
+                        }
+                        Fetched::Modified { version, data } => {
+                            let entries = data.entries
+                                .into_iter()
+                                .map(|entry| entry.into_repr_c())
+                                .collect::<Result<Vec<_>, _>>()?;
+                            o_cb(
+                                user_data.0,
+                                FFI_RESULT_OK,
+                                true,
+                                version,
+                                entries.as_safe_ptr(),
+                                entries.len(),
+                            )
+                        }
+                    }
+                    Ok(())
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Same as `dir_list`, but hands the listing over encoded as CBOR: a map with an
+/// `entries` array, each entry being a map with the fields of `DirEntry`. Missing times
+/// are `null`.
+///
+/// Callback parameters: user data, error code, encoded listing vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn dir_list_cbor(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        encoded: *const u8,
+                        encoded_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            get_dir(client, &parent_info)
+                .map_err(AppError::from)
+                .and_then(move |response| {
+                    let encoded = response.to_cbor()?;
+                    o_cb(user_data.0, FFI_RESULT_OK, encoded.as_ptr(), encoded.len());
+                    Ok(())
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// List the files and sub-directories of the directory progressively, so the first entries
+/// can be shown before the whole directory has been processed.
+///
+/// The `o_each_cb` callback is invoked once for each entry, passing user data and the
+/// entry, which is valid only during the callback. Entries come in no particular order.
+///
+/// The `o_done_cb` callback is invoked after the listing is done, or in case of error.
+#[no_mangle]
+pub unsafe extern "C" fn dir_list_progressive(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_each_cb: extern "C" fn(user_data: *mut c_void, entry: *const DirEntry),
+    o_done_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_done_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            let each = move |entry: NativeDirEntry| {
+                entry.into_repr_c().map(|entry| o_each_cb(user_data.0, &entry))
+            };
+
+            for_each_dir_entry(client, &parent_info, each)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AppError::from), user_data, o_done_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Fetch the directory found at `path` below `parent_info`, along with its sub-directories,
+/// down to `depth` levels in a single call. `path` is a `/`-separated list of sub-directory
+/// names; an empty path denotes `parent_info` itself. Depth 1 lists the directory only, 2
+/// its sub-directories too, and so on. The tree is valid only during the callback.
+///
+/// Callback parameters: user data, error code, entries vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn dir_fetch_tree(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    path: *const c_char,
+    depth: u32,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        entries: *const DirTreeNode,
+                        entries_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let path = from_c_str(path)?;
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            let client2 = client.clone();
+
+            resolve_dir(client, &parent_info, &path)
+                .and_then(move |dir| fetch_tree(&client2, &dir, depth as usize))
                 .map_err(AppError::from)
+                .and_then(move |nodes| {
+                    let nodes = nodes
+                        .into_iter()
+                        .map(|node| node.into_repr_c())
+                        .collect::<Result<Vec<_>, _>>()?;
+                    o_cb(user_data.0, FFI_RESULT_OK, nodes.as_safe_ptr(), nodes.len());
+                    Ok(())
+                })
                 .map_err(move |err| {
                     call_result_cb!(Err::<(), _>(err), user_data, o_cb);
                 })
@@ -81,6 +271,74 @@ pub unsafe extern "C" fn dir_fetch_file(
     })
 }
 
+/// Retrieve file with the given name, and its version, from the directory.
+///
+/// Callback parameters: user data, error code, file, version
+#[no_mangle]
+pub unsafe extern "C" fn dir_fetch_file(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        file: *const File,
+                        version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let file_name = from_c_str(file_name)?;
+        fetch_file(app, parent_info, file_name, user_data, o_cb)
+    })
+}
+
+/// Same as `dir_fetch_file`, but takes the file name as a nul-terminated UTF-16 string.
+///
+/// Callback parameters: user data, error code, file, version
+#[no_mangle]
+pub unsafe extern "C" fn dir_fetch_file_w(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: *const u16,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        file: *const File,
+                        version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let file_name = from_c_wstr(file_name)?;
+        fetch_file(app, parent_info, file_name, user_data, o_cb)
+    })
+}
+
+unsafe fn fetch_file(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: String,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        file: *const File,
+                        version: u64),
+) -> Result<(), AppError> {
+    let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+    let user_data = OpaqueCtx(user_data);
+
+    send_with_user_data(app, user_data, move |client, _| {
+        file_helper::fetch(client.clone(), parent_info, file_name)
+            .map(move |(version, file)| {
+                let ffi_file = file.into_repr_c();
+                o_cb(user_data.0, FFI_RESULT_OK, &ffi_file, version)
+            })
+            .map_err(AppError::from)
+            .map_err(move |err| {
+                call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+            })
+            .into_box()
+            .into()
+    })
+}
+
 /// Insert the file into the parent directory.
 ///
 /// Callback parameters: user data, error code
@@ -94,13 +352,42 @@ pub unsafe extern "C" fn dir_insert_file(
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
 ) {
     catch_unwind_cb(user_data, o_cb, || {
-        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
-        let file = NativeFile::clone_from_repr_c(file)?;
         let file_name = from_c_str(file_name)?;
+        insert_file(app, parent_info, file_name, file, user_data, o_cb)
+    })
+}
 
-        send(app, user_data, o_cb, move |client, _| {
-            file_helper::insert(client.clone(), parent_info, file_name, &file)
-        })
+/// Same as `dir_insert_file`, but takes the file name as a nul-terminated UTF-16 string.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn dir_insert_file_w(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: *const u16,
+    file: *const File,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let file_name = from_c_wstr(file_name)?;
+        insert_file(app, parent_info, file_name, file, user_data, o_cb)
+    })
+}
+
+unsafe fn insert_file(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: String,
+    file: *const File,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) -> Result<(), AppError> {
+    let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+    let file = NativeFile::clone_from_repr_c(file)?;
+
+    send(app, user_data, o_cb, move |client, _| {
+        file_helper::insert(client.clone(), parent_info, file_name, &file)
     })
 }
 
@@ -119,13 +406,44 @@ pub unsafe extern "C" fn dir_update_file(
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
 ) {
     catch_unwind_cb(user_data, o_cb, || {
-        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
-        let file = NativeFile::clone_from_repr_c(file)?;
         let file_name = from_c_str(file_name)?;
+        update_file(app, parent_info, file_name, file, version, user_data, o_cb)
+    })
+}
 
-        send(app, user_data, o_cb, move |client, _| {
-            file_helper::update(client.clone(), parent_info, file_name, &file, version)
-        })
+/// Same as `dir_update_file`, but takes the file name as a nul-terminated UTF-16 string.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn dir_update_file_w(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: *const u16,
+    file: *const File,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let file_name = from_c_wstr(file_name)?;
+        update_file(app, parent_info, file_name, file, version, user_data, o_cb)
+    })
+}
+
+unsafe fn update_file(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: String,
+    file: *const File,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) -> Result<(), AppError> {
+    let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+    let file = NativeFile::clone_from_repr_c(file)?;
+
+    send(app, user_data, o_cb, move |client, _| {
+        file_helper::update(client.clone(), parent_info, file_name, &file, version)
     })
 }
 
@@ -142,11 +460,173 @@ pub unsafe extern "C" fn dir_delete_file(
     o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
 ) {
     catch_unwind_cb(user_data, o_cb, || {
-        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
         let file_name = from_c_str(file_name)?;
+        delete_file(app, parent_info, file_name, version, user_data, o_cb)
+    })
+}
+
+/// Same as `dir_delete_file`, but takes the file name as a nul-terminated UTF-16 string.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn dir_delete_file_w(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: *const u16,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let file_name = from_c_wstr(file_name)?;
+        delete_file(app, parent_info, file_name, version, user_data, o_cb)
+    })
+}
+
+unsafe fn delete_file(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    file_name: String,
+    version: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) -> Result<(), AppError> {
+    let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+
+    send(app, user_data, o_cb, move |client, _| {
+        file_helper::delete(client, &parent_info, file_name, version)
+    })
+}
+
+/// Replace the user metadata of the file at `path` and set its modification time to now,
+/// without rewriting its contents. `path` is relative to `parent_info`, with the file name
+/// as its last `/`-separated component.
+///
+/// Callback parameters: user data, error code, updated file, new version
+#[no_mangle]
+pub unsafe extern "C" fn file_update_metadata(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    path: *const c_char,
+    user_metadata_ptr: *const u8,
+    user_metadata_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        file: *const File,
+                        version: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let path = from_c_str(path)?;
+        let user_metadata = slice::from_raw_parts(user_metadata_ptr, user_metadata_len).to_vec();
+        let user_data = OpaqueCtx(user_data);
+
+        let (dir_path, file_name) = match path.rfind('/') {
+            Some(index) => (path[..index].to_owned(), path[index + 1..].to_owned()),
+            None => (String::new(), path),
+        };
+
+        send_with_user_data(app, user_data, move |client, _| {
+            let client2 = client.clone();
+
+            resolve_dir(client, &parent_info, &dir_path)
+                .and_then(move |dir| {
+                    file_helper::update_metadata(client2, dir, file_name, user_metadata)
+                })
+                .map(move |(version, file)| {
+                    let ffi_file = file.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &ffi_file, version)
+                })
+                .map_err(AppError::from)
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Fetch the user metadata of the directory at `path`, relative to `parent_info`, together
+/// with the time it was last updated, in milliseconds since the Unix epoch. An empty path
+/// denotes `parent_info` itself. Both are empty if no metadata has been set.
+///
+/// Callback parameters: user data, error code, user metadata, user metadata size,
+/// modification time
+#[no_mangle]
+pub unsafe extern "C" fn dir_fetch_metadata(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        user_metadata_ptr: *const u8,
+                        user_metadata_len: usize,
+                        modified_ms: i64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let path = from_c_str(path)?;
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            let client2 = client.clone();
+
+            resolve_dir(client, &parent_info, &path)
+                .and_then(move |dir| fetch_dir_metadata(&client2, &dir))
+                .map(move |metadata| match metadata {
+                    Some(metadata) => {
+                        let modified = metadata.modified;
+                        let modified_ms = modified.timestamp() * 1000 +
+                            i64::from(modified.timestamp_subsec_millis());
+                        o_cb(
+                            user_data.0,
+                            FFI_RESULT_OK,
+                            metadata.user_metadata.as_safe_ptr(),
+                            metadata.user_metadata.len(),
+                            modified_ms,
+                        )
+                    }
+                    None => o_cb(user_data.0, FFI_RESULT_OK, ptr::null(), 0, 0),
+                })
+                .map_err(AppError::from)
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Replace the user metadata of the directory at `path`, relative to `parent_info`, and set
+/// its modification time to now. An empty path denotes `parent_info` itself. Files and
+/// sub-directories of the directory are left untouched.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn dir_update_metadata(
+    app: *const App,
+    parent_info: *const FfiMDataInfo,
+    path: *const c_char,
+    user_metadata_ptr: *const u8,
+    user_metadata_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let path = from_c_str(path)?;
+        let user_metadata = slice::from_raw_parts(user_metadata_ptr, user_metadata_len).to_vec();
 
         send(app, user_data, o_cb, move |client, _| {
-            file_helper::delete(client, &parent_info, file_name, version)
+            let client2 = client.clone();
+
+            resolve_dir(client, &parent_info, &path)
+                .and_then(move |dir| update_dir_metadata(&client2, &dir, user_metadata))
+                .map(|_| ())
         })
     })
 }
@@ -186,6 +666,8 @@ pub unsafe extern "C" fn file_open(
             let writer = if open_mode & (OPEN_MODE_OVERWRITE | OPEN_MODE_APPEND) != 0 {
                 let writer_mode = if open_mode & OPEN_MODE_APPEND != 0 {
                     Mode::Append
+                } else if open_mode & OPEN_MODE_COMPRESS != 0 {
+                    Mode::OverwriteCompressed
                 } else {
                     Mode::Overwrite
                 };
@@ -212,6 +694,83 @@ pub unsafe extern "C" fn file_open(
     })
 }
 
+/// Upload the local file at `local_path` into the directory as a new file named
+/// `dest_name`. The file is read and self-encrypted chunk by chunk, so it never has to be
+/// held in memory whole. `o_progress` is called after every chunk with the number of
+/// bytes uploaded so far and the size of the local file.
+///
+/// Callback parameters: user data, error code, uploaded file
+#[no_mangle]
+pub unsafe extern "C" fn file_upload_from_path(
+    app: *const App,
+    local_path: *const c_char,
+    parent_info: *const FfiMDataInfo,
+    dest_name: *const c_char,
+    user_data: *mut c_void,
+    o_progress: extern "C" fn(user_data: *mut c_void, uploaded: u64, total: u64),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        file: *const File),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let local_path = from_c_str(local_path)?;
+        let parent_info = MDataInfo::clone_from_repr_c(parent_info)?;
+        let dest_name = from_c_str(dest_name)?;
+        let source = fs::File::open(local_path)?;
+        let total = source.metadata()?.len();
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            let client2 = client.clone();
+            let enc_key = parent_info.enc_key().cloned();
+
+            file_helper::write(
+                client.clone(),
+                NativeFile::new(Vec::new()),
+                Mode::Overwrite,
+                enc_key,
+            ).map_err(AppError::from)
+                .and_then(move |writer| {
+                    future::loop_fn((source, writer, 0), move |(mut source, writer, uploaded)| {
+                        let mut chunk = vec![0; UPLOAD_CHUNK_SIZE];
+                        let len = match source.read(&mut chunk) {
+                            Ok(len) => len,
+                            Err(err) => return err!(AppError::from(err)),
+                        };
+                        if len == 0 {
+                            return ok!(Loop::Break(writer));
+                        }
+                        chunk.truncate(len);
+
+                        writer
+                            .write(&chunk)
+                            .map_err(AppError::from)
+                            .map(move |()| {
+                                let uploaded = uploaded + len as u64;
+                                o_progress(user_data.0, uploaded, total);
+                                Loop::Continue((source, writer, uploaded))
+                            })
+                            .into_box()
+                    })
+                })
+                .and_then(|writer| writer.close().map_err(AppError::from))
+                .and_then(move |file| {
+                    file_helper::insert(client2, parent_info, dest_name, &file)
+                        .map(move |()| file)
+                        .map_err(AppError::from)
+                })
+                .map(move |file| {
+                    o_cb(user_data.0, FFI_RESULT_OK, &file.into_repr_c());
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Get a size of file opened for read.
 ///
 /// Callback parameters: user data, error code, file size
@@ -225,7 +784,7 @@ pub unsafe extern "C" fn file_size(
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_client, context| {
+        send_with_user_data(app, user_data, move |_client, context| {
             let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
 
             if let Some(ref reader) = file_ctx.reader {
@@ -256,7 +815,7 @@ pub unsafe extern "C" fn file_read(
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_client, context| {
+        send_with_user_data(app, user_data, move |_client, context| {
             let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
 
             if let Some(ref reader) = file_ctx.reader {
@@ -271,6 +830,52 @@ pub unsafe extern "C" fn file_read(
                     )
                     .map(move |data| {
                         o_cb(user_data.0, FFI_RESULT_OK, data.as_safe_ptr(), data.len());
+                        buffer_pool::recycle(data);
+                    })
+                    .map_err(move |err| {
+                        call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                    })
+                    .into_box()
+                    .into()
+            } else {
+                call_result_cb!(Err::<(), _>(AppError::InvalidFileMode), user_data, o_cb);
+                None
+            }
+        })
+    })
+}
+
+/// Read data from file, like `file_read`, but hand the data over without
+/// copying it. The buffer has to be released with `app_buffer_free`.
+///
+/// Callback parameters: user data, error code, data buffer
+#[no_mangle]
+pub unsafe extern "C" fn file_read_buffer(
+    app: *const App,
+    file_h: FileContextHandle,
+    position: u64,
+    len: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, data: FfiBuffer),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |_client, context| {
+            let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
+
+            if let Some(ref reader) = file_ctx.reader {
+                reader
+                    .read(
+                        position,
+                        if len == FILE_READ_TO_END {
+                            reader.size() - position
+                        } else {
+                            len
+                        },
+                    )
+                    .map(move |data| {
+                        o_cb(user_data.0, FFI_RESULT_OK, FfiBuffer::from_vec(data));
                     })
                     .map_err(move |err| {
                         call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
@@ -299,14 +904,55 @@ pub unsafe extern "C" fn file_write(
 ) {
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
-        let data = vec_clone_from_raw_parts(data, data_len);
+        let data = buffer_pool::copy(slice::from_raw_parts(data, data_len));
+
+        send_with_user_data(app, user_data, move |_client, context| {
+            let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
+
+            if let Some(ref writer) = file_ctx.writer {
+                // The writer keeps its own copy of the data.
+                let write = writer.write(&data);
+                buffer_pool::recycle(data);
+
+                write
+                    .then(move |res| {
+                        call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
+                        Ok(())
+                    })
+                    .into_box()
+                    .into()
+            } else {
+                call_result_cb!(Err::<(), _>(AppError::InvalidFileMode), user_data, o_cb);
+                None
+            }
+        })
+    })
+}
+
+/// Write data to file in smaller chunks, like `file_write`, but without copying
+/// the data. The caller has to keep `data` valid and unchanged until `o_cb` is
+/// called.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn file_write_borrowed(
+    app: *const App,
+    file_h: FileContextHandle,
+    data: *const u8,
+    data_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let data = BorrowedSlice::new(data, data_len);
 
-        (*app).send(move |_client, context| {
+        send_with_user_data(app, user_data, move |_client, context| {
             let file_ctx = try_cb!(context.object_cache().get_file(file_h), user_data, o_cb);
 
             if let Some(ref writer) = file_ctx.writer {
                 writer
-                    .write(&data)
+                    .write(data.as_slice())
                     .then(move |res| {
                         call_result_cb!(res.map_err(AppError::from), user_data, o_cb);
                         Ok(())
@@ -344,7 +990,7 @@ pub unsafe extern "C" fn file_close(
     catch_unwind_cb(user_data, o_cb, || {
         let user_data = OpaqueCtx(user_data);
 
-        (*app).send(move |_client, context| {
+        send_with_user_data(app, user_data, move |_client, context| {
             let file_ctx = try_cb!(context.object_cache().remove_file(file_h), user_data, o_cb);
 
             if let Some(writer) = file_ctx.writer {
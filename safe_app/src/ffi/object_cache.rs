@@ -0,0 +1,130 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use App;
+use errors::AppError;
+use ffi::helper::{send_sync, send_with_user_data};
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::time::SystemTime;
+
+/// Turn handle-leak diagnostics on or off. While on, the object cache records
+/// which FFI function created each handle and when, to be reported by
+/// `object_cache_debug_dump`. Turning it off forgets the handles recorded so far.
+///
+/// Resolving the function names requires the library not to be stripped of its
+/// symbols.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn object_cache_debug_enable(
+    app: *const App,
+    enable: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        send_sync(app, user_data, o_cb, move |_, context| {
+            context.object_cache().set_debug(enable);
+            Ok(())
+        })
+    })
+}
+
+/// Report the live handles created while diagnostics were on, one line per type
+/// and creating function, e.g.
+///
+/// `mdata_entries created by mutable_data::entries::mdata_entries_new: 2 live
+/// (handles 3, 5), oldest 12 s old`
+///
+/// Callback parameters: user data, error code, report
+#[no_mangle]
+pub unsafe extern "C" fn object_cache_debug_dump(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        report: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AppError> {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |_, context| {
+            let now = SystemTime::now();
+            let mut report = String::new();
+
+            for group in context.object_cache().debug_dump() {
+                let handles: Vec<_> = group.handles.iter().map(|h| h.to_string()).collect();
+                let age = now.duration_since(group.oldest).map(|age| age.as_secs()).unwrap_or(0);
+                report.push_str(&format!(
+                    "{} created by {}: {} live (handles {}), oldest {} s old\n",
+                    group.kind,
+                    group.function,
+                    group.handles.len(),
+                    handles.join(", "),
+                    age
+                ));
+            }
+
+            let report = try_cb!(
+                CString::new(report).map_err(AppError::from),
+                user_data.0,
+                o_cb
+            );
+            o_cb(user_data.0, FFI_RESULT_OK, report.as_ptr());
+            None
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi::mutable_data::entries::{mdata_entries_free, mdata_entries_new};
+    use ffi_utils::test_utils::{call_0, call_1};
+    use object_cache::MDataEntriesHandle;
+    use test_utils::create_app;
+
+    // Test that handles created through the FFI are reported until freed.
+    #[test]
+    fn debug_dump() {
+        let app = create_app();
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| object_cache_debug_enable(&app, true, ud, cb)));
+
+            let entries_h: MDataEntriesHandle =
+                unwrap!(call_1(|ud, cb| mdata_entries_new(&app, ud, cb)));
+
+            let report: String = unwrap!(call_1(|ud, cb| object_cache_debug_dump(&app, ud, cb)));
+            let lines: Vec<_> = report.lines().collect();
+            assert_eq!(lines.len(), 1);
+            assert!(lines[0].starts_with("mdata_entries created by "));
+            assert!(lines[0].contains(&format!(": 1 live (handles {})", entries_h)));
+            // Without debug info, the closure might have been inlined.
+            if cfg!(debug_assertions) {
+                assert!(lines[0].contains("mutable_data::entries::mdata_entries_new"));
+            }
+
+            unwrap!(call_0(|ud, cb| mdata_entries_free(&app, entries_h, ud, cb)));
+
+            let report: String = unwrap!(call_1(|ud, cb| object_cache_debug_dump(&app, ud, cb)));
+            assert!(report.is_empty());
+        }
+    }
+}
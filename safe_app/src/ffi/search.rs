@@ -0,0 +1,239 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb, from_c_str,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use safe_core::{FutureExt, MDataInfo, search};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use std::os::raw::{c_char, c_void};
+
+/// FFI object representing a search result.
+#[repr(C)]
+pub struct SearchHit {
+    /// Reference of the matching entry.
+    pub entry_ref: *const u8,
+    /// Reference length.
+    pub entry_ref_len: usize,
+}
+
+/// Create a new empty search index owned by the app.
+///
+/// Callback parameters: user data, error code, index mdata info
+#[no_mangle]
+pub unsafe extern "C" fn search_index_create(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        index: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            search::create(client)
+                .map(move |index| {
+                    let index = index.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &index);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Index `text` under the given entry reference, replacing whatever was indexed
+/// under it before.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn search_index_add(
+    app: *const App,
+    index: *const FfiMDataInfo,
+    entry_ref: *const u8,
+    entry_ref_len: usize,
+    text: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let index = MDataInfo::clone_from_repr_c(index)?;
+        let entry_ref = vec_clone_from_raw_parts(entry_ref, entry_ref_len);
+        let text = from_c_str(text)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            search::index_add(client, &index, entry_ref, &text)
+                .map(move |_| o_cb(user_data.0, FFI_RESULT_OK))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Remove the given entry reference from the index.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn search_index_remove(
+    app: *const App,
+    index: *const FfiMDataInfo,
+    entry_ref: *const u8,
+    entry_ref_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let index = MDataInfo::clone_from_repr_c(index)?;
+        let entry_ref = vec_clone_from_raw_parts(entry_ref, entry_ref_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            search::index_remove(client, &index, entry_ref)
+                .map(move |_| o_cb(user_data.0, FFI_RESULT_OK))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Search the index for entries containing all the words of `query`.
+///
+/// Callback parameters: user data, error code, hits vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn search_index_search(
+    app: *const App,
+    index: *const FfiMDataInfo,
+    query: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        hits: *const SearchHit,
+                        hits_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let index = MDataInfo::clone_from_repr_c(index)?;
+        let query = from_c_str(query)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            search::search(client, &index, &query)
+                .map(move |refs| {
+                    let hits: Vec<_> = refs.iter()
+                        .map(|entry_ref| {
+                            SearchHit {
+                                entry_ref: entry_ref.as_safe_ptr(),
+                                entry_ref_len: entry_ref.len(),
+                            }
+                        })
+                        .collect();
+
+                    o_cb(user_data.0, FFI_RESULT_OK, hits.as_safe_ptr(), hits.len());
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{call_0, call_1, send_via_user_data, sender_as_user_data};
+    use std::ffi::CString;
+    use std::slice;
+    use std::sync::mpsc;
+    use test_utils::create_app;
+
+    // Test indexing and searching through the FFI.
+    #[test]
+    fn add_search_remove() {
+        let app = create_app();
+
+        let index: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| search_index_create(&app, ud, cb))) };
+        let index = index.into_repr_c();
+
+        let text = unwrap!(CString::new("The quick brown fox"));
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                search_index_add(&app, &index, b"fox".as_ptr(), 3, text.as_ptr(), ud, cb)
+            }))
+        };
+
+        assert_eq!(unsafe { search(&app, &index, "QUICK fox") }, vec![b"fox".to_vec()]);
+        assert!(unsafe { search(&app, &index, "slow fox") }.is_empty());
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                search_index_remove(&app, &index, b"fox".as_ptr(), 3, ud, cb)
+            }))
+        };
+        assert!(unsafe { search(&app, &index, "fox") }.is_empty());
+    }
+
+    unsafe fn search(app: &App, index: &FfiMDataInfo, query: &str) -> Vec<Vec<u8>> {
+        extern "C" fn search_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            hits: *const SearchHit,
+            hits_len: usize,
+        ) {
+            unsafe {
+                let result: Result<Vec<_>, i32> = if (*res).error_code == 0 {
+                    Ok(
+                        slice::from_raw_parts(hits, hits_len)
+                            .iter()
+                            .map(|hit| vec_clone_from_raw_parts(hit.entry_ref, hit.entry_ref_len))
+                            .collect(),
+                    )
+                } else {
+                    Err((*res).error_code)
+                };
+
+                send_via_user_data(user_data, result);
+            }
+        }
+
+        let query = unwrap!(CString::new(query));
+        let (tx, rx) = mpsc::channel::<Result<Vec<Vec<u8>>, i32>>();
+        let mut ud = Default::default();
+        search_index_search(
+            app,
+            index,
+            query.as_ptr(),
+            sender_as_user_data(&tx, &mut ud),
+            search_cb,
+        );
+        unwrap!(unwrap!(rx.recv()))
+    }
+}
@@ -0,0 +1,278 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use safe_core::FutureExt;
+use settings::Settings;
+pub use settings::SettingsFormat;
+use std::os::raw::{c_char, c_void};
+
+/// Get the settings document `name` from the app's own container, encoded in `format`.
+/// The document is empty if it isn't set.
+///
+/// Callback parameters: user data, error code, document vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn app_settings_get(
+    app: *const App,
+    name: *const c_char,
+    format: SettingsFormat,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        document: *const u8,
+                        document_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let client2 = client.clone();
+
+            Settings::open(client, context)
+                .and_then(move |settings| settings.get(&client2, &name))
+                .and_then(move |document| {
+                    let encoded = match document {
+                        Some(document) => format.encode(&document)?,
+                        None => Vec::new(),
+                    };
+                    o_cb(user_data.0, FFI_RESULT_OK, encoded.as_ptr(), encoded.len());
+                    Ok(())
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Set the settings document `name` in the app's own container, replacing the previous
+/// one, if any. The document is encoded in `format`.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_settings_set(
+    app: *const App,
+    name: *const c_char,
+    format: SettingsFormat,
+    document: *const u8,
+    document_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+        let document = format.decode(&vec_clone_from_raw_parts(document, document_len))?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let client2 = client.clone();
+
+            Settings::open(client, context)
+                .and_then(move |settings| settings.set(&client2, &name, &document))
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Merge `patch` into the settings document `name` in the app's own container, following
+/// JSON Merge Patch (RFC 7396): maps are merged recursively, null removes a key and other
+/// values replace the existing ones. The patch and the merged document are encoded in
+/// `format`.
+///
+/// Callback parameters: user data, error code, merged document vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn app_settings_merge(
+    app: *const App,
+    name: *const c_char,
+    format: SettingsFormat,
+    patch: *const u8,
+    patch_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        document: *const u8,
+                        document_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+        let patch = format.decode(&vec_clone_from_raw_parts(patch, patch_len))?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let client2 = client.clone();
+
+            Settings::open(client, context)
+                .and_then(move |settings| settings.merge(&client2, &name, patch))
+                .and_then(move |document| {
+                    let encoded = format.encode(&document)?;
+                    o_cb(user_data.0, FFI_RESULT_OK, encoded.as_ptr(), encoded.len());
+                    Ok(())
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Remove the settings document `name` from the app's own container.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_settings_remove(
+    app: *const App,
+    name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let name = from_c_str(name)?;
+
+        send_with_user_data(app, user_data, move |client, context| {
+            let client2 = client.clone();
+
+            Settings::open(client, context)
+                .and_then(move |settings| settings.remove(&client2, &name))
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use errors::ERR_NO_SUCH_ENTRY;
+    use ffi_utils::test_utils::{call_0, call_vec_u8};
+    use std::collections::HashMap;
+    use std::ffi::CString;
+    use test_utils::{create_app_by_req, create_auth_req_with_access};
+
+    // Test setting, merging, getting and removing a settings document through the FFI.
+    #[test]
+    fn set_merge_get_remove() {
+        let app = create_app_by_req(&create_auth_req_with_access(HashMap::new()));
+        let name = unwrap!(CString::new("prefs"));
+
+        let document: Vec<u8> = unsafe {
+            unwrap!(call_vec_u8(|ud, cb| {
+                app_settings_get(&app, name.as_ptr(), SettingsFormat::Json, ud, cb)
+            }))
+        };
+        assert!(document.is_empty());
+
+        let document = br#"{"theme":"dark","font":{"size":12}}"#;
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                app_settings_set(
+                    &app,
+                    name.as_ptr(),
+                    SettingsFormat::Json,
+                    document.as_ptr(),
+                    document.len(),
+                    ud,
+                    cb,
+                )
+            }))
+        }
+
+        let patch = br#"{"font":{"size":14},"theme":null}"#;
+        let merged: Vec<u8> = unsafe {
+            unwrap!(call_vec_u8(|ud, cb| {
+                app_settings_merge(
+                    &app,
+                    name.as_ptr(),
+                    SettingsFormat::Json,
+                    patch.as_ptr(),
+                    patch.len(),
+                    ud,
+                    cb,
+                )
+            }))
+        };
+        assert_eq!(merged, br#"{"font":{"size":14}}"#.to_vec());
+
+        // Stored as CBOR, the document can be fetched in either format.
+        let cbor: Vec<u8> = unsafe {
+            unwrap!(call_vec_u8(|ud, cb| {
+                app_settings_get(&app, name.as_ptr(), SettingsFormat::Cbor, ud, cb)
+            }))
+        };
+        let document = unwrap!(SettingsFormat::Cbor.decode(&cbor));
+        assert_eq!(unwrap!(SettingsFormat::Json.encode(&document)), merged);
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| app_settings_remove(&app, name.as_ptr(), ud, cb)));
+        }
+        let res = unsafe { call_0(|ud, cb| app_settings_remove(&app, name.as_ptr(), ud, cb)) };
+        match res {
+            Err(ERR_NO_SUCH_ENTRY) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+    // Test setting a document again after it has been removed.
+    #[test]
+    fn set_after_remove() {
+        let app = create_app_by_req(&create_auth_req_with_access(HashMap::new()));
+        let name = unwrap!(CString::new("prefs"));
+
+        let set = |document: &[u8]| unsafe {
+            unwrap!(call_0(|ud, cb| {
+                app_settings_set(
+                    &app,
+                    name.as_ptr(),
+                    SettingsFormat::Json,
+                    document.as_ptr(),
+                    document.len(),
+                    ud,
+                    cb,
+                )
+            }))
+        };
+
+        set(br#"{"theme":"dark"}"#);
+        unsafe {
+            unwrap!(call_0(|ud, cb| app_settings_remove(&app, name.as_ptr(), ud, cb)));
+        }
+        set(br#"{"theme":"light"}"#);
+
+        let document: Vec<u8> = unsafe {
+            unwrap!(call_vec_u8(|ud, cb| {
+                app_settings_get(&app, name.as_ptr(), SettingsFormat::Json, ud, cb)
+            }))
+        };
+        assert_eq!(document, br#"{"theme":"light"}"#.to_vec());
+    }
+}
@@ -29,6 +29,7 @@ use safe_core::ffi::ipc::resp::AuthGranted as FfiAuthGranted;
 use safe_core::ipc::{AuthGranted, Permission, gen_req_id};
 use safe_core::ipc::req::{AuthReq, ContainerPermissions};
 use std::collections::HashMap;
+use std::ptr;
 use test_utils::create_app;
 use test_utils::gen_app_exchange_info;
 
@@ -80,10 +81,214 @@ fn account_info() {
     unsafe { app_free(app) };
 }
 
+// Test that the network statistics reflect a request made by the app.
+#[test]
+fn network_stats() {
+    use ffi_utils::test_utils::{send_via_user_data, sender_as_user_data};
+    use std::sync::mpsc;
+
+    extern "C" fn stats_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        stats: *const FfiNetworkStats,
+    ) {
+        unsafe {
+            let result = if (*res).error_code == 0 {
+                let requests = slice::from_raw_parts((*stats).requests, (*stats).requests_len);
+                let kinds: Vec<_> = requests
+                    .iter()
+                    .map(|req| unwrap!(CStr::from_ptr(req.kind).to_str()).to_owned())
+                    .collect();
+                Ok(((*stats).requests_sent, (*stats).bytes_sent, kinds))
+            } else {
+                Err((*res).error_code)
+            };
+
+            send_via_user_data(user_data, result);
+        }
+    }
+
+    let app = create_app();
+
+    unsafe {
+        unwrap!(app.send(move |client, _| {
+            client
+                .put_idata(ImmutableData::new(vec![1, 2, 3]))
+                .map_err(move |_| ())
+                .into_box()
+                .into()
+        }));
+    }
+
+    let (tx, rx) = mpsc::channel::<Result<(u64, u64, Vec<String>), i32>>();
+    let mut ud = Default::default();
+    unsafe { client_network_stats(&app, sender_as_user_data(&tx, &mut ud), stats_cb) };
+
+    let (requests_sent, bytes_sent, kinds) = unwrap!(unwrap!(rx.recv()));
+    assert!(requests_sent > 0);
+    assert!(bytes_sent > 0);
+    assert!(kinds.contains(&"PutIData".to_owned()));
+}
+
+// Test that unregistered apps can't get account usage statistics, as there's
+// no owning account to ask on behalf of.
+#[test]
+fn account_info_unregistered() {
+    use errors::ERR_OPERATION_FORBIDDEN;
+    use maidsafe_utilities::serialisation::serialise;
+    use safe_core::ipc::BootstrapConfig;
+
+    let bootstrap_cfg = unwrap!(serialise(&BootstrapConfig::default()));
+    let app: *mut App = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            app_unregistered(
+                bootstrap_cfg.as_ptr(),
+                bootstrap_cfg.len(),
+                ud,
+                disconnect_cb,
+                cb,
+            )
+        }))
+    };
+
+    let res: Result<AccountInfo, i32> =
+        unsafe { call_1(|ud, cb| app_account_info(app, ud, cb)) };
+    match res {
+        Err(code) => assert_eq!(code, ERR_OPERATION_FORBIDDEN),
+        Ok(_) => panic!("Unregistered app got account info"),
+    }
+
+    unsafe { app_free(app) };
+
+    extern "C" fn disconnect_cb(_user_data: *mut c_void) {
+        panic!("Disconnect occurred")
+    }
+}
+
+// Test creating and shutting down an app with the non-blocking variants.
+#[test]
+fn async_unregistered_and_shutdown() {
+    let app: *mut App = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            app_unregistered_async(ptr::null(), 0, ud, disconnect_cb, cb)
+        }))
+    };
+    assert!(!app.is_null());
+
+    let abandoned: u64 = unsafe { unwrap!(call_1(|ud, cb| app_shutdown_async(app, 1000, ud, cb))) };
+    assert_eq!(abandoned, 0);
+
+    extern "C" fn disconnect_cb(_user_data: *mut c_void) {
+        panic!("Disconnect occurred")
+    }
+}
+
+// Test that the user data destructor is invoked for operations dropped before
+// completing when the app is freed, and only for those.
+#[test]
+fn user_data_destructor() {
+    use ffi::helper::send_with_user_data;
+    use ffi_utils::test_utils::call_0;
+    use futures::future;
+    use safe_core::FutureExt;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::mpsc;
+
+    extern "C" fn destructor(user_data: *mut c_void) {
+        let count = unsafe { &*(user_data as *const AtomicUsize) };
+        let _ = count.fetch_add(1, Ordering::SeqCst);
+    }
+
+    let completed = AtomicUsize::new(0);
+    let pending = AtomicUsize::new(0);
+    let completed_ud = OpaqueCtx(&completed as *const AtomicUsize as *mut c_void);
+    let pending_ud = OpaqueCtx(&pending as *const AtomicUsize as *mut c_void);
+
+    let app = Box::into_raw(Box::new(create_app()));
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            app_set_user_data_destructor(app, Some(destructor), ud, cb)
+        }))
+    };
+
+    let (tx, rx) = mpsc::channel();
+    unsafe {
+        unwrap!(send_with_user_data(app, completed_ud, move |_, _| {
+            unwrap!(tx.send(()));
+            None
+        }));
+        unwrap!(send_with_user_data(app, pending_ud, |_, _| {
+            Some(future::empty().into_box())
+        }));
+    }
+    unwrap!(rx.recv());
+
+    // Freeing the app waits for its event loop to finish.
+    unsafe { app_free(app) };
+
+    assert_eq!(completed.load(Ordering::SeqCst), 0);
+    assert_eq!(pending.load(Ordering::SeqCst), 1);
+}
+
+// Test that null pointers and invalid handles are reported as errors instead of
+// crashing the process.
+#[test]
+fn invalid_ffi_input() {
+    use errors::{ERR_INVALID_CIPHER_OPT_HANDLE, ERR_NULL_POINTER};
+    use ffi::cipher_opt::{cipher_opt_free, cipher_opt_new_plaintext};
+    use ffi_utils::test_utils::call_0;
+
+    let res: Result<AccountInfo, i32> =
+        unsafe { call_1(|ud, cb| app_account_info(ptr::null_mut(), ud, cb)) };
+    assert_eq!(res.err(), Some(ERR_NULL_POINTER));
+
+    let msg = unsafe { app_last_error_message().into_string() };
+    assert_eq!(msg, Some("Null pointer".to_owned()));
+
+    let res: Result<u64, i32> =
+        unsafe { call_1(|ud, cb| cipher_opt_new_plaintext(ptr::null(), ud, cb)) };
+    assert_eq!(res.err(), Some(ERR_NULL_POINTER));
+
+    // Freeing a null app is a no-op.
+    unsafe { app_free(ptr::null_mut()) };
+
+    let app = create_app();
+    let app = Box::into_raw(Box::new(app));
+
+    let res = unsafe { call_0(|ud, cb| cipher_opt_free(app, 0xdead_beef, ud, cb)) };
+    assert_eq!(res.err(), Some(ERR_INVALID_CIPHER_OPT_HANDLE));
+
+    unsafe { app_free(app) };
+}
+
+// Test that a panic while holding the app's lock doesn't render the app unusable.
+#[test]
+fn poisoned_lock_recovery() {
+    use std::panic::{self, AssertUnwindSafe};
+
+    let app = create_app();
+
+    let res = panic::catch_unwind(AssertUnwindSafe(|| {
+        let _guard = unwrap!(app.core_tx.lock());
+        panic!("simulated panic");
+    }));
+    assert!(res.is_err());
+    assert!(app.core_tx.is_poisoned());
+
+    let app = Box::into_raw(Box::new(app));
+    let stats: Result<AccountInfo, i32> =
+        unsafe { call_1(|ud, cb| app_account_info(app, ud, cb)) };
+    assert!(stats.is_ok());
+
+    unsafe { app_free(app) };
+}
+
 // Test disconnection and reconnection with apps.
 #[cfg(all(test, feature = "use-mock-routing"))]
 #[test]
 fn network_status_callback() {
+    skip_unless_mock!();
+
     use ffi_utils::test_utils::{UserData, call_0, call_1_with_custom, send_via_user_data_custom};
     use maidsafe_utilities::serialisation::serialise;
     use safe_core::ipc::BootstrapConfig;
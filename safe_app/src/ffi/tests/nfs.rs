@@ -17,19 +17,32 @@
 
 use App;
 use errors::AppError;
+use ffi::app_buffer_free;
 use ffi::nfs::*;
-use ffi_utils::ErrorCode;
-use ffi_utils::test_utils::{call_0, call_1, call_2, call_vec_u8};
+use ffi_utils::{ErrorCode, FfiBuffer, FfiResult, ReprC};
+use ffi_utils::test_utils::{call_0, call_1, call_2, call_vec, call_vec_u8, send_via_user_data,
+                            sender_as_user_data};
 use futures::Future;
 use object_cache::FileContextHandle;
+use routing::{Action, PermissionSet, User};
+use safe_core::{DIR_TAG, MDataInfo as NativeMDataInfo};
 use safe_core::ffi::MDataInfo;
-use safe_core::ffi::nfs::File;
+use safe_core::ffi::nfs::{DirEntry as FfiDirEntry, File};
 use safe_core::ipc::Permission;
+use safe_core::utils::wire_format::{Cbor, WireFormat};
+use safe_core::nfs::{DirEntry, DirTreeNode, GetDirResponse, journal};
 use safe_core::nfs::File as NativeFile;
 use safe_core::nfs::NfsError;
 use std;
 use std::collections::HashMap;
+use std::env;
 use std::ffi::CString;
+use std::fs;
+use std::io::Write;
+use std::os::raw::c_void;
+use std::slice;
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+use std::sync::mpsc;
 use test_utils::{create_app_by_req, create_auth_req_with_access, run};
 
 fn setup() -> (App, MDataInfo) {
@@ -118,6 +131,465 @@ fn basics() {
     }
 }
 
+// Test listing a directory as an array of structures and as CBOR.
+#[test]
+fn list_dir() {
+    let (app, container_info) = setup();
+
+    let file_name = unwrap!(CString::new("file.txt"));
+    let file = NativeFile::new(b"metadata".to_vec());
+    let created_ms = file.created_time().timestamp() * 1000 +
+        i64::from(file.created_time().timestamp_subsec_millis());
+    let ffi_file = file.into_repr_c();
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_insert_file(&app, &container_info, file_name.as_ptr(), &ffi_file, ud, cb)
+        }))
+    }
+
+    let entries: Vec<DirEntry> =
+        unsafe { unwrap!(call_vec(|ud, cb| dir_list(&app, &container_info, ud, cb))) };
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].name, "file.txt");
+    assert!(!entries[0].is_dir);
+    assert_eq!(entries[0].size, 0);
+    assert_eq!(entries[0].created_ms, Some(created_ms));
+    assert!(entries[0].created.is_some());
+    assert_eq!(entries[0].user_metadata, b"metadata".to_vec());
+
+    let encoded =
+        unsafe { unwrap!(call_vec_u8(|ud, cb| dir_list_cbor(&app, &container_info, ud, cb))) };
+    let response: GetDirResponse = unwrap!(Cbor::deserialise(&encoded));
+    assert_eq!(response.entries, entries);
+}
+
+// Test listing a directory progressively, one entry per callback.
+#[test]
+fn list_dir_progressive() {
+    let (app, container_info) = setup();
+
+    for name in &["a.txt", "b.txt"] {
+        let file_name = unwrap!(CString::new(*name));
+        let ffi_file = NativeFile::new(Vec::new()).into_repr_c();
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                dir_insert_file(&app, &container_info, file_name.as_ptr(), &ffi_file, ud, cb)
+            }))
+        }
+    }
+
+    let (tx, rx) = mpsc::channel::<i32>();
+    let mut user_data = (tx, Vec::<DirEntry>::new());
+
+    extern "C" fn each_cb(user_data: *mut c_void, entry: *const FfiDirEntry) {
+        unsafe {
+            let entry = unwrap!(DirEntry::clone_from_repr_c(entry));
+            let user_data = user_data as *mut (mpsc::Sender<i32>, Vec<DirEntry>);
+            (*user_data).1.push(entry);
+        }
+    }
+
+    extern "C" fn done_cb(user_data: *mut c_void, res: *const FfiResult) {
+        unsafe {
+            let user_data = user_data as *mut (mpsc::Sender<i32>, Vec<DirEntry>);
+            unwrap!((*user_data).0.send((*res).error_code));
+        }
+    }
+
+    unsafe {
+        let user_data: *mut _ = &mut user_data;
+        dir_list_progressive(&app, &container_info, user_data as *mut c_void, each_cb, done_cb);
+    }
+
+    assert_eq!(unwrap!(rx.recv()), 0);
+    let mut names: Vec<_> = user_data.1.into_iter().map(|entry| entry.name).collect();
+    names.sort();
+    assert_eq!(names, vec!["a.txt", "b.txt"]);
+}
+
+// Test fetching a directory listing only when it has changed.
+#[test]
+fn fetch_if_changed() {
+    let (app, container_info) = setup();
+
+    extern "C" fn cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        modified: bool,
+        version: u64,
+        entries: *const FfiDirEntry,
+        entries_len: usize,
+    ) {
+        unsafe {
+            let entries = if entries.is_null() {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(entries, entries_len)
+                    .iter()
+                    .map(|entry| unwrap!(DirEntry::clone_from_repr_c(entry)).name)
+                    .collect()
+            };
+            let tx = user_data as *mut mpsc::Sender<(i32, bool, u64, Vec<String>)>;
+            unwrap!((*tx).send(((*res).error_code, modified, version, entries)));
+        }
+    }
+
+    let fetch = |known_version| {
+        let (mut tx, rx) = mpsc::channel::<(i32, bool, u64, Vec<String>)>();
+        unsafe {
+            let tx: *mut _ = &mut tx;
+            dir_fetch_if_changed(&app, &container_info, known_version, tx as *mut c_void, cb);
+        }
+        let (error_code, modified, version, entries) = unwrap!(rx.recv());
+        assert_eq!(error_code, 0);
+        (modified, version, entries)
+    };
+
+    let (modified, version, entries) = fetch(0);
+    assert!(modified);
+    assert!(entries.is_empty());
+
+    let (modified, _, entries) = fetch(version);
+    assert!(!modified);
+    assert!(entries.is_empty());
+
+    let file_name = unwrap!(CString::new("file.txt"));
+    let ffi_file = NativeFile::new(Vec::new()).into_repr_c();
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_insert_file(&app, &container_info, file_name.as_ptr(), &ffi_file, ud, cb)
+        }))
+    }
+
+    let (modified, new_version, entries) = fetch(version);
+    assert!(modified);
+    assert_ne!(new_version, version);
+    assert_eq!(entries, vec!["file.txt"]);
+}
+
+// Test updating the metadata of a file and of a directory.
+// 1. Create a file and replace its metadata by path. The contents must be kept.
+// 2. Fetching the metadata of a directory without any should succeed with empty metadata.
+// 3. Set the metadata of the directory and fetch it back.
+#[test]
+fn update_metadata() {
+    let (app, container_info) = setup();
+
+    let file_name = unwrap!(CString::new("file.txt"));
+    let file = NativeFile::new(b"old".to_vec());
+    let ffi_file = file.clone().into_repr_c();
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_insert_file(&app, &container_info, file_name.as_ptr(), &ffi_file, ud, cb)
+        }))
+    }
+
+    let (updated_file, version): (NativeFile, u64) = unsafe {
+        unwrap!(call_2(|ud, cb| {
+            file_update_metadata(
+                &app,
+                &container_info,
+                file_name.as_ptr(),
+                b"new".as_ptr(),
+                3,
+                ud,
+                cb,
+            )
+        }))
+    };
+    assert_eq!(version, 1);
+    assert_eq!(updated_file.user_metadata(), b"new");
+    assert_eq!(updated_file.data_map_name(), file.data_map_name());
+
+    let (fetched_file, _): (NativeFile, u64) = unsafe {
+        unwrap!(call_2(|ud, cb| {
+            dir_fetch_file(&app, &container_info, file_name.as_ptr(), ud, cb)
+        }))
+    };
+    assert_eq!(fetched_file.user_metadata(), b"new");
+
+    extern "C" fn metadata_cb(
+        user_data: *mut c_void,
+        res: *const FfiResult,
+        user_metadata_ptr: *const u8,
+        user_metadata_len: usize,
+        modified_ms: i64,
+    ) {
+        unsafe {
+            let tx = user_data as *mut mpsc::Sender<(i32, Vec<u8>, i64)>;
+            let user_metadata = if user_metadata_ptr.is_null() {
+                Vec::new()
+            } else {
+                slice::from_raw_parts(user_metadata_ptr, user_metadata_len).to_vec()
+            };
+            unwrap!((*tx).send(((*res).error_code, user_metadata, modified_ms)));
+        }
+    }
+
+    let root_path = unwrap!(CString::new(""));
+    let fetch_metadata = || {
+        let (mut tx, rx) = mpsc::channel::<(i32, Vec<u8>, i64)>();
+        unsafe {
+            let tx: *mut _ = &mut tx;
+            dir_fetch_metadata(
+                &app,
+                &container_info,
+                root_path.as_ptr(),
+                tx as *mut c_void,
+                metadata_cb,
+            );
+        }
+        unwrap!(rx.recv())
+    };
+
+    assert_eq!(fetch_metadata(), (0, Vec::new(), 0));
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_update_metadata(
+                &app,
+                &container_info,
+                root_path.as_ptr(),
+                b"dir".as_ptr(),
+                3,
+                ud,
+                cb,
+            )
+        }))
+    }
+
+    let (error_code, user_metadata, modified_ms) = fetch_metadata();
+    assert_eq!(error_code, 0);
+    assert_eq!(user_metadata, b"dir");
+    assert!(modified_ms > 0);
+}
+
+// Test fetching a directory tree in one call.
+// 1. Create `photos/2017/beach.jpg` in the container.
+// 2. Fetch the whole tree and check its structure.
+// 3. Fetch it with a lower depth limit and check that deeper levels are left out.
+// 4. Fetch a sub-tree by path.
+// 5. Fetching a non-existing path should fail.
+#[test]
+fn fetch_tree() {
+    let (app, container_info) = setup();
+    let parent = unsafe { unwrap!(NativeMDataInfo::clone_from_repr_c(&container_info)) };
+
+    let year = run(&app, move |client, _| {
+        let photos = unwrap!(NativeMDataInfo::random_private(DIR_TAG));
+        let year = unwrap!(NativeMDataInfo::random_private(DIR_TAG));
+        let perms = btree_map![
+            User::Key(unwrap!(client.public_signing_key())) => PermissionSet::new()
+                .allow(Action::Insert)
+                .allow(Action::Update)
+                .allow(Action::Delete)
+        ];
+
+        let client2 = client.clone();
+        let photos2 = photos.clone();
+        let year2 = year.clone();
+
+        journal::create_sub_dir(client, &parent, "photos", &photos, perms.clone())
+            .and_then(move |_| journal::create_sub_dir(&client2, &photos2, "2017", &year2, perms))
+            .then(move |res| {
+                unwrap!(res);
+                Ok::<_, AppError>(year)
+            })
+    });
+    let year = year.into_repr_c();
+
+    let file_name = unwrap!(CString::new("beach.jpg"));
+    let ffi_file = NativeFile::new(Vec::new()).into_repr_c();
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_insert_file(&app, &year, file_name.as_ptr(), &ffi_file, ud, cb)
+        }))
+    }
+
+    let root = unwrap!(CString::new(""));
+    let tree: Vec<DirTreeNode> = unsafe {
+        unwrap!(call_vec(|ud, cb| {
+            dir_fetch_tree(&app, &container_info, root.as_ptr(), 3, ud, cb)
+        }))
+    };
+    assert_eq!(tree.len(), 1);
+    assert_eq!(tree[0].entry.name, "photos");
+    assert!(tree[0].entry.is_dir);
+    assert_eq!(tree[0].children.len(), 1);
+    assert_eq!(tree[0].children[0].entry.name, "2017");
+    assert_eq!(tree[0].children[0].children.len(), 1);
+    assert_eq!(tree[0].children[0].children[0].entry.name, "beach.jpg");
+    assert!(!tree[0].children[0].children[0].entry.is_dir);
+
+    let shallow: Vec<DirTreeNode> = unsafe {
+        unwrap!(call_vec(|ud, cb| {
+            dir_fetch_tree(&app, &container_info, root.as_ptr(), 2, ud, cb)
+        }))
+    };
+    assert_eq!(shallow[0].children[0].entry.name, "2017");
+    assert!(shallow[0].children[0].children.is_empty());
+
+    let path = unwrap!(CString::new("photos/2017"));
+    let sub_tree: Vec<DirTreeNode> = unsafe {
+        unwrap!(call_vec(|ud, cb| {
+            dir_fetch_tree(&app, &container_info, path.as_ptr(), 1, ud, cb)
+        }))
+    };
+    assert_eq!(sub_tree, tree[0].children[0].children);
+
+    let path = unwrap!(CString::new("photos/2018"));
+    let res: Result<Vec<DirTreeNode>, i32> = unsafe {
+        call_vec(|ud, cb| dir_fetch_tree(&app, &container_info, path.as_ptr(), 1, ud, cb))
+    };
+    match res {
+        Err(code) if code == AppError::from(NfsError::FileNotFound).error_code() => (),
+        Err(x) => panic!("Unexpected: {:?}", x),
+        Ok(_) => panic!("Unexpected success"),
+    }
+}
+
+// Test uploading a local file spanning several chunks.
+// 1. Upload the file into the container.
+// 2. Check that progress got reported for every chunk.
+// 3. Fetch the file back and check its content.
+#[test]
+fn upload_from_path() {
+    static PROGRESS_CALLS: AtomicUsize = ATOMIC_USIZE_INIT;
+    static UPLOADED: AtomicUsize = ATOMIC_USIZE_INIT;
+
+    extern "C" fn progress(_user_data: *mut c_void, uploaded: u64, total: u64) {
+        assert!(uploaded <= total);
+        let _ = PROGRESS_CALLS.fetch_add(1, Ordering::SeqCst);
+        UPLOADED.store(uploaded as usize, Ordering::SeqCst);
+    }
+
+    let (app, container_info) = setup();
+
+    let content: Vec<u8> = (0..2 * UPLOAD_CHUNK_SIZE + 100).map(|i| i as u8).collect();
+    let local_path = env::temp_dir().join("safe_app_upload_from_path_test");
+    unwrap!(unwrap!(fs::File::create(&local_path)).write_all(&content));
+    let ffi_local_path = unwrap!(CString::new(unwrap!(local_path.to_str())));
+    let file_name = unwrap!(CString::new("uploaded.bin"));
+
+    let uploaded: NativeFile = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            file_upload_from_path(
+                &app,
+                ffi_local_path.as_ptr(),
+                &container_info,
+                file_name.as_ptr(),
+                ud,
+                progress,
+                cb,
+            )
+        }))
+    };
+    unwrap!(fs::remove_file(&local_path));
+
+    assert_eq!(uploaded.size(), content.len() as u64);
+    assert_eq!(PROGRESS_CALLS.load(Ordering::SeqCst), 3);
+    assert_eq!(UPLOADED.load(Ordering::SeqCst), content.len());
+
+    let (file, _): (NativeFile, u64) = unsafe {
+        unwrap!(call_2(|ud, cb| {
+            dir_fetch_file(&app, &container_info, file_name.as_ptr(), ud, cb)
+        }))
+    };
+    assert_eq!(file, uploaded);
+
+    let read_h = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            file_open(
+                &app,
+                &container_info,
+                &file.into_repr_c(),
+                OPEN_MODE_READ,
+                ud,
+                cb,
+            )
+        }))
+    };
+    let retrieved_content = unsafe {
+        unwrap!(call_vec_u8(|ud, cb| {
+            file_read(&app, read_h, 0, FILE_READ_TO_END, ud, cb)
+        }))
+    };
+    assert_eq!(retrieved_content, content);
+
+    let _: NativeFile = unsafe { unwrap!(call_1(|ud, cb| file_close(&app, read_h, ud, cb))) };
+}
+
+// Test that files inserted with a UTF-16 name are found under the same UTF-8 name and
+// vice versa.
+#[test]
+fn wide_file_names() {
+    let (app, container_info) = setup();
+
+    let file_name = "\u{17c}\u{f3}\u{142}w \u{1f600}.txt";
+    let ffi_file_name = unwrap!(CString::new(file_name));
+    let mut wide_file_name: Vec<u16> = file_name.encode_utf16().collect();
+    wide_file_name.push(0);
+
+    let ffi_file = NativeFile::new(Vec::new()).into_repr_c();
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_insert_file_w(
+                &app,
+                &container_info,
+                wide_file_name.as_ptr(),
+                &ffi_file,
+                ud,
+                cb,
+            )
+        }))
+    }
+
+    let (_, version): (NativeFile, u64) = unsafe {
+        unwrap!(call_2(|ud, cb| {
+            dir_fetch_file(&app, &container_info, ffi_file_name.as_ptr(), ud, cb)
+        }))
+    };
+    assert_eq!(version, 0);
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_update_file(
+                &app,
+                &container_info,
+                ffi_file_name.as_ptr(),
+                &ffi_file,
+                version + 1,
+                ud,
+                cb,
+            )
+        }))
+    }
+
+    let (_, version): (NativeFile, u64) = unsafe {
+        unwrap!(call_2(|ud, cb| {
+            dir_fetch_file_w(&app, &container_info, wide_file_name.as_ptr(), ud, cb)
+        }))
+    };
+    assert_eq!(version, 1);
+
+    unsafe {
+        unwrap!(call_0(|ud, cb| {
+            dir_delete_file_w(
+                &app,
+                &container_info,
+                wide_file_name.as_ptr(),
+                version + 1,
+                ud,
+                cb,
+            )
+        }))
+    }
+}
+
 // Test NFS functions for writing and updating file contents.
 // 1. Create an empty file, open it for writing, write contents.
 // 2. Insert file into a container.
@@ -956,6 +1428,90 @@ fn file_write_chunks() {
     assert_eq!(retrieved_content, vec![0u8; 2 * GOAL_SIZE]);
 }
 
+// Test writing and reading a file without copying the data across the FFI.
+#[test]
+fn file_zero_copy() {
+    let (app, container_info) = setup();
+    let content = vec![7u8; 5555];
+
+    let ffi_file = NativeFile::new(Vec::new()).into_repr_c();
+    let write_h = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            file_open(
+                &app,
+                &container_info,
+                &ffi_file,
+                OPEN_MODE_OVERWRITE,
+                ud,
+                cb,
+            )
+        }))
+    };
+
+    let written_file: NativeFile = unsafe {
+        unwrap!(call_0(|ud, cb| {
+            file_write_borrowed(&app, write_h, content.as_ptr(), content.len(), ud, cb)
+        }));
+        unwrap!(call_1(|ud, cb| file_close(&app, write_h, ud, cb)))
+    };
+
+    let read_h = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            file_open(
+                &app,
+                &container_info,
+                &written_file.into_repr_c(),
+                OPEN_MODE_READ,
+                ud,
+                cb,
+            )
+        }))
+    };
+
+    extern "C" fn read_cb(user_data: *mut c_void, res: *const FfiResult, data: FfiBuffer) {
+        unsafe {
+            let result = if (*res).error_code == 0 {
+                let retrieved = slice::from_raw_parts(data.ptr, data.len).to_vec();
+                app_buffer_free(data);
+                Ok(retrieved)
+            } else {
+                // Errors come with a null buffer, which is safe to free.
+                assert!(data.is_null());
+                app_buffer_free(data);
+                Err((*res).error_code)
+            };
+
+            send_via_user_data(user_data, result);
+        }
+    }
+
+    let (tx, rx) = mpsc::channel::<Result<Vec<u8>, i32>>();
+    let mut ud = Default::default();
+    unsafe {
+        file_read_buffer(
+            &app,
+            read_h,
+            0,
+            FILE_READ_TO_END,
+            sender_as_user_data(&tx, &mut ud),
+            read_cb,
+        )
+    };
+    assert_eq!(unwrap!(unwrap!(rx.recv())), content);
+
+    unsafe {
+        file_read_buffer(
+            &app,
+            read_h + 1_000,
+            0,
+            FILE_READ_TO_END,
+            sender_as_user_data(&tx, &mut ud),
+            read_cb,
+        )
+    };
+    assert!(unwrap!(rx.recv()).is_err());
+}
+
 // Helper function for writing to a file in chunks.
 fn write_chunks(
     app: &App,
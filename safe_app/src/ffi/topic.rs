@@ -0,0 +1,194 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, catch_unwind_cb,
+                vec_clone_from_raw_parts};
+use futures::Future;
+use safe_core::{FutureExt, MDataInfo, topic};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use safe_core::topic::Subscription;
+use std::os::raw::c_void;
+use std::time::Duration;
+
+/// Create a new notification topic owned by the app.
+///
+/// Callback parameters: user data, error code, topic mdata info
+#[no_mangle]
+pub unsafe extern "C" fn topic_create(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        topic: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            topic::create(client)
+                .map(move |topic| {
+                    let topic = topic.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &topic);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Publish a notification to a topic owned by the app. Fails with
+/// `ERR_NOTIFICATION_TOO_LARGE` if the payload is larger than 1024 bytes.
+///
+/// Callback parameters: user data, error code, sequence number of the notification
+#[no_mangle]
+pub unsafe extern "C" fn topic_publish(
+    app: *const App,
+    topic: *const FfiMDataInfo,
+    payload: *const u8,
+    payload_len: usize,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, seq: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let topic = MDataInfo::clone_from_repr_c(topic)?;
+        let payload = vec_clone_from_raw_parts(payload, payload_len);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            topic::publish(client, &topic, payload)
+                .map(move |seq| o_cb(user_data.0, FFI_RESULT_OK, seq))
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Subscribe to notifications published to a topic after the one with sequence
+/// number `since` (pass 0 to receive all of them). The topic is polled every
+/// `interval_ms` milliseconds and `o_notify` is called for every new notification.
+/// The subscription stays active until it's passed to `topic_unsubscribe`.
+///
+/// Callback parameters: user data, error code, subscription
+#[no_mangle]
+pub unsafe extern "C" fn topic_subscribe(
+    app: *const App,
+    topic: *const FfiMDataInfo,
+    since: u64,
+    interval_ms: u64,
+    user_data: *mut c_void,
+    o_notify: extern "C" fn(user_data: *mut c_void,
+                            seq: u64,
+                            payload: *const u8,
+                            payload_len: usize),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        subscription: *mut Subscription),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let topic = MDataInfo::clone_from_repr_c(topic)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            let (poll, subscription) = topic::subscribe(
+                client,
+                topic,
+                since,
+                Duration::from_millis(interval_ms),
+                move |seq, payload| o_notify(user_data.0, seq, payload.as_ptr(), payload.len()),
+            );
+            o_cb(
+                user_data.0,
+                FFI_RESULT_OK,
+                Box::into_raw(Box::new(subscription)),
+            );
+
+            poll.map_err(|err| debug!("Topic subscription failed: {:?}", err))
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Cancel a subscription created by `topic_subscribe`. No notifications are
+/// delivered after this call returns. Using `subscription` after a call to this
+/// function is undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn topic_unsubscribe(subscription: *mut Subscription) {
+    let _ = Box::from_raw(subscription);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{UserData, call_1, call_1_with_custom, send_via_user_data_custom};
+    use std::sync::mpsc::{self, Receiver, Sender};
+    use test_utils::create_app;
+
+    // Test subscribing to a topic published to by another app.
+    #[test]
+    fn publish_subscribe() {
+        let publisher = create_app();
+        let subscriber = create_app();
+
+        let topic: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| topic_create(&publisher, ud, cb))) };
+        let topic = topic.into_repr_c();
+
+        let (tx, rx): (Sender<(u64, Vec<u8>)>, Receiver<_>) = mpsc::channel();
+        let mut ud = UserData::default();
+        let ptr: *const _ = &tx;
+        ud.custom = ptr as *mut c_void;
+
+        let subscription: *mut Subscription = unsafe {
+            unwrap!(call_1_with_custom(&mut ud, |ud, cb| {
+                topic_subscribe(&subscriber, &topic, 0, 10, ud, notify_cb, cb)
+            }))
+        };
+
+        let seq: u64 = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                topic_publish(&publisher, &topic, b"hello".as_ptr(), 5, ud, cb)
+            }))
+        };
+        assert_eq!(seq, 1);
+
+        let received = unwrap!(rx.recv_timeout(Duration::from_secs(10)));
+        assert_eq!(received, (1, b"hello".to_vec()));
+
+        unsafe { topic_unsubscribe(subscription) };
+
+        extern "C" fn notify_cb(
+            user_data: *mut c_void,
+            seq: u64,
+            payload: *const u8,
+            payload_len: usize,
+        ) {
+            unsafe {
+                let payload = vec_clone_from_raw_parts(payload, payload_len);
+                send_via_user_data_custom(user_data, (seq, payload));
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+use {App, AppError};
+use ffi::helper::{send_sync, send_with_user_data};
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb};
+use futures::Future;
+use safe_core::{FutureExt, MDataInfo, upload_ledger};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use safe_core::ffi::arrays::XorNameArray;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+/// FFI object representing an upload recorded in the ledger.
+#[repr(C)]
+pub struct UploadRecord {
+    /// Name of the uploaded `ImmutableData`.
+    pub name: XorNameArray,
+    /// Size of the serialised `ImmutableData`, in bytes.
+    pub size: u64,
+    /// Time of the upload, in seconds since the Unix epoch.
+    pub uploaded_at: u64,
+    /// ID of the app which uploaded the data.
+    pub app_id: *const c_char,
+}
+
+/// Create a new empty upload ledger.
+///
+/// Callback parameters: user data, error code, ledger mdata info
+#[no_mangle]
+pub unsafe extern "C" fn upload_ledger_create(
+    app: *const App,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        ledger: *const FfiMDataInfo),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+
+        send_with_user_data(app, user_data, move |client, _| {
+            upload_ledger::create(client)
+                .map(move |ledger| {
+                    let ledger = ledger.into_repr_c();
+                    o_cb(user_data.0, FFI_RESULT_OK, &ledger);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(AppError::from(err)), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Record every `ImmutableData` the app puts from now on in the ledger, under the ID of the
+/// app. Pass null `ledger` to stop recording.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn app_set_upload_ledger(
+    app: *const App,
+    ledger: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let ledger = if ledger.is_null() {
+            None
+        } else {
+            Some(MDataInfo::clone_from_repr_c(ledger)?)
+        };
+
+        send_sync(app, user_data, o_cb, move |client, context| {
+            let ledger = match ledger {
+                Some(ledger) => Some((ledger, context.as_registered()?.app_id.clone())),
+                None => None,
+            };
+            client.set_upload_ledger(ledger);
+            Ok(())
+        })
+    })
+}
+
+/// List the uploads recorded in the ledger. The records are valid only during the callback.
+///
+/// Callback parameters: user data, error code, records vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn upload_ledger_list(
+    app: *const App,
+    ledger: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        records: *const UploadRecord,
+                        records_len: usize),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let ledger = MDataInfo::clone_from_repr_c(ledger)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            upload_ledger::list(client, &ledger)
+                .map_err(AppError::from)
+                .and_then(move |uploads| {
+                    let app_ids = uploads
+                        .values()
+                        .map(|upload| CString::new(upload.app_id.clone()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let records: Vec<_> = uploads
+                        .iter()
+                        .zip(&app_ids)
+                        .map(|((name, upload), app_id)| {
+                            UploadRecord {
+                                name: name.0,
+                                size: upload.size,
+                                uploaded_at: upload.uploaded_at,
+                                app_id: app_id.as_ptr(),
+                            }
+                        })
+                        .collect();
+
+                    o_cb(user_data.0, FFI_RESULT_OK, records.as_safe_ptr(), records.len());
+                    Ok(())
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{call_0, call_1};
+    use routing::ImmutableData;
+    use safe_core::{CoreError, Priority};
+    use std::ffi::CStr;
+    use std::ptr;
+    use std::slice;
+    use std::sync::mpsc;
+    use test_utils::{create_app, run};
+
+    // Test recording uploads made through the FFI and listing them.
+    #[test]
+    fn record_and_list() {
+        let app = create_app();
+
+        let ledger: MDataInfo =
+            unsafe { unwrap!(call_1(|ud, cb| upload_ledger_create(&app, ud, cb))) };
+        let ledger = ledger.into_repr_c();
+        unsafe { unwrap!(call_0(|ud, cb| app_set_upload_ledger(&app, &ledger, ud, cb))) };
+
+        let name = put_idata(&app, b"recorded");
+
+        unsafe { unwrap!(call_0(|ud, cb| app_set_upload_ledger(&app, ptr::null(), ud, cb))) };
+        let _ = put_idata(&app, b"unrecorded");
+
+        extern "C" fn cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            records: *const UploadRecord,
+            records_len: usize,
+        ) {
+            unsafe {
+                assert_eq!((*res).error_code, 0);
+                let records = slice::from_raw_parts(records, records_len)
+                    .iter()
+                    .map(|record| {
+                        let app_id = unwrap!(CStr::from_ptr(record.app_id).to_str()).to_owned();
+                        (record.name, app_id)
+                    })
+                    .collect::<Vec<_>>();
+                let tx = user_data as *mut mpsc::Sender<Vec<(XorNameArray, String)>>;
+                unwrap!((*tx).send(records));
+            }
+        }
+
+        let (mut tx, rx) = mpsc::channel::<Vec<(XorNameArray, String)>>();
+        unsafe {
+            let tx: *mut _ = &mut tx;
+            upload_ledger_list(&app, &ledger, tx as *mut c_void, cb);
+        }
+        let records = unwrap!(rx.recv());
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].0, name);
+        assert!(!records[0].1.is_empty());
+    }
+
+    fn put_idata(app: &App, value: &[u8]) -> XorNameArray {
+        let data = ImmutableData::new(value.to_vec());
+        let name = data.name().0;
+        run(app, move |client, _| {
+            let client2 = client.clone();
+            // The upload is recorded by a background job; the one scheduled after it runs
+            // once it's done.
+            client
+                .put_idata(data)
+                .and_then(move |()| client2.schedule(Priority::Low, || Ok::<_, CoreError>(())))
+                .map_err(AppError::from)
+        });
+        name
+    }
+}
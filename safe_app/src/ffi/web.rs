@@ -0,0 +1,139 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {App, AppError};
+use ffi::helper::send_with_user_data;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str};
+use futures::Future;
+use safe_core::{FutureExt, web};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+/// Fetch the file at the given `safe://[service.]public_name[/path]` URL.
+/// Works with unregistered apps too.
+///
+/// Callback parameters: user data, error code, content, content length, MIME type
+#[no_mangle]
+pub unsafe extern "C" fn web_fetch(
+    app: *const App,
+    url: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        content: *const u8,
+                        content_len: usize,
+                        mime_type: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || {
+        let user_data = OpaqueCtx(user_data);
+        let url = from_c_str(url)?;
+
+        send_with_user_data(app, user_data, move |client, _| {
+            web::fetch(client, &url)
+                .map_err(AppError::from)
+                .and_then(move |(content, mime_type)| {
+                    let mime_type = CString::new(mime_type)?;
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        content.as_ptr(),
+                        content.len(),
+                        mime_type.as_ptr(),
+                    );
+                    Ok(())
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::vec_clone_from_raw_parts;
+    use ffi_utils::test_utils::{send_via_user_data, sender_as_user_data};
+    use safe_authenticator::AuthError;
+    use safe_authenticator::test_utils as authenticator;
+    use safe_core::{DIR_TAG, MDataInfo, dns};
+    use safe_core::nfs::{File, Mode, create_dir, file_helper};
+    use safe_core::utils::generate_random_string;
+    use std::ffi::CStr;
+    use std::sync::mpsc;
+
+    // Test fetching a URL from an unregistered app.
+    #[test]
+    fn fetch() {
+        let auth = authenticator::create_account_and_login();
+        let public_name = unwrap!(generate_random_string(10));
+
+        let name = public_name.clone();
+        authenticator::run(&auth, move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let dir = unwrap!(MDataInfo::random_public(DIR_TAG));
+            let dir2 = dir.clone();
+
+            create_dir(client, &dir, btree_map![], btree_map![])
+                .and_then(move |_| {
+                    file_helper::write(c2, File::new(Vec::new()), Mode::Overwrite, None)
+                })
+                .and_then(|writer| writer.write(b"hello").and_then(move |_| writer.close()))
+                .and_then(move |file| file_helper::insert(c3, dir2, "hello.txt", &file))
+                .map_err(AuthError::from)
+                .and_then(move |_| {
+                    dns::register(&c4, &name)
+                        .and_then(move |_| dns::set_service(&c4, &name, "www", &dir))
+                        .map_err(AuthError::from)
+                })
+        });
+
+        let app = unwrap!(App::unregistered(|| (), None));
+        let url = unwrap!(CString::new(format!("safe://{}/hello.txt", public_name)));
+
+        let (tx, rx) = mpsc::channel::<Result<(Vec<u8>, String), i32>>();
+        let mut ud = Default::default();
+        unsafe { web_fetch(&app, url.as_ptr(), sender_as_user_data(&tx, &mut ud), fetch_cb) };
+
+        let (content, mime_type) = unwrap!(unwrap!(rx.recv()));
+        assert_eq!(content, b"hello");
+        assert_eq!(mime_type, "text/plain");
+
+        extern "C" fn fetch_cb(
+            user_data: *mut c_void,
+            res: *const FfiResult,
+            content: *const u8,
+            content_len: usize,
+            mime_type: *const c_char,
+        ) {
+            unsafe {
+                let result = if (*res).error_code == 0 {
+                    let mime_type = unwrap!(CStr::from_ptr(mime_type).to_str()).to_owned();
+                    Ok((vec_clone_from_raw_parts(content, content_len), mime_type))
+                } else {
+                    Err((*res).error_code)
+                };
+
+                send_via_user_data(user_data, result);
+            }
+        }
+    }
+}
@@ -38,10 +38,15 @@ maidsafe_logo.png",
                                            option_unwrap_used))]
 #![cfg_attr(feature="cargo-clippy", allow(implicit_hasher, too_many_arguments, use_debug))]
 
+extern crate backtrace;
 extern crate config_file_handler;
 #[macro_use]
 extern crate ffi_utils;
+#[cfg(feature = "fuse-mount")]
+extern crate fuse;
 extern crate futures;
+#[cfg(feature = "fuse-mount")]
+extern crate libc;
 #[macro_use]
 extern crate log;
 extern crate maidsafe_utilities;
@@ -54,8 +59,13 @@ extern crate safe_authenticator;
 #[macro_use]
 extern crate safe_core;
 extern crate self_encryption;
+extern crate serde;
+extern crate serde_cbor;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
+#[cfg(feature = "fuse-mount")]
+extern crate time;
 extern crate tiny_keccak;
 extern crate tokio_core;
 #[macro_use]
@@ -65,9 +75,14 @@ pub mod ffi;
 
 pub use ffi::*;
 pub use ffi::access_container::*;
+pub use ffi::append_log::*;
+pub use ffi::big_map::*;
 pub use ffi::cipher_opt::*;
+pub use ffi::contacts::*;
 pub use ffi::crypto::*;
+pub use ffi::dns::*;
 pub use ffi::immutable_data::*;
+pub use ffi::inbox::*;
 pub use ffi::ipc::*;
 pub use ffi::logging::*;
 pub use ffi::mdata_info::*;
@@ -75,12 +90,22 @@ pub use ffi::mutable_data::*;
 pub use ffi::mutable_data::entries::*;
 pub use ffi::mutable_data::entry_actions::*;
 pub use ffi::mutable_data::metadata::*;
+pub use ffi::mutable_data::multisig::*;
 pub use ffi::mutable_data::permissions::*;
 pub use ffi::nfs::*;
+pub use ffi::object_cache::*;
+pub use ffi::search::*;
+pub use ffi::settings::*;
+pub use ffi::topic::*;
+pub use ffi::upload_ledger::*;
+pub use ffi::web::*;
 
 mod errors;
+#[cfg(feature = "fuse-mount")]
+pub mod mount;
 pub mod object_cache;
 pub mod permissions;
+pub mod settings;
 
 #[cfg(test)]
 mod tests;
@@ -91,26 +116,30 @@ pub mod test_utils;
 
 pub use self::errors::*;
 
+use self::ffi::UserDataDestructor;
 use self::object_cache::ObjectCache;
 #[cfg(any(test, feature = "testing"))]
 pub use ffi::test_utils::{test_create_app, test_create_app_with_access};
 use futures::{Future, future};
 use futures::stream::Stream;
 use futures::sync::mpsc as futures_mpsc;
-use maidsafe_utilities::serialisation::deserialise;
 use maidsafe_utilities::thread::{self, Joiner};
-use safe_core::{Client, ClientKeys, CoreMsg, CoreMsgTx, FutureExt, NetworkEvent, NetworkTx,
-                event_loop, utils};
+use routing::ClientError;
+use safe_core::{Client, ClientKeys, CoreError, CoreMsg, CoreMsgTx, FutureExt, MDataInfo,
+                NetworkEvent, NetworkTx, Priority, app_container_name, event_loop, nfs, utils};
 #[cfg(feature = "use-mock-routing")]
 use safe_core::MockRouting as Routing;
 use safe_core::crypto::shared_secretbox;
 use safe_core::ipc::{AccessContInfo, AppKeys, AuthGranted, BootstrapConfig};
-use safe_core::ipc::resp::{AccessContainerEntry, access_container_enc_key};
-use std::cell::RefCell;
+use safe_core::ipc::resp::{ACCESS_CONTAINER_ENTRY_FORMAT, AccessContainerEntry,
+                           access_container_enc_key};
+use safe_core::utils::wire_format::deserialise;
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::rc::Rc;
-use std::sync::Mutex;
+use std::sync::{Mutex, PoisonError};
 use std::sync::mpsc as std_mpsc;
+use std::time::Duration;
 use tokio_core::reactor::{Core, Handle};
 
 macro_rules! try_tx {
@@ -127,6 +156,7 @@ type AppFuture<T> = Future<Item = T, Error = AppError>;
 /// Handle to an application instance.
 pub struct App {
     core_tx: Mutex<CoreMsgTx<AppContext>>,
+    user_data_destructor: Mutex<Option<UserDataDestructor>>,
     _core_joiner: Joiner,
 }
 
@@ -146,7 +176,8 @@ impl App {
         })
     }
 
-    /// Create registered app.
+    /// Create registered app. Apps authorised with a scope pass their scoped ID (see
+    /// `AppExchangeInfo::scoped_id`) as `app_id`.
     pub fn registered<N>(
         app_id: String,
         auth_granted: AuthGranted,
@@ -289,6 +320,7 @@ impl App {
 
         Ok(App {
             core_tx: Mutex::new(core_tx),
+            user_data_destructor: Mutex::new(None),
             _core_joiner: joiner,
         })
     }
@@ -301,20 +333,65 @@ impl App {
             + 'static,
     {
         let msg = CoreMsg::new(f);
-        let core_tx = unwrap!(self.core_tx.lock());
+        let core_tx = self.core_tx.lock().unwrap_or_else(PoisonError::into_inner);
         core_tx.unbounded_send(msg).map_err(AppError::from)
     }
+
+    /// Run `f` on the app's event loop as a background job with the given priority, subject
+    /// to the limits set with `Client::set_scheduler_limits`. Failures of the job are logged.
+    pub fn schedule<F>(&self, priority: Priority, f: F) -> Result<(), AppError>
+    where
+        F: FnOnce(&Client<AppContext>, &AppContext) -> Box<Future<Item = (), Error = CoreError>>
+            + Send
+            + 'static,
+    {
+        self.send(move |client, context| {
+            let c2 = client.clone();
+            let context = context.clone();
+
+            client
+                .schedule(priority, move || f(&c2, &context))
+                .map_err(|err| warn!("Background job failed: {:?}", err))
+                .into_box()
+                .into()
+        })
+    }
+
+    /// Set the function to invoke with the user data of FFI operations which
+    /// are dropped without ever calling their callback, e.g. because the app is
+    /// freed while they are pending. `None` removes it.
+    pub fn set_user_data_destructor(&self, destructor: Option<UserDataDestructor>) {
+        *self.user_data_destructor.lock().unwrap_or_else(
+            PoisonError::into_inner,
+        ) = destructor;
+    }
+
+    /// Returns the function set by `set_user_data_destructor`, if any.
+    pub fn user_data_destructor(&self) -> Option<UserDataDestructor> {
+        *self.user_data_destructor.lock().unwrap_or_else(
+            PoisonError::into_inner,
+        )
+    }
+
+    /// Shut the app down gracefully. Operations already sent to the app are
+    /// given up to `timeout` to finish before the event loop is torn down.
+    /// The network requests of those still pending then are aborted, so they
+    /// fail with `OperationAborted`. Returns the number of operations abandoned
+    /// because they didn't finish even so.
+    pub fn shutdown(self, timeout: Duration) -> Result<usize, AppError> {
+        let (tx, rx) = std_mpsc::channel();
+        {
+            let core_tx = self.core_tx.lock().unwrap_or_else(PoisonError::into_inner);
+            core_tx.unbounded_send(CoreMsg::build_shutdown(timeout, tx))?;
+        }
+
+        Ok(rx.recv()?)
+    }
 }
 
 impl Drop for App {
     fn drop(&mut self) {
-        let core_tx = match self.core_tx.lock() {
-            Ok(core_tx) => core_tx,
-            Err(err) => {
-                info!("Unexpected error in drop: {:?}", err);
-                return;
-            }
-        };
+        let core_tx = self.core_tx.lock().unwrap_or_else(PoisonError::into_inner);
 
         let msg = CoreMsg::build_terminator();
         if let Err(err) = core_tx.unbounded_send(msg) {
@@ -344,6 +421,9 @@ pub struct Registered {
     sym_enc_key: shared_secretbox::Key,
     access_container_info: AccessContInfo,
     access_info: RefCell<AccessContainerEntry>,
+    // Version of the access container entry `access_info` was decoded from, `None` until it's
+    // fetched.
+    access_info_version: Cell<Option<u64>>,
 }
 
 impl AppContext {
@@ -362,6 +442,7 @@ impl AppContext {
             sym_enc_key: sym_enc_key,
             access_container_info: access_container_info,
             access_info: RefCell::new(HashMap::new()),
+            access_info_version: Cell::new(None),
         }))
     }
 
@@ -378,13 +459,24 @@ impl AppContext {
         Ok(&self.as_registered()?.sym_enc_key)
     }
 
-    /// Refresh access info by fetching it from the network.
+    /// Refresh access info by fetching it from the network. The cached access info is only
+    /// replaced if the version of the access container entry changed.
     pub fn refresh_access_info(&self, client: &Client<AppContext>) -> Box<AppFuture<()>> {
         let reg = Rc::clone(fry!(self.as_registered()));
         refresh_access_info(reg, client)
     }
 
-    /// Fetch a list of containers that this app has access to
+    /// Drop the cached access info, so that it's fetched again from the network the next
+    /// time it's needed.
+    pub fn invalidate_access_info(&self) -> Result<(), AppError> {
+        let reg = self.as_registered()?;
+        reg.access_info_version.set(None);
+        reg.access_info.borrow_mut().clear();
+        Ok(())
+    }
+
+    /// Fetch a list of containers that this app has access to. The access info is cached
+    /// after the first fetch, until refreshed or invalidated.
     pub fn get_access_info(
         &self,
         client: &Client<AppContext>,
@@ -399,6 +491,59 @@ impl AppContext {
             .into_box()
     }
 
+    /// Fetch `MDataInfo` of the app's own container. If it's not in the cached
+    /// access info, the access info is refreshed first, as the container might have
+    /// been reserved by a later authorisation. The authenticator only reserves the
+    /// container, so it's created here if this is its first use. Fails with
+    /// `NoSuchContainer` if the app never asked for its own container.
+    pub fn get_app_container_info(
+        &self,
+        client: &Client<AppContext>,
+    ) -> Box<AppFuture<MDataInfo>> {
+        let reg = Rc::clone(fry!(self.as_registered()));
+        let name = app_container_name(&reg.app_id);
+        let c2 = client.clone();
+        let c3 = client.clone();
+
+        fetch_access_info(Rc::clone(&reg), client)
+            .and_then(move |_| {
+                let cached = reg.access_info.borrow().get(&name).map(
+                    |&(ref info, _)| info.clone(),
+                );
+                match cached {
+                    Some(info) => ok!(info),
+                    None => {
+                        refresh_access_info(Rc::clone(&reg), &c2)
+                            .and_then(move |_| {
+                                reg.access_info
+                                    .borrow()
+                                    .get(&name)
+                                    .map(|&(ref info, _)| info.clone())
+                                    .ok_or(AppError::NoSuchContainer)
+                            })
+                            .into_box()
+                    }
+                }
+            })
+            .and_then(move |info| {
+                let c4 = c3.clone();
+
+                c3.get_mdata_version(info.name, info.type_tag)
+                    .then(move |res| match res {
+                        Ok(_) => ok!(info),
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => {
+                            let sign_pk = fry!(c4.public_signing_key());
+                            nfs::create_app_container(&c4, &info, sign_pk)
+                                .map(move |()| info)
+                                .map_err(AppError::from)
+                                .into_box()
+                        }
+                        Err(err) => err!(err),
+                    })
+            })
+            .into_box()
+    }
+
     fn as_registered(&self) -> Result<&Rc<Registered>, AppError> {
         match *self {
             AppContext::Registered(ref a) => Ok(a),
@@ -422,10 +567,16 @@ fn refresh_access_info(context: Rc<Registered>, client: &Client<AppContext>) ->
         )
         .map_err(AppError::from)
         .and_then(move |value| {
+            if context.access_info_version.get() == Some(value.entry_version) {
+                return Ok(());
+            }
+
             let encoded = utils::symmetric_decrypt(&value.content, &context.sym_enc_key)?;
+            let (encoded, _) = ACCESS_CONTAINER_ENTRY_FORMAT.decode(&encoded)?;
             let decoded = deserialise(&encoded)?;
 
             *context.access_info.borrow_mut() = decoded;
+            context.access_info_version.set(Some(value.entry_version));
 
             Ok(())
         })
@@ -433,7 +584,7 @@ fn refresh_access_info(context: Rc<Registered>, client: &Client<AppContext>) ->
 }
 
 fn fetch_access_info(context: Rc<Registered>, client: &Client<AppContext>) -> Box<AppFuture<()>> {
-    if context.access_info.borrow().is_empty() {
+    if context.access_info_version.get().is_none() {
         refresh_access_info(context, client)
     } else {
         future::ok(()).into_box()
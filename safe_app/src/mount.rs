@@ -0,0 +1,913 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Mounting of NFS directories as local filesystems through FUSE, so that containers can be
+//! browsed and edited with the usual tools. Requires libfuse on Linux or OSXFUSE on macOS.
+//!
+//! Directory entries are fetched on lookup and listing. Files opened for reading are read
+//! from the network range by range, while files opened for writing are buffered in memory and
+//! stored when flushed or closed. Renaming isn't atomic: the entry is linked into its new
+//! directory before being removed from the old one.
+
+use {App, AppContext};
+use errors::AppError;
+use fuse::{self, FileAttr, FileType, Filesystem, ReplyAttr, ReplyCreate, ReplyData,
+           ReplyDirectory, ReplyEmpty, ReplyEntry, ReplyOpen, ReplyWrite, Request};
+use futures::Future;
+use libc::{EACCES, EBADF, EEXIST, EINVAL, EIO, EISDIR, ENOENT, ENOTDIR, ENOTEMPTY, O_ACCMODE,
+           O_RDONLY, O_TRUNC, c_int};
+use routing::{Action, ClientError, EntryActions, PermissionSet, User};
+use safe_core::{Client, CoreError, DIR_TAG, FutureExt, MDataInfo};
+use safe_core::nfs::{Entry, File, Mode, NfsError, NfsFuture, decode_entry, encode_file,
+                     file_helper, journal, list_dir};
+use std::cmp;
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::sync::mpsc;
+use time::{self, Timespec};
+
+const ROOT_INO: u64 = 1;
+// How long the kernel may cache attributes and lookups.
+const TTL: Timespec = Timespec { sec: 1, nsec: 0 };
+
+/// Mounts the container `name` of the app at `mountpoint`, blocking until it's unmounted.
+pub fn mount_container<P: AsRef<Path>>(
+    app: &App,
+    name: &str,
+    mountpoint: P,
+) -> Result<(), AppError> {
+    let name = name.to_owned();
+    let (tx, rx) = mpsc::channel();
+
+    app.send(move |client, context| {
+        let fut = context.get_access_info(client).then(move |res| {
+            let res = res.and_then(|mut containers| {
+                containers
+                    .remove(&name)
+                    .map(|(info, _)| info)
+                    .ok_or(AppError::NoSuchContainer)
+            });
+            let _ = tx.send(res);
+            Ok(())
+        });
+        Some(fut.into_box())
+    })?;
+
+    let root = rx.recv()??;
+    mount(app, root, mountpoint)
+}
+
+/// Mounts the directory `root` at `mountpoint`, blocking until it's unmounted.
+pub fn mount<P: AsRef<Path>>(app: &App, root: MDataInfo, mountpoint: P) -> Result<(), AppError> {
+    let options = [OsStr::new("-o"), OsStr::new("fsname=safe")];
+    fuse::mount(SafeFs::new(app, root), &mountpoint, &options)?;
+    Ok(())
+}
+
+struct SafeFs<'a> {
+    app: &'a App,
+    nodes: HashMap<u64, Node>,
+    inos: HashMap<(u64, String), u64>,
+    handles: HashMap<u64, OpenFile>,
+    next_ino: u64,
+    next_fh: u64,
+    uid: u32,
+    gid: u32,
+    // Reported as the times of the directories, which don't record any.
+    mounted: Timespec,
+}
+
+struct Node {
+    parent: u64,
+    name: String,
+    kind: Kind,
+}
+
+#[derive(Clone)]
+enum Kind {
+    Dir(MDataInfo),
+    File(File),
+}
+
+struct OpenFile {
+    ino: u64,
+    // Whole content of a file opened for writing.
+    content: Option<Vec<u8>>,
+    dirty: bool,
+}
+
+impl<'a> SafeFs<'a> {
+    fn new(app: &'a App, root: MDataInfo) -> Self {
+        let mut nodes = HashMap::new();
+        let _ = nodes.insert(
+            ROOT_INO,
+            Node {
+                parent: ROOT_INO,
+                name: String::new(),
+                kind: Kind::Dir(root),
+            },
+        );
+
+        SafeFs {
+            app,
+            nodes,
+            inos: HashMap::new(),
+            handles: HashMap::new(),
+            next_ino: ROOT_INO + 1,
+            next_fh: 1,
+            uid: 0,
+            gid: 0,
+            mounted: time::get_time(),
+        }
+    }
+
+    // Runs the operation returned by `f` in the event loop of the app and waits for its result.
+    fn run<F, R>(&self, f: F) -> Result<R, c_int>
+    where
+        F: FnOnce(&Client<AppContext>) -> Box<NfsFuture<R>> + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = mpsc::channel();
+
+        self.app
+            .send(move |client, _| {
+                let fut = f(client).then(move |res| {
+                    let _ = tx.send(res.map_err(|err| {
+                        debug!("Filesystem operation failed: {:?}", err);
+                        errno(&err)
+                    }));
+                    Ok(())
+                });
+                Some(fut.into_box())
+            })
+            .map_err(|err| {
+                warn!("Could not reach the event loop: {:?}", err);
+                EIO
+            })?;
+
+        rx.recv().map_err(|_| EIO)?
+    }
+
+    fn dir(&self, ino: u64) -> Result<MDataInfo, c_int> {
+        match self.nodes.get(&ino) {
+            Some(&Node { kind: Kind::Dir(ref info), .. }) => Ok(info.clone()),
+            Some(_) => Err(ENOTDIR),
+            None => Err(ENOENT),
+        }
+    }
+
+    // Returns the directory holding the file, its name in it and the file.
+    fn file(&self, ino: u64) -> Result<(MDataInfo, String, File), c_int> {
+        let node = self.nodes.get(&ino).ok_or(ENOENT)?;
+        match node.kind {
+            Kind::File(ref file) => Ok((self.dir(node.parent)?, node.name.clone(), file.clone())),
+            Kind::Dir(_) => Err(EISDIR),
+        }
+    }
+
+    // Records the entry `name` of the directory `parent`, keeping its inode if already known.
+    fn add_node(&mut self, parent: u64, name: &str, kind: Kind) -> FileAttr {
+        let key = (parent, name.to_owned());
+        let known = self.inos.get(&key).cloned();
+        let ino = match known {
+            Some(ino) => ino,
+            None => {
+                let ino = self.next_ino;
+                self.next_ino += 1;
+                let _ = self.inos.insert(key, ino);
+                ino
+            }
+        };
+
+        let attr = self.attr_of(ino, &kind);
+        let _ = self.nodes.insert(
+            ino,
+            Node {
+                parent,
+                name: name.to_owned(),
+                kind,
+            },
+        );
+        attr
+    }
+
+    fn forget_node(&mut self, parent: u64, name: &str) {
+        if let Some(ino) = self.inos.remove(&(parent, name.to_owned())) {
+            let _ = self.nodes.remove(&ino);
+        }
+    }
+
+    fn attr(&self, ino: u64) -> Result<FileAttr, c_int> {
+        let node = self.nodes.get(&ino).ok_or(ENOENT)?;
+        let mut attr = self.attr_of(ino, &node.kind);
+
+        // Unsaved writes change the size.
+        let buffered = self.handles
+            .values()
+            .filter(|handle| handle.ino == ino && handle.dirty)
+            .filter_map(|handle| handle.content.as_ref())
+            .map(|content| content.len() as u64)
+            .next();
+        if let Some(size) = buffered {
+            attr.size = size;
+            attr.blocks = blocks(size);
+        }
+
+        Ok(attr)
+    }
+
+    fn attr_of(&self, ino: u64, kind: &Kind) -> FileAttr {
+        let (kind, perm, size, crtime, mtime) = match *kind {
+            Kind::Dir(_) => (FileType::Directory, 0o755, 0, self.mounted, self.mounted),
+            Kind::File(ref file) => {
+                let created = file.created_time();
+                let modified = file.modified_time();
+                (
+                    FileType::RegularFile,
+                    0o644,
+                    file.size(),
+                    Timespec::new(created.timestamp(), created.timestamp_subsec_nanos() as i32),
+                    Timespec::new(modified.timestamp(), modified.timestamp_subsec_nanos() as i32),
+                )
+            }
+        };
+
+        FileAttr {
+            ino,
+            size,
+            blocks: blocks(size),
+            atime: mtime,
+            mtime,
+            ctime: mtime,
+            crtime,
+            kind,
+            perm,
+            nlink: 1,
+            uid: self.uid,
+            gid: self.gid,
+            rdev: 0,
+            flags: 0,
+        }
+    }
+
+    fn open_handle(&mut self, ino: u64, content: Option<Vec<u8>>, dirty: bool) -> u64 {
+        let fh = self.next_fh;
+        self.next_fh += 1;
+        let _ = self.handles.insert(
+            fh,
+            OpenFile {
+                ino,
+                content,
+                dirty,
+            },
+        );
+        fh
+    }
+
+    // Decrypted content and version of the entry `name` of `parent`. The content of deleted
+    // entries is empty.
+    fn fetch(&self, parent: &MDataInfo, name: &str) -> Result<(Vec<u8>, u64), c_int> {
+        let key = parent.enc_entry_key(name.as_bytes()).map_err(|_| EIO)?;
+        let parent = parent.clone();
+
+        self.run(move |client| {
+            client
+                .get_mdata_value(parent.name, parent.type_tag, key)
+                .map_err(NfsError::from)
+                .and_then(move |value| {
+                    let content = if value.content.is_empty() {
+                        Vec::new()
+                    } else {
+                        parent.decrypt(&value.content)?
+                    };
+                    Ok((content, value.entry_version))
+                })
+                .into_box()
+        })
+    }
+
+    fn lookup_entry(&self, parent: &MDataInfo, name: &str) -> Result<Kind, c_int> {
+        let (content, _) = self.fetch(parent, name)?;
+        if content.is_empty() {
+            return Err(ENOENT);
+        }
+        decode(&content).ok_or(EIO)
+    }
+
+    fn list(&self, dir: &MDataInfo) -> Result<Vec<(String, Kind)>, c_int> {
+        let dir = dir.clone();
+        let entries = self.run(move |client| list_dir(client, &dir))?;
+
+        Ok(
+            entries
+                .into_iter()
+                .filter_map(|(key, value)| {
+                    String::from_utf8(key).ok().and_then(|name| {
+                        decode(&value.content).map(|kind| (name, kind))
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    fn mutate(&self, parent: &MDataInfo, actions: EntryActions) -> Result<(), c_int> {
+        let (name, tag) = (parent.name, parent.type_tag);
+        self.run(move |client| {
+            client
+                .mutate_mdata_entries(name, tag, actions.into())
+                .map_err(NfsError::from)
+                .into_box()
+        })
+    }
+
+    // Inserts the entry, taking over the one of a deleted entry of the same name.
+    fn insert(&self, parent: &MDataInfo, name: &str, content: &[u8]) -> Result<(), c_int> {
+        let key = parent.enc_entry_key(name.as_bytes()).map_err(|_| EIO)?;
+        let value = parent.enc_entry_value(content).map_err(|_| EIO)?;
+
+        let actions = match self.fetch(parent, name) {
+            Ok((ref current, version)) if current.is_empty() => {
+                EntryActions::new().update(key, value, version + 1)
+            }
+            Ok(_) => return Err(EEXIST),
+            Err(ENOENT) => EntryActions::new().ins(key, value, 0),
+            Err(err) => return Err(err),
+        };
+        self.mutate(parent, actions)
+    }
+
+    fn remove(&self, parent: &MDataInfo, name: &str) -> Result<(), c_int> {
+        let (content, version) = self.fetch(parent, name)?;
+        if content.is_empty() {
+            return Err(ENOENT);
+        }
+        let key = parent.enc_entry_key(name.as_bytes()).map_err(|_| EIO)?;
+        self.mutate(parent, EntryActions::new().del(key, version + 1))
+    }
+
+    fn load(&self, parent: &MDataInfo, file: &File) -> Result<Vec<u8>, c_int> {
+        let key = parent.enc_key().cloned();
+        let file = file.clone();
+
+        self.run(move |client| {
+            file_helper::read(client.clone(), &file, key)
+                .and_then(|reader| {
+                    let size = reader.size();
+                    reader.read(0, size)
+                })
+                .into_box()
+        })
+    }
+
+    // Writes `content` as the new content of `file`, returning the updated file. The data map
+    // is encrypted with the key of `parent`.
+    fn store(&self, parent: &MDataInfo, file: File, content: Vec<u8>) -> Result<File, c_int> {
+        let key = parent.enc_key().cloned();
+
+        self.run(move |client| {
+            file_helper::write(client.clone(), file, Mode::Overwrite, key)
+                .and_then(move |writer| writer.write(&content).map(move |_| writer))
+                .and_then(|writer| writer.close())
+                .into_box()
+        })
+    }
+
+    fn save(&mut self, ino: u64, content: Vec<u8>) -> Result<(), c_int> {
+        let (parent, name, file) = self.file(ino)?;
+        let file = self.store(&parent, file, content)?;
+
+        let file2 = file.clone();
+        self.run(move |client| {
+            file_helper::update(client.clone(), parent, name, &file2, 0)
+        })?;
+
+        if let Some(node) = self.nodes.get_mut(&ino) {
+            node.kind = Kind::File(file);
+        }
+        Ok(())
+    }
+
+    fn lookup_node(&mut self, parent: u64, name: &OsStr) -> Result<FileAttr, c_int> {
+        let info = self.dir(parent)?;
+        let name = name_str(name)?;
+        let kind = self.lookup_entry(&info, &name)?;
+        Ok(self.add_node(parent, &name, kind))
+    }
+
+    fn read_dir(&mut self, ino: u64) -> Result<Vec<(u64, FileType, String)>, c_int> {
+        let dir = self.dir(ino)?;
+        let parent = self.nodes.get(&ino).map_or(ROOT_INO, |node| node.parent);
+
+        let mut output = vec![
+            (ino, FileType::Directory, ".".to_owned()),
+            (parent, FileType::Directory, "..".to_owned()),
+        ];
+        for (name, kind) in self.list(&dir)? {
+            let attr = self.add_node(ino, &name, kind);
+            output.push((attr.ino, attr.kind, name));
+        }
+        Ok(output)
+    }
+
+    fn make_dir(&mut self, parent: u64, name: &OsStr) -> Result<FileAttr, c_int> {
+        let info = self.dir(parent)?;
+        let name = name_str(name)?;
+
+        // Sub-directories are private if their parent is.
+        let dir = if info.enc_key().is_some() {
+            MDataInfo::random_private(DIR_TAG)
+        } else {
+            MDataInfo::random_public(DIR_TAG)
+        }.map_err(|_| EIO)?;
+
+        let dir2 = dir.clone();
+        let name2 = name.clone();
+        self.run(move |client| {
+            let perms = btree_map![
+                User::Key(fry!(client.public_signing_key())) => PermissionSet::new()
+                    .allow(Action::Insert)
+                    .allow(Action::Update)
+                    .allow(Action::Delete)
+                    .allow(Action::ManagePermissions)
+            ];
+            journal::create_sub_dir(client, &info, name2, &dir2, perms)
+        })?;
+
+        Ok(self.add_node(parent, &name, Kind::Dir(dir)))
+    }
+
+    fn create_file(&mut self, parent: u64, name: &OsStr) -> Result<(FileAttr, u64), c_int> {
+        let info = self.dir(parent)?;
+        let name = name_str(name)?;
+
+        let file = self.store(&info, File::new(Vec::new()), Vec::new())?;
+        let content = encode_file(&file).map_err(|_| EIO)?;
+        self.insert(&info, &name, &content)?;
+
+        let attr = self.add_node(parent, &name, Kind::File(file));
+        let fh = self.open_handle(attr.ino, Some(Vec::new()), false);
+        Ok((attr, fh))
+    }
+
+    fn remove_file(&mut self, parent: u64, name: &OsStr) -> Result<(), c_int> {
+        let info = self.dir(parent)?;
+        let name = name_str(name)?;
+
+        if let Kind::Dir(_) = self.lookup_entry(&info, &name)? {
+            return Err(EISDIR);
+        }
+        self.remove(&info, &name)?;
+        self.forget_node(parent, &name);
+        Ok(())
+    }
+
+    fn remove_dir(&mut self, parent: u64, name: &OsStr) -> Result<(), c_int> {
+        let info = self.dir(parent)?;
+        let name = name_str(name)?;
+
+        match self.lookup_entry(&info, &name)? {
+            Kind::Dir(dir) => {
+                if !self.list(&dir)?.is_empty() {
+                    return Err(ENOTEMPTY);
+                }
+            }
+            Kind::File(_) => return Err(ENOTDIR),
+        }
+        self.remove(&info, &name)?;
+        self.forget_node(parent, &name);
+        Ok(())
+    }
+
+    fn rename_entry(
+        &mut self,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+    ) -> Result<(), c_int> {
+        let from = self.dir(parent)?;
+        let to = self.dir(new_parent)?;
+        let name = name_str(name)?;
+        let new_name = name_str(new_name)?;
+        if parent == new_parent && name == new_name {
+            return Ok(());
+        }
+
+        let (content, _) = self.fetch(&from, &name)?;
+        let content = match decode(&content).ok_or(ENOENT)? {
+            // The data map of a file is encrypted with the key of its directory, so the content
+            // is stored again under the key of the new one.
+            Kind::File(ref file) if from.enc_key() != to.enc_key() => {
+                let data = self.load(&from, file)?;
+                let file = self.store(&to, file.clone(), data)?;
+                encode_file(&file).map_err(|_| EIO)?
+            }
+            _ => content,
+        };
+
+        // Replaces an existing file, as `rename(2)` does, but not a directory.
+        match self.lookup_entry(&to, &new_name) {
+            Ok(Kind::File(_)) => self.remove(&to, &new_name)?,
+            Ok(Kind::Dir(_)) => return Err(EEXIST),
+            Err(ENOENT) => (),
+            Err(err) => return Err(err),
+        }
+        self.insert(&to, &new_name, &content)?;
+        self.remove(&from, &name)?;
+
+        self.forget_node(new_parent, &new_name);
+        if let Some(ino) = self.inos.remove(&(parent, name)) {
+            let _ = self.inos.insert((new_parent, new_name.clone()), ino);
+            if let Some(node) = self.nodes.get_mut(&ino) {
+                node.parent = new_parent;
+                node.name = new_name;
+                if let Some(kind) = decode(&content) {
+                    node.kind = kind;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn open_file(&mut self, ino: u64, flags: u32) -> Result<u64, c_int> {
+        let (parent, _, file) = self.file(ino)?;
+        let flags = flags as c_int;
+
+        let (content, dirty) = if flags & O_ACCMODE == O_RDONLY {
+            (None, false)
+        } else if flags & O_TRUNC != 0 {
+            (Some(Vec::new()), true)
+        } else {
+            (Some(self.load(&parent, &file)?), false)
+        };
+        Ok(self.open_handle(ino, content, dirty))
+    }
+
+    fn read_file(&self, ino: u64, fh: u64, offset: u64, size: u64) -> Result<Vec<u8>, c_int> {
+        if let Some(content) = self.handles.get(&fh).and_then(|handle| handle.content.as_ref()) {
+            let start = cmp::min(offset, content.len() as u64) as usize;
+            let end = cmp::min(offset + size, content.len() as u64) as usize;
+            return Ok(content[start..end].to_vec());
+        }
+
+        let (parent, _, file) = self.file(ino)?;
+        let key = parent.enc_key().cloned();
+        self.run(move |client| {
+            file_helper::read(client.clone(), &file, key)
+                .and_then(move |reader| {
+                    let end = cmp::min(offset + size, reader.size());
+                    if offset >= end {
+                        ok!(Vec::new())
+                    } else {
+                        reader.read(offset, end - offset)
+                    }
+                })
+                .into_box()
+        })
+    }
+
+    fn write_file(&mut self, fh: u64, offset: u64, data: &[u8]) -> Result<u32, c_int> {
+        let handle = self.handles.get_mut(&fh).ok_or(EBADF)?;
+        let content = handle.content.as_mut().ok_or(EBADF)?;
+
+        let start = offset as usize;
+        let end = start + data.len();
+        if content.len() < end {
+            content.resize(end, 0);
+        }
+        content[start..end].copy_from_slice(data);
+        handle.dirty = true;
+
+        Ok(data.len() as u32)
+    }
+
+    fn truncate(&mut self, ino: u64, fh: Option<u64>, size: u64) -> Result<(), c_int> {
+        if let Some(fh) = fh {
+            if let Some(&mut OpenFile {
+                            content: Some(ref mut content),
+                            ref mut dirty,
+                            ..
+                        }) = self.handles.get_mut(&fh)
+            {
+                content.resize(size as usize, 0);
+                *dirty = true;
+                return Ok(());
+            }
+        }
+
+        let (parent, _, file) = self.file(ino)?;
+        let mut content = if size == 0 {
+            Vec::new()
+        } else {
+            self.load(&parent, &file)?
+        };
+        content.resize(size as usize, 0);
+        self.save(ino, content)
+    }
+
+    fn flush_file(&mut self, fh: u64) -> Result<(), c_int> {
+        let (ino, content) = {
+            let handle = self.handles.get_mut(&fh).ok_or(EBADF)?;
+            if !handle.dirty {
+                return Ok(());
+            }
+            handle.dirty = false;
+            (handle.ino, handle.content.clone().unwrap_or_default())
+        };
+
+        if let Err(err) = self.save(ino, content) {
+            if let Some(handle) = self.handles.get_mut(&fh) {
+                handle.dirty = true;
+            }
+            return Err(err);
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Filesystem for SafeFs<'a> {
+    fn init(&mut self, req: &Request) -> Result<(), c_int> {
+        self.uid = req.uid();
+        self.gid = req.gid();
+        Ok(())
+    }
+
+    fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
+        match self.lookup_node(parent, name) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
+        match self.attr(ino) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn setattr(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _mode: Option<u32>,
+        _uid: Option<u32>,
+        _gid: Option<u32>,
+        size: Option<u64>,
+        _atime: Option<Timespec>,
+        _mtime: Option<Timespec>,
+        fh: Option<u64>,
+        _crtime: Option<Timespec>,
+        _chgtime: Option<Timespec>,
+        _bkuptime: Option<Timespec>,
+        _flags: Option<u32>,
+        reply: ReplyAttr,
+    ) {
+        // Only the size can be changed. Other attributes are silently kept, as tools like `cp
+        // -p` would fail otherwise.
+        let res = match size {
+            Some(size) => self.truncate(ino, fh, size),
+            None => Ok(()),
+        };
+        match res.and_then(|()| self.attr(ino)) {
+            Ok(attr) => reply.attr(&TTL, &attr),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn mkdir(&mut self, _req: &Request, parent: u64, name: &OsStr, _mode: u32, reply: ReplyEntry) {
+        match self.make_dir(parent, name) {
+            Ok(attr) => reply.entry(&TTL, &attr, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn unlink(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_file(parent, name) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_dir(parent, name) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn rename(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+        reply: ReplyEmpty,
+    ) {
+        match self.rename_entry(parent, name, new_parent, new_name) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn open(&mut self, _req: &Request, ino: u64, flags: u32, reply: ReplyOpen) {
+        match self.open_file(ino, flags) {
+            Ok(fh) => reply.opened(fh, 0),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn read(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        fh: u64,
+        offset: i64,
+        size: u32,
+        reply: ReplyData,
+    ) {
+        match self.read_file(ino, fh, offset as u64, u64::from(size)) {
+            Ok(data) => reply.data(&data),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn write(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        fh: u64,
+        offset: i64,
+        data: &[u8],
+        _flags: u32,
+        reply: ReplyWrite,
+    ) {
+        match self.write_file(fh, offset as u64, data) {
+            Ok(written) => reply.written(written),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn flush(&mut self, _req: &Request, _ino: u64, fh: u64, _lock_owner: u64, reply: ReplyEmpty) {
+        match self.flush_file(fh) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn fsync(&mut self, _req: &Request, _ino: u64, fh: u64, _datasync: bool, reply: ReplyEmpty) {
+        match self.flush_file(fh) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn release(
+        &mut self,
+        _req: &Request,
+        _ino: u64,
+        fh: u64,
+        _flags: u32,
+        _lock_owner: u64,
+        _flush: bool,
+        reply: ReplyEmpty,
+    ) {
+        let res = self.flush_file(fh);
+        let _ = self.handles.remove(&fh);
+        match res {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    fn readdir(
+        &mut self,
+        _req: &Request,
+        ino: u64,
+        _fh: u64,
+        offset: i64,
+        mut reply: ReplyDirectory,
+    ) {
+        let entries = match self.read_dir(ino) {
+            Ok(entries) => entries,
+            Err(err) => return reply.error(err),
+        };
+
+        // The offset of an entry is the one to resume the listing from after it.
+        for (index, (ino, kind, name)) in entries.into_iter().enumerate().skip(offset as usize) {
+            if reply.add(ino, index as i64 + 1, kind, name) {
+                break;
+            }
+        }
+        reply.ok();
+    }
+
+    fn create(
+        &mut self,
+        _req: &Request,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        flags: u32,
+        reply: ReplyCreate,
+    ) {
+        match self.create_file(parent, name) {
+            Ok((attr, fh)) => reply.created(&TTL, &attr, 0, fh, flags),
+            Err(err) => reply.error(err),
+        }
+    }
+}
+
+// Directories hold the `MDataInfo` of their sub-directories and the `File` of their files.
+fn decode(content: &[u8]) -> Option<Kind> {
+    match decode_entry(content) {
+        Ok(Entry::Dir(info)) => Some(Kind::Dir(info)),
+        Ok(Entry::File(file)) => Some(Kind::File(file)),
+        Err(_) => None,
+    }
+}
+
+fn name_str(name: &OsStr) -> Result<String, c_int> {
+    name.to_str().map(str::to_owned).ok_or(EINVAL)
+}
+
+fn blocks(size: u64) -> u64 {
+    (size + 511) / 512
+}
+
+fn errno(err: &NfsError) -> c_int {
+    match *err {
+        NfsError::FileNotFound => ENOENT,
+        NfsError::FileExists => EEXIST,
+        NfsError::InvalidRange => EINVAL,
+        NfsError::CoreError(CoreError::RoutingClientError(ref err)) => {
+            match *err {
+                ClientError::NoSuchData |
+                ClientError::NoSuchEntry => ENOENT,
+                ClientError::DataExists => EEXIST,
+                ClientError::AccessDenied => EACCES,
+                _ => EIO,
+            }
+        }
+        _ => EIO,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_core::nfs::encode_dir;
+
+    // Entries are told apart by the format they are encoded with.
+    #[test]
+    fn decode_entries() {
+        let dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+        match decode(&unwrap!(encode_dir(&dir))) {
+            Some(Kind::Dir(ref decoded)) => assert_eq!(*decoded, dir),
+            _ => panic!("Sub-directory not recognised"),
+        }
+
+        let file = File::new(vec![1, 2, 3]);
+        match decode(&unwrap!(encode_file(&file))) {
+            Some(Kind::File(ref decoded)) => assert_eq!(*decoded, file),
+            _ => panic!("File not recognised"),
+        }
+
+        assert!(decode(&[]).is_none());
+    }
+
+    #[test]
+    fn errors_to_errnos() {
+        assert_eq!(errno(&NfsError::FileNotFound), ENOENT);
+        assert_eq!(errno(&NfsError::FileExists), EEXIST);
+        assert_eq!(
+            errno(&NfsError::from(
+                CoreError::RoutingClientError(ClientError::AccessDenied),
+            )),
+            EACCES
+        );
+        assert_eq!(
+            errno(&NfsError::from(
+                CoreError::RoutingClientError(ClientError::NoSuchEntry),
+            )),
+            ENOENT
+        );
+        assert_eq!(errno(&NfsError::from(CoreError::RequestTimeout)), EIO);
+    }
+}
@@ -20,6 +20,7 @@
 
 use super::errors::AppError;
 use AppContext;
+use backtrace;
 use ffi::cipher_opt::CipherOpt;
 use ffi::nfs::FileContext;
 use routing::{EntryAction, PermissionSet, User, Value};
@@ -29,6 +30,7 @@ use safe_core::crypto::{shared_box, shared_sign};
 use self_encryption::{SelfEncryptor, SequentialEncryptor};
 use std::cell::{Cell, RefCell, RefMut};
 use std::collections::{BTreeMap, HashMap};
+use std::time::SystemTime;
 use std::u64;
 
 /// Value of handles which should receive special handling.
@@ -84,6 +86,7 @@ pub struct ObjectCache {
     pub_sign_key: Store<sign::PublicKey>,
     sec_sign_key: Store<shared_sign::SecretKey>,
     file: Store<FileContext>,
+    tracker: HandleTracker,
 }
 
 impl ObjectCache {
@@ -102,6 +105,7 @@ impl ObjectCache {
             pub_sign_key: Store::new(),
             sec_sign_key: Store::new(),
             file: Store::new(),
+            tracker: HandleTracker::new(),
         }
     }
 
@@ -119,7 +123,62 @@ impl ObjectCache {
         self.pub_sign_key.clear();
         self.sec_sign_key.clear();
         self.file.clear();
+        self.tracker.clear();
     }
+
+    /// Turn recording of where and when handles are created on or off. Turning it
+    /// off forgets the handles recorded so far.
+    pub fn set_debug(&self, enabled: bool) {
+        self.tracker.enabled.set(enabled);
+        if !enabled {
+            self.tracker.clear();
+        }
+    }
+
+    /// Live handles created while debugging was on, grouped by type and the FFI
+    /// function which created them.
+    pub fn debug_dump(&self) -> Vec<LiveHandles> {
+        let mut groups: BTreeMap<(&'static str, String), LiveHandles> = BTreeMap::new();
+
+        for (handle, origin) in self.tracker.origins.borrow().iter() {
+            let group = groups
+                .entry((origin.kind, origin.function.clone()))
+                .or_insert_with(|| {
+                    LiveHandles {
+                        kind: origin.kind,
+                        function: origin.function.clone(),
+                        handles: Vec::new(),
+                        oldest: origin.created,
+                    }
+                });
+            group.handles.push(*handle);
+            if origin.created < group.oldest {
+                group.oldest = origin.created;
+            }
+        }
+
+        groups
+            .into_iter()
+            .map(|(_, mut group)| {
+                group.handles.sort();
+                group
+            })
+            .collect()
+    }
+}
+
+/// Group of live handles reported by `ObjectCache::debug_dump`.
+#[derive(Debug)]
+pub struct LiveHandles {
+    /// Type of the objects, e.g. `mdata_entries`.
+    pub kind: &'static str,
+    /// Path of the FFI function which created the handles, relative to `ffi`, or
+    /// `<unknown>` if they were created outside of the FFI.
+    pub function: String,
+    /// The handles, in ascending order.
+    pub handles: Vec<ObjectHandle>,
+    /// Creation time of the oldest handle.
+    pub oldest: SystemTime,
 }
 
 macro_rules! impl_cache {
@@ -135,6 +194,7 @@ macro_rules! impl_cache {
             pub fn $insert(&self, value: $ty) -> $handle {
                 let handle = self.handle_gen.gen();
                 self.$name.insert(handle, value);
+                self.tracker.created(handle, stringify!($name));
                 handle
             }
 
@@ -145,7 +205,9 @@ macro_rules! impl_cache {
 
             /// Remove object from the object cache and return it.
             pub fn $remove(&self, handle: $handle) -> Result<$ty, AppError> {
-                self.$name.remove(handle).ok_or(AppError::$error)
+                let value = self.$name.remove(handle).ok_or(AppError::$error)?;
+                self.tracker.removed(handle);
+                Ok(value)
             }
         }
     }
@@ -260,6 +322,77 @@ impl HandleGenerator {
     }
 }
 
+// Records where live handles were created, while enabled.
+struct HandleTracker {
+    enabled: Cell<bool>,
+    origins: RefCell<HashMap<ObjectHandle, HandleOrigin>>,
+}
+
+struct HandleOrigin {
+    kind: &'static str,
+    function: String,
+    created: SystemTime,
+}
+
+impl HandleTracker {
+    fn new() -> Self {
+        HandleTracker {
+            enabled: Cell::new(false),
+            origins: RefCell::new(HashMap::new()),
+        }
+    }
+
+    fn created(&self, handle: ObjectHandle, kind: &'static str) {
+        if !self.enabled.get() {
+            return;
+        }
+        let origin = HandleOrigin {
+            kind,
+            function: ffi_caller(),
+            created: SystemTime::now(),
+        };
+        let _ = self.origins.borrow_mut().insert(handle, origin);
+    }
+
+    fn removed(&self, handle: ObjectHandle) {
+        let _ = self.origins.borrow_mut().remove(&handle);
+    }
+
+    fn clear(&self) {
+        self.origins.borrow_mut().clear()
+    }
+}
+
+// Finds the innermost FFI function on the stack. Objects are inserted by closures
+// running on the event loop, whose symbols are named after the enclosing function.
+// Resolving the symbols requires them not to be stripped from the binary.
+fn ffi_caller() -> String {
+    const PREFIX: &str = "safe_app::ffi::";
+    let mut caller = None;
+
+    backtrace::trace(|frame| {
+        backtrace::resolve(frame.ip(), |symbol| {
+            if caller.is_some() {
+                return;
+            }
+            let name = match symbol.name() {
+                Some(name) => format!("{:#}", name),
+                None => return,
+            };
+            if let Some(pos) = name.find(PREFIX) {
+                let path = &name[pos + PREFIX.len()..];
+                if !path.starts_with("helper::") {
+                    let path = path.split("::{{closure}}").next().unwrap_or(path);
+                    caller = Some(path.to_owned());
+                }
+            }
+        });
+        caller.is_none()
+    });
+
+    caller.unwrap_or_else(|| "<unknown>".to_owned())
+}
+
 struct Store<V> {
     inner: RefCell<HashMap<ObjectHandle, V>>,
 }
@@ -309,4 +442,33 @@ mod tests {
         object_cache.reset();
         assert!(object_cache.get_pub_sign_key(handle).is_err());
     }
+
+    // Test that live handles are only recorded while debugging is on, and grouped
+    // by type.
+    #[test]
+    fn debug_dump() {
+        let object_cache = ObjectCache::new();
+        let (pk, _) = sign::gen_keypair();
+
+        let _ = object_cache.insert_pub_sign_key(pk);
+        object_cache.set_debug(true);
+
+        let h1 = object_cache.insert_pub_sign_key(pk);
+        let h2 = object_cache.insert_pub_sign_key(pk);
+        let h3 = object_cache.insert_mdata_entries(Default::default());
+        let h4 = object_cache.insert_mdata_entries(Default::default());
+        let _ = unwrap!(object_cache.remove_mdata_entries(h4));
+
+        let dump = object_cache.debug_dump();
+        assert_eq!(dump.len(), 2);
+        assert_eq!(dump[0].kind, "mdata_entries");
+        assert_eq!(dump[0].handles, vec![h3]);
+        assert_eq!(dump[1].kind, "pub_sign_key");
+        assert_eq!(dump[1].handles, vec![h1, h2]);
+        // Not created through the FFI.
+        assert_eq!(dump[1].function, "<unknown>");
+
+        object_cache.set_debug(false);
+        assert!(object_cache.debug_dump().is_empty());
+    }
 }
@@ -0,0 +1,336 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! App settings.
+//!
+//! Settings are schema-less documents stored in the app's own container, one entry per
+//! document, under keys prefixed with `SETTINGS_KEY_PREFIX` so they can't clash with
+//! files kept there. Documents are stored encoded as CBOR and, like all entries of the
+//! container, encrypted with its keys. Over the FFI they can be exchanged as CBOR or JSON.
+
+use {AppContext, AppError, AppFuture};
+use futures::Future;
+use routing::{ClientError, EntryActions};
+use safe_core::{Client, CoreError, FutureExt, MDataInfo};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_cbor::{self, Value};
+use serde_json;
+
+/// Prefix of the app container keys settings are stored under.
+pub const SETTINGS_KEY_PREFIX: &str = "_settings/";
+
+/// Encoding of settings documents exchanged over the FFI.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum SettingsFormat {
+    /// CBOR (RFC 7049).
+    Cbor,
+    /// JSON. Maps must have string keys to be encoded as JSON.
+    Json,
+}
+
+impl SettingsFormat {
+    /// Decodes a document.
+    pub fn decode(&self, encoded: &[u8]) -> Result<Value, AppError> {
+        match *self {
+            SettingsFormat::Cbor => serde_cbor::from_slice(encoded).map_err(|error| {
+                AppError::from(format!("Invalid CBOR document: {}", error))
+            }),
+            SettingsFormat::Json => serde_json::from_slice(encoded).map_err(|error| {
+                AppError::from(format!("Invalid JSON document: {}", error))
+            }),
+        }
+    }
+
+    /// Encodes a document.
+    pub fn encode(&self, document: &Value) -> Result<Vec<u8>, AppError> {
+        match *self {
+            SettingsFormat::Cbor => serde_cbor::to_vec(document).map_err(|error| {
+                AppError::from(format!("Can't encode the document as CBOR: {}", error))
+            }),
+            SettingsFormat::Json => serde_json::to_vec(document).map_err(|error| {
+                AppError::from(format!("Can't encode the document as JSON: {}", error))
+            }),
+        }
+    }
+}
+
+/// Settings documents of an app.
+#[derive(Clone, Debug)]
+pub struct Settings {
+    container: MDataInfo,
+}
+
+impl Settings {
+    /// Settings stored in the given container.
+    pub fn new(container: MDataInfo) -> Self {
+        Settings { container: container }
+    }
+
+    /// Settings stored in the app's own container.
+    /// Fails with `NoSuchContainer` if the app never asked for its own container.
+    pub fn open(client: &Client<AppContext>, context: &AppContext) -> Box<AppFuture<Self>> {
+        context
+            .get_app_container_info(client)
+            .map(Settings::new)
+            .into_box()
+    }
+
+    /// Gets the document `name`, or `None` if it isn't set.
+    pub fn get(&self, client: &Client<AppContext>, name: &str) -> Box<AppFuture<Option<Value>>> {
+        self.get_as(client, name)
+    }
+
+    /// Gets the document `name` deserialised as `V`, or `None` if it isn't set.
+    pub fn get_as<V>(&self, client: &Client<AppContext>, name: &str) -> Box<AppFuture<Option<V>>>
+    where
+        V: DeserializeOwned + 'static,
+    {
+        self.fetch(client, name)
+            .and_then(|(_, encoded)| -> Result<_, AppError> {
+                match encoded {
+                    Some(encoded) => Ok(Some(decode(&encoded)?)),
+                    None => Ok(None),
+                }
+            })
+            .into_box()
+    }
+
+    /// Sets the document `name`, replacing the previous one, if any.
+    pub fn set(
+        &self,
+        client: &Client<AppContext>,
+        name: &str,
+        document: &Value,
+    ) -> Box<AppFuture<()>> {
+        self.set_as(client, name, document)
+    }
+
+    /// Sets the document `name` to `value` serialised, replacing the previous one, if any.
+    pub fn set_as<V: Serialize>(
+        &self,
+        client: &Client<AppContext>,
+        name: &str,
+        value: &V,
+    ) -> Box<AppFuture<()>> {
+        let settings = self.clone();
+        let client2 = client.clone();
+        let name = name.to_owned();
+        let encoded = fry!(encode(value));
+
+        self.fetch(client, &name)
+            .and_then(move |(version, _)| settings.store(&client2, &name, version, encoded))
+            .into_box()
+    }
+
+    /// Merges `patch` into the document `name` as described by JSON Merge Patch (RFC 7396):
+    /// maps are merged recursively, `Null` removes the key it's stored under and any other
+    /// value replaces the one in the document. Returns the merged document.
+    pub fn merge(
+        &self,
+        client: &Client<AppContext>,
+        name: &str,
+        patch: Value,
+    ) -> Box<AppFuture<Value>> {
+        let settings = self.clone();
+        let client2 = client.clone();
+        let name = name.to_owned();
+
+        self.fetch(client, &name)
+            .and_then(move |(version, encoded)| {
+                let document = match encoded {
+                    Some(encoded) => fry!(decode(&encoded)),
+                    None => Value::Null,
+                };
+                let merged = merge_patch(document, patch);
+                let encoded = fry!(encode(&merged));
+
+                settings
+                    .store(&client2, &name, version, encoded)
+                    .map(move |_| merged)
+                    .into_box()
+            })
+            .into_box()
+    }
+
+    /// Removes the document `name`.
+    /// Fails with `NoSuchEntry` if it isn't set.
+    pub fn remove(&self, client: &Client<AppContext>, name: &str) -> Box<AppFuture<()>> {
+        let container = self.container.clone();
+        let client2 = client.clone();
+        let key = fry!(self.entry_key(name));
+
+        self.fetch(client, name)
+            .and_then(move |res| {
+                let version = match res {
+                    (Some(version), Some(_)) => version,
+                    _ => {
+                        return err!(AppError::from(
+                            CoreError::RoutingClientError(ClientError::NoSuchEntry),
+                        ))
+                    }
+                };
+
+                client2
+                    .mutate_mdata_entries(
+                        container.name,
+                        container.type_tag,
+                        EntryActions::new().del(key, version + 1).into(),
+                    )
+                    .map_err(AppError::from)
+                    .into_box()
+            })
+            .into_box()
+    }
+
+    fn entry_key(&self, name: &str) -> Result<Vec<u8>, AppError> {
+        let key = format!("{}{}", SETTINGS_KEY_PREFIX, name);
+        Ok(self.container.enc_entry_key(key.as_bytes())?)
+    }
+
+    // Fetches the version of the entry holding the document `name`, or `None` if there's
+    // no such entry, together with the encoded document, or `None` if it isn't set. The
+    // entry of a removed document is kept, so its version is returned without a document.
+    fn fetch(
+        &self,
+        client: &Client<AppContext>,
+        name: &str,
+    ) -> Box<AppFuture<(Option<u64>, Option<Vec<u8>>)>> {
+        let container = self.container.clone();
+        let key = fry!(self.entry_key(name));
+
+        client
+            .get_mdata_value(container.name, container.type_tag, key)
+            .then(move |res| -> Result<_, AppError> {
+                match res {
+                    // Empty entry means the document has been removed.
+                    Ok(ref value) if value.content.is_empty() => {
+                        Ok((Some(value.entry_version), None))
+                    }
+                    Ok(value) => {
+                        let encoded = container.decrypt(&value.content)?;
+                        Ok((Some(value.entry_version), Some(encoded)))
+                    }
+                    Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                        Ok((None, None))
+                    }
+                    Err(error) => Err(AppError::from(error)),
+                }
+            })
+            .into_box()
+    }
+
+    // Stores the encoded document in the entry currently at `version`, which might be the
+    // entry of a removed document, or in a new entry if `None`.
+    fn store(
+        &self,
+        client: &Client<AppContext>,
+        name: &str,
+        version: Option<u64>,
+        encoded: Vec<u8>,
+    ) -> Box<AppFuture<()>> {
+        let key = fry!(self.entry_key(name));
+        let content = fry!(self.container.enc_entry_value(&encoded));
+        let actions = match version {
+            Some(version) => EntryActions::new().update(key, content, version + 1),
+            None => EntryActions::new().ins(key, content, 0),
+        };
+
+        client
+            .mutate_mdata_entries(self.container.name, self.container.type_tag, actions.into())
+            .map_err(AppError::from)
+            .into_box()
+    }
+}
+
+// Applies a JSON Merge Patch (RFC 7396) to the document.
+fn merge_patch(document: Value, patch: Value) -> Value {
+    match patch {
+        Value::Object(patch) => {
+            let mut document = match document {
+                Value::Object(document) => document,
+                _ => Default::default(),
+            };
+            for (key, value) in patch {
+                if let Value::Null = value {
+                    let _ = document.remove(&key);
+                } else {
+                    let current = document.remove(&key).unwrap_or(Value::Null);
+                    let _ = document.insert(key, merge_patch(current, value));
+                }
+            }
+            Value::Object(document)
+        }
+        patch => patch,
+    }
+}
+
+fn encode<V: Serialize>(value: &V) -> Result<Vec<u8>, AppError> {
+    serde_cbor::to_vec(value).map_err(|error| {
+        AppError::from(format!("Can't encode the document: {}", error))
+    })
+}
+
+fn decode<V: DeserializeOwned>(encoded: &[u8]) -> Result<V, AppError> {
+    serde_cbor::from_slice(encoded).map_err(|error| {
+        AppError::from(format!("Invalid document: {}", error))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_cbor::ObjectKey;
+    use std::collections::BTreeMap;
+
+    fn object(entries: Vec<(&str, Value)>) -> Value {
+        Value::Object(
+            entries
+                .into_iter()
+                .map(|(key, value)| (ObjectKey::String(key.to_owned()), value))
+                .collect::<BTreeMap<_, _>>(),
+        )
+    }
+
+    // Test applying merge patches.
+    #[test]
+    fn merge() {
+        let document = object(vec![
+            ("theme", Value::String("dark".to_owned())),
+            ("font", object(vec![("size", Value::U64(12)), ("family", Value::Null)])),
+            ("beta", Value::Bool(true)),
+        ]);
+        let patch = object(vec![
+            ("theme", Value::String("light".to_owned())),
+            ("font", object(vec![("size", Value::U64(14))])),
+            ("beta", Value::Null),
+            ("lang", Value::String("en".to_owned())),
+        ]);
+        let merged = object(vec![
+            ("theme", Value::String("light".to_owned())),
+            ("font", object(vec![("size", Value::U64(14)), ("family", Value::Null)])),
+            ("lang", Value::String("en".to_owned())),
+        ]);
+        assert_eq!(merge_patch(document, patch), merged);
+
+        // A patch which isn't a map replaces the whole document.
+        assert_eq!(
+            merge_patch(merged, Value::Bool(false)),
+            Value::Bool(false)
+        );
+    }
+}
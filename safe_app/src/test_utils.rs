@@ -32,6 +32,9 @@ pub fn gen_app_exchange_info() -> AppExchangeInfo {
         scope: None,
         name: unwrap!(utils::generate_random_string(10)),
         vendor: unwrap!(utils::generate_random_string(10)),
+        description: None,
+        homepage: None,
+        icon: None,
     }
 }
 
@@ -94,6 +97,24 @@ pub fn create_app_by_req(auth_req: &NativeAuthReq) -> App {
     ))
 }
 
+/// Create an account with the given credentials and keys derived from `seed`.
+/// The account can then be shared by several apps via `create_app_on_account`.
+pub fn create_account_with(locator: &str, password: &str, seed: &[u8]) {
+    let _ = authenticator::create_account_with(locator, password, seed);
+}
+
+/// Register an app on an account created by the test utilities, as if on
+/// another device of the same user.
+pub fn create_app_on_account(locator: &str, auth_req: &NativeAuthReq) -> App {
+    let auth = authenticator::login_test_account(locator);
+    let auth_granted = unwrap!(authenticator::register_app(&auth, auth_req));
+    unwrap!(App::registered(
+        auth_req.app.id.clone(),
+        auth_granted,
+        || (),
+    ))
+}
+
 /// Create an app authorisation request with optional app id and access info.
 pub fn create_auth_req(
     app_id: Option<String>,
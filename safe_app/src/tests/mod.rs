@@ -100,10 +100,57 @@ fn get_access_info() {
     });
 }
 
+// Test that the access info is cached until its version changes or it's invalidated.
+#[test]
+fn access_info_cache() {
+    let mut container_permissions = HashMap::new();
+    let _ = container_permissions.insert("_videos".to_string(), btree_set![Permission::Read]);
+
+    let app = create_app_by_req(&create_auth_req_with_access(container_permissions));
+
+    run(&app, move |client, context| {
+        let reg = Rc::clone(unwrap!(context.as_registered()));
+        let reg2 = Rc::clone(&reg);
+        let reg3 = Rc::clone(&reg);
+        let context2 = context.clone();
+        let context3 = context.clone();
+        let client2 = client.clone();
+        let client3 = client.clone();
+
+        context
+            .get_access_info(client)
+            .and_then(move |info| {
+                assert!(info.contains_key("_videos"));
+                assert!(reg.access_info_version.get().is_some());
+
+                // Refreshing keeps the cached info as long as the version doesn't change.
+                let _ = reg.access_info.borrow_mut().remove("_videos");
+                context2.refresh_access_info(&client2)
+            })
+            .and_then(move |()| {
+                assert!(!reg2.access_info.borrow().contains_key("_videos"));
+
+                unwrap!(context3.invalidate_access_info());
+                assert!(reg2.access_info_version.get().is_none());
+                assert!(reg2.access_info.borrow().is_empty());
+
+                context3.get_access_info(&client3)
+            })
+            .then(move |res| {
+                let info = unwrap!(res);
+                assert!(info.contains_key("_videos"));
+                assert!(reg3.access_info_version.get().is_some());
+                Ok(())
+            })
+    });
+}
+
 // Make sure we can login to a registered app with low balance.
 #[cfg(feature = "use-mock-routing")]
 #[test]
 pub fn login_registered_with_low_balance() {
+    skip_unless_mock!();
+
     // Register a hook prohibiting mutations and login
     let routing_hook = move |mut routing: MockRouting| -> MockRouting {
         routing.set_request_hook(move |req| {
@@ -274,3 +321,68 @@ fn app_container_creation() {
 
     assert_eq!(num_containers(&app), 1); // should only contain app container
 }
+
+// Test that shutting down waits for pending operations and reports those which
+// didn't finish in time.
+#[test]
+fn graceful_shutdown() {
+    use futures::future;
+    use safe_core::FutureExt;
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use test_utils::create_app;
+    use tokio_core::reactor::Timeout;
+
+    let app = create_app();
+    let (tx, rx) = mpsc::channel();
+    unwrap!(app.send(move |client, _| {
+        let delay = unwrap!(Timeout::new(Duration::from_millis(100), &client.el_handle()));
+        Some(
+            delay
+                .map(move |_| unwrap!(tx.send(())))
+                .map_err(|_| ())
+                .into_box(),
+        )
+    }));
+
+    assert_eq!(unwrap!(app.shutdown(Duration::from_secs(10))), 0);
+    unwrap!(rx.try_recv());
+
+    let app = create_app();
+    unwrap!(app.send(|_, _| Some(future::empty().into_box())));
+    assert_eq!(unwrap!(app.shutdown(Duration::from_millis(100))), 1);
+}
+
+// Test that shutting down aborts the network requests of operations which
+// didn't finish in time, so they fail instead of being abandoned.
+#[cfg(feature = "use-mock-routing")]
+#[test]
+fn shutdown_aborts_requests() {
+    use safe_core::{CoreError, FutureExt};
+    use std::sync::mpsc;
+    use std::time::Duration;
+    use test_utils::create_app;
+
+    skip_unless_mock!();
+
+    let app = create_app();
+    let (tx, rx) = mpsc::channel();
+    unwrap!(app.send(move |client, _| {
+        client.set_simulate_timeout(true);
+        Some(
+            client
+                .get_idata(::rand::random())
+                .then(move |res| {
+                    unwrap!(tx.send(res));
+                    Ok(())
+                })
+                .into_box(),
+        )
+    }));
+
+    assert_eq!(unwrap!(app.shutdown(Duration::from_millis(100))), 0);
+    match unwrap!(rx.try_recv()) {
+        Err(CoreError::OperationAborted) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+}
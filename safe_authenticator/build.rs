@@ -15,15 +15,20 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-//! Build script for generating C header files from FFI modules.
+//! Build script for generating C header files and, with the `nodejs` feature, N-API
+//! bindings from FFI modules.
 
 extern crate ffi_utils;
 #[macro_use]
 extern crate unwrap;
 
+use std::env;
+
 static HEADER_NAME: &'static str = "safe_authenticator";
 static HEADER_DIRECTORY: &'static str = "../auto-gen/c-include/";
 static ROOT_FILE: &'static str = "src/lib.rs";
+static NODEJS_DIRECTORY: &'static str = "../auto-gen/nodejs/";
+static OPAQUE_TYPES: &'static [&'static str] = &["Authenticator"];
 
 fn main() {
     unwrap!(ffi_utils::header_gen::gen_headers(
@@ -31,4 +36,13 @@ fn main() {
         HEADER_DIRECTORY,
         ROOT_FILE,
     ));
+
+    if env::var_os("CARGO_FEATURE_NODEJS").is_some() {
+        unwrap!(ffi_utils::napi_gen::gen_bindings(
+            HEADER_NAME,
+            NODEJS_DIRECTORY,
+            ROOT_FILE,
+            OPAQUE_TYPES,
+        ));
+    }
 }
@@ -21,18 +21,27 @@
 
 use super::{AuthError, AuthFuture};
 use futures::Future;
-use maidsafe_utilities::serialisation::{deserialise, serialise};
 use routing::EntryActions;
 use rust_sodium::crypto::secretbox;
-use safe_core::{Client, FutureExt, MDataInfo, recovery};
+use safe_core::{Client, FutureExt, KeyOp, MDataInfo, recovery};
 use safe_core::ipc::AppKeys;
-use safe_core::ipc::resp::{AccessContainerEntry, access_container_enc_key};
+use safe_core::ipc::resp::{ACCESS_CONTAINER_ENTRY_FORMAT, AccessContainerEntry,
+                           access_container_enc_key};
 use safe_core::utils::{symmetric_decrypt, symmetric_encrypt};
+use safe_core::utils::migration::Format;
+use safe_core::utils::wire_format::{deserialise, serialise};
 use std::collections::HashMap;
 
 /// Key of the authenticator entry in the access container
 pub const AUTHENTICATOR_ENTRY: &str = "authenticator";
 
+/// Format of the serialised authenticator entry, applied before encryption. Entries in an
+/// older layout are upgraded when decoded and stored in the current one on the next update.
+pub const AUTHENTICATOR_ENTRY_FORMAT: Format = Format {
+    header: b"SAFE-AUE",
+    migrations: &[],
+};
+
 /// Gets access container entry key corresponding to the given app.
 pub fn enc_key(
     access_container: &MDataInfo,
@@ -51,6 +60,7 @@ pub fn decode_authenticator_entry(
     enc_key: &secretbox::Key,
 ) -> Result<HashMap<String, MDataInfo>, AuthError> {
     let plaintext = symmetric_decrypt(encoded, enc_key)?;
+    let (plaintext, _) = AUTHENTICATOR_ENTRY_FORMAT.decode(&plaintext)?;
     Ok(deserialise(&plaintext)?)
 }
 
@@ -59,7 +69,7 @@ pub fn encode_authenticator_entry(
     decoded: &HashMap<String, MDataInfo>,
     enc_key: &secretbox::Key,
 ) -> Result<Vec<u8>, AuthError> {
-    let plaintext = serialise(decoded)?;
+    let plaintext = AUTHENTICATOR_ENTRY_FORMAT.encode(&serialise(decoded)?);
     Ok(symmetric_encrypt(&plaintext, enc_key, None)?)
 }
 
@@ -79,6 +89,7 @@ pub fn fetch_authenticator_entry<T: 'static>(
         .get_mdata_value(access_container.name, access_container.type_tag, key)
         .map_err(From::from)
         .and_then(move |value| {
+            c2.record_key_usage(KeyOp::Decrypt, Some(access_container.name));
             let enc_key = c2.secret_symmetric_key()?;
             decode_authenticator_entry(&value.content, &enc_key).map(
                 |decoded| (value.entry_version, decoded),
@@ -123,6 +134,7 @@ pub fn decode_app_entry(
     enc_key: &secretbox::Key,
 ) -> Result<AccessContainerEntry, AuthError> {
     let plaintext = symmetric_decrypt(encoded, enc_key)?;
+    let (plaintext, _) = ACCESS_CONTAINER_ENTRY_FORMAT.decode(&plaintext)?;
     Ok(deserialise(&plaintext)?)
 }
 
@@ -131,7 +143,7 @@ pub fn encode_app_entry(
     decoded: &AccessContainerEntry,
     enc_key: &secretbox::Key,
 ) -> Result<Vec<u8>, AuthError> {
-    let plaintext = serialise(decoded)?;
+    let plaintext = ACCESS_CONTAINER_ENTRY_FORMAT.encode(&serialise(decoded)?);
     Ok(symmetric_encrypt(&plaintext, enc_key, None)?)
 }
 
@@ -0,0 +1,243 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Deletion of the user's account.
+//!
+//! `MutableData` can't be removed from the network, so the data known to be owned by the
+//! account is wiped instead: all entries of the root and standard containers, the app
+//! containers, the public IDs with their services, the access container and the config root
+//! are deleted and all permissions granted on them are revoked. The keys of all authorised
+//! apps and the device list are removed next, and the account packet is deleted last, after
+//! which the account can no longer be logged into.
+//!
+//! The wiped data stays owned by the account keys and the account locator can't be
+//! registered again. `MutableData` the user created without recording it in one of the
+//! containers isn't known to the account and is left as it is.
+
+use {AuthError, AuthFuture};
+use access_container;
+use devices;
+use futures::{Future, future, stream};
+use futures::stream::Stream;
+use public_id::{self, PUBLIC_NAMES_CONTAINER};
+use routing::{ClientError, EntryAction, EntryActions, User, XorName};
+use safe_core::{Client, CoreError, FutureExt, PUBLIC_ID_TAG, public_id_name};
+use std::collections::BTreeMap;
+
+/// Deletes the account of the logged in user, confirmed by the user entering the account
+/// credentials again. Fails with `InvalidCredentials` if they don't match.
+pub fn delete_account(client: &Client<()>, locator: &str, password: &str) -> Box<AuthFuture<()>> {
+    if !fry!(client.verify_credentials(locator, password)) {
+        return err!(AuthError::InvalidCredentials);
+    }
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+
+    list_owned_data(client)
+        .and_then(move |data| {
+            stream::iter_ok(data).for_each(move |(name, type_tag)| wipe(&c2, name, type_tag))
+        })
+        .and_then(move |_| revoke_auth_keys(&c3))
+        .and_then(move |_| devices::clear(&c5))
+        .and_then(move |_| c4.delete_account_packet().map_err(AuthError::from))
+        .into_box()
+}
+
+// Lists the names and type tags of the `MutableData` known to be owned by the account, the
+// access container and the config root coming last as the others are found through them.
+fn list_owned_data(client: &Client<()>) -> Box<AuthFuture<Vec<(XorName, u64)>>> {
+    let c2 = client.clone();
+    let access_container = fry!(client.access_container());
+    let config_root = fry!(client.config_root_dir());
+
+    access_container::fetch_authenticator_entry(client)
+        .map(|(_, containers)| Some(containers))
+        .or_else(|error| match error {
+            // The standard containers haven't been created yet.
+            AuthError::CoreError(CoreError::RoutingClientError(ClientError::NoSuchData)) |
+            AuthError::CoreError(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                Ok(None)
+            }
+            error => Err(error),
+        })
+        .and_then(move |containers| {
+            let containers = containers.unwrap_or_default();
+            let public_ids = if containers.contains_key(PUBLIC_NAMES_CONTAINER) {
+                list_public_ids(&c2)
+            } else {
+                ok!(Vec::new())
+            };
+
+            let mut data: Vec<_> = containers
+                .values()
+                .map(|info| (info.name, info.type_tag))
+                .collect();
+
+            public_ids.map(move |public_ids| {
+                data.extend(public_ids);
+                data.push((access_container.name, access_container.type_tag));
+                data.push((config_root.name, config_root.type_tag));
+
+                let mut output = Vec::with_capacity(data.len());
+                for item in data {
+                    if !output.contains(&item) {
+                        output.push(item);
+                    }
+                }
+                output
+            })
+        })
+        .into_box()
+}
+
+// Lists public IDs of the user along with their services containers.
+fn list_public_ids(client: &Client<()>) -> Box<AuthFuture<Vec<(XorName, u64)>>> {
+    let c2 = client.clone();
+
+    public_id::list(client)
+        .and_then(move |names| {
+            let services = names.into_iter().map(move |name| {
+                public_id::fetch_services(&c2, &name).map(move |services| {
+                    vec![
+                        (public_id_name(&name), PUBLIC_ID_TAG),
+                        (services.name, services.type_tag),
+                    ]
+                })
+            });
+
+            future::join_all(services)
+        })
+        .map(|data| data.into_iter().flat_map(|items| items).collect())
+        .into_box()
+}
+
+// Deletes all entries of the `MutableData` and revokes all permissions granted on it.
+fn wipe(client: &Client<()>, name: XorName, type_tag: u64) -> Box<AuthFuture<()>> {
+    trace!("Wiping {:?} ({})", name, type_tag);
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+
+    client
+        .list_mdata_entries(name, type_tag)
+        .and_then(move |entries| {
+            let actions: BTreeMap<Vec<u8>, EntryAction> = entries
+                .into_iter()
+                .filter(|&(_, ref value)| !value.content.is_empty())
+                .fold(EntryActions::new(), |actions, (key, value)| {
+                    actions.del(key, value.entry_version + 1)
+                })
+                .into();
+
+            if actions.is_empty() {
+                ok!(())
+            } else {
+                c2.mutate_mdata_entries(name, type_tag, actions)
+            }
+        })
+        .and_then(move |_| c3.get_mdata_shell(name, type_tag))
+        .and_then(move |shell| {
+            let users: Vec<User> = shell.permissions().keys().cloned().collect();
+
+            stream::iter_ok(users)
+                .fold(shell.version(), move |version, user| {
+                    c4.del_mdata_user_permissions(name, type_tag, user, version + 1)
+                        .map(move |_| version + 1)
+                })
+                .map(|_| ())
+        })
+        .or_else(|error| match error {
+            CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(()),
+            error => Err(error),
+        })
+        .map_err(AuthError::from)
+        .into_box()
+}
+
+// Removes the keys of all authorised apps.
+fn revoke_auth_keys(client: &Client<()>) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    client
+        .list_auth_keys_and_version()
+        .and_then(move |(keys, version)| {
+            stream::iter_ok(keys)
+                .fold(version, move |version, key| {
+                    c2.del_auth_key(key, version + 1).map(move |_| version + 1)
+                })
+                .map(|_| ())
+        })
+        .map_err(AuthError::from)
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Authenticator;
+    use safe_core::utils::generate_random_string;
+    use test_utils::{create_authenticator, run, try_run};
+
+    // Test that deleting the account needs the right credentials, wipes the account's
+    // containers and prevents further logins.
+    #[test]
+    fn delete() {
+        let (auth, locator, password) = create_authenticator();
+
+        let public_name = unwrap!(generate_random_string(10));
+        let _ = run(&auth, move |client| public_id::create(client, public_name));
+        let containers = run(&auth, |client| {
+            access_container::fetch_authenticator_entry(client).map(|(_, containers)| containers)
+        });
+        assert!(containers.contains_key(PUBLIC_NAMES_CONTAINER));
+
+        let l2 = locator.clone();
+        let wrong_password = format!("{}!", password);
+        match try_run(&auth, move |client| delete_account(client, &l2, &wrong_password)) {
+            Err(AuthError::InvalidCredentials) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let (l2, p2) = (locator.clone(), password.clone());
+        run(&auth, move |client| delete_account(client, &l2, &p2));
+
+        for info in containers.values() {
+            let (name, type_tag) = (info.name, info.type_tag);
+            let (entries, shell) = run(&auth, move |client| {
+                let c2 = client.clone();
+                client
+                    .list_mdata_entries(name, type_tag)
+                    .join(c2.get_mdata_shell(name, type_tag))
+                    .map_err(AuthError::from)
+            });
+            assert!(entries.values().all(|value| value.content.is_empty()));
+            assert!(shell.permissions().is_empty());
+        }
+
+        match Authenticator::login(locator, password, || ()) {
+            Err(AuthError::CoreError(CoreError::RoutingClientError(
+                ClientError::NoSuchAccount,
+            ))) => (),
+            Err(error) => panic!("Unexpected {:?}", error),
+            Ok(_) => panic!("Unexpected success"),
+        }
+    }
+}
@@ -52,7 +52,7 @@ pub fn app_state(client: &Client<()>, apps: &Apps, app_id: &str) -> Box<AuthFutu
     if let Some(app) = apps.get(&app_id_hash) {
         let app_keys = app.keys.clone();
 
-        access_container::fetch_entry(client, app_id, app_keys)
+        access_container::fetch_entry(client, &app.registered_id(), app_keys)
             .then(move |res| {
                 match res {
                     Ok((_version, Some(_))) => Ok(AppState::Authenticated),
@@ -115,10 +115,10 @@ fn update_access_container(
 ) -> Box<AuthFuture<()>> {
     let c2 = client.clone();
 
-    let app_info = app.info.clone();
+    let app_id = app.registered_id();
     let app_keys = app.keys.clone();
 
-    access_container::fetch_entry(client, &app_info.id, app_keys.clone())
+    access_container::fetch_entry(client, &app_id, app_keys.clone())
         .then(move |res| {
             let version = match res {
                 // Updating an existing entry
@@ -130,10 +130,10 @@ fn update_access_container(
                 // Error has occurred while trying to get an existing entry
                 Err(e) => return Err(e),
             };
-            Ok((version, app_info, app_keys, permissions))
+            Ok((version, app_id, app_keys, permissions))
         })
-        .and_then(move |(version, app_info, app_keys, permissions)| {
-            access_container::put_entry(&c2, &app_info.id, &app_keys, &permissions, version)
+        .and_then(move |(version, app_id, app_keys, permissions)| {
+            access_container::put_entry(&c2, &app_id, &app_keys, &permissions, version)
         })
         .into_box()
 }
@@ -144,7 +144,8 @@ fn update_access_container(
 /// If the app is found, then the `AuthGranted` struct is returned based on that information.
 /// If the app is not found in the access container, then it will be authenticated.
 pub fn authenticate(client: &Client<()>, auth_req: AuthReq) -> Box<AuthFuture<AuthGranted>> {
-    let app_id = auth_req.app.id.clone();
+    fry!(auth_req.app.validate());
+    let app_id = auth_req.app.scoped_id();
     let permissions = auth_req.containers.clone();
     let app_container = auth_req.app_container;
 
@@ -171,6 +172,7 @@ pub fn authenticate(client: &Client<()>, auth_req: AuthReq) -> Box<AuthFuture<Au
                     let app = AppInfo {
                         info: auth_req.app,
                         keys: keys,
+                        legacy_id: false,
                     };
                     config::insert_app(
                         &c3,
@@ -178,13 +180,13 @@ pub fn authenticate(client: &Client<()>, auth_req: AuthReq) -> Box<AuthFuture<Au
                         config::next_version(apps_version),
                         app.clone()
                     )
-                        .map(move |_| (app, app_state, app_id))
+                        .map(move |_| (app, app_state))
                         .into_box()
                 }
                 AppState::Authenticated | AppState::Revoked => {
                     let app_entry_name = sha3_256(app_id.as_bytes());
                     if let Some(app) = apps.remove(&app_entry_name) {
-                        ok!((app, app_state, app_id))
+                        ok!((app, app_state))
                     } else {
                         err!(AuthError::from(
                             "Logical error - couldn't find a revoked app in config"
@@ -193,11 +195,11 @@ pub fn authenticate(client: &Client<()>, auth_req: AuthReq) -> Box<AuthFuture<Au
                 }
             }
         })
-        .and_then(move |(app, app_state, app_id)| {
+        .and_then(move |(app, app_state)| {
             match app_state {
                 AppState::Authenticated => {
                     // Return info of the already registered app
-                    authenticated_app(&c4, app, app_id, app_container)
+                    authenticated_app(&c4, app, app_container)
                 }
                 AppState::NotAuthenticated |
                 AppState::Revoked => {
@@ -210,16 +212,16 @@ pub fn authenticate(client: &Client<()>, auth_req: AuthReq) -> Box<AuthFuture<Au
 }
 
 /// Return info of an already registered app.
-/// If `app_container` is `true` then we also create/update the dedicated container.
+/// If `app_container` is `true` then we also reserve/update the dedicated container.
 fn authenticated_app(
     client: &Client<()>,
     app: AppInfo,
-    app_id: String,
     app_container: bool,
 ) -> Box<AuthFuture<AuthGranted>> {
     let c2 = client.clone();
     let c3 = client.clone();
 
+    let app_id = app.registered_id();
     let app_keys = app.keys.clone();
     let sign_pk = app.keys.sign_pk;
     let bootstrap_config = fry!(Client::<()>::bootstrap_config());
@@ -229,9 +231,9 @@ fn authenticated_app(
         .and_then(move |(_version, perms)| {
             let perms = perms.unwrap_or_else(AccessContainerEntry::default);
 
-            // Check whether we need to create/update dedicated container
+            // Check whether we need to reserve/update dedicated container
             if app_container && !app_container_exists(&perms, &app_id) {
-                let future = app_container::fetch_or_create(&c2, &app_id, sign_pk)
+                let future = app_container::fetch_or_reserve(&c2, &app_id, sign_pk)
                     .and_then(move |mdata_info| {
                         let perms = insert_app_container(perms, &app_id, mdata_info);
                         update_access_container(&c2, &app, perms.clone()).map(move |_| perms)
@@ -259,7 +261,7 @@ fn authenticated_app(
 ///
 /// 1. Insert app's key to Maid Managers
 /// 2. Update container permissions for requested containers
-/// 3. Create the app container (if it's been requested)
+/// 3. Reserve the app container (if it's been requested); the app creates it on first use
 /// 4. Insert or update the access container entry for an app
 /// 5. Return `AuthGranted`
 fn authenticate_new_app(
@@ -277,7 +279,7 @@ fn authenticate_new_app(
     let sign_pk = app.keys.sign_pk;
     let app_keys = app.keys.clone();
     let app_keys_auth = app.keys.clone();
-    let app_id = app.info.id.clone();
+    let app_id = app.registered_id();
 
     client
         .list_auth_keys_and_version()
@@ -293,7 +295,7 @@ fn authenticate_new_app(
                 .into_box()
         })
         .and_then(move |(perms, sign_pk)| if app_container {
-            app_container::fetch_or_create(&c4, &app_id, sign_pk)
+            app_container::fetch_or_reserve(&c4, &app_id, sign_pk)
                 .and_then(move |mdata_info| {
                     ok!(insert_app_container(perms, &app_id, mdata_info))
                 })
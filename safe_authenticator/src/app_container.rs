@@ -20,9 +20,9 @@
 use {AuthError, AuthFuture};
 use access_container;
 use futures::Future;
-use routing::{Action, EntryActions, PermissionSet, User};
+use routing::{Action, ClientError, EntryActions, PermissionSet, User};
 use rust_sodium::crypto::sign;
-use safe_core::{Client, DIR_TAG, FutureExt, MDataInfo, app_container_name, nfs};
+use safe_core::{Client, CoreError, DIR_TAG, FutureExt, MDataInfo, app_container_name};
 
 /// Returns an app's dedicated container if available and stored in the access container,
 /// `None` otherwise.
@@ -38,8 +38,10 @@ pub fn fetch(client: &Client<()>, app_id: &str) -> Box<AuthFuture<Option<MDataIn
 }
 
 /// Checks if an app's dedicated container is available and stored in the access container.
-/// If no previously created container has been found, then it will be created.
-pub fn fetch_or_create(
+/// If no previously reserved container has been found, then a new one will be reserved.
+/// The container itself is created by the app on its first use, see
+/// `nfs::create_app_container`.
+pub fn fetch_or_reserve(
     client: &Client<()>,
     app_id: &str,
     app_sign_pk: sign::PublicKey,
@@ -52,8 +54,8 @@ pub fn fetch_or_create(
         .and_then(move |(ac_entry_version, mut ac_entries)| {
             match ac_entries.remove(&app_cont_name) {
                 Some(mdata_info) => {
-                    // Reuse the already existing app container and update
-                    // permissions for it
+                    // Reuse the already reserved app container and update
+                    // permissions for it, if it has been created already
                     let ps = PermissionSet::new()
                         .allow(Action::Insert)
                         .allow(Action::Update)
@@ -68,23 +70,28 @@ pub fn fetch_or_create(
                                 User::Key(app_sign_pk),
                                 ps,
                                 version + 1,
-                            ).map(move |_| mdata_info)
+                            )
                         })
-                        .map_err(From::from)
+                        .or_else(|err| match err {
+                            // Not created yet, so the app will have full access once it is
+                            CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(()),
+                            err => Err(AuthError::from(err)),
+                        })
+                        .map(move |_| mdata_info)
                         .into_box()
                 }
                 None => {
-                    // If the container is not found, create it
-                    create(&c2, app_sign_pk)
-                        .and_then(move |md_info| {
-                            let _ = ac_entries.insert(app_cont_name, md_info.clone());
+                    // If the container is not found, reserve it
+                    let md_info = fry!(MDataInfo::random_private(DIR_TAG).map_err(
+                        AuthError::from,
+                    ));
+                    let _ = ac_entries.insert(app_cont_name, md_info.clone());
 
-                            access_container::put_authenticator_entry(
-                                &c3,
-                                &ac_entries,
-                                ac_entry_version + 1,
-                            ).map(move |()| md_info)
-                        })
+                    access_container::put_authenticator_entry(
+                        &c3,
+                        &ac_entries,
+                        ac_entry_version + 1,
+                    ).map(move |()| md_info)
                         .into_box()
                 }
             }
@@ -109,7 +116,18 @@ pub fn remove(client: Client<()>, app_id: &str) -> Box<AuthFuture<bool>> {
                     let c3 = c2.clone();
 
                     c2.list_mdata_entries(mdata_info.name, mdata_info.type_tag)
+                        .map(Some)
+                        .or_else(|err| match err {
+                            // Never used by the app, so not created
+                            CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(None),
+                            err => Err(err),
+                        })
                         .and_then(move |entries| {
+                            let entries = match entries {
+                                Some(entries) => entries,
+                                None => return ok!(()),
+                            };
+
                             // Remove all entries in MData
                             let actions = entries.iter().fold(EntryActions::new(), |actions,
                              (entry_name, val)| {
@@ -142,19 +160,3 @@ pub fn remove(client: Client<()>, app_id: &str) -> Box<AuthFuture<bool>> {
         })
         .into_box()
 }
-
-// Creates a new app's dedicated container
-fn create(client: &Client<()>, app_sign_pk: sign::PublicKey) -> Box<AuthFuture<MDataInfo>> {
-    let dir = fry!(MDataInfo::random_private(DIR_TAG).map_err(AuthError::from));
-    nfs::create_dir(
-        client,
-        &dir,
-        btree_map![],
-        btree_map![User::Key(app_sign_pk) => PermissionSet::new()
-                .allow(Action::Insert)
-                .allow(Action::Update)
-                .allow(Action::Delete)
-                .allow(Action::ManagePermissions)],
-    ).map(move |()| dir).map_err(From::from)
-        .into_box()
-}
@@ -19,14 +19,17 @@ use super::{AuthError, AuthFuture};
 use futures::Future;
 use futures::future::{self, Either, Loop};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
+use pending::{PendingRequest, PendingRequests};
+use policy::AuthPolicy;
 use routing::{ClientError, EntryActions, EntryError};
 use safe_core::{Client, CoreError, FutureExt};
 use safe_core::ipc::IpcError;
-use safe_core::ipc::req::AppExchangeInfo;
+use safe_core::ipc::req::{AppExchangeInfo, AuthReq, ContainerPermissions};
 use safe_core::ipc::resp::AppKeys;
+use safe_core::utils::migration::Format;
 use serde::Serialize;
 use serde::de::DeserializeOwned;
-use std::collections::{HashMap, VecDeque};
+use std::collections::{BTreeMap, HashMap, VecDeque};
 use tiny_keccak::sha3_256;
 
 /// App data stored in the authenticator configuration.
@@ -42,6 +45,21 @@ pub struct AppInfo {
     pub info: AppExchangeInfo,
     /// Application keys
     pub keys: AppKeys,
+    /// Whether the app was registered with a scope before every scope was registered
+    /// separately. Its access container entry and app container are then keyed by the
+    /// plain ID instead of the scoped one.
+    pub legacy_id: bool,
+}
+
+impl AppInfo {
+    /// ID the access container entry and the app container of the app are keyed by.
+    pub fn registered_id(&self) -> String {
+        if self.legacy_id {
+            self.info.id.clone()
+        } else {
+            self.info.scoped_id()
+        }
+    }
 }
 
 /// Config file key under which the list of registered apps is stored.
@@ -50,6 +68,24 @@ pub const KEY_APPS: &[u8] = b"apps";
 /// Config file key under which the revocation queue is stored.
 pub const KEY_APP_REVOCATION_QUEUE: &[u8] = b"revocation-queue";
 
+/// Config file key under which the authorisation policy is stored.
+pub const KEY_AUTH_POLICY: &[u8] = b"auth-policy";
+
+/// Config file key under which the queue of pending auth requests is stored.
+pub const KEY_PENDING_REQUESTS: &[u8] = b"pending-requests";
+
+/// Format of the serialised registered apps, applied before encryption.
+pub const APPS_FORMAT: Format = Format {
+    header: b"SAFE-APS",
+    migrations: &[migrate_apps_v0, migrate_apps_v1],
+};
+
+/// Format of the serialised pending requests, applied before encryption.
+pub const PENDING_REQUESTS_FORMAT: Format = Format {
+    header: b"SAFE-PRQ",
+    migrations: &[migrate_pending_requests_v0],
+};
+
 /// Maps from a SHA-3 hash of an app ID to app info
 pub type Apps = HashMap<[u8; 32], AppInfo>;
 /// Contains a queue of revocations that are currently running or have failed
@@ -86,7 +122,7 @@ pub fn insert_app(
     app: AppInfo,
 ) -> Box<AuthFuture<(u64, Apps)>> {
     let client = client.clone();
-    let hash = sha3_256(app.info.id.as_bytes());
+    let hash = sha3_256(app.info.scoped_id().as_bytes());
 
     mutate_entry(&client, KEY_APPS, apps, new_version, move |apps| {
         apps.insert(hash, app.clone()).is_none()
@@ -159,11 +195,53 @@ pub fn remove_from_app_revocation_queue(
     )
 }
 
+/// Get the authorisation policy.
+/// Returns version and the policy in a tuple.
+/// If the policy is not found in the config file, returns the default (empty) one.
+pub fn get_auth_policy(client: &Client<()>) -> Box<AuthFuture<(Option<u64>, AuthPolicy)>> {
+    get_entry(client, KEY_AUTH_POLICY)
+}
+
+/// Atomically modify the authorisation policy and store it in the network.
+pub fn update_auth_policy<F>(
+    client: &Client<()>,
+    policy: AuthPolicy,
+    new_version: u64,
+    f: F,
+) -> Box<AuthFuture<(u64, AuthPolicy)>>
+where
+    F: Fn(&mut AuthPolicy) -> bool + 'static,
+{
+    mutate_entry(client, KEY_AUTH_POLICY, policy, new_version, f)
+}
+
+/// Get auth requests waiting for the user's decision.
+/// Returns version and the pending requests in a tuple.
+pub fn get_pending_requests(
+    client: &Client<()>,
+) -> Box<AuthFuture<(Option<u64>, PendingRequests)>> {
+    get_entry(client, KEY_PENDING_REQUESTS)
+}
+
+/// Atomically modify the pending requests and store them in the network.
+pub fn update_pending_requests<F>(
+    client: &Client<()>,
+    requests: PendingRequests,
+    new_version: u64,
+    f: F,
+) -> Box<AuthFuture<(u64, PendingRequests)>>
+where
+    F: Fn(&mut PendingRequests) -> bool + 'static,
+{
+    mutate_entry(client, KEY_PENDING_REQUESTS, requests, new_version, f)
+}
+
 fn get_entry<T>(client: &Client<()>, key: &[u8]) -> Box<AuthFuture<(Option<u64>, T)>>
 where
     T: Default + DeserializeOwned + Serialize + 'static,
 {
     let parent = fry!(client.config_root_dir());
+    let raw_key = key.to_vec();
     let key = fry!(parent.enc_entry_key(key));
 
     client
@@ -171,6 +249,10 @@ where
         .and_then(move |value| {
             let decoded = parent.decrypt(&value.content)?;
             let decoded = if !decoded.is_empty() {
+                let decoded = match entry_format(&raw_key) {
+                    Some(format) => format.decode(&decoded)?.0,
+                    None => decoded,
+                };
                 deserialise(&decoded)?
             } else {
                 Default::default()
@@ -198,8 +280,13 @@ where
 {
     let parent = fry!(client.config_root_dir());
 
-    let key = fry!(parent.enc_entry_key(key));
     let encoded = fry!(serialise(content));
+    let encoded = match entry_format(key) {
+        Some(format) => format.encode(&encoded),
+        None => encoded,
+    };
+
+    let key = fry!(parent.enc_entry_key(key));
     let encoded = fry!(parent.enc_entry_value(&encoded));
 
     let actions = if new_version == 0 {
@@ -273,7 +360,242 @@ where
                 });
             Either::A(f)
         } else {
-            Either::B(future::ok(Loop::Break((new_version - 1, item))))
+            Either::B(future::ok(Loop::Break((new_version.saturating_sub(1), item))))
         }
     }).into_box()
 }
+
+// Entries written before versioning was introduced stay unversioned.
+fn entry_format(key: &[u8]) -> Option<&'static Format> {
+    if key == KEY_APPS {
+        Some(&APPS_FORMAT)
+    } else if key == KEY_PENDING_REQUESTS {
+        Some(&PENDING_REQUESTS_FORMAT)
+    } else {
+        None
+    }
+}
+
+// Layouts predating the display metadata (description, homepage and icon) of apps.
+
+#[derive(Deserialize)]
+struct AppExchangeInfoV0 {
+    id: String,
+    scope: Option<String>,
+    name: String,
+    vendor: String,
+}
+
+impl From<AppExchangeInfoV0> for AppExchangeInfo {
+    fn from(info: AppExchangeInfoV0) -> Self {
+        AppExchangeInfo {
+            id: info.id,
+            scope: info.scope,
+            name: info.name,
+            vendor: info.vendor,
+            description: None,
+            homepage: None,
+            icon: None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct AppInfoV0 {
+    info: AppExchangeInfoV0,
+    keys: AppKeys,
+}
+
+// Layout predating the separate registration of every scope of an app.
+#[derive(Serialize, Deserialize)]
+struct AppInfoV1 {
+    info: AppExchangeInfo,
+    keys: AppKeys,
+}
+
+#[derive(Deserialize)]
+struct AuthReqV0 {
+    app: AppExchangeInfoV0,
+    app_container: bool,
+    containers: HashMap<String, ContainerPermissions>,
+}
+
+#[derive(Deserialize)]
+struct PendingRequestV0 {
+    req: AuthReqV0,
+    expires_at: u64,
+}
+
+fn migrate_apps_v0(payload: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+    let apps: HashMap<[u8; 32], AppInfoV0> = deserialise(&payload)?;
+    let apps: HashMap<[u8; 32], AppInfoV1> = apps
+        .into_iter()
+        .map(|(hash, app)| {
+            (
+                hash,
+                AppInfoV1 {
+                    info: app.info.into(),
+                    keys: app.keys,
+                },
+            )
+        })
+        .collect();
+    Ok(serialise(&apps)?)
+}
+
+// Scoped apps used to be registered under their plain ID. They are moved under their
+// scoped ID, but keep using the plain one for their access container entry.
+fn migrate_apps_v1(payload: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+    let apps: HashMap<[u8; 32], AppInfoV1> = deserialise(&payload)?;
+    let apps: Apps = apps
+        .into_iter()
+        .map(|(hash, app)| {
+            let legacy_id = app.info.scope.is_some() && hash == sha3_256(app.info.id.as_bytes());
+            let hash = sha3_256(app.info.scoped_id().as_bytes());
+            (
+                hash,
+                AppInfo {
+                    info: app.info,
+                    keys: app.keys,
+                    legacy_id,
+                },
+            )
+        })
+        .collect();
+    Ok(serialise(&apps)?)
+}
+
+fn migrate_pending_requests_v0(payload: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+    let requests: BTreeMap<u32, PendingRequestV0> = deserialise(&payload)?;
+    let requests: PendingRequests = requests
+        .into_iter()
+        .map(|(req_id, pending)| {
+            let req = AuthReq {
+                app: pending.req.app.into(),
+                app_container: pending.req.app_container,
+                containers: pending.req.containers,
+            };
+            (
+                req_id,
+                PendingRequest {
+                    req: req,
+                    expires_at: pending.expires_at,
+                },
+            )
+        })
+        .collect();
+    Ok(serialise(&requests)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rust_sodium::crypto::sign;
+    use safe_core::ipc::req::AppIcon;
+
+    #[derive(Serialize)]
+    struct OldAppExchangeInfo {
+        id: String,
+        scope: Option<String>,
+        name: String,
+        vendor: String,
+    }
+
+    #[derive(Serialize)]
+    struct OldAppInfo {
+        info: OldAppExchangeInfo,
+        keys: AppKeys,
+    }
+
+    // Test that apps registered before the display metadata existed are still readable.
+    #[test]
+    fn apps_migration() {
+        let (owner_key, _) = sign::gen_keypair();
+        let keys = AppKeys::random(owner_key);
+        let old_app = OldAppInfo {
+            info: OldAppExchangeInfo {
+                id: "app".to_string(),
+                scope: None,
+                name: "App".to_string(),
+                vendor: "Vendor".to_string(),
+            },
+            keys: keys.clone(),
+        };
+        let hash = sha3_256(b"app");
+        let mut old_apps = HashMap::new();
+        let _ = old_apps.insert(hash, old_app);
+
+        // Unversioned data is migrated.
+        let (payload, migrated) = unwrap!(APPS_FORMAT.decode(&unwrap!(serialise(&old_apps))));
+        assert!(migrated);
+        let apps: Apps = unwrap!(deserialise(&payload));
+        let app = unwrap!(apps.get(&hash));
+        assert_eq!(app.info.id, "app");
+        assert_eq!(app.info.name, "App");
+        assert_eq!(app.info.vendor, "Vendor");
+        assert_eq!(app.info.description, None);
+        assert_eq!(app.info.icon, None);
+        assert_eq!(app.keys, keys);
+
+        // The current layout round-trips.
+        let mut apps = apps;
+        unwrap!(apps.get_mut(&hash)).info.icon = Some(AppIcon::Data(vec![1, 2, 3]));
+        let encoded = APPS_FORMAT.encode(&unwrap!(serialise(&apps)));
+        let (payload, migrated) = unwrap!(APPS_FORMAT.decode(&encoded));
+        assert!(!migrated);
+        let apps: Apps = unwrap!(deserialise(&payload));
+        assert_eq!(
+            unwrap!(apps.get(&hash)).info.icon,
+            Some(AppIcon::Data(vec![1, 2, 3]))
+        );
+    }
+
+    // Test that apps registered with a scope under their plain ID are moved under their
+    // scoped ID, but keep the plain ID for their access container entry.
+    #[test]
+    fn scoped_apps_migration() {
+        let (owner_key, _) = sign::gen_keypair();
+        let app_info = |id: &str| {
+            AppExchangeInfo {
+                id: id.to_string(),
+                scope: Some("work".to_string()),
+                name: "App".to_string(),
+                vendor: "Vendor".to_string(),
+                description: None,
+                homepage: None,
+                icon: None,
+            }
+        };
+        let legacy = AppInfoV1 {
+            info: app_info("legacy"),
+            keys: AppKeys::random(owner_key),
+        };
+        let current = AppInfoV1 {
+            info: app_info("current"),
+            keys: AppKeys::random(owner_key),
+        };
+
+        let mut old_apps = HashMap::new();
+        let _ = old_apps.insert(sha3_256(b"legacy"), legacy);
+        let _ = old_apps.insert(sha3_256(b"current#work"), current);
+
+        let v1 = Format {
+            header: APPS_FORMAT.header,
+            migrations: &[migrate_apps_v0],
+        };
+        let (payload, migrated) = unwrap!(APPS_FORMAT.decode(
+            &v1.encode(&unwrap!(serialise(&old_apps))),
+        ));
+        assert!(migrated);
+        let apps: Apps = unwrap!(deserialise(&payload));
+        assert_eq!(apps.len(), 2);
+
+        let legacy = unwrap!(apps.get(&sha3_256(b"legacy#work")));
+        assert!(legacy.legacy_id);
+        assert_eq!(legacy.registered_id(), "legacy");
+
+        let current = unwrap!(apps.get(&sha3_256(b"current#work")));
+        assert!(!current.legacy_id);
+        assert_eq!(current.registered_id(), "current#work");
+    }
+}
@@ -0,0 +1,236 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Devices that have logged into the account.
+//!
+//! The list is kept in its own entry of the session packet, encrypted with the secret
+//! symmetric key of the account. A device identifies itself by a signing key pair it
+//! generates and keeps locally, and registers after each login so its last-seen time stays
+//! current. Revoking a device flags it in the list, and its next registration fails with
+//! `DeviceRevoked`, telling it to wipe the credentials it has cached.
+//!
+//! Revocation is advisory: every device logs in with the same account keys, so the network
+//! can't tell them apart and a device which skips registration keeps full access for as long
+//! as it holds the keys.
+
+use {AuthError, AuthFuture};
+use futures::Future;
+use routing::{ClientError, EntryActions};
+use rust_sodium::crypto::sign;
+use safe_core::{Client, CoreError, FutureExt, KeyOp};
+use safe_core::ipc::now_secs;
+use safe_core::utils::{symmetric_decrypt, symmetric_encrypt};
+use safe_core::utils::migration::Format;
+use safe_core::utils::wire_format::{deserialise, serialise};
+
+/// Key of the session packet entry listing the devices.
+pub const DEVICES_ENTRY_KEY: &[u8] = b"devices";
+
+/// Format of the serialised device list, applied before encryption.
+pub const DEVICES_FORMAT: Format = Format {
+    header: b"SAFE-DEV",
+    migrations: &[],
+};
+
+/// Device that has logged into the account.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Device {
+    /// User-facing name of the device.
+    pub name: String,
+    /// Public key identifying the device.
+    pub public_key: sign::PublicKey,
+    /// Time of the first registration, in seconds since the Unix epoch.
+    pub first_seen: u64,
+    /// Time of the last registration, in seconds since the Unix epoch.
+    pub last_seen: u64,
+    /// Whether the device has been revoked.
+    pub revoked: bool,
+}
+
+/// Lists devices that have registered with the account, including revoked ones.
+pub fn list(client: &Client<()>) -> Box<AuthFuture<Vec<Device>>> {
+    fetch(client).map(|(_, devices)| devices).into_box()
+}
+
+/// Registers a login from the device identified by `public_key`, recording it on its first
+/// login and updating its name and last-seen time afterwards. Fails with `DeviceRevoked` if
+/// the device has been revoked.
+pub fn register(
+    client: &Client<()>,
+    name: String,
+    public_key: sign::PublicKey,
+) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    fetch(client)
+        .and_then(move |(version, mut devices)| {
+            let now = now_secs();
+            let revoked = match devices.iter().position(|d| d.public_key == public_key) {
+                Some(index) => {
+                    let device = &mut devices[index];
+                    if !device.revoked {
+                        device.name = name;
+                        device.last_seen = now;
+                    }
+                    device.revoked
+                }
+                None => {
+                    devices.push(Device {
+                        name,
+                        public_key,
+                        first_seen: now,
+                        last_seen: now,
+                        revoked: false,
+                    });
+                    false
+                }
+            };
+
+            if revoked {
+                err!(AuthError::DeviceRevoked)
+            } else {
+                store(&c2, version, &devices)
+            }
+        })
+        .into_box()
+}
+
+/// Revokes the device identified by `public_key`, which only takes effect if the device
+/// registers again (see the module docs). Fails with `NoSuchDevice` if it has never
+/// registered.
+pub fn revoke(client: &Client<()>, public_key: sign::PublicKey) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    fetch(client)
+        .and_then(move |(version, mut devices)| {
+            match devices.iter_mut().find(|d| d.public_key == public_key) {
+                Some(device) => device.revoked = true,
+                None => return err!(AuthError::NoSuchDevice),
+            }
+            store(&c2, version, &devices)
+        })
+        .into_box()
+}
+
+/// Deletes the device list.
+pub fn clear(client: &Client<()>) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    fetch(client)
+        .and_then(move |(version, devices)| match version {
+            Some(version) if !devices.is_empty() => {
+                c2.mutate_session_packet_entries(
+                    EntryActions::new()
+                        .del(DEVICES_ENTRY_KEY.to_vec(), version + 1)
+                        .into(),
+                ).map_err(AuthError::from)
+                    .into_box()
+            }
+            _ => ok!(()),
+        })
+        .into_box()
+}
+
+// Fetches the device list along with the version of its entry, which is `None` if the entry
+// hasn't been created yet.
+fn fetch(client: &Client<()>) -> Box<AuthFuture<(Option<u64>, Vec<Device>)>> {
+    let c2 = client.clone();
+    let sk = fry!(client.secret_symmetric_key());
+
+    client
+        .get_session_packet_value(DEVICES_ENTRY_KEY.to_vec())
+        .then(move |res| match res {
+            Ok(ref value) if value.content.is_empty() => Ok((Some(value.entry_version), vec![])),
+            Ok(value) => {
+                c2.record_key_usage(KeyOp::Decrypt, None);
+                let plaintext = symmetric_decrypt(&value.content, &sk)?;
+                let (plaintext, _) = DEVICES_FORMAT.decode(&plaintext)?;
+                Ok((Some(value.entry_version), deserialise(&plaintext)?))
+            }
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => Ok((None, vec![])),
+            Err(error) => Err(AuthError::from(error)),
+        })
+        .into_box()
+}
+
+fn store(client: &Client<()>, version: Option<u64>, devices: &[Device]) -> Box<AuthFuture<()>> {
+    let content = {
+        let sk = fry!(client.secret_symmetric_key());
+        let plaintext = DEVICES_FORMAT.encode(&fry!(serialise(&devices)));
+        fry!(symmetric_encrypt(&plaintext, &sk, None))
+    };
+    let key = DEVICES_ENTRY_KEY.to_vec();
+
+    let actions = match version {
+        Some(version) => EntryActions::new().update(key, content, version + 1),
+        None => EntryActions::new().ins(key, content, 0),
+    };
+
+    client
+        .mutate_session_packet_entries(actions.into())
+        .map_err(AuthError::from)
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Authenticator;
+    use test_utils::{create_authenticator, run, try_run};
+
+    // Test registering, listing and revoking devices from two logins to the same account.
+    #[test]
+    fn register_and_revoke() {
+        let (auth, locator, password) = create_authenticator();
+        let (laptop_pk, _) = sign::gen_keypair();
+        let (phone_pk, _) = sign::gen_keypair();
+
+        run(&auth, move |client| register(client, "laptop".to_owned(), laptop_pk));
+        let auth2 = unwrap!(Authenticator::login(locator, password, || ()));
+        run(&auth2, move |client| register(client, "phone".to_owned(), phone_pk));
+        run(&auth, move |client| register(client, "work laptop".to_owned(), laptop_pk));
+
+        let devices = run(&auth2, list);
+        assert_eq!(devices.len(), 2);
+        assert_eq!(devices[0].name, "work laptop");
+        assert_eq!(devices[0].public_key, laptop_pk);
+        assert!(devices[0].first_seen <= devices[0].last_seen);
+        assert_eq!(devices[1].name, "phone");
+        assert!(devices.iter().all(|device| !device.revoked));
+
+        run(&auth2, move |client| revoke(client, laptop_pk));
+        match try_run(&auth, move |client| {
+            register(client, "laptop".to_owned(), laptop_pk)
+        }) {
+            Err(AuthError::DeviceRevoked) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let devices = run(&auth, list);
+        assert!(devices[0].revoked);
+        assert!(!devices[1].revoked);
+
+        let (unknown_pk, _) = sign::gen_keypair();
+        match try_run(&auth, move |client| revoke(client, unknown_pk)) {
+            Err(AuthError::NoSuchDevice) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        run(&auth, clear);
+        assert!(run(&auth, list).is_empty());
+    }
+}
@@ -0,0 +1,597 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Export and import of the account's files.
+//!
+//! `export` downloads the files of the standard containers into a local directory, each
+//! container becoming a sub-directory named after it in which the directory tree of the
+//! container is recreated. `_publicNames` is left out, as it only maps public IDs to their
+//! services. The `manifest.json` written next to the containers lists the exported
+//! directories and files, along with the times and user metadata of the latter, which the
+//! local file system can't hold.
+//!
+//! `import` uploads such an export into the containers of the same names, possibly of
+//! another account. Files which already exist are left as they are, so an interrupted
+//! import can be run again.
+//!
+//! App containers aren't exported, as they can only be used by their apps, which would have
+//! to be authorised again first. Entries which are neither files nor directories, or whose
+//! names can't be used as local file names, are listed as skipped in the manifest.
+
+use {AuthError, AuthFuture};
+use access_container;
+use chrono::{DateTime, Utc};
+use futures::{Future, future, stream};
+use futures::future::Loop;
+use futures::stream::Stream;
+use public_id::PUBLIC_NAMES_CONTAINER;
+use safe_core::{Client, DIR_TAG, FutureExt, MDataInfo};
+use safe_core::ipc::now_secs;
+use safe_core::nfs::{self, Entry, File, Mode, file_helper, journal};
+use serde_json;
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Name of the manifest file in the export directory.
+pub const MANIFEST_FILE_NAME: &'static str = "manifest.json";
+
+/// Version of the manifest format written by `export`.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Description of an export, stored in the export directory as `manifest.json`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct Manifest {
+    /// Version of the manifest format.
+    pub version: u32,
+    /// Time of the export in seconds since the Unix epoch.
+    pub exported_at: u64,
+    /// Exported directories, parents coming before their sub-directories.
+    pub dirs: Vec<ExportedDir>,
+    /// Exported files.
+    pub files: Vec<ExportedFile>,
+    /// Entries which weren't exported, as `container/path`.
+    pub skipped: Vec<String>,
+}
+
+/// Directory listed in a `Manifest`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ExportedDir {
+    /// Container the directory is in.
+    pub container: String,
+    /// `/`-separated path of the directory in the container, empty for the container itself.
+    pub path: String,
+}
+
+/// File listed in a `Manifest`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct ExportedFile {
+    /// Container the file is in.
+    pub container: String,
+    /// `/`-separated path of the file in the container.
+    pub path: String,
+    /// Size of the file in bytes.
+    pub size: u64,
+    /// Creation time of the file.
+    pub created: DateTime<Utc>,
+    /// Modification time of the file.
+    pub modified: DateTime<Utc>,
+    /// User metadata of the file.
+    pub user_metadata: Vec<u8>,
+}
+
+/// Outcome of an `import`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ImportSummary {
+    /// Number of files uploaded.
+    pub imported: usize,
+    /// Files which weren't uploaded, as `container/path`, because a file or directory of the
+    /// same name already existed or because the account has no such container.
+    pub skipped: Vec<String>,
+}
+
+/// Exports the files of the standard containers into `dest`, which is created if needed,
+/// and returns the manifest written along with them.
+pub fn export(client: &Client<()>, dest: &Path) -> Box<AuthFuture<Manifest>> {
+    let client = client.clone();
+    let dest = dest.to_path_buf();
+
+    access_container::fetch_authenticator_entry(&client)
+        .and_then(move |(_, containers)| {
+            let dest2 = dest.clone();
+            let manifest = Manifest {
+                version: MANIFEST_VERSION,
+                exported_at: now_secs(),
+                dirs: Vec::new(),
+                files: Vec::new(),
+                skipped: Vec::new(),
+            };
+            // Directories left to export, in reverse order as the last one is taken first.
+            let pending: Vec<_> = containers
+                .into_iter()
+                .rev()
+                .filter(|&(ref name, _)| name != PUBLIC_NAMES_CONTAINER)
+                .map(|(name, info)| {
+                    let dir = ExportedDir {
+                        container: name,
+                        path: String::new(),
+                    };
+                    (dir, info)
+                })
+                .collect();
+
+            future::loop_fn((manifest, pending), move |(manifest, mut pending)| {
+                let (dir, info) = match pending.pop() {
+                    Some(item) => item,
+                    None => return ok!(Loop::Break(manifest)),
+                };
+
+                export_dir(&client, &dest2, dir, &info, manifest)
+                    .map(move |(manifest, sub_dirs)| {
+                        pending.extend(sub_dirs.into_iter().rev());
+                        Loop::Continue((manifest, pending))
+                    })
+                    .into_box()
+            }).map(move |manifest| (manifest, dest))
+        })
+        .and_then(|(manifest, dest)| -> Result<_, AuthError> {
+            let json = serde_json::to_vec_pretty(&manifest).map_err(
+                |err| AuthError::from(err.to_string()),
+            )?;
+            fs::File::create(dest.join(MANIFEST_FILE_NAME))?.write_all(&json)?;
+            Ok(manifest)
+        })
+        .into_box()
+}
+
+/// Uploads the files exported into `src` by `export` into the containers of the same names.
+/// Manifests listing paths which would lead out of `src` are rejected.
+pub fn import(client: &Client<()>, src: &Path) -> Box<AuthFuture<ImportSummary>> {
+    let mut json = Vec::new();
+    fry!(fs::File::open(src.join(MANIFEST_FILE_NAME)).and_then(
+        |mut file| file.read_to_end(&mut json),
+    ));
+    let manifest: Manifest = fry!(serde_json::from_slice(&json).map_err(
+        |err| AuthError::from(err.to_string()),
+    ));
+    if manifest.version != MANIFEST_VERSION {
+        return err!(AuthError::from(
+            format!("Unsupported manifest version {}", manifest.version),
+        ));
+    }
+
+    // The paths are joined onto `src`, so they must not lead out of it.
+    if let Some(path) = manifest
+        .dirs
+        .iter()
+        .filter(|dir| !is_valid_path(&dir.container, &dir.path, true))
+        .map(|dir| format!("{}/{}", dir.container, dir.path))
+        .chain(
+            manifest
+                .files
+                .iter()
+                .filter(|file| !is_valid_path(&file.container, &file.path, false))
+                .map(|file| format!("{}/{}", file.container, file.path)),
+        )
+        .next()
+    {
+        return err!(AuthError::from(format!("Invalid path in manifest: {}", path)));
+    }
+
+    let Manifest { dirs, files, .. } = manifest;
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let src = src.to_path_buf();
+
+    access_container::fetch_authenticator_entry(client)
+        .and_then(move |(_, containers)| {
+            let known: BTreeMap<_, _> = containers
+                .into_iter()
+                .map(|(name, info)| ((name, String::new()), info))
+                .collect();
+
+            stream::iter_ok::<_, AuthError>(dirs).fold(known, move |known, dir| {
+                import_dir(&c2, known, dir)
+            })
+        })
+        .and_then(move |known| {
+            let summary = ImportSummary {
+                imported: 0,
+                skipped: Vec::new(),
+            };
+
+            stream::iter_ok::<_, AuthError>(files).fold(summary, move |mut summary, file| {
+                let desc = format!("{}/{}", file.container, file.path);
+                let key = (file.container.clone(), parent_path(&file.path).to_string());
+                let parent = match known.get(&key) {
+                    Some(parent) => parent.clone(),
+                    None => {
+                        summary.skipped.push(desc);
+                        return ok!(summary);
+                    }
+                };
+
+                let local = local_path(&src, &file.container, &file.path);
+                import_file(&c3, parent, &local, file)
+                    .map(move |imported| {
+                        if imported {
+                            summary.imported += 1;
+                        } else {
+                            summary.skipped.push(desc);
+                        }
+                        summary
+                    })
+                    .into_box()
+            })
+        })
+        .into_box()
+}
+
+// Writes the files of `dir` into the export directory and records them in the manifest.
+// Returns the manifest along with the sub-directories of `dir`, which are left to export.
+fn export_dir(
+    client: &Client<()>,
+    dest: &Path,
+    dir: ExportedDir,
+    info: &MDataInfo,
+    mut manifest: Manifest,
+) -> Box<AuthFuture<(Manifest, Vec<(ExportedDir, MDataInfo)>)>> {
+    let local_dir = local_path(dest, &dir.container, &dir.path);
+    fry!(fs::create_dir_all(&local_dir));
+
+    let client = client.clone();
+    let info = info.clone();
+
+    nfs::list_dir(&client, &info)
+        .map_err(AuthError::from)
+        .and_then(move |entries| {
+            let mut sub_dirs = Vec::new();
+            let mut files = Vec::new();
+
+            for (key, value) in entries {
+                let name = String::from_utf8_lossy(&key).into_owned();
+                let path = if dir.path.is_empty() {
+                    name.clone()
+                } else {
+                    format!("{}/{}", dir.path, name)
+                };
+
+                if !is_valid_name(&key) {
+                    manifest.skipped.push(format!("{}/{}", dir.container, path));
+                    continue;
+                }
+
+                match nfs::decode_entry(&value.content) {
+                    Ok(Entry::Dir(sub_dir)) => {
+                        let exported = ExportedDir {
+                            container: dir.container.clone(),
+                            path: path,
+                        };
+                        sub_dirs.push((exported, sub_dir));
+                    }
+                    Ok(Entry::File(file)) => files.push((name, path, file)),
+                    Err(_) => manifest.skipped.push(format!("{}/{}", dir.container, path)),
+                }
+            }
+
+            let container = dir.container.clone();
+            manifest.dirs.push(dir);
+
+            stream::iter_ok::<_, AuthError>(files)
+                .fold(manifest, move |mut manifest, (name, path, file)| {
+                    let local = local_dir.join(&name);
+                    let exported = ExportedFile {
+                        container: container.clone(),
+                        path: path,
+                        size: file.size(),
+                        created: *file.created_time(),
+                        modified: *file.modified_time(),
+                        user_metadata: file.user_metadata().to_vec(),
+                    };
+
+                    file_helper::read(client.clone(), &file, info.enc_key().cloned())
+                        .and_then(|reader| reader.read(0, reader.size()))
+                        .map_err(AuthError::from)
+                        .and_then(move |content| -> Result<_, AuthError> {
+                            fs::File::create(local)?.write_all(&content)?;
+                            manifest.files.push(exported);
+                            Ok(manifest)
+                        })
+                })
+                .map(move |manifest| (manifest, sub_dirs))
+        })
+        .into_box()
+}
+
+// Looks the directory up in its parent, creating it if it doesn't exist yet, and adds it to
+// the known directories. Directories whose parent isn't known are skipped along with their
+// files.
+fn import_dir(
+    client: &Client<()>,
+    mut known: BTreeMap<(String, String), MDataInfo>,
+    dir: ExportedDir,
+) -> Box<AuthFuture<BTreeMap<(String, String), MDataInfo>>> {
+    if dir.path.is_empty() {
+        return ok!(known);
+    }
+    let parent_key = (dir.container.clone(), parent_path(&dir.path).to_string());
+    let parent = match known.get(&parent_key) {
+        Some(parent) => parent.clone(),
+        None => return ok!(known),
+    };
+    let name = file_name(&dir.path).to_string();
+    let client = client.clone();
+
+    nfs::list_dir(&client, &parent)
+        .map_err(AuthError::from)
+        .and_then(move |entries| {
+            if let Some(value) = entries.get(name.as_bytes()) {
+                // An existing file of the same name makes the directory skipped.
+                if let Ok(Entry::Dir(info)) = nfs::decode_entry(&value.content) {
+                    let _ = known.insert((dir.container, dir.path), info);
+                }
+                return ok!(known);
+            }
+
+            // Sub-directories of public containers are kept public.
+            let info = if parent.enc_info.is_some() {
+                fry!(MDataInfo::random_private(DIR_TAG))
+            } else {
+                fry!(MDataInfo::random_public(DIR_TAG))
+            };
+
+            journal::create_sub_dir(&client, &parent, name, &info, btree_map![])
+                .map_err(AuthError::from)
+                .map(move |_| {
+                    let _ = known.insert((dir.container, dir.path), info);
+                    known
+                })
+                .into_box()
+        })
+        .into_box()
+}
+
+// Uploads the local file into `parent`, unless an entry of the same name exists there
+// already. Returns whether it was uploaded.
+fn import_file(
+    client: &Client<()>,
+    parent: MDataInfo,
+    local: &Path,
+    exported: ExportedFile,
+) -> Box<AuthFuture<bool>> {
+    let mut content = Vec::new();
+    fry!(fs::File::open(local).and_then(
+        |mut file| file.read_to_end(&mut content),
+    ));
+
+    let name = file_name(&exported.path).to_string();
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    nfs::list_dir(client, &parent)
+        .map_err(AuthError::from)
+        .and_then(move |entries| {
+            if entries.contains_key(name.as_bytes()) {
+                return ok!(false);
+            }
+
+            let enc_key = parent.enc_key().cloned();
+            let ExportedFile {
+                created,
+                modified,
+                user_metadata,
+                ..
+            } = exported;
+
+            file_helper::write(c2, File::new(user_metadata), Mode::Overwrite, enc_key)
+                .and_then(move |writer| {
+                    writer.write(&content).and_then(move |_| writer.close())
+                })
+                .and_then(move |mut file| {
+                    file.set_created_time(created);
+                    file.set_modified_time(modified);
+                    file_helper::insert(c3, parent, name, &file)
+                })
+                .map_err(AuthError::from)
+                .map(|_| true)
+                .into_box()
+        })
+        .into_box()
+}
+
+// Whether the entry name can be used as a local file name on any platform we support.
+fn is_valid_name(name: &[u8]) -> bool {
+    match ::std::str::from_utf8(name) {
+        Ok(name) => {
+            !name.is_empty() && name != "." && name != ".." &&
+                !name.contains(|c| c == '/' || c == '\\' || c == '\0') &&
+                is_normal_component(name)
+        }
+        Err(_) => false,
+    }
+}
+
+// Whether the name is a single plain component of a local path, e.g. not a drive prefix.
+fn is_normal_component(name: &str) -> bool {
+    let mut components = Path::new(name).components();
+    match (components.next(), components.next()) {
+        (Some(Component::Normal(_)), None) => true,
+        _ => false,
+    }
+}
+
+// Whether the entry at `path` in `container` can be joined onto a local directory without
+// leading out of it. `path` can only be empty if `allow_empty` is set.
+fn is_valid_path(container: &str, path: &str, allow_empty: bool) -> bool {
+    if !is_valid_name(container.as_bytes()) {
+        return false;
+    }
+    if path.is_empty() {
+        allow_empty
+    } else {
+        path.split('/').all(|name| is_valid_name(name.as_bytes()))
+    }
+}
+
+// Local path of the entry at `path` in `container`.
+fn local_path(root: &Path, container: &str, path: &str) -> PathBuf {
+    path.split('/').filter(|name| !name.is_empty()).fold(
+        root.join(container),
+        |local, name| local.join(name),
+    )
+}
+
+// Path of the directory containing the entry at `path`.
+fn parent_path(path: &str) -> &str {
+    path.rfind('/').map_or("", |index| &path[..index])
+}
+
+// Name of the entry at `path`.
+fn file_name(path: &str) -> &str {
+    path.rfind('/').map_or(path, |index| &path[index + 1..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use std::env;
+    use test_utils::{create_account_and_login, create_file,
+                     get_container_from_authenticator_entry, run, try_run};
+
+    // Test exporting the files of one account and importing them into another one.
+    #[test]
+    fn export_and_import() {
+        let auth = create_account_and_login();
+        let docs = unwrap!(get_container_from_authenticator_entry(&auth, "_documents"));
+        unwrap!(create_file(&auth, docs.clone(), "a.txt", b"hello".to_vec()));
+
+        let sub_dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+        let (d2, s2) = (docs.clone(), sub_dir.clone());
+        run(&auth, move |client| {
+            journal::create_sub_dir(client, &d2, "sub", &s2, btree_map![]).map_err(AuthError::from)
+        });
+        unwrap!(create_file(&auth, sub_dir, "b.txt", b"world".to_vec()));
+
+        let dest = env::temp_dir().join(format!("safe_auth_export_{}", rand::random::<u64>()));
+        let d2 = dest.clone();
+        let manifest = run(&auth, move |client| export(client, &d2));
+
+        let mut paths: Vec<_> = manifest
+            .files
+            .iter()
+            .filter(|file| file.container == "_documents")
+            .map(|file| file.path.as_str())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.txt", "sub/b.txt"]);
+        assert!(manifest.dirs.contains(&ExportedDir {
+            container: "_documents".to_string(),
+            path: "sub".to_string(),
+        }));
+        assert!(!manifest.dirs.iter().any(
+            |dir| dir.container == PUBLIC_NAMES_CONTAINER,
+        ));
+
+        let mut content = Vec::new();
+        let local = dest.join("_documents").join("sub").join("b.txt");
+        unwrap!(unwrap!(fs::File::open(local)).read_to_end(&mut content));
+        assert_eq!(content, b"world");
+
+        let other = create_account_and_login();
+        let d2 = dest.clone();
+        let summary = run(&other, move |client| import(client, &d2));
+        assert_eq!(summary.imported, 2);
+        assert!(summary.skipped.is_empty());
+
+        let docs = unwrap!(get_container_from_authenticator_entry(&other, "_documents"));
+        let (file, content) = run(&other, move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+
+            nfs::list_dir(client, &docs)
+                .and_then(move |entries| {
+                    let sub_dir = unwrap!(nfs::decode_entry(&entries[&b"sub"[..]].content));
+                    let sub_dir = unwrap!(sub_dir.into_dir());
+                    file_helper::fetch(c2, sub_dir.clone(), "b.txt")
+                        .map(move |(_, file)| (sub_dir, file))
+                })
+                .and_then(move |(sub_dir, file)| {
+                    file_helper::read(c3, &file, sub_dir.enc_key().cloned())
+                        .and_then(|reader| reader.read(0, reader.size()))
+                        .map(move |content| (file, content))
+                })
+                .map_err(AuthError::from)
+        });
+        assert_eq!(content, b"world");
+        let exported = unwrap!(manifest.files.iter().find(|file| file.path == "sub/b.txt"));
+        assert_eq!(*file.created_time(), exported.created);
+        assert_eq!(*file.modified_time(), exported.modified);
+
+        // Importing again leaves the existing files as they are.
+        let d2 = dest.clone();
+        let summary = run(&other, move |client| import(client, &d2));
+        assert_eq!(summary.imported, 0);
+        assert_eq!(summary.skipped.len(), 2);
+
+        let _ = fs::remove_dir_all(dest);
+    }
+
+    // Test that manifests with paths leading out of the export directory are rejected.
+    #[test]
+    fn import_invalid_paths() {
+        assert!(is_valid_path("_documents", "", true));
+        assert!(is_valid_path("_documents", "sub/b.txt", false));
+        assert!(!is_valid_path("_documents", "", false));
+        assert!(!is_valid_path("..", "", true));
+        assert!(!is_valid_path("_documents", "../b.txt", false));
+        assert!(!is_valid_path("_documents", "sub//b.txt", false));
+        assert!(!is_valid_path("_documents", "/etc/passwd", false));
+
+        let auth = create_account_and_login();
+        let src = env::temp_dir().join(format!("safe_auth_import_{}", rand::random::<u64>()));
+        unwrap!(fs::create_dir_all(&src));
+
+        let manifest = Manifest {
+            version: MANIFEST_VERSION,
+            exported_at: now_secs(),
+            dirs: vec![],
+            files: vec![
+                ExportedFile {
+                    container: "_documents".to_string(),
+                    path: "../../secret".to_string(),
+                    size: 0,
+                    created: Utc::now(),
+                    modified: Utc::now(),
+                    user_metadata: vec![],
+                },
+            ],
+            skipped: vec![],
+        };
+        let json = unwrap!(serde_json::to_vec(&manifest));
+        unwrap!(unwrap!(fs::File::create(src.join(MANIFEST_FILE_NAME))).write_all(&json));
+
+        let s2 = src.clone();
+        match try_run(&auth, move |client| import(client, &s2)) {
+            Err(AuthError::Unexpected(_)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let _ = fs::remove_dir_all(src);
+    }
+}
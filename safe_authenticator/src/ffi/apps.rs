@@ -17,27 +17,29 @@
 
 use AuthError;
 use Authenticator;
+use access_container;
 use app_auth::{AppState, app_state};
 use app_container;
 use config;
 use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, SafePtr, catch_unwind_cb, from_c_str,
-                vec_into_raw_parts};
+                ptr_as_ref, vec_into_raw_parts};
 use futures::Future;
-use maidsafe_utilities::serialisation::deserialise;
 use routing::User::Key;
 use routing::XorName;
 use rust_sodium::crypto::sign::PublicKey;
-use safe_core::FutureExt;
+use safe_core::{FutureExt, MDataInfo};
+use safe_core::entry_acl;
 use safe_core::ffi::arrays::XorNameArray;
 use safe_core::ffi::ipc::req::{AppExchangeInfo as FfiAppExchangeInfo,
                                ContainerPermissions as FfiContainerPermissions};
 use safe_core::ffi::ipc::resp::AppAccess as FfiAppAccess;
 use safe_core::ipc::{IpcError, access_container_enc_key};
 use safe_core::ipc::req::{AppExchangeInfo, containers_into_vec};
-use safe_core::ipc::resp::{AccessContainerEntry, AppAccess};
-use safe_core::utils::symmetric_decrypt;
+use safe_core::ipc::resp::AppAccess;
 use std::collections::HashMap;
+use std::ffi::CString;
 use std::os::raw::{c_char, c_void};
+use tiny_keccak::sha3_256;
 
 /// Application registered in the authenticator
 #[repr(C)]
@@ -65,7 +67,35 @@ impl Drop for RegisteredApp {
     }
 }
 
-/// Removes a revoked app from the authenticator config.
+/// Rule of the entry-level ACL of an MD, for a single app.
+#[repr(C)]
+pub struct EntryAclRule {
+    /// Pointer to the prefix of the entry keys the rule governs.
+    pub prefix: *const u8,
+    /// Length of the prefix.
+    pub prefix_len: usize,
+    /// Capacity of the prefix. Internal data required for the Rust allocator.
+    pub prefix_cap: usize,
+    /// App the rule applies to and its permissions over the entries.
+    pub app_access: FfiAppAccess,
+}
+
+impl Drop for EntryAclRule {
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Vec::from_raw_parts(self.prefix as *mut u8, self.prefix_len, self.prefix_cap);
+            if !self.app_access.name.is_null() {
+                let _ = CString::from_raw(self.app_access.name as *mut _);
+            }
+            if !self.app_access.app_id.is_null() {
+                let _ = CString::from_raw(self.app_access.app_id as *mut _);
+            }
+        }
+    }
+}
+
+/// Removes a revoked app from the authenticator config. As with `auth_revoke_app`, apps
+/// authorised with a scope are identified by their scoped ID.
 ///
 /// Callback parameters: user data, error code
 #[no_mangle]
@@ -81,9 +111,8 @@ pub unsafe extern "C" fn auth_rm_revoked_app(
     catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
         let app_id = from_c_str(app_id)?;
         let app_id2 = app_id.clone();
-        let app_id3 = app_id.clone();
 
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             let c2 = client.clone();
             let c3 = client.clone();
             let c4 = client.clone();
@@ -100,9 +129,16 @@ pub unsafe extern "C" fn auth_rm_revoked_app(
                     AppState::NotAuthenticated => Err(AuthError::IpcError(IpcError::UnknownApp)),
                 })
                 .and_then(move |(apps, apps_version)| {
+                    // Apps registered under their plain ID keep their app container there.
+                    let registered_id = match apps.get(&sha3_256(app_id2.as_bytes())) {
+                        Some(app) => app.registered_id(),
+                        None => return err!(AuthError::IpcError(IpcError::UnknownApp)),
+                    };
                     config::remove_app(&c3, apps, config::next_version(apps_version), &app_id2)
+                        .map(move |_| registered_id)
+                        .into_box()
                 })
-                .and_then(move |_| app_container::remove(c4, &app_id3))
+                .and_then(move |registered_id| app_container::remove(c4, &registered_id))
                 .then(move |res| {
                     call_result_cb!(res, user_data, o_cb);
                     Ok(())
@@ -128,7 +164,7 @@ pub unsafe extern "C" fn auth_revoked_apps(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             let c2 = client.clone();
             let c3 = client.clone();
 
@@ -150,7 +186,11 @@ pub unsafe extern "C" fn auth_revoked_apps(
                     })?;
 
                     for app in auth_cfg.values() {
-                        let key = access_container_enc_key(&app.info.id, &app.keys.enc_key, nonce)?;
+                        let key = access_container_enc_key(
+                            &app.registered_id(),
+                            &app.keys.enc_key,
+                            nonce,
+                        )?;
 
                         // If the app is not in the access container, or if the app entry has
                         // been deleted (is empty), then it's revoked.
@@ -194,7 +234,7 @@ pub unsafe extern "C" fn auth_registered_apps(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             let c2 = client.clone();
             let c3 = client.clone();
 
@@ -217,7 +257,11 @@ pub unsafe extern "C" fn auth_registered_apps(
                     })?;
 
                     for app in auth_cfg.values() {
-                        let key = access_container_enc_key(&app.info.id, &app.keys.enc_key, nonce)?;
+                        let key = access_container_enc_key(
+                            &app.registered_id(),
+                            &app.keys.enc_key,
+                            nonce,
+                        )?;
 
                         // Empty entry means it has been deleted.
                         let entry = match entries.get(&key) {
@@ -226,8 +270,10 @@ pub unsafe extern "C" fn auth_registered_apps(
                         };
 
                         if let Some(entry) = entry {
-                            let plaintext = symmetric_decrypt(&entry.content, &app.keys.enc_key)?;
-                            let app_access = deserialise::<AccessContainerEntry>(&plaintext)?;
+                            let app_access = access_container::decode_app_entry(
+                                &entry.content,
+                                &app.keys.enc_key,
+                            )?;
 
                             let containers =
                                 containers_into_vec(
@@ -280,7 +326,7 @@ pub unsafe extern "C" fn auth_apps_accessing_mutable_data(
     let name = XorName(*md_name);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             let c2 = client.clone();
 
             client
@@ -348,6 +394,83 @@ pub unsafe extern "C" fn auth_apps_accessing_mutable_data(
     })
 }
 
+/// Return the entry-level ACL of an MD (see `safe_core::entry_acl`), one item per prefix and
+/// app. Apps which aren't registered in the authenticator have null `name` and `app_id`.
+/// The ACL of the user's own private containers is decrypted, other MDs are read as public.
+///
+/// Callback parameters: user data, error code, ACL rule vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn auth_entry_acl(
+    auth: *const Authenticator,
+    md_name: *const XorNameArray,
+    md_type_tag: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        rules: *const EntryAclRule,
+                        rules_len: usize),
+) {
+    let user_data = OpaqueCtx(user_data);
+    let name = XorName(*md_name);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+
+            access_container::fetch_authenticator_entry(client)
+                .and_then(move |(_, containers)| {
+                    let info = containers
+                        .into_iter()
+                        .map(|(_, info)| info)
+                        .find(|info| info.name == name && info.type_tag == md_type_tag)
+                        .unwrap_or_else(|| MDataInfo::new_public(name, md_type_tag));
+                    entry_acl::fetch(&c2, &info).map_err(AuthError::from)
+                })
+                .join(config::list_apps(&c3).map(|(_, apps)| {
+                    apps.into_iter()
+                        .map(|(_, app_info)| (app_info.keys.sign_pk, app_info.info))
+                        .collect::<HashMap<PublicKey, AppExchangeInfo>>()
+                }))
+                .and_then(move |(acl, apps)| {
+                    let mut rules = Vec::new();
+
+                    for (prefix, keys) in acl.rules() {
+                        for (key, permissions) in keys {
+                            let app_info = apps.get(key);
+                            let app_access = AppAccess {
+                                sign_key: *key,
+                                permissions: *permissions,
+                                name: app_info.map(|info| info.name.clone()),
+                                app_id: app_info.map(|info| info.id.clone()),
+                            };
+                            let (prefix, prefix_len, prefix_cap) =
+                                vec_into_raw_parts(prefix.clone());
+
+                            rules.push(EntryAclRule {
+                                prefix,
+                                prefix_len,
+                                prefix_cap,
+                                app_access: app_access.into_repr_c()?,
+                            });
+                        }
+                    }
+
+                    o_cb(user_data.0, FFI_RESULT_OK, rules.as_safe_ptr(), rules.len());
+
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })?;
+
+        Ok(())
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -357,8 +480,8 @@ mod tests {
     use ffi_utils::test_utils::call_0;
     use revocation::revoke_app;
     use safe_core::ipc::AuthReq;
-    use test_utils::{create_account_and_login, create_file, fetch_file, get_app_or_err, rand_app,
-                     register_app, run};
+    use test_utils::{create_account_and_login, create_app_container, create_file, fetch_file,
+                     get_app_or_err, rand_app, register_app, run};
 
     // Negative test - non-existing app:
     // 1. Try to call `auth_rm_revoked_app` with a random, non-existing app_id
@@ -458,10 +581,12 @@ mod tests {
             },
         ));
 
-        // Put a file with predefined content into app A's own container.
+        // Put a file with predefined content into app A's own container, which gets
+        // created on its first use.
         let mdata_info = unwrap!({
             run(&auth, move |client| fetch(client, &app_id3))
         });
+        assert_eq!(create_app_container(&auth, &app_id), mdata_info);
         unwrap!(create_file(&auth, mdata_info.clone(), "test", vec![1; 10]));
 
         // Revoke app A
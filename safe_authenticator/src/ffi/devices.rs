@@ -0,0 +1,151 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {AuthError, Authenticator};
+use devices;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str, ptr_as_ref};
+use futures::Future;
+use rust_sodium::crypto::sign;
+use safe_core::FutureExt;
+use safe_core::ffi::arrays::SignPublicKey;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+/// Device that has logged into the account. The name is only valid for the duration of
+/// the callback.
+#[repr(C)]
+pub struct RegisteredDevice {
+    /// User-facing name of the device
+    pub name: *const c_char,
+    /// Public key identifying the device
+    pub public_key: SignPublicKey,
+    /// Time of the first registration, in seconds since the Unix epoch
+    pub first_seen: u64,
+    /// Time of the last registration, in seconds since the Unix epoch
+    pub last_seen: u64,
+    /// Whether the device has been revoked
+    pub revoked: bool,
+}
+
+/// Register a login from the device identified by the given public key, recording
+/// the device on its first login. Fails with `ERR_DEVICE_REVOKED` if the device
+/// has been revoked, in which case it should wipe its cached credentials.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_register_device(
+    auth: *const Authenticator,
+    name: *const c_char,
+    public_key: *const SignPublicKey,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let name = from_c_str(name)?;
+        let public_key = sign::PublicKey(*public_key);
+
+        ptr_as_ref(auth)?.send(move |client| {
+            devices::register(client, name, public_key)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get a list of devices that have logged into the account, including revoked ones.
+///
+/// Callback parameters: user data, error code, devices vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn auth_registered_devices(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        devices: *const RegisteredDevice,
+                        devices_len: usize),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            devices::list(client)
+                .and_then(move |devices| {
+                    let names = devices
+                        .iter()
+                        .map(|device| CString::new(device.name.clone()))
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let devices: Vec<_> = devices
+                        .iter()
+                        .zip(&names)
+                        .map(|(device, name)| {
+                            RegisteredDevice {
+                                name: name.as_ptr(),
+                                public_key: device.public_key.0,
+                                first_seen: device.first_seen,
+                                last_seen: device.last_seen,
+                                revoked: device.revoked,
+                            }
+                        })
+                        .collect();
+
+                    o_cb(user_data.0, FFI_RESULT_OK, devices.as_ptr(), devices.len());
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Revoke the device identified by the given public key. Its next registration
+/// fails, telling it to wipe its cached credentials. This is advisory only: the
+/// device shares the account keys, so one which doesn't register again keeps
+/// full access.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_revoke_device(
+    auth: *const Authenticator,
+    public_key: *const SignPublicKey,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let public_key = sign::PublicKey(*public_key);
+
+        ptr_as_ref(auth)?.send(move |client| {
+            devices::revoke(client, public_key)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
@@ -19,19 +19,24 @@ use {AuthError, Authenticator};
 use access_container;
 use app_auth;
 use config;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb, from_c_str};
-use futures::{Future, Stream, stream};
-use ipc::{decode_ipc_msg, decode_share_mdata_req, encode_response, update_container_perms};
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, SafePtr, catch_unwind_cb, from_c_str,
+                ptr_as_ref};
+use futures::Future;
+use ipc::{decode_ipc_msg, decode_share_mdata_req, encode_response, grant_combined_req,
+          record_req_version, share_mdata, update_container_perms, verify_req_mac};
+use pending;
 use revocation::{flush_app_revocation_queue, revoke_app};
 use routing::{ClientError, User};
 use safe_core::{Client, CoreError, FutureExt};
-use safe_core::ffi::ipc::req::{AuthReq as FfiAuthReq, ContainersReq as FfiContainersReq,
+use safe_core::ffi::ipc::req::{AuthReq as FfiAuthReq, CombinedReq as FfiCombinedReq,
+                               ContainersReq as FfiContainersReq,
                                ShareMDataReq as FfiShareMDataReq};
 use safe_core::ffi::ipc::resp::MetadataResponse as FfiUserMetadata;
-use safe_core::ipc::{IpcError, IpcMsg, decode_msg};
-use safe_core::ipc::req::{AuthReq, ContainersReq, IpcReq, ShareMDataReq};
+use safe_core::ipc::{DecodedMsg, IpcError, IpcMsg, decode_msg_with_details, now_secs};
+use safe_core::ipc::uri;
+use safe_core::ipc::req::{AuthReq, CombinedReq, ContainersReq, IpcReq, ShareMDataReq};
 use safe_core::ipc::resp::IpcResp;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::os::raw::{c_char, c_void};
 
 /// Decodes a given encoded IPC message without requiring an authorised account.
@@ -51,7 +56,14 @@ pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
 
     catch_unwind_cb(user_data.0, o_err, || -> Result<_, AuthError> {
         let msg_raw = CStr::from_ptr(msg).to_str()?;
-        let msg = decode_msg(msg_raw)?;
+        let DecodedMsg { msg, stamp, version, .. } = decode_msg_with_details(msg_raw)?;
+        record_req_version(&msg, version);
+
+        // Without an account there's nowhere to keep track of seen nonces,
+        // so only the expiry can be checked.
+        if stamp.map_or(false, |stamp| stamp.is_expired(now_secs())) {
+            return Err(AuthError::IpcError(IpcError::RequestExpired));
+        }
 
         match msg {
             IpcMsg::Req {
@@ -79,6 +91,9 @@ pub unsafe extern "C" fn auth_unregistered_decode_ipc_msg(
 }
 
 /// Decodes a given encoded IPC message and calls a corresponding callback.
+/// Auth requests are always passed to `o_auth`, even if the authorisation policy
+/// would grant them, and combined requests are rejected through `o_err`.
+/// See `auth_decode_ipc_msg_v2` for both.
 #[no_mangle]
 pub unsafe extern "C" fn auth_decode_ipc_msg(
     auth: *const Authenticator,
@@ -99,16 +114,122 @@ pub unsafe extern "C" fn auth_decode_ipc_msg(
     o_err: extern "C" fn(user_data: *mut c_void,
                          result: *const FfiResult,
                          response: *const c_char),
+) {
+    decode_ipc_msg_impl(
+        auth,
+        msg,
+        user_data,
+        o_auth,
+        o_containers,
+        o_unregistered,
+        o_share_mdata,
+        None,
+        o_err,
+        None,
+    )
+}
+
+/// Decodes a given encoded IPC message and calls a corresponding callback, like
+/// `auth_decode_ipc_msg`. Combined auth and share requests are passed to `o_combined`.
+/// If an auth request has been granted by the authorisation policy without prompting
+/// the user, `o_auto_resp` is called with the encoded response for the app.
+#[no_mangle]
+pub unsafe extern "C" fn auth_decode_ipc_msg_v2(
+    auth: *const Authenticator,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, req: *const FfiAuthReq),
+    o_containers: extern "C" fn(user_data: *mut c_void,
+                                req_id: u32,
+                                req: *const FfiContainersReq),
+    o_unregistered: extern "C" fn(user_data: *mut c_void,
+                                  req_id: u32,
+                                  extra_data: *const u8,
+                                  extra_data_len: usize),
+    o_share_mdata: extern "C" fn(user_data: *mut c_void,
+                                 req_id: u32,
+                                 req: *const FfiShareMDataReq,
+                                 metadata: *const FfiUserMetadata),
+    o_combined: extern "C" fn(user_data: *mut c_void,
+                              req_id: u32,
+                              req: *const FfiCombinedReq,
+                              metadata: *const FfiUserMetadata),
+    o_err: extern "C" fn(user_data: *mut c_void,
+                         result: *const FfiResult,
+                         response: *const c_char),
+    o_auto_resp: extern "C" fn(user_data: *mut c_void, req_id: u32, response: *const c_char),
+) {
+    decode_ipc_msg_impl(
+        auth,
+        msg,
+        user_data,
+        o_auth,
+        o_containers,
+        o_unregistered,
+        o_share_mdata,
+        Some(o_combined),
+        o_err,
+        Some(o_auto_resp),
+    )
+}
+
+// Requests granted by the authorisation policy are passed to `o_auto_resp`. Without it,
+// the user is prompted for them instead. Without `o_combined`, combined requests are
+// rejected.
+unsafe fn decode_ipc_msg_impl(
+    auth: *const Authenticator,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_auth: extern "C" fn(user_data: *mut c_void, req_id: u32, req: *const FfiAuthReq),
+    o_containers: extern "C" fn(user_data: *mut c_void,
+                                req_id: u32,
+                                req: *const FfiContainersReq),
+    o_unregistered: extern "C" fn(user_data: *mut c_void,
+                                  req_id: u32,
+                                  extra_data: *const u8,
+                                  extra_data_len: usize),
+    o_share_mdata: extern "C" fn(user_data: *mut c_void,
+                                 req_id: u32,
+                                 req: *const FfiShareMDataReq,
+                                 metadata: *const FfiUserMetadata),
+    o_combined: Option<
+        extern "C" fn(user_data: *mut c_void,
+                      req_id: u32,
+                      req: *const FfiCombinedReq,
+                      metadata: *const FfiUserMetadata),
+    >,
+    o_err: extern "C" fn(user_data: *mut c_void,
+                         result: *const FfiResult,
+                         response: *const c_char),
+    o_auto_resp: Option<
+        extern "C" fn(user_data: *mut c_void, req_id: u32, response: *const c_char),
+    >,
 ) {
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_err, || -> Result<_, AuthError> {
         let msg_raw = CStr::from_ptr(msg).to_str()?;
-        let msg = decode_msg(msg_raw)?;
+        let decoded = decode_msg_with_details(msg_raw)?;
+        ptr_as_ref(auth)?.check_req_stamp(&decoded)?;
+        let legacy_allowed = ptr_as_ref(auth)?.legacy_reqs_allowed();
+        let DecodedMsg { msg, mac, version, .. } = decoded;
+        record_req_version(&msg, version);
+
+        if let Some(resp) = throttle_auth_req(&*auth, &msg)? {
+            let (error_code, description) = ffi_error!(AuthError::from(IpcError::RequestDenied));
+            let res = FfiResult {
+                error_code,
+                description: description.as_ptr(),
+            };
+            o_err(user_data.0, &res, resp.as_ptr());
+            return Ok(());
+        }
 
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             let c1 = client.clone();
-            decode_ipc_msg(client, msg)
+            let c2 = client.clone();
+            verify_req_mac(client, &msg, mac, legacy_allowed)
+                .and_then(move |()| decode_ipc_msg(&c2, msg, version, o_auto_resp.is_some()))
                 .and_then(move |msg| match msg {
                     Ok(IpcMsg::Req {
                            req: IpcReq::Auth(auth_req),
@@ -145,15 +266,7 @@ pub unsafe extern "C" fn auth_decode_ipc_msg(
                         decode_share_mdata_req(&c1, &share_mdata_req)
                             .and_then(move |metadata_cont| {
                                 let share_mdata_req_repr_c = share_mdata_req.into_repr_c()?;
-
-                                let mut ffi_metadata_cont = Vec::with_capacity(metadata_cont.len());
-                                for metadata in metadata_cont {
-                                    if let Some(metadata) = metadata {
-                                        ffi_metadata_cont.push(metadata);
-                                    } else {
-                                        ffi_metadata_cont.push(FfiUserMetadata::invalid());
-                                    }
-                                }
+                                let ffi_metadata_cont = metadata_into_repr_c(metadata_cont);
 
                                 o_share_mdata(
                                     user_data.0,
@@ -166,6 +279,50 @@ pub unsafe extern "C" fn auth_decode_ipc_msg(
                             })
                             .into_box()
                     }
+                    Ok(IpcMsg::Req {
+                           req: IpcReq::Combined(_),
+                           req_id,
+                       }) if o_combined.is_none() => {
+                        let err = || {
+                            IpcError::Unexpected(
+                                "Combined requests aren't supported by this authenticator"
+                                    .to_owned(),
+                            )
+                        };
+                        let (error_code, description) = ffi_error!(AuthError::from(err()));
+                        let resp = fry!(encode_response(&IpcMsg::Resp {
+                            req_id: req_id,
+                            resp: IpcResp::Combined(Err(err())),
+                        }));
+                        let res = FfiResult {
+                            error_code,
+                            description: description.as_ptr(),
+                        };
+                        o_err(user_data.0, &res, resp.as_ptr());
+                        ok!(())
+                    }
+                    Ok(IpcMsg::Req {
+                           req: IpcReq::Combined(combined_req),
+                           req_id,
+                       }) => {
+                        decode_share_mdata_req(&c1, &combined_req.share_mdata_req())
+                            .and_then(move |metadata_cont| {
+                                let combined_req_repr_c = combined_req.into_repr_c()?;
+                                let ffi_metadata_cont = metadata_into_repr_c(metadata_cont);
+
+                                if let Some(o_combined) = o_combined {
+                                    o_combined(
+                                        user_data.0,
+                                        req_id,
+                                        &combined_req_repr_c,
+                                        ffi_metadata_cont.as_ptr(),
+                                    );
+                                }
+
+                                Ok(())
+                            })
+                            .into_box()
+                    }
                     Err((error_code, description, err)) => {
                         let res = FfiResult {
                             error_code,
@@ -174,7 +331,17 @@ pub unsafe extern "C" fn auth_decode_ipc_msg(
                         o_err(user_data.0, &res, err.as_ptr());
                         ok!(())
                     }
-                    Ok(IpcMsg::Resp { .. }) |
+                    Ok(IpcMsg::Resp { req_id, resp }) => {
+                        // The request has been decided by the authorisation policy.
+                        let resp = fry!(encode_response(&IpcMsg::Resp {
+                            req_id: req_id,
+                            resp: resp,
+                        }));
+                        if let Some(o_auto_resp) = o_auto_resp {
+                            o_auto_resp(user_data.0, req_id, resp.as_ptr());
+                        }
+                        ok!(())
+                    }
                     Ok(IpcMsg::Revoked { .. }) |
                     Ok(IpcMsg::Err(..)) => {
                         let err = AuthError::Unexpected(
@@ -215,47 +382,21 @@ pub unsafe extern "C" fn encode_share_mdata_resp(
     catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
         let share_mdata_req = ShareMDataReq::clone_from_repr_c(req)?;
         if is_granted {
-            (*auth).send(move |client| {
-                let client_cloned0 = client.clone();
-                let client_cloned1 = client.clone();
+            ptr_as_ref(auth)?.send(move |client| {
+                let c2 = client.clone();
                 let user_data = user_data.0;
-                config::get_app(client, &share_mdata_req.app.id)
+                config::get_app(client, &share_mdata_req.app.scoped_id())
                     .and_then(move |app_info| {
                         let user = User::Key(app_info.keys.sign_pk);
-                        let num_mdata = share_mdata_req.mdata.len();
-                        stream::iter_ok(share_mdata_req.mdata.into_iter())
-                        .map(move |mdata| {
-                            client_cloned0.get_mdata_shell(mdata.name, mdata.type_tag)
-                                          .map(|md| (md.version(), mdata))
-                        })
-                        .buffer_unordered(num_mdata)
-                        .map(move |(version, mdata)| {
-                            client_cloned1.set_mdata_user_permissions(
-                                mdata.name,
-                                mdata.type_tag,
-                                user,
-                                mdata.perms,
-                                version + 1,
-                            )
-                        })
-                        .buffer_unordered(num_mdata)
-                        .map_err(AuthError::CoreError)
-                        .for_each(|()| Ok(()))
-                        .and_then(move |()| {
-                            let resp = encode_response(
-                                &IpcMsg::Resp {
-                                    req_id: req_id,
-                                    resp: IpcResp::ShareMData(Ok(())),
-                                }
-                            ).map_err(AuthError::IpcError)?;
-                            o_cb(
-                                user_data,
-                                FFI_RESULT_OK,
-                                resp.as_ptr()
-                            );
-                            Ok(())
-                        })
-                        .into_box()
+                        share_mdata(&c2, user, share_mdata_req.mdata)
+                    })
+                    .and_then(move |()| {
+                        let resp = encode_response(&IpcMsg::Resp {
+                            req_id: req_id,
+                            resp: IpcResp::ShareMData(Ok(())),
+                        })?;
+                        o_cb(user_data, FFI_RESULT_OK, resp.as_ptr());
+                        Ok(())
                     })
                     .map_err(move |e| {
                         call_result_cb!(Err::<(), _>(e), user_data, o_cb);
@@ -279,7 +420,68 @@ pub unsafe extern "C" fn encode_share_mdata_resp(
     })
 }
 
-/// Revoke app access.
+/// Provides and encodes a response to a combined request. If granted, the app
+/// is authenticated and the requested mutable data is shared with it; if any
+/// part can't be granted, the whole request fails.
+///
+/// Callback parameters: user data, error code, response ptr
+#[no_mangle]
+pub unsafe extern "C" fn encode_combined_resp(
+    auth: *const Authenticator,
+    req: *const FfiCombinedReq,
+    req_id: u32,
+    is_granted: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        response: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let combined_req = CombinedReq::clone_from_repr_c(req)?;
+
+        if !is_granted {
+            let resp = encode_response(&IpcMsg::Resp {
+                req_id: req_id,
+                resp: IpcResp::Combined(Err(IpcError::AuthDenied)),
+            })?;
+            o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
+            return Ok(());
+        }
+
+        ptr_as_ref(auth)?.send(move |client| {
+            grant_combined_req(client, combined_req)
+                .and_then(move |auth_granted| {
+                    let resp = encode_response(&IpcMsg::Resp {
+                        req_id: req_id,
+                        resp: IpcResp::Combined(Ok(auth_granted)),
+                    })?;
+                    Ok(o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr()))
+                })
+                .or_else(move |e| -> Result<(), AuthError> {
+                    let (error_code, description) = ffi_error!(e);
+                    let resp = encode_response(&IpcMsg::Resp {
+                        req_id: req_id,
+                        resp: IpcResp::Combined(Err(e.into())),
+                    })?;
+                    let res = FfiResult {
+                        error_code,
+                        description: description.as_ptr(),
+                    };
+                    Ok(o_cb(user_data.0, &res, resp.as_ptr()))
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Revoke app access. Apps authorised with a scope are identified by their scoped ID,
+/// `<app id>#<scope>`, and each scope is revoked separately.
 ///
 /// Callback parameters: user data, error code, response ptr
 #[no_mangle]
@@ -296,7 +498,7 @@ pub unsafe extern "C" fn auth_revoke_app(
     catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
         let app_id = from_c_str(app_id)?;
 
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             revoke_app(client, &app_id)
                 .and_then(move |_| {
                     let resp = encode_response(&IpcMsg::Revoked { app_id: app_id })?;
@@ -326,7 +528,7 @@ pub unsafe extern "C" fn auth_flush_app_revocation_queue(
     let user_data = OpaqueCtx(user_data);
 
     catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             flush_app_revocation_queue(client)
                 .then(move |res| {
                     call_result_cb!(res, user_data, o_cb);
@@ -394,40 +596,11 @@ pub unsafe extern "C" fn encode_auth_resp(
         let auth_req = AuthReq::clone_from_repr_c(req)?;
 
         if !is_granted {
-            let resp = encode_response(&IpcMsg::Resp {
-                req_id: req_id,
-                resp: IpcResp::Auth(Err(IpcError::AuthDenied)),
-            })?;
-
+            let resp = encode_auth_denied_resp(req_id)?;
             o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
         } else {
-            (*auth).send(move |client| {
-                app_auth::authenticate(client, auth_req)
-                    .and_then(move |auth_granted| {
-                        let resp = encode_response(&IpcMsg::Resp {
-                            req_id: req_id,
-                            resp: IpcResp::Auth(Ok(auth_granted)),
-                        })?;
-
-                        Ok(o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr()))
-                    })
-                    .or_else(move |e| -> Result<(), AuthError> {
-                        let (error_code, description) = ffi_error!(e);
-                        let resp = encode_response(&IpcMsg::Resp {
-                            req_id: req_id,
-                            resp: IpcResp::Auth(Err(e.into())),
-                        })?;
-                        let res = FfiResult {
-                            error_code,
-                            description: description.as_ptr(),
-                        };
-                        Ok(o_cb(user_data.0, &res, resp.as_ptr()))
-                    })
-                    .map_err(move |e| {
-                        call_result_cb!(Err::<(), _>(e), user_data, o_cb);
-                    })
-                    .into_box()
-                    .into()
+            ptr_as_ref(auth)?.send(move |client| {
+                grant_auth_req(client, auth_req, req_id, user_data, o_cb).into()
             })?;
         }
 
@@ -435,6 +608,274 @@ pub unsafe extern "C" fn encode_auth_resp(
     })
 }
 
+/// Wraps an encoded IPC response into a URI under the scheme of the app with the
+/// given id, to be opened by that app.
+///
+/// Callback parameters: user data, error code, URI
+#[no_mangle]
+pub unsafe extern "C" fn auth_ipc_app_uri(
+    app_id: *const c_char,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, uri: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AuthError> {
+        let app_id = from_c_str(app_id)?;
+        let msg = from_c_str(msg)?;
+        let uri = CString::new(uri::to_app_uri(&app_id, &msg))?;
+        o_cb(user_data, FFI_RESULT_OK, uri.as_ptr());
+        Ok(())
+    })
+}
+
+/// Extracts the encoded IPC message from a `safe-auth://` URI, ready to be
+/// passed to `auth_decode_ipc_msg`.
+///
+/// Callback parameters: user data, error code, encoded message
+#[no_mangle]
+pub unsafe extern "C" fn auth_ipc_uri_payload(
+    uri: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, msg: *const c_char),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AuthError> {
+        let uri = from_c_str(uri)?;
+        let (_, payload) = uri::parse_uri(&uri)?;
+        let payload = CString::new(payload)?;
+        o_cb(user_data, FFI_RESULT_OK, payload.as_ptr());
+        Ok(())
+    })
+}
+
+/// Queues an auth request to be answered by the user later, e.g. when it arrives
+/// while there's no UI to prompt the user. Pending requests expire after a day.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_queue_ipc_msg(
+    auth: *const Authenticator,
+    msg: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let msg_raw = CStr::from_ptr(msg).to_str()?;
+        let decoded = decode_msg_with_details(msg_raw)?;
+        ptr_as_ref(auth)?.check_req_stamp(&decoded)?;
+        record_req_version(&decoded.msg, decoded.version);
+
+        let (req_id, auth_req) = match decoded.msg {
+            IpcMsg::Req {
+                req: IpcReq::Auth(auth_req),
+                req_id,
+            } => (req_id, auth_req),
+            _ => return Err(AuthError::IpcError(IpcError::InvalidMsg)),
+        };
+
+        if ptr_as_ref(auth)?.check_auth_throttle(&auth_req.app.id).is_err() {
+            return Err(AuthError::IpcError(IpcError::RequestDenied));
+        }
+
+        ptr_as_ref(auth)?.send(move |client| {
+            pending::push(client, req_id, auth_req)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Allow IPC requests from apps built against versions of the library which
+/// predate replay protection: requests without a stamp, and requests from
+/// registered apps without a MAC. They are rejected by default.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_legacy_reqs_allowed(
+    auth: *const Authenticator,
+    allowed: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.set_legacy_reqs_allowed(allowed);
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
+/// Get a list of auth requests waiting for the user's decision.
+///
+/// Callback parameters: user data, error code, pending requests vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn auth_pending_requests(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        pending_reqs: *const PendingAuthReq,
+                        pending_reqs_len: usize),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            pending::list(client)
+                .and_then(move |requests| {
+                    let mut reqs = Vec::with_capacity(requests.len());
+                    for (req_id, auth_req) in requests {
+                        reqs.push(PendingAuthReq {
+                            req_id: req_id,
+                            req: auth_req.into_repr_c()?,
+                        });
+                    }
+
+                    o_cb(user_data.0, FFI_RESULT_OK, reqs.as_safe_ptr(), reqs.len());
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Answers a pending auth request and removes it from the queue.
+///
+/// Callback parameters: user data, error code, response ptr
+#[no_mangle]
+pub unsafe extern "C" fn auth_respond(
+    auth: *const Authenticator,
+    req_id: u32,
+    is_granted: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        response: *const c_char),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            let c2 = client.clone();
+
+            pending::take(client, req_id)
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .and_then(move |auth_req| if is_granted {
+                    grant_auth_req(&c2, auth_req, req_id, user_data, o_cb)
+                } else {
+                    match encode_auth_denied_resp(req_id) {
+                        Ok(resp) => o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr()),
+                        Err(e) => {
+                            call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                        }
+                    }
+                    ok!(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Auth request waiting for the user's decision
+#[repr(C)]
+pub struct PendingAuthReq {
+    /// ID of the request
+    pub req_id: u32,
+    /// The request itself
+    pub req: FfiAuthReq,
+}
+
+// Replaces missing metadata with placeholders, so the result can be indexed
+// the same way as the requested mutable data.
+fn metadata_into_repr_c(metadata_cont: Vec<Option<FfiUserMetadata>>) -> Vec<FfiUserMetadata> {
+    metadata_cont
+        .into_iter()
+        .map(|metadata| metadata.unwrap_or_else(FfiUserMetadata::invalid))
+        .collect()
+}
+
+// Records an incoming auth request with the throttle. Returns the encoded
+// `RequestDenied` response if the app has to back off.
+fn throttle_auth_req(auth: &Authenticator, msg: &IpcMsg) -> Result<Option<CString>, AuthError> {
+    let (req_id, app_id, resp) = match *msg {
+        IpcMsg::Req {
+            req: IpcReq::Auth(ref auth_req),
+            req_id,
+        } => (req_id, &auth_req.app.id, IpcResp::Auth(Err(IpcError::RequestDenied))),
+        IpcMsg::Req {
+            req: IpcReq::Combined(ref combined_req),
+            req_id,
+        } => (
+            req_id,
+            &combined_req.auth.app.id,
+            IpcResp::Combined(Err(IpcError::RequestDenied)),
+        ),
+        _ => return Ok(None),
+    };
+
+    if auth.check_auth_throttle(app_id).is_err() {
+        return Ok(Some(encode_response(&IpcMsg::Resp {
+            req_id: req_id,
+            resp: resp,
+        })?));
+    }
+    Ok(None)
+}
+
+fn encode_auth_denied_resp(req_id: u32) -> Result<CString, AuthError> {
+    Ok(encode_response(&IpcMsg::Resp {
+        req_id: req_id,
+        resp: IpcResp::Auth(Err(IpcError::AuthDenied)),
+    })?)
+}
+
+fn grant_auth_req(
+    client: &Client<()>,
+    auth_req: AuthReq,
+    req_id: u32,
+    user_data: OpaqueCtx,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        response: *const c_char),
+) -> Box<Future<Item = (), Error = ()>> {
+    app_auth::authenticate(client, auth_req)
+        .and_then(move |auth_granted| {
+            let resp = encode_response(&IpcMsg::Resp {
+                req_id: req_id,
+                resp: IpcResp::Auth(Ok(auth_granted)),
+            })?;
+
+            Ok(o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr()))
+        })
+        .or_else(move |e| -> Result<(), AuthError> {
+            let (error_code, description) = ffi_error!(e);
+            let resp = encode_response(&IpcMsg::Resp {
+                req_id: req_id,
+                resp: IpcResp::Auth(Err(e.into())),
+            })?;
+            let res = FfiResult {
+                error_code,
+                description: description.as_ptr(),
+            };
+            Ok(o_cb(user_data.0, &res, resp.as_ptr()))
+        })
+        .map_err(move |e| {
+            call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+        })
+        .into_box()
+}
+
 /// Update containers permissions for an App.
 ///
 /// Callback parameters: user data, error code, response ptr
@@ -463,9 +904,9 @@ pub unsafe extern "C" fn encode_containers_resp(
             o_cb(user_data.0, FFI_RESULT_OK, resp.as_ptr());
         } else {
             let permissions = cont_req.containers.clone();
-            let app_id = cont_req.app.id.clone();
+            let app_id = cont_req.app.scoped_id();
 
-            (*auth).send(move |client| {
+            ptr_as_ref(auth)?.send(move |client| {
                 let c2 = client.clone();
                 let c3 = client.clone();
                 let c4 = client.clone();
@@ -478,6 +919,7 @@ pub unsafe extern "C" fn encode_containers_resp(
                         )
                     })
                     .and_then(move |(app, mut perms)| {
+                        let app_id = app.registered_id();
                         let app_keys = app.keys;
 
                         access_container::fetch_entry(&c3, &app_id, app_keys.clone())
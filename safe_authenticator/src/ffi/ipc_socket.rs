@@ -0,0 +1,167 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Local socket transport for IPC messages, see `safe_core::ipc::socket`.
+
+use errors::AuthError;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str};
+use safe_core::ipc::socket::{self, IpcListener};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use std::path::PathBuf;
+
+/// Starts listening for IPC messages from apps on a local socket at `path`, or
+/// at the default location if `path` is null. Every received message is
+/// passed to `o_msg` together with the id of the connection it came from, to be
+/// decoded with `auth_decode_ipc_msg`. The response has to be sent back with
+/// `auth_ipc_listener_respond`. `o_msg` is called from the listener's own thread.
+///
+/// Callback parameters: user data, error code, listener
+#[no_mangle]
+pub unsafe extern "C" fn auth_ipc_listener_start(
+    path: *const c_char,
+    user_data: *mut c_void,
+    o_msg: extern "C" fn(user_data: *mut c_void, connection_id: u64, msg: *const c_char),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        listener: *mut IpcListener),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<(), AuthError> {
+        let path = if path.is_null() {
+            socket::default_socket_path()?
+        } else {
+            PathBuf::from(from_c_str(path)?)
+        };
+
+        let listener = IpcListener::start(path, move |connection_id, msg| {
+            match CString::new(msg) {
+                Ok(msg) => o_msg(user_data.0, connection_id, msg.as_ptr()),
+                Err(error) => debug!("Invalid IPC message: {:?}", error),
+            }
+        })?;
+
+        o_cb(user_data.0, FFI_RESULT_OK, Box::into_raw(Box::new(listener)));
+        Ok(())
+    })
+}
+
+/// Sends the encoded response to the IPC message received over the given
+/// connection. Fails with `ERR_NULL_POINTER` if `listener` or `resp` is null.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_ipc_listener_respond(
+    listener: *const IpcListener,
+    connection_id: u64,
+    resp: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<(), AuthError> {
+        if listener.is_null() || resp.is_null() {
+            return Err(AuthError::NullPointer);
+        }
+
+        let resp = from_c_str(resp)?;
+        (*listener).respond(connection_id, &resp)?;
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
+/// Stops the listener and removes its socket. Connections which haven't been
+/// responded to yet are closed. Using `listener` after a call to this function
+/// is undefined behaviour. Does nothing if `listener` is null.
+#[no_mangle]
+pub unsafe extern "C" fn auth_ipc_listener_stop(listener: *mut IpcListener) {
+    if !listener.is_null() {
+        let _ = Box::from_raw(listener);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::{UserData, call_0, call_1_with_custom,
+                                send_via_user_data_custom};
+    use rand;
+    use std::sync::mpsc::{self, Receiver, Sender};
+
+    // Test receiving an IPC message over the socket and responding to it.
+    #[test]
+    fn listener() {
+        let path = unwrap!(socket::socket_path(
+            &format!("safe_auth_ipc_{}", rand::random::<u64>()),
+        ));
+        let path_c = unwrap!(CString::new(unwrap!(path.to_str())));
+
+        let (tx, rx): (Sender<(u64, String)>, Receiver<(u64, String)>) = mpsc::channel();
+
+        let mut custom_ud: UserData = Default::default();
+        let ptr: *const _ = &tx;
+        custom_ud.custom = ptr as *mut c_void;
+
+        let listener: *mut IpcListener = unsafe {
+            unwrap!(call_1_with_custom(&mut custom_ud, |ud, cb| {
+                auth_ipc_listener_start(path_c.as_ptr(), ud, msg_cb, cb)
+            }))
+        };
+
+        let path2 = path.clone();
+        let client = ::std::thread::spawn(move || unwrap!(socket::send_msg(&path2, "request")));
+
+        let (connection_id, msg) = unwrap!(rx.recv());
+        assert_eq!(msg, "request");
+
+        let resp = unwrap!(CString::new("response"));
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_ipc_listener_respond(listener, connection_id, resp.as_ptr(), ud, cb)
+            }));
+        }
+        assert_eq!(unwrap!(client.join()), "response");
+
+        unsafe { auth_ipc_listener_stop(listener) };
+        if cfg!(unix) {
+            assert!(!path.exists());
+        }
+
+        extern "C" fn msg_cb(user_data: *mut c_void, connection_id: u64, msg: *const c_char) {
+            unsafe {
+                let msg = unwrap!(from_c_str(msg));
+                send_via_user_data_custom(user_data, (connection_id, msg));
+            }
+        }
+    }
+
+    // Test that null pointers are reported as errors instead of crashing the process.
+    #[test]
+    fn null_pointers() {
+        use errors::ERR_NULL_POINTER;
+        use std::ptr;
+
+        let resp = unwrap!(CString::new("response"));
+        let res = unsafe {
+            call_0(|ud, cb| auth_ipc_listener_respond(ptr::null(), 0, resp.as_ptr(), ud, cb))
+        };
+        assert_eq!(res.err(), Some(ERR_NULL_POINTER));
+
+        unsafe { auth_ipc_listener_stop(ptr::null_mut()) };
+    }
+}
@@ -0,0 +1,244 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use Authenticator;
+use errors::AuthError;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, ptr_as_ref};
+use rust_sodium::crypto::{box_, secretbox, sign};
+use rust_sodium::utils::memzero;
+use safe_core::{ClientKeys, CoreError, KeyStore};
+use safe_core::crypto::{shared_box, shared_secretbox, shared_sign};
+use safe_core::ffi::arrays::{AsymPublicKey, AsymSecretKey, SignPublicKey, SignSecretKey,
+                             SymSecretKey, XorNameArray};
+use std::os::raw::c_void;
+use std::ptr;
+
+/// Keys of the account, as exchanged with a key store implemented by the platform. The
+/// secret keys are wiped when the structure is dropped.
+#[repr(C)]
+pub struct AccountKeys {
+    /// Signing public key.
+    pub sign_pk: SignPublicKey,
+    /// Signing secret key.
+    pub sign_sk: SignSecretKey,
+    /// Encryption public key.
+    pub enc_pk: AsymPublicKey,
+    /// Encryption secret key.
+    pub enc_sk: AsymSecretKey,
+    /// Symmetric encryption key.
+    pub enc_key: SymSecretKey,
+}
+
+impl Drop for AccountKeys {
+    fn drop(&mut self) {
+        memzero(&mut self.sign_sk);
+        memzero(&mut self.enc_sk);
+        memzero(&mut self.enc_key);
+    }
+}
+
+/// Moves the secret keys of the account into a key store implemented by the platform, e.g.
+/// on top of the Android Keystore or the Secure Enclave. `o_store` is called once with the
+/// keys to keep, `o_load` each time the keys are needed, to fill them in. Both are called on
+/// the authenticator's event loop with `store_data`, which must stay valid as long as the
+/// authenticator, and return 0 on success or an error code otherwise. The keys passed to
+/// them are wiped once they return.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_key_store(
+    auth: *const Authenticator,
+    store_data: *mut c_void,
+    o_store: extern "C" fn(store_data: *mut c_void, keys: *const AccountKeys) -> i32,
+    o_load: extern "C" fn(store_data: *mut c_void, o_keys: *mut AccountKeys) -> i32,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let store_data = OpaqueCtx(store_data);
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            let store = FfiKeyStore {
+                store_data: store_data,
+                o_store: o_store,
+                o_load: o_load,
+            };
+            let res = client.set_key_store(Box::new(store)).map_err(AuthError::from);
+            call_result_cb!(res, user_data, o_cb);
+            None
+        })
+    })
+}
+
+/// Sets the function to call each time one of the account's secret keys is used to sign or
+/// decrypt, which includes signing each mutation request the authenticator sends. It's called
+/// on the authenticator's event loop with `hook_data`, which must stay valid until the hook is
+/// replaced or the authenticator is freed, the operation (0 for signing, 1 for decryption) and
+/// the name of the data involved, or null if it wasn't network data. Pass a null hook to
+/// remove it.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_key_usage_hook(
+    auth: *const Authenticator,
+    hook_data: *mut c_void,
+    o_hook: Option<extern "C" fn(hook_data: *mut c_void, op: u32, data_id: *const XorNameArray)>,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let hook_data = OpaqueCtx(hook_data);
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            match o_hook {
+                Some(o_hook) => {
+                    client.set_key_usage_hook(move |op, data_id| {
+                        let data_id = data_id.as_ref().map_or(ptr::null(), |name| {
+                            &name.0 as *const XorNameArray
+                        });
+                        o_hook(hook_data.0, op as u32, data_id)
+                    })
+                }
+                None => client.remove_key_usage_hook(),
+            }
+            o_cb(user_data.0, FFI_RESULT_OK);
+            None
+        })
+    })
+}
+
+// Key store calling back into the platform.
+struct FfiKeyStore {
+    store_data: OpaqueCtx,
+    o_store: extern "C" fn(store_data: *mut c_void, keys: *const AccountKeys) -> i32,
+    o_load: extern "C" fn(store_data: *mut c_void, o_keys: *mut AccountKeys) -> i32,
+}
+
+impl KeyStore for FfiKeyStore {
+    fn store(&mut self, keys: &ClientKeys) -> Result<(), CoreError> {
+        let keys = AccountKeys {
+            sign_pk: keys.sign_pk.0,
+            sign_sk: keys.sign_sk.0,
+            enc_pk: keys.enc_pk.0,
+            enc_sk: keys.enc_sk.0,
+            enc_key: keys.enc_key.0,
+        };
+
+        match (self.o_store)(self.store_data.0, &keys) {
+            0 => Ok(()),
+            code => Err(CoreError::Unexpected(
+                format!("Key store failed to store the keys: {}", code),
+            )),
+        }
+    }
+
+    fn load(&self) -> Result<ClientKeys, CoreError> {
+        let mut keys = AccountKeys {
+            sign_pk: [0; sign::PUBLICKEYBYTES],
+            sign_sk: [0; sign::SECRETKEYBYTES],
+            enc_pk: [0; box_::PUBLICKEYBYTES],
+            enc_sk: [0; box_::SECRETKEYBYTES],
+            enc_key: [0; secretbox::KEYBYTES],
+        };
+
+        match (self.o_load)(self.store_data.0, &mut keys) {
+            0 => Ok(ClientKeys {
+                sign_pk: sign::PublicKey(keys.sign_pk),
+                sign_sk: shared_sign::SecretKey::from_raw(&keys.sign_sk),
+                enc_pk: box_::PublicKey(keys.enc_pk),
+                enc_sk: shared_box::SecretKey::from_raw(&keys.enc_sk),
+                enc_key: shared_secretbox::Key::from_raw(&keys.enc_key),
+            }),
+            code => Err(CoreError::Unexpected(
+                format!("Key store failed to load the keys: {}", code),
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::test_utils::call_0;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use test_utils::{create_account_and_login, run};
+
+    // Key store kept by the test in place of the platform.
+    struct TestStore {
+        keys: Mutex<Option<AccountKeys>>,
+        loads: AtomicUsize,
+    }
+
+    fn copy_keys(keys: &AccountKeys) -> AccountKeys {
+        AccountKeys {
+            sign_pk: keys.sign_pk,
+            sign_sk: keys.sign_sk,
+            enc_pk: keys.enc_pk,
+            enc_sk: keys.enc_sk,
+            enc_key: keys.enc_key,
+        }
+    }
+
+    extern "C" fn store_keys(store_data: *mut c_void, keys: *const AccountKeys) -> i32 {
+        let store = unsafe { &*(store_data as *const TestStore) };
+        *unwrap!(store.keys.lock()) = Some(copy_keys(unsafe { &*keys }));
+        0
+    }
+
+    extern "C" fn load_keys(store_data: *mut c_void, o_keys: *mut AccountKeys) -> i32 {
+        let store = unsafe { &*(store_data as *const TestStore) };
+        let _ = store.loads.fetch_add(1, Ordering::SeqCst);
+        match *unwrap!(store.keys.lock()) {
+            Some(ref keys) => {
+                unsafe { *o_keys = copy_keys(keys) };
+                0
+            }
+            None => -1,
+        }
+    }
+
+    // Test that the keys are handed to the platform key store and loaded from it.
+    #[test]
+    fn platform_key_store() {
+        let store = TestStore {
+            keys: Mutex::new(None),
+            loads: AtomicUsize::new(0),
+        };
+        let store_data = &store as *const TestStore as *mut c_void;
+
+        let auth = create_account_and_login();
+        let sign_sk = run(&auth, |client| {
+            client.secret_signing_key().map_err(AuthError::from)
+        });
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_set_key_store(&auth, store_data, store_keys, load_keys, ud, cb)
+            }))
+        };
+        assert!(unwrap!(store.keys.lock()).is_some());
+
+        let loaded = run(&auth, |client| {
+            client.secret_signing_key().map_err(AuthError::from)
+        });
+        assert!(loaded == sign_sk);
+        assert_eq!(store.loads.load(Ordering::SeqCst), 1);
+    }
+}
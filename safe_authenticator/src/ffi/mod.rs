@@ -17,20 +17,38 @@
 
 /// Apps management
 pub mod apps;
+/// Devices management
+pub mod devices;
+/// Platform key storage and key usage reporting
+pub mod key_store;
 /// Logging utilities
 pub mod logging;
 /// Authenticator communication with apps
 pub mod ipc;
+/// Local socket transport for communication with apps
+pub mod ipc_socket;
+/// Authorisation policy management
+pub mod policy;
+/// Public ID management
+pub mod public_id;
 
 use Authenticator;
+use account_deletion;
 use config_file_handler;
+use export;
 use errors::AuthError;
-use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str};
+use ffi_utils::{FFI_RESULT_OK, FfiResult, FfiString, OpaqueCtx, catch_unwind_cb, from_c_str,
+                from_c_wstr, last_error, ptr_as_ref, spawn_cb};
 use futures::Future;
+use login_guard::{self, LoginObserver};
 use safe_core::FutureExt;
 use safe_core::ffi::AccountInfo as FfiAccountInfo;
+use safe_core::ffi::error_codes::error_code_description_ptr;
+use safe_core::utils::secret::SecretString;
 use std::ffi::{CStr, CString, OsStr};
 use std::os::raw::{c_char, c_void};
+use std::path::Path;
+use std::time::Duration;
 
 /// Create a registered client. This or any one of the other companion
 /// functions to get an authenticator instance must be called before initiating any
@@ -54,25 +72,119 @@ pub unsafe extern "C" fn create_acc(
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
         trace!("Authenticator - create a client account.");
 
-        let acc_locator = from_c_str(account_locator)?;
-        let acc_password = from_c_str(account_password)?;
-        let invitation = from_c_str(invitation)?;
+        let acc_locator = SecretString::from(from_c_str(account_locator)?);
+        let acc_password = SecretString::from(from_c_str(account_password)?);
+        let invitation = SecretString::from(from_c_str(invitation)?);
 
-        let authenticator =
-            Authenticator::create_acc(acc_locator, acc_password, invitation, move || {
-                o_disconnect_notifier_cb(user_data.0)
-            })?;
+        create_acc_impl(
+            acc_locator,
+            acc_password,
+            invitation,
+            user_data,
+            o_disconnect_notifier_cb,
+            o_cb,
+        )
+    })
+}
 
-        o_cb(
-            user_data.0,
-            FFI_RESULT_OK,
-            Box::into_raw(Box::new(authenticator)),
-        );
+/// Same as `create_acc`, but takes the credentials as nul-terminated UTF-16 strings,
+/// as used by Windows wide-string APIs.
+///
+/// Callback parameters: user data, error code, authenticator
+#[no_mangle]
+pub unsafe extern "C" fn create_acc_w(
+    account_locator: *const u16,
+    account_password: *const u16,
+    invitation: *const u16,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        authenticator: *mut Authenticator),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        trace!("Authenticator - create a client account.");
+
+        let acc_locator = SecretString::from(from_c_wstr(account_locator)?);
+        let acc_password = SecretString::from(from_c_wstr(account_password)?);
+        let invitation = SecretString::from(from_c_wstr(invitation)?);
+
+        create_acc_impl(
+            acc_locator,
+            acc_password,
+            invitation,
+            user_data,
+            o_disconnect_notifier_cb,
+            o_cb,
+        )
+    })
+}
+
+/// Same as `create_acc`, but creates the account on a background thread and
+/// returns immediately.
+///
+/// Callback parameters: user data, error code, authenticator
+#[no_mangle]
+pub unsafe extern "C" fn create_acc_async(
+    account_locator: *const c_char,
+    account_password: *const c_char,
+    invitation: *const c_char,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        authenticator: *mut Authenticator),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        trace!("Authenticator - create a client account.");
+
+        let acc_locator = SecretString::from(from_c_str(account_locator)?);
+        let acc_password = SecretString::from(from_c_str(account_password)?);
+        let invitation = SecretString::from(from_c_str(invitation)?);
+
+        spawn_cb(user_data, o_cb, move || {
+            create_acc_impl(
+                acc_locator,
+                acc_password,
+                invitation,
+                user_data,
+                o_disconnect_notifier_cb,
+                o_cb,
+            )
+        });
 
         Ok(())
     })
 }
 
+fn create_acc_impl(
+    acc_locator: SecretString,
+    acc_password: SecretString,
+    invitation: SecretString,
+    user_data: OpaqueCtx,
+    o_disconnect_notifier_cb: extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        authenticator: *mut Authenticator),
+) -> Result<(), AuthError> {
+    let authenticator =
+        Authenticator::create_acc(acc_locator, acc_password, invitation, move || {
+            o_disconnect_notifier_cb(user_data.0)
+        })?;
+
+    o_cb(
+        user_data.0,
+        FFI_RESULT_OK,
+        Box::into_raw(Box::new(authenticator)),
+    );
+
+    Ok(())
+}
+
 /// Log into a registered account. This or any one of the other companion
 /// functions to get an authenticator instance must be called before initiating
 /// any operation allowed for authenticator. The `user_data` parameter corresponds to the
@@ -94,23 +206,137 @@ pub unsafe extern "C" fn login(
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
         trace!("Authenticator - log in a registered client.");
 
-        let acc_locator = from_c_str(account_locator)?;
-        let acc_password = from_c_str(account_password)?;
+        let acc_locator = SecretString::from(from_c_str(account_locator)?);
+        let acc_password = SecretString::from(from_c_str(account_password)?);
 
-        let authenticator = Authenticator::login(acc_locator, acc_password, move || {
-            o_disconnect_notifier_cb(user_data.0)
-        })?;
+        login_impl(
+            acc_locator,
+            acc_password,
+            user_data,
+            o_disconnect_notifier_cb,
+            o_cb,
+        )
+    })
+}
 
-        o_cb(
-            user_data.0,
-            FFI_RESULT_OK,
-            Box::into_raw(Box::new(authenticator)),
-        );
+/// Same as `login`, but takes the credentials as nul-terminated UTF-16 strings.
+///
+/// Callback parameters: user data, error code, authenticator
+#[no_mangle]
+pub unsafe extern "C" fn login_w(
+    account_locator: *const u16,
+    account_password: *const u16,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: unsafe extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        authenticaor: *mut Authenticator),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        trace!("Authenticator - log in a registered client.");
+
+        let acc_locator = SecretString::from(from_c_wstr(account_locator)?);
+        let acc_password = SecretString::from(from_c_wstr(account_password)?);
+
+        login_impl(
+            acc_locator,
+            acc_password,
+            user_data,
+            o_disconnect_notifier_cb,
+            o_cb,
+        )
+    })
+}
+
+/// Same as `login`, but logs in on a background thread and returns immediately.
+///
+/// Callback parameters: user data, error code, authenticator
+#[no_mangle]
+pub unsafe extern "C" fn login_async(
+    account_locator: *const c_char,
+    account_password: *const c_char,
+    user_data: *mut c_void,
+    o_disconnect_notifier_cb: unsafe extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        authenticaor: *mut Authenticator),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        trace!("Authenticator - log in a registered client.");
+
+        let acc_locator = SecretString::from(from_c_str(account_locator)?);
+        let acc_password = SecretString::from(from_c_str(account_password)?);
+
+        spawn_cb(user_data, o_cb, move || {
+            login_impl(
+                acc_locator,
+                acc_password,
+                user_data,
+                o_disconnect_notifier_cb,
+                o_cb,
+            )
+        });
 
         Ok(())
     })
 }
 
+fn login_impl(
+    acc_locator: SecretString,
+    acc_password: SecretString,
+    user_data: OpaqueCtx,
+    o_disconnect_notifier_cb: unsafe extern "C" fn(user_data: *mut c_void),
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        authenticaor: *mut Authenticator),
+) -> Result<(), AuthError> {
+    let authenticator = Authenticator::login(acc_locator, acc_password, move || unsafe {
+        o_disconnect_notifier_cb(user_data.0)
+    })?;
+
+    o_cb(
+        user_data.0,
+        FFI_RESULT_OK,
+        Box::into_raw(Box::new(authenticator)),
+    );
+
+    Ok(())
+}
+
+/// Set the function to notify of failed login attempts, with the number of consecutive
+/// failures of the account and the number of seconds before the next attempt is allowed.
+/// Attempts made sooner fail with `ERR_LOGIN_DELAYED` without contacting the network. The
+/// function is called on the thread logging in, with `observer_data`, which must stay valid
+/// until the function is replaced. Pass a null function to remove it.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_login_failure_observer(
+    observer_data: *mut c_void,
+    o_failure: Option<
+        extern "C" fn(observer_data: *mut c_void, failed_attempts: u32, delay_secs: u64),
+    >,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let observer_data = OpaqueCtx(observer_data);
+
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let observer = o_failure.map(|o_failure| -> LoginObserver {
+            Box::new(move |failures, delay| {
+                o_failure(observer_data.0, failures, delay.as_secs())
+            })
+        });
+        login_guard::set_observer(observer);
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    })
+}
+
 /// Try to restore a failed connection with the network.
 ///
 /// Callback parameters: user data, error code
@@ -122,7 +348,7 @@ pub unsafe extern "C" fn auth_reconnect(
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
         let user_data = OpaqueCtx(user_data);
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             try_cb!(
                 client.restart_routing().map_err(AuthError::from),
                 user_data.0,
@@ -134,6 +360,49 @@ pub unsafe extern "C" fn auth_reconnect(
     })
 }
 
+/// Disconnect from the network while keeping the logged-in session, e.g. when
+/// the mobile app hosting the authenticator goes into the background.
+/// Requests made while suspended fail until `auth_resume` is called.
+#[no_mangle]
+pub unsafe extern "C" fn auth_suspend(
+    auth: *mut Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let user_data = OpaqueCtx(user_data);
+        ptr_as_ref(auth)?.send(move |client| {
+            client.suspend();
+            o_cb(user_data.0, FFI_RESULT_OK);
+            None
+        })
+    })
+}
+
+/// Reconnect a suspended authenticator to the network without requiring
+/// the account credentials again. Does nothing if it is not suspended.
+#[no_mangle]
+pub unsafe extern "C" fn auth_resume(
+    auth: *mut Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let user_data = OpaqueCtx(user_data);
+        ptr_as_ref(auth)?.send(move |client| {
+            if client.is_suspended() {
+                try_cb!(
+                    client.restart_routing().map_err(AuthError::from),
+                    user_data.0,
+                    o_cb
+                );
+            }
+            o_cb(user_data.0, FFI_RESULT_OK);
+            None
+        })
+    })
+}
+
 /// Get the account usage statistics.
 ///
 /// Callback parameters: user data, error code, account info
@@ -147,7 +416,7 @@ pub unsafe extern "C" fn auth_account_info(
 ) {
     catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
         let user_data = OpaqueCtx(user_data);
-        (*auth).send(move |client| {
+        ptr_as_ref(auth)?.send(move |client| {
             client
                 .get_account_info()
                 .map(move |acc_info| {
@@ -166,6 +435,108 @@ pub unsafe extern "C" fn auth_account_info(
     })
 }
 
+/// Delete the account of the logged in user. The user confirms the deletion by entering
+/// the account credentials again. All data recorded in the account containers is wiped,
+/// the keys of authorised apps are revoked and the account can no longer be logged into.
+/// The authenticator should be freed afterwards.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_delete_account(
+    auth: *const Authenticator,
+    account_locator: *const c_char,
+    account_password: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let locator = SecretString::from(from_c_str(account_locator)?);
+        let password = SecretString::from(from_c_str(account_password)?);
+
+        ptr_as_ref(auth)?.send(move |client| {
+            account_deletion::delete_account(client, &locator, &password)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Export the files of the account's standard containers into the local directory at
+/// `dest_path`, along with a `manifest.json` describing them.
+///
+/// Callback parameters: user data, error code, number of exported files
+#[no_mangle]
+pub unsafe extern "C" fn auth_export_account(
+    auth: *const Authenticator,
+    dest_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult, files_len: u64),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let dest_path = from_c_str(dest_path)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            export::export(client, Path::new(&dest_path))
+                .map(move |manifest| {
+                    o_cb(user_data.0, FFI_RESULT_OK, manifest.files.len() as u64);
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Import the files exported by `auth_export_account` from the local directory at
+/// `src_path` into the account's containers of the same names. Files which already exist
+/// are skipped.
+///
+/// Callback parameters: user data, error code, number of imported files, number of skipped
+/// files
+#[no_mangle]
+pub unsafe extern "C" fn auth_import_account(
+    auth: *const Authenticator,
+    src_path: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        imported_len: u64,
+                        skipped_len: u64),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let src_path = from_c_str(src_path)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            export::import(client, Path::new(&src_path))
+                .map(move |summary| {
+                    o_cb(
+                        user_data.0,
+                        FFI_RESULT_OK,
+                        summary.imported as u64,
+                        summary.skipped.len() as u64,
+                    );
+                })
+                .map_err(move |err| {
+                    call_result_cb!(Err::<(), _>(err), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
 /// Returns the expected name for the application executable without an extension
 #[no_mangle]
 pub unsafe extern "C" fn auth_exe_file_stem(
@@ -207,25 +578,124 @@ pub unsafe extern "C" fn auth_set_additional_search_path(
     });
 }
 
+/// Same as `auth_set_additional_search_path`, but takes the path as a nul-terminated
+/// UTF-16 string.
+#[no_mangle]
+pub unsafe extern "C" fn auth_set_additional_search_path_w(
+    new_path: *const u16,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let new_path = from_c_wstr(new_path)?;
+        config_file_handler::set_additional_search_path(OsStr::new(&new_path));
+        o_cb(user_data, FFI_RESULT_OK);
+        Ok(())
+    });
+}
+
 /// Discard and clean up the previously allocated authenticator instance.
 /// Use this only if the authenticator is obtained from one of the auth
 /// functions in this crate (`create_acc` or `login`).
 /// Using `auth` after a call to this function is undefined behaviour.
 #[no_mangle]
 pub unsafe extern "C" fn auth_free(auth: *mut Authenticator) {
-    let _ = Box::from_raw(auth);
+    if !auth.is_null() {
+        let _ = Box::from_raw(auth);
+    }
+}
+
+/// Gracefully shut down and clean up the previously allocated authenticator
+/// instance. Operations already sent to the authenticator are given up to
+/// `timeout_ms` milliseconds to finish. Those still waiting for the network
+/// then have their callbacks invoked with an "operation aborted" error. Using
+/// `auth` after a call to this function is undefined behaviour.
+///
+/// Callback parameters: user data, error code, number of operations
+/// abandoned because they didn't finish even so
+#[no_mangle]
+pub unsafe extern "C" fn auth_shutdown(
+    auth: *mut Authenticator,
+    timeout_ms: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        abandoned: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let _ = ptr_as_ref(auth)?;
+        let auth = *Box::from_raw(auth);
+        let abandoned = auth.shutdown(Duration::from_millis(timeout_ms))?;
+        o_cb(user_data, FFI_RESULT_OK, abandoned as u64);
+        Ok(())
+    })
+}
+
+/// Same as `auth_shutdown`, but waits for the pending operations on a background
+/// thread and returns immediately.
+///
+/// Callback parameters: user data, error code, number of abandoned operations
+#[no_mangle]
+pub unsafe extern "C" fn auth_shutdown_async(
+    auth: *mut Authenticator,
+    timeout_ms: u64,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        abandoned: u64),
+) {
+    catch_unwind_cb(user_data, o_cb, || -> Result<_, AuthError> {
+        let user_data = OpaqueCtx(user_data);
+        let _ = ptr_as_ref(auth)?;
+        let auth = *Box::from_raw(auth);
+
+        spawn_cb(user_data, o_cb, move || -> Result<_, AuthError> {
+            let abandoned = auth.shutdown(Duration::from_millis(timeout_ms))?;
+            o_cb(user_data.0, FFI_RESULT_OK, abandoned as u64);
+            Ok(())
+        });
+
+        Ok(())
+    })
+}
+
+/// Returns the description of the most recent error reported on the calling
+/// thread, or a null string if there was none. Errors of asynchronous
+/// operations are reported on the thread their callback runs on, so this should
+/// be called from within the callback. The string has to be released with
+/// `auth_string_free`.
+#[no_mangle]
+pub extern "C" fn auth_last_error_message() -> FfiString {
+    last_error().map_or_else(FfiString::null, FfiString::from_string)
+}
+
+/// Release a string handed over by one of the functions of this crate which
+/// return `FfiString`. Using the string after a call to this function is
+/// undefined behaviour.
+#[no_mangle]
+pub unsafe extern "C" fn auth_string_free(s: FfiString) {
+    let _ = s.into_string();
+}
+
+/// Returns the static description of the given error code, or null if the code
+/// is unknown. The string must not be freed.
+#[no_mangle]
+pub extern "C" fn auth_error_code_description(code: i32) -> *const c_char {
+    error_code_description_ptr(code)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use Authenticator;
-    use ffi_utils::test_utils::call_1;
+    use errors::ERR_LOGIN_DELAYED;
+    use ffi_utils::test_utils::{call_0, call_1};
     use routing::ImmutableData;
     use safe_core::ffi::AccountInfo;
     use safe_core::utils;
     use std::ffi::CString;
     use std::os::raw::c_void;
+    use std::sync::Mutex;
 
     // Test creating an account and logging in.
     #[test]
@@ -272,10 +742,120 @@ mod tests {
         }
     }
 
+    // Test creating an account, logging in and shutting down with the non-blocking variants.
+    #[test]
+    fn async_create_account_and_login() {
+        let acc_locator = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let acc_password = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let invitation = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+
+        let auth_h: *mut Authenticator = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                create_acc_async(
+                    acc_locator.as_ptr(),
+                    acc_password.as_ptr(),
+                    invitation.as_ptr(),
+                    ud,
+                    disconnect_cb,
+                    cb,
+                )
+            }))
+        };
+        assert!(!auth_h.is_null());
+        unsafe { auth_free(auth_h) };
+
+        let auth_h: *mut Authenticator = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                login_async(
+                    acc_locator.as_ptr(),
+                    acc_password.as_ptr(),
+                    ud,
+                    disconnect_cb,
+                    cb,
+                )
+            }))
+        };
+        assert!(!auth_h.is_null());
+
+        let abandoned: u64 =
+            unsafe { unwrap!(call_1(|ud, cb| auth_shutdown_async(auth_h, 1000, ud, cb))) };
+        assert_eq!(abandoned, 0);
+
+        extern "C" fn disconnect_cb(_user_data: *mut c_void) {
+            panic!("Disconnect occurred")
+        }
+    }
+
+    // Test that credentials passed as UTF-16 match the same credentials passed as UTF-8.
+    #[test]
+    fn wide_string_credentials() {
+        let locator = unwrap!(utils::generate_random_string(10));
+        let password = format!("{}\u{17c}\u{f3}\u{142}\u{1f600}", locator);
+        let invitation = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+
+        let wide = |s: &str| {
+            let mut wide: Vec<u16> = s.encode_utf16().collect();
+            wide.push(0);
+            wide
+        };
+        let acc_locator_w = wide(&locator);
+        let acc_password_w = wide(&password);
+        let invitation_w = wide(unwrap!(invitation.to_str()));
+        let acc_locator = unwrap!(CString::new(locator));
+        let acc_password = unwrap!(CString::new(password));
+
+        {
+            let auth_h: *mut Authenticator = unsafe {
+                unwrap!(call_1(|ud, cb| {
+                    create_acc_w(
+                        acc_locator_w.as_ptr(),
+                        acc_password_w.as_ptr(),
+                        invitation_w.as_ptr(),
+                        ud,
+                        disconnect_cb,
+                        cb,
+                    )
+                }))
+            };
+            assert!(!auth_h.is_null());
+            unsafe { auth_free(auth_h) };
+        }
+
+        for &wide_login in &[false, true] {
+            let auth_h: *mut Authenticator = unsafe {
+                unwrap!(call_1(|ud, cb| if wide_login {
+                    login_w(
+                        acc_locator_w.as_ptr(),
+                        acc_password_w.as_ptr(),
+                        ud,
+                        disconnect_cb,
+                        cb,
+                    )
+                } else {
+                    login(
+                        acc_locator.as_ptr(),
+                        acc_password.as_ptr(),
+                        ud,
+                        disconnect_cb,
+                        cb,
+                    )
+                }))
+            };
+            assert!(!auth_h.is_null());
+            unsafe { auth_free(auth_h) };
+        }
+
+        extern "C" fn disconnect_cb(_user_data: *mut c_void) {
+            panic!("Disconnect occurred")
+        }
+    }
+
     // Test disconnection and reconnection with the authenticator.
     #[cfg(all(test, feature = "use-mock-routing"))]
     #[test]
     fn network_status_callback() {
+        skip_unless_mock!();
+
         use ffi_utils::test_utils::{UserData, call_0, call_1_with_custom,
                                     send_via_user_data_custom};
         use std::time::Duration;
@@ -348,6 +928,47 @@ mod tests {
         }
     }
 
+    // Test suspending the authenticator and resuming it without logging in again.
+    #[test]
+    fn suspend_and_resume() {
+        use ffi_utils::test_utils::call_0;
+
+        let acc_locator = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let acc_password = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let invitation = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+
+        let auth: *mut Authenticator = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                create_acc(
+                    acc_locator.as_ptr(),
+                    acc_password.as_ptr(),
+                    invitation.as_ptr(),
+                    ud,
+                    disconnect_cb,
+                    cb,
+                )
+            }))
+        };
+
+        // Resuming an active authenticator is a no-op.
+        unsafe { unwrap!(call_0(|ud, cb| auth_resume(auth, ud, cb))) };
+
+        unsafe { unwrap!(call_0(|ud, cb| auth_suspend(auth, ud, cb))) };
+
+        // Network requests fail while suspended.
+        let res: Result<AccountInfo, i32> =
+            unsafe { call_1(|ud, cb| auth_account_info(auth, ud, cb)) };
+        assert!(res.is_err());
+
+        unsafe { unwrap!(call_0(|ud, cb| auth_resume(auth, ud, cb))) };
+
+        let stats: AccountInfo =
+            unsafe { unwrap!(call_1(|ud, cb| auth_account_info(auth, ud, cb))) };
+        assert!(stats.mutations_available > 0);
+
+        unsafe { auth_free(auth) };
+    }
+
     // Test account usage statistics before and after a mutation.
     #[test]
     fn account_info() {
@@ -393,7 +1014,82 @@ mod tests {
         unsafe { auth_free(auth) };
     }
 
+    // Test that a null authenticator pointer is reported as an error instead of
+    // crashing the process.
+    #[test]
+    fn null_authenticator() {
+        use errors::ERR_NULL_POINTER;
+        use std::ptr;
+
+        let res: Result<AccountInfo, i32> =
+            unsafe { call_1(|ud, cb| auth_account_info(ptr::null_mut(), ud, cb)) };
+        assert_eq!(res.err(), Some(ERR_NULL_POINTER));
+
+        let msg = unsafe { auth_last_error_message().into_string() };
+        assert_eq!(msg, Some("Null pointer".to_owned()));
+
+        let desc = unsafe { CStr::from_ptr(auth_error_code_description(ERR_NULL_POINTER)) };
+        assert_eq!(unwrap!(desc.to_str()), "Null pointer");
+        assert!(auth_error_code_description(0).is_null());
+
+        // Freeing a null authenticator is a no-op.
+        unsafe { auth_free(ptr::null_mut()) };
+    }
+
     extern "C" fn disconnect_cb(_user_data: *mut c_void) {
         panic!("Disconnect occurred")
     }
+
+    // Test that repeated failed logins get reported and delay further attempts.
+    #[test]
+    fn login_failures() {
+        extern "C" fn record(observer_data: *mut c_void, failed_attempts: u32, delay_secs: u64) {
+            let failures = unsafe { &*(observer_data as *const Mutex<Vec<(u32, u64)>>) };
+            unwrap!(failures.lock()).push((failed_attempts, delay_secs));
+        }
+
+        let failures = Mutex::new(Vec::new());
+        let observer_data = &failures as *const _ as *mut c_void;
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_set_login_failure_observer(observer_data, Some(record), ud, cb)
+            }))
+        };
+
+        // No account is registered under this locator.
+        let acc_locator = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let acc_password = unwrap!(CString::new(unwrap!(utils::generate_random_string(10))));
+        let try_login = || unsafe {
+            call_1(|ud, cb| {
+                login(
+                    acc_locator.as_ptr(),
+                    acc_password.as_ptr(),
+                    ud,
+                    disconnect_cb,
+                    cb,
+                )
+            }).map(|auth_h: *mut Authenticator| auth_free(auth_h))
+        };
+
+        for _ in 0..login_guard::FREE_LOGIN_ATTEMPTS {
+            match try_login() {
+                Err(ERR_LOGIN_DELAYED) | Ok(()) => panic!("Unexpected login result"),
+                Err(_) => (),
+            }
+        }
+        assert_eq!(try_login(), Err(ERR_LOGIN_DELAYED));
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_set_login_failure_observer(observer_data, None, ud, cb)
+            }))
+        };
+
+        // Other tests may fail to log in concurrently, so look for this one's failures only.
+        let failures = unwrap!(failures.lock());
+        assert!(failures.contains(&(
+            login_guard::FREE_LOGIN_ATTEMPTS,
+            login_guard::INITIAL_LOGIN_DELAY_SECS,
+        )));
+    }
 }
@@ -0,0 +1,253 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {AuthError, Authenticator};
+use config;
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, catch_unwind_cb, from_c_str, ptr_as_ref};
+use futures::Future;
+use policy;
+use safe_core::FutureExt;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+/// Authorisation policy. The arrays are only valid for the duration of the callback.
+#[repr(C)]
+pub struct AuthPolicy {
+    /// IDs of apps which are always granted access
+    pub allowed_apps: *const *const c_char,
+    /// Length of the allowed apps array
+    pub allowed_apps_len: usize,
+    /// Containers requests for which are always denied
+    pub denied_containers: *const *const c_char,
+    /// Length of the denied containers array
+    pub denied_containers_len: usize,
+    /// Whether read-only requests are granted automatically
+    pub grant_read_only: bool,
+}
+
+/// Get the authorisation policy.
+///
+/// Callback parameters: user data, error code, policy
+#[no_mangle]
+pub unsafe extern "C" fn auth_policy(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        policy: *const AuthPolicy),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            config::get_auth_policy(client)
+                .and_then(move |(_, policy)| {
+                    let allowed_apps = to_c_strings(policy.allowed_apps)?;
+                    let denied_containers = to_c_strings(policy.denied_containers)?;
+                    let allowed_apps_ptrs: Vec<_> =
+                        allowed_apps.iter().map(|s| s.as_ptr()).collect();
+                    let denied_containers_ptrs: Vec<_> =
+                        denied_containers.iter().map(|s| s.as_ptr()).collect();
+
+                    let ffi_policy = AuthPolicy {
+                        allowed_apps: allowed_apps_ptrs.as_ptr(),
+                        allowed_apps_len: allowed_apps_ptrs.len(),
+                        denied_containers: denied_containers_ptrs.as_ptr(),
+                        denied_containers_len: denied_containers_ptrs.len(),
+                        grant_read_only: policy.grant_read_only,
+                    };
+                    o_cb(user_data.0, FFI_RESULT_OK, &ffi_policy);
+
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Add the app to (or remove it from) the list of apps which are always granted
+/// access without prompting the user.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_policy_set_app_allowed(
+    auth: *const Authenticator,
+    app_id: *const c_char,
+    allowed: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let app_id = from_c_str(app_id)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            policy::set_app_allowed(client, app_id, allowed)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Add the container to (or remove it from) the list of containers requests for
+/// which are always denied without prompting the user.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_policy_set_container_denied(
+    auth: *const Authenticator,
+    container: *const c_char,
+    denied: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let container = from_c_str(container)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            policy::set_container_denied(client, container, denied)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Enable or disable granting of read-only requests without prompting the user.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_policy_set_grant_read_only(
+    auth: *const Authenticator,
+    enabled: bool,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            policy::set_grant_read_only(client, enabled)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+fn to_c_strings<I: IntoIterator<Item = String>>(strings: I) -> Result<Vec<CString>, AuthError> {
+    Ok(strings.into_iter().map(CString::new).collect::<Result<_, _>>()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::ReprC;
+    use ffi_utils::test_utils::{call_0, call_1};
+    use std::slice;
+    use test_utils::create_account_and_login;
+
+    #[derive(Debug)]
+    struct Policy {
+        allowed_apps: Vec<String>,
+        denied_containers: Vec<String>,
+        grant_read_only: bool,
+    }
+
+    impl ReprC for Policy {
+        type C = *const AuthPolicy;
+        type Error = AuthError;
+
+        unsafe fn clone_from_repr_c(c_repr: Self::C) -> Result<Self, Self::Error> {
+            Ok(Policy {
+                allowed_apps: strings((*c_repr).allowed_apps, (*c_repr).allowed_apps_len)?,
+                denied_containers: strings(
+                    (*c_repr).denied_containers,
+                    (*c_repr).denied_containers_len,
+                )?,
+                grant_read_only: (*c_repr).grant_read_only,
+            })
+        }
+    }
+
+    unsafe fn strings(ptr: *const *const c_char, len: usize) -> Result<Vec<String>, AuthError> {
+        slice::from_raw_parts(ptr, len)
+            .iter()
+            .map(|s| from_c_str(*s).map_err(AuthError::from))
+            .collect()
+    }
+
+    // Test managing the authorisation policy rules.
+    #[test]
+    fn manage_rules() {
+        let auth = create_account_and_login();
+        let app_id = unwrap!(CString::new("app"));
+        let container = unwrap!(CString::new("_music"));
+
+        let policy: Policy = unsafe { unwrap!(call_1(|ud, cb| auth_policy(&auth, ud, cb))) };
+        assert!(policy.allowed_apps.is_empty());
+        assert!(policy.denied_containers.is_empty());
+        assert!(!policy.grant_read_only);
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_policy_set_app_allowed(&auth, app_id.as_ptr(), true, ud, cb)
+            }));
+            unwrap!(call_0(|ud, cb| {
+                auth_policy_set_container_denied(&auth, container.as_ptr(), true, ud, cb)
+            }));
+            unwrap!(call_0(
+                |ud, cb| auth_policy_set_grant_read_only(&auth, true, ud, cb),
+            ));
+        }
+
+        let policy: Policy = unsafe { unwrap!(call_1(|ud, cb| auth_policy(&auth, ud, cb))) };
+        assert_eq!(policy.allowed_apps, vec!["app".to_owned()]);
+        assert_eq!(policy.denied_containers, vec!["_music".to_owned()]);
+        assert!(policy.grant_read_only);
+
+        // Removing a rule which is not there is a no-op.
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_policy_set_app_allowed(&auth, app_id.as_ptr(), false, ud, cb)
+            }));
+            unwrap!(call_0(|ud, cb| {
+                auth_policy_set_app_allowed(&auth, app_id.as_ptr(), false, ud, cb)
+            }));
+        }
+
+        let policy: Policy = unsafe { unwrap!(call_1(|ud, cb| auth_policy(&auth, ud, cb))) };
+        assert!(policy.allowed_apps.is_empty());
+    }
+}
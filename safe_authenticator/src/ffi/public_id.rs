@@ -0,0 +1,315 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use {AuthError, Authenticator};
+use ffi_utils::{FFI_RESULT_OK, FfiResult, OpaqueCtx, ReprC, catch_unwind_cb, from_c_str,
+                ptr_as_ref};
+use futures::Future;
+use public_id;
+use safe_core::{FutureExt, MDataInfo, dns};
+use safe_core::ffi::MDataInfo as FfiMDataInfo;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+
+/// Claim a new public ID (e.g. the `myname` part of `safe://myname`) and create
+/// an empty services container for it.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_public_id_create(
+    auth: *const Authenticator,
+    public_name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let public_name = from_c_str(public_name)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            public_id::create(client, public_name)
+                .then(move |res| {
+                    call_result_cb!(res.map(|_| ()), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get a list of public IDs owned by the user.
+///
+/// Callback parameters: user data, error code, public names vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn auth_public_ids(
+    auth: *const Authenticator,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        public_names: *const *const c_char,
+                        public_names_len: usize),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        ptr_as_ref(auth)?.send(move |client| {
+            public_id::list(client)
+                .and_then(move |names| {
+                    let names = names
+                        .into_iter()
+                        .map(CString::new)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let ptrs: Vec<_> = names.iter().map(|name| name.as_ptr()).collect();
+
+                    o_cb(user_data.0, FFI_RESULT_OK, ptrs.as_ptr(), ptrs.len());
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Delete a public ID owned by the user. The public name stays reserved for
+/// the user, but it no longer points to any services.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_public_id_delete(
+    auth: *const Authenticator,
+    public_name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let public_name = from_c_str(public_name)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            public_id::delete(client, &public_name)
+                .then(move |res| {
+                    call_result_cb!(res, user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Attach a service (e.g. `www`) to a public ID owned by the user, pointing it
+/// at the given public directory. An existing service of the same name is replaced.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_public_id_set_service(
+    auth: *const Authenticator,
+    public_name: *const c_char,
+    service: *const c_char,
+    target: *const FfiMDataInfo,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let public_name = from_c_str(public_name)?;
+        let service = from_c_str(service)?;
+        let target = MDataInfo::clone_from_repr_c(target)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            dns::set_service(client, &public_name, &service, &target)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AuthError::from), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Detach a service from a public ID owned by the user.
+///
+/// Callback parameters: user data, error code
+#[no_mangle]
+pub unsafe extern "C" fn auth_public_id_remove_service(
+    auth: *const Authenticator,
+    public_name: *const c_char,
+    service: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void, result: *const FfiResult),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let public_name = from_c_str(public_name)?;
+        let service = from_c_str(service)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            dns::remove_service(client, &public_name, &service)
+                .then(move |res| {
+                    call_result_cb!(res.map_err(AuthError::from), user_data, o_cb);
+                    Ok(())
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+/// Get a list of services attached to the given public ID.
+///
+/// Callback parameters: user data, error code, service names vector, vector size
+#[no_mangle]
+pub unsafe extern "C" fn auth_public_id_services(
+    auth: *const Authenticator,
+    public_name: *const c_char,
+    user_data: *mut c_void,
+    o_cb: extern "C" fn(user_data: *mut c_void,
+                        result: *const FfiResult,
+                        services: *const *const c_char,
+                        services_len: usize),
+) {
+    let user_data = OpaqueCtx(user_data);
+
+    catch_unwind_cb(user_data.0, o_cb, || -> Result<_, AuthError> {
+        let public_name = from_c_str(public_name)?;
+
+        ptr_as_ref(auth)?.send(move |client| {
+            dns::list_services(client, &public_name)
+                .map_err(AuthError::from)
+                .and_then(move |services| {
+                    let services = services
+                        .into_iter()
+                        .map(CString::new)
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let ptrs: Vec<_> = services.iter().map(|name| name.as_ptr()).collect();
+
+                    o_cb(user_data.0, FFI_RESULT_OK, ptrs.as_ptr(), ptrs.len());
+                    Ok(())
+                })
+                .map_err(move |e| {
+                    call_result_cb!(Err::<(), _>(e), user_data, o_cb);
+                })
+                .into_box()
+                .into()
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ffi_utils::StringError;
+    use ffi_utils::test_utils::{call_0, call_vec};
+    use safe_core::DIR_TAG;
+    use safe_core::utils::generate_random_string;
+    use test_utils::create_account_and_login;
+
+    struct PublicName(String);
+    impl ReprC for PublicName {
+        type C = *const *const c_char;
+        type Error = StringError;
+
+        unsafe fn clone_from_repr_c(c_repr: Self::C) -> Result<Self, Self::Error> {
+            Ok(PublicName(from_c_str(*c_repr)?))
+        }
+    }
+
+    // Test public ID management through the FFI.
+    #[test]
+    fn create_list_delete() {
+        let auth = create_account_and_login();
+        let name = unwrap!(generate_random_string(10));
+        let name_c = unwrap!(CString::new(name.clone()));
+
+        unsafe {
+            unwrap!(call_0(
+                |ud, cb| auth_public_id_create(&auth, name_c.as_ptr(), ud, cb),
+            ))
+        };
+
+        let names: Vec<PublicName> =
+            unsafe { unwrap!(call_vec(|ud, cb| auth_public_ids(&auth, ud, cb))) };
+        let names: Vec<_> = names.into_iter().map(|name| name.0).collect();
+        assert_eq!(names, vec![name]);
+
+        unsafe {
+            unwrap!(call_0(
+                |ud, cb| auth_public_id_delete(&auth, name_c.as_ptr(), ud, cb),
+            ))
+        };
+
+        let names: Vec<PublicName> =
+            unsafe { unwrap!(call_vec(|ud, cb| auth_public_ids(&auth, ud, cb))) };
+        assert!(names.is_empty());
+    }
+
+    // Test attaching and detaching services through the FFI.
+    #[test]
+    fn services() {
+        let auth = create_account_and_login();
+        let name = unwrap!(generate_random_string(10));
+        let name_c = unwrap!(CString::new(name));
+        let service_c = unwrap!(CString::new("blog"));
+        let target = unwrap!(MDataInfo::random_public(DIR_TAG)).into_repr_c();
+
+        unsafe {
+            unwrap!(call_0(
+                |ud, cb| auth_public_id_create(&auth, name_c.as_ptr(), ud, cb),
+            ));
+            unwrap!(call_0(|ud, cb| {
+                auth_public_id_set_service(
+                    &auth,
+                    name_c.as_ptr(),
+                    service_c.as_ptr(),
+                    &target,
+                    ud,
+                    cb,
+                )
+            }));
+        }
+
+        let services: Vec<PublicName> = unsafe {
+            unwrap!(call_vec(
+                |ud, cb| auth_public_id_services(&auth, name_c.as_ptr(), ud, cb),
+            ))
+        };
+        let services: Vec<_> = services.into_iter().map(|name| name.0).collect();
+        assert_eq!(services, vec!["blog".to_owned()]);
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_public_id_remove_service(&auth, name_c.as_ptr(), service_c.as_ptr(), ud, cb)
+            }))
+        };
+
+        let services: Vec<PublicName> = unsafe {
+            unwrap!(call_vec(
+                |ud, cb| auth_public_id_services(&auth, name_c.as_ptr(), ud, cb),
+            ))
+        };
+        assert!(services.is_empty());
+    }
+}
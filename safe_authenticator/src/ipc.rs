@@ -17,41 +17,103 @@
 
 use super::{AuthError, AuthFuture};
 use access_container;
-use app_auth::{AppState, app_state};
+use app_auth::{self, AppState, app_state};
+use app_container;
 use config;
 use ffi_utils::StringError;
-use futures::Future;
+use futures::{Future, Stream, stream};
 use futures::future::{self, Either};
 use maidsafe_utilities::serialisation::deserialise;
+use policy::Decision;
+use revocation;
 use routing::{ClientError, User, XorName};
 use rust_sodium::crypto::sign;
 use safe_core::{Client, CoreError, FutureExt, recovery};
 use safe_core::ffi::ipc::resp::MetadataResponse as FfiUserMetadata;
-use safe_core::ipc::{self, IpcError, IpcMsg};
-use safe_core::ipc::req::{ContainerPermissions, IpcReq, ShareMDataReq,
+use safe_core::ipc::{self, IpcError, IpcMsg, ReqMac};
+use safe_core::ipc::req::{CombinedReq, ContainerPermissions, IpcReq, ShareMData, ShareMDataReq,
                           container_perms_into_permission_set};
-use safe_core::ipc::resp::{AccessContainerEntry, IpcResp, METADATA_KEY, UserMetadata};
-use std::collections::HashMap;
+use safe_core::ipc::resp::{AccessContainerEntry, AuthGranted, IpcResp, METADATA_KEY,
+                           UserMetadata};
+use std::collections::{HashMap, VecDeque};
 use std::ffi::CString;
+use std::sync::{Mutex, PoisonError};
+
+/// Maximum number of requests whose protocol version is remembered.
+const MAX_REQ_VERSIONS: usize = 256;
+
+lazy_static! {
+    // Protocol versions of the most recently decoded requests, by request ID, so that
+    // the responses are encoded with a version the requesting apps understand.
+    static ref REQ_VERSIONS: Mutex<VecDeque<(u32, u32)>> = Mutex::new(VecDeque::new());
+}
 
 /// Decodes a given encoded IPC message and returns either an `IpcMsg` struct or
-/// an error code + description & an encoded `IpcMsg::Resp` in case of an error
+/// an error code + description & an encoded `IpcMsg::Resp` in case of an error.
+/// Auth, combined and containers requests are first checked against the authorisation
+/// policy - if the policy grants an auth request and `auto_grant` is set, the
+/// `IpcMsg::Resp` to be sent back to the app is returned. `version` is the protocol version the
+/// message was encoded with, which all responses to it are encoded with too.
 #[cfg_attr(feature = "cargo-clippy", allow(type_complexity))]
 pub fn decode_ipc_msg(
     client: &Client<()>,
     msg: IpcMsg,
+    version: u32,
+    auto_grant: bool,
 ) -> Box<AuthFuture<Result<IpcMsg, (i32, CString, CString)>>> {
+    record_req_version(&msg, version);
+    fry!(validate_app(&msg));
+
     match msg {
         IpcMsg::Req {
             req: IpcReq::Auth(auth_req),
             req_id,
         } => {
-            // Ok status should be returned for all app states (including
-            // Revoked and Authenticated).
-            ok!(Ok(IpcMsg::Req {
-                req_id: req_id,
-                req: IpcReq::Auth(auth_req),
-            }))
+            let c2 = client.clone();
+
+            config::get_auth_policy(client)
+                .and_then(move |(_, policy)| {
+                    let decision = match policy.evaluate(&auth_req) {
+                        // Without a way to pass the response on, the user is prompted
+                        // instead.
+                        Decision::Grant if !auto_grant => Decision::Prompt,
+                        decision => decision,
+                    };
+
+                    match decision {
+                        // Ok status should be returned for all app states (including
+                        // Revoked and Authenticated).
+                        Decision::Prompt => {
+                            ok!(Ok(IpcMsg::Req {
+                                req_id: req_id,
+                                req: IpcReq::Auth(auth_req),
+                            }))
+                        }
+                        Decision::Grant => {
+                            app_auth::authenticate(&c2, auth_req)
+                                .map(move |auth_granted| {
+                                    Ok(IpcMsg::Resp {
+                                        req_id: req_id,
+                                        resp: IpcResp::Auth(Ok(auth_granted)),
+                                    })
+                                })
+                                .into_box()
+                        }
+                        Decision::Deny => {
+                            let (error_code, description) =
+                                ffi_error!(AuthError::from(IpcError::AuthDenied));
+
+                            let resp = IpcMsg::Resp {
+                                resp: IpcResp::Auth(Err(IpcError::AuthDenied)),
+                                req_id: req_id,
+                            };
+                            let resp = fry!(encode_response(&resp));
+
+                            ok!(Err((error_code, description, resp)))
+                        }
+                    }
+                })
+                .into_box()
         }
         IpcMsg::Req {
             req: IpcReq::Unregistered(extra_data),
@@ -71,20 +133,68 @@ pub fn decode_ipc_msg(
                 req: IpcReq::ShareMData(share_mdata_req),
             }))
         }
+        IpcMsg::Req {
+            req: IpcReq::Combined(combined_req),
+            req_id,
+        } => {
+            // Sharing mutable data always needs the user's consent, so the policy can
+            // only deny combined requests.
+            config::get_auth_policy(client)
+                .and_then(move |(_, policy)| match policy.evaluate(&combined_req.auth) {
+                    Decision::Deny => {
+                        let (error_code, description) =
+                            ffi_error!(AuthError::from(IpcError::AuthDenied));
+
+                        let resp = IpcMsg::Resp {
+                            resp: IpcResp::Combined(Err(IpcError::AuthDenied)),
+                            req_id: req_id,
+                        };
+                        let resp = encode_response(&resp)?;
+
+                        Ok(Err((error_code, description, resp)))
+                    }
+                    Decision::Grant | Decision::Prompt => {
+                        Ok(Ok(IpcMsg::Req {
+                            req_id: req_id,
+                            req: IpcReq::Combined(combined_req),
+                        }))
+                    }
+                })
+                .into_box()
+        }
         IpcMsg::Req {
             req: IpcReq::Containers(cont_req),
             req_id,
         } => {
-            let app_id = cont_req.app.id.clone();
+            let app_id = cont_req.app.scoped_id();
 
             let c2 = client.clone();
 
+            let c3 = client.clone();
+
             config::list_apps(client)
                 .and_then(move |(_config_version, config)| {
                     app_state(&c2, &config, &app_id)
                 })
                 .and_then(move |app_state| {
+                    config::get_auth_policy(&c3).map(move |(_, policy)| (app_state, policy))
+                })
+                .and_then(move |(app_state, policy)| {
+                    let denied = policy.denies_containers(&cont_req.containers);
+
                     match app_state {
+                        AppState::Authenticated if denied => {
+                            let (error_code, description) =
+                                ffi_error!(AuthError::from(IpcError::ContainersDenied));
+
+                            let resp = IpcMsg::Resp {
+                                resp: IpcResp::Containers(Err(IpcError::ContainersDenied)),
+                                req_id: req_id,
+                            };
+                            let resp = encode_response(&resp)?;
+
+                            Ok(Err((error_code, description, resp)))
+                        }
                         AppState::Authenticated => {
                             Ok(Ok(IpcMsg::Req {
                                 req_id: req_id,
@@ -117,6 +227,58 @@ pub fn decode_ipc_msg(
     }
 }
 
+// Checks the info of the app sending a request, see `AppExchangeInfo::validate`.
+fn validate_app(msg: &IpcMsg) -> Result<(), IpcError> {
+    match *msg {
+        IpcMsg::Req { req: IpcReq::Auth(ref req), .. } => req.app.validate(),
+        IpcMsg::Req { req: IpcReq::Containers(ref req), .. } => req.app.validate(),
+        IpcMsg::Req { req: IpcReq::ShareMData(ref req), .. } => req.app.validate(),
+        IpcMsg::Req { req: IpcReq::Combined(ref req), .. } => req.auth.app.validate(),
+        _ => Ok(()),
+    }
+}
+
+/// Checks that a request from an app registered with the authenticator carries
+/// a valid MAC, keyed with the app's encryption key. Requests without a MAC are
+/// only accepted if `legacy_allowed` is set. Auth and unregistered requests come
+/// from apps which don't have keys yet, so they aren't checked.
+pub fn verify_req_mac(
+    client: &Client<()>,
+    msg: &IpcMsg,
+    mac: Option<ReqMac>,
+    legacy_allowed: bool,
+) -> Box<AuthFuture<()>> {
+    let app_id = match *msg {
+        IpcMsg::Req { req: IpcReq::Containers(ref req), .. } => req.app.scoped_id(),
+        IpcMsg::Req { req: IpcReq::ShareMData(ref req), .. } => req.app.scoped_id(),
+        _ => return ok!(()),
+    };
+
+    config::get_app(client, &app_id)
+        .then(move |res| match (res, mac) {
+            (Ok(app), Some(mac)) => {
+                if mac.verify(&app.keys.enc_key) {
+                    Ok(())
+                } else {
+                    debug!("Rejecting IPC request with an invalid MAC from {}", app_id);
+                    Err(AuthError::IpcError(IpcError::InvalidMsg))
+                }
+            }
+            (Ok(_), None) => {
+                if legacy_allowed {
+                    Ok(())
+                } else {
+                    debug!("Rejecting IPC request without a MAC from {}", app_id);
+                    Err(AuthError::IpcError(IpcError::InvalidMsg))
+                }
+            }
+            // Requests from unknown apps are rejected once they're handled.
+            (Err(AuthError::IpcError(IpcError::UnknownApp)), _) => Ok(()),
+            (Err(e), _) => Err(e),
+        })
+        .into_box()
+}
+
 /// Updates containers permissions (adds a given key to the permissions set)
 pub fn update_container_perms(
     client: &Client<()>,
@@ -171,8 +333,182 @@ pub fn update_container_perms(
         .into_box()
 }
 
+/// Grants the user with the given key the requested permissions to the mutable data.
+pub fn share_mdata(
+    client: &Client<()>,
+    user: User,
+    mdata: Vec<ShareMData>,
+) -> Box<AuthFuture<()>> {
+    if mdata.is_empty() {
+        return ok!(());
+    }
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let num_mdata = mdata.len();
+
+    stream::iter_ok(mdata.into_iter())
+        .map(move |mdata| {
+            c2.get_mdata_shell(mdata.name, mdata.type_tag).map(
+                |md| (md.version(), mdata),
+            )
+        })
+        .buffer_unordered(num_mdata)
+        .map(move |(version, mdata)| {
+            c3.set_mdata_user_permissions(
+                mdata.name,
+                mdata.type_tag,
+                user,
+                mdata.perms,
+                version + 1,
+            )
+        })
+        .buffer_unordered(num_mdata)
+        .map_err(AuthError::CoreError)
+        .for_each(|()| Ok(()))
+        .into_box()
+}
+
+/// Grants a combined request: authenticates the app, then shares the requested
+/// mutable data with it. Ownership of the mutable data is verified first, so a
+/// request which can't be granted in full is rejected before anything changes.
+/// If sharing still fails, the app's registration is rolled back.
+pub fn grant_combined_req(client: &Client<()>, req: CombinedReq) -> Box<AuthFuture<AuthGranted>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+    let c6 = client.clone();
+    let CombinedReq { auth, mdata } = req;
+    let app_id = auth.app.scoped_id();
+    let share_req = ShareMDataReq {
+        app: auth.app.clone(),
+        mdata: mdata,
+    };
+
+    decode_share_mdata_req(client, &share_req)
+        .and_then(move |_| config::list_apps(&c2))
+        .and_then(move |(_, apps)| {
+            app_state(&c3, &apps, &app_id).map(move |state| (state, app_id))
+        })
+        .and_then(move |(prev_state, app_id)| {
+            app_auth::authenticate(&c4, auth).map(move |auth_granted| {
+                (auth_granted, prev_state, app_id)
+            })
+        })
+        .and_then(move |(auth_granted, prev_state, app_id)| {
+            let sign_pk = auth_granted.app_keys.sign_pk;
+            let shared: Vec<_> = share_req
+                .mdata
+                .iter()
+                .map(|mdata| (mdata.name, mdata.type_tag))
+                .collect();
+
+            share_mdata(&c5, User::Key(sign_pk), share_req.mdata)
+                .map(move |_| auth_granted)
+                .or_else(move |err| {
+                    rollback_combined_req(&c6, prev_state, app_id, sign_pk, shared).then(
+                        move |res| {
+                            if let Err(rollback_err) = res {
+                                debug!("Couldn't roll back a combined request: {:?}", rollback_err);
+                            }
+                            Err(err)
+                        },
+                    )
+                })
+        })
+        .into_box()
+}
+
+// Undoes what `grant_combined_req` has changed for an app whose data couldn't be
+// shared, returning it to `prev_state`. Already authenticated apps are left alone.
+fn rollback_combined_req(
+    client: &Client<()>,
+    prev_state: AppState,
+    app_id: String,
+    sign_pk: sign::PublicKey,
+    shared: Vec<(XorName, u64)>,
+) -> Box<AuthFuture<()>> {
+    if prev_state == AppState::Authenticated {
+        return ok!(());
+    }
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let c5 = client.clone();
+
+    unshare_mdata(client, User::Key(sign_pk), shared)
+        .and_then(move |()| revocation::revoke_app(&c2, &app_id).map(move |()| app_id))
+        .and_then(move |app_id| if prev_state == AppState::NotAuthenticated {
+            let f = app_container::remove(c3, &app_id)
+                .and_then(move |_| config::list_apps(&c4))
+                .and_then(move |(version, apps)| {
+                    config::remove_app(&c5, apps, config::next_version(version), &app_id)
+                })
+                .map(|_| ());
+            Either::A(f)
+        } else {
+            Either::B(future::ok(()))
+        })
+        .into_box()
+}
+
+// Removes the permissions of `user` from the given mutable data, if any.
+fn unshare_mdata(
+    client: &Client<()>,
+    user: User,
+    mdata: Vec<(XorName, u64)>,
+) -> Box<AuthFuture<()>> {
+    let reqs: Vec<_> = mdata
+        .into_iter()
+        .map(|(name, type_tag)| {
+            let c2 = client.clone();
+
+            client
+                .get_mdata_version(name, type_tag)
+                .and_then(move |version| {
+                    recovery::del_mdata_user_permissions(&c2, name, type_tag, user, version + 1)
+                })
+                .map_err(AuthError::from)
+        })
+        .collect();
+
+    future::join_all(reqs).map(|_| ()).into_box()
+}
+
+/// Remembers the protocol version the request was encoded with, so that the
+/// response to it is encoded with the same version by `encode_response`.
+pub fn record_req_version(msg: &IpcMsg, version: u32) {
+    let req_id = match *msg {
+        IpcMsg::Req { req_id, .. } => req_id,
+        _ => return,
+    };
+
+    let mut versions = REQ_VERSIONS.lock().unwrap_or_else(PoisonError::into_inner);
+    versions.retain(|&(id, _)| id != req_id);
+    if versions.len() >= MAX_REQ_VERSIONS {
+        let _ = versions.pop_front();
+    }
+    versions.push_back((req_id, version));
+}
+
+/// Encodes the message with the protocol version of the request it responds to,
+/// or with the current version if the request is unknown or it's not a response.
 pub fn encode_response(msg: &IpcMsg) -> Result<CString, IpcError> {
-    let resp = ipc::encode_msg(msg)?;
+    let version = match *msg {
+        IpcMsg::Resp { req_id, .. } => {
+            REQ_VERSIONS
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .find(|&&(id, _)| id == req_id)
+                .map_or(ipc::IPC_PROTOCOL_VERSION, |&(_, version)| version)
+        }
+        _ => ipc::IPC_PROTOCOL_VERSION,
+    };
+
+    let resp = ipc::encode_msg_with_version(msg, version)?;
     Ok(CString::new(resp).map_err(StringError::from)?)
 }
 
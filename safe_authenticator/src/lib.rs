@@ -41,17 +41,21 @@
                                    option_unwrap_used))]
 #![cfg_attr(feature="cargo-clippy", allow(implicit_hasher, too_many_arguments, use_debug))]
 
+extern crate chrono;
 extern crate config_file_handler;
 #[macro_use]
 extern crate ffi_utils;
 extern crate futures;
 #[macro_use]
+extern crate lazy_static;
+#[macro_use]
 extern crate log;
 extern crate maidsafe_utilities;
 extern crate routing;
 extern crate serde;
 #[macro_use]
 extern crate serde_derive;
+extern crate serde_json;
 extern crate rust_sodium;
 #[macro_use]
 extern crate safe_core;
@@ -67,17 +71,30 @@ pub mod ffi;
 
 pub use ffi::*;
 pub use ffi::apps::*;
+pub use ffi::devices::*;
 pub use ffi::ipc::*;
+pub use ffi::key_store::*;
 pub use ffi::logging::*;
+pub use ffi::policy::*;
+pub use ffi::public_id::*;
 
 mod access_container;
+mod account_deletion;
 mod app_auth;
 mod app_container;
 mod config;
+mod devices;
 mod errors;
+mod export;
 mod ipc;
+mod login_guard;
+mod pending;
+mod policy;
+mod public_id;
+mod replay;
 mod revocation;
 mod std_dirs;
+mod throttle;
 
 /// Provides utilities to test the authenticator functionality
 #[cfg(any(test, feature = "testing"))]
@@ -91,12 +108,20 @@ use futures::Future;
 use futures::stream::Stream;
 use futures::sync::mpsc;
 use maidsafe_utilities::thread::{self, Joiner};
+use replay::ReplayGuard;
 use safe_core::{Client, CoreError, CoreMsg, CoreMsgTx, FutureExt, NetworkEvent, NetworkTx,
                 event_loop};
 #[cfg(feature = "use-mock-routing")]
 use safe_core::MockRouting;
-use std::sync::Mutex;
-use std::sync::mpsc::sync_channel;
+use safe_core::ipc::{DecodedMsg, IPC_PROTOCOL_VERSION, IPC_STAMPED_PROTOCOL_VERSION, IpcError,
+                     IpcMsg};
+use safe_core::ipc::now_secs;
+use safe_core::utils::secret::SecretString;
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, sync_channel};
+use std::time::Duration;
+use throttle::AuthThrottle;
 use tokio_core::reactor::{Core, Handle};
 
 /// Future type specialised with `AuthError` as an error type
@@ -115,6 +140,12 @@ macro_rules! try_tx {
 pub struct Authenticator {
     /// Channel to communicate with the core event loop
     pub core_tx: Mutex<CoreMsgTx<()>>,
+    // Throttles auth requests coming in through IPC
+    auth_throttle: Mutex<AuthThrottle>,
+    // Rejects replayed or expired IPC requests
+    replay_guard: Mutex<ReplayGuard>,
+    // Whether to accept IPC requests without a stamp or a MAC
+    legacy_reqs_allowed: AtomicBool,
     _core_joiner: Joiner,
 }
 
@@ -125,10 +156,25 @@ impl Authenticator {
         F: FnOnce(&Client<()>) -> Option<Box<Future<Item = (), Error = ()>>> + Send + 'static,
     {
         let msg = CoreMsg::new(|client, _| f(client));
-        let core_tx = unwrap!(self.core_tx.lock());
+        let core_tx = self.core_tx.lock().unwrap_or_else(PoisonError::into_inner);
         core_tx.unbounded_send(msg).map_err(AuthError::from)
     }
 
+    /// Shut the authenticator down gracefully. Operations already sent to it
+    /// are given up to `timeout` to finish before the event loop is torn down.
+    /// The network requests of those still pending then are aborted, so they
+    /// fail with `OperationAborted`. Returns the number of operations abandoned
+    /// because they didn't finish even so.
+    pub fn shutdown(self, timeout: Duration) -> Result<usize, AuthError> {
+        let (tx, rx) = channel();
+        {
+            let core_tx = self.core_tx.lock().unwrap_or_else(PoisonError::into_inner);
+            core_tx.unbounded_send(CoreMsg::build_shutdown(timeout, tx))?;
+        }
+
+        Ok(rx.recv()?)
+    }
+
     /// Create a new account
     pub fn create_acc<S, N>(
         locator: S,
@@ -140,8 +186,8 @@ impl Authenticator {
         N: FnMut() + Send + 'static,
         S: Into<String>,
     {
-        let locator = locator.into();
-        let password = password.into();
+        let locator = SecretString::from(locator.into());
+        let password = SecretString::from(password.into());
         let invitation = invitation.into();
 
         Self::create_acc_impl(
@@ -182,6 +228,8 @@ impl Authenticator {
             el_h.spawn(net_obs_fut);
 
             let client = try_tx!(create_client_fn(el_h, core_tx.clone(), net_tx), tx);
+            #[cfg(feature = "lock-keys")]
+            let _ = client.lock_keys_in_memory();
 
             unwrap!(core_tx.unbounded_send(CoreMsg::new(move |client, &()| {
                 std_dirs::create(client)
@@ -213,28 +261,36 @@ impl Authenticator {
 
         Ok(Authenticator {
             core_tx: Mutex::new(core_tx),
+            auth_throttle: Mutex::new(AuthThrottle::new()),
+            replay_guard: Mutex::new(ReplayGuard::persistent()),
+            legacy_reqs_allowed: AtomicBool::new(false),
             _core_joiner: joiner,
         })
     }
 
-    /// Log in to an existing account
+    /// Log in to an existing account. After repeated failures because of wrong credentials,
+    /// further attempts fail with `LoginDelayed` until the delay has passed.
     pub fn login<S, N>(locator: S, password: S, disconnect_notifier: N) -> Result<Self, AuthError>
     where
         S: Into<String>,
         N: FnMut() + Send + 'static,
     {
 
-        let locator = locator.into();
-        let password = password.into();
+        let locator = SecretString::from(locator.into());
+        let password = SecretString::from(password.into());
 
-        Self::login_impl(
-            move |el_h, core_tx, net_tx| Client::login(&locator, &password, el_h, core_tx, net_tx),
-            disconnect_notifier,
-        )
+        login_guard::guard(locator, move |locator| {
+            Self::login_impl(
+                move |el_h, core_tx, net_tx| {
+                    Client::login(&locator, &password, el_h, core_tx, net_tx)
+                },
+                disconnect_notifier,
+            )
+        })
     }
 
-    /// Log in to an existing account
-    pub fn login_impl<F: Send + 'static, N>(
+    // Log in to an existing account, bypassing the login guard.
+    fn login_impl<F: Send + 'static, N>(
         create_client_fn: F,
         mut disconnect_notifier: N,
     ) -> Result<Self, AuthError>
@@ -263,6 +319,8 @@ impl Authenticator {
             el_h.spawn(net_obs_fut);
 
             let client = try_tx!(create_client_fn(el_h, core_tx_clone, net_tx), tx);
+            #[cfg(feature = "lock-keys")]
+            let _ = client.lock_keys_in_memory();
 
             if !try_tx!(client.std_dirs_created(), tx) {
                 // Standard directories haven't been created during
@@ -283,7 +341,22 @@ impl Authenticator {
                         .into()
                 })));
             } else {
-                unwrap!(tx.send(Ok(core_tx)));
+                // Standard directories introduced after the account had been created are
+                // added now. The account stays usable without them, so login doesn't fail.
+                let core_tx2 = core_tx.clone();
+
+                unwrap!(core_tx.unbounded_send(CoreMsg::new(move |client, &()| {
+                    std_dirs::add_missing(client)
+                        .then(move |res| {
+                            if let Err(e) = res {
+                                warn!("Could not add missing standard directories: {:?}", e);
+                            }
+                            unwrap!(tx.send(Ok(core_tx2)));
+                            Ok(())
+                        })
+                        .into_box()
+                        .into()
+                })));
             }
 
             event_loop::run(el, &client, &(), core_rx);
@@ -301,6 +374,9 @@ impl Authenticator {
 
         Ok(Authenticator {
             core_tx: Mutex::new(core_tx),
+            auth_throttle: Mutex::new(AuthThrottle::new()),
+            replay_guard: Mutex::new(ReplayGuard::persistent()),
+            legacy_reqs_allowed: AtomicBool::new(false),
             _core_joiner: joiner,
         })
     }
@@ -321,8 +397,8 @@ impl Authenticator {
         N: FnMut() + Send + 'static,
     {
 
-        let locator = locator.into();
-        let password = password.into();
+        let locator = SecretString::from(locator.into());
+        let password = SecretString::from(password.into());
 
         Self::login_impl(
             move |el_h, core_tx, net_tx| {
@@ -352,8 +428,8 @@ impl Authenticator {
         F: Fn(MockRouting) -> MockRouting + Send + 'static,
         S: Into<String>,
     {
-        let locator = locator.into();
-        let password = password.into();
+        let locator = SecretString::from(locator.into());
+        let password = SecretString::from(password.into());
         let invitation = invitation.into();
 
         Self::create_acc_impl(
@@ -373,11 +449,74 @@ impl Authenticator {
     }
 }
 
+impl Authenticator {
+    // Records an auth request from the given app. Returns `Err` with the remaining
+    // back-off period if the app is sending requests too often.
+    fn check_auth_throttle(&self, app_id: &str) -> Result<(), Duration> {
+        let res = self.auth_throttle
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .check(app_id);
+        if let Err(backoff) = res {
+            debug!(
+                "Throttling auth requests from {} for {} s",
+                app_id,
+                backoff.as_secs()
+            );
+        }
+        res
+    }
+
+    // Records the stamp of an incoming IPC request. Fails with `RequestExpired`
+    // if the request has expired or is being replayed. Requests from apps using
+    // an older version of the protocol don't carry a stamp and are rejected
+    // with `UnsupportedVersion`, unless legacy requests are allowed.
+    fn check_req_stamp(&self, decoded: &DecodedMsg) -> Result<(), IpcError> {
+        match decoded.msg {
+            IpcMsg::Req { .. } => (),
+            _ => return Ok(()),
+        }
+
+        match decoded.stamp {
+            Some(ref stamp) => {
+                let mut replay_guard = self.replay_guard.lock().unwrap_or_else(
+                    PoisonError::into_inner,
+                );
+                if replay_guard.check(stamp, now_secs()) {
+                    Ok(())
+                } else {
+                    debug!("Rejecting expired or replayed IPC request: {:?}", stamp);
+                    Err(IpcError::RequestExpired)
+                }
+            }
+            None if self.legacy_reqs_allowed() => Ok(()),
+            None => {
+                debug!("Rejecting unstamped IPC request (v{})", decoded.version);
+                Err(IpcError::UnsupportedVersion {
+                    requested: decoded.version,
+                    supported: (IPC_STAMPED_PROTOCOL_VERSION, IPC_PROTOCOL_VERSION),
+                })
+            }
+        }
+    }
+
+    /// Allow IPC requests from apps which predate replay protection: requests
+    /// without a stamp, and requests from registered apps without a MAC.
+    /// They are rejected by default.
+    pub fn set_legacy_reqs_allowed(&self, allowed: bool) {
+        self.legacy_reqs_allowed.store(allowed, Ordering::SeqCst);
+    }
+
+    fn legacy_reqs_allowed(&self) -> bool {
+        self.legacy_reqs_allowed.load(Ordering::SeqCst)
+    }
+}
+
 impl Drop for Authenticator {
     fn drop(&mut self) {
         debug!("Authenticator is now being dropped.");
 
-        let core_tx = unwrap!(self.core_tx.lock());
+        let core_tx = self.core_tx.lock().unwrap_or_else(PoisonError::into_inner);
         let msg = CoreMsg::build_terminator();
 
         if let Err(e) = core_tx.unbounded_send(msg) {
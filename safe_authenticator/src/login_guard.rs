@@ -0,0 +1,304 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Protection of the login against guessing of the credentials.
+//!
+//! Failed login attempts are counted per account locator. Once an account has failed
+//! `FREE_LOGIN_ATTEMPTS` times in a row, further attempts are refused without contacting
+//! the network until a delay has passed, which doubles with every further failure. A
+//! successful login resets the count. Attempts in progress count as failures until they
+//! complete, so concurrent attempts can't get around the delay. Accounts are kept track of
+//! by the hash of their locator, so the locators themselves aren't retained.
+
+use errors::AuthError;
+use routing::ClientError;
+use safe_core::CoreError;
+use safe_core::utils::secret::SecretString;
+use std::cmp;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::{Duration, Instant};
+use tiny_keccak::sha3_256;
+
+/// Number of consecutive failed attempts allowed without any delay.
+pub const FREE_LOGIN_ATTEMPTS: u32 = 3;
+/// Delay imposed after `FREE_LOGIN_ATTEMPTS` failures.
+pub const INITIAL_LOGIN_DELAY_SECS: u64 = 5;
+/// Upper bound of the delay.
+pub const MAX_LOGIN_DELAY_SECS: u64 = 15 * 60;
+/// Failures are forgotten once there has been none for this long.
+pub const LOGIN_FAILURE_EXPIRY_SECS: u64 = 24 * 60 * 60;
+
+/// Function notified of failed login attempts, with the number of consecutive failures of
+/// the account and the delay before the next attempt is allowed.
+pub type LoginObserver = Box<Fn(u32, Duration) + Send>;
+
+lazy_static! {
+    static ref LOGIN_GUARD: Mutex<LoginGuard> = Mutex::new(LoginGuard::new());
+    // Shared, so it can be called without holding this lock, which the observer may need,
+    // e.g. to replace itself. It's only `Send`, hence the lock of its own.
+    static ref LOGIN_OBSERVER: Mutex<Option<Arc<Mutex<LoginObserver>>>> = Mutex::new(None);
+}
+
+/// Runs `login` with the account locator, unless it's attempted too soon after failed
+/// logins to the account, in which case it fails with `LoginDelayed`. Failures caused by
+/// wrong credentials are counted and reported to the observer.
+pub fn guard<T, F>(locator: SecretString, login: F) -> Result<T, AuthError>
+where
+    F: FnOnce(SecretString) -> Result<T, AuthError>,
+{
+    let key = sha3_256(locator.as_bytes());
+
+    if let Err(delay) = lock(&LOGIN_GUARD).reserve_at(key, Instant::now()) {
+        return Err(AuthError::LoginDelayed(secs_rounded_up(delay)));
+    }
+
+    let res = login(locator);
+    match res {
+        Ok(_) => lock(&LOGIN_GUARD).record_success(&key),
+        Err(ref error) if is_credentials_failure(error) => {
+            let (failures, delay) = lock(&LOGIN_GUARD).record_failure_at(key, Instant::now());
+            debug!(
+                "Failed login attempt {}, next one allowed in {} s",
+                failures,
+                delay.as_secs()
+            );
+            let observer = lock(&LOGIN_OBSERVER).clone();
+            if let Some(observer) = observer {
+                (*lock(&observer))(failures, delay);
+            }
+        }
+        Err(_) => lock(&LOGIN_GUARD).release(&key),
+    }
+    res
+}
+
+/// Sets the function to notify of failed login attempts, replacing the one set before.
+pub fn set_observer(observer: Option<LoginObserver>) {
+    *lock(&LOGIN_OBSERVER) = observer.map(|observer| Arc::new(Mutex::new(observer)));
+}
+
+/// Keeps track of consecutive failed logins per account.
+#[derive(Default)]
+pub struct LoginGuard {
+    accounts: HashMap<[u8; 32], Failures>,
+}
+
+struct Failures {
+    count: u32,
+    // Attempts in progress.
+    pending: u32,
+    // Time of the last failure or attempt.
+    last: Instant,
+}
+
+impl LoginGuard {
+    /// Create a guard with no failures recorded.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    // Returns `Err` with the remaining delay if the account can't be logged into yet.
+    // Otherwise records an attempt in progress, which counts as a failure until it's
+    // completed by `record_success`, `record_failure_at` or `release`.
+    fn reserve_at(&mut self, key: [u8; 32], now: Instant) -> Result<(), Duration> {
+        self.expire(now);
+
+        let failures = self.accounts.entry(key).or_insert(Failures {
+            count: 0,
+            pending: 0,
+            last: now,
+        });
+        let allowed_at = failures.last + delay(failures.count + failures.pending);
+        if now < allowed_at {
+            return Err(allowed_at - now);
+        }
+
+        failures.pending += 1;
+        failures.last = now;
+        Ok(())
+    }
+
+    // Records a failed login. Returns the number of consecutive failures and the delay
+    // before the next attempt.
+    fn record_failure_at(&mut self, key: [u8; 32], now: Instant) -> (u32, Duration) {
+        let failures = self.accounts.entry(key).or_insert(Failures {
+            count: 0,
+            pending: 0,
+            last: now,
+        });
+        failures.count += 1;
+        failures.pending = failures.pending.saturating_sub(1);
+        failures.last = now;
+        (failures.count, delay(failures.count))
+    }
+
+    fn record_success(&mut self, key: &[u8; 32]) {
+        let _ = self.accounts.remove(key);
+    }
+
+    // Completes an attempt which failed for reasons other than the credentials.
+    fn release(&mut self, key: &[u8; 32]) {
+        let remove = match self.accounts.get_mut(key) {
+            Some(failures) => {
+                failures.pending = failures.pending.saturating_sub(1);
+                failures.count == 0 && failures.pending == 0
+            }
+            None => false,
+        };
+        if remove {
+            let _ = self.accounts.remove(key);
+        }
+    }
+
+    fn expire(&mut self, now: Instant) {
+        let expiry = Duration::from_secs(LOGIN_FAILURE_EXPIRY_SECS);
+        self.accounts.retain(|_, failures| {
+            failures.pending > 0 || now.duration_since(failures.last) < expiry
+        });
+    }
+}
+
+// Delay imposed after the given number of consecutive failures.
+fn delay(failures: u32) -> Duration {
+    if failures < FREE_LOGIN_ATTEMPTS {
+        return Duration::from_secs(0);
+    }
+    // Capped so the shift can't overflow; the delay reaches the maximum well before.
+    let doublings = cmp::min(failures - FREE_LOGIN_ATTEMPTS, 16);
+    Duration::from_secs(cmp::min(
+        INITIAL_LOGIN_DELAY_SECS << doublings,
+        MAX_LOGIN_DELAY_SECS,
+    ))
+}
+
+// Whether the login failed because of wrong credentials, as opposed to e.g. a network error.
+fn is_credentials_failure(error: &AuthError) -> bool {
+    match *error {
+        AuthError::CoreError(CoreError::SymmetricDecipherFailure) |
+        AuthError::CoreError(CoreError::RoutingClientError(ClientError::NoSuchAccount)) |
+        AuthError::CoreError(CoreError::RoutingClientError(ClientError::NoSuchData)) => true,
+        _ => false,
+    }
+}
+
+fn secs_rounded_up(duration: Duration) -> u64 {
+    duration.as_secs() + if duration.subsec_nanos() > 0 { 1 } else { 0 }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that logins get delayed after repeated failures, with the delay growing with
+    // every further failure, and that a success resets it.
+    #[test]
+    fn delay_and_reset() {
+        let mut guard = LoginGuard::new();
+        let key = sha3_256(b"locator");
+        let start = Instant::now();
+
+        for attempt in 1..FREE_LOGIN_ATTEMPTS {
+            unwrap!(guard.reserve_at(key, start));
+            assert_eq!(
+                guard.record_failure_at(key, start),
+                (attempt, Duration::from_secs(0))
+            );
+        }
+
+        let initial = Duration::from_secs(INITIAL_LOGIN_DELAY_SECS);
+        unwrap!(guard.reserve_at(key, start));
+        assert_eq!(
+            guard.record_failure_at(key, start),
+            (FREE_LOGIN_ATTEMPTS, initial)
+        );
+
+        // Other accounts are not affected.
+        unwrap!(guard.reserve_at(sha3_256(b"other"), start));
+
+        let later = start + Duration::from_secs(1);
+        assert_eq!(
+            guard.reserve_at(key, later),
+            Err(initial - Duration::from_secs(1))
+        );
+
+        // Allowed again once the delay has passed, but another failure doubles it.
+        let after = start + initial;
+        unwrap!(guard.reserve_at(key, after));
+        assert_eq!(
+            guard.record_failure_at(key, after),
+            (FREE_LOGIN_ATTEMPTS + 1, initial * 2)
+        );
+        assert_eq!(guard.reserve_at(key, after), Err(initial * 2));
+
+        // The delay never exceeds the maximum.
+        assert_eq!(delay(100), Duration::from_secs(MAX_LOGIN_DELAY_SECS));
+
+        guard.record_success(&key);
+        unwrap!(guard.reserve_at(key, after));
+    }
+
+    // Test that attempts in progress count as failures, so they can't be made concurrently
+    // to get around the delay.
+    #[test]
+    fn concurrent_attempts() {
+        let mut guard = LoginGuard::new();
+        let key = sha3_256(b"locator");
+        let start = Instant::now();
+
+        for _ in 0..FREE_LOGIN_ATTEMPTS {
+            unwrap!(guard.reserve_at(key, start));
+        }
+        assert_eq!(
+            guard.reserve_at(key, start),
+            Err(Duration::from_secs(INITIAL_LOGIN_DELAY_SECS))
+        );
+
+        // Attempts failing for other reasons than the credentials don't count once done.
+        for _ in 0..FREE_LOGIN_ATTEMPTS {
+            guard.release(&key);
+        }
+        unwrap!(guard.reserve_at(key, start));
+    }
+
+    // Test that only failures caused by wrong credentials are counted.
+    #[test]
+    fn credentials_failures() {
+        let locator = |locator: &str| SecretString::from(locator.to_owned());
+        let wrong_password = |_| {
+            Err::<(), _>(AuthError::CoreError(CoreError::SymmetricDecipherFailure))
+        };
+        let network_error = |_| Err::<(), _>(AuthError::CoreError(CoreError::RequestTimeout));
+
+        for _ in 0..FREE_LOGIN_ATTEMPTS * 2 {
+            let _ = guard(locator("network-error"), &network_error);
+        }
+        unwrap!(guard(locator("network-error"), |_| Ok(())));
+
+        for _ in 0..FREE_LOGIN_ATTEMPTS {
+            let _ = guard(locator("wrong-password"), &wrong_password);
+        }
+        match guard(locator("wrong-password"), |_| Ok(())) {
+            Err(AuthError::LoginDelayed(secs)) => assert_eq!(secs, INITIAL_LOGIN_DELAY_SECS),
+            res => panic!("Unexpected {:?}", res),
+        }
+    }
+}
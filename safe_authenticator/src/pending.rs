@@ -0,0 +1,232 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Queue of auth requests waiting for the user's decision.
+//!
+//! Requests which arrive while there's no UI to prompt the user are persisted
+//! in the authenticator config, so they can be answered later.
+
+use super::{AuthError, AuthFuture};
+use config;
+use futures::Future;
+use safe_core::{Client, FutureExt};
+use safe_core::ipc::req::AuthReq;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::Rc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of seconds after which a pending request expires.
+pub const PENDING_REQUEST_EXPIRY_SECS: u64 = 24 * 60 * 60;
+
+/// Auth request waiting for the user's decision.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PendingRequest {
+    /// The request itself
+    pub req: AuthReq,
+    /// Time (in seconds since the Unix epoch) after which the request expires
+    pub expires_at: u64,
+}
+
+/// Maps from a request ID to the pending request
+pub type PendingRequests = BTreeMap<u32, PendingRequest>;
+
+/// Adds the request to the queue. Expired requests are dropped in the process.
+pub fn push(client: &Client<()>, req_id: u32, req: AuthReq) -> Box<AuthFuture<()>> {
+    let now = now();
+    let pending = PendingRequest {
+        req: req,
+        expires_at: now + PENDING_REQUEST_EXPIRY_SECS,
+    };
+
+    update(client, move |requests| {
+        let _ = remove_expired(requests, now);
+        let _ = requests.insert(req_id, pending.clone());
+        true
+    })
+}
+
+/// Returns requests which haven't expired yet, ordered by request ID.
+pub fn list(client: &Client<()>) -> Box<AuthFuture<Vec<(u32, AuthReq)>>> {
+    let now = now();
+
+    config::get_pending_requests(client)
+        .map(move |(_, requests)| {
+            requests
+                .into_iter()
+                .filter(|&(_, ref pending)| pending.expires_at > now)
+                .map(|(req_id, pending)| (req_id, pending.req))
+                .collect()
+        })
+        .into_box()
+}
+
+/// Removes the request from the queue and returns it. Expired requests are dropped in
+/// the process. Of concurrent calls for the same request, only one returns it.
+/// Fails with `NoSuchPendingRequest` if the request is not queued or has expired.
+pub fn take(client: &Client<()>, req_id: u32) -> Box<AuthFuture<AuthReq>> {
+    let c2 = client.clone();
+    let now = now();
+    // Set by the attempt to store the queue without the request, so it's only returned
+    // if it was this call which removed it.
+    let taken = Rc::new(RefCell::new(None));
+    let taken2 = Rc::clone(&taken);
+
+    config::get_pending_requests(client)
+        .and_then(move |(version, requests)| {
+            config::update_pending_requests(
+                &c2,
+                requests,
+                config::next_version(version),
+                move |requests| {
+                    let removed = remove_expired(requests, now);
+                    let pending = requests.remove(&req_id);
+                    let changed = pending.is_some() || removed;
+                    *taken2.borrow_mut() = pending;
+                    changed
+                },
+            )
+        })
+        .and_then(move |_| match taken.borrow_mut().take() {
+            Some(pending) => Ok(pending.req),
+            None => Err(AuthError::NoSuchPendingRequest(req_id)),
+        })
+        .into_box()
+}
+
+fn update<F>(client: &Client<()>, f: F) -> Box<AuthFuture<()>>
+where
+    F: Fn(&mut PendingRequests) -> bool + 'static,
+{
+    let c2 = client.clone();
+
+    config::get_pending_requests(client)
+        .and_then(move |(version, requests)| {
+            config::update_pending_requests(&c2, requests, config::next_version(version), f)
+        })
+        .map(|_| ())
+        .into_box()
+}
+
+fn remove_expired(requests: &mut PendingRequests, now: u64) -> bool {
+    let expired: Vec<_> = requests
+        .iter()
+        .filter(|&(_, pending)| pending.expires_at <= now)
+        .map(|(req_id, _)| *req_id)
+        .collect();
+
+    for req_id in &expired {
+        let _ = requests.remove(req_id);
+    }
+
+    !expired.is_empty()
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use test_utils::{create_account_and_login, rand_app, run, try_run};
+
+    fn rand_req() -> AuthReq {
+        AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: HashMap::new(),
+        }
+    }
+
+    // Test queueing, listing and taking pending requests.
+    #[test]
+    fn push_list_take() {
+        let auth = create_account_and_login();
+        let req0 = rand_req();
+        let req1 = rand_req();
+
+        let (r0, r1) = (req0.clone(), req1.clone());
+        run(&auth, move |client| {
+            let c2 = client.clone();
+            push(client, 1, r1).and_then(move |_| push(&c2, 0, r0))
+        });
+
+        let requests = run(&auth, list);
+        assert_eq!(requests, vec![(0, req0.clone()), (1, req1.clone())]);
+
+        let req = run(&auth, |client| take(client, 0));
+        assert_eq!(req, req0);
+
+        let requests = run(&auth, list);
+        assert_eq!(requests, vec![(1, req1)]);
+
+        let res = try_run(&auth, |client| take(client, 0));
+        assert_match!(res, Err(AuthError::NoSuchPendingRequest(0)));
+    }
+
+    // Test that of concurrent takes of the same request only one returns it.
+    #[test]
+    fn concurrent_take() {
+        let auth = create_account_and_login();
+        let req = rand_req();
+
+        let r2 = req.clone();
+        run(&auth, move |client| push(client, 0, r2));
+
+        let (res0, res1) = run(&auth, |client| {
+            let take0 = take(client, 0).then(Ok::<_, AuthError>);
+            let take1 = take(client, 0).then(Ok::<_, AuthError>);
+            take0.join(take1)
+        });
+
+        match (res0, res1) {
+            (Ok(taken), Err(AuthError::NoSuchPendingRequest(0))) |
+            (Err(AuthError::NoSuchPendingRequest(0)), Ok(taken)) => assert_eq!(taken, req),
+            x => panic!("Unexpected {:?}", x),
+        }
+        assert!(run(&auth, list).is_empty());
+    }
+
+    // Test that expired requests are not returned.
+    #[test]
+    fn expiry() {
+        let mut requests = PendingRequests::new();
+        let _ = requests.insert(
+            0,
+            PendingRequest {
+                req: rand_req(),
+                expires_at: 10,
+            },
+        );
+        let _ = requests.insert(
+            1,
+            PendingRequest {
+                req: rand_req(),
+                expires_at: 20,
+            },
+        );
+
+        assert!(!remove_expired(&mut requests, 5));
+        assert!(remove_expired(&mut requests, 10));
+        assert_eq!(requests.keys().cloned().collect::<Vec<_>>(), vec![1]);
+    }
+}
@@ -0,0 +1,178 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Authorisation policy.
+//!
+//! A set of rules, stored in the authenticator config, which allows to grant or
+//! deny some authorisation requests without prompting the user.
+
+use super::{AuthError, AuthFuture};
+use config;
+use futures::Future;
+use safe_core::{Client, FutureExt};
+use safe_core::ipc::req::{AuthReq, ContainerPermissions, Permission};
+use std::collections::{BTreeSet, HashMap};
+
+/// Rules used to decide on authorisation requests automatically.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct AuthPolicy {
+    /// IDs of apps which are always granted access.
+    pub allowed_apps: BTreeSet<String>,
+    /// Requests for any of these containers are always denied.
+    pub denied_containers: BTreeSet<String>,
+    /// Grant requests which ask for read-only access to containers.
+    pub grant_read_only: bool,
+}
+
+/// Decision made by the policy on an authorisation request.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Decision {
+    /// Grant the request without prompting the user.
+    Grant,
+    /// Deny the request without prompting the user.
+    Deny,
+    /// Let the user decide.
+    Prompt,
+}
+
+impl AuthPolicy {
+    /// Evaluates the policy against the given request. Denials take precedence over grants.
+    pub fn evaluate(&self, req: &AuthReq) -> Decision {
+        if self.denies_containers(&req.containers) {
+            return Decision::Deny;
+        }
+
+        if self.allowed_apps.contains(&req.app.id) {
+            return Decision::Grant;
+        }
+
+        // Dedicated app container always comes with full access.
+        let read_only = !req.app_container &&
+            req.containers.values().all(|perms| {
+                perms.iter().all(|perm| *perm == Permission::Read)
+            });
+
+        if self.grant_read_only && read_only {
+            Decision::Grant
+        } else {
+            Decision::Prompt
+        }
+    }
+
+    /// Returns true if access to any of the given containers is denied. Applies to
+    /// requests for additional containers by authenticated apps too.
+    pub fn denies_containers(&self, containers: &HashMap<String, ContainerPermissions>) -> bool {
+        containers.keys().any(
+            |name| self.denied_containers.contains(name),
+        )
+    }
+}
+
+/// Adds the app to (or removes it from) the list of apps which are always granted access.
+pub fn set_app_allowed(client: &Client<()>, app_id: String, allowed: bool) -> Box<AuthFuture<()>> {
+    update(client, move |policy| if allowed {
+        policy.allowed_apps.insert(app_id.clone())
+    } else {
+        policy.allowed_apps.remove(&app_id)
+    })
+}
+
+/// Adds the container to (or removes it from) the list of containers requests for
+/// which are always denied.
+pub fn set_container_denied(
+    client: &Client<()>,
+    container: String,
+    denied: bool,
+) -> Box<AuthFuture<()>> {
+    update(client, move |policy| if denied {
+        policy.denied_containers.insert(container.clone())
+    } else {
+        policy.denied_containers.remove(&container)
+    })
+}
+
+/// Enables or disables automatic granting of read-only requests.
+pub fn set_grant_read_only(client: &Client<()>, enabled: bool) -> Box<AuthFuture<()>> {
+    update(client, move |policy| if policy.grant_read_only != enabled {
+        policy.grant_read_only = enabled;
+        true
+    } else {
+        false
+    })
+}
+
+fn update<F>(client: &Client<()>, f: F) -> Box<AuthFuture<()>>
+where
+    F: Fn(&mut AuthPolicy) -> bool + 'static,
+{
+    let c2 = client.clone();
+
+    config::get_auth_policy(client)
+        .and_then(move |(version, policy)| {
+            config::update_auth_policy(&c2, policy, config::next_version(version), f)
+        })
+        .map(|_| ())
+        .map_err(AuthError::from)
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use test_utils::rand_app;
+
+    fn auth_req(containers: HashMap<String, BTreeSet<Permission>>) -> AuthReq {
+        AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: containers,
+        }
+    }
+
+    // Test the order in which the rules are evaluated.
+    #[test]
+    fn evaluate() {
+        let mut containers = HashMap::new();
+        let _ = containers.insert("_documents".to_owned(), btree_set![Permission::Read]);
+        let req = auth_req(containers);
+
+        let mut policy = AuthPolicy::default();
+        assert_eq!(policy.evaluate(&req), Decision::Prompt);
+
+        policy.grant_read_only = true;
+        assert_eq!(policy.evaluate(&req), Decision::Grant);
+
+        let mut write_req = req.clone();
+        let _ = write_req.containers.insert(
+            "_music".to_owned(),
+            btree_set![Permission::Insert],
+        );
+        assert_eq!(policy.evaluate(&write_req), Decision::Prompt);
+
+        let mut app_cont_req = req.clone();
+        app_cont_req.app_container = true;
+        assert_eq!(policy.evaluate(&app_cont_req), Decision::Prompt);
+
+        let _ = policy.allowed_apps.insert(write_req.app.id.clone());
+        assert_eq!(policy.evaluate(&write_req), Decision::Grant);
+
+        let _ = policy.denied_containers.insert("_music".to_owned());
+        assert_eq!(policy.evaluate(&write_req), Decision::Deny);
+        assert_eq!(policy.evaluate(&req), Decision::Grant);
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Public ID management.
+//!
+//! A public ID is a public `MutableData` stored at a well-known location (the SHA-3
+//! hash of the public name, with the `PUBLIC_ID_TAG` type tag). It contains a single
+//! entry pointing to the services `MutableData`, which maps service names to their
+//! locations. Public IDs owned by the user are recorded in the `_publicNames`
+//! standard container.
+
+use super::{AuthError, AuthFuture};
+use access_container;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryActions, XorName};
+use safe_core::{Client, CoreError, FutureExt, MDataInfo, PUBLIC_ID_TAG, mdata_info,
+                public_id_name};
+use safe_core::dns::{self, SERVICES_ENTRY_KEY};
+
+/// Name of the standard container which lists public IDs of the user.
+pub const PUBLIC_NAMES_CONTAINER: &str = "_publicNames";
+
+/// Fetches `MDataInfo` of the `_publicNames` container.
+fn public_names_container(client: &Client<()>) -> Box<AuthFuture<MDataInfo>> {
+    access_container::fetch_authenticator_entry(client)
+        .and_then(|(_, mut containers)| {
+            containers.remove(PUBLIC_NAMES_CONTAINER).ok_or_else(|| {
+                AuthError::from(format!(
+                    "'{}' not found in the access container",
+                    PUBLIC_NAMES_CONTAINER
+                ))
+            })
+        })
+        .into_box()
+}
+
+/// Claims a new public ID and creates an empty services `MutableData` for it.
+/// Fails with `DataExists` if the public name is already taken.
+/// Returns `MDataInfo` of the services container.
+pub fn create(client: &Client<()>, public_name: String) -> Box<AuthFuture<MDataInfo>> {
+    let c2 = client.clone();
+
+    dns::register(client, &public_name)
+        .map_err(AuthError::from)
+        .and_then(move |services| {
+            public_names_container(&c2).and_then(move |container| {
+                let key = container.enc_entry_key(public_name.as_bytes())?;
+                let value = container.enc_entry_value(&serialise(&services)?)?;
+                Ok((container, key, value, services))
+            })
+                .and_then(move |(container, key, value, services)| {
+                    c2.mutate_mdata_entries(
+                        container.name,
+                        container.type_tag,
+                        EntryActions::new().ins(key, value, 0).into(),
+                    ).map_err(AuthError::from)
+                        .map(move |_| services)
+                })
+        })
+        .into_box()
+}
+
+/// Lists public IDs owned by the user.
+pub fn list(client: &Client<()>) -> Box<AuthFuture<Vec<String>>> {
+    let c2 = client.clone();
+
+    public_names_container(client)
+        .and_then(move |container| {
+            c2.list_mdata_entries(container.name, container.type_tag)
+                .map_err(AuthError::from)
+                .and_then(move |entries| {
+                    let entries = mdata_info::decrypt_entries(&container, &entries)?;
+                    let mut names = Vec::with_capacity(entries.len());
+
+                    for (key, value) in entries {
+                        // Empty entry means the public ID has been deleted.
+                        if !value.content.is_empty() {
+                            names.push(String::from_utf8(key)?);
+                        }
+                    }
+
+                    names.sort();
+                    Ok(names)
+                })
+        })
+        .into_box()
+}
+
+/// Gets `MDataInfo` of the services container of the given public ID.
+pub fn fetch_services(client: &Client<()>, public_name: &str) -> Box<AuthFuture<MDataInfo>> {
+    let c2 = client.clone();
+    let public_name = public_name.to_owned();
+
+    public_names_container(client)
+        .and_then(move |container| {
+            let key = container.enc_entry_key(public_name.as_bytes())?;
+            Ok((container, key))
+        })
+        .and_then(move |(container, key)| {
+            c2.get_mdata_value(container.name, container.type_tag, key)
+                .map_err(AuthError::from)
+                .and_then(move |value| {
+                    if value.content.is_empty() {
+                        return Err(AuthError::from(
+                            CoreError::RoutingClientError(ClientError::NoSuchEntry),
+                        ));
+                    }
+                    let plaintext = container.decrypt(&value.content)?;
+                    Ok(deserialise(&plaintext)?)
+                })
+        })
+        .into_box()
+}
+
+/// Deletes the given public ID.
+///
+/// `MutableData` can't be removed from the network, so the public ID stays owned
+/// by the user, but its services pointer is removed and it's no longer listed.
+pub fn delete(client: &Client<()>, public_name: &str) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let name = public_id_name(public_name);
+    let public_name = public_name.to_owned();
+
+    public_names_container(client)
+        .and_then(move |container| {
+            let key = container.enc_entry_key(public_name.as_bytes())?;
+            Ok((container, key))
+        })
+        .and_then(move |(container, key)| {
+            c2.get_mdata_value(container.name, container.type_tag, key.clone())
+                .and_then(move |value| {
+                    c2.mutate_mdata_entries(
+                        container.name,
+                        container.type_tag,
+                        EntryActions::new().del(key, value.entry_version + 1).into(),
+                    )
+                })
+                .map_err(AuthError::from)
+        })
+        .and_then(move |_| delete_services_pointer(&c3, name))
+        .into_box()
+}
+
+fn delete_services_pointer(client: &Client<()>, name: XorName) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+    let key = SERVICES_ENTRY_KEY.to_vec();
+
+    client
+        .get_mdata_value(name, PUBLIC_ID_TAG, key.clone())
+        .and_then(move |value| {
+            c2.mutate_mdata_entries(
+                name,
+                PUBLIC_ID_TAG,
+                EntryActions::new().del(key, value.entry_version + 1).into(),
+            )
+        })
+        .or_else(|error| match error {
+            CoreError::RoutingClientError(ClientError::NoSuchEntry) => Ok(()),
+            error => Err(error),
+        })
+        .map_err(AuthError::from)
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use safe_core::SERVICES_TAG;
+    use safe_core::utils::generate_random_string;
+    use test_utils::{create_account_and_login, run, try_run};
+
+    // Test creating, listing and deleting public IDs.
+    #[test]
+    fn create_list_delete() {
+        let auth = create_account_and_login();
+        let name0 = unwrap!(generate_random_string(10));
+        let name1 = unwrap!(generate_random_string(10));
+
+        let (n0, n1) = (name0.clone(), name1.clone());
+        let services = run(&auth, move |client| {
+            let c2 = client.clone();
+            create(client, n0).and_then(move |services| {
+                create(&c2, n1).map(move |_| services)
+            })
+        });
+
+        let mut expected = vec![name0.clone(), name1.clone()];
+        expected.sort();
+        let names = run(&auth, list);
+        assert_eq!(names, expected);
+
+        // The services container must be reachable through the public ID.
+        let n0 = name0.clone();
+        let fetched = run(&auth, move |client| fetch_services(client, &n0));
+        assert_eq!(fetched.name, services.name);
+        assert_eq!(fetched.type_tag, SERVICES_TAG);
+
+        let n0 = name0.clone();
+        run(&auth, move |client| delete(client, &n0));
+
+        let names = run(&auth, list);
+        assert_eq!(names, vec![name1]);
+
+        let res = try_run(&auth, move |client| fetch_services(client, &name0));
+        assert_match!(
+            res,
+            Err(AuthError::CoreError(CoreError::RoutingClientError(ClientError::NoSuchEntry)))
+        );
+    }
+
+    // Test that a public name can't be claimed twice.
+    #[test]
+    fn create_existing() {
+        let auth = create_account_and_login();
+        let name = unwrap!(generate_random_string(10));
+
+        let n2 = name.clone();
+        let _ = run(&auth, move |client| create(client, n2));
+
+        let auth = create_account_and_login();
+        let res = try_run(&auth, move |client| create(client, name));
+        assert_match!(
+            res,
+            Err(AuthError::CoreError(CoreError::RoutingClientError(ClientError::DataExists)))
+        );
+    }
+}
@@ -0,0 +1,170 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Protection of incoming IPC requests against replays.
+//!
+//! Every request carries a `ReqStamp` with a random nonce and an expiry time.
+//! Expired requests are rejected, and so are requests whose nonce has already
+//! been seen. Nonces only need to be remembered until their request expires,
+//! but they are kept in a local file so that restarting the authenticator
+//! doesn't make requests it has already handled acceptable again.
+
+use config_file_handler::{self, FileHandler};
+use safe_core::ipc::{IPC_REQ_EXPIRY_SECS, ReqStamp};
+use std::collections::HashMap;
+use std::sync::{Mutex, PoisonError};
+
+/// Allowance for clocks of the app and the authenticator being out of sync.
+pub const MAX_CLOCK_SKEW_SECS: u64 = 60;
+
+/// Maps from nonces to the expiry times of their requests.
+type Nonces = HashMap<u64, u64>;
+
+lazy_static! {
+    // Serialises access to the nonces file by authenticators in this process.
+    static ref FILE_LOCK: Mutex<()> = Mutex::new(());
+}
+
+/// Keeps track of nonces of requests which haven't expired yet.
+#[derive(Default)]
+pub struct ReplayGuard {
+    nonces: Nonces,
+    file: Option<FileHandler<Nonces>>,
+}
+
+impl ReplayGuard {
+    /// Create a guard with no nonces recorded, keeping them in memory only.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Create a guard which also keeps the nonces in a local file, shared by
+    /// all authenticators on this device. Falls back to keeping them in memory
+    /// if the file can't be opened.
+    pub fn persistent() -> Self {
+        let file = file_name().and_then(|name| FileHandler::new(&name, true));
+        let file = match file {
+            Ok(file) => Some(file),
+            Err(e) => {
+                warn!("Can't open the file of seen IPC request nonces: {:?}", e);
+                None
+            }
+        };
+
+        ReplayGuard {
+            nonces: Nonces::new(),
+            file,
+        }
+    }
+
+    /// Records the stamp of an incoming request. Returns `false` if the request
+    /// has expired, has already been seen, or claims to expire further in the
+    /// future than a genuine request could.
+    pub fn check(&mut self, stamp: &ReqStamp, now: u64) -> bool {
+        let _guard = FILE_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(ref file) = self.file {
+            // The file is missing or empty until the first request is recorded.
+            if let Ok(nonces) = file.read_file() {
+                self.nonces.extend(nonces);
+            }
+        }
+
+        self.nonces.retain(|_, expires_at| *expires_at > now);
+
+        if stamp.is_expired(now) ||
+            stamp.expires_at > now + IPC_REQ_EXPIRY_SECS + MAX_CLOCK_SKEW_SECS
+        {
+            return false;
+        }
+
+        if self.nonces.insert(stamp.nonce, stamp.expires_at).is_some() {
+            return false;
+        }
+
+        if let Some(ref file) = self.file {
+            if let Err(e) = file.write_file(&self.nonces) {
+                warn!("Can't record the nonce of an IPC request: {:?}", e);
+            }
+        }
+
+        true
+    }
+}
+
+fn file_name() -> Result<String, config_file_handler::Error> {
+    let mut name = config_file_handler::exe_file_stem()?;
+    name.push(".ipc-nonces");
+    Ok(name.to_string_lossy().into_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+
+    // Test that requests are accepted only once and only until they expire.
+    #[test]
+    fn replays_and_expiry() {
+        let mut guard = ReplayGuard::new();
+        let now = 1_000_000;
+
+        let stamp = ReqStamp {
+            nonce: 1,
+            expires_at: now + IPC_REQ_EXPIRY_SECS,
+        };
+        assert!(guard.check(&stamp, now));
+        assert!(!guard.check(&stamp, now + 1));
+
+        // Another nonce is fine.
+        let other = ReqStamp { nonce: 2, ..stamp };
+        assert!(guard.check(&other, now + 1));
+
+        // Expired requests are rejected.
+        let expired = ReqStamp { nonce: 3, ..stamp };
+        assert!(!guard.check(&expired, now + IPC_REQ_EXPIRY_SECS));
+
+        // So are requests with an implausible expiry time.
+        let implausible = ReqStamp {
+            nonce: 4,
+            expires_at: now + IPC_REQ_EXPIRY_SECS + MAX_CLOCK_SKEW_SECS + 1,
+        };
+        assert!(!guard.check(&implausible, now));
+
+        // Nonces of expired requests are forgotten.
+        let _ = guard.check(&expired, now + IPC_REQ_EXPIRY_SECS);
+        assert!(guard.nonces.is_empty());
+    }
+
+    // Test that nonces recorded by a persistent guard are seen by guards
+    // created afterwards, e.g. after the authenticator has been restarted.
+    #[test]
+    fn persisted_nonces() {
+        let now = 1_000_000;
+        let stamp = ReqStamp {
+            nonce: rand::random(),
+            expires_at: now + IPC_REQ_EXPIRY_SECS,
+        };
+
+        let mut guard = ReplayGuard::persistent();
+        assert!(guard.file.is_some());
+        assert!(guard.check(&stamp, now));
+
+        let mut restarted = ReplayGuard::persistent();
+        assert!(!restarted.check(&stamp, now + 1));
+    }
+}
@@ -22,7 +22,7 @@ use futures::Future;
 use futures::future::{self, Either, Loop};
 use routing::{ClientError, EntryActions, User, Value};
 use rust_sodium::crypto::sign;
-use safe_core::{Client, CoreError, FutureExt, MDataInfo};
+use safe_core::{Client, CoreError, FutureExt, KeyOp, MDataInfo};
 use safe_core::recovery;
 use std::collections::{BTreeMap, HashMap, HashSet};
 use std::collections::hash_map::Entry;
@@ -109,7 +109,7 @@ fn revoke_single_app(client: &Client<()>, app_id: &str) -> Box<AuthFuture<()>> {
             delete_app_auth_key(&c2, app.keys.sign_pk).map(move |_| app)
         })
         .and_then(move |app| {
-            access_container::fetch_entry(&c3, &app.info.id, app.keys.clone())
+            access_container::fetch_entry(&c3, &app.registered_id(), app.keys.clone())
                 .and_then(move |(version, ac_entry)| {
                     match ac_entry {
                         Some(ac_entry) => {
@@ -170,7 +170,7 @@ fn clear_from_access_container_entry(
                 .map(move |_| (app, ac_entry_version))
         })
         .and_then(move |(app, version)| {
-            access_container::delete_entry(&c3, &app.info.id, &app.keys, version + 1)
+            access_container::delete_entry(&c3, &app.registered_id(), &app.keys, version + 1)
         })
         .into_box()
 }
@@ -199,7 +199,11 @@ fn revoke_container_perms(
                         version + 1,
                     )
                 })
-                .map_err(From::from)
+                .or_else(|err| match err {
+                    // The app's own container, not created as the app never used it
+                    CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(()),
+                    err => Err(AuthError::from(err)),
+                })
         })
         .collect();
 
@@ -227,7 +231,7 @@ fn reencrypt_containers_and_update_access_container(
     let ac_info = fry!(client.access_container().map_err(AuthError::from));
     let app_key = fry!(access_container::enc_key(
         &ac_info,
-        &revoked_app.info.id,
+        &revoked_app.registered_id(),
         &revoked_app.keys.enc_key,
     ));
 
@@ -307,6 +311,7 @@ fn update_access_container(
 
             // Update the authenticator entry
             if let Some(raw) = ac_entries.get_mut(&auth_key) {
+                c2.record_key_usage(KeyOp::Decrypt, Some(ac_info.name));
                 let sk = c2.secret_symmetric_key()?;
                 let mut decoded = access_container::decode_authenticator_entry(&raw.content, &sk)?;
 
@@ -323,7 +328,8 @@ fn update_access_container(
 
             // Update apps' entries
             for app in apps.values() {
-                let key = access_container::enc_key(&ac_info, &app.info.id, &app.keys.enc_key)?;
+                let key =
+                    access_container::enc_key(&ac_info, &app.registered_id(), &app.keys.enc_key)?;
 
                 if let Some(raw) = ac_entries.get_mut(&key) {
                     // Skip deleted entries.
@@ -392,7 +398,17 @@ fn reencrypt_containers(client: &Client<()>, containers: Containers) -> Box<Auth
         let c3 = c2.clone();
 
         c2.list_mdata_entries(mdata_info.name, mdata_info.type_tag)
+            .map(Some)
+            .or_else(|err| match err {
+                // The app's own container, not created as the app never used it
+                CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(None),
+                err => Err(err),
+            })
             .and_then(move |entries| {
+                let entries = match entries {
+                    Some(entries) => entries,
+                    None => return Ok((mdata_info, None)),
+                };
                 let mut actions = EntryActions::new();
 
                 for (old_key, value) in entries {
@@ -421,10 +437,13 @@ fn reencrypt_containers(client: &Client<()>, containers: Containers) -> Box<Auth
                     }
                 }
 
-                Ok((mdata_info, actions))
+                Ok((mdata_info, Some(actions)))
             })
-            .and_then(move |(mdata_info, actions)| {
-                c3.mutate_mdata_entries(mdata_info.name, mdata_info.type_tag, actions.into())
+            .and_then(move |(mdata_info, actions)| match actions {
+                Some(actions) => {
+                    c3.mutate_mdata_entries(mdata_info.name, mdata_info.type_tag, actions.into())
+                }
+                None => ok!(()),
             })
             .map_err(From::from)
     });
@@ -19,17 +19,16 @@ use {AuthError, AuthFuture};
 use access_container::{self, AUTHENTICATOR_ENTRY};
 use config::KEY_APPS;
 use futures::{Future, future};
-use maidsafe_utilities::serialisation::serialise;
 use routing::{ClientError, Value};
 use safe_core::{Client, CoreError, DIR_TAG, FutureExt, MDataInfo};
 use safe_core::ipc::access_container_enc_key;
 use safe_core::mdata_info;
 use safe_core::nfs::create_dir;
-use safe_core::utils::symmetric_encrypt;
 use std::collections::HashMap;
 
 /// Default Directories to be created at registration
-pub static DEFAULT_PRIVATE_DIRS: [&'static str; 5] = [
+pub static DEFAULT_PRIVATE_DIRS: [&'static str; 6] = [
+    "_contacts",
     "_documents",
     "_downloads",
     "_music",
@@ -56,7 +55,10 @@ pub fn create(client: &Client<()>) -> Box<AuthFuture<()>> {
             match res {
                 Ok((_, default_containers)) => {
                     // Make sure that all default dirs have been created
+                    let c4 = c3.clone();
                     create_std_dirs(&c3, &default_containers)
+                        .and_then(move |()| add_missing(&c4))
+                        .into_box()
                 }
                 Err(AuthError::CoreError(
                     CoreError::RoutingClientError(ClientError::NoSuchData))) => {
@@ -118,10 +120,9 @@ fn create_access_container(
             })),
         ).map_err(AuthError::from)
     );
-    let access_cont_value = fry!(symmetric_encrypt(
-        &fry!(serialise(default_entries)),
+    let access_cont_value = fry!(access_container::encode_authenticator_entry(
+        default_entries,
         &enc_key,
-        None,
     ));
 
     create_dir(
@@ -148,6 +149,33 @@ pub fn random_std_dirs() -> Result<Vec<(&'static str, MDataInfo)>, CoreError> {
     priv_dirs.chain(pub_dirs).collect()
 }
 
+/// Adds the standard directories the account doesn't have yet, because they were introduced
+/// after it had been created, to its access container. Run on login.
+pub fn add_missing<T: 'static>(client: &Client<T>) -> Box<AuthFuture<()>> {
+    let c2 = client.clone();
+
+    access_container::fetch_authenticator_entry(client)
+        .and_then(move |(version, mut containers)| {
+            let missing: HashMap<_, _> = fry!(random_std_dirs())
+                .into_iter()
+                .filter(|&(name, _)| !containers.contains_key(name))
+                .map(|(name, dir)| (String::from(name), dir))
+                .collect();
+            if missing.is_empty() {
+                return ok!(());
+            }
+
+            let c3 = c2.clone();
+            create_std_dirs(&c2, &missing)
+                .and_then(move |()| {
+                    containers.extend(missing);
+                    access_container::put_authenticator_entry(&c3, &containers, version + 1)
+                })
+                .into_box()
+        })
+        .into_box()
+}
+
 /// A registration helper function to create the set of default dirs
 /// in the users root directory.
 pub fn create_std_dirs<T: 'static>(
@@ -167,8 +195,37 @@ pub fn create_std_dirs<T: 'static>(
 #[cfg(test)]
 mod tests {
     use super::*;
+    use Authenticator;
     use futures::Future;
-    use test_utils::{create_account_and_login, run};
+    use test_utils::{create_account_and_login, create_authenticator, run};
+
+    // Test that standard directories missing from an existing account are added on login.
+    #[test]
+    fn adds_missing_dirs_on_login() {
+        let (auth, locator, password) = create_authenticator();
+
+        run(&auth, |client| {
+            let c2 = client.clone();
+
+            access_container::fetch_authenticator_entry(client).and_then(
+                move |(version, mut containers)| {
+                    let _ = containers.remove("_contacts");
+                    access_container::put_authenticator_entry(&c2, &containers, version + 1)
+                },
+            )
+        });
+        drop(auth);
+
+        let auth = unwrap!(Authenticator::login(locator, password, || ()));
+        let containers = run(&auth, |client| {
+            access_container::fetch_authenticator_entry(client).map(|(_, containers)| containers)
+        });
+        assert!(containers.contains_key("_contacts"));
+        assert_eq!(
+            containers.len(),
+            DEFAULT_PUBLIC_DIRS.len() + DEFAULT_PRIVATE_DIRS.len()
+        );
+    }
 
     // Test creation of default dirs.
     #[test]
@@ -27,15 +27,16 @@ use rand::{self, Rng};
 use revocation;
 use routing::User;
 use rust_sodium::crypto::sign;
-use safe_core::{Client, FutureExt, MDataInfo};
+use safe_core::{Client, FutureExt, MDataInfo, app_container_name};
 #[cfg(feature = "use-mock-routing")]
 use safe_core::MockRouting;
 use safe_core::ipc::{self, AppExchangeInfo, AuthGranted, AuthReq, IpcMsg, IpcReq};
 use safe_core::ipc::req::{ContainerPermissions, container_perms_into_permission_set};
 use safe_core::ipc::resp::AccessContainerEntry;
-use safe_core::nfs::{File, Mode, file_helper};
+use safe_core::nfs::{self, File, Mode, file_helper};
+use safe_core::utils::test_utils::test_env;
 use std::collections::HashMap;
-use std::sync::mpsc;
+use std::sync::{Mutex, PoisonError, mpsc};
 
 #[macro_export]
 macro_rules! assert_match {
@@ -47,6 +48,45 @@ macro_rules! assert_match {
     }
 }
 
+/// Credentials of an account created by the test utilities.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestAccount {
+    /// Account locator.
+    pub locator: String,
+    /// Account password.
+    pub password: String,
+    /// Seed the MAID keys were derived from, if the account was created with
+    /// `create_account_with`.
+    pub seed: Option<Vec<u8>>,
+}
+
+lazy_static! {
+    static ref TEST_ACCOUNTS: Mutex<HashMap<String, TestAccount>> = Mutex::new(HashMap::new());
+}
+
+fn record_test_account(account: TestAccount) {
+    let _ = TEST_ACCOUNTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(account.locator.clone(), account);
+}
+
+/// Returns the credentials of an account created by the test utilities.
+pub fn test_account(locator: &str) -> Option<TestAccount> {
+    TEST_ACCOUNTS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get(locator)
+        .cloned()
+}
+
+/// Logs in to an account created by the test utilities, e.g. to simulate another
+/// device of the same user. Panics if there's no such account.
+pub fn login_test_account(locator: &str) -> Authenticator {
+    let account = unwrap!(test_account(locator), "Unknown test account {}", locator);
+    unwrap!(Authenticator::login(account.locator, account.password, || ()))
+}
+
 /// Creates a new random account for authenticator. Returns the `Authenticator`
 /// instance and the locator and password strings.
 pub fn create_authenticator() -> (Authenticator, String, String) {
@@ -54,7 +94,7 @@ pub fn create_authenticator() -> (Authenticator, String, String) {
 
     let locator: String = rng.gen_ascii_chars().take(10).collect();
     let password: String = rng.gen_ascii_chars().take(10).collect();
-    let invitation: String = rng.gen_ascii_chars().take(10).collect();
+    let invitation = test_env().invitation();
 
     let auth = unwrap!(Authenticator::create_acc(
         locator.clone(),
@@ -62,11 +102,49 @@ pub fn create_authenticator() -> (Authenticator, String, String) {
         invitation,
         || (),
     ));
+    record_test_account(TestAccount {
+        locator: locator.clone(),
+        password: password.clone(),
+        seed: None,
+    });
 
     (auth, locator, password)
 }
 
+/// Creates an account with the given credentials and the MAID keys derived from
+/// `seed`, so the same account can be recreated by another test run. The account
+/// is recorded for `login_test_account`.
+pub fn create_account_with(locator: &str, password: &str, seed: &[u8]) -> Authenticator {
+    let account = TestAccount {
+        locator: locator.to_owned(),
+        password: password.to_owned(),
+        seed: Some(seed.to_vec()),
+    };
+    let account2 = account.clone();
+    let invitation = test_env().invitation();
+
+    let auth = unwrap!(Authenticator::create_acc_impl(
+        move |el_h, core_tx, net_tx| {
+            Client::registered_with_keys_seed(
+                &account2.locator,
+                &account2.password,
+                &invitation,
+                unwrap!(account2.seed.as_ref()),
+                el_h,
+                core_tx,
+                net_tx,
+            )
+        },
+        || (),
+    ));
+    record_test_account(account);
+
+    auth
+}
+
 /// Create a random authenticator and login using the same credentials.
+/// Test runs can enable the `fast-kdf` feature, so this doesn't pay for the full
+/// key derivation cost twice.
 pub fn create_account_and_login() -> Authenticator {
     let (_, locator, password) = create_authenticator();
     unwrap!(Authenticator::login(locator, password, || ()))
@@ -130,7 +208,9 @@ pub fn register_app(
     };
 
     // Invoke `decode_ipc_msg` and expect to get AuthReq back.
-    let ipc_req = run(authenticator, move |client| decode_ipc_msg(client, msg));
+    let ipc_req = run(authenticator, move |client| {
+        decode_ipc_msg(client, msg, ipc::IPC_PROTOCOL_VERSION, false)
+    });
     match ipc_req {
         Ok(IpcMsg::Req { req: IpcReq::Auth(_), .. }) => (),
         x => return Err(AuthError::Unexpected(format!("Unexpected {:?}", x))),
@@ -205,6 +285,9 @@ pub fn rand_app() -> AppExchangeInfo {
         scope: None,
         name: rng.gen_ascii_chars().take(10).collect(),
         vendor: rng.gen_ascii_chars().take(10).collect(),
+        description: None,
+        homepage: None,
+        icon: None,
     }
 }
 
@@ -327,3 +410,24 @@ pub fn compare_access_container_entries(
         assert_eq!(perms, expected);
     }
 }
+
+/// Create the app's own container reserved by the authenticator, as the app does on its
+/// first use. Returns its `MDataInfo`.
+pub fn create_app_container(authenticator: &Authenticator, app_id: &str) -> MDataInfo {
+    let app_id = app_id.to_owned();
+    let container = unwrap!(get_container_from_authenticator_entry(
+        authenticator,
+        &app_container_name(&app_id),
+    ));
+    let container2 = container.clone();
+
+    run(authenticator, move |client| {
+        let c2 = client.clone();
+        config::get_app(client, &app_id).and_then(move |app| {
+            nfs::create_app_container(&c2, &container2, app.keys.sign_pk)
+                .map_err(AuthError::from)
+        })
+    });
+
+    container
+}
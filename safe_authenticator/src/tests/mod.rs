@@ -22,25 +22,36 @@ mod utils;
 
 use self::utils::{ChannelType, create_containers_req, decode_ipc_msg, err_cb, unregistered_cb};
 use access_container as access_container_tools;
+use app_auth;
 use app_container;
 use config::{self, KEY_APPS};
-use errors::{AuthError, ERR_INVALID_MSG, ERR_OPERATION_FORBIDDEN, ERR_UNEXPECTED, ERR_UNKNOWN_APP};
+use errors::{AuthError, ERR_AUTH_DENIED, ERR_CONTAINERS_DENIED, ERR_INVALID_MSG,
+             ERR_OPERATION_FORBIDDEN, ERR_REQUEST_DENIED, ERR_REQUEST_EXPIRED, ERR_UNEXPECTED,
+             ERR_UNKNOWN_APP, ERR_UNSUPPORTED_VERSION};
 use ffi::apps::*;
-use ffi::ipc::{auth_revoke_app, encode_auth_resp, encode_containers_resp, encode_unregistered_resp};
-use ffi_utils::{ReprC, StringError, from_c_str};
-use ffi_utils::test_utils::{call_1, call_vec, sender_as_user_data};
+use ffi::ipc::{PendingAuthReq, auth_ipc_app_uri, auth_ipc_uri_payload, auth_pending_requests,
+               auth_queue_ipc_msg, auth_respond, auth_revoke_app, encode_auth_resp,
+               encode_containers_resp, encode_unregistered_resp};
+use ffi_utils::{ReprC, StringError, base64_encode, from_c_str};
+use ffi_utils::test_utils::{call_0, call_1, call_vec, sender_as_user_data};
 use futures::{Future, future};
-use safe_core::{app_container_name, mdata_info};
+use maidsafe_utilities::serialisation::serialise;
+use policy;
+use rust_sodium::crypto::sign::Seed;
+use safe_core::{ClientKeys, app_container_name, mdata_info, utils};
 use safe_core::ffi::ipc::req::AppExchangeInfo as FfiAppExchangeInfo;
-use safe_core::ipc::{self, AuthReq, BootstrapConfig, ContainersReq, IpcError, IpcMsg, IpcReq,
-                     IpcResp, Permission};
+use safe_core::ipc::{self, AppExchangeInfo, AuthReq, BootstrapConfig, CombinedReq, ContainersReq,
+                     IpcError, IpcMsg, IpcReq, IpcResp, Permission};
 use std::collections::HashMap;
 use std::ffi::CString;
 use std::sync::mpsc;
 use std::time::Duration;
 use std_dirs::{DEFAULT_PRIVATE_DIRS, DEFAULT_PUBLIC_DIRS};
-use test_utils::{access_container, compare_access_container_entries, create_account_and_login,
-                 rand_app, register_app, run};
+use test_utils::{TestAccount, access_container, compare_access_container_entries,
+                 create_account_and_login, create_account_with, create_app_container,
+                 get_app_or_err, login_test_account, rand_app, register_app, register_rand_app,
+                 run, test_account, try_run};
+use throttle;
 use tiny_keccak::sha3_256;
 
 #[cfg(feature = "use-mock-routing")]
@@ -50,14 +61,15 @@ mod mock_routing {
     use access_container as access_container_tools;
     use errors::AuthError;
     use futures::Future;
-    use routing::{ClientError, Request, Response, User};
+    use ipc;
+    use rand;
+    use routing::{Action, ClientError, MutableData, PermissionSet, Request, Response};
     use safe_core::{CoreError, MockRouting, app_container_name};
-    use safe_core::ipc::AuthReq;
-    use safe_core::nfs::NfsError;
+    use safe_core::ipc::{AuthReq, CombinedReq, IpcError, ShareMData};
     use safe_core::utils::generate_random_string;
     use std_dirs::{DEFAULT_PRIVATE_DIRS, DEFAULT_PUBLIC_DIRS};
-    use test_utils::{access_container, create_account_and_login_with_hook, rand_app, register_app,
-                     run};
+    use test_utils::{access_container, create_account_and_login_with_hook, get_app_or_err,
+                     rand_app, register_app, run, try_run};
 
     // Test operation recovery for std dirs creation.
     // 1. Try to create a new user's account using `safe_authenticator::Authenticator::create_acc`
@@ -72,6 +84,8 @@ mod mock_routing {
     // 5. Check the access container entry in the user's config root - it must be accessible
     #[test]
     fn std_dirs_recovery() {
+        skip_unless_mock!();
+
         use safe_core::DIR_TAG;
 
         // Add a request hook to forbid root dir modification. In this case
@@ -147,6 +161,8 @@ mod mock_routing {
     // Ensure that users can log in with low account balance.
     #[test]
     fn login_with_low_balance() {
+        skip_unless_mock!();
+
         // Register a hook prohibiting mutations and login
         let routing_hook = move |mut routing: MockRouting| -> MockRouting {
             routing.set_request_hook(move |req| {
@@ -225,20 +241,22 @@ mod mock_routing {
     // 5. Try to authenticate the app again, it should continue without errors
     //
     // 6. Simulate a network failure for the `set_mdata_user_permissions` operation
-    //    (relating to the app's container - so that it will be created successfuly, but fail
-    //    at the permissions set stage).
+    //    (relating to the permissions of the requested containers).
     // 7. Try to authenticate the app again, it should continue without errors.
     //
     // 8. Simulate a network failure for the `mutate_mdata_entries` operation
-    //    (relating to update of the access container).
+    //    (relating to the reservation of the app's container and update of the access
+    //    container).
     // 9. Try to authenticate the app again, it should succeed now.
     //
-    // 10. Check that the app's container has been created.
-    // 11. Check that the app's container has required permissions.
-    // 12. Check that the app's container is listed in the access container entry for
+    // 10. Check that the app's container has been reserved, but not created, as that's
+    //     left to the app.
+    // 11. Check that the app's container is listed in the access container entry for
     //     the app.
     #[test]
     fn app_authentication_recovery() {
+        skip_unless_mock!();
+
         let locator = unwrap!(generate_random_string(10));
         let password = unwrap!(generate_random_string(10));
         let invitation = unwrap!(generate_random_string(10));
@@ -321,36 +339,6 @@ mod mock_routing {
             x => panic!("Unexpected {:?}", x),
         }
 
-        // Simulate a network failure for the `app_container` setup step -
-        // it should fail at the third request for `SetMDataPermissions` (after
-        // setting permissions for 2 requested containers, `_video` and `_documents`)
-        let routing_hook = move |mut routing: MockRouting| -> MockRouting {
-            routing.set_request_hook(move |req| {
-                match *req {
-                    Request::PutMData { msg_id, .. } => {
-                        Some(Response::PutMData {
-                            res: Err(ClientError::LowBalance),
-                            msg_id,
-                        })
-                    }
-                    // Pass-through
-                    _ => None,
-                }
-            });
-            routing
-        };
-        let auth = unwrap!(Authenticator::login_with_hook(
-            locator.clone(),
-            password.clone(),
-            || (),
-            routing_hook,
-        ));
-        match register_app(&auth, &auth_req) {
-            Err(AuthError::NfsError(NfsError::CoreError(
-                CoreError::RoutingClientError(ClientError::LowBalance)))) => (),
-            x => panic!("Unexpected {:?}", x),
-        }
-
         // Simulate a network failure for the `MutateMDataEntries` request, which
         // is supposed to setup the access container entry for the app
         let routing_hook = move |mut routing: MockRouting| -> MockRouting {
@@ -393,35 +381,111 @@ mod mock_routing {
             x => panic!("Unexpected {:?}", x),
         };
 
-        // Check that the app's container has been created and that the access container
-        // contains info about all of the requested containers.
+        // Check that the app's container has been reserved, but not created, as that's
+        // left to the app, and that the access container contains info about all of the
+        // requested containers.
         let mut ac_entries = access_container(&auth, app_id.clone(), auth_granted.clone());
         let (_videos_md, _) = unwrap!(ac_entries.remove("_videos"));
         let (_documents_md, _) = unwrap!(ac_entries.remove("_documents"));
         let (app_container_md, _) = unwrap!(ac_entries.remove(&app_container_name(&app_id)));
 
-        let app_pk = auth_granted.app_keys.sign_pk;
-
         run(&auth, move |client| {
-            let c2 = client.clone();
-
             client
                 .get_mdata_version(app_container_md.name, app_container_md.type_tag)
                 .then(move |res| {
-                    let version = unwrap!(res);
-                    assert_eq!(version, 0);
-
-                    // Check that the app's container has required permissions.
-                    c2.list_mdata_permissions(app_container_md.name, app_container_md.type_tag)
+                    match res {
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => (),
+                        x => panic!("Unexpected {:?}", x),
+                    }
+                    Ok::<_, AuthError>(())
                 })
-                .then(move |res| {
-                    let perms = unwrap!(res);
-                    assert!(perms.contains_key(&User::Key(app_pk)));
-                    assert_eq!(perms.len(), 1);
+        });
+    }
 
-                    Ok(())
-                })
+    // Test that a combined request is granted atomically.
+    // 1. Put mutable data owned by the user and simulate a failure of the requests which
+    //    would share it with an app.
+    // 2. Granting a combined request for the app and the mdata should fail.
+    // 3. The app shouldn't be registered and its key shouldn't be in Maid Managers.
+    #[test]
+    fn combined_request_share_failure() {
+        skip_unless_mock!();
+
+        let tag = 15_000;
+        let routing_hook = move |mut routing: MockRouting| -> MockRouting {
+            routing.set_request_hook(move |req| {
+                match *req {
+                    Request::SetMDataUserPermissions {
+                        tag: req_tag,
+                        msg_id,
+                        ..
+                    } if req_tag == tag => {
+                        Some(Response::SetMDataUserPermissions {
+                            res: Err(ClientError::AccessDenied),
+                            msg_id,
+                        })
+                    }
+                    // Pass-through
+                    _ => None,
+                }
+            });
+            routing
+        };
+        let authenticator = create_account_and_login_with_hook(routing_hook);
+
+        let (user, auth_keys) = run(&authenticator, |client| {
+            let user = unwrap!(client.public_signing_key());
+            client
+                .list_auth_keys_and_version()
+                .map(move |(keys, _)| (user, keys))
+                .map_err(AuthError::from)
+        });
+
+        let name = rand::random();
+        let mdata = unwrap!(MutableData::new(
+            name,
+            tag,
+            btree_map![],
+            btree_map![],
+            btree_set![user],
+        ));
+        run(&authenticator, move |client| {
+            client.put_mdata(mdata).map_err(AuthError::CoreError)
+        });
+
+        let app = rand_app();
+        let req = CombinedReq {
+            auth: AuthReq {
+                app: app.clone(),
+                app_container: true,
+                containers: create_containers_req(),
+            },
+            mdata: vec![
+                ShareMData {
+                    type_tag: tag,
+                    name: name,
+                    perms: PermissionSet::new().allow(Action::Insert),
+                },
+            ],
+        };
+
+        match try_run(&authenticator, move |client| ipc::grant_combined_req(client, req)) {
+            Err(AuthError::CoreError(CoreError::RoutingClientError(_))) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        match get_app_or_err(&authenticator, &app.id) {
+            Err(AuthError::IpcError(IpcError::UnknownApp)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let new_auth_keys = run(&authenticator, |client| {
+            client
+                .list_auth_keys_and_version()
+                .map(|(keys, _)| keys)
+                .map_err(AuthError::from)
         });
+        assert_eq!(new_auth_keys, auth_keys);
     }
 }
 
@@ -488,6 +552,36 @@ fn config_root_dir() {
     assert!(config.content.is_empty());
 }
 
+// Test logging in to a seeded test account from two devices.
+#[test]
+fn seeded_account_on_two_devices() {
+    let locator = unwrap!(utils::generate_random_string(10));
+    let password = unwrap!(utils::generate_random_string(10));
+    let seed = b"two devices";
+
+    let device1 = create_account_with(&locator, &password, seed);
+    assert_eq!(
+        test_account(&locator),
+        Some(TestAccount {
+            locator: locator.clone(),
+            password: password.clone(),
+            seed: Some(seed.to_vec()),
+        })
+    );
+    let device2 = login_test_account(&locator);
+
+    // Both devices have the keys derived from the seed.
+    let expected_key = ClientKeys::new(Some(&Seed(sha3_256(seed)))).sign_pk;
+    for device in &[&device1, &device2] {
+        let key = run(device, |client| client.public_signing_key().map_err(AuthError::from));
+        assert_eq!(key, expected_key);
+    }
+
+    // Changes made on one device are visible on the other.
+    let (app_id, _) = unwrap!(register_rand_app(&device1, true, HashMap::new()));
+    assert!(get_app_or_err(&device2, &app_id).is_ok());
+}
+
 // Test app authentication.
 #[test]
 fn app_authentication() {
@@ -581,6 +675,9 @@ fn app_authentication() {
     let app_keys = auth_granted.app_keys;
     let app_sign_pk = app_keys.sign_pk;
 
+    // The app's container is only reserved until the app uses it.
+    let _ = create_app_container(&authenticator, &app_id);
+
     compare_access_container_entries(
         &authenticator,
         app_sign_pk,
@@ -806,6 +903,72 @@ fn authenticated_app_can_be_authenticated_again() {
     };
 }
 
+// Authorise the same app under two scopes and check that each scope gets keys and an app
+// container of its own.
+#[test]
+fn scoped_apps() {
+    let authenticator = create_account_and_login();
+
+    let app = rand_app();
+    let mut work = app.clone();
+    work.scope = Some("work".to_string());
+    let mut personal = app.clone();
+    personal.scope = Some("personal".to_string());
+    assert_ne!(work.scoped_id(), personal.scoped_id());
+
+    let register = |app: &AppExchangeInfo| {
+        unwrap!(register_app(
+            &authenticator,
+            &AuthReq {
+                app: app.clone(),
+                app_container: true,
+                containers: Default::default(),
+            },
+        ))
+    };
+    let work_granted = register(&work);
+    let personal_granted = register(&personal);
+    assert_ne!(work_granted.app_keys.sign_pk, personal_granted.app_keys.sign_pk);
+
+    let work_entry = access_container(&authenticator, work.scoped_id(), work_granted);
+    let personal_entry = access_container(&authenticator, personal.scoped_id(), personal_granted);
+
+    let &(ref work_container, _) = unwrap!(work_entry.get(&app_container_name(&work.scoped_id())));
+    let &(ref personal_container, _) =
+        unwrap!(personal_entry.get(&app_container_name(&personal.scoped_id())));
+    assert_ne!(work_container.name, personal_container.name);
+    assert!(!work_entry.contains_key(&app_container_name(&personal.scoped_id())));
+
+    // Scopes containing the separator would make scoped IDs ambiguous.
+    let mut ambiguous = app.clone();
+    ambiguous.scope = Some("work#personal".to_string());
+    let auth_req = AuthReq {
+        app: ambiguous,
+        app_container: true,
+        containers: Default::default(),
+    };
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Auth(auth_req.clone()),
+    };
+    match decode_ipc_msg(&authenticator, &unwrap!(ipc::encode_msg(&msg))) {
+        Err((ERR_INVALID_MSG, None)) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+    match try_run(&authenticator, move |client| app_auth::authenticate(client, auth_req)) {
+        Err(AuthError::IpcError(IpcError::InvalidMsg)) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    // The unscoped app is a different app again.
+    let unscoped = run(&authenticator, move |client| {
+        config::list_apps(client).map(move |(_, apps)| {
+            apps.contains_key(&sha3_256(app.id.as_bytes()))
+        })
+    });
+    assert!(!unscoped);
+}
+
 // Create and serialize a containers request for a random app, make sure we get an error.
 #[test]
 fn containers_unknown_app() {
@@ -896,6 +1059,464 @@ fn containers_access_request() {
     compare_access_container_entries(&authenticator, app_sign_pk, access_container, expected);
 }
 
+// Test that the authorisation policy is applied when decoding auth requests.
+// 1. Enable automatic granting of read-only requests and deny the `_music` container.
+// 2. A read-only request should be granted without prompting the user.
+// 3. A request for `_music` should be denied without prompting the user.
+// 4. Without automatic granting, the read-only request should be passed on to the user.
+// 5. A combined request for `_music` should be denied too.
+#[test]
+fn auth_policy() {
+    let authenticator = create_account_and_login();
+
+    run(&authenticator, |client| {
+        let c2 = client.clone();
+        policy::set_grant_read_only(client, true).and_then(move |_| {
+            policy::set_container_denied(&c2, "_music".to_owned(), true)
+        })
+    });
+
+    let mut containers = HashMap::new();
+    let _ = containers.insert("_documents".to_owned(), btree_set![Permission::Read]);
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Auth(AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: containers.clone(),
+        }),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match unwrap!(decode_ipc_msg(&authenticator, &encoded_msg)) {
+        (IpcMsg::Resp { resp: IpcResp::Auth(Ok(_)), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let read_only_req = AuthReq {
+        app: rand_app(),
+        app_container: false,
+        containers: containers.clone(),
+    };
+
+    let _ = containers.insert("_music".to_owned(), btree_set![Permission::Insert]);
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Auth(AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: containers.clone(),
+        }),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match decode_ipc_msg(&authenticator, &encoded_msg) {
+        Err((code, Some(IpcMsg::Resp { resp: IpcResp::Auth(Err(IpcError::AuthDenied)), .. })))
+            if code == ERR_AUTH_DENIED => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+    let denied_req = AuthReq {
+        app: rand_app(),
+        app_container: false,
+        containers: containers,
+    };
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Auth(read_only_req),
+    };
+    match unwrap!(run(&authenticator, move |client| {
+        ::ipc::decode_ipc_msg(client, msg, ipc::IPC_PROTOCOL_VERSION, false)
+    })) {
+        IpcMsg::Req { req: IpcReq::Auth(_), .. } => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Combined(CombinedReq {
+            auth: denied_req,
+            mdata: vec![],
+        }),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match decode_ipc_msg(&authenticator, &encoded_msg) {
+        Err((code,
+             Some(IpcMsg::Resp { resp: IpcResp::Combined(Err(IpcError::AuthDenied)), .. })))
+            if code == ERR_AUTH_DENIED => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+}
+
+// Test that the authorisation policy is applied to containers requests of authenticated apps.
+// 1. Register an app and deny the `_music` container.
+// 2. A containers request for `_music` should be denied without prompting the user.
+// 3. A containers request for other containers should be passed on to the user.
+#[test]
+fn containers_policy() {
+    let authenticator = create_account_and_login();
+
+    let app = rand_app();
+    let auth_req = AuthReq {
+        app: app.clone(),
+        app_container: false,
+        containers: HashMap::new(),
+    };
+    let auth_granted = unwrap!(register_app(&authenticator, &auth_req));
+    let enc_key = auth_granted.app_keys.enc_key;
+
+    run(&authenticator, |client| {
+        policy::set_container_denied(client, "_music".to_owned(), true)
+    });
+
+    let mut containers = HashMap::new();
+    let _ = containers.insert("_music".to_owned(), btree_set![Permission::Read]);
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Containers(ContainersReq {
+            app: app.clone(),
+            containers: containers,
+        }),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg_with_mac(&msg, &enc_key));
+
+    match decode_ipc_msg(&authenticator, &encoded_msg) {
+        Err((code,
+             Some(IpcMsg::Resp {
+                      resp: IpcResp::Containers(Err(IpcError::ContainersDenied)), ..
+                  }))) if code == ERR_CONTAINERS_DENIED => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Containers(ContainersReq {
+            app: app,
+            containers: create_containers_req(),
+        }),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg_with_mac(&msg, &enc_key));
+
+    match unwrap!(decode_ipc_msg(&authenticator, &encoded_msg)) {
+        (IpcMsg::Req { req: IpcReq::Containers(_), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+}
+
+// Test throttling of auth requests from an app which keeps sending them.
+// 1. Requests up to the limit should be passed on to the user.
+// 2. The next request should be denied with `RequestDenied`.
+// 3. Requests from other apps should not be affected.
+#[test]
+fn auth_request_throttling() {
+    let authenticator = create_account_and_login();
+    let app = rand_app();
+
+    let auth_msg = |app: AppExchangeInfo| {
+        let msg = IpcMsg::Req {
+            req_id: ipc::gen_req_id(),
+            req: IpcReq::Auth(AuthReq {
+                app: app,
+                app_container: false,
+                containers: HashMap::new(),
+            }),
+        };
+        unwrap!(ipc::encode_msg(&msg))
+    };
+
+    for _ in 0..throttle::MAX_AUTH_REQS_PER_WINDOW {
+        match unwrap!(decode_ipc_msg(&authenticator, &auth_msg(app.clone()))) {
+            (IpcMsg::Req { req: IpcReq::Auth(_), .. }, _) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
+    match decode_ipc_msg(&authenticator, &auth_msg(app)) {
+        Err((code, Some(IpcMsg::Resp { resp: IpcResp::Auth(Err(IpcError::RequestDenied)), .. })))
+            if code == ERR_REQUEST_DENIED => (),
+        x => panic!("Unexpected {:?}", x),
+    };
+
+    match unwrap!(decode_ipc_msg(&authenticator, &auth_msg(rand_app()))) {
+        (IpcMsg::Req { req: IpcReq::Auth(_), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+// Test that captured IPC requests can't be replayed.
+// 1. A request should be decoded fine the first time.
+// 2. Decoding the same encoded request again should fail with `RequestExpired`.
+#[test]
+fn replayed_request() {
+    let authenticator = create_account_and_login();
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Auth(AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: HashMap::new(),
+        }),
+    };
+    let encoded = unwrap!(ipc::encode_msg(&msg));
+
+    match unwrap!(decode_ipc_msg(&authenticator, &encoded)) {
+        (IpcMsg::Req { req: IpcReq::Auth(_), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    match decode_ipc_msg(&authenticator, &encoded) {
+        Err((code, None)) if code == ERR_REQUEST_EXPIRED => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+// Test that responses are encoded with the protocol version of the request.
+// 1. Decode an auth request encoded with an older version of the protocol.
+// 2. Deny it. The response should be encoded with the version of the request.
+// 3. Responses to requests with the current version should use the current version.
+#[test]
+fn resp_protocol_version() {
+    let authenticator = create_account_and_login();
+    let version = ipc::IPC_APP_INFO_PROTOCOL_VERSION;
+    assert!(version < ipc::IPC_PROTOCOL_VERSION);
+
+    let deny = |encoded: &str| -> (IpcMsg, u32) {
+        let (req_id, auth_req) = match unwrap!(decode_ipc_msg(&authenticator, encoded)) {
+            (IpcMsg::Req {
+                 req_id,
+                 req: IpcReq::Auth(auth_req),
+             },
+             _) => (req_id, auth_req),
+            x => panic!("Unexpected {:?}", x),
+        };
+
+        let resp: String = unsafe {
+            unwrap!(call_1(|ud, cb| {
+                let auth_req = unwrap!(auth_req.into_repr_c());
+                encode_auth_resp(
+                    &authenticator,
+                    &auth_req,
+                    req_id,
+                    false, // is_granted
+                    ud,
+                    cb,
+                )
+            }))
+        };
+        unwrap!(ipc::decode_msg_with_version(&resp))
+    };
+
+    let req_id = ipc::gen_req_id();
+    let msg = IpcMsg::Req {
+        req_id: req_id,
+        req: IpcReq::Auth(AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: HashMap::new(),
+        }),
+    };
+
+    match deny(&unwrap!(ipc::encode_msg_with_version(&msg, version))) {
+        (IpcMsg::Resp {
+             req_id: resp_req_id,
+             resp: IpcResp::Auth(Err(IpcError::AuthDenied)),
+         },
+         resp_version) => {
+            assert_eq!(resp_req_id, req_id);
+            assert_eq!(resp_version, version);
+        }
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    match deny(&unwrap!(ipc::encode_msg(&msg))) {
+        (IpcMsg::Resp { resp: IpcResp::Auth(Err(IpcError::AuthDenied)), .. }, resp_version) => {
+            assert_eq!(resp_version, ipc::IPC_PROTOCOL_VERSION)
+        }
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+// Test that requests from apps predating replay protection are rejected unless
+// the authenticator allows legacy requests.
+// 1. An unstamped request should fail with `UnsupportedVersion`.
+// 2. A containers request of a registered app without a MAC should fail with `InvalidMsg`.
+// 3. The same containers request with a MAC should be decoded fine.
+// 4. After allowing legacy requests, both should be decoded fine.
+#[test]
+fn legacy_requests() {
+    let authenticator = create_account_and_login();
+
+    let unstamped = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Unregistered(Vec::new()),
+    };
+    let unstamped = base64_encode(&unwrap!(serialise(&unstamped)));
+
+    match decode_ipc_msg(&authenticator, &unstamped) {
+        Err((code, None)) if code == ERR_UNSUPPORTED_VERSION => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    let app = rand_app();
+    let auth_req = AuthReq {
+        app: app.clone(),
+        app_container: false,
+        containers: HashMap::new(),
+    };
+    let auth_granted = unwrap!(register_app(&authenticator, &auth_req));
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Containers(ContainersReq {
+            app: app,
+            containers: create_containers_req(),
+        }),
+    };
+
+    match decode_ipc_msg(&authenticator, &unwrap!(ipc::encode_msg(&msg))) {
+        Err((code, None)) if code == ERR_INVALID_MSG => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    let encoded = unwrap!(ipc::encode_msg_with_mac(&msg, &auth_granted.app_keys.enc_key));
+    match unwrap!(decode_ipc_msg(&authenticator, &encoded)) {
+        (IpcMsg::Req { req: IpcReq::Containers(_), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    authenticator.set_legacy_reqs_allowed(true);
+
+    match unwrap!(decode_ipc_msg(&authenticator, &unstamped)) {
+        (IpcMsg::Req { req: IpcReq::Unregistered(_), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+    match unwrap!(decode_ipc_msg(&authenticator, &unwrap!(ipc::encode_msg(&msg)))) {
+        (IpcMsg::Req { req: IpcReq::Containers(_), .. }, _) => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+}
+
+// Test wrapping IPC messages into URIs.
+// 1. Extract the payload of a `safe-auth` URI and decode the request from it.
+// 2. Wrap a response into an app URI; its scheme should be scoped to the app id.
+#[test]
+fn ipc_uri() {
+    let app = rand_app();
+    let app_id = app.id.clone();
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Unregistered(Vec::new()),
+    };
+
+    let uri = unwrap!(ipc::uri::encode_auth_uri(&msg));
+    let uri = unwrap!(CString::new(uri));
+    let payload: String = unsafe {
+        unwrap!(call_1(|ud, cb| auth_ipc_uri_payload(uri.as_ptr(), ud, cb)))
+    };
+    assert_eq!(unwrap!(ipc::decode_msg(&payload)), msg);
+
+    let app_id_c = unwrap!(CString::new(app_id.clone()));
+    let payload = unwrap!(CString::new(payload));
+    let uri: String = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            auth_ipc_app_uri(app_id_c.as_ptr(), payload.as_ptr(), ud, cb)
+        }))
+    };
+
+    let (scheme, _) = unwrap!(ipc::uri::parse_uri(&uri));
+    assert_eq!(scheme, ipc::uri::app_uri_scheme(&app_id));
+    assert_eq!(unwrap!(ipc::uri::decode_uri(&uri)), msg);
+}
+
+// Test answering queued auth requests.
+// 1. Queue two auth requests. Both of them should be listed as pending.
+// 2. Grant the first request. The response should contain the granted access.
+// 3. Deny the second request. The response should contain `AuthDenied`.
+// 4. There should be no pending requests left.
+#[test]
+fn pending_auth_requests() {
+    let authenticator = create_account_and_login();
+
+    let req_ids = [ipc::gen_req_id(), ipc::gen_req_id()];
+    let mut app_ids = Vec::new();
+
+    for req_id in &req_ids {
+        let auth_req = AuthReq {
+            app: rand_app(),
+            app_container: false,
+            containers: create_containers_req(),
+        };
+        app_ids.push(auth_req.app.id.clone());
+
+        let msg = IpcMsg::Req {
+            req_id: *req_id,
+            req: IpcReq::Auth(auth_req),
+        };
+        let encoded_msg = unwrap!(CString::new(unwrap!(ipc::encode_msg(&msg))));
+
+        unsafe {
+            unwrap!(call_0(|ud, cb| {
+                auth_queue_ipc_msg(&authenticator, encoded_msg.as_ptr(), ud, cb)
+            }))
+        };
+    }
+
+    let pending: Vec<PendingAppId> =
+        unsafe { unwrap!(call_vec(|ud, cb| auth_pending_requests(&authenticator, ud, cb))) };
+    let mut expected: Vec<_> = req_ids.iter().cloned().zip(app_ids).collect();
+    expected.sort();
+    assert_eq!(
+        pending.into_iter().map(|p| (p.0, p.1)).collect::<Vec<_>>(),
+        expected
+    );
+
+    let resp: String = unsafe {
+        unwrap!(call_1(
+            |ud, cb| auth_respond(&authenticator, req_ids[0], true, ud, cb),
+        ))
+    };
+    match unwrap!(ipc::decode_msg(&resp)) {
+        IpcMsg::Resp {
+            req_id,
+            resp: IpcResp::Auth(Ok(_)),
+        } if req_id == req_ids[0] => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    let resp: String = unsafe {
+        unwrap!(call_1(
+            |ud, cb| auth_respond(&authenticator, req_ids[1], false, ud, cb),
+        ))
+    };
+    match unwrap!(ipc::decode_msg(&resp)) {
+        IpcMsg::Resp {
+            req_id,
+            resp: IpcResp::Auth(Err(IpcError::AuthDenied)),
+        } if req_id == req_ids[1] => (),
+        x => panic!("Unexpected {:?}", x),
+    }
+
+    let pending: Vec<PendingAppId> =
+        unsafe { unwrap!(call_vec(|ud, cb| auth_pending_requests(&authenticator, ud, cb))) };
+    assert!(pending.is_empty());
+}
+
+struct PendingAppId(u32, String);
+impl ReprC for PendingAppId {
+    type C = *const PendingAuthReq;
+    type Error = StringError;
+
+    unsafe fn clone_from_repr_c(c_repr: Self::C) -> Result<Self, Self::Error> {
+        Ok(PendingAppId(
+            (*c_repr).req_id,
+            from_c_str((*c_repr).req.app.id)?,
+        ))
+    }
+}
+
 struct RegisteredAppId(String);
 impl ReprC for RegisteredAppId {
     type C = *const RegisteredApp;
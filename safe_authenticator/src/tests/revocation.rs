@@ -25,8 +25,9 @@ use safe_core::{CoreError, MDataInfo, app_container_name};
 use safe_core::ipc::{AuthReq, Permission};
 use safe_core::nfs::NfsError;
 use std::collections::HashMap;
-use test_utils::{access_container, create_account_and_login, create_authenticator, create_file,
-                 fetch_file, get_container_from_authenticator_entry, rand_app, register_app,
+use test_utils::{access_container, create_account_and_login, create_app_container,
+                 create_authenticator, create_file, fetch_file,
+                 get_container_from_authenticator_entry, rand_app, register_app,
                  register_rand_app, revoke, run, try_access_container};
 
 #[cfg(feature = "use-mock-routing")]
@@ -76,6 +77,8 @@ mod mock_routing {
     //     `MDataInfo`.
     #[test]
     fn app_revocation_recovery() {
+        skip_unless_mock!();
+
         let (auth, locator, password) = create_authenticator();
 
         // Create a test app and authenticate it.
@@ -206,6 +209,8 @@ mod mock_routing {
     // 5. Try to re-authenticate the app again. This time it will succeed.
     #[test]
     fn app_authentication_during_pending_revocation() {
+        skip_unless_mock!();
+
         // Create account.
         let (auth, locator, password) = create_authenticator();
 
@@ -244,6 +249,8 @@ mod mock_routing {
     // 4. Verify both apps are successfully revoked.
     #[test]
     fn flushing_app_revocation_queue() {
+        skip_unless_mock!();
+
         // Create account.
         let (auth, locator, password) = create_authenticator();
 
@@ -330,6 +337,8 @@ mod mock_routing {
     // Test one app being revoked by multiple authenticator concurrently.
     #[test]
     fn concurrent_revocation_of_single_app() {
+        skip_unless_mock!();
+
         let rng = SeededRng::new();
 
         // Number of concurrent operations.
@@ -362,10 +371,7 @@ mod mock_routing {
 
         // Put a file into the dedicated container of each app.
         for app_id in &[&app_id_0, &app_id_1] {
-            let info = unwrap!(get_container_from_authenticator_entry(
-                &auth,
-                &app_container_name(app_id),
-            ));
+            let info = create_app_container(&auth, app_id);
             unwrap!(create_file(&auth, info, "private.txt", vec![0; 10]));
         }
 
@@ -432,6 +438,8 @@ mod mock_routing {
     // Test multiple apps being revoked concurrently.
     #[test]
     fn concurrent_revocation_of_multiple_apps() {
+        skip_unless_mock!();
+
         let rng = SeededRng::new();
 
         // Create account.
@@ -464,10 +472,7 @@ mod mock_routing {
 
         // Put a file into the dedicated container of each app.
         for app_id in &[&app_id_0, &app_id_1, &app_id_2] {
-            let info = unwrap!(get_container_from_authenticator_entry(
-                &auth,
-                &app_container_name(app_id),
-            ));
+            let info = create_app_container(&auth, app_id);
             unwrap!(create_file(&auth, info, "private.txt", vec![0; 10]));
         }
 
@@ -765,6 +770,7 @@ fn app_revocation() {
 
     let app_container_name = app_container_name(&app_id2);
     let (app_container_md, _) = unwrap!(ac_entries.remove(&app_container_name));
+    let _ = create_app_container(&authenticator, &app_id2);
     unwrap!(create_file(
         &authenticator,
         app_container_md.clone(),
@@ -951,10 +957,7 @@ fn revocation_with_unencrypted_container_entries() {
         .ins(shared_key.clone(), shared_content.clone(), 0)
         .into();
 
-    let dedicated_info = unwrap!(get_container_from_authenticator_entry(
-            &auth,
-            &app_container_name(&app_id),
-        ));
+    let dedicated_info = create_app_container(&auth, &app_id);
     let dedicated_info2 = dedicated_info.clone();
     let dedicated_key = b"dedicated-key".to_vec();
     let dedicated_content = b"dedicated-value".to_vec();
@@ -100,7 +100,8 @@ fn read_data() {
     unwrap!(auth.send(move |client| {
         let client = client.clone();
 
-        // Read access container and ensure all standard containers exists.
+        // Read access container and ensure all standard containers exists. Those introduced
+        // after the data had been written, e.g. `_contacts`, are added on login.
         access_container::fetch_authenticator_entry(&client)
             .then(move |res| {
                 let (_, containers) = unwrap!(res);
@@ -209,6 +210,9 @@ fn setup() -> (Stash, PathBuf) {
             scope: None,
             name: "test-app-0".to_string(),
             vendor: "test-vendor-0".to_string(),
+            description: None,
+            homepage: None,
+            icon: None,
         };
 
         AuthReq {
@@ -224,6 +228,9 @@ fn setup() -> (Stash, PathBuf) {
             scope: None,
             name: "test-app-1".to_string(),
             vendor: "test-vendor-1".to_string(),
+            description: None,
+            homepage: None,
+            icon: None,
         };
 
         AuthReq {
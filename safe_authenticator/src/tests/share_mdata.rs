@@ -18,20 +18,24 @@
 use super::utils::{Payload, decode_ipc_msg};
 use errors::{AuthError, ERR_INVALID_OWNER, ERR_SHARE_MDATA_DENIED};
 use ffi::apps::*;
-use ffi::ipc::encode_share_mdata_resp;
+use ffi::ipc::{encode_combined_resp, encode_share_mdata_resp};
 use ffi_utils::FfiResult;
-use ffi_utils::test_utils::{call_vec, send_via_user_data, sender_as_user_data};
+use ffi_utils::test_utils::{call_1, call_vec, send_via_user_data, sender_as_user_data};
 use futures::Future;
 use maidsafe_utilities::serialisation::serialise;
 use rand;
 use routing::{Action, MutableData, PermissionSet, User, Value};
 use rust_sodium::crypto::sign;
-use safe_core::ipc::{self, AuthReq, IpcMsg, IpcReq, ShareMData, ShareMDataReq};
+use safe_core::MDataInfo;
+use safe_core::entry_acl::{self, EntryAcl};
+use safe_core::ipc::{self, AuthReq, CombinedReq, IpcMsg, IpcReq, IpcResp, ShareMData,
+                     ShareMDataReq};
 use safe_core::ipc::req::AppExchangeInfo;
 use safe_core::ipc::resp::{AppAccess, METADATA_KEY, UserMetadata};
 use std::collections::BTreeMap;
 use std::ffi::CStr;
 use std::os::raw::{c_char, c_void};
+use std::slice;
 use std::sync::mpsc;
 use std::time::Duration;
 use test_utils::{create_account_and_login, rand_app, register_app, run};
@@ -190,7 +194,7 @@ fn share_some_mdatas_with_valid_metadata() {
         req_id: req_id,
         req: IpcReq::ShareMData(req.clone()),
     };
-    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+    let encoded_msg = unwrap!(ipc::encode_msg_with_mac(&msg, &app_auth.app_keys.enc_key));
 
     let decoded = unwrap!(decode_ipc_msg(&authenticator, &encoded_msg));
     match decoded {
@@ -420,7 +424,7 @@ fn auth_apps_accessing_mdatas() {
             req_id: req_id,
             req: IpcReq::ShareMData(req.clone()),
         };
-        let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+        let encoded_msg = unwrap!(ipc::encode_msg_with_mac(&msg, &app_auth.app_keys.enc_key));
 
         let decoded = unwrap!(decode_ipc_msg(&authenticator, &encoded_msg));
 
@@ -491,6 +495,69 @@ fn auth_apps_accessing_mdatas() {
     }
 }
 
+// Test that the entry-level ACL of an MD is listed by `auth_entry_acl`, with the apps not
+// registered in the authenticator left anonymous.
+#[test]
+fn entry_acl_display() {
+    extern "C" fn cb(
+        user_data: *mut c_void,
+        result: *const FfiResult,
+        rules: *const EntryAclRule,
+        rules_len: usize,
+    ) {
+        let rules = unsafe {
+            assert_eq!((*result).error_code, 0);
+            slice::from_raw_parts(rules, rules_len)
+                .iter()
+                .map(|rule| {
+                    let prefix = slice::from_raw_parts(rule.prefix, rule.prefix_len).to_vec();
+                    (prefix, rule.app_access.sign_key, rule.app_access.app_id.is_null())
+                })
+                .collect::<Vec<_>>()
+        };
+        unsafe { send_via_user_data(user_data, rules) }
+    }
+
+    let authenticator = create_account_and_login();
+    let (app_key, _) = sign::gen_keypair();
+    let name = rand::random();
+    let tag = 15_002;
+
+    run(&authenticator, move |client| {
+        let client2 = client.clone();
+        let info = MDataInfo::new_public(name, tag);
+        let owners = btree_set![unwrap!(client.owner_key())];
+        let mdata = unwrap!(MutableData::new(name, tag, btree_map![], btree_map![], owners));
+
+        let mut acl = EntryAcl::new();
+        acl.set(
+            b"photos/".to_vec(),
+            app_key,
+            PermissionSet::new().allow(Action::Insert),
+        );
+
+        client
+            .put_mdata(mdata)
+            .and_then(move |_| entry_acl::store(&client2, &info, &acl))
+            .map_err(AuthError::CoreError)
+    });
+
+    let (tx, rx) = mpsc::channel::<Vec<(Vec<u8>, [u8; sign::PUBLICKEYBYTES], bool)>>();
+    let mut ud = Default::default();
+    unsafe {
+        auth_entry_acl(
+            &authenticator,
+            &name.0,
+            tag,
+            sender_as_user_data(&tx, &mut ud),
+            cb,
+        );
+    }
+
+    let rules = unwrap!(rx.recv_timeout(Duration::from_secs(30)));
+    assert_eq!(rules, vec![(b"photos/".to_vec(), app_key.0, true)]);
+}
+
 extern "C" fn encode_share_mdata_cb(
     user_data: *mut c_void,
     result: *const FfiResult,
@@ -517,3 +584,122 @@ extern "C" fn encode_share_mdata_cb(
         send_via_user_data::<Result<(), (i32, String)>>(user_data, ret);
     }
 }
+
+// Test granting a combined request.
+// 1. Request authentication together with shared access to an owned mdata.
+// 2. The request should be decoded with the mdata metadata.
+// 3. Granting it should register the app and give it access to the mdata.
+// 4. A combined request for mdata which isn't owned by the user should fail to decode.
+#[test]
+fn combined_request() {
+    let authenticator = create_account_and_login();
+
+    let user = run(&authenticator, move |client| {
+        client.public_signing_key().map_err(AuthError::CoreError)
+    });
+
+    let name = rand::random();
+    let tag = 15_000;
+    let perms = PermissionSet::new().allow(Action::Insert);
+    let mdata = unwrap!(MutableData::new(
+        name,
+        tag,
+        btree_map![],
+        btree_map![],
+        btree_set![user],
+    ));
+    run(&authenticator, move |client| {
+        client.put_mdata(mdata).map_err(AuthError::CoreError)
+    });
+
+    let app = rand_app();
+    let req = CombinedReq {
+        auth: AuthReq {
+            app: app.clone(),
+            app_container: false,
+            containers: Default::default(),
+        },
+        mdata: vec![
+            ShareMData {
+                type_tag: tag,
+                name: name,
+                perms: perms,
+            },
+        ],
+    };
+    let req_id = ipc::gen_req_id();
+    let msg = IpcMsg::Req {
+        req_id: req_id,
+        req: IpcReq::Combined(req.clone()),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match unwrap!(decode_ipc_msg(&authenticator, &encoded_msg)) {
+        (IpcMsg::Req { req: IpcReq::Combined(decoded_req), .. },
+         Some(Payload::Metadata(metadatas))) => {
+            assert_eq!(decoded_req, req);
+            assert_eq!(metadatas, vec![(None, name, tag)]);
+        }
+        x => panic!("Unexpected: {:?}", x),
+    };
+
+    let req_c = unwrap!(req.into_repr_c());
+    let encoded_resp: String = unsafe {
+        unwrap!(call_1(|ud, cb| {
+            encode_combined_resp(&authenticator, &req_c, req_id, true, ud, cb)
+        }))
+    };
+
+    let app_key = match unwrap!(ipc::decode_msg(&encoded_resp)) {
+        IpcMsg::Resp { resp: IpcResp::Combined(Ok(auth_granted)), .. } => {
+            auth_granted.app_keys.sign_pk
+        }
+        x => panic!("Unexpected: {:?}", x),
+    };
+
+    let app_access: Vec<AppAccess> = unsafe {
+        unwrap!(call_vec(|ud, cb| {
+            auth_apps_accessing_mutable_data(&authenticator, &name.0, tag, ud, cb)
+        }))
+    };
+    let access = unwrap!(app_access.iter().find(|access| access.sign_key == app_key));
+    assert_eq!(access.permissions, perms);
+    assert_eq!(access.app_id, Some(app.id.clone()));
+
+    // Request shared access to mdata owned by someone else.
+    let name = rand::random();
+    let mdata = unwrap!(MutableData::new(
+        name,
+        tag,
+        btree_map![],
+        btree_map![],
+        btree_set![sign::gen_keypair().0],
+    ));
+    run(&authenticator, move |client| {
+        client.put_mdata(mdata).map_err(AuthError::CoreError)
+    });
+
+    let msg = IpcMsg::Req {
+        req_id: ipc::gen_req_id(),
+        req: IpcReq::Combined(CombinedReq {
+            auth: AuthReq {
+                app: rand_app(),
+                app_container: false,
+                containers: Default::default(),
+            },
+            mdata: vec![
+                ShareMData {
+                    type_tag: tag,
+                    name: name,
+                    perms: perms,
+                },
+            ],
+        }),
+    };
+    let encoded_msg = unwrap!(ipc::encode_msg(&msg));
+
+    match decode_ipc_msg(&authenticator, &encoded_msg) {
+        Err((ERR_INVALID_OWNER, None)) => (),
+        x => panic!("Unexpected: {:?}", x),
+    };
+}
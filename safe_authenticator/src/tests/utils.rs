@@ -19,10 +19,12 @@ use Authenticator;
 use ffi_utils::{FfiResult, ReprC, vec_clone_from_raw_parts};
 use ffi_utils::test_utils::{send_via_user_data, sender_as_user_data};
 use routing::XorName;
-use safe_core::ffi::ipc::req::{AuthReq as FfiAuthReq, ContainersReq as FfiContainersReq,
+use safe_core::ffi::ipc::req::{AuthReq as FfiAuthReq, CombinedReq as FfiCombinedReq,
+                               ContainersReq as FfiContainersReq,
                                ShareMDataReq as FfiShareMDataReq};
 use safe_core::ffi::ipc::resp::MetadataResponse as FfiUserMetadata;
-use safe_core::ipc::{self, AuthReq, ContainersReq, IpcMsg, IpcReq, Permission, ShareMDataReq};
+use safe_core::ipc::{self, AuthReq, CombinedReq, ContainersReq, IpcMsg, IpcReq, Permission,
+                     ShareMDataReq};
 use safe_core::ipc::req::ContainerPermissions;
 use safe_core::ipc::resp::UserMetadata;
 use std::collections::HashMap;
@@ -107,20 +109,7 @@ pub fn decode_ipc_msg(authenticator: &Authenticator, msg: &str) -> ChannelType {
                 Err(_) => return send_via_user_data::<ChannelType>(user_data, Err((-2, None))),
             };
 
-            let metadatas: Vec<_> = slice::from_raw_parts(ffi_metadatas, req.mdata.len())
-                .iter()
-                .map(|ffi_metadata| {
-                    (
-                        if ffi_metadata.name.is_null() {
-                            None
-                        } else {
-                            Some(unwrap!(UserMetadata::clone_from_repr_c(ffi_metadata)))
-                        },
-                        XorName(ffi_metadata.xor_name),
-                        ffi_metadata.type_tag,
-                    )
-                })
-                .collect();
+            let metadatas = metadata_clone_from_repr_c(ffi_metadatas, req.mdata.len());
 
             let msg = IpcMsg::Req {
                 req_id: req_id,
@@ -134,12 +123,38 @@ pub fn decode_ipc_msg(authenticator: &Authenticator, msg: &str) -> ChannelType {
         }
     }
 
+    extern "C" fn combined_cb(
+        user_data: *mut c_void,
+        req_id: u32,
+        req: *const FfiCombinedReq,
+        ffi_metadatas: *const FfiUserMetadata,
+    ) {
+        unsafe {
+            let req = match CombinedReq::clone_from_repr_c(req) {
+                Ok(req) => req,
+                Err(_) => return send_via_user_data::<ChannelType>(user_data, Err((-2, None))),
+            };
+
+            let metadatas = metadata_clone_from_repr_c(ffi_metadatas, req.mdata.len());
+
+            let msg = IpcMsg::Req {
+                req_id: req_id,
+                req: IpcReq::Combined(req),
+            };
+
+            send_via_user_data::<ChannelType>(
+                user_data,
+                Ok((msg, Some(Payload::Metadata(metadatas)))),
+            )
+        }
+    }
+
     let ffi_msg = unwrap!(CString::new(msg));
     let mut ud = Default::default();
 
     unsafe {
-        use ffi::ipc::auth_decode_ipc_msg;
-        auth_decode_ipc_msg(
+        use ffi::ipc::auth_decode_ipc_msg_v2;
+        auth_decode_ipc_msg_v2(
             authenticator,
             ffi_msg.as_ptr(),
             sender_as_user_data(&tx, &mut ud),
@@ -147,7 +162,9 @@ pub fn decode_ipc_msg(authenticator: &Authenticator, msg: &str) -> ChannelType {
             containers_cb,
             unregistered_cb,
             share_mdata_cb,
+            combined_cb,
             err_cb,
+            auto_resp_cb,
         );
     };
 
@@ -159,6 +176,26 @@ pub fn decode_ipc_msg(authenticator: &Authenticator, msg: &str) -> ChannelType {
     ret
 }
 
+unsafe fn metadata_clone_from_repr_c(
+    ffi_metadatas: *const FfiUserMetadata,
+    len: usize,
+) -> Vec<(Option<UserMetadata>, XorName, u64)> {
+    slice::from_raw_parts(ffi_metadatas, len)
+        .iter()
+        .map(|ffi_metadata| {
+            (
+                if ffi_metadata.name.is_null() {
+                    None
+                } else {
+                    Some(unwrap!(UserMetadata::clone_from_repr_c(ffi_metadata)))
+                },
+                XorName(ffi_metadata.xor_name),
+                ffi_metadata.type_tag,
+            )
+        })
+        .collect()
+}
+
 pub extern "C" fn unregistered_cb(
     user_data: *mut c_void,
     req_id: u32,
@@ -175,6 +212,18 @@ pub extern "C" fn unregistered_cb(
     }
 }
 
+pub extern "C" fn auto_resp_cb(user_data: *mut c_void, _req_id: u32, response: *const c_char) {
+    unsafe {
+        let response = CStr::from_ptr(response);
+        let result = match ipc::decode_msg(unwrap!(response.to_str())) {
+            Ok(msg) => Ok((msg, None)),
+            Err(_) => Err((-2, None)),
+        };
+
+        send_via_user_data::<ChannelType>(user_data, result)
+    }
+}
+
 pub extern "C" fn err_cb(user_data: *mut c_void, res: *const FfiResult, response: *const c_char) {
     unsafe {
         let ipc_resp = if response.is_null() {
@@ -0,0 +1,230 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Per-app throttling of incoming auth requests.
+//!
+//! An app which keeps sending auth requests gets them denied for a while instead
+//! of the user being flooded with prompts. The back-off period doubles every time
+//! the app exceeds the limit again.
+//!
+//! App IDs are chosen by the apps, so the number of apps tracked is capped. Apps
+//! whose records have nothing left to enforce are forgotten first, then the least
+//! recently active ones.
+
+use std::cmp;
+use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Maximum number of auth requests an app can make within `THROTTLE_WINDOW_SECS`.
+pub const MAX_AUTH_REQS_PER_WINDOW: usize = 5;
+/// Length of the sliding window in which requests are counted.
+pub const THROTTLE_WINDOW_SECS: u64 = 60;
+/// Back-off period applied when an app exceeds the limit for the first time.
+pub const INITIAL_BACKOFF_SECS: u64 = 30;
+/// Upper bound of the back-off period.
+pub const MAX_BACKOFF_SECS: u64 = 60 * 60;
+/// Maximum number of apps whose requests are tracked at once.
+pub const MAX_TRACKED_APPS: usize = 1024;
+
+/// Keeps track of recent auth requests per app ID.
+#[derive(Default)]
+pub struct AuthThrottle {
+    apps: HashMap<String, AppRecord>,
+}
+
+#[derive(Default)]
+struct AppRecord {
+    requests: VecDeque<Instant>,
+    blocked_until: Option<Instant>,
+    backoff: Option<Duration>,
+}
+
+impl AuthThrottle {
+    /// Create a throttle with no requests recorded.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Record an auth request from the given app. Returns `Err` with the remaining
+    /// back-off period if the request should be denied.
+    pub fn check(&mut self, app_id: &str) -> Result<(), Duration> {
+        self.check_at(app_id, Instant::now())
+    }
+
+    fn check_at(&mut self, app_id: &str, now: Instant) -> Result<(), Duration> {
+        let window = Duration::from_secs(THROTTLE_WINDOW_SECS);
+        if !self.apps.contains_key(app_id) {
+            self.prune(now, window);
+        }
+        let record = self.apps.entry(app_id.to_owned()).or_insert_with(
+            Default::default,
+        );
+
+        if let Some(blocked_until) = record.blocked_until {
+            if now < blocked_until {
+                return Err(blocked_until - now);
+            }
+            if now.duration_since(blocked_until) >= window {
+                // The app has behaved for a whole window since the back-off
+                // ended, so the next violation starts from the initial period.
+                record.blocked_until = None;
+                record.backoff = None;
+            }
+        }
+
+        while let Some(&oldest) = record.requests.front() {
+            if now.duration_since(oldest) < window {
+                break;
+            }
+            let _ = record.requests.pop_front();
+        }
+
+        if record.requests.len() >= MAX_AUTH_REQS_PER_WINDOW {
+            let backoff = match record.backoff {
+                Some(backoff) => cmp::min(backoff * 2, Duration::from_secs(MAX_BACKOFF_SECS)),
+                None => Duration::from_secs(INITIAL_BACKOFF_SECS),
+            };
+            record.requests.clear();
+            record.backoff = Some(backoff);
+            record.blocked_until = Some(now + backoff);
+            return Err(backoff);
+        }
+
+        record.requests.push_back(now);
+        Ok(())
+    }
+
+    // Makes room for another app once `MAX_TRACKED_APPS` are tracked.
+    fn prune(&mut self, now: Instant, window: Duration) {
+        if self.apps.len() < MAX_TRACKED_APPS {
+            return;
+        }
+
+        self.apps.retain(|_, record| !record.is_idle(now, window));
+
+        while self.apps.len() >= MAX_TRACKED_APPS {
+            let least_active = self.apps
+                .iter()
+                .min_by_key(|&(_, record)| record.last_active())
+                .map(|(app_id, _)| app_id.clone());
+            match least_active {
+                Some(app_id) => {
+                    let _ = self.apps.remove(&app_id);
+                }
+                None => break,
+            }
+        }
+    }
+}
+
+impl AppRecord {
+    // Whether forgetting the record wouldn't change any future decision: none of the
+    // requests count any more and the back-off would be reset.
+    fn is_idle(&self, now: Instant, window: Duration) -> bool {
+        let requests_expired = self.requests.back().map_or(true, |&last| {
+            now >= last && now.duration_since(last) >= window
+        });
+        let backoff_expired = self.blocked_until.map_or(true, |blocked_until| {
+            now >= blocked_until && now.duration_since(blocked_until) >= window
+        });
+        requests_expired && backoff_expired
+    }
+
+    fn last_active(&self) -> Option<Instant> {
+        cmp::max(self.requests.back().cloned(), self.blocked_until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that an app gets throttled after too many requests, and that the
+    // back-off period grows with repeated violations.
+    #[test]
+    fn throttle_and_backoff() {
+        let mut throttle = AuthThrottle::new();
+        let start = Instant::now();
+
+        for _ in 0..MAX_AUTH_REQS_PER_WINDOW {
+            unwrap!(throttle.check_at("app", start));
+        }
+
+        // Other apps are not affected.
+        unwrap!(throttle.check_at("other-app", start));
+
+        let initial = Duration::from_secs(INITIAL_BACKOFF_SECS);
+        assert_eq!(throttle.check_at("app", start), Err(initial));
+
+        // Still blocked before the back-off ends.
+        let later = start + Duration::from_secs(1);
+        assert_eq!(
+            throttle.check_at("app", later),
+            Err(initial - Duration::from_secs(1))
+        );
+
+        // Allowed again afterwards, but a repeated violation doubles the back-off.
+        let after = start + initial;
+        for _ in 0..MAX_AUTH_REQS_PER_WINDOW {
+            unwrap!(throttle.check_at("app", after));
+        }
+        assert_eq!(throttle.check_at("app", after), Err(initial * 2));
+
+        // Once the app has behaved for a whole window the back-off is reset.
+        let much_later = after + initial * 2 + Duration::from_secs(THROTTLE_WINDOW_SECS);
+        for _ in 0..MAX_AUTH_REQS_PER_WINDOW {
+            unwrap!(throttle.check_at("app", much_later));
+        }
+        assert_eq!(throttle.check_at("app", much_later), Err(initial));
+    }
+
+    // Test that the number of tracked apps is capped, forgetting idle apps first and then
+    // the least recently active ones.
+    #[test]
+    fn tracked_apps_cap() {
+        let mut throttle = AuthThrottle::new();
+        let start = Instant::now();
+
+        for _ in 0..(MAX_AUTH_REQS_PER_WINDOW + 1) {
+            let _ = throttle.check_at("blocked", start);
+        }
+        for i in 1..MAX_TRACKED_APPS {
+            unwrap!(throttle.check_at(&format!("app-{}", i), start));
+        }
+        assert_eq!(throttle.apps.len(), MAX_TRACKED_APPS);
+
+        // Once their requests have left the window, idle apps are forgotten, but the back-off
+        // of the blocked app is still remembered.
+        let later = start + Duration::from_secs(THROTTLE_WINDOW_SECS);
+        unwrap!(throttle.check_at("new-app", later));
+        assert_eq!(throttle.apps.len(), 2);
+        assert!(throttle.apps.contains_key("blocked"));
+
+        // Without idle apps, the least recently active one is forgotten, which is the one
+        // blocked until half way through the window.
+        for i in 2..MAX_TRACKED_APPS {
+            let at = later + Duration::from_millis(i as u64);
+            unwrap!(throttle.check_at(&format!("app-{}", i), at));
+        }
+        assert_eq!(throttle.apps.len(), MAX_TRACKED_APPS);
+        let at = later + Duration::from_millis(MAX_TRACKED_APPS as u64);
+        unwrap!(throttle.check_at("newest-app", at));
+        assert_eq!(throttle.apps.len(), MAX_TRACKED_APPS);
+        assert!(!throttle.apps.contains_key("blocked"));
+        assert!(throttle.apps.contains_key("new-app"));
+    }
+}
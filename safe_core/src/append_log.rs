@@ -0,0 +1,379 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Append-only logs.
+//!
+//! A log is a `MutableData` (with the `APPEND_LOG_TAG` type tag) whose head entry holds the
+//! number of entries and the entries appended since the last full chunk. Once `CHUNK_LEN`
+//! entries accumulate in the head, they're sealed into an `ImmutableData` chunk and a
+//! pointer to it is added to the log, so entries never change once written. Entries and
+//! chunks of a private log are encrypted.
+//!
+//! Every chunk still takes an entry of the `MutableData`, so a log can't grow forever: once
+//! `stats` shows it close to the limits of the network, new entries should go to a new log.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use futures::future;
+use immutable_data;
+use maidsafe_utilities::serialisation::{deserialise, serialise, serialised_size};
+use routing::{Action, ClientError, EntryActions, MutableData, PermissionSet, User, Value, XorName};
+use rust_sodium::crypto::sign;
+use std::collections::BTreeSet;
+use utils::FutureExt;
+use APPEND_LOG_TAG;
+
+/// Number of entries sealed into a single chunk.
+pub const CHUNK_LEN: u64 = 16;
+
+/// Maximum size of a single entry, in bytes.
+pub const MAX_ENTRY_LEN: usize = 4 * 1024;
+
+const HEAD_KEY: &[u8] = b"head";
+const CHUNK_KEY_PREFIX: &str = "chunk:";
+
+#[derive(Serialize, Deserialize)]
+struct Head {
+    len: u64,
+    // Entries which are not part of any chunk yet.
+    tail: Vec<Vec<u8>>,
+}
+
+/// Version, owners and size of a log, as returned by `stats`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct LogStats {
+    /// Version of the head of the log, incremented by every append.
+    pub version: u64,
+    /// Owners of the log.
+    pub owners: BTreeSet<sign::PublicKey>,
+    /// Approximate size of the log's `MutableData` when serialised, in bytes, to be compared
+    /// with `limits::MAX_MDATA_SIZE`.
+    pub size: u64,
+    /// Number of entries in the log's `MutableData`: the head and one per sealed chunk, to
+    /// be compared with `limits::MAX_MDATA_ENTRIES`.
+    pub mdata_entries: u64,
+}
+
+/// Creates a new empty log and returns its `MDataInfo`.
+pub fn create<T: 'static>(client: &Client<T>, private: bool) -> Box<CoreFuture<MDataInfo>> {
+    let owner_key = fry!(client.owner_key());
+    let sign_key = fry!(client.public_signing_key());
+    let log = fry!(if private {
+        MDataInfo::random_private(APPEND_LOG_TAG)
+    } else {
+        MDataInfo::random_public(APPEND_LOG_TAG)
+    });
+
+    let head = Head {
+        len: 0,
+        tail: Vec::new(),
+    };
+    let key = fry!(log.enc_entry_key(HEAD_KEY));
+    let content = fry!(serialise(&head).map_err(CoreError::from).and_then(|head| {
+        log.enc_entry_value(&head)
+    }));
+
+    let log_md = fry!(MutableData::new(
+        log.name,
+        log.type_tag,
+        btree_map![
+            User::Key(sign_key) => PermissionSet::new()
+                .allow(Action::Insert)
+                .allow(Action::Update),
+        ],
+        btree_map![key => Value { content, entry_version: 0 }],
+        btree_set![owner_key],
+    ));
+
+    client.put_mdata(log_md).map(move |_| log).into_box()
+}
+
+/// Appends an entry to the log and returns its index.
+///
+/// Fails with `InvalidSuccessor` if another entry is appended concurrently, in
+/// which case the call can be retried.
+pub fn append<T: 'static>(
+    client: &Client<T>,
+    log: &MDataInfo,
+    entry: Vec<u8>,
+) -> Box<CoreFuture<u64>> {
+    if entry.len() > MAX_ENTRY_LEN {
+        return err!(CoreError::RoutingClientError(ClientError::DataTooLarge));
+    }
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let log = log.clone();
+
+    fetch_head(client, &log)
+        .and_then(move |(mut head, version)| {
+            let index = head.len;
+            head.len += 1;
+            head.tail.push(entry);
+
+            if head.tail.len() as u64 == CHUNK_LEN {
+                // Seal the tail into a chunk.
+                let chunk = fry!(serialise(&head.tail));
+                head.tail.clear();
+
+                immutable_data::create(&c2, &chunk, log.enc_key().cloned())
+                    .and_then(move |data| {
+                        let name = *data.name();
+                        c2.put_idata(data).map(move |_| Some(name))
+                    })
+                    .map(move |chunk| (log, head, version, index, chunk))
+                    .into_box()
+            } else {
+                ok!((log, head, version, index, None))
+            }
+        })
+        .and_then(move |(log, head, version, index, chunk)| {
+            let mut actions = EntryActions::new().update(
+                log.enc_entry_key(HEAD_KEY)?,
+                log.enc_entry_value(&serialise(&head)?)?,
+                version + 1,
+            );
+            if let Some(chunk) = chunk {
+                actions = actions.ins(
+                    chunk_key(&log, index / CHUNK_LEN)?,
+                    log.enc_entry_value(&serialise(&chunk)?)?,
+                    0,
+                );
+            }
+            Ok((log, actions, index))
+        })
+        .and_then(move |(log, actions, index)| {
+            c3.mutate_mdata_entries(log.name, log.type_tag, actions.into())
+                .map(move |_| index)
+        })
+        .into_box()
+}
+
+/// Gets the number of entries in the log.
+pub fn len<T: 'static>(client: &Client<T>, log: &MDataInfo) -> Box<CoreFuture<u64>> {
+    fetch_head(client, log).map(|(head, _)| head.len).into_box()
+}
+
+/// Reads entries with indices in the range `from..to`. The range is clamped to
+/// the length of the log.
+pub fn read_range<T: 'static>(
+    client: &Client<T>,
+    log: &MDataInfo,
+    from: u64,
+    to: u64,
+) -> Box<CoreFuture<Vec<Vec<u8>>>> {
+    let c2 = client.clone();
+    let log = log.clone();
+
+    fetch_head(client, &log)
+        .and_then(move |(head, _)| {
+            let to = to.min(head.len);
+            if from >= to {
+                return ok!(Vec::new());
+            }
+
+            let sealed = head.len - head.tail.len() as u64;
+            let first_chunk = from / CHUNK_LEN;
+            let sealed_to = to.min(sealed);
+            let chunks = (first_chunk..(sealed_to + CHUNK_LEN - 1) / CHUNK_LEN)
+                .map(|chunk| fetch_chunk(&c2, &log, chunk))
+                .collect::<Vec<_>>();
+            let tail = head.tail;
+
+            future::join_all(chunks)
+                .map(move |chunks| {
+                    chunks
+                        .into_iter()
+                        .flat_map(|chunk| chunk)
+                        .chain(tail)
+                        .skip((from - first_chunk * CHUNK_LEN) as usize)
+                        .take((to - from) as usize)
+                        .collect()
+                })
+                .into_box()
+        })
+        .into_box()
+}
+
+/// Gets the version, owners and size of the log. Fetches the whole `MutableData` of the log,
+/// but none of its chunks.
+pub fn stats<T: 'static>(client: &Client<T>, log: &MDataInfo) -> Box<CoreFuture<LogStats>> {
+    let key = fry!(log.enc_entry_key(HEAD_KEY));
+
+    client
+        .get_mdata(log.name, log.type_tag)
+        .and_then(move |data| {
+            let version = data.get(&key)
+                .map(|value| value.entry_version)
+                .ok_or(CoreError::RoutingClientError(ClientError::NoSuchEntry))?;
+
+            Ok(LogStats {
+                version: version,
+                owners: data.owners().clone(),
+                size: serialised_size(&data),
+                mdata_entries: data.entries().len() as u64,
+            })
+        })
+        .into_box()
+}
+
+fn fetch_head<T: 'static>(client: &Client<T>, log: &MDataInfo) -> Box<CoreFuture<(Head, u64)>> {
+    let log = log.clone();
+    let key = fry!(log.enc_entry_key(HEAD_KEY));
+
+    client
+        .get_mdata_value(log.name, log.type_tag, key)
+        .and_then(move |value| {
+            let head = deserialise(&log.decrypt(&value.content)?)?;
+            Ok((head, value.entry_version))
+        })
+        .into_box()
+}
+
+fn fetch_chunk<T: 'static>(
+    client: &Client<T>,
+    log: &MDataInfo,
+    chunk: u64,
+) -> Box<CoreFuture<Vec<Vec<u8>>>> {
+    let c2 = client.clone();
+    let log = log.clone();
+    let key = fry!(chunk_key(&log, chunk));
+
+    client
+        .get_mdata_value(log.name, log.type_tag, key)
+        .and_then(move |value| {
+            let name: XorName = deserialise(&log.decrypt(&value.content)?)?;
+            Ok((log, name))
+        })
+        .and_then(move |(log, name)| {
+            immutable_data::get_value(&c2, &name, log.enc_key().cloned())
+        })
+        .and_then(|chunk| Ok(deserialise(&chunk)?))
+        .into_box()
+}
+
+fn chunk_key(log: &MDataInfo, chunk: u64) -> Result<Vec<u8>, CoreError> {
+    log.enc_entry_key(format!("{}{}", CHUNK_KEY_PREFIX, chunk).as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::Loop;
+    use utils::test_utils::random_client;
+
+    const ENTRIES: u64 = CHUNK_LEN * 2 + 3;
+
+    // Test appending entries across several chunks and reading them back.
+    #[test]
+    fn append_read() {
+        for &private in &[false, true] {
+            random_client(move |client| {
+                let c2 = client.clone();
+                let c3 = client.clone();
+                let c4 = client.clone();
+                let c5 = client.clone();
+
+                create(client, private)
+                    .then(move |res| {
+                        let log = unwrap!(res);
+
+                        future::loop_fn(0, move |i| {
+                            let log2 = log.clone();
+                            append(&c2, &log, vec![i as u8]).map(move |index| {
+                                assert_eq!(index, i);
+                                if index + 1 < ENTRIES {
+                                    Loop::Continue(i + 1)
+                                } else {
+                                    Loop::Break(log2)
+                                }
+                            })
+                        })
+                    })
+                    .then(move |res| {
+                        let log = unwrap!(res);
+                        len(&c3, &log).map(move |len| (log, len))
+                    })
+                    .then(move |res| {
+                        let (log, len) = unwrap!(res);
+                        assert_eq!(len, ENTRIES);
+
+                        // Spans a chunk boundary and the tail.
+                        read_range(&c4, &log, CHUNK_LEN - 1, ENTRIES + 10)
+                            .map(move |entries| (log, entries))
+                    })
+                    .then(move |res| {
+                        let (log, entries) = unwrap!(res);
+                        let expected: Vec<_> = (CHUNK_LEN - 1..ENTRIES)
+                            .map(|i| vec![i as u8])
+                            .collect();
+                        assert_eq!(entries, expected);
+
+                        read_range(&c5, &log, 1, 3)
+                    })
+                    .map(|entries| {
+                        assert_eq!(entries, vec![vec![1], vec![2]]);
+                    })
+            });
+        }
+    }
+
+    // Test that the stats follow appends and chunk sealing.
+    #[test]
+    fn stats_track_appends() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let owner = unwrap!(client.owner_key());
+
+            create(client, true)
+                .then(move |res| {
+                    let log = unwrap!(res);
+                    stats(&c2, &log).map(move |stats| (log, stats))
+                })
+                .then(move |res| {
+                    let (log, initial) = unwrap!(res);
+                    assert_eq!(initial.version, 0);
+                    assert_eq!(initial.owners, btree_set![owner]);
+                    assert_eq!(initial.mdata_entries, 1);
+
+                    future::loop_fn(0, move |i| {
+                        let log2 = log.clone();
+                        let initial = initial.clone();
+                        append(&c3, &log, vec![0; 100]).map(move |_| if i + 1 < CHUNK_LEN {
+                            Loop::Continue(i + 1)
+                        } else {
+                            Loop::Break((log2, initial))
+                        })
+                    })
+                })
+                .then(move |res| {
+                    let (log, initial) = unwrap!(res);
+                    stats(&c4, &log).map(move |stats| (initial, stats))
+                })
+                .map(|(initial, stats)| {
+                    assert_eq!(stats.version, CHUNK_LEN);
+                    // The head and the first sealed chunk.
+                    assert_eq!(stats.mdata_entries, 2);
+                    assert!(stats.size > initial.size);
+                })
+        });
+    }
+}
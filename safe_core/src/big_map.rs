@@ -0,0 +1,508 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Sharded key-value store.
+//!
+//! A single `MutableData` can hold only about a thousand entries, so a big map spreads its
+//! entries over any number of shards. Every shard owns a prefix of bits of the SHA-3 hash of
+//! the keys it stores. When a shard nears the entry limit, it's split into two new shards
+//! owning the prefix extended by `0` and `1` respectively. The index `MutableData` maps the
+//! prefixes of the current shards to their `MDataInfo`s, and its own `MDataInfo` identifies
+//! the whole map.
+//!
+//! Concurrent writers are not coordinated: an entry written to a shard while that shard is
+//! being split by another client might not make it into the new shards.
+
+use client::{Client, MDataInfo, mdata_info};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use futures::future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{Action, ClientError, EntryActions, MutableData, PermissionSet, User, Value};
+use std::collections::BTreeMap;
+use tiny_keccak::sha3_256;
+use utils::FutureExt;
+use BIG_MAP_TAG;
+
+/// Number of entries at which a shard gets split.
+pub const SHARD_SPLIT_ENTRIES: usize = 800;
+
+// Index keys are this prefix followed by the bit prefix of the shard.
+const SHARD_KEY_PREFIX: &[u8] = b"shard:";
+
+#[derive(Clone)]
+struct Shard {
+    // Bit prefix as a string of `0`s and `1`s.
+    prefix: String,
+    info: MDataInfo,
+    // Version of the index entry pointing to this shard.
+    version: u64,
+}
+
+impl Shard {
+    fn owns(&self, hash: &[u8; 32]) -> bool {
+        self.prefix.chars().enumerate().all(|(i, bit)| {
+            (bit == '1') == key_bit(hash, i)
+        })
+    }
+}
+
+/// Creates a new empty map and returns `MDataInfo` of its index.
+/// Entries of a private map are encrypted.
+pub fn create<T: 'static>(client: &Client<T>, private: bool) -> Box<CoreFuture<MDataInfo>> {
+    let c2 = client.clone();
+
+    let index = fry!(new_info(private));
+    let shard = fry!(new_info(private));
+
+    let key = fry!(index.enc_entry_key(SHARD_KEY_PREFIX));
+    let content = fry!(serialise(&shard).map_err(CoreError::from).and_then(|shard| {
+        index.enc_entry_value(&shard)
+    }));
+    let index_md = fry!(new_mdata(
+        client,
+        &index,
+        btree_map![key => Value { content, entry_version: 0 }],
+    ));
+    let shard_md = fry!(new_mdata(client, &shard, btree_map![]));
+
+    client
+        .put_mdata(shard_md)
+        .and_then(move |_| c2.put_mdata(index_md))
+        .map(move |_| index)
+        .into_box()
+}
+
+/// Gets the value stored under `key`.
+/// Fails with `NoSuchEntry` if there is none.
+pub fn get<T: 'static>(
+    client: &Client<T>,
+    map: &MDataInfo,
+    key: &[u8],
+) -> Box<CoreFuture<Value>> {
+    let c2 = client.clone();
+    let key = key.to_vec();
+
+    find_shard(client, map, &key)
+        .and_then(move |shard| {
+            let enc_key = shard.info.enc_entry_key(&key)?;
+            Ok((shard, enc_key))
+        })
+        .and_then(move |(shard, enc_key)| {
+            c2.get_mdata_value(shard.info.name, shard.info.type_tag, enc_key)
+                .and_then(move |value| {
+                    // Empty entry means the value has been deleted.
+                    if value.content.is_empty() {
+                        return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+                    }
+                    Ok(Value {
+                        content: shard.info.decrypt(&value.content)?,
+                        entry_version: value.entry_version,
+                    })
+                })
+        })
+        .into_box()
+}
+
+/// Stores `value` under `key`, replacing the current value if there is one.
+/// The shard the key falls into is split if it has grown too big.
+pub fn put<T: 'static>(
+    client: &Client<T>,
+    map: &MDataInfo,
+    key: &[u8],
+    value: &[u8],
+) -> Box<CoreFuture<()>> {
+    put_with_limit(client, map, key, value, SHARD_SPLIT_ENTRIES)
+}
+
+/// Deletes the value stored under `key`.
+/// Fails with `NoSuchEntry` if there is none.
+pub fn delete<T: 'static>(client: &Client<T>, map: &MDataInfo, key: &[u8]) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let key = key.to_vec();
+
+    find_shard(client, map, &key)
+        .and_then(move |shard| {
+            let enc_key = shard.info.enc_entry_key(&key)?;
+            Ok((shard, enc_key))
+        })
+        .and_then(move |(shard, enc_key)| {
+            c2.get_mdata_value(shard.info.name, shard.info.type_tag, enc_key.clone())
+                .and_then(move |value| {
+                    if value.content.is_empty() {
+                        return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+                    }
+                    Ok((shard, enc_key, value.entry_version + 1))
+                })
+        })
+        .and_then(move |(shard, enc_key, version)| {
+            c3.mutate_mdata_entries(
+                shard.info.name,
+                shard.info.type_tag,
+                EntryActions::new().del(enc_key, version).into(),
+            )
+        })
+        .into_box()
+}
+
+/// Lists all entries of the map.
+pub fn list<T: 'static>(
+    client: &Client<T>,
+    map: &MDataInfo,
+) -> Box<CoreFuture<BTreeMap<Vec<u8>, Value>>> {
+    let c2 = client.clone();
+
+    fetch_shards(client, map)
+        .and_then(move |shards| {
+            let lists = shards.into_iter().map(move |shard| {
+                c2.list_mdata_entries(shard.info.name, shard.info.type_tag)
+                    .and_then(move |entries| decrypt_entries(&shard.info, entries))
+            });
+            future::join_all(lists)
+        })
+        .map(|lists| {
+            lists.into_iter().fold(BTreeMap::new(), |mut all, entries| {
+                all.extend(entries);
+                all
+            })
+        })
+        .into_box()
+}
+
+fn put_with_limit<T: 'static>(
+    client: &Client<T>,
+    map: &MDataInfo,
+    key: &[u8],
+    value: &[u8],
+    split_at: usize,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let c4 = client.clone();
+    let map = map.clone();
+    let key = key.to_vec();
+    let value = value.to_vec();
+
+    find_shard(client, &map, &key)
+        .and_then(move |shard| {
+            let enc_key = shard.info.enc_entry_key(&key)?;
+            let enc_value = shard.info.enc_entry_value(&value)?;
+            Ok((shard, enc_key, enc_value))
+        })
+        .and_then(move |(shard, enc_key, enc_value)| {
+            c2.get_mdata_value(shard.info.name, shard.info.type_tag, enc_key.clone())
+                .then(move |res| {
+                    let (actions, inserted) = match res {
+                        Ok(current) => (
+                            EntryActions::new().update(
+                                enc_key,
+                                enc_value,
+                                current.entry_version + 1,
+                            ),
+                            false,
+                        ),
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                            (EntryActions::new().ins(enc_key, enc_value, 0), true)
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    Ok((shard, actions, inserted))
+                })
+        })
+        .and_then(move |(shard, actions, inserted)| {
+            c3.mutate_mdata_entries(shard.info.name, shard.info.type_tag, actions.into())
+                .map(move |_| (shard, inserted))
+        })
+        .and_then(move |(shard, inserted)| if inserted {
+            let c5 = c4.clone();
+            c4.list_mdata_keys(shard.info.name, shard.info.type_tag)
+                .and_then(move |keys| if keys.len() >= split_at {
+                    split(&c5, &map, shard)
+                } else {
+                    ok!(())
+                })
+                .into_box()
+        } else {
+            ok!(())
+        })
+        .into_box()
+}
+
+// Moves the entries of the shard into two new shards and replaces it with them
+// in the index.
+fn split<T: 'static>(client: &Client<T>, map: &MDataInfo, shard: Shard) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let map = map.clone();
+    let private = shard.info.enc_key().is_some();
+    let depth = shard.prefix.len();
+
+    client
+        .list_mdata_entries(shard.info.name, shard.info.type_tag)
+        .and_then(move |entries| {
+            let entries = decrypt_entries(&shard.info, entries)?;
+            let mut halves = (BTreeMap::new(), BTreeMap::new());
+
+            for (key, value) in entries {
+                let hash = sha3_256(&key);
+                let _ = if key_bit(&hash, depth) {
+                    halves.1.insert(key, value)
+                } else {
+                    halves.0.insert(key, value)
+                };
+            }
+
+            let mut children = Vec::with_capacity(2);
+            for (bit, entries) in vec![("0", halves.0), ("1", halves.1)] {
+                let info = new_info(private)?;
+                let entries = mdata_info::encrypt_entries(&info, &entries)?;
+                let md = new_mdata(&c2, &info, entries)?;
+                let child = Shard {
+                    prefix: format!("{}{}", shard.prefix, bit),
+                    info: info,
+                    version: 0,
+                };
+                children.push((child, md));
+            }
+
+            Ok((shard, children))
+        })
+        .and_then(move |(shard, children)| {
+            let puts: Vec<_> = children
+                .iter()
+                .map(|&(_, ref md)| c3.put_mdata(md.clone()))
+                .collect();
+            let children: Vec<_> = children.into_iter().map(|(child, _)| child).collect();
+
+            future::join_all(puts).and_then(move |_| {
+                let mut actions = EntryActions::new().del(
+                    index_key(&map, &shard.prefix)?,
+                    shard.version + 1,
+                );
+                for child in &children {
+                    actions = actions.ins(
+                        index_key(&map, &child.prefix)?,
+                        map.enc_entry_value(&serialise(&child.info)?)?,
+                        0,
+                    );
+                }
+                Ok((map, actions))
+            })
+                .and_then(move |(map, actions)| {
+                    c3.mutate_mdata_entries(map.name, map.type_tag, actions.into())
+                })
+        })
+        .into_box()
+}
+
+fn find_shard<T: 'static>(
+    client: &Client<T>,
+    map: &MDataInfo,
+    key: &[u8],
+) -> Box<CoreFuture<Shard>> {
+    let hash = sha3_256(key);
+
+    fetch_shards(client, map)
+        .and_then(move |shards| {
+            shards.into_iter().find(|shard| shard.owns(&hash)).ok_or_else(|| {
+                CoreError::from("Big map index doesn't cover the key")
+            })
+        })
+        .into_box()
+}
+
+fn fetch_shards<T: 'static>(client: &Client<T>, map: &MDataInfo) -> Box<CoreFuture<Vec<Shard>>> {
+    let map = map.clone();
+
+    client
+        .list_mdata_entries(map.name, map.type_tag)
+        .and_then(move |entries| {
+            let mut shards = Vec::with_capacity(entries.len());
+
+            for (key, value) in decrypt_entries(&map, entries)? {
+                if !key.starts_with(SHARD_KEY_PREFIX) {
+                    continue;
+                }
+                let prefix = String::from_utf8(key[SHARD_KEY_PREFIX.len()..].to_vec())
+                    .map_err(|_| CoreError::from("Invalid big map index entry"))?;
+                shards.push(Shard {
+                    prefix: prefix,
+                    info: deserialise(&value.content)?,
+                    version: value.entry_version,
+                });
+            }
+
+            Ok(shards)
+        })
+        .into_box()
+}
+
+// Decrypts entries, skipping deleted ones.
+fn decrypt_entries(
+    info: &MDataInfo,
+    entries: BTreeMap<Vec<u8>, Value>,
+) -> Result<BTreeMap<Vec<u8>, Value>, CoreError> {
+    let mut output = BTreeMap::new();
+
+    for (key, value) in entries {
+        if value.content.is_empty() {
+            continue;
+        }
+        let _ = output.insert(
+            info.decrypt(&key)?,
+            Value {
+                content: info.decrypt(&value.content)?,
+                entry_version: value.entry_version,
+            },
+        );
+    }
+
+    Ok(output)
+}
+
+fn index_key(map: &MDataInfo, prefix: &str) -> Result<Vec<u8>, CoreError> {
+    let mut key = SHARD_KEY_PREFIX.to_vec();
+    key.extend_from_slice(prefix.as_bytes());
+    map.enc_entry_key(&key)
+}
+
+fn new_info(private: bool) -> Result<MDataInfo, CoreError> {
+    if private {
+        MDataInfo::random_private(BIG_MAP_TAG)
+    } else {
+        MDataInfo::random_public(BIG_MAP_TAG)
+    }
+}
+
+fn new_mdata<T: 'static>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    entries: BTreeMap<Vec<u8>, Value>,
+) -> Result<MutableData, CoreError> {
+    let owner_key = client.owner_key()?;
+    let sign_key = client.public_signing_key()?;
+
+    Ok(MutableData::new(
+        info.name,
+        info.type_tag,
+        btree_map![
+            User::Key(sign_key) => PermissionSet::new()
+                .allow(Action::Insert)
+                .allow(Action::Update)
+                .allow(Action::Delete),
+        ],
+        entries,
+        btree_set![owner_key],
+    )?)
+}
+
+fn key_bit(hash: &[u8; 32], index: usize) -> bool {
+    (hash[index / 8] >> (7 - index % 8)) & 1 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future::Loop;
+    use utils::test_utils::random_client;
+
+    // Test storing enough entries to split shards, then reading them back.
+    #[test]
+    fn put_get_split() {
+        for &private in &[false, true] {
+            random_client(move |client| {
+                let c2 = client.clone();
+                let c3 = client.clone();
+                let c4 = client.clone();
+                let c5 = client.clone();
+                let c6 = client.clone();
+
+                create(client, private)
+                    .then(move |res| {
+                        let map = unwrap!(res);
+
+                        future::loop_fn(0u8, move |i| {
+                            let map2 = map.clone();
+                            put_with_limit(&c2, &map, &[i], &[i, i], 4).map(move |_| if i < 19 {
+                                Loop::Continue(i + 1)
+                            } else {
+                                Loop::Break(map2)
+                            })
+                        })
+                    })
+                    .then(move |res| {
+                        let map = unwrap!(res);
+                        fetch_shards(&c3, &map).map(move |shards| (map, shards))
+                    })
+                    .then(move |res| {
+                        let (map, shards) = unwrap!(res);
+                        assert!(shards.len() > 1);
+
+                        put(&c4, &map, &[7], b"updated").map(move |_| map)
+                    })
+                    .then(move |res| {
+                        let map = unwrap!(res);
+                        delete(&c5, &map, &[8]).map(move |_| map)
+                    })
+                    .then(move |res| {
+                        let map = unwrap!(res);
+                        let c7 = c6.clone();
+                        get(&c6, &map, &[7]).map(move |value| (map, value, c7))
+                    })
+                    .then(move |res| {
+                        let (map, value, c7) = unwrap!(res);
+                        assert_eq!(value.content, b"updated");
+                        assert_eq!(value.entry_version, 1);
+
+                        list(&c7, &map)
+                    })
+                    .map(|entries| {
+                        assert_eq!(entries.len(), 19);
+                        for i in 0..20u8 {
+                            match i {
+                                7 => assert_eq!(entries[&vec![i]].content, b"updated"),
+                                8 => assert!(!entries.contains_key(&vec![i])),
+                                _ => assert_eq!(entries[&vec![i]].content, vec![i, i]),
+                            }
+                        }
+                    })
+            });
+        }
+    }
+
+    // Test that keys are assigned to shards by their hash prefix.
+    #[test]
+    fn shard_ownership() {
+        let info = unwrap!(MDataInfo::random_public(BIG_MAP_TAG));
+        let shard = |prefix: &str| {
+            Shard {
+                prefix: prefix.to_owned(),
+                info: info.clone(),
+                version: 0,
+            }
+        };
+
+        let hash = [0b1010_0000; 32];
+        assert!(shard("").owns(&hash));
+        assert!(shard("1").owns(&hash));
+        assert!(shard("101").owns(&hash));
+        assert!(!shard("0").owns(&hash));
+        assert!(!shard("11").owns(&hash));
+    }
+}
@@ -16,6 +16,7 @@
 // relating to use of the SAFE Network Software.
 
 use DIR_TAG;
+use argon2::{self, Variant, Version};
 use client::MDataInfo;
 use crypto::{shared_box, shared_secretbox, shared_sign};
 use errors::CoreError;
@@ -23,7 +24,81 @@ use maidsafe_utilities::serialisation::{deserialise, serialise};
 use routing::{FullId, XOR_NAME_LEN, XorName};
 use rust_sodium::crypto::{box_, pwhash, secretbox, sign};
 use rust_sodium::crypto::sign::Seed;
+use rust_sodium::utils::memzero;
 use tiny_keccak::sha3_256;
+use utils::migration::Format;
+use utils::secret::SecretBytes;
+
+/// Marks an account packet encrypted with a key derived by a configurable `Kdf`. Packets
+/// without it come from clients predating `Kdf` and use `Kdf::Scrypt`.
+const KDF_HEADER: &'static [u8] = b"SAFE-KDF";
+
+/// Prefixes the network id when deriving the location of the backup account packet.
+const BACKUP_ID_LABEL: &'static [u8] = b"SAFE-BACKUP";
+
+/// Format of the serialised `Account`, applied before encryption. Append a migration here
+/// when changing the layout of `Account`, and accounts get upgraded on their next login.
+pub const ACCOUNT_FORMAT: Format = Format {
+    header: b"SAFE-ACC",
+    migrations: &[],
+};
+
+/// scrypt cost of the network id and `Kdf::Scrypt` key derivation.
+#[cfg(not(feature = "fast-kdf"))]
+const SCRYPT_OPSLIMIT: pwhash::OpsLimit = pwhash::OPSLIMIT_INTERACTIVE;
+/// Builds explicitly opting into `fast-kdf` use the lowest cost libsodium accepts.
+/// Accounts created by them can't be logged into by regular builds and vice versa.
+#[cfg(feature = "fast-kdf")]
+const SCRYPT_OPSLIMIT: pwhash::OpsLimit = pwhash::OpsLimit(32_768);
+
+/// Default memory cost of `Kdf::Argon2id`, in KiB.
+pub const ARGON2ID_DEFAULT_MEM_COST: u32 = 64 * 1024;
+/// Default number of passes of `Kdf::Argon2id`.
+pub const ARGON2ID_DEFAULT_TIME_COST: u32 = 3;
+/// Default degree of parallelism of `Kdf::Argon2id`.
+pub const ARGON2ID_DEFAULT_LANES: u32 = 1;
+
+/// Key derivation function turning the user's credentials into the key the account packet
+/// is encrypted with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Kdf {
+    /// scrypt with the interactive limits. Packets encrypted with it can be read by all
+    /// clients, including those predating configurable KDFs.
+    Scrypt,
+    /// Argon2id with tunable cost parameters.
+    Argon2id {
+        /// Memory cost in KiB.
+        mem_cost: u32,
+        /// Number of passes.
+        time_cost: u32,
+        /// Degree of parallelism.
+        lanes: u32,
+    },
+}
+
+impl Kdf {
+    /// Argon2id with the default cost parameters.
+    pub fn argon2id() -> Self {
+        Kdf::Argon2id {
+            mem_cost: ARGON2ID_DEFAULT_MEM_COST,
+            time_cost: ARGON2ID_DEFAULT_TIME_COST,
+            lanes: ARGON2ID_DEFAULT_LANES,
+        }
+    }
+}
+
+impl Default for Kdf {
+    fn default() -> Self {
+        Kdf::Scrypt
+    }
+}
+
+// Account packet encrypted with a key derived by `kdf`, following `KDF_HEADER`.
+#[derive(Deserialize, Serialize)]
+struct VersionedPacket {
+    kdf: Kdf,
+    cipher_text: Vec<u8>,
+}
 
 /// Representing the User Account information on the network
 #[derive(Debug, PartialEq, Deserialize, Serialize)]
@@ -54,21 +129,74 @@ impl Account {
     /// Symmetric encryption of Account using User's credentials.
     /// Credentials are passed through key-derivation-function first
     pub fn encrypt(&self, password: &[u8], pin: &[u8]) -> Result<Vec<u8>, CoreError> {
-        let serialised_self = serialise(self)?;
-        let (key, nonce) = Self::generate_crypto_keys(password, pin)?;
+        self.encrypt_with_kdf(password, pin, Kdf::Scrypt)
+    }
 
-        Ok(secretbox::seal(&serialised_self, &nonce, &key))
+    /// Symmetric encryption of Account using User's credentials passed through the given
+    /// key-derivation-function. The function is recorded alongside the cipher text.
+    pub fn encrypt_with_kdf(
+        &self,
+        password: &[u8],
+        pin: &[u8],
+        kdf: Kdf,
+    ) -> Result<Vec<u8>, CoreError> {
+        let serialised_self = SecretBytes::from(serialise(self)?);
+        let serialised_self = SecretBytes::from(ACCOUNT_FORMAT.encode(&serialised_self));
+        let (key, nonce) = Self::generate_crypto_keys_with_kdf(password, pin, kdf)?;
+        let cipher_text = secretbox::seal(&serialised_self, &nonce, &key);
+
+        match kdf {
+            // Keep the format readable by older clients.
+            Kdf::Scrypt => Ok(cipher_text),
+            Kdf::Argon2id { .. } => {
+                let mut output = KDF_HEADER.to_vec();
+                output.extend_from_slice(&serialise(&VersionedPacket { kdf, cipher_text })?);
+                Ok(output)
+            }
+        }
     }
 
     /// Symmetric decryption of Account using User's credentials.
     /// Credentials are passed through key-derivation-function first
     pub fn decrypt(encrypted_self: &[u8], password: &[u8], pin: &[u8]) -> Result<Self, CoreError> {
-        let (key, nonce) = Self::generate_crypto_keys(password, pin)?;
-        let decrypted_self = secretbox::open(encrypted_self, &nonce, &key).map_err(|_| {
-            CoreError::SymmetricDecipherFailure
-        })?;
+        Self::decrypt_with_kdf(encrypted_self, password, pin).map(|(account, _)| account)
+    }
+
+    /// Symmetric decryption of Account using User's credentials. Returns the account along
+    /// with the key-derivation-function it was encrypted with.
+    pub fn decrypt_with_kdf(
+        encrypted_self: &[u8],
+        password: &[u8],
+        pin: &[u8],
+    ) -> Result<(Self, Kdf), CoreError> {
+        Self::decrypt_and_migrate(encrypted_self, password, pin)
+            .map(|(account, kdf, _)| (account, kdf))
+    }
+
+    /// Symmetric decryption of Account using User's credentials, upgrading older layouts
+    /// according to `ACCOUNT_FORMAT`. Returns the account, the key-derivation-function it was
+    /// encrypted with and whether it was upgraded, in which case the stored packet should be
+    /// replaced.
+    pub fn decrypt_and_migrate(
+        encrypted_self: &[u8],
+        password: &[u8],
+        pin: &[u8],
+    ) -> Result<(Self, Kdf, bool), CoreError> {
+        let (kdf, cipher_text) = if encrypted_self.starts_with(KDF_HEADER) {
+            let packet: VersionedPacket = deserialise(&encrypted_self[KDF_HEADER.len()..])?;
+            (packet.kdf, packet.cipher_text)
+        } else {
+            (Kdf::Scrypt, encrypted_self.to_vec())
+        };
+
+        let (key, nonce) = Self::generate_crypto_keys_with_kdf(password, pin, kdf)?;
+        let decrypted_self = secretbox::open(&cipher_text, &nonce, &key)
+            .map(SecretBytes::from)
+            .map_err(|_| CoreError::SymmetricDecipherFailure)?;
+        let (decrypted_self, migrated) = ACCOUNT_FORMAT.decode(&decrypted_self)?;
+        let decrypted_self = SecretBytes::from(decrypted_self);
 
-        Ok(deserialise(&decrypted_self)?)
+        Ok((deserialise(&decrypted_self)?, kdf, migrated))
     }
 
     /// Generate User's Identity for the network using supplied credentials in
@@ -80,6 +208,14 @@ impl Account {
         Ok(id)
     }
 
+    /// Location of the backup copy of the account packet, derived from the network id of
+    /// the account, so it can't be found without the user's credentials either.
+    pub fn generate_backup_network_id(network_id: &XorName) -> XorName {
+        let mut input = BACKUP_ID_LABEL.to_vec();
+        input.extend_from_slice(&network_id.0);
+        XorName(sha3_256(&input))
+    }
+
     fn generate_crypto_keys(
         password: &[u8],
         pin: &[u8],
@@ -87,11 +223,53 @@ impl Account {
         let mut output = [0; secretbox::KEYBYTES + secretbox::NONCEBYTES];
         Self::derive_key(&mut output[..], password, pin)?;
 
+        Ok(Self::split_crypto_keys(&mut output))
+    }
+
+    fn generate_crypto_keys_with_kdf(
+        password: &[u8],
+        pin: &[u8],
+        kdf: Kdf,
+    ) -> Result<(secretbox::Key, secretbox::Nonce), CoreError> {
+        let (mem_cost, time_cost, lanes) = match kdf {
+            Kdf::Scrypt => return Self::generate_crypto_keys(password, pin),
+            Kdf::Argon2id {
+                mem_cost,
+                time_cost,
+                lanes,
+            } => (mem_cost, time_cost, lanes),
+        };
+        // The recorded parameters are kept, but `fast-kdf` builds derive with the minimum cost.
+        #[cfg(feature = "fast-kdf")]
+        let (mem_cost, time_cost) = (mem_cost.min(8 * lanes), time_cost.min(1));
+
+        let mut output = [0; secretbox::KEYBYTES + secretbox::NONCEBYTES];
+        let config = argon2::Config {
+            variant: Variant::Argon2id,
+            version: Version::Version13,
+            mem_cost,
+            time_cost,
+            lanes,
+            hash_length: output.len() as u32,
+            ..argon2::Config::default()
+        };
+        let mut hash = argon2::hash_raw(password, &sha3_256(pin), &config)
+            .map_err(|_| CoreError::UnsuccessfulPwHash)?;
+        output.copy_from_slice(&hash);
+        memzero(&mut hash);
+
+        Ok(Self::split_crypto_keys(&mut output))
+    }
+
+    fn split_crypto_keys(
+        output: &mut [u8; secretbox::KEYBYTES + secretbox::NONCEBYTES],
+    ) -> (secretbox::Key, secretbox::Nonce) {
         // OK to unwrap here, as we guaranteed the slices have the correct length.
         let key = unwrap!(secretbox::Key::from_slice(&output[..secretbox::KEYBYTES]));
         let nonce = unwrap!(secretbox::Nonce::from_slice(&output[secretbox::KEYBYTES..]));
+        memzero(&mut output[..]);
 
-        Ok((key, nonce))
+        (key, nonce)
     }
 
     fn derive_key(output: &mut [u8], input: &[u8], user_salt: &[u8]) -> Result<(), CoreError> {
@@ -112,7 +290,7 @@ impl Account {
             output,
             input,
             &salt,
-            pwhash::OPSLIMIT_INTERACTIVE,
+            SCRYPT_OPSLIMIT,
             pwhash::MEMLIMIT_INTERACTIVE,
         ).map(|_| ())
             .map_err(|_| CoreError::UnsuccessfulPwHash)
@@ -175,6 +353,70 @@ mod tests {
     use maidsafe_utilities::serialisation::{deserialise, serialise};
     use std::u32;
 
+    // Test that builds not opting into `fast-kdf`, including plain test builds, use the
+    // full key derivation cost.
+    #[cfg(not(feature = "fast-kdf"))]
+    #[test]
+    fn production_kdf_params() {
+        assert_eq!(SCRYPT_OPSLIMIT.0, pwhash::OPSLIMIT_INTERACTIVE.0);
+    }
+
+    // Test encrypting with Argon2id and reading both new and legacy packets.
+    #[test]
+    fn kdf_versions() {
+        let account = unwrap!(Account::new(ClientKeys::new(None)));
+        let password = b"impossible to guess";
+        let pin = b"1000";
+        let kdf = Kdf::Argon2id {
+            mem_cost: 1024,
+            time_cost: 1,
+            lanes: 1,
+        };
+
+        let legacy = unwrap!(account.encrypt(password, pin));
+        let hardened = unwrap!(account.encrypt_with_kdf(password, pin, kdf));
+        assert!(!legacy.starts_with(KDF_HEADER));
+        assert!(hardened.starts_with(KDF_HEADER));
+
+        let (decrypted, decrypted_kdf) =
+            unwrap!(Account::decrypt_with_kdf(&legacy, password, pin));
+        assert_eq!(decrypted, account);
+        assert_eq!(decrypted_kdf, Kdf::Scrypt);
+
+        let (decrypted, decrypted_kdf) =
+            unwrap!(Account::decrypt_with_kdf(&hardened, password, pin));
+        assert_eq!(decrypted, account);
+        assert_eq!(decrypted_kdf, kdf);
+
+        assert!(Account::decrypt(&hardened, b"wrong password", pin).is_err());
+    }
+
+    // Test that current packets need no migration and packets from newer clients are rejected.
+    #[test]
+    fn format_versions() {
+        let account = unwrap!(Account::new(ClientKeys::new(None)));
+        let password = b"impossible to guess";
+        let pin = b"1000";
+
+        let encrypted = unwrap!(account.encrypt(password, pin));
+        let (decrypted, _, migrated) =
+            unwrap!(Account::decrypt_and_migrate(&encrypted, password, pin));
+        assert_eq!(decrypted, account);
+        assert!(!migrated);
+
+        let mut plaintext = ACCOUNT_FORMAT.header.to_vec();
+        plaintext.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 99]);
+        plaintext.extend_from_slice(&unwrap!(serialise(&account)));
+        let (key, nonce) =
+            unwrap!(Account::generate_crypto_keys_with_kdf(password, pin, Kdf::Scrypt));
+        let encrypted = secretbox::seal(&plaintext, &nonce, &key);
+
+        match Account::decrypt_and_migrate(&encrypted, password, pin) {
+            Err(CoreError::UnsupportedFormatVersion(99)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
     // Test deterministically generating User's Identity for the network using supplied credentials.
     #[test]
     fn generate_network_id() {
@@ -0,0 +1,618 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Routing client which leaves the network connection to the host environment.
+//!
+//! Instead of connecting to the network itself, this client serialises every request
+//! into a `HostRequest` and passes it to a transport registered with `set_transport`.
+//! The host is responsible for getting the request to the network (e.g. a browser app
+//! forwarding it to a gateway over a WebSocket) and hands the responses back through
+//! `deliver`.
+//!
+//! This is meant for hosts which would rather own the connection, e.g. to route it
+//! through a gateway. It's built and checked for the usual native targets only: the
+//! library doesn't compile to `wasm32-unknown-unknown`, as the event loop depends on
+//! `tokio-core` and other dependencies need the standard networking and threading
+//! support that target lacks.
+
+use errors::CoreError;
+use ffi_utils::OpaqueCtx;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use rand;
+use routing::{Authority, BootstrapConfig, EntryAction, Event, FullId, ImmutableData,
+              InterfaceError, MessageId, MutableData, PermissionSet, Request, Response,
+              RoutingError, User, XorName};
+use rust_sodium::crypto::sign;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::os::raw::c_void;
+use std::sync::{Mutex, PoisonError};
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+
+/// Callback receiving serialised `HostRequest`s: user data, pointer to the message
+/// and its length. The message is only valid for the duration of the call.
+pub type HostTransport = extern "C" fn(user_data: *mut c_void,
+                                       message: *const u8,
+                                       message_len: usize);
+
+/// Request passed to the host transport.
+#[derive(Serialize, Deserialize)]
+pub struct HostRequest {
+    /// Identifies the client which sent the request. Has to be copied into the
+    /// corresponding `HostResponse`.
+    pub client_id: u64,
+    /// Source authority.
+    pub src: Authority<XorName>,
+    /// Destination authority.
+    pub dst: Authority<XorName>,
+    /// The request itself.
+    pub request: Request,
+    /// Signature of the serialised `(src, dst, request)` tuple by the client's signing key.
+    pub signature: sign::Signature,
+}
+
+/// Response delivered by the host.
+#[derive(Serialize, Deserialize)]
+pub struct HostResponse {
+    /// `client_id` of the request being responded to.
+    pub client_id: u64,
+    /// Source authority.
+    pub src: Authority<XorName>,
+    /// Destination authority.
+    pub dst: Authority<XorName>,
+    /// The response itself.
+    pub response: Response,
+}
+
+#[derive(Clone, Copy)]
+struct Transport {
+    user_data: OpaqueCtx,
+    send: HostTransport,
+}
+
+lazy_static! {
+    static ref TRANSPORT: Mutex<Option<Transport>> = Mutex::new(None);
+    static ref CLIENTS: Mutex<HashMap<u64, Sender<Event>>> = Mutex::new(HashMap::new());
+}
+
+static NEXT_CLIENT_ID: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Registers the transport all requests are passed to. The callback can be called
+/// from any thread. Replaces any previously registered transport.
+pub fn set_transport(user_data: *mut c_void, send: HostTransport) {
+    let mut transport = TRANSPORT.lock().unwrap_or_else(PoisonError::into_inner);
+    *transport = Some(Transport {
+        user_data: OpaqueCtx(user_data),
+        send,
+    });
+}
+
+/// Delivers a serialised `HostResponse` to the client it's addressed to.
+pub fn deliver(message: &[u8]) -> Result<(), CoreError> {
+    let HostResponse {
+        client_id,
+        src,
+        dst,
+        response,
+    } = deserialise(message)?;
+
+    let clients = CLIENTS.lock().unwrap_or_else(PoisonError::into_inner);
+    let sender = clients.get(&client_id).ok_or_else(|| {
+        CoreError::Unexpected(format!("No client with ID {}", client_id))
+    })?;
+
+    sender
+        .send(Event::Response {
+            response: response,
+            src: src,
+            dst: dst,
+        })
+        .map_err(|_| CoreError::Unexpected(format!("Client {} has shut down", client_id)))
+}
+
+/// Notifies the client with the given ID that the host lost its connection to the
+/// network.
+pub fn disconnect(client_id: u64) {
+    let clients = CLIENTS.lock().unwrap_or_else(PoisonError::into_inner);
+    if let Some(sender) = clients.get(&client_id) {
+        let _ = sender.send(Event::Terminate);
+    }
+}
+
+/// Routing client passing the requests to the host transport.
+pub struct Routing {
+    client_id: u64,
+    sender: Sender<Event>,
+    full_id: FullId,
+    client_auth: Authority<XorName>,
+}
+
+impl Routing {
+    /// Initialises the client. Fails to connect if no transport has been registered.
+    /// The function signature mirrors `routing::Client`.
+    pub fn new(
+        sender: Sender<Event>,
+        id: Option<FullId>,
+        _bootstrap_config: Option<BootstrapConfig>,
+        _msg_expiry_dur: Duration,
+    ) -> Result<Self, RoutingError> {
+        let _ = ::rust_sodium::init();
+
+        let client_id = NEXT_CLIENT_ID.fetch_add(1, Ordering::SeqCst) as u64;
+        let full_id = id.unwrap_or_else(FullId::new);
+        let client_auth = Authority::Client {
+            client_id: *full_id.public_id(),
+            proxy_node_name: rand::random(),
+        };
+
+        let connected = TRANSPORT
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .is_some();
+        let _ = sender.send(if connected {
+            Event::Connected
+        } else {
+            Event::Terminate
+        });
+
+        let _ = CLIENTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(client_id, sender.clone());
+
+        Ok(Routing {
+            client_id,
+            sender,
+            full_id,
+            client_auth,
+        })
+    }
+
+    /// ID identifying this client in the `HostRequest`s.
+    pub fn client_id(&self) -> u64 {
+        self.client_id
+    }
+
+    /// Gets MAID account information.
+    pub fn get_account_info(
+        &mut self,
+        dst: Authority<XorName>,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::GetAccountInfo(msg_id))
+    }
+
+    /// Puts ImmutableData to the network.
+    pub fn put_idata(
+        &mut self,
+        dst: Authority<XorName>,
+        data: ImmutableData,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::PutIData { data, msg_id })
+    }
+
+    /// Fetches ImmutableData from the network by the given name.
+    pub fn get_idata(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::GetIData { name, msg_id })
+    }
+
+    /// Creates a new MutableData in the network.
+    pub fn put_mdata(
+        &mut self,
+        dst: Authority<XorName>,
+        data: MutableData,
+        msg_id: MessageId,
+        requester: sign::PublicKey,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::PutMData {
+                data,
+                msg_id,
+                requester,
+            },
+        )
+    }
+
+    /// Fetches a latest version number.
+    pub fn get_mdata_version(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::GetMDataVersion { name, tag, msg_id })
+    }
+
+    /// Fetches a complete MutableData object.
+    pub fn get_mdata(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::GetMData { name, tag, msg_id })
+    }
+
+    /// Fetches a shell of given MutableData.
+    pub fn get_mdata_shell(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::GetMDataShell { name, tag, msg_id })
+    }
+
+    /// Fetches a list of entries (keys + values).
+    pub fn list_mdata_entries(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::ListMDataEntries { name, tag, msg_id })
+    }
+
+    /// Fetches a list of keys in MutableData.
+    pub fn list_mdata_keys(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::ListMDataKeys { name, tag, msg_id })
+    }
+
+    /// Fetches a list of values in MutableData.
+    pub fn list_mdata_values(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::ListMDataValues { name, tag, msg_id })
+    }
+
+    /// Fetches a single value from MutableData
+    pub fn get_mdata_value(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        key: Vec<u8>,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::GetMDataValue {
+                name,
+                tag,
+                key,
+                msg_id,
+            },
+        )
+    }
+
+    /// Updates MutableData entries in bulk.
+    pub fn mutate_mdata_entries(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        actions: BTreeMap<Vec<u8>, EntryAction>,
+        msg_id: MessageId,
+        requester: sign::PublicKey,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::MutateMDataEntries {
+                name,
+                tag,
+                actions,
+                msg_id,
+                requester,
+            },
+        )
+    }
+
+    /// Fetches a complete list of permissions.
+    pub fn list_mdata_permissions(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::ListMDataPermissions { name, tag, msg_id })
+    }
+
+    /// Fetches a list of permissions for a particular User.
+    pub fn list_mdata_user_permissions(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        user: User,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::ListMDataUserPermissions {
+                name,
+                tag,
+                user,
+                msg_id,
+            },
+        )
+    }
+
+    /// Updates or inserts a list of permissions for a particular User in the given
+    /// MutableData.
+    pub fn set_mdata_user_permissions(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        user: User,
+        permissions: PermissionSet,
+        version: u64,
+        msg_id: MessageId,
+        requester: sign::PublicKey,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::SetMDataUserPermissions {
+                name,
+                tag,
+                user,
+                permissions,
+                version,
+                msg_id,
+                requester,
+            },
+        )
+    }
+
+    /// Deletes a list of permissions for a particular User in the given MutableData.
+    pub fn del_mdata_user_permissions(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        user: User,
+        version: u64,
+        msg_id: MessageId,
+        requester: sign::PublicKey,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::DelMDataUserPermissions {
+                name,
+                tag,
+                user,
+                version,
+                msg_id,
+                requester,
+            },
+        )
+    }
+
+    /// Sends an ownership transfer request.
+    pub fn change_mdata_owner(
+        &mut self,
+        dst: Authority<XorName>,
+        name: XorName,
+        tag: u64,
+        new_owners: BTreeSet<sign::PublicKey>,
+        version: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::ChangeMDataOwner {
+                name,
+                tag,
+                new_owners,
+                version,
+                msg_id,
+            },
+        )
+    }
+
+    /// Fetches a list of authorised keys and version in MaidManager.
+    pub fn list_auth_keys_and_version(
+        &mut self,
+        dst: Authority<XorName>,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(dst, Request::ListAuthKeysAndVersion(msg_id))
+    }
+
+    /// Adds a new authorised key to MaidManager.
+    pub fn ins_auth_key(
+        &mut self,
+        dst: Authority<XorName>,
+        key: sign::PublicKey,
+        version: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::InsAuthKey {
+                key,
+                version,
+                msg_id,
+            },
+        )
+    }
+
+    /// Removes an authorised key from MaidManager.
+    pub fn del_auth_key(
+        &mut self,
+        dst: Authority<XorName>,
+        key: sign::PublicKey,
+        version: u64,
+        msg_id: MessageId,
+    ) -> Result<(), InterfaceError> {
+        self.send(
+            dst,
+            Request::DelAuthKey {
+                key,
+                version,
+                msg_id,
+            },
+        )
+    }
+
+    /// Returns the default bootstrap config. Connecting is up to the host, so it isn't
+    /// used for anything.
+    pub fn bootstrap_config() -> Result<BootstrapConfig, InterfaceError> {
+        Ok(BootstrapConfig::default())
+    }
+
+    fn send(&self, dst: Authority<XorName>, request: Request) -> Result<(), InterfaceError> {
+        let transport = match *TRANSPORT.lock().unwrap_or_else(PoisonError::into_inner) {
+            Some(transport) => transport,
+            None => return Err(InterfaceError::NotConnected),
+        };
+
+        let src = self.client_auth;
+        let signature = {
+            let signed = serialise(&(&src, &dst, &request)).map_err(to_interface_error)?;
+            sign::sign_detached(&signed, self.full_id.signing_private_key())
+        };
+        let message = serialise(&HostRequest {
+            client_id: self.client_id,
+            src,
+            dst,
+            request,
+            signature,
+        }).map_err(to_interface_error)?;
+
+        (transport.send)(transport.user_data.0, message.as_ptr(), message.len());
+        Ok(())
+    }
+}
+
+impl Drop for Routing {
+    fn drop(&mut self) {
+        let _ = CLIENTS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&self.client_id);
+        let _ = self.sender.send(Event::Terminate);
+    }
+}
+
+fn to_interface_error<E: Into<RoutingError>>(err: E) -> InterfaceError {
+    InterfaceError::RoutingError(err.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+
+    // Test that requests are passed to the transport and responses delivered back
+    // to the client.
+    #[test]
+    fn round_trip() {
+        let sent: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+        let sent_ptr: *const _ = &sent;
+        set_transport(sent_ptr as *mut c_void, transport);
+
+        let (tx, rx) = mpsc::channel();
+        let full_id = FullId::new();
+        let public_key = *full_id.public_id().signing_public_key();
+        let mut routing = unwrap!(Routing::new(tx, Some(full_id), None, Duration::from_secs(0)));
+
+        match unwrap!(rx.recv()) {
+            Event::Connected => (),
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        let name: XorName = rand::random();
+        let msg_id = MessageId::new();
+        let dst = Authority::NaeManager(name);
+        unwrap!(routing.get_idata(dst, name, msg_id));
+
+        let request: HostRequest = {
+            let sent = unwrap!(sent.lock());
+            assert_eq!(sent.len(), 1);
+            unwrap!(deserialise(&sent[0]))
+        };
+        assert_eq!(request.client_id, routing.client_id());
+        assert_eq!(request.dst, dst);
+        assert_eq!(request.request, Request::GetIData { name, msg_id });
+
+        let signed = unwrap!(serialise(&(&request.src, &request.dst, &request.request)));
+        assert!(sign::verify_detached(
+            &request.signature,
+            &signed,
+            &public_key,
+        ));
+
+        let data = ImmutableData::new(vec![1, 2, 3]);
+        let response = unwrap!(serialise(&HostResponse {
+            client_id: request.client_id,
+            src: request.dst,
+            dst: request.src,
+            response: Response::GetIData {
+                res: Ok(data.clone()),
+                msg_id,
+            },
+        }));
+        unwrap!(deliver(&response));
+
+        match unwrap!(rx.recv()) {
+            Event::Response {
+                response: Response::GetIData { res: Ok(res), msg_id: res_msg_id },
+                ..
+            } => {
+                assert_eq!(res, data);
+                assert_eq!(res_msg_id, msg_id);
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+
+        // Responses to clients which have been dropped are rejected.
+        drop(routing);
+        assert!(deliver(&response).is_err());
+
+        extern "C" fn transport(user_data: *mut c_void, message: *const u8, message_len: usize) {
+            unsafe {
+                let sent = user_data as *const Mutex<Vec<Vec<u8>>>;
+                let message = ::std::slice::from_raw_parts(message, message_len);
+                unwrap!((*sent).lock()).push(message.to_vec());
+            }
+        }
+    }
+}
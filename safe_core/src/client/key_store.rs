@@ -0,0 +1,143 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use client::ClientKeys;
+use crypto::{shared_box, shared_secretbox, shared_sign};
+use errors::CoreError;
+use rust_sodium::crypto::{box_, secretbox, sign};
+use utils::secret::MemoryLock;
+
+/// Storage of a client's long-lived keys.
+///
+/// The client doesn't keep its own copy of the secret keys but loads them from its key store
+/// each time it needs them, so platforms can keep them in hardware-backed storage like the
+/// Android Keystore or the Secure Enclave, e.g. wrapped with a key which never leaves the
+/// hardware. `MemoryKeyStore` is used unless another store is set with
+/// `Client::set_key_store`.
+///
+/// Note the routing client still needs the signing and encryption keys to identify itself
+/// to the network: they're loaded when it's set up and stay in its memory for as long as
+/// it's connected, whichever store is used.
+pub trait KeyStore {
+    /// Takes custody of the keys, replacing any held before.
+    fn store(&mut self, keys: &ClientKeys) -> Result<(), CoreError>;
+
+    /// Returns the keys held. Fails with `OperationForbidden` if there are none.
+    fn load(&self) -> Result<ClientKeys, CoreError>;
+
+    /// Keeps the keys out of swap, if they're held in the memory of this process. Returns
+    /// `false` if the platform denied it.
+    fn lock_in_memory(&mut self) -> bool {
+        true
+    }
+}
+
+/// Operation one of the client's secret keys is used for, as reported to the hook set with
+/// `Client::set_key_usage_hook`. The discriminants are the values passed over FFI.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum KeyOp {
+    /// Signing, e.g. of a mutation request.
+    Sign = 0,
+    /// Decryption of data encrypted for the client.
+    Decrypt = 1,
+}
+
+/// Key store keeping the keys in memory.
+#[derive(Default)]
+pub struct MemoryKeyStore {
+    // Declared first, so the locks are dropped before the keys they lock.
+    locks: Vec<MemoryLock>,
+    keys: Option<ClientKeys>,
+}
+
+impl MemoryKeyStore {
+    /// Creates a store holding the given keys.
+    pub fn new(keys: ClientKeys) -> Self {
+        MemoryKeyStore {
+            locks: Vec::new(),
+            keys: Some(keys),
+        }
+    }
+}
+
+impl KeyStore for MemoryKeyStore {
+    fn store(&mut self, keys: &ClientKeys) -> Result<(), CoreError> {
+        self.locks.clear();
+        self.keys = Some(keys.clone());
+        Ok(())
+    }
+
+    fn load(&self) -> Result<ClientKeys, CoreError> {
+        self.keys.clone().ok_or(CoreError::OperationForbidden)
+    }
+
+    fn lock_in_memory(&mut self) -> bool {
+        let keys = match self.keys {
+            Some(ref keys) => keys,
+            None => return true,
+        };
+        let locks = vec![
+            MemoryLock::new(&*keys.sign_sk),
+            MemoryLock::new(&*keys.enc_sk),
+            MemoryLock::new(&*keys.enc_key),
+        ];
+
+        let all_locked = locks.iter().all(Option::is_some);
+        self.locks = locks.into_iter().filter_map(|lock| lock).collect();
+        all_locked
+    }
+}
+
+// Copy of the keys with the secret ones zeroed, for the parts of the client which only
+// need the public ones.
+pub fn public_keys_only(keys: &ClientKeys) -> ClientKeys {
+    ClientKeys {
+        sign_pk: keys.sign_pk,
+        sign_sk: shared_sign::SecretKey::from_raw(&[0; sign::SECRETKEYBYTES]),
+        enc_pk: keys.enc_pk,
+        enc_sk: shared_box::SecretKey::from_raw(&[0; box_::SECRETKEYBYTES]),
+        enc_key: shared_secretbox::Key::from_raw(&[0; secretbox::KEYBYTES]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that the memory key store hands back the keys it was given.
+    #[test]
+    fn memory_key_store() {
+        let mut store = MemoryKeyStore::default();
+        match store.load() {
+            Err(CoreError::OperationForbidden) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let keys = ClientKeys::new(None);
+        unwrap!(store.store(&keys));
+        assert_eq!(unwrap!(store.load()), keys);
+
+        // Locking may be denied by the environment running the test.
+        let _ = store.lock_in_memory();
+        assert_eq!(unwrap!(store.load()), keys);
+
+        let public = public_keys_only(&keys);
+        assert_eq!(public.sign_pk, keys.sign_pk);
+        assert_eq!(public.enc_pk, keys.enc_pk);
+        assert!(public.sign_sk != keys.sign_sk);
+    }
+}
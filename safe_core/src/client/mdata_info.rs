@@ -25,6 +25,7 @@ use rand::{OsRng, Rng};
 use routing::{EntryAction, Value, XorName};
 use rust_sodium::crypto::secretbox;
 use std::collections::{BTreeMap, BTreeSet};
+use std::collections::btree_map;
 use tiny_keccak::sha3_256;
 use utils::{symmetric_decrypt, symmetric_encrypt};
 
@@ -76,6 +77,29 @@ impl MDataInfo {
         Ok(Self::new_private(rng.gen(), type_tag, enc_info))
     }
 
+    /// Deterministically derive `MDataInfo` for private mutable data from a secret
+    /// and a label. The same inputs always give the same name, type tag and
+    /// encryption info, so the data can be found again without storing its location.
+    /// Derived type tags are always at least `2^63` to stay clear of well-known tags.
+    pub fn derive_private(secret: &[u8], label: &[u8]) -> Self {
+        let seed = sha3_256(&[secret, label].concat());
+        let derive = |purpose: &[u8]| sha3_256(&[purpose, &seed[..]].concat());
+
+        let name = XorName(derive(b"name"));
+
+        let tag_bytes = derive(b"type_tag");
+        let type_tag = tag_bytes[..8].iter().fold(
+            0u64,
+            |tag, byte| (tag << 8) | u64::from(*byte),
+        ) | (1 << 63);
+
+        let key = shared_secretbox::Key::from_raw(&derive(b"enc_key"));
+        let mut nonce = [0; secretbox::NONCEBYTES];
+        nonce.copy_from_slice(&derive(b"enc_nonce")[..secretbox::NONCEBYTES]);
+
+        Self::new_private(name, type_tag, (key, secretbox::Nonce(nonce)))
+    }
+
     /// Generate random `MDataInfo` for public mutable data.
     pub fn random_public(type_tag: u64) -> Result<Self, CoreError> {
         let mut rng = os_rng()?;
@@ -248,6 +272,128 @@ pub fn decrypt_values(info: &MDataInfo, values: &[Value]) -> Result<Vec<Value>,
     Ok(output)
 }
 
+/// Entries of a mutable data, decrypted only when accessed. Looking up a single entry
+/// encrypts its key rather than decrypting every other key, so it stays cheap for large
+/// containers.
+#[derive(Clone, Debug)]
+pub struct LazyEntries {
+    info: MDataInfo,
+    entries: BTreeMap<Vec<u8>, Value>,
+}
+
+impl LazyEntries {
+    /// Wrap entries fetched from the mutable data described by `info`.
+    pub fn new(info: MDataInfo, entries: BTreeMap<Vec<u8>, Value>) -> Self {
+        LazyEntries { info, entries }
+    }
+
+    /// Number of entries, including deleted ones.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the decrypted value of the entry with the given plain text key.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Value>, CoreError> {
+        let mut enc_keys = Vec::with_capacity(2);
+        if let Some((ref enc_key, seed)) = self.info.new_enc_info {
+            enc_keys.push(enc_entry_key(key, enc_key, seed)?);
+        }
+        if let Some((ref enc_key, seed)) = self.info.enc_info {
+            enc_keys.push(enc_entry_key(key, enc_key, seed)?);
+        } else {
+            enc_keys.push(key.to_vec());
+        }
+
+        for enc_key in enc_keys {
+            if let Some(value) = self.entries.get(&enc_key) {
+                return Ok(Some(self.decrypt_value(value)?));
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Iterates over the entries without decrypting them.
+    pub fn iter(&self) -> LazyEntriesIter {
+        LazyEntriesIter {
+            info: &self.info,
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// Decrypts all entries.
+    pub fn decrypt_all(&self) -> Result<BTreeMap<Vec<u8>, Value>, CoreError> {
+        decrypt_entries(&self.info, &self.entries)
+    }
+
+    fn decrypt_value(&self, value: &Value) -> Result<Value, CoreError> {
+        // Deleted entries have empty content, which isn't valid cipher text.
+        if value.content.is_empty() {
+            Ok(value.clone())
+        } else {
+            decrypt_value(&self.info, value)
+        }
+    }
+}
+
+/// Iterator over `LazyEntries`.
+pub struct LazyEntriesIter<'a> {
+    info: &'a MDataInfo,
+    inner: btree_map::Iter<'a, Vec<u8>, Value>,
+}
+
+impl<'a> Iterator for LazyEntriesIter<'a> {
+    type Item = LazyEntry<'a>;
+
+    fn next(&mut self) -> Option<LazyEntry<'a>> {
+        self.inner.next().map(|(key, value)| {
+            LazyEntry {
+                info: self.info,
+                key,
+                value,
+            }
+        })
+    }
+}
+
+/// Entry of `LazyEntries`, decrypting its key and value on request.
+pub struct LazyEntry<'a> {
+    info: &'a MDataInfo,
+    key: &'a [u8],
+    value: &'a Value,
+}
+
+impl<'a> LazyEntry<'a> {
+    /// Decrypted key.
+    pub fn key(&self) -> Result<Vec<u8>, CoreError> {
+        self.info.decrypt(self.key)
+    }
+
+    /// Decrypted value.
+    pub fn value(&self) -> Result<Value, CoreError> {
+        if self.value.content.is_empty() {
+            Ok(self.value.clone())
+        } else {
+            decrypt_value(self.info, self.value)
+        }
+    }
+
+    /// Version of the entry, available without decrypting anything.
+    pub fn entry_version(&self) -> u64 {
+        self.value.entry_version
+    }
+
+    /// Returns `true` if the entry has been deleted.
+    pub fn is_deleted(&self) -> bool {
+        self.value.content.is_empty()
+    }
+}
+
 fn encrypt_value(info: &MDataInfo, value: &Value) -> Result<Value, CoreError> {
     Ok(Value {
         content: info.enc_entry_value(&value.content)?,
@@ -338,6 +484,23 @@ mod tests {
         assert_eq!(unwrap!(info.decrypt(&enc_val)), val);
     }
 
+    // Ensure that derived mdata info only depends on the secret and the label.
+    #[test]
+    fn derive_private() {
+        let info1 = MDataInfo::derive_private(b"secret", b"label");
+        let info2 = MDataInfo::derive_private(b"secret", b"label");
+        assert_eq!(info1, info2);
+        assert!(info1.enc_info.is_some());
+        assert!(info1.type_tag >= 1 << 63);
+
+        let other_label = MDataInfo::derive_private(b"secret", b"other label");
+        assert_ne!(other_label.name, info1.name);
+        assert_ne!(other_label.enc_info, info1.enc_info);
+
+        let other_secret = MDataInfo::derive_private(b"other secret", b"label");
+        assert_ne!(other_secret.name, info1.name);
+    }
+
     // Ensure that a public mdata info is not encrypted.
     #[test]
     fn public_mdata_info_doesnt_encrypt() {
@@ -349,6 +512,52 @@ mod tests {
         assert_eq!(unwrap!(info.decrypt(&val)), val);
     }
 
+    // Test looking up and iterating entries decrypted on access.
+    #[test]
+    fn lazy_entries() {
+        let mut info = unwrap!(MDataInfo::random_private(0));
+        let plain = btree_map![
+            b"key-0".to_vec() => Value { content: b"value-0".to_vec(), entry_version: 0 },
+            b"key-1".to_vec() => Value { content: b"value-1".to_vec(), entry_version: 3 }
+        ];
+        let mut entries = unwrap!(encrypt_entries(&info, &plain));
+
+        // An entry encrypted with the new encryption info can be looked up too.
+        info.start_new_enc_info();
+        let new_key = unwrap!(info.enc_entry_key(b"key-2"));
+        let new_value = unwrap!(info.enc_entry_value(b"value-2"));
+        let _ = entries.insert(
+            new_key,
+            Value {
+                content: new_value,
+                entry_version: 0,
+            },
+        );
+
+        let lazy = LazyEntries::new(info, entries);
+        assert_eq!(lazy.len(), 3);
+        assert_eq!(
+            unwrap!(unwrap!(lazy.get(b"key-1"))),
+            Value {
+                content: b"value-1".to_vec(),
+                entry_version: 3,
+            }
+        );
+        assert_eq!(
+            unwrap!(unwrap!(lazy.get(b"key-2"))).content,
+            b"value-2".to_vec()
+        );
+        assert!(unwrap!(lazy.get(b"missing")).is_none());
+
+        let mut keys: Vec<_> = lazy.iter().map(|entry| unwrap!(entry.key())).collect();
+        keys.sort();
+        assert_eq!(
+            keys,
+            vec![b"key-0".to_vec(), b"key-1".to_vec(), b"key-2".to_vec()]
+        );
+        assert_eq!(unwrap!(lazy.decrypt_all()).len(), 3);
+    }
+
     // Test creating and committing new encryption info.
     #[test]
     fn decrypt() {
@@ -0,0 +1,463 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Populating the mock vault from declarative fixture files.
+//!
+//! A fixture lists accounts, `ImmutableData` blobs and `MutableData` with their
+//! entries and permissions. It can be written in JSON or, if the file has the
+//! `.toml` extension, in TOML:
+//!
+//! ```toml
+//! [[accounts]]
+//! name = "alice"
+//! locator = "alice-locator"
+//! password = "alice-password"
+//! seed = "alice"
+//!
+//! [[immutable_data]]
+//! name = "logo"
+//! file = "blobs/logo.png"
+//!
+//! [[mutable_data]]
+//! name = "guestbook"
+//! tag = 15000
+//! owner = "alice"
+//! entries = { greeting = "hello" }
+//! permissions = { anyone = { allow = ["Insert"] } }
+//! ```
+//!
+//! Accounts are referred to by their `name` as MData owners and permission
+//! users; `anyone` denotes `User::Anyone`. Blob files are resolved relative to
+//! the fixture file.
+
+use super::DataId;
+use super::routing::clone_vault;
+use super::vault::{self, Data, Vault};
+use client::ClientKeys;
+use client::account::Account;
+use errors::CoreError;
+use maidsafe_utilities::serialisation::serialise;
+use routing::{ACC_LOGIN_ENTRY_KEY, AccountPacket, Action, ClientError, ImmutableData,
+              MutableData, PermissionSet, TYPE_TAG_SESSION_PACKET, User, Value, XorName};
+use rust_sodium::crypto::sign::{self, Seed};
+use serde_json;
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use tiny_keccak::sha3_256;
+use toml;
+use utils;
+
+/// Contents of a fixture file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Fixture {
+    /// Accounts to create.
+    pub accounts: Vec<AccountFixture>,
+    /// `ImmutableData` to store.
+    pub immutable_data: Vec<IDataFixture>,
+    /// `MutableData` to store.
+    pub mutable_data: Vec<MDataFixture>,
+}
+
+/// Account to create.
+#[derive(Debug, Deserialize)]
+pub struct AccountFixture {
+    /// Name the account is referred to by in the rest of the fixture.
+    pub name: String,
+    /// Account locator.
+    pub locator: String,
+    /// Account password.
+    pub password: String,
+    /// Seed of the account keys. Random keys are used if not given.
+    #[serde(default)]
+    pub seed: Option<String>,
+}
+
+/// `ImmutableData` to store, given either inline or as a file.
+#[derive(Debug, Deserialize)]
+pub struct IDataFixture {
+    /// Name the data is referred to by in the loaded fixture.
+    pub name: String,
+    /// Path of the file with the content, relative to the fixture file.
+    #[serde(default)]
+    pub file: Option<PathBuf>,
+    /// Inline content.
+    #[serde(default)]
+    pub content: Option<String>,
+}
+
+/// `MutableData` to store.
+#[derive(Debug, Deserialize)]
+pub struct MDataFixture {
+    /// Name the data is referred to by. The network name is its SHA3 hash.
+    pub name: String,
+    /// Type tag.
+    pub tag: u64,
+    /// Name of the owning account.
+    pub owner: String,
+    /// Entries, all at version 0.
+    #[serde(default)]
+    pub entries: BTreeMap<String, String>,
+    /// Permissions keyed by account name or `anyone`.
+    #[serde(default)]
+    pub permissions: BTreeMap<String, PermissionsFixture>,
+}
+
+/// Actions allowed and denied to a user.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PermissionsFixture {
+    /// Allowed actions.
+    pub allow: Vec<Action>,
+    /// Denied actions.
+    pub deny: Vec<Action>,
+}
+
+/// Network identities of the data created from a fixture.
+#[derive(Debug, Default)]
+pub struct Loaded {
+    /// Keys of the created accounts, by fixture name.
+    pub accounts: HashMap<String, ClientKeys>,
+    /// Names of the stored `ImmutableData`, by fixture name.
+    pub immutable_data: HashMap<String, XorName>,
+    /// Names and type tags of the stored `MutableData`, by fixture name.
+    pub mutable_data: HashMap<String, (XorName, u64)>,
+}
+
+/// Reads the fixture file at `path` and stores its contents in the mock vault.
+pub fn load<P: AsRef<Path>>(path: P) -> Result<Loaded, CoreError> {
+    let path = path.as_ref();
+    let fixture = Fixture::read(path)?;
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+
+    let vault = clone_vault();
+    let mut vault = vault::lock(&vault, true);
+    fixture.populate(&mut vault, base_dir)
+}
+
+impl Fixture {
+    /// Parses a fixture file, as TOML if it has the `.toml` extension and as
+    /// JSON otherwise.
+    pub fn read(path: &Path) -> Result<Self, CoreError> {
+        let mut raw = String::new();
+        let _ = File::open(path)?.read_to_string(&mut raw)?;
+
+        if path.extension().map_or(false, |ext| ext == "toml") {
+            toml::from_str(&raw).map_err(|err| {
+                CoreError::Unexpected(format!("Invalid fixture {}: {}", path.display(), err))
+            })
+        } else {
+            serde_json::from_str(&raw).map_err(|err| {
+                CoreError::Unexpected(format!("Invalid fixture {}: {}", path.display(), err))
+            })
+        }
+    }
+
+    /// Stores the fixture contents in `vault`, resolving blob files relative
+    /// to `base_dir`.
+    pub fn populate(&self, vault: &mut Vault, base_dir: &Path) -> Result<Loaded, CoreError> {
+        let mut loaded = Loaded::default();
+
+        for account in &self.accounts {
+            let keys = insert_account(vault, account)?;
+            let _ = loaded.accounts.insert(account.name.clone(), keys);
+        }
+
+        for idata in &self.immutable_data {
+            let content = match (&idata.file, &idata.content) {
+                (&Some(ref file), &None) => {
+                    let mut content = Vec::new();
+                    let _ = File::open(base_dir.join(file))?.read_to_end(&mut content)?;
+                    content
+                }
+                (&None, &Some(ref content)) => content.clone().into_bytes(),
+                _ => {
+                    return Err(CoreError::Unexpected(format!(
+                        "Immutable data {} needs either a file or a content",
+                        idata.name
+                    )))
+                }
+            };
+
+            let data = ImmutableData::new(content);
+            let name = *data.name();
+            vault.insert_data(DataId::immutable(name), Data::Immutable(data));
+            let _ = loaded.immutable_data.insert(idata.name.clone(), name);
+        }
+
+        for mdata in &self.mutable_data {
+            let owner = account_key(&loaded, &mdata.owner)?;
+
+            let mut permissions = BTreeMap::new();
+            for (user, set) in &mdata.permissions {
+                let user = if user == "anyone" {
+                    User::Anyone
+                } else {
+                    User::Key(account_key(&loaded, user)?)
+                };
+
+                let mut permission_set = PermissionSet::new();
+                for action in &set.allow {
+                    permission_set = permission_set.allow(*action);
+                }
+                for action in &set.deny {
+                    permission_set = permission_set.deny(*action);
+                }
+                let _ = permissions.insert(user, permission_set);
+            }
+
+            let entries = mdata
+                .entries
+                .iter()
+                .map(|(key, content)| {
+                    (
+                        key.clone().into_bytes(),
+                        Value {
+                            content: content.clone().into_bytes(),
+                            entry_version: 0,
+                        },
+                    )
+                })
+                .collect();
+
+            let name = XorName(sha3_256(mdata.name.as_bytes()));
+            let data = MutableData::new(name, mdata.tag, permissions, entries, btree_set![owner])?;
+            let data_id = DataId::mutable(name, mdata.tag);
+            if vault.contains_data(&data_id) {
+                return Err(CoreError::RoutingClientError(ClientError::DataExists));
+            }
+            vault.insert_data(data_id, Data::Mutable(data));
+            let _ = loaded.mutable_data.insert(
+                mdata.name.clone(),
+                (name, mdata.tag),
+            );
+        }
+
+        Ok(loaded)
+    }
+}
+
+// Stores the session packet of the account the same way registering a client
+// does, so the account can be logged into with its locator and password.
+fn insert_account(vault: &mut Vault, account: &AccountFixture) -> Result<ClientKeys, CoreError> {
+    let (password, keyword, pin) =
+        utils::derive_secrets(account.locator.as_bytes(), account.password.as_bytes());
+    let acc_loc = Account::generate_network_id(&keyword, &pin)?;
+
+    let data_id = DataId::mutable(acc_loc, TYPE_TAG_SESSION_PACKET);
+    if vault.contains_data(&data_id) {
+        return Err(CoreError::RoutingClientError(ClientError::AccountExists));
+    }
+
+    let seed = account.seed.as_ref().map(
+        |seed| Seed(sha3_256(seed.as_bytes())),
+    );
+    let keys = ClientKeys::new(seed.as_ref());
+    let pub_key = keys.sign_pk;
+
+    let acc_ciphertext = Account::new(keys.clone())?.encrypt(&password, &pin)?;
+    let acc_data = btree_map![
+        ACC_LOGIN_ENTRY_KEY.to_owned() => Value {
+            content: serialise(&AccountPacket::AccPkt(acc_ciphertext))?,
+            entry_version: 0,
+        }
+    ];
+    let acc_md = MutableData::new(
+        acc_loc,
+        TYPE_TAG_SESSION_PACKET,
+        BTreeMap::new(),
+        acc_data,
+        btree_set![pub_key],
+    )?;
+
+    vault.insert_account(XorName(sha3_256(&pub_key.0)));
+    vault.insert_data(data_id, Data::Mutable(acc_md));
+
+    Ok(keys)
+}
+
+fn account_key(loaded: &Loaded, name: &str) -> Result<sign::PublicKey, CoreError> {
+    loaded
+        .accounts
+        .get(name)
+        .map(|keys| keys.sign_pk)
+        .ok_or_else(|| CoreError::Unexpected(format!("Unknown fixture account {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config_handler::{Config, DevConfig};
+    use maidsafe_utilities::serialisation::deserialise;
+    use rand;
+    use std::env;
+    use std::fs;
+    use std::io::Write;
+
+    const FIXTURE: &str = r#"
+        [[accounts]]
+        name = "alice"
+        locator = "alice-locator"
+        password = "alice-password"
+        seed = "alice"
+
+        [[accounts]]
+        name = "bob"
+        locator = "bob-locator"
+        password = "bob-password"
+
+        [[immutable_data]]
+        name = "blob"
+        file = "blob.txt"
+
+        [[immutable_data]]
+        name = "inline"
+        content = "inline content"
+
+        [[mutable_data]]
+        name = "guestbook"
+        tag = 15000
+        owner = "alice"
+        entries = { greeting = "hello" }
+
+        [mutable_data.permissions]
+        anyone = { allow = ["Insert"] }
+        bob = { allow = ["Update"], deny = ["Delete"] }
+    "#;
+
+    fn memory_vault() -> Vault {
+        Vault::new(Config {
+            dev: Some(DevConfig {
+                mock_unlimited_mutations: false,
+                mock_in_memory_storage: true,
+                mock_vault_path: None,
+            }),
+        })
+    }
+
+    fn write_file(path: &Path, content: &[u8]) {
+        unwrap!(unwrap!(File::create(path)).write_all(content));
+    }
+
+    // Test populating a vault from a TOML fixture with a blob file.
+    #[test]
+    fn populate_from_toml() {
+        let dir = env::temp_dir().join(format!("fixture-{}", rand::random::<u64>()));
+        unwrap!(fs::create_dir_all(&dir));
+        write_file(&dir.join("blob.txt"), b"blob content");
+        write_file(&dir.join("fixture.toml"), FIXTURE.as_bytes());
+
+        let fixture = unwrap!(Fixture::read(&dir.join("fixture.toml")));
+        let mut vault = memory_vault();
+        let loaded = unwrap!(fixture.populate(&mut vault, &dir));
+        unwrap!(fs::remove_dir_all(&dir));
+
+        // Accounts
+        let alice = &loaded.accounts["alice"];
+        let bob = &loaded.accounts["bob"];
+        assert_eq!(
+            alice.sign_pk,
+            ClientKeys::new(Some(&Seed(sha3_256(b"alice")))).sign_pk
+        );
+        assert!(vault.get_account(&XorName(sha3_256(&bob.sign_pk.0))).is_some());
+
+        let (password, keyword, pin) = utils::derive_secrets(b"alice-locator", b"alice-password");
+        let acc_loc = unwrap!(Account::generate_network_id(&keyword, &pin));
+        let session_packet_id = DataId::mutable(acc_loc, TYPE_TAG_SESSION_PACKET);
+        let session_packet = match vault.get_data(&session_packet_id) {
+            Some(Data::Mutable(data)) => data,
+            _ => panic!("Session packet not stored"),
+        };
+        let content = unwrap!(session_packet.get(ACC_LOGIN_ENTRY_KEY)).content.clone();
+        let account = match unwrap!(deserialise::<AccountPacket>(&content)) {
+            AccountPacket::AccPkt(ciphertext) => {
+                unwrap!(Account::decrypt(&ciphertext, &password, &pin))
+            }
+            _ => panic!("Unexpected account packet"),
+        };
+        assert_eq!(account.maid_keys, *alice);
+
+        // Immutable data
+        for &(name, content) in &[("blob", &b"blob content"[..]), ("inline", b"inline content")] {
+            match vault.get_data(&DataId::immutable(loaded.immutable_data[name])) {
+                Some(Data::Immutable(data)) => assert_eq!(&data.value()[..], content),
+                _ => panic!("Immutable data {} not stored", name),
+            }
+        }
+
+        // Mutable data
+        let (name, tag) = loaded.mutable_data["guestbook"];
+        let data = match vault.get_data(&DataId::mutable(name, tag)) {
+            Some(Data::Mutable(data)) => data,
+            _ => panic!("Mutable data not stored"),
+        };
+        assert_eq!(name, XorName(sha3_256(b"guestbook")));
+        assert_eq!(*data.owners(), btree_set![alice.sign_pk]);
+        assert_eq!(unwrap!(data.get(b"greeting")).content, b"hello".to_vec());
+
+        let anyone = unwrap!(data.user_permissions(&User::Anyone));
+        assert_eq!(anyone.is_allowed(Action::Insert), Some(true));
+        let bob_perms = unwrap!(data.user_permissions(&User::Key(bob.sign_pk)));
+        assert_eq!(bob_perms.is_allowed(Action::Update), Some(true));
+        assert_eq!(bob_perms.is_allowed(Action::Delete), Some(false));
+        assert_eq!(bob_perms.is_allowed(Action::Insert), None);
+    }
+
+    // Test a JSON fixture and the errors for invalid references.
+    #[test]
+    fn populate_from_json() {
+        let dir = env::temp_dir().join(format!("fixture-{}", rand::random::<u64>()));
+        unwrap!(fs::create_dir_all(&dir));
+        let path = dir.join("fixture.json");
+        write_file(
+            &path,
+            br#"{
+                "accounts": [
+                    { "name": "carol", "locator": "carol-locator", "password": "carol-password" }
+                ],
+                "mutable_data": [
+                    { "name": "empty", "tag": 15001, "owner": "carol" },
+                    { "name": "orphan", "tag": 15001, "owner": "dave" }
+                ]
+            }"#,
+        );
+        let fixture = unwrap!(Fixture::read(&path));
+        unwrap!(fs::remove_dir_all(&dir));
+
+        assert_eq!(fixture.accounts.len(), 1);
+        assert_eq!(fixture.mutable_data.len(), 2);
+        assert!(fixture.immutable_data.is_empty());
+
+        let mut vault = memory_vault();
+        match fixture.populate(&mut vault, &dir) {
+            Err(CoreError::Unexpected(ref msg)) if msg.contains("dave") => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        // Loading the same accounts twice fails.
+        let mut fixture = fixture;
+        let _ = fixture.mutable_data.pop();
+        let mut vault = memory_vault();
+        let _ = unwrap!(fixture.populate(&mut vault, &dir));
+        match fixture.populate(&mut vault, &dir) {
+            Err(CoreError::RoutingClientError(ClientError::AccountExists)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
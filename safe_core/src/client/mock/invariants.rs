@@ -0,0 +1,581 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Property-based tests applying random sequences of operations to the mock
+//! vault and checking them against a simple model of `MutableData`.
+
+use super::routing::Routing;
+use super::vault::Vault;
+use config_handler::{Config, DevConfig};
+use quickcheck::{Arbitrary, Gen, QuickCheck};
+use routing::{AccountInfo, Action, Authority, ClientError, EntryAction, EntryActions, Event,
+              FullId, ImmutableData, MessageId, MutableData, PermissionSet, Response,
+              TYPE_TAG_SESSION_PACKET, User, Value, XorName};
+use rust_sodium::crypto::sign;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver};
+use std::time::Duration;
+use tiny_keccak::sha3_256;
+
+const NUM_CASES: u64 = 30;
+const NUM_KEYS: u8 = 3;
+const NUM_BLOBS: u8 = 4;
+const TAG: u64 = 10_000;
+const ACTIONS: [Action; 4] = [
+    Action::Insert,
+    Action::Update,
+    Action::Delete,
+    Action::ManagePermissions,
+];
+
+// Receive the response to the given request and return its result.
+macro_rules! response {
+    ($rx:expr, $msg_id:expr, $res:path) => {
+        match unwrap!($rx.recv_timeout(Duration::from_secs(10))) {
+            Event::Response {
+                response: $res { res, msg_id, }, ..
+            } => {
+                assert_eq!(msg_id, $msg_id);
+                res
+            }
+            event => panic!("Unexpected event {:?}", event),
+        }
+    }
+}
+
+// Random operation sequences keep the account, the `MutableData` and the
+// stored blobs consistent with the model.
+#[test]
+fn vault_invariants() {
+    QuickCheck::new().tests(NUM_CASES).quickcheck(
+        check_invariants as fn(Vec<Op>) -> bool,
+    );
+}
+
+fn check_invariants(ops: Vec<Op>) -> bool {
+    let mut harness = Harness::new();
+    for op in &ops {
+        harness.apply(op);
+        harness.check_state();
+    }
+    true
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Actor {
+    // Owner of the account and of the `MutableData`.
+    Owner,
+    // App authorised to mutate on behalf of the owner's account.
+    App,
+    // Client which isn't authorised on the owner's account.
+    Stranger,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Grantee {
+    App,
+    Anyone,
+}
+
+#[derive(Clone, Debug)]
+enum Op {
+    PutIData { actor: Actor, blob: u8 },
+    Insert { actor: Actor, key: u8 },
+    Update { actor: Actor, key: u8, bump: u64 },
+    Delete { actor: Actor, key: u8, bump: u64 },
+    SetPermissions {
+        actor: Actor,
+        grantee: Grantee,
+        permissions: PermissionSet,
+        bump: u64,
+    },
+    DelPermissions {
+        actor: Actor,
+        grantee: Grantee,
+        bump: u64,
+    },
+}
+
+impl Arbitrary for Actor {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        match u8::arbitrary(g) % 3 {
+            0 => Actor::Owner,
+            1 => Actor::App,
+            _ => Actor::Stranger,
+        }
+    }
+}
+
+impl Arbitrary for Grantee {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        if bool::arbitrary(g) {
+            Grantee::App
+        } else {
+            Grantee::Anyone
+        }
+    }
+}
+
+impl Arbitrary for Op {
+    fn arbitrary<G: Gen>(g: &mut G) -> Self {
+        let actor = Actor::arbitrary(g);
+        let key = u8::arbitrary(g) % NUM_KEYS;
+        // Mostly valid successors, sometimes stale or skipped versions.
+        let bump = [1, 1, 1, 0, 2][usize::arbitrary(g) % 5];
+
+        match u8::arbitrary(g) % 6 {
+            0 => Op::PutIData {
+                actor,
+                blob: u8::arbitrary(g) % NUM_BLOBS,
+            },
+            1 => Op::Insert { actor, key },
+            2 => Op::Update { actor, key, bump },
+            3 => Op::Delete { actor, key, bump },
+            4 => {
+                let mut permissions = PermissionSet::new();
+                for action in &ACTIONS {
+                    permissions = match u8::arbitrary(g) % 3 {
+                        0 => permissions.allow(*action),
+                        1 => permissions.deny(*action),
+                        _ => permissions,
+                    };
+                }
+
+                Op::SetPermissions {
+                    actor,
+                    grantee: Grantee::arbitrary(g),
+                    permissions,
+                    bump,
+                }
+            }
+            _ => Op::DelPermissions {
+                actor,
+                grantee: Grantee::arbitrary(g),
+                bump,
+            },
+        }
+    }
+}
+
+struct Connection {
+    routing: Routing,
+    routing_rx: Receiver<Event>,
+    key: sign::PublicKey,
+}
+
+// Expected state of the vault.
+struct Model {
+    entries: BTreeMap<Vec<u8>, Value>,
+    permissions: BTreeMap<User, PermissionSet>,
+    version: u64,
+    blobs: BTreeSet<XorName>,
+    mutations_done: u64,
+}
+
+struct Harness {
+    connections: Vec<Connection>,
+    client_mgr: Authority<XorName>,
+    name: XorName,
+    model: Model,
+    last_data: Option<MutableData>,
+    next_content: u8,
+}
+
+impl Harness {
+    fn new() -> Self {
+        let vault = Arc::new(Mutex::new(Vault::new(Config {
+            dev: Some(DevConfig {
+                mock_unlimited_mutations: false,
+                mock_in_memory_storage: true,
+                mock_vault_path: None,
+            }),
+        })));
+        let mut connections: Vec<_> = (0..3).map(|_| connect(&vault)).collect();
+
+        // Create the owner's account, authorise the app and put the data.
+        let owner_key = connections[0].key;
+        let client_mgr = Authority::ClientManager(XorName(sha3_256(&owner_key[..])));
+        let account = unwrap!(MutableData::new(
+            XorName(sha3_256(&owner_key[..])),
+            TYPE_TAG_SESSION_PACKET,
+            Default::default(),
+            Default::default(),
+            btree_set![owner_key],
+        ));
+        let name = XorName(sha3_256(b"invariants"));
+        let data = unwrap!(MutableData::new(
+            name,
+            TAG,
+            Default::default(),
+            Default::default(),
+            btree_set![owner_key],
+        ));
+        let app_key = connections[1].key;
+
+        {
+            let owner = &mut connections[0];
+
+            let msg_id = MessageId::new();
+            unwrap!(owner.routing.put_mdata(client_mgr, account, msg_id, owner_key));
+            unwrap!(response!(owner.routing_rx, msg_id, Response::PutMData));
+
+            let msg_id = MessageId::new();
+            unwrap!(owner.routing.ins_auth_key(client_mgr, app_key, 1, msg_id));
+            unwrap!(response!(owner.routing_rx, msg_id, Response::InsAuthKey));
+
+            let msg_id = MessageId::new();
+            unwrap!(owner.routing.put_mdata(client_mgr, data, msg_id, owner_key));
+            unwrap!(response!(owner.routing_rx, msg_id, Response::PutMData));
+        }
+
+        let mut harness = Harness {
+            connections,
+            client_mgr,
+            name,
+            model: Model {
+                entries: BTreeMap::new(),
+                permissions: BTreeMap::new(),
+                version: 0,
+                blobs: BTreeSet::new(),
+                mutations_done: 0,
+            },
+            last_data: None,
+            next_content: 0,
+        };
+        let mutations_done = harness.account_info().mutations_done;
+        harness.model.mutations_done = mutations_done;
+        harness
+    }
+
+    fn apply(&mut self, op: &Op) {
+        match *op {
+            Op::PutIData { actor, blob } => {
+                let data = ImmutableData::new(vec![blob]);
+                let data_name = *data.name();
+                let client_mgr = self.client_mgr;
+
+                let res = {
+                    let conn = self.connection(actor);
+                    let msg_id = MessageId::new();
+                    unwrap!(conn.routing.put_idata(client_mgr, data, msg_id));
+                    response!(conn.routing_rx, msg_id, Response::PutIData)
+                };
+
+                // Storing a blob is charged even if it's already stored.
+                if self.check(op, actor != Actor::Stranger, res) {
+                    let _ = self.model.blobs.insert(data_name);
+                }
+            }
+            Op::Insert { actor, key } => {
+                let key = vec![key];
+                let content = self.next_content();
+                let (valid, version) = match self.model.entries.get(&key) {
+                    None => (Some(true), 0),
+                    Some(value) if value.content.is_empty() => (None, value.entry_version + 1),
+                    Some(value) => (Some(false), value.entry_version + 1),
+                };
+
+                let actions = EntryActions::new()
+                    .ins(key.clone(), content.clone(), version)
+                    .into();
+                let res = self.mutate_entries(actor, actions);
+                if self.check_entry_mutation(op, actor, Action::Insert, valid, res) {
+                    let _ = self.model.entries.insert(
+                        key,
+                        Value {
+                            content,
+                            entry_version: version,
+                        },
+                    );
+                }
+            }
+            Op::Update { actor, key, bump } => {
+                let key = vec![key];
+                let content = self.next_content();
+                let (valid, version) = match self.model.entries.get(&key) {
+                    None => (Some(false), bump),
+                    Some(value) => (Some(bump == 1), value.entry_version + bump),
+                };
+
+                let actions = EntryActions::new()
+                    .update(key.clone(), content.clone(), version)
+                    .into();
+                let res = self.mutate_entries(actor, actions);
+                if self.check_entry_mutation(op, actor, Action::Update, valid, res) {
+                    let _ = self.model.entries.insert(
+                        key,
+                        Value {
+                            content,
+                            entry_version: version,
+                        },
+                    );
+                }
+            }
+            Op::Delete { actor, key, bump } => {
+                let key = vec![key];
+                let (valid, version) = match self.model.entries.get(&key) {
+                    None => (Some(false), bump),
+                    Some(value) if value.content.is_empty() && bump == 1 => {
+                        (None, value.entry_version + bump)
+                    }
+                    Some(value) => (Some(bump == 1), value.entry_version + bump),
+                };
+
+                let actions = EntryActions::new().del(key.clone(), version).into();
+                let res = self.mutate_entries(actor, actions);
+                // Deleted entries are kept with an empty content.
+                if self.check_entry_mutation(op, actor, Action::Delete, valid, res) {
+                    let _ = self.model.entries.insert(
+                        key,
+                        Value {
+                            content: Vec::new(),
+                            entry_version: version,
+                        },
+                    );
+                }
+            }
+            Op::SetPermissions {
+                actor,
+                grantee,
+                permissions,
+                bump,
+            } => {
+                let user = self.user(grantee);
+                let version = self.model.version + bump;
+                let (name, client_mgr) = (self.name, self.client_mgr);
+
+                let res = {
+                    let conn = self.connection(actor);
+                    let msg_id = MessageId::new();
+                    unwrap!(conn.routing.set_mdata_user_permissions(
+                        client_mgr,
+                        name,
+                        TAG,
+                        user,
+                        permissions,
+                        version,
+                        msg_id,
+                        conn.key,
+                    ));
+                    response!(conn.routing_rx, msg_id, Response::SetMDataUserPermissions)
+                };
+
+                let valid = self.allowed(actor, Action::ManagePermissions) && bump == 1;
+                if self.check(op, valid, res) {
+                    let _ = self.model.permissions.insert(user, permissions);
+                    self.model.version = version;
+                }
+            }
+            Op::DelPermissions {
+                actor,
+                grantee,
+                bump,
+            } => {
+                let user = self.user(grantee);
+                let version = self.model.version + bump;
+                let (name, client_mgr) = (self.name, self.client_mgr);
+
+                let res = {
+                    let conn = self.connection(actor);
+                    let msg_id = MessageId::new();
+                    unwrap!(conn.routing.del_mdata_user_permissions(
+                        client_mgr,
+                        name,
+                        TAG,
+                        user,
+                        version,
+                        msg_id,
+                        conn.key,
+                    ));
+                    response!(conn.routing_rx, msg_id, Response::DelMDataUserPermissions)
+                };
+
+                let valid = self.allowed(actor, Action::ManagePermissions) && bump == 1 &&
+                    self.model.permissions.contains_key(&user);
+                if self.check(op, valid, res) {
+                    let _ = self.model.permissions.remove(&user);
+                    self.model.version = version;
+                }
+            }
+        }
+    }
+
+    // Compare the vault with the model.
+    fn check_state(&mut self) {
+        let name = self.name;
+        let data = {
+            let owner = self.connection(Actor::Owner);
+            let msg_id = MessageId::new();
+            unwrap!(owner.routing.get_mdata(
+                Authority::NaeManager(name),
+                name,
+                TAG,
+                msg_id,
+            ));
+            unwrap!(response!(owner.routing_rx, msg_id, Response::GetMData))
+        };
+
+        assert_eq!(*data.entries(), self.model.entries);
+        assert_eq!(*data.permissions(), self.model.permissions);
+        assert_eq!(data.version(), self.model.version);
+
+        // Versions never go back and entries are never removed.
+        if let Some(ref last_data) = self.last_data {
+            assert!(data.version() >= last_data.version());
+            for (key, last_value) in last_data.entries() {
+                let value = unwrap!(data.get(key));
+                assert!(value.entry_version >= last_value.entry_version);
+            }
+        }
+        self.last_data = Some(data);
+
+        assert_eq!(self.account_info().mutations_done, self.model.mutations_done);
+
+        for blob in &self.model.blobs.clone() {
+            let owner = self.connection(Actor::Owner);
+            let msg_id = MessageId::new();
+            unwrap!(owner.routing.get_idata(
+                Authority::NaeManager(*blob),
+                *blob,
+                msg_id,
+            ));
+            let data = unwrap!(response!(owner.routing_rx, msg_id, Response::GetIData));
+            assert_eq!(*data.name(), *blob);
+        }
+    }
+
+    // Checks that the mutation succeeded if and only if `expected`, and if so,
+    // accounts for it. Returns whether it succeeded.
+    fn check<T>(&mut self, op: &Op, expected: bool, res: Result<T, ClientError>) -> bool {
+        match res {
+            Ok(_) if expected => {
+                self.model.mutations_done += 1;
+                true
+            }
+            Err(_) if !expected => false,
+            Ok(_) => panic!("{:?} unexpectedly succeeded", op),
+            Err(err) => panic!("{:?} unexpectedly failed: {:?}", op, err),
+        }
+    }
+
+    // Like `check`, except `valid` may be `None` when the outcome of a valid
+    // request isn't specified by the model.
+    fn check_entry_mutation(
+        &mut self,
+        op: &Op,
+        actor: Actor,
+        action: Action,
+        valid: Option<bool>,
+        res: Result<(), ClientError>,
+    ) -> bool {
+        if !self.allowed(actor, action) {
+            return self.check(op, false, res);
+        }
+        match valid {
+            Some(valid) => self.check(op, valid, res),
+            None => {
+                let succeeded = res.is_ok();
+                self.check(op, succeeded, res)
+            }
+        }
+    }
+
+    // Whether the actor may perform the action on the data.
+    fn allowed(&self, actor: Actor, action: Action) -> bool {
+        let key = match actor {
+            Actor::Owner => return true,
+            Actor::App => self.connections[1].key,
+            Actor::Stranger => return false,
+        };
+
+        let allowed = |user: &User| {
+            self.model.permissions.get(user).and_then(
+                |set| set.is_allowed(action),
+            )
+        };
+        allowed(&User::Key(key)).or_else(|| allowed(&User::Anyone)) == Some(true)
+    }
+
+    fn mutate_entries(
+        &mut self,
+        actor: Actor,
+        actions: BTreeMap<Vec<u8>, EntryAction>,
+    ) -> Result<(), ClientError> {
+        let (name, client_mgr) = (self.name, self.client_mgr);
+
+        let conn = self.connection(actor);
+        let msg_id = MessageId::new();
+        unwrap!(conn.routing.mutate_mdata_entries(
+            client_mgr,
+            name,
+            TAG,
+            actions,
+            msg_id,
+            conn.key,
+        ));
+        response!(conn.routing_rx, msg_id, Response::MutateMDataEntries)
+    }
+
+    fn account_info(&mut self) -> AccountInfo {
+        let client_mgr = self.client_mgr;
+        let owner = self.connection(Actor::Owner);
+        let msg_id = MessageId::new();
+        unwrap!(owner.routing.get_account_info(client_mgr, msg_id));
+        unwrap!(response!(owner.routing_rx, msg_id, Response::GetAccountInfo))
+    }
+
+    fn connection(&mut self, actor: Actor) -> &mut Connection {
+        &mut self.connections[actor as usize]
+    }
+
+    fn user(&self, grantee: Grantee) -> User {
+        match grantee {
+            Grantee::App => User::Key(self.connections[1].key),
+            Grantee::Anyone => User::Anyone,
+        }
+    }
+
+    fn next_content(&mut self) -> Vec<u8> {
+        self.next_content = self.next_content.wrapping_add(1);
+        vec![self.next_content; 8]
+    }
+}
+
+// Connect a new client to the given vault.
+fn connect(vault: &Arc<Mutex<Vault>>) -> Connection {
+    let full_id = FullId::new();
+    let (routing_tx, routing_rx) = mpsc::channel();
+    let mut routing = unwrap!(Routing::new(
+        routing_tx,
+        Some(full_id.clone()),
+        None,
+        Duration::new(0, 0),
+    ));
+    routing.set_vault(vault);
+
+    match unwrap!(routing_rx.recv_timeout(Duration::from_secs(10))) {
+        Event::Connected => (),
+        e => panic!("Unexpected event {:?}", e),
+    }
+
+    Connection {
+        routing,
+        routing_rx,
+        key: *full_id.public_id().signing_public_key(),
+    }
+}
@@ -16,6 +16,10 @@
 // relating to use of the SAFE Network Software.
 
 mod account;
+#[cfg(any(test, feature = "testing"))]
+pub mod fixture;
+#[cfg(test)]
+mod invariants;
 mod routing;
 #[cfg(test)]
 mod tests;
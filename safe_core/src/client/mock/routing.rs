@@ -19,6 +19,7 @@
 
 use super::DataId;
 use super::vault::{self, Data, Vault, VaultGuard};
+use client::{NetworkMode, network_mode};
 use config_handler::{Config, get_config};
 use maidsafe_utilities::thread;
 use rand;
@@ -66,6 +67,15 @@ const LIST_AUTH_KEYS_AND_VERSION_DELAY_MS: u64 = DEFAULT_DELAY_MS;
 const INS_AUTH_KEY_DELAY_MS: u64 = DEFAULT_DELAY_MS;
 const DEL_AUTH_KEY_DELAY_MS: u64 = DEFAULT_DELAY_MS;
 
+// Forwards the request to the live network, if this instance is connected to one.
+macro_rules! forward_to_live {
+    ($self_:ident, $method:ident($($arg:expr),*)) => {
+        if let Some(ref mut live) = $self_.live {
+            return live.$method($($arg),*);
+        }
+    }
+}
+
 lazy_static! {
     static ref VAULT: Arc<Mutex<Vault>> = Arc::new(Mutex::new(Vault::new(get_config())));
 }
@@ -98,6 +108,7 @@ pub struct Routing {
     timeout_simulation: bool,
     request_hook: Option<Box<RequestHookFn>>,
     response_hook: Option<Box<ResponseHookFn>>,
+    live: Option<::routing::Client>,
 }
 
 impl Routing {
@@ -131,6 +142,39 @@ impl Routing {
             timeout_simulation: false,
             request_hook: None,
             response_hook: None,
+            live: None,
+        })
+    }
+
+    /// Connects to a live network, forwarding all requests to it. Hooks and
+    /// simulated failures have no effect on such instances.
+    pub fn live(
+        sender: Sender<Event>,
+        id: Option<FullId>,
+        bootstrap_config: Option<BootstrapConfig>,
+        msg_expiry_dur: Duration,
+    ) -> Result<Self, RoutingError> {
+        let full_id = id.unwrap_or_else(FullId::new);
+        let live = ::routing::Client::new(
+            sender.clone(),
+            Some(full_id.clone()),
+            bootstrap_config,
+            msg_expiry_dur,
+        )?;
+
+        Ok(Routing {
+            vault: clone_vault(),
+            sender: sender,
+            full_id: full_id,
+            client_auth: Authority::Client {
+                client_id: *FullId::new().public_id(),
+                proxy_node_name: rand::random(),
+            },
+            max_ops_countdown: None,
+            timeout_simulation: false,
+            request_hook: None,
+            response_hook: None,
+            live: Some(live),
         })
     }
 
@@ -145,6 +189,7 @@ impl Routing {
         dst: Authority<XorName>,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, get_account_info(dst, msg_id));
         let client_auth = self.client_auth;
 
         let skip = self.intercept_request(GET_ACCOUNT_INFO_DELAY_MS, dst, client_auth, || {
@@ -189,6 +234,7 @@ impl Routing {
         data: ImmutableData,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, put_idata(dst, data, msg_id));
         let data_name = *data.name();
         let client_auth = self.client_auth;
         let nae_auth = Authority::NaeManager(data_name);
@@ -238,6 +284,7 @@ impl Routing {
         name: XorName,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, get_idata(dst, name, msg_id));
         let client_auth = self.client_auth;
         let nae_auth = Authority::NaeManager(name);
 
@@ -280,6 +327,7 @@ impl Routing {
         msg_id: MessageId,
         requester: sign::PublicKey,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, put_mdata(dst, data, msg_id, requester));
         let data_name = DataId::mutable(*data.name(), data.tag());
         let client_auth = self.client_auth;
         let nae_auth = Authority::NaeManager(*data_name.name());
@@ -346,6 +394,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, get_mdata_version(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -364,6 +413,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, get_mdata(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -382,6 +432,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, get_mdata_shell(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -400,6 +451,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, list_mdata_entries(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -418,6 +470,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, list_mdata_keys(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -439,6 +492,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, list_mdata_values(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -461,6 +515,7 @@ impl Routing {
         key: Vec<u8>,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, get_mdata_value(dst, name, tag, key, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -486,6 +541,7 @@ impl Routing {
         msg_id: MessageId,
         requester: sign::PublicKey,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, mutate_mdata_entries(dst, name, tag, actions, msg_id, requester));
         let actions2 = actions.clone();
 
         self.mutate_mdata(dst,
@@ -513,6 +569,7 @@ impl Routing {
         tag: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, list_mdata_permissions(dst, name, tag, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -532,6 +589,7 @@ impl Routing {
         user: User,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, list_mdata_user_permissions(dst, name, tag, user, msg_id));
         self.read_mdata(dst,
                         name,
                         tag,
@@ -560,6 +618,19 @@ impl Routing {
         msg_id: MessageId,
         requester: sign::PublicKey,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(
+            self,
+            set_mdata_user_permissions(
+                dst,
+                name,
+                tag,
+                user,
+                permissions,
+                version,
+                msg_id,
+                requester
+            )
+        );
         self.mutate_mdata(dst,
                           name,
                           tag,
@@ -590,6 +661,10 @@ impl Routing {
         msg_id: MessageId,
         requester: sign::PublicKey,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(
+            self,
+            del_mdata_user_permissions(dst, name, tag, user, version, msg_id, requester)
+        );
         self.mutate_mdata(dst,
                           name,
                           tag,
@@ -618,6 +693,7 @@ impl Routing {
         version: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, change_mdata_owner(dst, name, tag, new_owners, version, msg_id));
         let new_owners_len = new_owners.len();
         let new_owner = match new_owners.into_iter().next() {
             Some(owner) if new_owners_len == 1 => owner,
@@ -681,6 +757,7 @@ impl Routing {
         dst: Authority<XorName>,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, list_auth_keys_and_version(dst, msg_id));
         let client_auth = self.client_auth;
 
         let skip =
@@ -725,6 +802,7 @@ impl Routing {
         version: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, ins_auth_key(dst, key, version, msg_id));
         let client_auth = self.client_auth;
 
         let skip = self.intercept_request(INS_AUTH_KEY_DELAY_MS, dst, client_auth, || {
@@ -772,6 +850,7 @@ impl Routing {
         version: u64,
         msg_id: MessageId,
     ) -> Result<(), InterfaceError> {
+        forward_to_live!(self, del_auth_key(dst, key, version, msg_id));
         let client_auth = self.client_auth;
 
         let skip = self.intercept_request(DEL_AUTH_KEY_DELAY_MS, dst, client_auth, || {
@@ -1009,6 +1088,9 @@ impl Routing {
 
     /// Returns the default boostrap config.
     pub fn bootstrap_config() -> Result<BootstrapConfig, InterfaceError> {
+        if network_mode() == NetworkMode::Live {
+            return ::routing::Client::bootstrap_config();
+        }
         Ok(BootstrapConfig::default())
     }
 
@@ -1018,6 +1100,20 @@ impl Routing {
         vault.config()
     }
 
+    /// Lists names of the `MutableData` with the given type tag owned by `owner`. There is
+    /// no such request on the network, so this fails when connected to it.
+    pub fn list_owned_mdata(
+        &self,
+        owner: &sign::PublicKey,
+        tag: u64,
+    ) -> Result<Vec<XorName>, ClientError> {
+        if self.live.is_some() {
+            return Err(ClientError::from("Not supported by the network"));
+        }
+        let vault = self.lock_vault(false);
+        Ok(vault.list_owned_mdata(owner, tag))
+    }
+
     fn verify_network_limits(&self, msg_id: MessageId, op: &str) -> Result<(), ClientError> {
         let client_name = self.client_name();
 
@@ -1129,6 +1225,10 @@ impl Routing {
 
 impl Drop for Routing {
     fn drop(&mut self) {
+        // The live client notifies about its termination itself.
+        if self.live.is_some() {
+            return;
+        }
         let _ = self.sender.send(Event::Terminate);
     }
 }
@@ -199,6 +199,22 @@ impl Vault {
     pub fn insert_data(&mut self, name: DataId, data: Data) {
         let _ = self.cache.nae_manager.insert(name, data);
     }
+
+    // Names of the mutable data with the given type tag owned by `owner`, in ascending order.
+    pub fn list_owned_mdata(&self, owner: &sign::PublicKey, tag: u64) -> Vec<XorName> {
+        let mut names: Vec<_> = self.cache
+            .nae_manager
+            .values()
+            .filter_map(|data| match *data {
+                Data::Mutable(ref data) if data.tag() == tag && data.owners().contains(owner) => {
+                    Some(*data.name())
+                }
+                _ => None,
+            })
+            .collect();
+        names.sort();
+        names
+    }
 }
 
 pub struct VaultGuard<'a>(MutexGuard<'a, Vault>);
@@ -20,54 +20,94 @@ pub mod mdata_info;
 /// Operations with recovery.
 pub mod recovery;
 
+/// Routing client delegating the network connection to the host.
+#[cfg(feature = "use-host-routing")]
+pub mod host_routing;
+
 mod account;
-#[cfg(feature = "use-mock-routing")]
+mod key_store;
+#[cfg(feature = "mock-vault")]
 mod mock;
+mod net_stats;
+mod network_mode;
 mod routing_event_loop;
+mod scheduler;
 
 use self::account::Account;
-pub use self::account::ClientKeys;
-pub use self::mdata_info::MDataInfo;
-#[cfg(feature = "use-mock-routing")]
+pub use self::account::{ClientKeys, Kdf};
+use self::key_store::public_keys_only;
+pub use self::key_store::{KeyOp, KeyStore, MemoryKeyStore};
+pub use self::mdata_info::{LazyEntries, MDataInfo};
+#[cfg(feature = "mock-vault")]
 pub use self::mock::Routing as MockRouting;
-#[cfg(feature = "use-mock-routing")]
+#[cfg(all(feature = "use-host-routing", not(feature = "mock-vault")))]
+use self::host_routing::Routing;
+#[cfg(feature = "mock-vault")]
 use self::mock::Routing;
-#[cfg(feature = "use-mock-routing")]
+#[cfg(feature = "mock-vault")]
 pub use self::mock::vault::file_store_path as mock_vault_path;
+use self::net_stats::StatsTracker;
+pub use self::net_stats::{NetworkStats, RequestCounts};
+pub use self::network_mode::{NetworkMode, network_mode, set_network_mode};
+use self::scheduler::{Next, Scheduler};
+pub use self::scheduler::{Priority, SchedulerLimits};
+#[cfg(all(feature = "mock-vault", any(test, feature = "testing")))]
+pub use self::mock::fixture as mock_fixture;
+use config_handler::{ClientConfig, client_config};
 use crypto::{shared_box, shared_secretbox, shared_sign};
 use errors::CoreError;
 use event::{CoreEvent, NetworkEvent, NetworkTx};
 use event_loop::{CoreFuture, CoreMsgTx};
-use futures::{Complete, Future};
+use futures::{Complete, Future, IntoFuture, Stream};
 use futures::future::{self, Either, FutureResult, Loop, Then};
+use futures::stream;
 use futures::sync::oneshot;
-use ipc::BootstrapConfig;
+use futures::unsync::oneshot as unsync_oneshot;
+use ipc::{BootstrapConfig, now_secs};
+use limits;
 use lru_cache::LruCache;
-use maidsafe_utilities::serialisation::{deserialise, serialise};
+use maidsafe_utilities::serialisation::{deserialise, serialise, serialised_size};
 use maidsafe_utilities::thread::{self, Joiner};
-use routing::{ACC_LOGIN_ENTRY_KEY, AccountInfo, AccountPacket, Authority, EntryAction, Event,
-              FullId, ImmutableData, InterfaceError, MessageId, MutableData, PermissionSet,
-              Response, TYPE_TAG_SESSION_PACKET, User, Value, XorName};
-#[cfg(not(feature = "use-mock-routing"))]
+use routing::{ACC_LOGIN_ENTRY_KEY, AccountInfo, AccountPacket, Authority, ClientError,
+              EntryAction, Event, FullId, ImmutableData, InterfaceError, MessageId, MutableData,
+              PermissionSet, Response, TYPE_TAG_SESSION_PACKET, User, Value, XorName};
+#[cfg(not(any(feature = "mock-vault", feature = "use-host-routing")))]
 use routing::Client as Routing;
 use rust_sodium::crypto::box_;
 use rust_sodium::crypto::sign::{self, Seed};
 use std::cell::{Ref, RefCell, RefMut};
-use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
 use std::fmt;
 use std::io;
-use std::rc::Rc;
+use std::mem;
+use std::rc::{Rc, Weak};
 use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 use tiny_keccak::sha3_256;
 use tokio_core::reactor::{Handle, Timeout};
+use upload_ledger::{self, Upload};
 use utils::{self, FutureExt};
+use utils::secret::{MemoryLock, SecretBytes};
+use SESSION_PACKET_BACKUP_TAG;
 
-const CONNECTION_TIMEOUT_SECS: u64 = 40;
-const REQUEST_TIMEOUT_SECS: u64 = 180;
 const SEED_SUBPARTS: usize = 4;
-const IMMUT_DATA_CACHE_SIZE: usize = 300;
-const RETRY_DELAY_MS: u64 = 800;
+const PREFETCH_TTL_SECS: u64 = 30;
+/// Number of attempts `Client::mutate_with_retry` makes before giving up on a conflict.
+pub const MUTATE_RETRY_ATTEMPTS: usize = 5;
+
+/// Outcome of a conditional fetch, such as `Client::get_mdata_if_newer`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Fetched<T> {
+    /// The data hasn't changed since the known version.
+    NotModified,
+    /// The data has changed.
+    Modified {
+        /// Current version of the data.
+        version: u64,
+        /// The data itself.
+        data: T,
+    },
+}
 
 macro_rules! match_event {
     ($r:ident, $event:path) => {
@@ -82,8 +122,8 @@ macro_rules! match_event {
 }
 
 macro_rules! wait_for_response {
-    ($rx:expr, $res:path, $msg_id:expr) => {
-        match $rx.recv_timeout(Duration::from_secs(REQUEST_TIMEOUT_SECS)) {
+    ($rx:expr, $res:path, $msg_id:expr, $timeout:expr) => {
+        match $rx.recv_timeout($timeout) {
             Ok(Event::Response {
                 response: $res { res, msg_id: res_msg_id },
                 ..
@@ -117,13 +157,40 @@ pub struct Client<T> {
 
 struct Inner<T> {
     el_handle: Handle,
-    routing: Routing,
+    // `None` while the client is suspended.
+    routing: Option<Routing>,
     hooks: HashMap<MessageId, Complete<CoreEvent>>,
     cache: LruCache<XorName, ImmutableData>,
+    // Entries of mutable data fetched ahead of time, by name and type tag.
+    prefetched: HashMap<(XorName, u64), (Instant, BTreeMap<Vec<u8>, Value>)>,
+    // Mutable data whose entries are being prefetched, flagged once it's mutated meanwhile.
+    prefetching: HashMap<(XorName, u64), bool>,
+    prefetch_limit: usize,
+    // Maximum number of requests awaiting a response at once, zero meaning no limit.
+    max_pipeline_depth: usize,
+    in_flight: usize,
+    // Requests waiting for one of the in-flight ones to complete.
+    pipeline_queue: VecDeque<unsync_oneshot::Sender<PipelineSlot<T>>>,
+    // Keeps the credentials out of swap, if requested.
+    key_locks: Vec<MemoryLock>,
+    // Holds the secret keys, if the client has any.
+    key_store: Box<KeyStore>,
+    // Called with every use of the secret keys, if set.
+    key_usage_hook: Option<Rc<Fn(KeyOp, Option<XorName>)>>,
     client_type: ClientType,
+    // Settings read from `client_config` when the client was created.
+    settings: ClientConfig,
     timeout: Duration,
+    net_stats: StatsTracker,
     joiner: Joiner,
     session_packet_version: u64,
+    backup_packet: BackupPacket,
+    // Upload ledger and the ID of the app uploads get recorded for.
+    upload_ledger: Option<(MDataInfo, String)>,
+    // Uploads waiting to be recorded in the upload ledger by the next batch.
+    pending_uploads: BTreeMap<XorName, Upload>,
+    // Background jobs waiting to run.
+    scheduler: Scheduler<Box<Future<Item = (), Error = ()>>>,
     core_tx: CoreMsgTx<T>,
     net_tx: NetworkTx,
 }
@@ -145,19 +212,35 @@ impl<T: 'static> Client<T> {
         config: Option<BootstrapConfig>,
     ) -> Result<Self, CoreError> {
         trace!("Creating unregistered client.");
+        let settings = client_config();
 
-        let (routing, routing_rx) = setup_routing(None, config.clone())?;
+        let (routing, routing_rx) = setup_routing(None, config.clone(), &settings)?;
         let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
 
         Ok(Self::new(Inner {
             el_handle: el_handle,
-            routing: routing,
+            routing: Some(routing),
             hooks: HashMap::with_capacity(10),
-            cache: LruCache::new(IMMUT_DATA_CACHE_SIZE),
+            cache: LruCache::new(settings.idata_cache_size),
+            prefetched: HashMap::new(),
+            prefetching: HashMap::new(),
+            prefetch_limit: settings.prefetch_limit,
+            max_pipeline_depth: settings.max_pipeline_depth,
+            in_flight: 0,
+            pipeline_queue: VecDeque::new(),
+            key_locks: Vec::new(),
+            key_store: Box::new(MemoryKeyStore::default()),
+            key_usage_hook: None,
             client_type: ClientType::unreg(config),
-            timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            timeout: settings.request_timeout(),
+            settings: settings,
+            net_stats: StatsTracker::default(),
             joiner: joiner,
             session_packet_version: 0,
+            backup_packet: BackupPacket::Missing,
+            upload_ledger: None,
+            pending_uploads: BTreeMap::new(),
+            scheduler: Scheduler::new(SchedulerLimits::default()),
             net_tx: net_tx,
             core_tx: core_tx,
         }))
@@ -242,6 +325,7 @@ impl<T: 'static> Client<T> {
         F: Fn(Routing) -> Routing,
     {
         trace!("Creating an account.");
+        let settings = client_config();
 
         let (password, keyword, pin) = utils::derive_secrets(acc_locator, acc_password);
 
@@ -252,12 +336,17 @@ impl<T: 'static> Client<T> {
         let pub_key = maid_keys.sign_pk;
         let full_id = Some(maid_keys.clone().into());
 
-        let (mut routing, routing_rx) = setup_routing(full_id, None)?;
+        let (mut routing, routing_rx) = setup_routing(full_id, None, &settings)?;
         routing = routing_wrapper_fn(routing);
 
         let acc = Account::new(maid_keys)?;
 
         let acc_ciphertext = acc.encrypt(&user_cred.password, &user_cred.pin)?;
+        let backup_md = backup_account_packet(
+            Account::generate_backup_network_id(&acc_loc),
+            serialise(&AccountPacket::AccPkt(acc_ciphertext.clone()))?,
+            pub_key,
+        )?;
         let acc_data =
             btree_map![
             ACC_LOGIN_ENTRY_KEY.to_owned() => Value {
@@ -289,25 +378,66 @@ impl<T: 'static> Client<T> {
             .put_mdata(cm_addr, acc_md.clone(), msg_id, pub_key)
             .map_err(CoreError::from)
             .and_then(|_| {
-                wait_for_response!(routing_rx, Response::PutMData, msg_id)
+                wait_for_response!(
+                    routing_rx,
+                    Response::PutMData,
+                    msg_id,
+                    settings.request_timeout()
+                )
             })
             .map_err(|e| {
                 warn!("Could not put account to the Network: {:?}", e);
                 e
             })?;
 
+        // The account exists at this point, so a failure to store the backup doesn't fail
+        // the registration. The backup gets created by the next `update_account_packet`.
+        let msg_id = MessageId::new();
+        let backup_packet = match routing
+            .put_mdata(cm_addr, backup_md, msg_id, pub_key)
+            .map_err(CoreError::from)
+            .and_then(|_| {
+                wait_for_response!(
+                    routing_rx,
+                    Response::PutMData,
+                    msg_id,
+                    settings.request_timeout()
+                )
+            }) {
+            Ok(_) => BackupPacket::Version(0),
+            Err(e) => {
+                warn!("Could not put the backup account packet: {:?}", e);
+                BackupPacket::Unknown
+            }
+        };
+
         // Create the client
         let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
 
         Ok(Self::new(Inner {
             el_handle: el_handle,
-            routing: routing,
+            routing: Some(routing),
             hooks: HashMap::with_capacity(10),
-            cache: LruCache::new(IMMUT_DATA_CACHE_SIZE),
+            cache: LruCache::new(settings.idata_cache_size),
+            prefetched: HashMap::new(),
+            prefetching: HashMap::new(),
+            prefetch_limit: settings.prefetch_limit,
+            max_pipeline_depth: settings.max_pipeline_depth,
+            in_flight: 0,
+            pipeline_queue: VecDeque::new(),
+            key_locks: Vec::new(),
+            key_store: Box::new(MemoryKeyStore::new(acc.maid_keys.clone())),
+            key_usage_hook: None,
             client_type: ClientType::reg(acc, acc_loc, user_cred, cm_addr),
-            timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            timeout: settings.request_timeout(),
+            settings: settings,
+            net_stats: StatsTracker::default(),
             joiner: joiner,
             session_packet_version: 0,
+            backup_packet: backup_packet,
+            upload_ledger: None,
+            pending_uploads: BTreeMap::new(),
+            scheduler: Scheduler::new(SchedulerLimits::default()),
             net_tx: net_tx,
             core_tx: core_tx,
         }))
@@ -367,45 +497,86 @@ impl<T: 'static> Client<T> {
         F: Fn(Routing) -> Routing,
     {
         trace!("Attempting to log into an acc.");
+        let settings = client_config();
 
         let (password, keyword, pin) = utils::derive_secrets(acc_locator, acc_password);
 
         let acc_loc = Account::generate_network_id(&keyword, &pin)?;
-        let user_cred = UserCred::new(password, pin);
+        let mut user_cred = UserCred::new(password, pin);
 
-        let dst = Authority::NaeManager(acc_loc);
+        let backup_loc = Account::generate_backup_network_id(&acc_loc);
 
-        let (acc_content, acc_version) = {
+        let (primary, backup) = {
             trace!("Creating throw-away routing getter for account packet.");
-            let (mut routing, routing_rx) = setup_routing(None, None)?;
+            let (mut routing, routing_rx) = setup_routing(None, None, &settings)?;
             routing = routing_wrapper_fn(routing);
 
-            let msg_id = MessageId::new();
-            let val = routing
-                .get_mdata_value(
-                    dst,
-                    acc_loc,
-                    TYPE_TAG_SESSION_PACKET,
-                    ACC_LOGIN_ENTRY_KEY.to_owned(),
-                    msg_id,
-                )
-                .map_err(CoreError::from)
-                .and_then(|_| {
-                    wait_for_response!(routing_rx, Response::GetMDataValue, msg_id)
-                })
-                .map_err(|e| {
-                    warn!("Could not fetch account from the Network: {:?}", e);
-                    e
-                })?;
-            (val.content, val.entry_version)
+            let primary = get_account_packet(
+                &mut routing,
+                &routing_rx,
+                acc_loc,
+                TYPE_TAG_SESSION_PACKET,
+                settings.request_timeout(),
+            );
+            let backup = get_account_packet(
+                &mut routing,
+                &routing_rx,
+                backup_loc,
+                SESSION_PACKET_BACKUP_TAG,
+                settings.request_timeout(),
+            );
+            (primary, backup)
         };
 
-        let acc = match deserialise::<AccountPacket>(&acc_content)? {
-            AccountPacket::AccPkt(acc_content) |
-            AccountPacket::WithInvitation { acc_pkt: acc_content, .. } => {
-                Account::decrypt(&acc_content, &user_cred.password, &user_cred.pin)?
+        let (primary_version, primary_content) = match primary {
+            Ok(value) => (value.entry_version, Ok(value.content)),
+            Err(error) => (0, Err(error)),
+        };
+        let backup_packet = match backup {
+            Ok(ref value) => BackupPacket::Version(value.entry_version),
+            Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => BackupPacket::Missing,
+            Err(_) => BackupPacket::Unknown,
+        };
+        let backup_content = backup.ok().map(|value| value.content);
+
+        // The account packet of a deleted account is left empty.
+        if let Ok(ref content) = primary_content {
+            if content.is_empty() {
+                return Err(CoreError::RoutingClientError(ClientError::NoSuchAccount));
+            }
+        }
+
+        // A primary copy which has been fetched but can't be decrypted is replaced by the
+        // backup, as long as that one can be decrypted. A failure to fetch the primary copy
+        // fails login, as the backup might be older than it.
+        let content = match primary_content {
+            Ok(content) => content,
+            Err(error) => {
+                warn!("Could not fetch account from the Network: {:?}", error);
+                return Err(error);
+            }
+        };
+        let ((acc, kdf, migrated), damaged) = match decrypt_account_packet(&content, &user_cred) {
+            Ok(decrypted) => {
+                let in_sync = backup_content.map_or(false, |backup| {
+                    account_packet_cipher_text(&backup) == account_packet_cipher_text(&content)
+                });
+                (decrypted, !in_sync)
+            }
+            Err(error) => {
+                match backup_content.map(|content| decrypt_account_packet(&content, &user_cred)) {
+                    Some(Ok(decrypted)) => {
+                        warn!("Logging in with the backup account packet: {:?}", error);
+                        (decrypted, true)
+                    }
+                    _ => {
+                        warn!("Could not decrypt the account packet: {:?}", error);
+                        return Err(error);
+                    }
+                }
             }
         };
+        user_cred.kdf = kdf;
 
         let id_packet = acc.maid_keys.clone().into();
 
@@ -414,23 +585,54 @@ impl<T: 'static> Client<T> {
         let cm_addr = Authority::ClientManager(XorName(digest));
 
         trace!("Creating an actual routing...");
-        let (mut routing, routing_rx) = setup_routing(Some(id_packet), None)?;
+        let (mut routing, routing_rx) = setup_routing(Some(id_packet), None, &settings)?;
         routing = routing_wrapper_fn(routing);
 
         let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
 
-        Ok(Self::new(Inner {
-            el_handle: el_handle,
-            routing: routing,
+        let client = Self::new(Inner {
+            el_handle: el_handle.clone(),
+            routing: Some(routing),
             hooks: HashMap::with_capacity(10),
-            cache: LruCache::new(IMMUT_DATA_CACHE_SIZE),
+            cache: LruCache::new(settings.idata_cache_size),
+            prefetched: HashMap::new(),
+            prefetching: HashMap::new(),
+            prefetch_limit: settings.prefetch_limit,
+            max_pipeline_depth: settings.max_pipeline_depth,
+            in_flight: 0,
+            pipeline_queue: VecDeque::new(),
+            key_locks: Vec::new(),
+            key_store: Box::new(MemoryKeyStore::new(acc.maid_keys.clone())),
+            key_usage_hook: None,
             client_type: ClientType::reg(acc, acc_loc, user_cred, cm_addr),
-            timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            timeout: settings.request_timeout(),
+            settings: settings,
+            net_stats: StatsTracker::default(),
             joiner: joiner,
-            session_packet_version: acc_version,
+            session_packet_version: primary_version,
+            backup_packet: backup_packet,
+            upload_ledger: None,
+            pending_uploads: BTreeMap::new(),
+            scheduler: Scheduler::new(SchedulerLimits::default()),
             net_tx: net_tx,
             core_tx: core_tx,
-        }))
+        });
+
+        if migrated {
+            // Store the upgraded layout once the event loop runs. The account stays usable
+            // if this fails, and the upgrade is retried on the next login.
+            trace!("Replacing the account packet with its upgraded version.");
+            el_handle.spawn(client.update_account_packet().map_err(|e| {
+                warn!("Could not store the upgraded account packet: {:?}", e);
+            }));
+        } else if damaged {
+            trace!("Repairing the account packet.");
+            el_handle.spawn(client.repair_account_packet().map_err(|e| {
+                warn!("Could not repair the account packet: {:?}", e);
+            }));
+        }
+
+        Ok(client)
     }
 
     /// This is a Gateway function to the Maidsafe network. This will help
@@ -468,20 +670,36 @@ impl<T: 'static> Client<T> {
         F: Fn(Routing) -> Routing,
     {
         trace!("Attempting to log into an acc using client keys.");
+        let settings = client_config();
         let (mut routing, routing_rx) =
-            setup_routing(Some(keys.clone().into()), Some(config.clone()))?;
+            setup_routing(Some(keys.clone().into()), Some(config.clone()), &settings)?;
         routing = routing_wrapper_fn(routing);
         let joiner = spawn_routing_thread(routing_rx, core_tx.clone(), net_tx.clone());
 
         Ok(Self::new(Inner {
             el_handle: el_handle,
-            routing: routing,
+            routing: Some(routing),
             hooks: HashMap::with_capacity(10),
-            cache: LruCache::new(IMMUT_DATA_CACHE_SIZE),
-            client_type: ClientType::from_keys(keys, owner, config),
-            timeout: Duration::from_secs(REQUEST_TIMEOUT_SECS),
+            cache: LruCache::new(settings.idata_cache_size),
+            prefetched: HashMap::new(),
+            prefetching: HashMap::new(),
+            prefetch_limit: settings.prefetch_limit,
+            max_pipeline_depth: settings.max_pipeline_depth,
+            in_flight: 0,
+            pipeline_queue: VecDeque::new(),
+            key_locks: Vec::new(),
+            key_store: Box::new(MemoryKeyStore::new(keys.clone())),
+            key_usage_hook: None,
+            client_type: ClientType::from_keys(&keys, owner, config),
+            timeout: settings.request_timeout(),
+            settings: settings,
+            net_stats: StatsTracker::default(),
             joiner: joiner,
             session_packet_version: 0,
+            backup_packet: BackupPacket::Missing,
+            upload_ledger: None,
+            pending_uploads: BTreeMap::new(),
+            scheduler: Scheduler::new(SchedulerLimits::default()),
             net_tx: net_tx,
             core_tx: core_tx,
         }))
@@ -523,15 +741,108 @@ impl<T: 'static> Client<T> {
         self.inner_mut().timeout = duration;
     }
 
+    /// Set the maximum number of requests awaiting a response at once. Requests are sent
+    /// without waiting for the responses to earlier ones, up to this depth; further ones
+    /// are queued until earlier ones complete. Zero, the default, means no limit.
+    pub fn set_max_pipeline_depth(&self, depth: usize) {
+        self.inner_mut().max_pipeline_depth = depth;
+
+        // Let queued requests through if the depth has grown.
+        loop {
+            let tx = {
+                let mut inner = self.inner_mut();
+                if depth != 0 && inner.in_flight >= depth {
+                    break;
+                }
+                match inner.pipeline_queue.pop_front() {
+                    Some(tx) => {
+                        inner.in_flight += 1;
+                        tx
+                    }
+                    None => break,
+                }
+            };
+            // A slot returned by a cancelled request releases itself when dropped here.
+            let _ = tx.send(PipelineSlot { inner: Rc::downgrade(&self.inner) });
+        }
+    }
+
+    /// Set the maximum number of listings `prefetch_mdata_entries` fetches concurrently.
+    /// Zero, the default, disables prefetching.
+    pub fn set_prefetch_limit(&self, limit: usize) {
+        self.inner_mut().prefetch_limit = limit;
+    }
+
+    /// Record every `ImmutableData` put from now on in the given upload ledger, attributed to
+    /// the app with the given ID. `None` stops recording. Uploads not recorded yet still go to
+    /// the previous ledger.
+    pub fn set_upload_ledger(&self, ledger: Option<(MDataInfo, String)>) {
+        if let Some((old, uploads)) = self.take_pending_uploads() {
+            let client = self.clone();
+            let _ = self.schedule(Priority::Low, move || {
+                record_uploads(&client, &old, uploads)
+            });
+        }
+        self.inner_mut().upload_ledger = ledger;
+    }
+
+    /// Set the limits on the background jobs run by `schedule`.
+    pub fn set_scheduler_limits(&self, limits: SchedulerLimits) {
+        self.inner_mut().scheduler.set_limits(limits);
+        self.run_scheduled();
+    }
+
+    /// Run `job` in the background on the event loop, once the jobs of higher priority and
+    /// those scheduled before it have started and the limits set by `set_scheduler_limits`
+    /// allow. Meant for work the user isn't waiting for, like refreshing caches or updating
+    /// ledgers, so that it doesn't compete with the user's requests. The job is called only
+    /// when it starts. Returns the result of the job; it runs even if the result is dropped.
+    pub fn schedule<F, R>(&self, priority: Priority, job: F) -> Box<CoreFuture<R::Item>>
+    where
+        F: FnOnce() -> R + 'static,
+        R: IntoFuture<Error = CoreError> + 'static,
+        R::Item: 'static,
+    {
+        let (tx, rx) = unsync_oneshot::channel();
+        let job = future::lazy(job).then(move |res| {
+            let _ = tx.send(res);
+            Ok::<_, ()>(())
+        });
+
+        self.inner_mut().scheduler.push(priority, job.into_box());
+        self.run_scheduled();
+
+        rx.map_err(|_| CoreError::OperationAborted)
+            .and_then(|res| res)
+            .into_box()
+    }
+
+    /// Disconnect from the network without discarding the client's keys, e.g. when
+    /// the hosting application is moved to the background. Pending requests are
+    /// aborted and new ones fail until `restart_routing` is called.
+    pub fn suspend(&self) {
+        let _ = self.inner_mut().routing.take();
+        self.inner_mut().hooks.clear();
+        self.inner_mut().net_stats.forget_pending();
+    }
+
+    /// Returns `true` if the client has been suspended.
+    pub fn is_suspended(&self) -> bool {
+        self.inner().routing.is_none()
+    }
+
     /// Restart the routing client and reconnect to the network.
+    /// This also resumes a suspended client.
     pub fn restart_routing(&self) -> Result<(), CoreError> {
         let opt_id = match self.inner().client_type {
-            ClientType::Registered { ref acc, .. } => Some(acc.maid_keys.clone().into()),
-            ClientType::FromKeys { ref keys, .. } => Some(keys.clone().into()),
+            ClientType::Registered { .. } |
+            ClientType::FromKeys { .. } => Some(self.inner().key_store.load()?.into()),
             ClientType::Unregistered { .. } => None,
         };
 
-        let (routing, routing_rx) = setup_routing(opt_id, self.inner().client_type.config())?;
+        let settings = self.inner().settings.clone();
+        let (routing, routing_rx) =
+            setup_routing(opt_id, self.inner().client_type.config(), &settings)?;
 
         let joiner = spawn_routing_thread(
             routing_rx,
@@ -540,7 +851,8 @@ impl<T: 'static> Client<T> {
         );
 
         self.inner_mut().hooks.clear();
-        self.inner_mut().routing = routing;
+        self.inner_mut().net_stats.forget_pending();
+        self.inner_mut().routing = Some(routing);
         self.inner_mut().joiner = joiner;
 
         self.inner().net_tx.unbounded_send(NetworkEvent::Connected)?;
@@ -548,6 +860,21 @@ impl<T: 'static> Client<T> {
         Ok(())
     }
 
+    /// Maximum number of chunk PUTs of a self-encrypted upload in flight at once.
+    pub fn put_window(&self) -> usize {
+        self.inner().settings.put_window
+    }
+
+    /// Returns the statistics of the traffic between this client and the network.
+    pub fn network_stats(&self) -> NetworkStats {
+        self.inner().net_stats.stats().clone()
+    }
+
+    #[doc(hidden)]
+    pub fn record_response(&self, id: &MessageId, bytes: u64, failed: bool) {
+        self.inner_mut().net_stats.response_received(id, bytes, failed);
+    }
+
     #[doc(hidden)]
     pub fn fire_hook(&self, id: &MessageId, event: CoreEvent) {
         // Using in `if` keeps borrow alive. Do not try to combine the 2 lines into one.
@@ -589,7 +916,7 @@ impl<T: 'static> Client<T> {
         }
 
         let inner = Rc::downgrade(&self.inner);
-        self.send(move |routing, msg_id| {
+        self.send("GetIData", serialised_size(&name), move |routing, msg_id| {
             routing.get_idata(Authority::NaeManager(name), name, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetIData))
             .map(move |data| {
@@ -602,24 +929,87 @@ impl<T: 'static> Client<T> {
             .into_box()
     }
 
+    /// Get several immutable data from the network. The GETs are issued concurrently and
+    /// the results are returned in the same order as `names`. Fails if any of them fails.
+    pub fn get_idata_many(&self, names: Vec<XorName>) -> Box<CoreFuture<Vec<ImmutableData>>> {
+        trace!("GetIData for {} names", names.len());
+
+        let fetches: Vec<_> = names.into_iter().map(|name| self.get_idata(name)).collect();
+        future::join_all(fetches).into_box()
+    }
+
     // TODO All these return the same future from all branches. So convert to impl
     // Trait when it arrives in stable. Change from `Box<CoreFuture>` -> `impl
     // CoreFuture`.
     /// Put immutable data onto the network.
+    /// The upload is recorded in the upload ledger, if one is set, once the put succeeds.
+    /// Uploads are recorded in batches by background jobs of low priority, so that a whole
+    /// batch costs a single mutation.
     pub fn put_idata(&self, data: ImmutableData) -> Box<CoreFuture<()>> {
         trace!("PutIData for {:?}", data);
 
-        self.send_mutation(move |routing, dst, msg_id| {
+        let name = *data.name();
+        let bytes = serialised_size(&data);
+        let put = self.send_mutation("PutIData", Some(name), bytes, move |routing, dst, msg_id| {
             routing.put_idata(dst, data.clone(), msg_id)
-        })
+        });
+
+        let (ledger, app_id) = match self.inner().upload_ledger.clone() {
+            Some(ledger) => ledger,
+            None => return put,
+        };
+        let client = self.clone();
+
+        put.map(move |()| {
+            let upload = Upload {
+                size: bytes,
+                uploaded_at: now_secs(),
+                app_id: app_id,
+            };
+            let first = {
+                let mut inner = client.inner_mut();
+                // The ledger might have been replaced while the put was in flight.
+                if inner.upload_ledger.as_ref().map(|&(ref info, _)| info) != Some(&ledger) {
+                    return;
+                }
+                let first = inner.pending_uploads.is_empty();
+                let _ = inner.pending_uploads.insert(name, upload);
+                first
+            };
+            // Uploads succeeding before the job starts join its batch.
+            if first {
+                let client2 = client.clone();
+                let _ = client.schedule(Priority::Low, move || {
+                    match client2.take_pending_uploads() {
+                        Some((ledger, uploads)) => record_uploads(&client2, &ledger, uploads),
+                        None => ok!(()),
+                    }
+                });
+            }
+        }).into_box()
+    }
+
+    // Takes the uploads waiting to be recorded, together with the ledger they're for.
+    fn take_pending_uploads(&self) -> Option<(MDataInfo, BTreeMap<XorName, Upload>)> {
+        let mut inner = self.inner_mut();
+        if inner.pending_uploads.is_empty() {
+            return None;
+        }
+        let uploads = mem::replace(&mut inner.pending_uploads, BTreeMap::new());
+        inner.upload_ledger.clone().map(
+            |(ledger, _)| (ledger, uploads),
+        )
     }
 
     /// Put `MutableData` onto the network.
     pub fn put_mdata(&self, data: MutableData) -> Box<CoreFuture<()>> {
         trace!("PutMData for {:?}", data);
 
+        fry!(limits::check_mdata(&data));
         let requester = fry!(self.public_signing_key());
-        self.send_mutation(move |routing, dst, msg_id| {
+        let name = *data.name();
+        let bytes = serialised_size(&data);
+        self.send_mutation("PutMData", Some(name), bytes, move |routing, dst, msg_id| {
             routing.put_mdata(dst, data.clone(), msg_id, requester)
         })
     }
@@ -633,27 +1023,160 @@ impl<T: 'static> Client<T> {
     ) -> Box<CoreFuture<()>> {
         trace!("PutMData for {:?}", name);
 
+        fry!(limits::check_entry_actions(&actions));
+        self.invalidate_prefetched(name, tag);
+
         let requester = fry!(self.public_signing_key());
-        self.send_mutation(move |routing, dst, msg_id| {
+        let bytes = serialised_size(&(name, tag, &actions));
+        self.send_mutation("MutateMDataEntries", Some(name), bytes, move |routing, dst, msg_id| {
             routing.mutate_mdata_entries(dst, name, tag, actions.clone(), msg_id, requester)
         })
     }
 
+    /// Mutates the entries of the given `MutableData`, resolving conflicts with concurrent
+    /// writers. `f` is called with the current decrypted entries and returns the plain-text
+    /// actions to apply. When the mutation fails with `InvalidSuccessor` or
+    /// `InvalidEntryActions`, the entries it touched are fetched again and `f` is re-applied,
+    /// up to `MUTATE_RETRY_ATTEMPTS` attempts in total. Returning no actions from `f` completes
+    /// without mutating anything.
+    pub fn mutate_with_retry<F>(&self, info: &MDataInfo, f: F) -> Box<CoreFuture<()>>
+    where
+        F: FnMut(&BTreeMap<Vec<u8>, Value>) -> BTreeMap<Vec<u8>, EntryAction> + 'static,
+    {
+        trace!("Mutating entries of {:?} with retry", info.name);
+
+        let client = self.clone();
+        let info = info.clone();
+        let info2 = info.clone();
+
+        self.list_mdata_entries(info.name, info.type_tag)
+            .and_then(move |entries| mdata_info::decrypt_entries(&info, &entries))
+            .and_then(move |entries| {
+                future::loop_fn((f, entries, 1), move |(mut f, mut entries, attempt)| {
+                    let actions = f(&entries);
+                    if actions.is_empty() {
+                        return Either::A(future::ok(Loop::Break(())));
+                    }
+
+                    let encrypted = match mdata_info::encrypt_entry_actions(&info2, &actions) {
+                        Ok(encrypted) => encrypted,
+                        Err(error) => return Either::A(future::err(error)),
+                    };
+                    let keys: Vec<_> = actions.into_iter().map(|(key, _)| key).collect();
+
+                    let client2 = client.clone();
+                    let info3 = info2.clone();
+
+                    let fut = client
+                        .mutate_mdata_entries(info2.name, info2.type_tag, encrypted)
+                        .map(|()| Loop::Break(()))
+                        .or_else(move |error| match error {
+                            CoreError::RoutingClientError(ClientError::InvalidSuccessor(_)) |
+                            CoreError::RoutingClientError(ClientError::InvalidEntryActions(_))
+                                if attempt < MUTATE_RETRY_ATTEMPTS => {
+                                debug!(
+                                    "Conflicting mutation of {:?} (attempt {}), retrying",
+                                    info3.name,
+                                    attempt
+                                );
+
+                                let fut = client2.fetch_mdata_values(&info3, keys.clone()).map(
+                                    move |mut fresh| {
+                                        for key in keys {
+                                            if let Some(value) = fresh.remove(&key) {
+                                                let _ = entries.insert(key, value);
+                                            } else {
+                                                let _ = entries.remove(&key);
+                                            }
+                                        }
+                                        Loop::Continue((f, entries, attempt + 1))
+                                    },
+                                );
+                                Either::A(fut)
+                            }
+                            _ => Either::B(future::err(error)),
+                        });
+                    Either::B(fut)
+                })
+            })
+            .into_box()
+    }
+
+    // Fetch the current decrypted values of the given plain-text keys. Missing entries are
+    // left out of the result.
+    fn fetch_mdata_values(
+        &self,
+        info: &MDataInfo,
+        keys: Vec<Vec<u8>>,
+    ) -> Box<CoreFuture<BTreeMap<Vec<u8>, Value>>> {
+        let info = info.clone();
+        let mut futures = Vec::with_capacity(keys.len());
+
+        for key in keys {
+            let key = fry!(info.enc_entry_key(&key));
+            let fut = self.get_mdata_value(info.name, info.type_tag, key.clone())
+                .map(move |value| Some((key, value)))
+                .or_else(|error| match error {
+                    CoreError::RoutingClientError(ClientError::NoSuchEntry) => Ok(None),
+                    error => Err(error),
+                });
+            futures.push(fut);
+        }
+
+        future::join_all(futures)
+            .and_then(move |values| {
+                let entries = values.into_iter().filter_map(|value| value).collect();
+                mdata_info::decrypt_entries(&info, &entries)
+            })
+            .into_box()
+    }
+
     /// Get entire `MutableData` from the network.
     pub fn get_mdata(&self, name: XorName, tag: u64) -> Box<CoreFuture<MutableData>> {
         trace!("GetMData for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag));
+        self.send("GetMData", bytes, move |routing, msg_id| {
             routing.get_mdata(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMData))
             .into_box()
     }
 
+    /// Get entire `MutableData` from the network, unless its version is still
+    /// `known_version`, in which case only the version is fetched and
+    /// `Fetched::NotModified` returned. Mind that the version of `MutableData` changes with
+    /// its permissions and owners only, not with its entries.
+    pub fn get_mdata_if_newer(
+        &self,
+        name: XorName,
+        tag: u64,
+        known_version: u64,
+    ) -> Box<CoreFuture<Fetched<MutableData>>> {
+        let client = self.clone();
+
+        self.get_mdata_version(name, tag)
+            .and_then(move |version| if version == known_version {
+                ok!(Fetched::NotModified)
+            } else {
+                client
+                    .get_mdata(name, tag)
+                    .map(|data| {
+                        Fetched::Modified {
+                            version: data.version(),
+                            data: data,
+                        }
+                    })
+                    .into_box()
+            })
+            .into_box()
+    }
+
     /// Get a shell (bare bones) version of `MutableData` from the network.
     pub fn get_mdata_shell(&self, name: XorName, tag: u64) -> Box<CoreFuture<MutableData>> {
         trace!("GetMDataShell for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag));
+        self.send("GetMDataShell", bytes, move |routing, msg_id| {
             routing.get_mdata_shell(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMDataShell))
             .into_box()
@@ -663,7 +1186,8 @@ impl<T: 'static> Client<T> {
     pub fn get_mdata_version(&self, name: XorName, tag: u64) -> Box<CoreFuture<u64>> {
         trace!("GetMDataVersion for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag));
+        self.send("GetMDataVersion", bytes, move |routing, msg_id| {
             routing.get_mdata_version(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMDataVersion))
             .into_box()
@@ -677,17 +1201,159 @@ impl<T: 'static> Client<T> {
     ) -> Box<CoreFuture<BTreeMap<Vec<u8>, Value>>> {
         trace!("ListMDataEntries for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        if let Some(entries) = self.take_prefetched(name, tag) {
+            trace!("MutableData entries found among the prefetched ones.");
+            return future::ok(entries).into_box();
+        }
+
+        self.fetch_mdata_entries(name, tag)
+    }
+
+    /// Fetch the entries of the given `MutableData`s in a background job of high priority,
+    /// with at most the number set by `set_prefetch_limit` in flight at once. A following
+    /// `list_mdata_entries` for any of them then completes without a network round trip.
+    /// Prefetched entries are used only once and expire after a short while. Data whose
+    /// entries are already being prefetched is skipped.
+    pub fn prefetch_mdata_entries(&self, data: Vec<(XorName, u64)>) {
+        let limit = self.inner().prefetch_limit;
+        if limit == 0 || data.is_empty() {
+            return;
+        }
+        let data: Vec<_> = {
+            let mut inner = self.inner_mut();
+            data.into_iter()
+                .filter(|key| if inner.prefetching.contains_key(key) {
+                    false
+                } else {
+                    let _ = inner.prefetching.insert(*key, false);
+                    true
+                })
+                .collect()
+        };
+        if data.is_empty() {
+            return;
+        }
+        trace!("Prefetching entries of {} MutableData", data.len());
+
+        let client = self.clone();
+        let client2 = self.clone();
+
+        let prefetch = stream::iter_ok::<_, CoreError>(data)
+            .map(move |(name, tag)| {
+                client.fetch_mdata_entries(name, tag).then(move |res| {
+                    Ok::<_, CoreError>(((name, tag), res.ok()))
+                })
+            })
+            .buffer_unordered(limit)
+            .for_each(move |(key, res)| {
+                let inner = &mut *client2.inner_mut();
+                // Entries fetched before a mutation of the data would be stale.
+                let mutated = inner.prefetching.remove(&key).unwrap_or(true);
+                let entries = match res {
+                    Some(entries) if !mutated => entries,
+                    _ => return Ok(()),
+                };
+
+                let ttl = Duration::from_secs(PREFETCH_TTL_SECS);
+                inner.prefetched.retain(|_, &mut (fetched, _)| {
+                    fetched.elapsed() < ttl
+                });
+                let fresher = inner.prefetched.get(&key).map_or(true, |&(_, ref cached)| {
+                    is_not_older(cached, &entries)
+                });
+                if fresher {
+                    let _ = inner.prefetched.insert(key, (Instant::now(), entries));
+                }
+                Ok(())
+            });
+
+        let _ = self.schedule(Priority::High, move || prefetch);
+    }
+
+    // Starts the scheduled jobs the limits allow, arranging to try again later if they hold
+    // any back.
+    fn run_scheduled(&self) {
+        loop {
+            let next = {
+                let inner = &mut *self.inner_mut();
+                inner.scheduler.next(inner.in_flight, Instant::now())
+            };
+
+            let client = self.clone();
+            match next {
+                Next::Start(job) => {
+                    self.el_handle().spawn(job.then(move |_| {
+                        client.inner_mut().scheduler.finished();
+                        client.run_scheduled();
+                        Ok(())
+                    }));
+                }
+                Next::RetryIn(delay) => {
+                    match Timeout::new(delay, &self.el_handle()) {
+                        Ok(timeout) => {
+                            self.el_handle().spawn(timeout.then(move |_| {
+                                client.inner_mut().scheduler.retry_fired();
+                                client.run_scheduled();
+                                Ok(())
+                            }))
+                        }
+                        Err(err) => {
+                            warn!("Can't delay background jobs: {:?}", err);
+                            self.inner_mut().scheduler.retry_fired();
+                        }
+                    }
+                    return;
+                }
+                Next::Wait => return,
+            }
+        }
+    }
+
+    // Drops the prefetched entries of the given data and those being prefetched, because
+    // it's being mutated.
+    fn invalidate_prefetched(&self, name: XorName, tag: u64) {
+        let inner = &mut *self.inner_mut();
+        let _ = inner.prefetched.remove(&(name, tag));
+        if let Some(mutated) = inner.prefetching.get_mut(&(name, tag)) {
+            *mutated = true;
+        }
+    }
+
+    fn take_prefetched(&self, name: XorName, tag: u64) -> Option<BTreeMap<Vec<u8>, Value>> {
+        let (fetched, entries) = self.inner_mut().prefetched.remove(&(name, tag))?;
+        if fetched.elapsed() < Duration::from_secs(PREFETCH_TTL_SECS) {
+            Some(entries)
+        } else {
+            None
+        }
+    }
+
+    fn fetch_mdata_entries(
+        &self,
+        name: XorName,
+        tag: u64,
+    ) -> Box<CoreFuture<BTreeMap<Vec<u8>, Value>>> {
+        let bytes = serialised_size(&(name, tag));
+        self.send("ListMDataEntries", bytes, move |routing, msg_id| {
             routing.list_mdata_entries(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataEntries))
             .into_box()
     }
 
+    /// Returns entries of the `MutableData` described by `info`, to be decrypted only as
+    /// they're accessed.
+    pub fn list_mdata_entries_lazy(&self, info: MDataInfo) -> Box<CoreFuture<LazyEntries>> {
+        self.list_mdata_entries(info.name, info.type_tag)
+            .map(move |entries| LazyEntries::new(info, entries))
+            .into_box()
+    }
+
     /// Returns a list of keys in `MutableData` stored on the network
     pub fn list_mdata_keys(&self, name: XorName, tag: u64) -> Box<CoreFuture<BTreeSet<Vec<u8>>>> {
         trace!("ListMDataKeys for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag));
+        self.send("ListMDataKeys", bytes, move |routing, msg_id| {
             routing.list_mdata_keys(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataKeys))
             .into_box()
@@ -697,7 +1363,8 @@ impl<T: 'static> Client<T> {
     pub fn list_mdata_values(&self, name: XorName, tag: u64) -> Box<CoreFuture<Vec<Value>>> {
         trace!("ListMDataValues for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag));
+        self.send("ListMDataValues", bytes, move |routing, msg_id| {
             routing.list_mdata_values(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataValues))
             .into_box()
@@ -707,7 +1374,8 @@ impl<T: 'static> Client<T> {
     pub fn get_mdata_value(&self, name: XorName, tag: u64, key: Vec<u8>) -> Box<CoreFuture<Value>> {
         trace!("GetMDataValue for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag, &key));
+        self.send("GetMDataValue", bytes, move |routing, msg_id| {
             routing.get_mdata_value(Authority::NaeManager(name), name, tag, key.clone(), msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::GetMDataValue))
             .into_box()
@@ -718,8 +1386,10 @@ impl<T: 'static> Client<T> {
         trace!("Account info GET issued.");
 
         let dst = fry!(self.cm_addr());
-        self.send(move |routing, msg_id| routing.get_account_info(dst, msg_id))
-            .and_then(|event| match_event!(event, CoreEvent::GetAccountInfo))
+        let bytes = serialised_size(&dst);
+        self.send("GetAccountInfo", bytes, move |routing, msg_id| {
+            routing.get_account_info(dst, msg_id)
+        }).and_then(|event| match_event!(event, CoreEvent::GetAccountInfo))
             .into_box()
     }
 
@@ -731,7 +1401,8 @@ impl<T: 'static> Client<T> {
     ) -> Box<CoreFuture<BTreeMap<User, PermissionSet>>> {
         trace!("ListMDataPermissions for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag));
+        self.send("ListMDataPermissions", bytes, move |routing, msg_id| {
             routing.list_mdata_permissions(Authority::NaeManager(name), name, tag, msg_id)
         }).and_then(|event| match_event!(event, CoreEvent::ListMDataPermissions))
             .into_box()
@@ -746,7 +1417,8 @@ impl<T: 'static> Client<T> {
     ) -> Box<CoreFuture<PermissionSet>> {
         trace!("ListMDataUserPermissions for {:?}", name);
 
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&(name, tag, &user));
+        self.send("ListMDataUserPermissions", bytes, move |routing, msg_id| {
             let dst = Authority::NaeManager(name);
             routing.list_mdata_user_permissions(dst, name, tag, user, msg_id)
         }).and_then(|event| {
@@ -767,18 +1439,24 @@ impl<T: 'static> Client<T> {
         trace!("SetMDataUserPermissions for {:?}", name);
 
         let requester = fry!(self.public_signing_key());
-        self.send_mutation(move |routing, dst, msg_id| {
-            routing.set_mdata_user_permissions(
-                dst,
-                name,
-                tag,
-                user,
-                permissions,
-                version,
-                msg_id,
-                requester,
-            )
-        })
+        let bytes = serialised_size(&(name, tag, &user, &permissions, version));
+        self.send_mutation(
+            "SetMDataUserPermissions",
+            Some(name),
+            bytes,
+            move |routing, dst, msg_id| {
+                routing.set_mdata_user_permissions(
+                    dst,
+                    name,
+                    tag,
+                    user,
+                    permissions,
+                    version,
+                    msg_id,
+                    requester,
+                )
+            },
+        )
     }
 
     /// Deletes a permission set for a given user
@@ -792,9 +1470,15 @@ impl<T: 'static> Client<T> {
         trace!("DelMDataUserPermissions for {:?}", name);
 
         let requester = fry!(self.public_signing_key());
-        self.send_mutation(move |routing, dst, msg_id| {
-            routing.del_mdata_user_permissions(dst, name, tag, user, version, msg_id, requester)
-        })
+        let bytes = serialised_size(&(name, tag, &user, version));
+        self.send_mutation(
+            "DelMDataUserPermissions",
+            Some(name),
+            bytes,
+            move |routing, dst, msg_id| {
+                routing.del_mdata_user_permissions(dst, name, tag, user, version, msg_id, requester)
+            },
+        )
     }
 
     /// Sends an ownership transfer request
@@ -807,7 +1491,8 @@ impl<T: 'static> Client<T> {
     ) -> Box<CoreFuture<()>> {
         trace!("ChangeMDataOwner for {:?}", name);
 
-        self.send_mutation(move |routing, dst, msg_id| {
+        let bytes = serialised_size(&(name, tag, &new_owner, version));
+        self.send_mutation("ChangeMDataOwner", Some(name), bytes, move |routing, dst, msg_id| {
             routing.change_mdata_owner(dst, name, tag, btree_set![new_owner], version, msg_id)
         })
     }
@@ -817,7 +1502,8 @@ impl<T: 'static> Client<T> {
         trace!("ListAuthKeysAndVersion");
 
         let dst = fry!(self.cm_addr());
-        self.send(move |routing, msg_id| {
+        let bytes = serialised_size(&dst);
+        self.send("ListAuthKeysAndVersion", bytes, move |routing, msg_id| {
             routing.list_auth_keys_and_version(dst, msg_id)
         }).and_then(|event| {
                 match_event!(event, CoreEvent::ListAuthKeysAndVersion)
@@ -829,7 +1515,8 @@ impl<T: 'static> Client<T> {
     pub fn ins_auth_key(&self, key: sign::PublicKey, version: u64) -> Box<CoreFuture<()>> {
         trace!("InsAuthKey ({:?})", key);
 
-        self.send_mutation(move |routing, dst, msg_id| {
+        let bytes = serialised_size(&(&key, version));
+        self.send_mutation("InsAuthKey", None, bytes, move |routing, dst, msg_id| {
             routing.ins_auth_key(dst, key, version, msg_id)
         })
     }
@@ -838,7 +1525,8 @@ impl<T: 'static> Client<T> {
     pub fn del_auth_key(&self, key: sign::PublicKey, version: u64) -> Box<CoreFuture<()>> {
         trace!("DelAuthKey ({:?})", key);
 
-        self.send_mutation(move |routing, dst, msg_id| {
+        let bytes = serialised_size(&(&key, version));
+        self.send_mutation("DelAuthKey", None, bytes, move |routing, dst, msg_id| {
             routing.del_auth_key(dst, key, version, msg_id)
         })
     }
@@ -877,6 +1565,23 @@ impl<T: 'static> Client<T> {
         }
     }
 
+    /// Sets the key-derivation-function the account packet is encrypted with.
+    /// Doesn't actually modify the session packet - you should call
+    /// `update_account_packet` afterwards to actually update it on the
+    /// network.
+    pub fn set_kdf(&self, kdf: Kdf) -> Result<(), CoreError> {
+        trace!("Setting account KDF to {:?}.", kdf);
+
+        let mut inner = self.inner_mut();
+        inner.client_type.user_cred_mut()?.kdf = kdf;
+        Ok(())
+    }
+
+    /// Returns the key-derivation-function the account packet is encrypted with.
+    pub fn kdf(&self) -> Result<Kdf, CoreError> {
+        self.inner().client_type.user_cred().map(|keys| keys.kdf)
+    }
+
     /// Get User's Access Container if available in account packet used for
     /// current login
     pub fn access_container(&self) -> Result<MDataInfo, CoreError> {
@@ -919,7 +1624,7 @@ impl<T: 'static> Client<T> {
 
     /// Returns the Secret encryption key
     pub fn secret_encryption_key(&self) -> Result<shared_box::SecretKey, CoreError> {
-        self.inner().client_type.secret_encryption_key()
+        Ok(self.inner().key_store.load()?.enc_sk)
     }
 
     /// Returns the public and secret encryption keys.
@@ -928,7 +1633,7 @@ impl<T: 'static> Client<T> {
     ) -> Result<(box_::PublicKey, shared_box::SecretKey), CoreError> {
         let inner = self.inner();
         let pk = inner.client_type.public_encryption_key()?;
-        let sk = inner.client_type.secret_encryption_key()?;
+        let sk = inner.key_store.load()?.enc_sk;
         Ok((pk, sk))
     }
 
@@ -939,112 +1644,420 @@ impl<T: 'static> Client<T> {
 
     /// Returns the Secret Signing key
     pub fn secret_signing_key(&self) -> Result<shared_sign::SecretKey, CoreError> {
-        self.inner().client_type.secret_signing_key()
+        Ok(self.inner().key_store.load()?.sign_sk)
     }
 
     /// Returns the Symmetric Encryption key
     pub fn secret_symmetric_key(&self) -> Result<shared_secretbox::Key, CoreError> {
-        self.inner().client_type.secret_symmetric_key()
+        Ok(self.inner().key_store.load()?.enc_key)
     }
 
     /// Returns the public and secret signing keys.
     pub fn signing_keypair(&self) -> Result<(sign::PublicKey, shared_sign::SecretKey), CoreError> {
         let inner = self.inner();
         let pk = inner.client_type.public_signing_key()?;
-        let sk = inner.client_type.secret_signing_key()?;
+        let sk = inner.key_store.load()?.sign_sk;
         Ok((pk, sk))
     }
 
+    /// Locks the secret keys and credentials of the client into RAM, so they never get
+    /// swapped out to disk, which suits long-running processes. Keys held outside of the
+    /// process by the key store are left to it. Returns `false` if the platform denied
+    /// locking some of them; the client works the same either way.
+    pub fn lock_keys_in_memory(&self) -> bool {
+        let inner = &mut *self.inner_mut();
+        let locks = match inner.client_type {
+            ClientType::Registered { ref user_cred, .. } => {
+                vec![
+                    MemoryLock::new(&*user_cred.password),
+                    MemoryLock::new(&*user_cred.pin),
+                ]
+            }
+            ClientType::FromKeys { .. } |
+            ClientType::Unregistered { .. } => Vec::new(),
+        };
+
+        let all_locked = inner.key_store.lock_in_memory() && locks.iter().all(Option::is_some);
+        inner.key_locks = locks.into_iter().filter_map(|lock| lock).collect();
+        all_locked
+    }
+
+    /// Moves the secret keys of the client into `store`, e.g. one backed by the platform's
+    /// hardware key storage, from which they're loaded each time they're needed from now on.
+    /// Fails with `OperationForbidden` for unregistered clients, which have no keys.
+    pub fn set_key_store(&self, mut store: Box<KeyStore>) -> Result<(), CoreError> {
+        let keys = self.inner().key_store.load()?;
+        store.store(&keys)?;
+        self.inner_mut().key_store = store;
+        Ok(())
+    }
+
+    /// Sets the function to call each time one of the secret keys of the client is used to
+    /// sign or decrypt, with the operation and the name of the data involved, or `None` if it
+    /// wasn't network data. Mutation requests are signed with the client's signing key, so
+    /// each of them is reported too. Replaces the hook set before, if any.
+    pub fn set_key_usage_hook<F>(&self, hook: F)
+    where
+        F: Fn(KeyOp, Option<XorName>) + 'static,
+    {
+        self.inner_mut().key_usage_hook = Some(Rc::new(hook));
+    }
+
+    /// Removes the hook set with `set_key_usage_hook`.
+    pub fn remove_key_usage_hook(&self) {
+        self.inner_mut().key_usage_hook = None;
+    }
+
+    /// Reports a use of the secret keys of the client to the key usage hook, if there's one.
+    /// Called by the client itself for signing mutations and by the code decrypting data for
+    /// the client with its keys.
+    pub fn record_key_usage(&self, op: KeyOp, data_id: Option<XorName>) {
+        // Released before calling the hook, so it can use the client.
+        let hook = self.inner().key_usage_hook.clone();
+        if let Some(hook) = hook {
+            hook(op, data_id);
+        }
+    }
+
     /// Return the owner signing key
     pub fn owner_key(&self) -> Result<sign::PublicKey, CoreError> {
         self.inner().client_type.owner_key()
     }
 
+    /// Returns handle of the event loop the client runs in.
+    pub fn el_handle(&self) -> Handle {
+        self.inner().el_handle.clone()
+    }
+
     /// Returns the `crust::Config` associated with the `crust::Service` (if any).
     pub fn bootstrap_config() -> Result<BootstrapConfig, CoreError> {
         Ok(Routing::bootstrap_config()?)
     }
 
-    fn prepare_account_packet_update(
-        account: &Account,
-        keys: &UserCred,
-        entry_version: u64,
-    ) -> Result<BTreeMap<Vec<u8>, EntryAction>, CoreError> {
-        let encrypted_account = account.encrypt(&keys.password, &keys.pin)?;
-        let content = serialise(&AccountPacket::AccPkt(encrypted_account))?;
-        Ok(btree_map![
-            ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Update(Value {
-                content,
-                entry_version,
-            })
-        ])
-    }
-
-    /// Updates user's account packet
+    /// Updates user's account packet, both the primary copy and the backup. The backup is
+    /// created if it doesn't exist yet. Only a failure to update the primary copy fails the
+    /// update; an outdated backup gets repaired on the next login.
     pub fn update_account_packet(&self) -> Box<CoreFuture<()>> {
         trace!("Updating account packet.");
 
-        let entry_version = {
-            let mut inner = self.inner_mut();
-            inner.session_packet_version += 1;
-            inner.session_packet_version
-        };
+        let content = fry!(self.account_packet_content());
+        let backup = self.update_backup_account_packet(content.clone())
+            .or_else(|error| {
+                warn!("Could not update the backup account packet: {:?}", error);
+                Ok::<_, CoreError>(())
+            });
 
-        let update = {
-            let inner = self.inner();
-            let account = fry!(inner.client_type.acc());
-            let keys = fry!(inner.client_type.user_cred());
+        self.update_primary_account_packet(content)
+            .join(backup)
+            .map(|_| ())
+            .into_box()
+    }
 
-            fry!(Self::prepare_account_packet_update(
-                account,
-                keys,
-                entry_version,
-            ))
-        };
+    /// Rewrites the copies of user's account packet which are missing or damaged. A primary
+    /// copy which can be decrypted with the user's credentials is never overwritten, as it
+    /// might be newer than the account of this client; the backup is synced from it instead.
+    /// Login runs this in the background when it finds a damaged copy.
+    pub fn repair_account_packet(&self) -> Box<CoreFuture<()>> {
+        trace!("Repairing account packet.");
+
+        let content = fry!(self.account_packet_content());
+        let acc_loc = fry!(self.inner().client_type.acc_loc());
+        let backup_loc = Account::generate_backup_network_id(&acc_loc);
+        let client = self.clone();
+
+        let primary =
+            self.get_mdata_value(acc_loc, TYPE_TAG_SESSION_PACKET, ACC_LOGIN_ENTRY_KEY.to_owned());
+        let backup = self.get_mdata_value(
+            backup_loc,
+            SESSION_PACKET_BACKUP_TAG,
+            ACC_LOGIN_ENTRY_KEY.to_owned(),
+        ).map(Some)
+            .or_else(|error| match error {
+                CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(None),
+                error => Err(error),
+            });
 
-        let data_name = fry!(self.inner().client_type.acc_loc());
+        primary
+            .join(backup)
+            .and_then(move |(primary, backup)| {
+                // The account has been deleted in the meantime.
+                if primary.content.is_empty() {
+                    return err!(CoreError::RoutingClientError(ClientError::NoSuchAccount));
+                }
+
+                {
+                    let mut inner = client.inner_mut();
+                    inner.session_packet_version = primary.entry_version;
+                    inner.backup_packet = match backup {
+                        Some(ref value) => BackupPacket::Version(value.entry_version),
+                        None => BackupPacket::Missing,
+                    };
+                }
+
+                let intact = {
+                    let inner = client.inner();
+                    let user_cred = fry!(inner.client_type.user_cred());
+                    decrypt_account_packet(&primary.content, user_cred).is_ok()
+                };
+                let (repair_primary, content) = if intact {
+                    (ok!(()), primary.content)
+                } else {
+                    (client.update_primary_account_packet(content.clone()), content)
+                };
+                let expected = account_packet_cipher_text(&content);
+                let repair_backup = match backup {
+                    Some(ref value) if account_packet_cipher_text(&value.content) == expected => {
+                        ok!(())
+                    }
+                    _ => client.update_backup_account_packet(content),
+                };
 
-        self.mutate_mdata_entries(data_name, TYPE_TAG_SESSION_PACKET, update)
+                repair_primary.join(repair_backup).map(|_| ()).into_box()
+            })
+            .into_box()
     }
 
-    /// Sends a request and returns a future that resolves to the response.
-    fn send<F>(&self, req: F) -> Box<CoreFuture<CoreEvent>>
-    where
-        F: Fn(&mut Routing, MessageId) -> Result<(), InterfaceError> + 'static,
-    {
-        let inner = Rc::downgrade(&self.inner);
-        let func = move |_| if let Some(inner) = inner.upgrade() {
-            let msg_id = MessageId::new();
-            if let Err(error) = req(&mut inner.borrow_mut().routing, msg_id) {
-                return future::err(CoreError::from(error)).into_box();
-            }
+    /// Deletes user's account packet, after which the account can no longer be logged into.
+    /// The session packet itself can't be removed from the network, so its address stays
+    /// taken. The backup copy is deleted as well.
+    pub fn delete_account_packet(&self) -> Box<CoreFuture<()>> {
+        trace!("Deleting account packet.");
 
-            let (hook, rx) = oneshot::channel();
-            let _ = inner.borrow_mut().hooks.insert(msg_id, hook);
+        let entry_version = self.inner().session_packet_version + 1;
+        let data_name = fry!(self.inner().client_type.acc_loc());
+        let client = self.clone();
+        let client2 = self.clone();
 
-            let rx = rx.map_err(|_| CoreError::OperationAborted);
-            let rx = setup_timeout_and_retry_delay(&inner, msg_id, rx);
-            let rx = rx.map(|event| if let CoreEvent::RateLimitExceeded = event {
-                Loop::Continue(())
-            } else {
-                Loop::Break(event)
+        let actions = btree_map![
+            ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Del(entry_version)
+        ];
+        let delete_primary = self.mutate_mdata_entries(data_name, TYPE_TAG_SESSION_PACKET, actions)
+            .map(move |()| {
+                client.inner_mut().session_packet_version = entry_version;
             });
-            rx.into_box()
-        } else {
-            future::err(CoreError::OperationAborted).into_box()
-        };
 
-        future::loop_fn((), func).into_box()
+        let delete_backup = self.backup_packet_version().and_then(move |version| match version {
+            Some(version) => {
+                let actions = btree_map![
+                    ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Del(version + 1)
+                ];
+                client2
+                    .mutate_mdata_entries(
+                        Account::generate_backup_network_id(&data_name),
+                        SESSION_PACKET_BACKUP_TAG,
+                        actions,
+                    )
+                    .map(move |()| {
+                        client2.inner_mut().backup_packet = BackupPacket::Version(version + 1);
+                    })
+                    .into_box()
+            }
+            None => ok!(()),
+        });
+
+        delete_primary.join(delete_backup).map(|_| ()).into_box()
     }
 
-    /// Sends a mutation request.
-    fn send_mutation<F>(&self, req: F) -> Box<CoreFuture<()>>
-    where
-        F: Fn(&mut Routing, Authority<XorName>, MessageId) -> Result<(), InterfaceError> + 'static,
-    {
-        let dst = fry!(self.cm_addr());
+    /// Fetches a value of user's session packet.
+    pub fn get_session_packet_value(&self, key: Vec<u8>) -> Box<CoreFuture<Value>> {
+        let data_name = fry!(self.inner().client_type.acc_loc());
+        self.get_mdata_value(data_name, TYPE_TAG_SESSION_PACKET, key)
+    }
+
+    /// Mutates entries of user's session packet. The account packet is managed by
+    /// `update_account_packet` and can't be mutated this way.
+    pub fn mutate_session_packet_entries(
+        &self,
+        actions: BTreeMap<Vec<u8>, EntryAction>,
+    ) -> Box<CoreFuture<()>> {
+        if actions.contains_key(ACC_LOGIN_ENTRY_KEY) {
+            return err!(CoreError::OperationForbidden);
+        }
+
+        let data_name = fry!(self.inner().client_type.acc_loc());
+        self.mutate_mdata_entries(data_name, TYPE_TAG_SESSION_PACKET, actions)
+    }
+
+    /// Checks that the given credentials are those of the logged in account, e.g. to have
+    /// the user confirm an irreversible operation.
+    pub fn verify_credentials(
+        &self,
+        acc_locator: &str,
+        acc_password: &str,
+    ) -> Result<bool, CoreError> {
+        let (password, keyword, pin) =
+            utils::derive_secrets(acc_locator.as_bytes(), acc_password.as_bytes());
+        let acc_loc = Account::generate_network_id(&keyword, &pin)?;
+
+        let inner = self.inner();
+        let user_cred = inner.client_type.user_cred()?;
+
+        Ok(
+            acc_loc == inner.client_type.acc_loc()? && password[..] == user_cred.password[..] &&
+                pin[..] == user_cred.pin[..],
+        )
+    }
+
+    // Serialised account packet holding the account of this client.
+    fn account_packet_content(&self) -> Result<Vec<u8>, CoreError> {
+        let inner = self.inner();
+        let account = inner.client_type.acc()?;
+        let keys = inner.client_type.user_cred()?;
+
+        // The client keeps the account without its secret keys, which are in the key store.
+        let account = Account {
+            maid_keys: inner.key_store.load()?,
+            access_container: account.access_container.clone(),
+            config_root: account.config_root.clone(),
+            root_dirs_created: account.root_dirs_created,
+        };
+        let encrypted_account = account.encrypt_with_kdf(&keys.password, &keys.pin, keys.kdf)?;
+        Ok(serialise(&AccountPacket::AccPkt(encrypted_account))?)
+    }
+
+    fn update_primary_account_packet(&self, content: Vec<u8>) -> Box<CoreFuture<()>> {
+        let entry_version = self.inner().session_packet_version + 1;
+        let data_name = fry!(self.inner().client_type.acc_loc());
+        let client = self.clone();
 
-        self.send(move |routing, msg_id| req(routing, dst, msg_id))
+        let actions = btree_map![
+            ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Update(Value {
+                content,
+                entry_version,
+            })
+        ];
+
+        self.mutate_mdata_entries(data_name, TYPE_TAG_SESSION_PACKET, actions)
+            .map(move |()| {
+                client.inner_mut().session_packet_version = entry_version;
+            })
+            .into_box()
+    }
+
+    fn update_backup_account_packet(&self, content: Vec<u8>) -> Box<CoreFuture<()>> {
+        let acc_loc = fry!(self.inner().client_type.acc_loc());
+        let data_name = Account::generate_backup_network_id(&acc_loc);
+        let owner = fry!(self.owner_key());
+        let client = self.clone();
+        let client2 = self.clone();
+        let client3 = self.clone();
+
+        self.backup_packet_version()
+            .and_then(move |version| match version {
+                Some(version) => {
+                    let entry_version = version + 1;
+                    let actions = btree_map![
+                        ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Update(Value {
+                            content,
+                            entry_version,
+                        })
+                    ];
+
+                    client
+                        .mutate_mdata_entries(data_name, SESSION_PACKET_BACKUP_TAG, actions)
+                        .map(move |()| entry_version)
+                        .into_box()
+                }
+                None => {
+                    let data = fry!(backup_account_packet(data_name, content, owner));
+                    client.put_mdata(data).map(|()| 0).into_box()
+                }
+            })
+            .map(move |entry_version| {
+                client2.inner_mut().backup_packet = BackupPacket::Version(entry_version);
+            })
+            .map_err(move |error| {
+                // The version is refetched with the next update.
+                client3.inner_mut().backup_packet = BackupPacket::Unknown;
+                error
+            })
+            .into_box()
+    }
+
+    // Entry version of the backup copy of the account packet, `None` if there's no backup.
+    // Fetched from the network if the client doesn't know it.
+    fn backup_packet_version(&self) -> Box<CoreFuture<Option<u64>>> {
+        let backup_packet = self.inner().backup_packet;
+
+        match backup_packet {
+            BackupPacket::Missing => ok!(None),
+            BackupPacket::Version(version) => ok!(Some(version)),
+            BackupPacket::Unknown => {
+                let acc_loc = fry!(self.inner().client_type.acc_loc());
+                self.get_mdata_value(
+                    Account::generate_backup_network_id(&acc_loc),
+                    SESSION_PACKET_BACKUP_TAG,
+                    ACC_LOGIN_ENTRY_KEY.to_owned(),
+                ).map(|value| Some(value.entry_version))
+                    .or_else(|error| match error {
+                        CoreError::RoutingClientError(ClientError::NoSuchData) => Ok(None),
+                        error => Err(error),
+                    })
+                    .into_box()
+            }
+        }
+    }
+
+    /// Sends a request and returns a future that resolves to the response.
+    /// `kind` and `bytes` (the serialised size of the request arguments) go into the
+    /// network statistics.
+    fn send<F>(&self, kind: &'static str, bytes: u64, req: F) -> Box<CoreFuture<CoreEvent>>
+    where
+        F: Fn(&mut Routing, MessageId) -> Result<(), InterfaceError> + 'static,
+    {
+        let inner = Rc::downgrade(&self.inner);
+        let func = move |_| if let Some(inner) = inner.upgrade() {
+            let msg_id = MessageId::new();
+            if let Some(ref mut routing) = inner.borrow_mut().routing {
+                if let Err(error) = req(routing, msg_id) {
+                    return future::err(CoreError::from(error)).into_box();
+                }
+            } else {
+                // The client is suspended.
+                return future::err(CoreError::OperationAborted).into_box();
+            }
+            inner.borrow_mut().net_stats.request_sent(msg_id, kind, bytes);
+
+            let (hook, rx) = oneshot::channel();
+            let _ = inner.borrow_mut().hooks.insert(msg_id, hook);
+
+            let rx = rx.map_err(|_| CoreError::OperationAborted);
+            let rx = setup_timeout_and_retry_delay(&inner, msg_id, rx);
+            let rx = rx.map(|event| if let CoreEvent::RateLimitExceeded = event {
+                Loop::Continue(())
+            } else {
+                Loop::Break(event)
+            });
+            rx.into_box()
+        } else {
+            future::err(CoreError::OperationAborted).into_box()
+        };
+
+        acquire_pipeline_slot(&self.inner)
+            .and_then(move |slot| {
+                future::loop_fn((), func).then(move |result| {
+                    drop(slot);
+                    result
+                })
+            })
+            .into_box()
+    }
+
+    /// Sends a mutation request concerning the data named `data_id`, if any.
+    fn send_mutation<F>(
+        &self,
+        kind: &'static str,
+        data_id: Option<XorName>,
+        bytes: u64,
+        req: F,
+    ) -> Box<CoreFuture<()>>
+    where
+        F: Fn(&mut Routing, Authority<XorName>, MessageId) -> Result<(), InterfaceError> + 'static,
+    {
+        let dst = fry!(self.cm_addr());
+        self.record_key_usage(KeyOp::Sign, data_id);
+
+        self.send(kind, bytes, move |routing, msg_id| req(routing, dst, msg_id))
             .and_then(|event| match_event!(event, CoreEvent::Mutation))
             .into_box()
     }
@@ -1062,6 +2075,33 @@ impl<T: 'static> Client<T> {
     }
 }
 
+#[cfg(any(test, feature = "testing"))]
+impl<T: 'static> Client<T> {
+    /// Creates an account like `registered`, but with the MAID keys derived from
+    /// `keys_seed`, so tests can recreate the same account deterministically.
+    pub fn registered_with_keys_seed(
+        acc_locator: &str,
+        acc_password: &str,
+        invitation: &str,
+        keys_seed: &[u8],
+        el_handle: Handle,
+        core_tx: CoreMsgTx<T>,
+        net_tx: NetworkTx,
+    ) -> Result<Client<T>, CoreError> {
+        let id_seed = Seed(sha3_256(keys_seed));
+
+        Self::registered_impl(
+            acc_locator.as_bytes(),
+            acc_password.as_bytes(),
+            invitation,
+            el_handle,
+            core_tx,
+            net_tx,
+            Some(&id_seed),
+            |routing| routing,
+        )
+    }
+}
 
 #[cfg(any(all(test, feature = "use-mock-routing"),
             all(feature = "testing", feature = "use-mock-routing")))]
@@ -1117,21 +2157,39 @@ impl<T: 'static> Client<T> {
 
     #[doc(hidden)]
     pub fn set_network_limits(&self, max_ops_count: Option<u64>) {
-        self.inner.borrow_mut().routing.set_network_limits(
-            max_ops_count,
-        );
+        if let Some(ref mut routing) = self.inner.borrow_mut().routing {
+            routing.set_network_limits(max_ops_count);
+        }
     }
 
     #[doc(hidden)]
     pub fn simulate_network_disconnect(&self) {
-        self.inner.borrow_mut().routing.simulate_disconnect();
+        if let Some(ref mut routing) = self.inner.borrow_mut().routing {
+            routing.simulate_disconnect();
+        }
     }
 
     #[doc(hidden)]
     pub fn set_simulate_timeout(&self, enabled: bool) {
-        self.inner.borrow_mut().routing.set_simulate_timeout(
-            enabled,
-        );
+        if let Some(ref mut routing) = self.inner.borrow_mut().routing {
+            routing.set_simulate_timeout(enabled);
+        }
+    }
+}
+
+#[cfg(feature = "mock-vault")]
+impl<T: 'static> Client<T> {
+    /// Lists names of the `MutableData` with the given type tag owned by the account, so that
+    /// admin and debugging tools can find e.g. all app containers without looking them up
+    /// elsewhere. Only the mock vault can enumerate data; the network can't.
+    pub fn list_owned_mdata(&self, tag: u64) -> Box<CoreFuture<Vec<XorName>>> {
+        let owner = fry!(self.owner_key());
+        let res = match self.inner().routing {
+            Some(ref routing) => routing.list_owned_mdata(&owner, tag).map_err(CoreError::from),
+            // The client is suspended.
+            None => Err(CoreError::OperationAborted),
+        };
+        future::result(res).into_box()
     }
 }
 
@@ -1141,6 +2199,53 @@ impl<T> fmt::Debug for Client<T> {
     }
 }
 
+// Permission to have a request in flight, handed over to the next queued request when
+// dropped.
+struct PipelineSlot<T> {
+    inner: Weak<RefCell<Inner<T>>>,
+}
+
+impl<T> Drop for PipelineSlot<T> {
+    fn drop(&mut self) {
+        let inner = match self.inner.upgrade() {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut inner = inner.borrow_mut();
+
+        while let Some(tx) = inner.pipeline_queue.pop_front() {
+            let slot = PipelineSlot { inner: self.inner.clone() };
+            match tx.send(slot) {
+                Ok(()) => return,
+                // The queued request has been cancelled. Detach the returned slot so
+                // dropping it doesn't release it a second time.
+                Err(mut slot) => slot.inner = Weak::new(),
+            }
+        }
+
+        inner.in_flight -= 1;
+    }
+}
+
+fn acquire_pipeline_slot<T: 'static>(
+    inner: &Rc<RefCell<Inner<T>>>,
+) -> Box<CoreFuture<PipelineSlot<T>>> {
+    let mut inner_mut = inner.borrow_mut();
+
+    if inner_mut.max_pipeline_depth == 0 || inner_mut.in_flight < inner_mut.max_pipeline_depth {
+        inner_mut.in_flight += 1;
+        return future::ok(PipelineSlot { inner: Rc::downgrade(inner) }).into_box();
+    }
+
+    trace!(
+        "Pipeline depth of {} reached; queueing the request.",
+        inner_mut.max_pipeline_depth
+    );
+    let (tx, rx) = unsync_oneshot::channel();
+    inner_mut.pipeline_queue.push_back(tx);
+    rx.map_err(|_| CoreError::OperationAborted).into_box()
+}
+
 fn setup_timeout_and_retry_delay<T, F>(
     inner: &Rc<RefCell<Inner<T>>>,
     msg_id: MessageId,
@@ -1155,7 +2260,7 @@ where
     let future = future.and_then(move |event| {
         if let CoreEvent::RateLimitExceeded = event {
             if let Some(inner) = inner_weak.upgrade() {
-                let delay = Duration::from_millis(RETRY_DELAY_MS);
+                let delay = inner.borrow().settings.retry_delay();
                 let fut = timeout(delay, &inner.borrow().el_handle).or_else(move |_| Ok(event));
                 return Either::A(fut);
             }
@@ -1169,7 +2274,9 @@ where
     let inner_weak = Rc::downgrade(inner);
     let timeout = timeout(duration, &inner.borrow().el_handle).then(move |result| {
         if let Some(inner) = inner_weak.upgrade() {
-            let _ = inner.borrow_mut().hooks.remove(&msg_id);
+            let mut inner = inner.borrow_mut();
+            let _ = inner.hooks.remove(&msg_id);
+            inner.net_stats.timed_out(&msg_id);
         }
 
         result
@@ -1184,6 +2291,31 @@ where
         .into_box()
 }
 
+// Records the uploads in the ledger. A failure to record them is only logged, as the
+// uploads themselves have succeeded.
+fn record_uploads<T: 'static>(
+    client: &Client<T>,
+    ledger: &MDataInfo,
+    uploads: BTreeMap<XorName, Upload>,
+) -> Box<CoreFuture<()>> {
+    upload_ledger::record(client, ledger, uploads)
+        .or_else(|err| {
+            warn!("Failed to record uploads: {:?}", err);
+            Ok(())
+        })
+        .into_box()
+}
+
+// Returns `true` unless `cached` holds an entry of a newer version than `fetched`. Entries are
+// never removed from a `MutableData`, so `fetched` has to hold all of those of `cached`.
+fn is_not_older(cached: &BTreeMap<Vec<u8>, Value>, fetched: &BTreeMap<Vec<u8>, Value>) -> bool {
+    cached.iter().all(|(key, value)| {
+        fetched.get(key).map_or(false, |fetched| {
+            fetched.entry_version >= value.entry_version
+        })
+    })
+}
+
 // Create a future that resolves into `CoreError::RequestTimeout` after the given time interval.
 fn timeout(duration: Duration, handle: &Handle) -> TimeoutFuture {
     let timeout = match Timeout::new(duration, handle) {
@@ -1221,20 +2353,34 @@ type TimeoutFuture = Either<
 // Helper Struct
 // ------------------------------------------------------------
 
+// What the client knows about the backup copy of the account packet.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BackupPacket {
+    // There's no backup.
+    Missing,
+    // The backup exists and its entry has the given version.
+    Version(u64),
+    // Storing or fetching the backup failed, so it's not known whether it exists.
+    Unknown,
+}
+
 struct UserCred {
-    pin: Vec<u8>,
-    password: Vec<u8>,
+    pin: SecretBytes,
+    password: SecretBytes,
+    kdf: Kdf,
 }
 
 impl UserCred {
     fn new(password: Vec<u8>, pin: Vec<u8>) -> UserCred {
         UserCred {
-            pin: pin,
-            password: password,
+            pin: SecretBytes::from(pin),
+            password: SecretBytes::from(password),
+            kdf: Kdf::Scrypt,
         }
     }
 }
 
+// The keys kept here have their secret parts zeroed; the secret keys are in the key store.
 #[cfg_attr(feature = "cargo-clippy", allow(large_enum_variant))]
 enum ClientType {
     Unregistered { config: Option<BootstrapConfig> },
@@ -1253,12 +2399,12 @@ enum ClientType {
 }
 
 impl ClientType {
-    fn from_keys(keys: ClientKeys, owner_key: sign::PublicKey, config: BootstrapConfig) -> Self {
+    fn from_keys(keys: &ClientKeys, owner_key: sign::PublicKey, config: BootstrapConfig) -> Self {
         let digest = sha3_256(&owner_key.0);
         let cm_addr = Authority::ClientManager(XorName(digest));
 
         ClientType::FromKeys {
-            keys,
+            keys: public_keys_only(keys),
             owner_key,
             cm_addr,
             config,
@@ -1266,11 +2412,12 @@ impl ClientType {
     }
 
     fn reg(
-        acc: Account,
+        mut acc: Account,
         acc_loc: XorName,
         user_cred: UserCred,
         cm_addr: Authority<XorName>,
     ) -> Self {
+        acc.maid_keys = public_keys_only(&acc.maid_keys);
         ClientType::Registered {
             acc,
             acc_loc,
@@ -1323,6 +2470,14 @@ impl ClientType {
         }
     }
 
+    fn user_cred_mut(&mut self) -> Result<&mut UserCred, CoreError> {
+        match *self {
+            ClientType::Registered { ref mut user_cred, .. } => Ok(user_cred),
+            ClientType::FromKeys { .. } |
+            ClientType::Unregistered { .. } => Err(CoreError::OperationForbidden),
+        }
+    }
+
     fn cm_addr(&self) -> Result<&Authority<XorName>, CoreError> {
         match *self {
             ClientType::FromKeys { ref cm_addr, .. } |
@@ -1347,14 +2502,6 @@ impl ClientType {
         }
     }
 
-    fn secret_signing_key(&self) -> Result<shared_sign::SecretKey, CoreError> {
-        match *self {
-            ClientType::FromKeys { ref keys, .. } => Ok(keys.sign_sk.clone()),
-            ClientType::Registered { ref acc, .. } => Ok(acc.maid_keys.sign_sk.clone()),
-            ClientType::Unregistered { .. } => Err(CoreError::OperationForbidden),
-        }
-    }
-
     fn public_encryption_key(&self) -> Result<box_::PublicKey, CoreError> {
         match *self {
             ClientType::FromKeys { ref keys, .. } => Ok(keys.enc_pk),
@@ -1362,38 +2509,99 @@ impl ClientType {
             ClientType::Unregistered { .. } => Err(CoreError::OperationForbidden),
         }
     }
+}
 
-    fn secret_encryption_key(&self) -> Result<shared_box::SecretKey, CoreError> {
-        match *self {
-            ClientType::FromKeys { ref keys, .. } => Ok(keys.enc_sk.clone()),
-            ClientType::Registered { ref acc, .. } => Ok(acc.maid_keys.enc_sk.clone()),
-            ClientType::Unregistered { .. } => Err(CoreError::OperationForbidden),
+// Fetches the account packet stored in the session packet or its backup.
+fn get_account_packet(
+    routing: &mut Routing,
+    routing_rx: &Receiver<Event>,
+    name: XorName,
+    tag: u64,
+    timeout: Duration,
+) -> Result<Value, CoreError> {
+    let msg_id = MessageId::new();
+    routing
+        .get_mdata_value(
+            Authority::NaeManager(name),
+            name,
+            tag,
+            ACC_LOGIN_ENTRY_KEY.to_owned(),
+            msg_id,
+        )
+        .map_err(CoreError::from)
+        .and_then(|_| {
+            wait_for_response!(routing_rx, Response::GetMDataValue, msg_id, timeout)
+        })
+}
+
+fn decrypt_account_packet(
+    content: &[u8],
+    user_cred: &UserCred,
+) -> Result<(Account, Kdf, bool), CoreError> {
+    match deserialise::<AccountPacket>(content)? {
+        AccountPacket::AccPkt(acc_content) |
+        AccountPacket::WithInvitation { acc_pkt: acc_content, .. } => {
+            Account::decrypt_and_migrate(&acc_content, &user_cred.password, &user_cred.pin)
         }
     }
+}
 
-    fn secret_symmetric_key(&self) -> Result<shared_secretbox::Key, CoreError> {
-        match *self {
-            ClientType::FromKeys { ref keys, .. } => Ok(keys.enc_key.clone()),
-            ClientType::Registered { ref acc, .. } => Ok(acc.maid_keys.enc_key.clone()),
-            ClientType::Unregistered { .. } => Err(CoreError::OperationForbidden),
-        }
+// Encrypted account held by the account packet, `None` if it can't be deserialised.
+fn account_packet_cipher_text(content: &[u8]) -> Option<Vec<u8>> {
+    match deserialise::<AccountPacket>(content) {
+        Ok(AccountPacket::AccPkt(acc_content)) |
+        Ok(AccountPacket::WithInvitation { acc_pkt: acc_content, .. }) => Some(acc_content),
+        Err(_) => None,
     }
 }
 
+// `MutableData` holding the backup copy of the account packet.
+fn backup_account_packet(
+    name: XorName,
+    content: Vec<u8>,
+    owner: sign::PublicKey,
+) -> Result<MutableData, CoreError> {
+    let entries = btree_map![
+        ACC_LOGIN_ENTRY_KEY.to_owned() => Value {
+            content,
+            entry_version: 0,
+        }
+    ];
+
+    Ok(MutableData::new(
+        name,
+        SESSION_PACKET_BACKUP_TAG,
+        BTreeMap::new(),
+        entries,
+        btree_set![owner],
+    )?)
+}
+
 fn setup_routing(
     full_id: Option<FullId>,
     config: Option<BootstrapConfig>,
+    settings: &ClientConfig,
 ) -> Result<(Routing, Receiver<Event>), CoreError> {
     let (routing_tx, routing_rx) = mpsc::channel();
-    let routing = Routing::new(
+    // With the mock vault compiled in, `Routing` is the mock one, which forwards all
+    // requests to a real routing client when connected to the live network.
+    #[cfg(feature = "mock-vault")]
+    let new_routing = match network_mode() {
+        NetworkMode::Live => Routing::live,
+        NetworkMode::Mock => Routing::new,
+    };
+    #[cfg(not(feature = "mock-vault"))]
+    let new_routing = Routing::new;
+
+    let routing = new_routing(
         routing_tx,
         full_id,
         config,
-        Duration::from_secs(REQUEST_TIMEOUT_SECS),
+        settings.request_timeout(),
     )?;
 
     trace!("Waiting to get connected to the Network...");
-    match routing_rx.recv_timeout(Duration::from_secs(CONNECTION_TIMEOUT_SECS)) {
+    match routing_rx.recv_timeout(settings.connection_timeout()) {
         Ok(Event::Connected) => (),
         Ok(Event::Terminate) => {
             // TODO: Consider adding a separate error type for this
@@ -1437,7 +2645,8 @@ mod tests {
     use futures::sync::mpsc;
     #[cfg(feature = "use-mock-routing")]
     use rand;
-    use routing::{ClientError, ImmutableData};
+    use routing::{Action, ClientError, ImmutableData};
+    use std::cell::Cell;
     use tokio_core::reactor::Core;
     use utils;
     use utils::test_utils::{finish, random_client, setup_client};
@@ -1661,10 +2870,289 @@ mod tests {
                      });
     }
 
+    // Test switching the account packet to a different KDF.
+    #[test]
+    fn kdf_switch() {
+        let sec_0 = unwrap!(utils::generate_random_string(10));
+        let sec_1 = unwrap!(utils::generate_random_string(10));
+        let inv = unwrap!(utils::generate_random_string(10));
+
+        let kdf = Kdf::Argon2id {
+            mem_cost: 1024,
+            time_cost: 1,
+            lanes: 1,
+        };
+
+        setup_client(|el_h, core_tx, net_tx| {
+                         Client::registered(&sec_0, &sec_1, &inv, el_h, core_tx, net_tx)
+                     },
+                     move |client| {
+                         assert_eq!(unwrap!(client.kdf()), Kdf::Scrypt);
+                         unwrap!(client.set_kdf(kdf));
+                         client.update_account_packet()
+                     });
+
+        setup_client(|el_h, core_tx, net_tx| Client::login(&sec_0, &sec_1, el_h, core_tx, net_tx),
+                     move |client| {
+                         assert_eq!(unwrap!(client.kdf()), kdf);
+                         finish()
+                     });
+    }
+
+    // Test logging in with the backup account packet when the primary copy is corrupted.
+    #[test]
+    fn login_with_backup_packet() {
+        let sec_0 = unwrap!(utils::generate_random_string(10));
+        let sec_1 = unwrap!(utils::generate_random_string(10));
+        let inv = unwrap!(utils::generate_random_string(10));
+
+        let sign_pk = setup_client(
+            |el_h, core_tx, net_tx| Client::registered(&sec_0, &sec_1, &inv, el_h, core_tx, net_tx),
+            |client| {
+                let sign_pk = unwrap!(client.public_signing_key());
+                corrupt_account_packet(client, TYPE_TAG_SESSION_PACKET).map(move |_| sign_pk)
+            },
+        );
+
+        setup_client(
+            |el_h, core_tx, net_tx| Client::login(&sec_0, &sec_1, el_h, core_tx, net_tx),
+            move |client| {
+                assert_eq!(unwrap!(client.public_signing_key()), sign_pk);
+                finish()
+            },
+        );
+    }
+
+    // Test that repairing the account packet rewrites the damaged copy only.
+    #[test]
+    fn repair_account_packet() {
+        let sec_0 = unwrap!(utils::generate_random_string(10));
+        let sec_1 = unwrap!(utils::generate_random_string(10));
+        let inv = unwrap!(utils::generate_random_string(10));
+
+        setup_client(
+            |el_h, core_tx, net_tx| Client::registered(&sec_0, &sec_1, &inv, el_h, core_tx, net_tx),
+            |client| {
+                let client2 = client.clone();
+                let client3 = client.clone();
+                let acc_loc = unwrap!(client.inner().client_type.acc_loc());
+                let backup_loc = Account::generate_backup_network_id(&acc_loc);
+
+                corrupt_account_packet(client, SESSION_PACKET_BACKUP_TAG)
+                    .then(move |res| {
+                        unwrap!(res);
+                        client2.repair_account_packet()
+                    })
+                    .then(move |res| {
+                        unwrap!(res);
+                        let primary = client3.get_mdata_value(
+                            acc_loc,
+                            TYPE_TAG_SESSION_PACKET,
+                            ACC_LOGIN_ENTRY_KEY.to_owned(),
+                        );
+                        let backup = client3.get_mdata_value(
+                            backup_loc,
+                            SESSION_PACKET_BACKUP_TAG,
+                            ACC_LOGIN_ENTRY_KEY.to_owned(),
+                        );
+                        primary.join(backup)
+                    })
+                    .map(|(primary, backup)| {
+                        // The primary copy is intact, so it's left alone.
+                        assert_eq!(primary.entry_version, 0);
+                        assert_eq!(backup.entry_version, 2);
+                        assert!(account_packet_cipher_text(&backup.content).is_some());
+                        assert_eq!(
+                            account_packet_cipher_text(&backup.content),
+                            account_packet_cipher_text(&primary.content)
+                        );
+                    })
+            },
+        );
+
+        setup_client(
+            |el_h, core_tx, net_tx| Client::login(&sec_0, &sec_1, el_h, core_tx, net_tx),
+            |_| finish(),
+        );
+    }
+
+    // Test that repairing the account packet never overwrites a primary copy which can be
+    // decrypted, even if it holds a different account state than the client.
+    #[test]
+    fn repair_account_packet_newer_primary() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let acc_loc = unwrap!(client.inner().client_type.acc_loc());
+            let backup_loc = Account::generate_backup_network_id(&acc_loc);
+
+            // Another session switched the KDF of the account.
+            unwrap!(client.set_kdf(Kdf::Argon2id {
+                mem_cost: 1024,
+                time_cost: 1,
+                lanes: 1,
+            }));
+            let newer = unwrap!(client.account_packet_content());
+            unwrap!(client.set_kdf(Kdf::Scrypt));
+            let newer_cipher_text = account_packet_cipher_text(&newer);
+            let actions = btree_map![
+                ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Update(Value {
+                    content: newer,
+                    entry_version: 1,
+                })
+            ];
+
+            client
+                .mutate_mdata_entries(acc_loc, TYPE_TAG_SESSION_PACKET, actions)
+                .then(move |res| {
+                    unwrap!(res);
+                    client2.repair_account_packet()
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    let primary = client3.get_mdata_value(
+                        acc_loc,
+                        TYPE_TAG_SESSION_PACKET,
+                        ACC_LOGIN_ENTRY_KEY.to_owned(),
+                    );
+                    let backup = client3.get_mdata_value(
+                        backup_loc,
+                        SESSION_PACKET_BACKUP_TAG,
+                        ACC_LOGIN_ENTRY_KEY.to_owned(),
+                    );
+                    primary.join(backup)
+                })
+                .map(move |(primary, backup)| {
+                    assert_eq!(primary.entry_version, 1);
+                    assert_eq!(account_packet_cipher_text(&primary.content), newer_cipher_text);
+                    assert_eq!(backup.entry_version, 1);
+                    assert_eq!(account_packet_cipher_text(&backup.content), newer_cipher_text);
+                })
+        });
+    }
+
+    // Test that login doesn't fall back to the backup account packet when fetching the
+    // primary copy fails, as the backup might be outdated.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn login_primary_fetch_failure() {
+        use routing::{Request, Response};
+
+        let sec_0 = unwrap!(utils::generate_random_string(10));
+        let sec_1 = unwrap!(utils::generate_random_string(10));
+        let inv = unwrap!(utils::generate_random_string(10));
+
+        setup_client(
+            |el_h, core_tx, net_tx| Client::registered(&sec_0, &sec_1, &inv, el_h, core_tx, net_tx),
+            |_| finish(),
+        );
+
+        let routing_hook = |mut routing: Routing| -> Routing {
+            routing.set_request_hook(|req| match *req {
+                Request::GetMDataValue { tag, msg_id, .. } if tag == TYPE_TAG_SESSION_PACKET => {
+                    Some(Response::GetMDataValue {
+                        res: Err(ClientError::NetworkOther("Transient".to_owned())),
+                        msg_id,
+                    })
+                }
+                // Pass-through
+                _ => None,
+            });
+            routing
+        };
+
+        let el = unwrap!(Core::new());
+        let (core_tx, _): (CoreMsgTx<()>, _) = mpsc::unbounded();
+        let (net_tx, _) = mpsc::unbounded();
+
+        match Client::login_with_hook(&sec_0, &sec_1, el.handle(), core_tx, net_tx, routing_hook) {
+            Err(CoreError::RoutingClientError(ClientError::NetworkOther(_))) => (),
+            Err(error) => panic!("Unexpected error: {:?}", error),
+            Ok(_) => panic!("Login with an unavailable primary account packet succeeded"),
+        }
+    }
+
+    // Test that a failure to store the backup account packet doesn't fail the update.
+    // 1. Register an account, simulating failures of mutations of the backup packet.
+    // 2. Updating the account packet should succeed, leaving the backup version unknown.
+    // 3. Logging in should still be possible.
+    #[cfg(feature = "use-mock-routing")]
+    #[test]
+    fn update_account_packet_backup_failure() {
+        use routing::{Request, Response};
+
+        let sec_0 = unwrap!(utils::generate_random_string(10));
+        let sec_1 = unwrap!(utils::generate_random_string(10));
+        let inv = unwrap!(utils::generate_random_string(10));
+
+        setup_client(
+            |el_h, core_tx, net_tx| {
+                let routing_hook = |mut routing: Routing| -> Routing {
+                    routing.set_request_hook(|req| match *req {
+                        Request::MutateMDataEntries { tag, msg_id, .. }
+                            if tag == SESSION_PACKET_BACKUP_TAG => {
+                            Some(Response::MutateMDataEntries {
+                                res: Err(ClientError::AccessDenied),
+                                msg_id,
+                            })
+                        }
+                        // Pass-through
+                        _ => None,
+                    });
+                    routing
+                };
+
+                Client::registered_with_hook(
+                    &sec_0,
+                    &sec_1,
+                    &inv,
+                    el_h,
+                    core_tx,
+                    net_tx,
+                    routing_hook,
+                )
+            },
+            |client| {
+                let client2 = client.clone();
+
+                client.update_account_packet().map(move |()| {
+                    let inner = client2.inner();
+                    assert_eq!(inner.session_packet_version, 1);
+                    assert_eq!(inner.backup_packet, BackupPacket::Unknown);
+                })
+            },
+        );
+
+        setup_client(
+            |el_h, core_tx, net_tx| Client::login(&sec_0, &sec_1, el_h, core_tx, net_tx),
+            |_| finish(),
+        );
+    }
+
+    // Overwrites a copy of the account packet of the client with garbage.
+    fn corrupt_account_packet(client: &Client<()>, tag: u64) -> Box<CoreFuture<()>> {
+        let acc_loc = unwrap!(client.inner().client_type.acc_loc());
+        let name = if tag == TYPE_TAG_SESSION_PACKET {
+            acc_loc
+        } else {
+            Account::generate_backup_network_id(&acc_loc)
+        };
+        let actions = btree_map![
+            ACC_LOGIN_ENTRY_KEY.to_owned() => EntryAction::Update(Value {
+                content: vec![0; 10],
+                entry_version: 1,
+            })
+        ];
+
+        client.mutate_mdata_entries(name, tag, actions)
+    }
+
     // Test restarting routing after a network disconnect.
     #[cfg(feature = "use-mock-routing")]
     #[test]
     fn restart_routing() {
+        skip_unless_mock!();
+
         use event::NetworkEvent;
         use utils::test_utils::random_client_with_net_obs;
         use futures;
@@ -1702,6 +3190,8 @@ mod tests {
     fn timeout() {
         use std::time::Duration;
 
+        skip_unless_mock!();
+
         // Get
         random_client(|client| {
             let client2 = client.clone();
@@ -1731,4 +3221,451 @@ mod tests {
                 })
         })
     }
+
+    // Test listing the `MutableData` of the account by type tag.
+    #[cfg(feature = "mock-vault")]
+    #[test]
+    fn list_owned_mdata() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let owners = btree_set![unwrap!(client.owner_key())];
+
+            let mut names: Vec<XorName> = vec![::rand::random(), ::rand::random()];
+            let data0 = unwrap!(MutableData::new(
+                names[0],
+                DIR_TAG,
+                btree_map![],
+                btree_map![],
+                owners.clone(),
+            ));
+            let data1 = unwrap!(MutableData::new(
+                names[1],
+                DIR_TAG,
+                btree_map![],
+                btree_map![],
+                owners,
+            ));
+            names.sort();
+
+            client
+                .put_mdata(data0)
+                .and_then(move |()| client2.put_mdata(data1))
+                .and_then(move |()| client3.list_owned_mdata(DIR_TAG))
+                .map(move |listed| assert_eq!(listed, names))
+        })
+    }
+
+    // Test that `MutableData` is fetched only when its version has changed.
+    #[test]
+    fn get_mdata_if_newer() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+
+            let name: XorName = ::rand::random();
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                name,
+                DIR_TAG,
+                btree_map![],
+                btree_map![],
+                owners,
+            ));
+
+            client
+                .put_mdata(data)
+                .and_then(move |()| client2.get_mdata_if_newer(name, DIR_TAG, 0))
+                .and_then(move |fetched| {
+                    assert_eq!(fetched, Fetched::NotModified);
+                    client3.set_mdata_user_permissions(
+                        name,
+                        DIR_TAG,
+                        User::Anyone,
+                        PermissionSet::new().allow(Action::Insert),
+                        1,
+                    )
+                })
+                .and_then(move |()| client4.get_mdata_if_newer(name, DIR_TAG, 0))
+                .map(move |fetched| match fetched {
+                    Fetched::Modified { version, data } => {
+                        assert_eq!(version, 1);
+                        assert_eq!(*data.name(), name);
+                    }
+                    Fetched::NotModified => panic!("Unexpected NotModified"),
+                })
+        })
+    }
+
+    // Test that prefetched entries are served once and dropped on mutation.
+    #[test]
+    fn prefetch_mdata_entries() {
+        use futures::IntoFuture;
+        use std::time::Duration;
+
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let handle = client.el_handle();
+
+            let name: XorName = ::rand::random();
+            let entries = btree_map![
+                b"key".to_vec() => Value { content: b"value".to_vec(), entry_version: 0 }
+            ];
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                name,
+                DIR_TAG,
+                btree_map![],
+                entries.clone(),
+                owners,
+            ));
+
+            client.set_prefetch_limit(2);
+            client
+                .put_mdata(data)
+                .and_then(move |()| {
+                    client2.prefetch_mdata_entries(vec![(name, DIR_TAG)]);
+
+                    future::loop_fn((), move |()| {
+                        let client2 = client2.clone();
+                        Timeout::new(Duration::from_millis(10), &handle)
+                            .into_future()
+                            .flatten()
+                            .map_err(CoreError::from)
+                            .map(move |_| {
+                                if client2.inner().prefetched.is_empty() {
+                                    Loop::Continue(())
+                                } else {
+                                    Loop::Break(())
+                                }
+                            })
+                    })
+                })
+                .and_then(move |()| {
+                    let fetched = unwrap!(client3.take_prefetched(name, DIR_TAG));
+                    assert_eq!(fetched, entries);
+                    assert!(client3.take_prefetched(name, DIR_TAG).is_none());
+
+                    let _ = client3.inner_mut().prefetched.insert(
+                        (name, DIR_TAG),
+                        (Instant::now(), BTreeMap::new()),
+                    );
+                    client3
+                        .mutate_mdata_entries(name, DIR_TAG, BTreeMap::new())
+                        .then(move |_| Ok::<_, CoreError>(client3))
+                })
+                .map(move |client3| {
+                    assert!(client3.inner().prefetched.is_empty());
+                })
+        })
+    }
+
+    // Test that entries prefetched while the data is being mutated are dropped.
+    #[test]
+    fn prefetch_mutated_meanwhile() {
+        use futures::IntoFuture;
+        use routing::EntryActions;
+        use std::time::Duration;
+
+        random_client(|client| {
+            let client2 = client.clone();
+            let handle = client.el_handle();
+
+            let name: XorName = ::rand::random();
+            let entries = btree_map![
+                b"key".to_vec() => Value { content: b"value".to_vec(), entry_version: 0 }
+            ];
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                name,
+                DIR_TAG,
+                btree_map![],
+                entries,
+                owners,
+            ));
+
+            client.set_prefetch_limit(2);
+            client
+                .put_mdata(data)
+                .and_then(move |()| {
+                    client2.prefetch_mdata_entries(vec![(name, DIR_TAG)]);
+                    assert!(client2.inner().prefetching.contains_key(&(name, DIR_TAG)));
+
+                    let actions = EntryActions::new()
+                        .update(b"key".to_vec(), b"new value".to_vec(), 1)
+                        .into();
+                    client2
+                        .mutate_mdata_entries(name, DIR_TAG, actions)
+                        .map(move |()| client2)
+                })
+                .and_then(move |client2| {
+                    future::loop_fn(client2, move |client2| {
+                        Timeout::new(Duration::from_millis(10), &handle)
+                            .into_future()
+                            .flatten()
+                            .map_err(CoreError::from)
+                            .map(move |_| if client2.inner().prefetching.is_empty() {
+                                Loop::Break(client2)
+                            } else {
+                                Loop::Continue(client2)
+                            })
+                    })
+                })
+                .map(move |client2| {
+                    assert!(client2.inner().prefetched.is_empty());
+                })
+        })
+    }
+
+    // Test that `mutate_with_retry` re-applies the mutation on fresh entries after a conflict.
+    #[test]
+    fn mutate_with_retry() {
+        use routing::EntryActions;
+        use std::cell::Cell;
+
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+
+            let info = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let info2 = info.clone();
+            let info3 = info.clone();
+            let info4 = info.clone();
+
+            let entries = btree_map![
+                b"counter".to_vec() => Value { content: vec![0], entry_version: 0 }
+            ];
+            let entries = unwrap!(mdata_info::encrypt_entries(&info, &entries));
+            let stale = entries.clone();
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                info.name,
+                info.type_tag,
+                btree_map![],
+                entries,
+                owners,
+            ));
+
+            let calls = Rc::new(Cell::new(0));
+            let calls2 = calls.clone();
+
+            client
+                .put_mdata(data)
+                .and_then(move |()| {
+                    // Another writer bumps the counter...
+                    let actions = EntryActions::new()
+                        .update(b"counter".to_vec(), vec![1], 1)
+                        .into();
+                    let actions = unwrap!(mdata_info::encrypt_entry_actions(&info2, &actions));
+                    client2.mutate_mdata_entries(info2.name, info2.type_tag, actions)
+                })
+                .and_then(move |()| {
+                    // ...while this client still sees the old version.
+                    let _ = client3.inner_mut().prefetched.insert(
+                        (info3.name, info3.type_tag),
+                        (Instant::now(), stale),
+                    );
+
+                    client3.mutate_with_retry(&info3, move |entries| {
+                        calls2.set(calls2.get() + 1);
+
+                        let value = unwrap!(entries.get(&b"counter"[..]));
+                        EntryActions::new()
+                            .update(
+                                b"counter".to_vec(),
+                                vec![value.content[0] + 1],
+                                value.entry_version + 1,
+                            )
+                            .into()
+                    })
+                })
+                .and_then(move |()| {
+                    let key = unwrap!(info4.enc_entry_key(b"counter"));
+                    client4
+                        .get_mdata_value(info4.name, info4.type_tag, key)
+                        .map(move |value| (info4, value))
+                })
+                .map(move |(info, value)| {
+                    assert_eq!(calls.get(), 2);
+                    assert_eq!(value.entry_version, 2);
+                    assert_eq!(unwrap!(info.decrypt(&value.content)), vec![2]);
+                })
+        })
+    }
+
+    // Test that requests beyond the pipeline depth are queued and eventually sent.
+    #[test]
+    fn pipeline_depth() {
+        random_client(|client| {
+            let client2 = client.clone();
+            client.set_max_pipeline_depth(1);
+
+            let puts: Vec<_> = (0..3)
+                .map(|_| {
+                    let data = unwrap!(utils::generate_random_vector(10));
+                    client.put_idata(ImmutableData::new(data))
+                })
+                .collect();
+            assert_eq!(client.inner().in_flight, 1);
+            assert_eq!(client.inner().pipeline_queue.len(), 2);
+
+            future::join_all(puts).map(move |_| {
+                assert_eq!(client2.inner().in_flight, 0);
+                assert!(client2.inner().pipeline_queue.is_empty());
+            })
+        })
+    }
+
+    // Test that background jobs run by priority, one at a time, once no request is in flight.
+    #[test]
+    fn scheduled_jobs() {
+        random_client(|client| {
+            client.set_scheduler_limits(SchedulerLimits {
+                max_running: 1,
+                min_interval: Duration::from_secs(0),
+                max_in_flight: 1,
+            });
+
+            // Keeps the jobs from starting until it completes.
+            let data = ImmutableData::new(unwrap!(utils::generate_random_vector(10)));
+            let put = client.put_idata(data);
+
+            let order = Rc::new(RefCell::new(Vec::new()));
+            let jobs = vec![
+                (Priority::Low, 1),
+                (Priority::High, 2),
+                (Priority::Normal, 3),
+                (Priority::High, 4),
+            ];
+            let jobs: Vec<_> = jobs.into_iter()
+                .map(|(priority, id)| {
+                    let order = Rc::clone(&order);
+                    client.schedule(priority, move || {
+                        order.borrow_mut().push(id);
+                        Ok::<_, CoreError>(id)
+                    })
+                })
+                .collect();
+            assert!(order.borrow().is_empty());
+
+            put.join(future::join_all(jobs)).map(move |(_, ids)| {
+                assert_eq!(ids, vec![1, 2, 3, 4]);
+                assert_eq!(*order.borrow(), vec![2, 4, 3, 1]);
+            })
+        })
+    }
+
+    // Test that requests, responses and their failures show up in the network statistics.
+    #[test]
+    fn network_stats() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+
+            let data = ImmutableData::new(unwrap!(utils::generate_random_vector(100)));
+            client
+                .put_idata(data)
+                .then(move |res| {
+                    unwrap!(res);
+                    client2.get_mdata_version(::rand::random(), DIR_TAG)
+                })
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => (),
+                        x => panic!("Unexpected {:?}", x),
+                    }
+
+                    let stats = client3.network_stats();
+                    assert!(stats.bytes_sent > 100);
+                    assert!(stats.bytes_received > 0);
+                    assert_eq!(
+                        stats.requests["PutIData"],
+                        RequestCounts { sent: 1, failed: 0 }
+                    );
+                    assert_eq!(
+                        stats.requests["GetMDataVersion"],
+                        RequestCounts { sent: 1, failed: 1 }
+                    );
+                    assert!(stats.rtt.is_some());
+                    Ok::<_, CoreError>(())
+                })
+        })
+    }
+
+    // Test that locking the keys doesn't disturb the client.
+    #[test]
+    fn lock_keys_in_memory() {
+        random_client(|client| {
+            // Locking may be denied by the environment running the test.
+            let _ = client.lock_keys_in_memory();
+            let _ = unwrap!(client.secret_symmetric_key());
+
+            let data = ImmutableData::new(unwrap!(utils::generate_random_vector(10)));
+            client.put_idata(data)
+        })
+    }
+
+    // Test that the secret keys are moved into a custom key store and loaded from it.
+    #[test]
+    fn custom_key_store() {
+        struct CountingKeyStore {
+            keys: MemoryKeyStore,
+            loads: Rc<Cell<usize>>,
+        }
+
+        impl KeyStore for CountingKeyStore {
+            fn store(&mut self, keys: &ClientKeys) -> Result<(), CoreError> {
+                self.keys.store(keys)
+            }
+
+            fn load(&self) -> Result<ClientKeys, CoreError> {
+                self.loads.set(self.loads.get() + 1);
+                self.keys.load()
+            }
+        }
+
+        random_client(|client| {
+            let sign_sk = unwrap!(client.secret_signing_key());
+            let loads = Rc::new(Cell::new(0));
+            unwrap!(client.set_key_store(Box::new(CountingKeyStore {
+                keys: MemoryKeyStore::default(),
+                loads: Rc::clone(&loads),
+            })));
+
+            assert!(unwrap!(client.secret_signing_key()) == sign_sk);
+            assert_eq!(loads.get(), 1);
+
+            // Writing the account packet needs the secret keys too.
+            client.update_account_packet().map(
+                move |_| assert!(loads.get() > 1),
+            )
+        })
+    }
+
+    // Test that mutations are reported to the key usage hook as uses of the signing key.
+    #[test]
+    fn key_usage_hook() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let uses = Rc::new(RefCell::new(Vec::new()));
+            let uses2 = Rc::clone(&uses);
+            client.set_key_usage_hook(move |op, data_id| uses2.borrow_mut().push((op, data_id)));
+
+            let data = ImmutableData::new(unwrap!(utils::generate_random_vector(100)));
+            let name = *data.name();
+
+            client.put_idata(data).then(move |res| {
+                unwrap!(res);
+                assert_eq!(*uses.borrow(), vec![(KeyOp::Sign, Some(name))]);
+
+                c2.remove_key_usage_hook();
+                c2.record_key_usage(KeyOp::Decrypt, None);
+                assert_eq!(uses.borrow().len(), 1);
+                Ok::<_, CoreError>(())
+            })
+        })
+    }
 }
@@ -0,0 +1,156 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use routing::MessageId;
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+/// Number of requests of one kind.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct RequestCounts {
+    /// Requests sent, including retries.
+    pub sent: u64,
+    /// Requests which failed, timed out or were rejected by the rate limiter.
+    pub failed: u64,
+}
+
+/// Statistics of the traffic between a client and the network since the client was created.
+///
+/// Sizes are those of the serialised request arguments and responses, so they don't include
+/// the framing added by routing and only approximate the actual traffic.
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NetworkStats {
+    /// Bytes sent to the network.
+    pub bytes_sent: u64,
+    /// Bytes received from the network.
+    pub bytes_received: u64,
+    /// Request counts keyed by the kind of request, e.g. `"GetIData"`.
+    pub requests: BTreeMap<&'static str, RequestCounts>,
+    /// Smoothed round trip time, if any response has been received yet.
+    pub rtt: Option<Duration>,
+}
+
+impl NetworkStats {
+    /// Total number of requests sent.
+    pub fn requests_sent(&self) -> u64 {
+        self.requests.values().map(|counts| counts.sent).sum()
+    }
+
+    /// Total number of requests which failed.
+    pub fn requests_failed(&self) -> u64 {
+        self.requests.values().map(|counts| counts.failed).sum()
+    }
+
+    /// Ratio of failed to sent requests, zero if nothing has been sent yet.
+    pub fn failure_rate(&self) -> f64 {
+        match self.requests_sent() {
+            0 => 0.0,
+            sent => self.requests_failed() as f64 / sent as f64,
+        }
+    }
+}
+
+// Collects the statistics, remembering the requests awaiting a response.
+#[derive(Default)]
+pub struct StatsTracker {
+    stats: NetworkStats,
+    pending: HashMap<MessageId, (&'static str, Instant)>,
+}
+
+impl StatsTracker {
+    pub fn stats(&self) -> &NetworkStats {
+        &self.stats
+    }
+
+    pub fn request_sent(&mut self, msg_id: MessageId, kind: &'static str, bytes: u64) {
+        self.stats.bytes_sent += bytes;
+        self.stats.requests.entry(kind).or_insert_with(Default::default).sent += 1;
+        let _ = self.pending.insert(msg_id, (kind, Instant::now()));
+    }
+
+    pub fn response_received(&mut self, msg_id: &MessageId, bytes: u64, failed: bool) {
+        self.stats.bytes_received += bytes;
+
+        if let Some((kind, sent_at)) = self.pending.remove(msg_id) {
+            self.update_rtt(sent_at.elapsed());
+            if failed {
+                self.fail(kind);
+            }
+        }
+    }
+
+    pub fn timed_out(&mut self, msg_id: &MessageId) {
+        if let Some((kind, _)) = self.pending.remove(msg_id) {
+            self.fail(kind);
+        }
+    }
+
+    // Requests dropped on suspend or restart aren't counted as failed - their outcome is unknown.
+    pub fn forget_pending(&mut self) {
+        self.pending.clear();
+    }
+
+    fn fail(&mut self, kind: &'static str) {
+        self.stats.requests.entry(kind).or_insert_with(Default::default).failed += 1;
+    }
+
+    // Exponentially weighted moving average with a gain of 1/8, as in TCP.
+    fn update_rtt(&mut self, sample: Duration) {
+        self.stats.rtt = Some(match self.stats.rtt {
+            Some(rtt) => (rtt * 7 + sample) / 8,
+            None => sample,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_and_rtt() {
+        let mut tracker = StatsTracker::default();
+        let first = MessageId::new();
+        let second = MessageId::new();
+        let third = MessageId::new();
+
+        tracker.request_sent(first, "GetIData", 10);
+        tracker.request_sent(second, "GetIData", 10);
+        tracker.request_sent(third, "PutIData", 100);
+
+        tracker.response_received(&first, 1000, false);
+        tracker.response_received(&second, 20, true);
+        tracker.timed_out(&third);
+        // Late response to a timed out request.
+        tracker.response_received(&third, 5, false);
+
+        let stats = tracker.stats();
+        assert_eq!(stats.bytes_sent, 120);
+        assert_eq!(stats.bytes_received, 1025);
+        assert_eq!(
+            stats.requests["GetIData"],
+            RequestCounts { sent: 2, failed: 1 }
+        );
+        assert_eq!(
+            stats.requests["PutIData"],
+            RequestCounts { sent: 1, failed: 1 }
+        );
+        assert_eq!(stats.requests_sent(), 3);
+        assert_eq!(stats.requests_failed(), 2);
+        assert!(stats.rtt.is_some());
+    }
+}
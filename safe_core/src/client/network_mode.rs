@@ -0,0 +1,106 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use config_handler::get_config;
+use errors::CoreError;
+use std::env;
+use std::sync::atomic::{ATOMIC_USIZE_INIT, AtomicUsize, Ordering};
+
+/// Network the clients connect to.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum NetworkMode {
+    /// The network given by the bootstrap config.
+    Live,
+    /// The in-process mock vault. Only available in builds with the `mock-vault` feature.
+    Mock,
+}
+
+const UNRESOLVED: usize = 0;
+const LIVE: usize = 1;
+const MOCK: usize = 2;
+
+static MODE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+/// Selects the network for the clients created from now on, taking precedence over the
+/// `SAFE_TEST_NETWORK` env var and the config file.
+/// Fails if the mock vault is requested but this crate was built without it.
+pub fn set_network_mode(mode: NetworkMode) -> Result<(), CoreError> {
+    if mode == NetworkMode::Mock && !cfg!(feature = "mock-vault") {
+        return Err(CoreError::from(
+            "safe_core was built without the mock vault",
+        ));
+    }
+
+    MODE.store(encode(mode), Ordering::SeqCst);
+    Ok(())
+}
+
+/// Returns the network the clients connect to.
+///
+/// Unless selected with `set_network_mode`, it's given by the `SAFE_TEST_NETWORK` env var or the
+/// `network_mode` config option. Without either, builds with the `use-mock-routing` feature use
+/// the mock vault and all the others the live network.
+pub fn network_mode() -> NetworkMode {
+    match MODE.load(Ordering::SeqCst) {
+        LIVE => NetworkMode::Live,
+        MOCK => NetworkMode::Mock,
+        _ => {
+            let mode = configured_mode();
+            let _ = MODE.compare_and_swap(UNRESOLVED, encode(mode), Ordering::SeqCst);
+            mode
+        }
+    }
+}
+
+fn configured_mode() -> NetworkMode {
+    let configured = match env::var("SAFE_TEST_NETWORK").as_ref().map(String::as_str) {
+        Ok("live") => Some(NetworkMode::Live),
+        Ok("mock") => Some(NetworkMode::Mock),
+        _ => get_config().dev.and_then(|dev| dev.network_mode),
+    };
+
+    match configured {
+        Some(NetworkMode::Mock) if !cfg!(feature = "mock-vault") => {
+            warn!("Mock network requested, but safe_core was built without the mock vault.");
+            NetworkMode::Live
+        }
+        Some(mode) => mode,
+        None if cfg!(feature = "use-mock-routing") => NetworkMode::Mock,
+        None => NetworkMode::Live,
+    }
+}
+
+fn encode(mode: NetworkMode) -> usize {
+    match mode {
+        NetworkMode::Live => LIVE,
+        NetworkMode::Mock => MOCK,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Builds without the mock vault refuse to switch to it.
+    #[cfg(not(feature = "mock-vault"))]
+    #[test]
+    fn mock_unavailable() {
+        assert!(set_network_mode(NetworkMode::Mock).is_err());
+        assert_eq!(network_mode(), NetworkMode::Live);
+    }
+}
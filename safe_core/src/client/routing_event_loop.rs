@@ -18,6 +18,7 @@
 use errors::CoreError;
 use event::{CoreEvent, NetworkEvent, NetworkTx};
 use event_loop::{CoreMsg, CoreMsgTx};
+use maidsafe_utilities::serialisation::serialised_size;
 use routing::{Event, MessageId, Response};
 use std::sync::mpsc::Receiver;
 
@@ -30,11 +31,12 @@ where
         trace!("Received Routing Event: {:?}", it);
         match it {
             Event::Response { response, .. } => {
+                let bytes = serialised_size(&response);
                 let (msg_id, event) = match get_core_event(response) {
                     Ok(val) => val,
                     Err(_) => break,
                 };
-                if !fire(&mut core_tx, msg_id, event) {
+                if !fire(&mut core_tx, msg_id, event, bytes) {
                     break;
                 }
             }
@@ -139,8 +141,14 @@ fn get_core_event(res: Response) -> Result<(MessageId, CoreEvent), CoreError> {
 /// loop has hung up or sending fails for some other reason, treat it as an
 /// exit condition. The return value thus signifies if the firing was
 /// successful.
-fn fire<T: 'static>(core_tx: &mut CoreMsgTx<T>, msg_id: MessageId, event: CoreEvent) -> bool {
+fn fire<T: 'static>(
+    core_tx: &mut CoreMsgTx<T>,
+    msg_id: MessageId,
+    event: CoreEvent,
+    bytes: u64,
+) -> bool {
     let msg = CoreMsg::new(move |client, _| {
+        client.record_response(&msg_id, bytes, event.is_err());
         client.fire_hook(&msg_id, event);
         None
     });
@@ -0,0 +1,222 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::time::{Duration, Instant};
+
+/// Time to wait before trying to start a job again when too many requests are in flight.
+const BUSY_RETRY_MS: u64 = 50;
+
+/// Priority of a background job scheduled with `Client::schedule`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Priority {
+    /// Work which can wait, e.g. housekeeping.
+    Low,
+    /// The default.
+    Normal,
+    /// Work which should run before other background jobs, e.g. fetching what the user is
+    /// likely to ask for next.
+    High,
+}
+
+/// Limits on the background jobs run by the client.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SchedulerLimits {
+    /// Maximum number of jobs running at once, zero meaning no limit. Defaults to 1.
+    pub max_running: usize,
+    /// Minimum time between the starts of two jobs. Defaults to 100 milliseconds.
+    pub min_interval: Duration,
+    /// Jobs don't start while this many requests or more are awaiting a response, so that
+    /// they don't hold up the user's requests. Zero means no limit. Defaults to 1.
+    pub max_in_flight: usize,
+}
+
+impl Default for SchedulerLimits {
+    fn default() -> Self {
+        SchedulerLimits {
+            max_running: 1,
+            min_interval: Duration::from_millis(100),
+            max_in_flight: 1,
+        }
+    }
+}
+
+// What the client should do next about the scheduled jobs.
+#[derive(Debug, PartialEq)]
+pub enum Next<J> {
+    // Start the job.
+    Start(J),
+    // Try again after the delay.
+    RetryIn(Duration),
+    // Nothing until a job is scheduled or one finishes.
+    Wait,
+}
+
+// Jobs waiting to run, along with what the limits are checked against.
+pub struct Scheduler<J> {
+    limits: SchedulerLimits,
+    queues: BTreeMap<Priority, VecDeque<J>>,
+    running: usize,
+    last_started: Option<Instant>,
+    // Whether the client has a retry pending already.
+    retry_pending: bool,
+}
+
+impl<J> Scheduler<J> {
+    pub fn new(limits: SchedulerLimits) -> Self {
+        Scheduler {
+            limits: limits,
+            queues: BTreeMap::new(),
+            running: 0,
+            last_started: None,
+            retry_pending: false,
+        }
+    }
+
+    pub fn set_limits(&mut self, limits: SchedulerLimits) {
+        self.limits = limits;
+    }
+
+    pub fn push(&mut self, priority: Priority, job: J) {
+        self.queues
+            .entry(priority)
+            .or_insert_with(VecDeque::new)
+            .push_back(job);
+    }
+
+    pub fn queued(&self) -> usize {
+        self.queues.values().map(VecDeque::len).sum()
+    }
+
+    // Takes the job to start next, if the limits allow it given the number of requests in
+    // flight. Jobs of higher priority come first, jobs of the same priority in the order they
+    // were scheduled.
+    pub fn next(&mut self, in_flight: usize, now: Instant) -> Next<J> {
+        if self.queued() == 0 ||
+            (self.limits.max_running != 0 && self.running >= self.limits.max_running)
+        {
+            return Next::Wait;
+        }
+
+        let delay = if self.limits.max_in_flight != 0 && in_flight >= self.limits.max_in_flight {
+            Some(Duration::from_millis(BUSY_RETRY_MS))
+        } else {
+            self.last_started.and_then(|last_started| {
+                let elapsed = now.duration_since(last_started);
+                if elapsed < self.limits.min_interval {
+                    Some(self.limits.min_interval - elapsed)
+                } else {
+                    None
+                }
+            })
+        };
+
+        if let Some(delay) = delay {
+            if self.retry_pending {
+                return Next::Wait;
+            }
+            self.retry_pending = true;
+            return Next::RetryIn(delay);
+        }
+
+        let job = self.queues
+            .values_mut()
+            .rev()
+            .filter_map(VecDeque::pop_front)
+            .next();
+
+        match job {
+            Some(job) => {
+                self.running += 1;
+                self.last_started = Some(now);
+                Next::Start(job)
+            }
+            None => Next::Wait,
+        }
+    }
+
+    pub fn retry_fired(&mut self) {
+        self.retry_pending = false;
+    }
+
+    pub fn finished(&mut self) {
+        self.running -= 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test that jobs are taken by priority, then in the order they were scheduled.
+    #[test]
+    fn order() {
+        let mut scheduler = Scheduler::new(SchedulerLimits {
+            max_running: 0,
+            min_interval: Duration::from_secs(0),
+            max_in_flight: 0,
+        });
+        scheduler.push(Priority::Low, 1);
+        scheduler.push(Priority::High, 2);
+        scheduler.push(Priority::Normal, 3);
+        scheduler.push(Priority::High, 4);
+
+        let now = Instant::now();
+        assert_eq!(scheduler.next(0, now), Next::Start(2));
+        assert_eq!(scheduler.next(0, now), Next::Start(4));
+        assert_eq!(scheduler.next(0, now), Next::Start(3));
+        assert_eq!(scheduler.next(0, now), Next::Start(1));
+        assert_eq!(scheduler.next(0, now), Next::Wait);
+        assert_eq!(scheduler.running, 4);
+    }
+
+    // Test that jobs are held back by the limits.
+    #[test]
+    fn limits() {
+        let mut scheduler = Scheduler::new(SchedulerLimits {
+            max_running: 1,
+            min_interval: Duration::from_secs(10),
+            max_in_flight: 2,
+        });
+        scheduler.push(Priority::Normal, 1);
+        scheduler.push(Priority::Normal, 2);
+
+        let now = Instant::now();
+        // Too many requests in flight.
+        assert_eq!(
+            scheduler.next(2, now),
+            Next::RetryIn(Duration::from_millis(BUSY_RETRY_MS))
+        );
+        // A retry is pending already.
+        assert_eq!(scheduler.next(2, now), Next::Wait);
+        scheduler.retry_fired();
+
+        assert_eq!(scheduler.next(1, now), Next::Start(1));
+        // Too many jobs running.
+        assert_eq!(scheduler.next(0, now), Next::Wait);
+        scheduler.finished();
+
+        // Too early after the last job started.
+        let later = now + Duration::from_secs(4);
+        assert_eq!(scheduler.next(0, later), Next::RetryIn(Duration::from_secs(6)));
+        scheduler.retry_fired();
+
+        let later = now + Duration::from_secs(10);
+        assert_eq!(scheduler.next(0, later), Next::Start(2));
+        assert_eq!(scheduler.queued(), 0);
+    }
+}
@@ -16,16 +16,23 @@
 // relating to use of the SAFE Network Software.
 
 use CoreError;
+use client::NetworkMode;
 use config_file_handler;
+use std::env;
 use std::ffi::OsString;
 #[cfg(test)]
 use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::Duration;
 
 /// Configuration for safe-core.
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Config {
     /// Developer options.
     pub dev: Option<DevConfig>,
+    /// Client settings overriding the defaults.
+    pub client: Option<ClientConfigOverrides>,
 }
 
 /// Extra configuration options intended for developers.
@@ -37,6 +44,167 @@ pub struct DevConfig {
     pub mock_in_memory_storage: bool,
     /// Set the mock-vault path if using file store (`mock_in_memory_storage` is `false`).
     pub mock_vault_path: Option<String>,
+    /// Network to connect to (`"mock"` or `"live"`), if not the default for the build.
+    pub network_mode: Option<NetworkMode>,
+}
+
+/// Settings of the clients. Read when a client is created, by `client_config`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ClientConfig {
+    /// Time to wait for the connection to the network, in seconds. Defaults to 40.
+    pub connection_timeout_secs: u64,
+    /// Time to wait for the response to a request, in seconds. Defaults to 180.
+    pub request_timeout_secs: u64,
+    /// Delay before retrying a request rejected by the rate limiter, in milliseconds.
+    /// Defaults to 800.
+    pub retry_delay_ms: u64,
+    /// Number of `ImmutableData` chunks kept in memory. Defaults to 300.
+    pub idata_cache_size: usize,
+    /// Number of `MutableData` whose entries can be prefetched, zero disabling prefetching.
+    /// Defaults to 0.
+    pub prefetch_limit: usize,
+    /// Maximum number of requests awaiting a response at once, zero meaning no limit.
+    /// Defaults to 0.
+    pub max_pipeline_depth: usize,
+    /// Maximum number of chunk PUTs of a self-encrypted upload in flight at once. Defaults to 8.
+    pub put_window: usize,
+    /// Log levels used if the host doesn't pass any when initialising logging, in the format
+    /// accepted by `LogLevels`, e.g. `"warn,safe_core=debug"`. Defaults to none.
+    pub log_levels: Option<String>,
+}
+
+impl ClientConfig {
+    /// Request timeout as a `Duration`.
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    /// Connection timeout as a `Duration`.
+    pub fn connection_timeout(&self) -> Duration {
+        Duration::from_secs(self.connection_timeout_secs)
+    }
+
+    /// Retry delay as a `Duration`.
+    pub fn retry_delay(&self) -> Duration {
+        Duration::from_millis(self.retry_delay_ms)
+    }
+
+    /// Replaces the settings which are set in `overrides`.
+    pub fn apply(&mut self, overrides: &ClientConfigOverrides) {
+        if let Some(value) = overrides.connection_timeout_secs {
+            self.connection_timeout_secs = value;
+        }
+        if let Some(value) = overrides.request_timeout_secs {
+            self.request_timeout_secs = value;
+        }
+        if let Some(value) = overrides.retry_delay_ms {
+            self.retry_delay_ms = value;
+        }
+        if let Some(value) = overrides.idata_cache_size {
+            self.idata_cache_size = value;
+        }
+        if let Some(value) = overrides.prefetch_limit {
+            self.prefetch_limit = value;
+        }
+        if let Some(value) = overrides.max_pipeline_depth {
+            self.max_pipeline_depth = value;
+        }
+        if let Some(value) = overrides.put_window {
+            self.put_window = value;
+        }
+        if let Some(ref value) = overrides.log_levels {
+            self.log_levels = Some(value.clone());
+        }
+    }
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        ClientConfig {
+            connection_timeout_secs: 40,
+            request_timeout_secs: 180,
+            retry_delay_ms: 800,
+            idata_cache_size: 300,
+            prefetch_limit: 0,
+            max_pipeline_depth: 0,
+            put_window: 8,
+            log_levels: None,
+        }
+    }
+}
+
+/// One layer of client settings: the `client` section of the config file, the environment or
+/// the overrides set with `set_client_config_overrides`. Unset fields leave the setting of the
+/// layers below unchanged.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+pub struct ClientConfigOverrides {
+    /// See `ClientConfig::connection_timeout_secs`.
+    pub connection_timeout_secs: Option<u64>,
+    /// See `ClientConfig::request_timeout_secs`.
+    pub request_timeout_secs: Option<u64>,
+    /// See `ClientConfig::retry_delay_ms`.
+    pub retry_delay_ms: Option<u64>,
+    /// See `ClientConfig::idata_cache_size`.
+    pub idata_cache_size: Option<usize>,
+    /// See `ClientConfig::prefetch_limit`.
+    pub prefetch_limit: Option<usize>,
+    /// See `ClientConfig::max_pipeline_depth`.
+    pub max_pipeline_depth: Option<usize>,
+    /// See `ClientConfig::put_window`.
+    pub put_window: Option<usize>,
+    /// See `ClientConfig::log_levels`.
+    pub log_levels: Option<String>,
+}
+
+impl ClientConfigOverrides {
+    /// Reads the settings from the `SAFE_CLIENT_*` env vars, named after the fields in upper
+    /// case, e.g. `SAFE_CLIENT_REQUEST_TIMEOUT_SECS`. Values which don't parse are ignored.
+    pub fn from_env() -> Self {
+        ClientConfigOverrides {
+            connection_timeout_secs: env_var("SAFE_CLIENT_CONNECTION_TIMEOUT_SECS"),
+            request_timeout_secs: env_var("SAFE_CLIENT_REQUEST_TIMEOUT_SECS"),
+            retry_delay_ms: env_var("SAFE_CLIENT_RETRY_DELAY_MS"),
+            idata_cache_size: env_var("SAFE_CLIENT_IDATA_CACHE_SIZE"),
+            prefetch_limit: env_var("SAFE_CLIENT_PREFETCH_LIMIT"),
+            max_pipeline_depth: env_var("SAFE_CLIENT_MAX_PIPELINE_DEPTH"),
+            put_window: env_var("SAFE_CLIENT_PUT_WINDOW"),
+            log_levels: env::var("SAFE_CLIENT_LOG_LEVELS").ok(),
+        }
+    }
+}
+
+lazy_static! {
+    static ref OVERRIDES: Mutex<ClientConfigOverrides> = Mutex::new(Default::default());
+}
+
+/// Sets the overrides applied on top of the config file and the environment, e.g. from the
+/// host application's own settings. They take effect for the clients created from now on.
+pub fn set_client_config_overrides(overrides: ClientConfigOverrides) {
+    *unwrap!(OVERRIDES.lock()) = overrides;
+}
+
+/// Returns the client settings: the defaults, overridden by the config file, then by the
+/// environment and finally by the overrides set with `set_client_config_overrides`.
+pub fn client_config() -> ClientConfig {
+    let mut config = ClientConfig::default();
+    if let Some(ref overrides) = get_config().client {
+        config.apply(overrides);
+    }
+    config.apply(&ClientConfigOverrides::from_env());
+    config.apply(&unwrap!(OVERRIDES.lock()));
+    config
+}
+
+fn env_var<T: FromStr>(name: &str) -> Option<T> {
+    let value = match env::var(name) {
+        Ok(value) => value,
+        Err(_) => return None,
+    };
+    let parsed = value.parse().ok();
+    if parsed.is_none() {
+        warn!("Ignoring invalid value of {}: {}", name, value);
+    }
+    parsed
 }
 
 /// Reads the `safe_core` config file and returns it or a default if this fails.
@@ -107,6 +275,7 @@ mod test {
         let dev_config = unwrap!(config.dev, "{} is missing `dev` field.", path.display());
         assert_eq!(dev_config.mock_unlimited_mutations, true);
         assert_eq!(dev_config.mock_in_memory_storage, true);
+        assert_eq!(dev_config.network_mode, Some(NetworkMode::Mock));
     }
 
     #[test]
@@ -129,5 +298,35 @@ mod test {
         assert_eq!(dev_config.mock_unlimited_mutations, false);
         assert_eq!(dev_config.mock_in_memory_storage, false);
         assert_eq!(dev_config.mock_vault_path, Some(String::from("./tmp")));
+
+        let client_config = unwrap!(config.client, "{} is missing `client` field.", path.display());
+        assert_eq!(client_config.request_timeout_secs, Some(60));
+        assert_eq!(client_config.idata_cache_size, None);
+    }
+
+    // Each layer only overrides the settings it sets.
+    #[test]
+    fn client_config_layers() {
+        let mut config = ClientConfig::default();
+        config.apply(&ClientConfigOverrides {
+            request_timeout_secs: Some(10),
+            prefetch_limit: Some(5),
+            ..Default::default()
+        });
+        config.apply(&ClientConfigOverrides {
+            prefetch_limit: Some(7),
+            put_window: Some(2),
+            log_levels: Some("debug".to_owned()),
+            ..Default::default()
+        });
+
+        assert_eq!(config.request_timeout(), Duration::from_secs(10));
+        assert_eq!(config.prefetch_limit, 7);
+        assert_eq!(config.put_window, 2);
+        assert_eq!(config.log_levels, Some("debug".to_owned()));
+        assert_eq!(
+            config.connection_timeout_secs,
+            ClientConfig::default().connection_timeout_secs
+        );
     }
 }
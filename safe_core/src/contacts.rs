@@ -0,0 +1,242 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Address book.
+//!
+//! Contacts are stored in the `_contacts` standard container, so every app granted access to
+//! it shares the same address book. Each entry maps an alias chosen by the user to a
+//! `Contact`, encrypted with the container's keys.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryActions};
+use rust_sodium::crypto::{box_, sign};
+use std::collections::BTreeMap;
+use utils::FutureExt;
+
+/// Name of the standard container holding the contacts.
+pub const CONTACTS_CONTAINER: &str = "_contacts";
+
+/// Entry of the address book. At least one of the fields has to be set.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Contact {
+    /// Public name of the contact.
+    pub public_name: Option<String>,
+    /// Public encryption key of the contact.
+    pub enc_key: Option<box_::PublicKey>,
+    /// Public signing key of the contact.
+    pub sign_key: Option<sign::PublicKey>,
+}
+
+impl Contact {
+    fn is_empty(&self) -> bool {
+        self.public_name.is_none() && self.enc_key.is_none() && self.sign_key.is_none()
+    }
+}
+
+/// Adds a contact under `alias`, replacing the contact already stored under it, if any.
+pub fn add<T: 'static>(
+    client: &Client<T>,
+    contacts: &MDataInfo,
+    alias: &str,
+    contact: &Contact,
+) -> Box<CoreFuture<()>> {
+    if contact.is_empty() {
+        return err!(CoreError::from("Contact has neither a public name nor keys"));
+    }
+
+    let c2 = client.clone();
+    let contacts = contacts.clone();
+    let key = fry!(contacts.enc_entry_key(alias.as_bytes()));
+    let content = fry!(serialise(contact).map_err(CoreError::from).and_then(|contact| {
+        contacts.enc_entry_value(&contact)
+    }));
+
+    client
+        .get_mdata_value(contacts.name, contacts.type_tag, key.clone())
+        .then(move |res| match res {
+            Ok(value) => Ok(EntryActions::new().update(key, content, value.entry_version + 1)),
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                Ok(EntryActions::new().ins(key, content, 0))
+            }
+            Err(error) => Err(error),
+        })
+        .and_then(move |actions| {
+            c2.mutate_mdata_entries(contacts.name, contacts.type_tag, actions.into())
+        })
+        .into_box()
+}
+
+/// Gets the contact stored under `alias`.
+/// Fails with `NoSuchEntry` if there is none.
+pub fn get<T: 'static>(
+    client: &Client<T>,
+    contacts: &MDataInfo,
+    alias: &str,
+) -> Box<CoreFuture<Contact>> {
+    let contacts = contacts.clone();
+    let key = fry!(contacts.enc_entry_key(alias.as_bytes()));
+
+    client
+        .get_mdata_value(contacts.name, contacts.type_tag, key)
+        .and_then(move |value| {
+            // Empty entry means the contact has been removed.
+            if value.content.is_empty() {
+                return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            Ok(deserialise(&contacts.decrypt(&value.content)?)?)
+        })
+        .into_box()
+}
+
+/// Lists all contacts, keyed by their aliases.
+pub fn list<T: 'static>(
+    client: &Client<T>,
+    contacts: &MDataInfo,
+) -> Box<CoreFuture<BTreeMap<String, Contact>>> {
+    let contacts = contacts.clone();
+
+    client
+        .list_mdata_entries(contacts.name, contacts.type_tag)
+        .and_then(move |entries| {
+            let mut result = BTreeMap::new();
+
+            for (key, value) in entries {
+                if value.content.is_empty() {
+                    continue;
+                }
+
+                let alias = String::from_utf8(contacts.decrypt(&key)?).map_err(|_| {
+                    CoreError::from("Invalid contact alias")
+                })?;
+                let contact = deserialise(&contacts.decrypt(&value.content)?)?;
+                let _ = result.insert(alias, contact);
+            }
+
+            Ok(result)
+        })
+        .into_box()
+}
+
+/// Removes the contact stored under `alias`.
+pub fn remove<T: 'static>(
+    client: &Client<T>,
+    contacts: &MDataInfo,
+    alias: &str,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let contacts = contacts.clone();
+    let key = fry!(contacts.enc_entry_key(alias.as_bytes()));
+
+    client
+        .get_mdata_value(contacts.name, contacts.type_tag, key.clone())
+        .and_then(move |value| {
+            if value.content.is_empty() {
+                return err!(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            c2.mutate_mdata_entries(
+                contacts.name,
+                contacts.type_tag,
+                EntryActions::new()
+                    .del(key, value.entry_version + 1)
+                    .into(),
+            )
+        })
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use routing::{Action, MutableData, PermissionSet, User};
+    use utils::test_utils::random_client;
+
+    // Test adding, replacing, listing and removing contacts.
+    #[test]
+    fn add_list_remove() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+
+            let contacts = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let contacts_md = unwrap!(MutableData::new(
+                contacts.name,
+                contacts.type_tag,
+                btree_map![
+                    User::Key(unwrap!(client.public_signing_key())) => PermissionSet::new()
+                        .allow(Action::Insert)
+                        .allow(Action::Update)
+                        .allow(Action::Delete),
+                ],
+                btree_map![],
+                btree_set![unwrap!(client.owner_key())],
+            ));
+
+            let (enc_key, _) = box_::gen_keypair();
+            let alice = Contact {
+                public_name: Some("alice".to_string()),
+                ..Default::default()
+            };
+            let bob = Contact {
+                enc_key: Some(enc_key),
+                ..Default::default()
+            };
+            let (alice2, bob2, bob3) = (alice.clone(), bob.clone(), bob.clone());
+
+            client
+                .put_mdata(contacts_md)
+                .then(move |res| {
+                    unwrap!(res);
+                    add(&c2, &contacts, "alice", &bob).map(move |_| contacts)
+                })
+                .then(move |res| {
+                    let contacts = unwrap!(res);
+                    add(&c3, &contacts, "alice", &alice).map(move |_| contacts)
+                })
+                .then(move |res| {
+                    let contacts = unwrap!(res);
+                    add(&c4, &contacts, "bob", &bob2).map(move |_| contacts)
+                })
+                .then(move |res| {
+                    let contacts = unwrap!(res);
+                    get(&c5, &contacts, "alice").map(move |contact| (contacts, contact))
+                })
+                .then(move |res| {
+                    let (contacts, contact) = unwrap!(res);
+                    assert_eq!(contact, alice2);
+
+                    remove(&c6, &contacts, "alice").map(move |_| contacts)
+                })
+                .then(move |res| {
+                    let contacts = unwrap!(res);
+                    list(&c7, &contacts)
+                })
+                .map(move |contacts| {
+                    assert_eq!(contacts.len(), 1);
+                    assert_eq!(contacts.get("bob"), Some(&bob3));
+                })
+        });
+    }
+}
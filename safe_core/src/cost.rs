@@ -0,0 +1,223 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Estimates of the mutations operations consume from the account balance.
+//!
+//! The network charges one mutation per PUT or mutation request, regardless of its size or
+//! the number of entries it touches. `ImmutableData` chunks are charged even when the
+//! network already holds them, so the estimates don't depend on de-duplication.
+
+use client::Client;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use routing::ClientError;
+use self_encryption::{MAX_CHUNK_SIZE, MIN_CHUNK_SIZE};
+use utils::FutureExt;
+
+// Size of a serialised `DataMap` without its chunk details.
+const DATA_MAP_OVERHEAD: u64 = 12;
+// Size of the serialised details of a single chunk in a `DataMap`.
+const CHUNK_DETAILS_SIZE: u64 = 92;
+
+/// Operation whose cost is estimated.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Operation {
+    /// Storing `size` bytes with `immutable_data::create` and putting the result.
+    PutIData {
+        /// Size of the content.
+        size: u64,
+    },
+    /// Writing `size` bytes into a file and inserting it into a directory (or updating an
+    /// existing one). Compressed files usually cost less, as `size` is taken as is.
+    FileUpload {
+        /// Size of the content.
+        size: u64,
+    },
+    /// Creating a directory with `nfs::create_dir`.
+    CreateDir,
+    /// Creating a directory and linking it into its parent with `journal::create_sub_dir`.
+    CreateSubDir,
+    /// Putting a new `MutableData`, including its initial entries.
+    PutMData,
+    /// Mutating the entries of a `MutableData` in one request.
+    MutateMDataEntries,
+}
+
+/// Returns the number of mutations the operation will consume.
+pub fn estimate_mutations(op: &Operation) -> u64 {
+    match *op {
+        Operation::PutIData { size } => put_idata_mutations(size),
+        Operation::FileUpload { size } => {
+            let chunks = chunk_count(size);
+            chunks + put_idata_mutations(DATA_MAP_OVERHEAD + chunks * CHUNK_DETAILS_SIZE) + 1
+        }
+        // Journal entry, the directory itself and the link into the parent.
+        Operation::CreateSubDir => 3,
+        Operation::CreateDir |
+        Operation::PutMData |
+        Operation::MutateMDataEntries => 1,
+    }
+}
+
+/// Returns the number of mutations the operations will consume in total.
+pub fn estimate_total_mutations(ops: &[Operation]) -> u64 {
+    ops.iter().map(estimate_mutations).sum()
+}
+
+/// Fails with `LowBalance` if the account has less than `mutations` mutations available.
+pub fn check_balance<T: 'static>(client: &Client<T>, mutations: u64) -> Box<CoreFuture<()>> {
+    client
+        .get_account_info()
+        .and_then(move |info| if info.mutations_available < mutations {
+            Err(CoreError::RoutingClientError(ClientError::LowBalance))
+        } else {
+            Ok(())
+        })
+        .into_box()
+}
+
+// The content is self-encrypted into chunks, and the resulting `DataMap` is small enough to
+// fit into the single `ImmutableData` put at the end.
+fn put_idata_mutations(size: u64) -> u64 {
+    chunk_count(size) + 1
+}
+
+// Number of chunks self-encryption splits `size` bytes into. Content smaller than three
+// chunks of the minimum size is kept in the `DataMap` itself.
+fn chunk_count(size: u64) -> u64 {
+    let min = u64::from(MIN_CHUNK_SIZE);
+    let max = u64::from(MAX_CHUNK_SIZE);
+
+    if size < 3 * min {
+        0
+    } else if size < 3 * max {
+        3
+    } else {
+        (size + max - 1) / max
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use client::MDataInfo;
+    use nfs::{File, Mode, NfsError, NfsFuture, create_dir, file_helper};
+    use utils::test_utils::random_client;
+
+    // Test the estimates of file uploads against the mutations actually consumed.
+    #[test]
+    fn file_upload() {
+        assert_eq!(estimate_mutations(&Operation::FileUpload { size: 0 }), 2);
+        assert_eq!(estimate_mutations(&Operation::FileUpload { size: 10_000 }), 5);
+        assert_eq!(
+            estimate_mutations(&Operation::FileUpload { size: 5 * 1024 * 1024 }),
+            7
+        );
+        // 40 chunks give a `DataMap` large enough to be self-encrypted too.
+        assert_eq!(
+            estimate_mutations(&Operation::FileUpload { size: 40 * 1024 * 1024 }),
+            45
+        );
+
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let root = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let root2 = root.clone();
+            let root3 = root.clone();
+            let root4 = root.clone();
+
+            create_dir(client, &root, btree_map![], btree_map![])
+                .then(move |res| {
+                    unwrap!(res);
+                    upload(&c2, &root2, 0)
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    upload(&c3, &root3, 10_000)
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    upload(&c4, &root4, 5 * 1024 * 1024)
+                })
+        });
+    }
+
+    // Test that the balance check fails once the estimate exceeds the available mutations.
+    #[test]
+    fn balance() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+
+            client
+                .get_account_info()
+                .then(move |res| {
+                    let available = unwrap!(res).mutations_available;
+                    check_balance(&c2, available).map(move |_| available)
+                })
+                .then(move |res| {
+                    let available = unwrap!(res);
+                    check_balance(&c3, available + 1)
+                })
+                .then(|res| match res {
+                    Err(CoreError::RoutingClientError(ClientError::LowBalance)) => {
+                        Ok::<_, CoreError>(())
+                    }
+                    x => panic!("Unexpected {:?}", x),
+                })
+        });
+    }
+
+    // Uploads a file of `size` bytes and checks the estimate.
+    fn upload(client: &Client<()>, root: &MDataInfo, size: u64) -> Box<NfsFuture<()>> {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let root = root.clone();
+
+        client
+            .get_account_info()
+            .map_err(NfsError::from)
+            .and_then(move |before| {
+                file_helper::write(c2, File::new(Vec::new()), Mode::Overwrite, None)
+                    .and_then(move |writer| {
+                        writer.write(&vec![7; size as usize]).map(move |_| writer)
+                    })
+                    .and_then(|writer| writer.close())
+                    .and_then(move |file| {
+                        file_helper::insert(c3, root, format!("{}", size), &file)
+                    })
+                    .map(move |_| before)
+            })
+            .and_then(move |before| {
+                c4.get_account_info()
+                    .map(move |after| (before, after))
+                    .map_err(NfsError::from)
+            })
+            .map(move |(before, after)| {
+                assert_eq!(
+                    after.mutations_done - before.mutations_done,
+                    estimate_mutations(&Operation::FileUpload { size: size })
+                );
+            })
+            .into_box()
+    }
+}
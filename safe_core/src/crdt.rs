@@ -0,0 +1,438 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Conflict-free replicated data types stored in `MutableData` entries.
+//!
+//! When several writers update the same entry, all but the first of them fail with
+//! `InvalidSuccessor` (or `EntryExists`). With a CRDT as the entry value, the loser can just
+//! fetch the new value, merge its own state into it and try again, without losing either
+//! update. `merge` and `update` do exactly that.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use futures::future::{self, Loop};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use rand;
+use routing::{ClientError, EntryActions, EntryError};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet};
+use utils::FutureExt;
+
+/// Maximum number of times a conflicting mutation is retried.
+pub const MAX_ATTEMPTS: usize = 10;
+
+/// State which can be merged with concurrently modified copies of itself.
+pub trait Crdt: Clone + Default + Serialize + DeserializeOwned + 'static {
+    /// Merge `other` into `self`. Merging has to be commutative, associative
+    /// and idempotent.
+    fn merge(&mut self, other: Self);
+}
+
+/// Register holding the most recently written value.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct LwwRegister<V> {
+    value: Option<V>,
+    timestamp: u64,
+    // Decides between writes with the same timestamp.
+    tie_breaker: u64,
+}
+
+impl<V> LwwRegister<V> {
+    /// Current value.
+    pub fn get(&self) -> Option<&V> {
+        self.value.as_ref()
+    }
+
+    /// Set the value. The write is ordered after the current value even if
+    /// `timestamp` is older than it, so a local write is never lost to a value
+    /// it has already observed.
+    pub fn set(&mut self, value: V, timestamp: u64) {
+        self.value = Some(value);
+        self.timestamp = cmp::max(timestamp, self.timestamp + 1);
+        self.tie_breaker = rand::random();
+    }
+}
+
+impl<V> Default for LwwRegister<V> {
+    fn default() -> Self {
+        LwwRegister {
+            value: None,
+            timestamp: 0,
+            tie_breaker: 0,
+        }
+    }
+}
+
+impl<V: Clone + Serialize + DeserializeOwned + 'static> Crdt for LwwRegister<V> {
+    fn merge(&mut self, other: Self) {
+        if (other.timestamp, other.tie_breaker) > (self.timestamp, self.tie_breaker) {
+            *self = other;
+        }
+    }
+}
+
+/// Set which elements can be added to, but never removed from.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GSet<V: Ord> {
+    items: BTreeSet<V>,
+}
+
+impl<V: Ord> GSet<V> {
+    /// Add an element.
+    pub fn insert(&mut self, item: V) {
+        let _ = self.items.insert(item);
+    }
+
+    /// Check whether the set contains `item`.
+    pub fn contains(&self, item: &V) -> bool {
+        self.items.contains(item)
+    }
+
+    /// Elements of the set.
+    pub fn items(&self) -> &BTreeSet<V> {
+        &self.items
+    }
+}
+
+impl<V: Ord> Default for GSet<V> {
+    fn default() -> Self {
+        GSet { items: BTreeSet::new() }
+    }
+}
+
+impl<V: Clone + Ord + Serialize + DeserializeOwned + 'static> Crdt for GSet<V> {
+    fn merge(&mut self, other: Self) {
+        self.items.extend(other.items);
+    }
+}
+
+/// Map with observed-remove semantics: removing a key only removes the inserts
+/// the remover has seen, so a concurrent insert of the same key wins. Values
+/// of concurrent inserts are merged.
+///
+/// Removed inserts are remembered forever, so the map only suits keys which
+/// aren't removed and reinserted at a high rate.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OrMap<K: Ord, V> {
+    // Values along with tags of the inserts which haven't been removed.
+    entries: BTreeMap<K, (BTreeSet<u64>, V)>,
+    removed: BTreeSet<u64>,
+}
+
+impl<K: Ord, V: Crdt> OrMap<K, V> {
+    /// Insert a value, merging it into the current one if the key is present.
+    pub fn insert(&mut self, key: K, value: V) {
+        let tag = rand::random();
+        if let Some(&mut (ref mut tags, ref mut current)) = self.entries.get_mut(&key) {
+            let _ = tags.insert(tag);
+            current.merge(value);
+            return;
+        }
+        let _ = self.entries.insert(key, (btree_set![tag], value));
+    }
+
+    /// Remove a key.
+    pub fn remove(&mut self, key: &K) {
+        if let Some((tags, _)) = self.entries.remove(key) {
+            self.removed.extend(tags);
+        }
+    }
+
+    /// Value of the given key.
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|&(_, ref value)| value)
+    }
+
+    /// Keys and values of the map.
+    pub fn iter<'a>(&'a self) -> Box<Iterator<Item = (&'a K, &'a V)> + 'a> {
+        Box::new(self.entries.iter().map(|(key, &(_, ref value))| (key, value)))
+    }
+
+    /// Number of keys in the map.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Check whether the map is empty.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+impl<K: Ord, V> Default for OrMap<K, V> {
+    fn default() -> Self {
+        OrMap {
+            entries: BTreeMap::new(),
+            removed: BTreeSet::new(),
+        }
+    }
+}
+
+impl<K, V> Crdt for OrMap<K, V>
+where
+    K: Clone + Ord + Serialize + DeserializeOwned + 'static,
+    V: Crdt,
+{
+    fn merge(&mut self, other: Self) {
+        self.removed.extend(other.removed);
+
+        for (key, (tags, value)) in other.entries {
+            if let Some(&mut (ref mut current_tags, ref mut current)) = self.entries.get_mut(&key) {
+                current_tags.extend(tags);
+                current.merge(value);
+                continue;
+            }
+            let _ = self.entries.insert(key, (tags, value));
+        }
+
+        let removed = &self.removed;
+        let mut dead = Vec::new();
+        for (key, &mut (ref mut tags, _)) in &mut self.entries {
+            *tags = tags.difference(removed).cloned().collect();
+            if tags.is_empty() {
+                dead.push(key.clone());
+            }
+        }
+        for key in dead {
+            let _ = self.entries.remove(&key);
+        }
+    }
+}
+
+/// Fetch the state stored in the given entry, or the default state if there's
+/// no such entry yet.
+pub fn fetch<T: 'static, C: Crdt>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    key: &[u8],
+) -> Box<CoreFuture<C>> {
+    fetch_versioned(client, info, key)
+        .map(|(state, _)| state)
+        .into_box()
+}
+
+/// Merge `state` into the state stored in the given entry, creating the entry
+/// if it doesn't exist yet. Mutations conflicting with concurrent writers are
+/// retried, up to `MAX_ATTEMPTS` times. Returns the merged state.
+pub fn merge<T: 'static, C: Crdt>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    key: Vec<u8>,
+    state: C,
+) -> Box<CoreFuture<C>> {
+    let client = client.clone();
+    let info = info.clone();
+
+    future::loop_fn(0, move |attempts| {
+        let c2 = client.clone();
+        let info2 = info.clone();
+        let key2 = key.clone();
+        let state = state.clone();
+
+        fetch_versioned(&client, &info, &key)
+            .and_then(move |(mut current, version)| {
+                current.merge(state);
+
+                let enc_key = info2.enc_entry_key(&key2)?;
+                let content = info2.enc_entry_value(&serialise(&current)?)?;
+                let actions = match version {
+                    Some(version) => EntryActions::new().update(enc_key, content, version + 1),
+                    None => EntryActions::new().ins(enc_key, content, 0),
+                };
+
+                Ok((info2, current, actions))
+            })
+            .and_then(move |(info, current, actions)| {
+                c2.mutate_mdata_entries(info.name, info.type_tag, actions.into())
+                    .then(move |res| match res {
+                        Ok(()) => Ok(Loop::Break(current)),
+                        Err(CoreError::RoutingClientError(
+                            ClientError::InvalidEntryActions(ref errors),
+                        )) if attempts < MAX_ATTEMPTS && is_conflict(errors) => {
+                            Ok(Loop::Continue(attempts + 1))
+                        }
+                        Err(error) => Err(error),
+                    })
+            })
+    }).into_box()
+}
+
+/// Fetch the state stored in the given entry, modify it with `f` and merge it
+/// back as `merge` does. Returns the merged state.
+pub fn update<T, C, F>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    key: Vec<u8>,
+    f: F,
+) -> Box<CoreFuture<C>>
+where
+    T: 'static,
+    C: Crdt,
+    F: FnOnce(&mut C) + 'static,
+{
+    let client2 = client.clone();
+    let info2 = info.clone();
+
+    fetch(client, info, &key)
+        .and_then(move |mut state| {
+            f(&mut state);
+            merge(&client2, &info2, key, state)
+        })
+        .into_box()
+}
+
+fn fetch_versioned<T: 'static, C: Crdt>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    key: &[u8],
+) -> Box<CoreFuture<(C, Option<u64>)>> {
+    let info = info.clone();
+    let key = fry!(info.enc_entry_key(key));
+
+    client
+        .get_mdata_value(info.name, info.type_tag, key)
+        .then(move |res| match res {
+            // Empty entry means the entry has been deleted.
+            Ok(ref value) if value.content.is_empty() => {
+                Ok((C::default(), Some(value.entry_version)))
+            }
+            Ok(value) => {
+                let state = deserialise(&info.decrypt(&value.content)?)?;
+                Ok((state, Some(value.entry_version)))
+            }
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                Ok((C::default(), None))
+            }
+            Err(error) => Err(error),
+        })
+        .into_box()
+}
+
+fn is_conflict(errors: &BTreeMap<Vec<u8>, EntryError>) -> bool {
+    errors.values().any(|error| match *error {
+        EntryError::EntryExists(_) |
+        EntryError::InvalidSuccessor(_) => true,
+        _ => false,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::{Action, MutableData, PermissionSet, User};
+    use utils::test_utils::random_client;
+
+    // Test that a local write wins over the value it was made on, and that
+    // concurrent writes converge.
+    #[test]
+    fn lww_register() {
+        let mut a = LwwRegister::default();
+        a.set("a", 10);
+
+        let mut b = a.clone();
+        b.set("b", 5);
+        let mut merged = a.clone();
+        merged.merge(b.clone());
+        assert_eq!(merged.get(), Some(&"b"));
+
+        a.set("c", 11);
+        let mut ab = a.clone();
+        ab.merge(b.clone());
+        let mut ba = b;
+        ba.merge(a);
+        assert_eq!(ab.get(), ba.get());
+    }
+
+    // Test that a concurrent insert wins over a remove.
+    #[test]
+    fn or_map() {
+        let mut a = OrMap::default();
+        a.insert(1, GSet::default());
+        let mut b = a.clone();
+
+        a.remove(&1);
+        let mut set = GSet::default();
+        set.insert("x");
+        b.insert(1, set);
+        b.insert(2, GSet::default());
+
+        let mut ab = a.clone();
+        ab.merge(b.clone());
+        let mut ba = b;
+        ba.merge(a);
+
+        for merged in &[ab, ba] {
+            assert_eq!(merged.len(), 2);
+            assert!(unwrap!(merged.get(&1)).contains(&"x"));
+        }
+
+        // Removal of everything observed removes the key for good.
+        let mut c = OrMap::default();
+        c.insert(1, GSet::<u8>::default());
+        let mut d = c.clone();
+        d.remove(&1);
+        c.merge(d);
+        assert!(c.is_empty());
+    }
+
+    // Test that concurrent merges into the same entry are all retained.
+    #[test]
+    fn concurrent_merges() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+
+            let info = unwrap!(MDataInfo::random_private(10_000));
+            let md = unwrap!(MutableData::new(
+                info.name,
+                info.type_tag,
+                btree_map![
+                    User::Key(unwrap!(client.public_signing_key())) => PermissionSet::new()
+                        .allow(Action::Insert)
+                        .allow(Action::Update),
+                ],
+                btree_map![],
+                btree_set![unwrap!(client.owner_key())],
+            ));
+
+            client
+                .put_mdata(md)
+                .then(move |res| {
+                    unwrap!(res);
+
+                    let merges: Vec<_> = (0..3)
+                        .map(|i| {
+                            let mut set = GSet::default();
+                            set.insert(i);
+                            merge(&c2, &info, b"set".to_vec(), set)
+                        })
+                        .collect();
+
+                    future::join_all(merges).map(move |_| info)
+                })
+                .then(move |res| {
+                    let info = unwrap!(res);
+                    fetch::<_, GSet<u8>>(&c3, &info, b"set")
+                })
+                .map(|set| assert_eq!(*set.items(), btree_set![0, 1, 2]))
+        });
+    }
+}
@@ -0,0 +1,346 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Public names.
+//!
+//! A public name (the `myname` part of `safe://www.myname`) is claimed by storing a public
+//! `MutableData` at `public_id_name(myname)` with the `PUBLIC_ID_TAG` type tag. Its single
+//! `SERVICES_ENTRY_KEY` entry points to the services `MutableData`, which maps service names
+//! (`www`, `blog`, ...) to the locations of the public directories serving them.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::{Future, IntoFuture};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryActions, MutableData, Value, XorName};
+use utils::FutureExt;
+use {PUBLIC_ID_TAG, SERVICES_TAG, public_id_name};
+
+/// Key of the public ID entry which points to the services `MutableData`.
+pub const SERVICES_ENTRY_KEY: &[u8] = b"_services";
+
+/// Service resolved when a path doesn't name one explicitly.
+pub const DEFAULT_SERVICE: &str = "www";
+
+/// Claims `public_name` and creates an empty services `MutableData` for it.
+/// Fails with `DataExists` if the public name is already taken.
+/// Returns `MDataInfo` of the services container.
+pub fn register<T: 'static>(client: &Client<T>, public_name: &str) -> Box<CoreFuture<MDataInfo>> {
+    let c2 = client.clone();
+
+    let owner_key = fry!(client.owner_key());
+    let services = fry!(MDataInfo::random_public(SERVICES_TAG));
+
+    let pointer = fry!(serialise(&(services.name, services.type_tag)));
+    let public_id = fry!(MutableData::new(
+        public_id_name(public_name),
+        PUBLIC_ID_TAG,
+        btree_map![],
+        btree_map![
+            SERVICES_ENTRY_KEY.to_vec() => Value { content: pointer, entry_version: 0 }
+        ],
+        btree_set![owner_key],
+    ));
+    let services_md = fry!(MutableData::new(
+        services.name,
+        services.type_tag,
+        btree_map![],
+        btree_map![],
+        btree_set![owner_key],
+    ));
+
+    client
+        .put_mdata(public_id)
+        .and_then(move |_| c2.put_mdata(services_md))
+        .map(move |_| services)
+        .into_box()
+}
+
+/// Gets `MDataInfo` of the services container of `public_name`.
+/// Fails with `NoSuchEntry` if the public name has no services attached.
+pub fn fetch_services<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+) -> Box<CoreFuture<MDataInfo>> {
+    client
+        .get_mdata_value(
+            public_id_name(public_name),
+            PUBLIC_ID_TAG,
+            SERVICES_ENTRY_KEY.to_vec(),
+        )
+        .and_then(|value| {
+            let (name, type_tag) = decode_pointer(&value.content)?;
+            Ok(MDataInfo::new_public(name, type_tag))
+        })
+        .into_box()
+}
+
+/// Lists names of the services attached to `public_name`.
+pub fn list_services<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+) -> Box<CoreFuture<Vec<String>>> {
+    let c2 = client.clone();
+
+    fetch_services(client, public_name)
+        .and_then(move |services| {
+            c2.list_mdata_entries(services.name, services.type_tag)
+        })
+        .and_then(|entries| {
+            let mut names = Vec::with_capacity(entries.len());
+
+            for (key, value) in entries {
+                // Empty entry means the service has been removed.
+                if !value.content.is_empty() {
+                    let name = String::from_utf8(key).map_err(|_| {
+                        CoreError::from("Invalid service name")
+                    })?;
+                    names.push(name);
+                }
+            }
+
+            names.sort();
+            Ok(names)
+        })
+        .into_box()
+}
+
+/// Points `service` of `public_name` at the public directory `target`, replacing
+/// the previous target if the service already exists.
+///
+/// The services container is readable by anyone, so only public targets are
+/// accepted; attaching a private directory fails with `OperationForbidden`.
+pub fn set_service<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+    service: &str,
+    target: &MDataInfo,
+) -> Box<CoreFuture<()>> {
+    if target.enc_key().is_some() {
+        return err!(CoreError::OperationForbidden);
+    }
+
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let key = service.as_bytes().to_vec();
+    let content = fry!(serialise(&(target.name, target.type_tag)));
+
+    fetch_services(client, public_name)
+        .and_then(move |services| {
+            c2.get_mdata_value(services.name, services.type_tag, key.clone())
+                .then(move |res| {
+                    let actions = match res {
+                        Ok(value) => EntryActions::new().update(
+                            key,
+                            content,
+                            value.entry_version + 1,
+                        ),
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                            EntryActions::new().ins(key, content, 0)
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    Ok((services, actions))
+                })
+        })
+        .and_then(move |(services, actions)| {
+            c3.mutate_mdata_entries(services.name, services.type_tag, actions.into())
+        })
+        .into_box()
+}
+
+/// Detaches `service` from `public_name`.
+/// Fails with `NoSuchEntry` if the service doesn't exist.
+pub fn remove_service<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+    service: &str,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let key = service.as_bytes().to_vec();
+
+    fetch_services(client, public_name)
+        .and_then(move |services| {
+            c2.get_mdata_value(services.name, services.type_tag, key.clone())
+                .and_then(move |value| {
+                    if value.content.is_empty() {
+                        return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+                    }
+                    Ok((services, key, value.entry_version))
+                })
+        })
+        .and_then(move |(services, key, version)| {
+            c3.mutate_mdata_entries(
+                services.name,
+                services.type_tag,
+                EntryActions::new().del(key, version + 1).into(),
+            )
+        })
+        .into_box()
+}
+
+/// Resolves `service` of `public_name` to `MDataInfo` of the directory serving it.
+/// Fails with `NoSuchEntry` if either the public name or the service doesn't exist.
+pub fn resolve<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+    service: &str,
+) -> Box<CoreFuture<MDataInfo>> {
+    let c2 = client.clone();
+    let key = service.as_bytes().to_vec();
+
+    fetch_services(client, public_name)
+        .and_then(move |services| {
+            c2.get_mdata_value(services.name, services.type_tag, key)
+        })
+        .and_then(|value| {
+            let (name, type_tag) = decode_pointer(&value.content)?;
+            Ok(MDataInfo::new_public(name, type_tag))
+        })
+        .into_box()
+}
+
+/// Resolves a `public_name/service` path. The service part is optional and
+/// defaults to `DEFAULT_SERVICE`.
+pub fn resolve_path<T: 'static>(client: &Client<T>, path: &str) -> Box<CoreFuture<MDataInfo>> {
+    let client = client.clone();
+
+    parse_path(path)
+        .map(|(public_name, service)| (public_name.to_owned(), service.to_owned()))
+        .into_future()
+        .and_then(move |(public_name, service)| resolve(&client, &public_name, &service))
+        .into_box()
+}
+
+/// Splits a `public_name/service` path into its parts, substituting
+/// `DEFAULT_SERVICE` when the service is omitted.
+pub fn parse_path(path: &str) -> Result<(&str, &str), CoreError> {
+    let path = path.trim_matches('/');
+    let (public_name, service) = match path.find('/') {
+        Some(index) => (&path[..index], &path[index + 1..]),
+        None => (path, DEFAULT_SERVICE),
+    };
+
+    if public_name.is_empty() || service.is_empty() || service.contains('/') {
+        return Err(CoreError::Unexpected(format!("Invalid public name path: {}", path)));
+    }
+
+    Ok((public_name, service))
+}
+
+fn decode_pointer(content: &[u8]) -> Result<(XorName, u64), CoreError> {
+    // Empty entry means the pointer has been deleted.
+    if content.is_empty() {
+        return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+    }
+    Ok(deserialise(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use utils::generate_random_string;
+    use utils::test_utils::random_client;
+
+    // Test attaching, resolving and removing services of a public name.
+    #[test]
+    fn services() {
+        let public_name = unwrap!(generate_random_string(10));
+
+        random_client(move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+
+            let www = unwrap!(MDataInfo::random_public(DIR_TAG));
+            let www2 = www.clone();
+
+            register(client, &public_name)
+                .then(move |res| {
+                    let services = unwrap!(res);
+                    assert_eq!(services.type_tag, SERVICES_TAG);
+                    set_service(&c2, &public_name, "www", &www)
+                        .map(move |_| public_name)
+                })
+                .then(move |res| {
+                    let public_name = unwrap!(res);
+                    let path = format!("{}/", public_name);
+                    resolve_path(&c3, &path).map(move |info| (public_name, info))
+                })
+                .then(move |res| {
+                    let (public_name, info) = unwrap!(res);
+                    assert_eq!(info.name, www2.name);
+                    assert_eq!(info.type_tag, DIR_TAG);
+                    list_services(&c4, &public_name).map(move |names| (public_name, names))
+                })
+                .then(move |res| {
+                    let (public_name, names) = unwrap!(res);
+                    assert_eq!(names, vec!["www".to_owned()]);
+                    remove_service(&c5, &public_name, "www").map(move |_| public_name)
+                })
+                .then(move |res| {
+                    let public_name = unwrap!(res);
+                    resolve(&c6, &public_name, "www").then(move |res| {
+                        match res {
+                            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => (),
+                            res => panic!("Unexpected {:?}", res),
+                        }
+                        list_services(&c7, &public_name)
+                    })
+                })
+                .map(|names| assert!(names.is_empty()))
+        });
+    }
+
+    // Test that private directories can't be attached as services.
+    #[test]
+    fn private_service() {
+        let public_name = unwrap!(generate_random_string(10));
+
+        random_client(move |client| {
+            let c2 = client.clone();
+            let private = unwrap!(MDataInfo::random_private(DIR_TAG));
+
+            register(client, &public_name)
+                .and_then(move |_| set_service(&c2, &public_name, "www", &private))
+                .then(|res| -> Result<_, CoreError> {
+                    match res {
+                        Err(CoreError::OperationForbidden) => (),
+                        res => panic!("Unexpected {:?}", res),
+                    }
+                    Ok(())
+                })
+        });
+    }
+
+    // Test parsing of public name paths.
+    #[test]
+    fn parse() {
+        assert_eq!(unwrap!(parse_path("name")), ("name", DEFAULT_SERVICE));
+        assert_eq!(unwrap!(parse_path("name/blog")), ("name", "blog"));
+        assert_eq!(unwrap!(parse_path("/name/blog/")), ("name", "blog"));
+        assert!(parse_path("").is_err());
+        assert!(parse_path("/blog").is_err());
+        assert!(parse_path("name/blog/post").is_err());
+    }
+}
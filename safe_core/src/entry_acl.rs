@@ -0,0 +1,321 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Access control for individual `MutableData` entries.
+//!
+//! Network permissions apply to a `MutableData` as a whole, which is too coarse when several
+//! apps share a container but each should only touch its own part of it. By convention, the
+//! entry under `ACL_ENTRY_KEY` holds an `EntryAcl` mapping entry-key prefixes to the
+//! permissions of individual app keys. Entries are governed by the rule with the longest
+//! matching prefix (the ACL entry itself included); entries no rule matches are only subject
+//! to the network permissions.
+//!
+//! The network knows nothing about the convention, so it's enforced by the clients, which
+//! write through `mutate_entries` rather than mutating the entries directly.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{Action, ClientError, EntryAction, PermissionSet, Value};
+use rust_sodium::crypto::sign;
+use std::collections::BTreeMap;
+use utils::FutureExt;
+
+/// Key of the entry holding the `EntryAcl` of a `MutableData`, before encryption.
+pub const ACL_ENTRY_KEY: &[u8] = b"_acl";
+
+/// Permissions of app keys over entries, by entry-key prefix.
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize, Deserialize)]
+pub struct EntryAcl {
+    rules: BTreeMap<Vec<u8>, BTreeMap<sign::PublicKey, PermissionSet>>,
+}
+
+impl EntryAcl {
+    /// Creates an ACL without any rules.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rules of the ACL, by prefix.
+    pub fn rules(&self) -> &BTreeMap<Vec<u8>, BTreeMap<sign::PublicKey, PermissionSet>> {
+        &self.rules
+    }
+
+    /// Sets the permissions of `key` over the entries starting with `prefix`. Keys not
+    /// listed for a prefix aren't allowed to modify its entries at all.
+    pub fn set(&mut self, prefix: Vec<u8>, key: sign::PublicKey, permissions: PermissionSet) {
+        let _ = self.rules.entry(prefix).or_insert_with(BTreeMap::new).insert(
+            key,
+            permissions,
+        );
+    }
+
+    /// Removes the permissions of `key` over the entries starting with `prefix`, dropping the
+    /// rule once no key is left in it.
+    pub fn remove(&mut self, prefix: &[u8], key: &sign::PublicKey) -> Option<PermissionSet> {
+        let (permissions, empty) = match self.rules.get_mut(prefix) {
+            Some(rule) => (rule.remove(key), rule.is_empty()),
+            None => return None,
+        };
+        if empty {
+            let _ = self.rules.remove(prefix);
+        }
+        permissions
+    }
+
+    /// The rule governing the entry under `entry_key`, along with its prefix.
+    pub fn rule(
+        &self,
+        entry_key: &[u8],
+    ) -> Option<(&[u8], &BTreeMap<sign::PublicKey, PermissionSet>)> {
+        self.rules
+            .iter()
+            .filter(|&(prefix, _)| entry_key.starts_with(prefix))
+            .max_by_key(|&(prefix, _)| prefix.len())
+            .map(|(prefix, rule)| (&prefix[..], rule))
+    }
+
+    /// Whether `key` may perform `action` on the entry under `entry_key`.
+    pub fn is_allowed(&self, entry_key: &[u8], key: &sign::PublicKey, action: Action) -> bool {
+        match self.rule(entry_key) {
+            Some((_, rule)) => {
+                rule.get(key).and_then(|permissions| permissions.is_allowed(action)) ==
+                    Some(true)
+            }
+            None => true,
+        }
+    }
+
+    /// Checks that `key` may perform all of `actions`, whose entry keys are in plain text.
+    /// Fails with `EntryAccessDenied` naming the first entry it may not modify.
+    pub fn check(
+        &self,
+        key: &sign::PublicKey,
+        actions: &BTreeMap<Vec<u8>, EntryAction>,
+    ) -> Result<(), CoreError> {
+        for (entry_key, action) in actions {
+            if !self.is_allowed(entry_key, key, action_kind(action)) {
+                return Err(CoreError::EntryAccessDenied(entry_key.clone()));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Fetches the ACL of the data described by `info`. Data without one has an empty ACL.
+pub fn fetch<T: 'static>(client: &Client<T>, info: &MDataInfo) -> Box<CoreFuture<EntryAcl>> {
+    fetch_value(client, info).map(|(_, acl)| acl).into_box()
+}
+
+/// Stores `acl` as the ACL of the data described by `info`. Replacing an existing ACL is
+/// subject to the rule governing `ACL_ENTRY_KEY`.
+pub fn store<T: 'static>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    acl: &EntryAcl,
+) -> Box<CoreFuture<()>> {
+    let client2 = client.clone();
+    let info2 = info.clone();
+    let key = fry!(info.enc_entry_key(ACL_ENTRY_KEY));
+    let content = fry!(serialise(acl).map_err(CoreError::from).and_then(|encoded| {
+        info.enc_entry_value(&encoded)
+    }));
+
+    fetch_value(client, info)
+        .and_then(move |(version, _)| {
+            let action = match version {
+                Some(version) => {
+                    EntryAction::Update(Value {
+                        content,
+                        entry_version: version + 1,
+                    })
+                }
+                None => {
+                    EntryAction::Ins(Value {
+                        content,
+                        entry_version: 0,
+                    })
+                }
+            };
+            mutate_entries(&client2, &info2, btree_map![key => action])
+        })
+        .into_box()
+}
+
+/// Applies `actions` to the data described by `info`, after checking them against its ACL
+/// on behalf of the client's signing key. The actions are given as they're sent to the
+/// network, i.e. with encrypted keys and values for private data.
+pub fn mutate_entries<T: 'static>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    actions: BTreeMap<Vec<u8>, EntryAction>,
+) -> Box<CoreFuture<()>> {
+    let client2 = client.clone();
+    let info2 = info.clone();
+    let sign_pk = fry!(client.public_signing_key());
+
+    fetch(client, info)
+        .and_then(move |acl| {
+            let plain_actions = actions
+                .iter()
+                .map(|(key, action)| Ok((info2.decrypt(key)?, action.clone())))
+                .collect::<Result<_, CoreError>>()?;
+            acl.check(&sign_pk, &plain_actions)?;
+            Ok(actions)
+        })
+        .and_then(move |actions| {
+            client2.mutate_mdata_entries(info2.name, info2.type_tag, actions)
+        })
+        .into_box()
+}
+
+// Fetches the ACL along with the version of its entry, `None` if there's no such entry.
+fn fetch_value<T: 'static>(
+    client: &Client<T>,
+    info: &MDataInfo,
+) -> Box<CoreFuture<(Option<u64>, EntryAcl)>> {
+    let info = info.clone();
+    let key = fry!(info.enc_entry_key(ACL_ENTRY_KEY));
+
+    client
+        .get_mdata_value(info.name, info.type_tag, key)
+        .then(move |res| match res {
+            Ok(ref value) if value.content.is_empty() => {
+                Ok((Some(value.entry_version), EntryAcl::new()))
+            }
+            Ok(value) => {
+                let acl = deserialise(&info.decrypt(&value.content)?)?;
+                Ok((Some(value.entry_version), acl))
+            }
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                Ok((None, EntryAcl::new()))
+            }
+            Err(err) => Err(err),
+        })
+        .into_box()
+}
+
+fn action_kind(action: &EntryAction) -> Action {
+    match *action {
+        EntryAction::Ins(_) => Action::Insert,
+        EntryAction::Update(_) => Action::Update,
+        EntryAction::Del(_) => Action::Delete,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::MutableData;
+    use utils::test_utils::random_client;
+
+    // The rule with the longest matching prefix governs an entry.
+    #[test]
+    fn longest_prefix_rule() {
+        let (app, _) = sign::gen_keypair();
+        let (other, _) = sign::gen_keypair();
+
+        let mut acl = EntryAcl::new();
+        acl.set(b"photos/".to_vec(), app, PermissionSet::new().allow(Action::Insert));
+        acl.set(b"photos/shared/".to_vec(), other, PermissionSet::new().allow(Action::Insert));
+
+        assert!(acl.is_allowed(b"notes", &other, Action::Delete));
+        assert!(acl.is_allowed(b"photos/a.jpg", &app, Action::Insert));
+        assert!(!acl.is_allowed(b"photos/a.jpg", &app, Action::Update));
+        assert!(!acl.is_allowed(b"photos/a.jpg", &other, Action::Insert));
+        assert!(acl.is_allowed(b"photos/shared/b.jpg", &other, Action::Insert));
+        assert!(!acl.is_allowed(b"photos/shared/b.jpg", &app, Action::Insert));
+
+        let (prefix, _) = unwrap!(acl.rule(b"photos/shared/b.jpg"));
+        assert_eq!(prefix, b"photos/shared/");
+
+        assert!(acl.remove(b"photos/shared/", &other).is_some());
+        assert!(acl.rule(b"photos/shared/b.jpg").is_some());
+        assert!(acl.is_allowed(b"photos/shared/b.jpg", &app, Action::Insert));
+    }
+
+    // Writes through `mutate_entries` are checked against the stored ACL, for private data
+    // too.
+    #[test]
+    fn enforced_on_write() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+
+            let info = unwrap!(MDataInfo::random_private(15_001));
+            let info2 = info.clone();
+            let info3 = info.clone();
+            let info4 = info.clone();
+            let info5 = info.clone();
+
+            let (other, _) = sign::gen_keypair();
+            let mut acl = EntryAcl::new();
+            acl.set(b"private/".to_vec(), other, PermissionSet::new().allow(Action::Insert));
+
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                info.name,
+                info.type_tag,
+                Default::default(),
+                Default::default(),
+                owners,
+            ));
+
+            client
+                .put_mdata(data)
+                .then(move |res| {
+                    unwrap!(res);
+                    store(&client2, &info2, &acl)
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    let key = unwrap!(info3.enc_entry_key(b"private/entry"));
+                    let value = Value {
+                        content: unwrap!(info3.enc_entry_value(b"value")),
+                        entry_version: 0,
+                    };
+                    mutate_entries(&client3, &info3, btree_map![key => EntryAction::Ins(value)])
+                })
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::EntryAccessDenied(ref key)) if key == b"private/entry" => (),
+                        x => panic!("Unexpected {:?}", x),
+                    }
+
+                    let key = unwrap!(info4.enc_entry_key(b"public/entry"));
+                    let value = Value {
+                        content: unwrap!(info4.enc_entry_value(b"value")),
+                        entry_version: 0,
+                    };
+                    mutate_entries(&client4, &info4, btree_map![key => EntryAction::Ins(value)])
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    fetch(&client5, &info5)
+                })
+                .then(|res| -> Result<_, CoreError> {
+                    let acl = unwrap!(res);
+                    assert!(acl.rule(b"private/entry").is_some());
+                    Ok(())
+                })
+        })
+    }
+}
@@ -73,6 +73,19 @@ pub enum CoreError {
     ConfigError(config_file_handler::Error),
     /// Io error.
     IoError(io::Error),
+    /// A `MutableData` entry is larger than the network allows.
+    EntryTooLarge,
+    /// File user metadata is larger than allowed.
+    UserMetadataTooLarge,
+    /// Stored data has a format version newer than this client understands.
+    UnsupportedFormatVersion(u64),
+    /// An owner-level request lacks valid signatures from some of the data owners.
+    MissingOwnerSignatures,
+    /// The entry-level ACL of a `MutableData` doesn't allow modifying the entry under the
+    /// given (plain text) key.
+    EntryAccessDenied(Vec<u8>),
+    /// A notification is larger than `topic::MAX_NOTIFICATION_LEN`.
+    NotificationTooLarge,
 }
 
 impl<'a> From<&'a str> for CoreError {
@@ -200,6 +213,18 @@ impl Debug for CoreError {
                 write!(formatter, "CoreError::ConfigError -> {:?}", error)
             }
             CoreError::IoError(ref error) => write!(formatter, "CoreError::IoError -> {:?}", error),
+            CoreError::EntryTooLarge => write!(formatter, "CoreError::EntryTooLarge"),
+            CoreError::UserMetadataTooLarge => write!(formatter, "CoreError::UserMetadataTooLarge"),
+            CoreError::UnsupportedFormatVersion(version) => {
+                write!(formatter, "CoreError::UnsupportedFormatVersion({})", version)
+            }
+            CoreError::MissingOwnerSignatures => {
+                write!(formatter, "CoreError::MissingOwnerSignatures")
+            }
+            CoreError::EntryAccessDenied(ref key) => {
+                write!(formatter, "CoreError::EntryAccessDenied -> {:?}", key)
+            }
+            CoreError::NotificationTooLarge => write!(formatter, "CoreError::NotificationTooLarge"),
         }
     }
 }
@@ -267,6 +292,26 @@ impl Display for CoreError {
             CoreError::RequestTimeout => write!(formatter, "CoreError::RequestTimeout"),
             CoreError::ConfigError(ref error) => write!(formatter, "Config file error: {}", error),
             CoreError::IoError(ref error) => write!(formatter, "Io error: {}", error),
+            CoreError::EntryTooLarge => write!(formatter, "MutableData entry is too large"),
+            CoreError::UserMetadataTooLarge => write!(formatter, "User metadata is too large"),
+            CoreError::UnsupportedFormatVersion(version) => {
+                write!(
+                    formatter,
+                    "Data format version {} is not supported by this client",
+                    version
+                )
+            }
+            CoreError::MissingOwnerSignatures => {
+                write!(formatter, "Request is not signed by all the owners")
+            }
+            CoreError::EntryAccessDenied(ref key) => {
+                write!(
+                    formatter,
+                    "Entry \"{}\" may not be modified under its access control list",
+                    String::from_utf8_lossy(key)
+                )
+            }
+            CoreError::NotificationTooLarge => write!(formatter, "Notification is too large"),
         }
     }
 }
@@ -297,6 +342,12 @@ impl Error for CoreError {
             CoreError::RequestTimeout => "Request has timed out",
             CoreError::ConfigError(ref error) => error.description(),
             CoreError::IoError(ref error) => error.description(),
+            CoreError::EntryTooLarge => "Entry too large",
+            CoreError::UserMetadataTooLarge => "User metadata too large",
+            CoreError::UnsupportedFormatVersion(_) => "Unsupported data format version",
+            CoreError::MissingOwnerSignatures => "Missing owner signatures",
+            CoreError::EntryAccessDenied(_) => "Entry access denied",
+            CoreError::NotificationTooLarge => "Notification too large",
         }
     }
 
@@ -59,6 +59,28 @@ pub enum CoreEvent {
     RateLimitExceeded,
 }
 
+impl CoreEvent {
+    /// Returns `true` if the event carries an error or the request has to be retried.
+    pub fn is_err(&self) -> bool {
+        match *self {
+            CoreEvent::GetAccountInfo(ref res) => res.is_err(),
+            CoreEvent::Mutation(ref res) => res.is_err(),
+            CoreEvent::GetIData(ref res) => res.is_err(),
+            CoreEvent::GetMDataVersion(ref res) => res.is_err(),
+            CoreEvent::ListMDataEntries(ref res) => res.is_err(),
+            CoreEvent::ListMDataKeys(ref res) => res.is_err(),
+            CoreEvent::ListMDataValues(ref res) => res.is_err(),
+            CoreEvent::GetMDataValue(ref res) => res.is_err(),
+            CoreEvent::ListMDataPermissions(ref res) => res.is_err(),
+            CoreEvent::ListMDataUserPermissions(ref res) => res.is_err(),
+            CoreEvent::ListAuthKeysAndVersion(ref res) => res.is_err(),
+            CoreEvent::GetMDataShell(ref res) => res.is_err(),
+            CoreEvent::GetMData(ref res) => res.is_err(),
+            CoreEvent::RateLimitExceeded => true,
+        }
+    }
+}
+
 /// Netowork Events that Client Modules need to deal with
 #[derive(Debug)]
 pub enum NetworkEvent {
@@ -17,10 +17,20 @@
 
 use client::Client;
 use errors::CoreError;
-use futures::Future;
+use futures::{Future, IntoFuture};
+use futures::future;
 use futures::stream::Stream;
 use futures::sync::mpsc;
-use tokio_core::reactor::Core;
+use futures::unsync::oneshot;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use std::sync::mpsc::Sender;
+use std::time::Duration;
+use tokio_core::reactor::{Core, Handle, Timeout};
+
+/// How long operations whose network requests have been aborted by a shutdown
+/// are given to report the failure, in milliseconds.
+const SHUTDOWN_ABORT_GRACE_MS: u64 = 100;
 
 /// Transmitter of messages to be run in the core event loop.
 pub type CoreMsgTx<T> = mpsc::UnboundedSender<CoreMsg<T>>;
@@ -32,7 +42,13 @@ pub type TailFuture = Box<Future<Item = (), Error = ()>>;
 type TailFutureFn<T> = FnMut(&Client<T>, &T) -> Option<TailFuture> + Send + 'static;
 
 /// The message format that core event loop understands.
-pub struct CoreMsg<T>(Option<Box<TailFutureFn<T>>>);
+pub struct CoreMsg<T>(CoreMsgKind<T>);
+
+enum CoreMsgKind<T> {
+    Run(Box<TailFutureFn<T>>),
+    Terminate,
+    Shutdown(Duration, Sender<usize>),
+}
 
 /// Future trait returned from core operations.
 pub type CoreFuture<T> = Future<Item = T, Error = CoreError>;
@@ -46,7 +62,7 @@ impl<T> CoreMsg<T> {
         F: FnOnce(&Client<T>, &T) -> Option<TailFuture> + Send + 'static,
     {
         let mut f = Some(f);
-        CoreMsg(Some(
+        CoreMsg(CoreMsgKind::Run(
             Box::new(move |client, context| -> Option<TailFuture> {
                 let f = unwrap!(f.take());
                 f(client, context)
@@ -57,27 +73,110 @@ impl<T> CoreMsg<T> {
     /// Construct a new message which when processed by the event loop will
     /// terminate the event loop. This will be the graceful exit condition.
     pub fn build_terminator() -> Self {
-        CoreMsg(None)
+        CoreMsg(CoreMsgKind::Terminate)
+    }
+
+    /// Construct a new message which when processed by the event loop will
+    /// stop it from accepting further messages, wait up to `timeout` for the
+    /// futures registered so far to finish and then terminate the event loop.
+    /// Network requests still in flight after `timeout` are aborted, failing
+    /// with `OperationAborted`, so the futures waiting on them get to finish.
+    /// The number of futures abandoned because they didn't finish even so is
+    /// sent to `done`.
+    pub fn build_shutdown(timeout: Duration, done: Sender<usize>) -> Self {
+        CoreMsg(CoreMsgKind::Shutdown(timeout, done))
     }
 }
 
 /// Run the core event loop. This will block until the event loop is alive.
 /// Hence must typically be called inside a spawned thread.
-pub fn run<T>(mut el: Core, client: &Client<T>, context: &T, el_rx: CoreMsgRx<T>) {
+pub fn run<T: 'static>(mut el: Core, client: &Client<T>, context: &T, el_rx: CoreMsgRx<T>) {
     let el_h = el.handle();
+    let pending = Rc::new(Pending::default());
+    let mut shutdown = None;
 
-    let keep_alive = el_rx.for_each(|core_msg| {
-        if let Some(mut f) = core_msg.0 {
-            if let Some(tail) = f(client, context) {
-                el_h.spawn(tail);
+    {
+        let keep_alive = el_rx.for_each(|core_msg| match core_msg.0 {
+            CoreMsgKind::Run(mut f) => {
+                if let Some(tail) = f(client, context) {
+                    let pending = Rc::clone(&pending);
+                    pending.started();
+                    el_h.spawn(tail.then(move |res| {
+                        pending.finished();
+                        res
+                    }));
+                }
+                Ok(())
+            }
+            CoreMsgKind::Terminate => Err(()),
+            CoreMsgKind::Shutdown(timeout, done) => {
+                shutdown = Some((timeout, done));
+                Err(())
             }
-            Ok(())
-        } else {
-            // Err(io::Error::new(ErrorKind::Other, "Graceful Termination"))
-            Err(())
+        });
+
+        let _ = el.run(keep_alive);
+    }
+
+    if let Some((timeout, done)) = shutdown {
+        debug!("Shutting down Core Event Loop with {} pending operations", pending.get());
+
+        let _ = el.run(settle(&el_h, &pending, timeout));
+
+        if pending.get() > 0 {
+            debug!("Aborting requests of {} pending operations", pending.get());
+            client.suspend();
+            let grace = Duration::from_millis(SHUTDOWN_ABORT_GRACE_MS);
+            let _ = el.run(settle(&el_h, &pending, grace));
         }
-    });
 
-    let _ = el.run(keep_alive);
+        let _ = done.send(pending.get());
+    }
+
     debug!("Exiting Core Event Loop");
 }
+
+// Futures registered with the event loop which haven't finished yet.
+#[derive(Default)]
+struct Pending {
+    count: Cell<usize>,
+    // Notified once `count` drops to zero.
+    idle_waiters: RefCell<Vec<oneshot::Sender<()>>>,
+}
+
+impl Pending {
+    fn get(&self) -> usize {
+        self.count.get()
+    }
+
+    fn started(&self) {
+        self.count.set(self.count.get() + 1);
+    }
+
+    fn finished(&self) {
+        self.count.set(self.count.get() - 1);
+        if self.count.get() == 0 {
+            for tx in self.idle_waiters.borrow_mut().drain(..) {
+                let _ = tx.send(());
+            }
+        }
+    }
+}
+
+// Resolves once there are no pending futures left or `timeout` has elapsed.
+fn settle(el_h: &Handle, pending: &Pending, timeout: Duration) -> TailFuture {
+    if pending.get() == 0 {
+        return Box::new(future::ok(()));
+    }
+
+    let (tx, rx) = oneshot::channel();
+    pending.idle_waiters.borrow_mut().push(tx);
+
+    let idle = rx.map_err(|_| ());
+    let expired = Timeout::new(timeout, el_h)
+        .into_future()
+        .flatten()
+        .then(|_| Ok::<_, ()>(()));
+
+    Box::new(idle.select(expired).then(|_| Ok(())))
+}
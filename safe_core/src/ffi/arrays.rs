@@ -34,6 +34,8 @@ pub type SymNonce = [u8; secretbox::NONCEBYTES];
 pub type SignPublicKey = [u8; sign::PUBLICKEYBYTES];
 /// Array containing sign private key bytes.
 pub type SignSecretKey = [u8; sign::SECRETKEYBYTES];
+/// Array containing detached signature bytes.
+pub type Signature = [u8; sign::SIGNATUREBYTES];
 
 /// Array containing `XorName` bytes.
 pub type XorNameArray = [u8; XOR_NAME_LEN];
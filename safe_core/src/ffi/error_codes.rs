@@ -0,0 +1,363 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Registry of the error codes reported through FFI by all the crates in this
+//! workspace. Codes are grouped into non-overlapping ranges by origin and never
+//! change meaning once released:
+//!
+//! - `-1` to `-99`: `CoreError`
+//! - `-100` to `-199`: routing `ClientError`
+//! - `-200` to `-299`: `IpcError`
+//! - `-300` to `-399`: `NfsError`
+//! - `-1000` to `-1099`: `AppError`
+//! - `-1100` to `-1199`: `AuthError`
+//! - `-2000` and below: failures not specific to any crate
+
+use errors::CoreError;
+use ipc::IpcError;
+use nfs::NfsError;
+use routing::ClientError;
+use std::os::raw::c_char;
+
+macro_rules! error_codes {
+    ($($(#[$attr:meta])* $name:ident = $code:expr => $desc:expr,)*) => {
+        $(
+            $(#[$attr])*
+            pub const $name: i32 = $code;
+        )*
+
+        // All registered codes along with their nul-terminated descriptions.
+        const REGISTRY: &'static [(i32, &'static str)] = &[
+            $(($code, concat!($desc, "\0")),)*
+        ];
+    }
+}
+
+error_codes! {
+    // Core errors
+    /// Serialisation error.
+    ERR_ENCODE_DECODE_ERROR = -1 => "Serialisation error",
+    /// Asymmetric decryption failed.
+    ERR_ASYMMETRIC_DECIPHER_FAILURE = -2 => "Asymmetric decryption failed",
+    /// Symmetric decryption failed.
+    ERR_SYMMETRIC_DECIPHER_FAILURE = -3 => "Symmetric decryption failed",
+    /// Received unexpected data.
+    ERR_RECEIVED_UNEXPECTED_DATA = -4 => "Received unexpected data",
+    /// Received unexpected event.
+    ERR_RECEIVED_UNEXPECTED_EVENT = -5 => "Received unexpected event",
+    /// Version cache miss.
+    ERR_VERSION_CACHE_MISS = -6 => "Version cache miss",
+    /// Root directory already exists.
+    ERR_ROOT_DIRECTORY_EXISTS = -7 => "Root directory already exists",
+    /// Random data generation failed.
+    ERR_RANDOM_DATA_GENERATION_FAILURE = -8 => "Random data generation failed",
+    /// Forbidden operation.
+    ERR_OPERATION_FORBIDDEN = -9 => "Forbidden operation",
+    /// Routing error.
+    ERR_ROUTING_ERROR = -10 => "Routing error",
+    /// Routing interface error.
+    ERR_ROUTING_INTERFACE_ERROR = -11 => "Routing interface error",
+    /// Unsupported salt size for password hashing.
+    ERR_UNSUPPORTED_SALT_SIZE_FOR_PW_HASH = -12 => "Unsupported salt size for password hashing",
+    /// Password hashing failed.
+    ERR_UNSUCCESSFUL_PW_HASH = -13 => "Password hashing failed",
+    /// Operation aborted.
+    ERR_OPERATION_ABORTED = -14 => "Operation aborted",
+    /// MPID messaging error.
+    ERR_MPID_MESSAGING_ERROR = -15 => "MPID messaging error",
+    /// Self-encryption error.
+    ERR_SELF_ENCRYPTION = -16 => "Self-encryption error",
+    /// Request timed out.
+    ERR_REQUEST_TIMEOUT = -17 => "Request timed out",
+    /// Config file error.
+    ERR_CONFIG_FILE = -18 => "Config file error",
+    /// I/O error in safe_core.
+    ERR_IO = -19 => "I/O error",
+    /// `MutableData` entry too large.
+    ERR_ENTRY_TOO_LARGE = -20 => "Entry too large",
+    /// File user metadata too large.
+    ERR_USER_METADATA_TOO_LARGE = -21 => "User metadata too large",
+    /// Data format version newer than supported.
+    ERR_UNSUPPORTED_FORMAT_VERSION = -22 => "Unsupported data format version",
+    /// Owner-level request not signed by all the owners.
+    ERR_MISSING_OWNER_SIGNATURES = -23 => "Missing owner signatures",
+    /// Entry modification not allowed by the entry-level ACL.
+    ERR_ENTRY_ACCESS_DENIED = -24 => "Entry access denied",
+    /// Notification larger than the maximum size.
+    ERR_NOTIFICATION_TOO_LARGE = -25 => "Notification too large",
+
+    // routing Client errors
+    /// Access denied.
+    ERR_ACCESS_DENIED = -100 => "Access denied",
+    /// No such account.
+    ERR_NO_SUCH_ACCOUNT = -101 => "No such account",
+    /// Account already exists.
+    ERR_ACCOUNT_EXISTS = -102 => "Account already exists",
+    /// No such data.
+    ERR_NO_SUCH_DATA = -103 => "No such data",
+    /// Data already exists.
+    ERR_DATA_EXISTS = -104 => "Data already exists",
+    /// Data too large.
+    ERR_DATA_TOO_LARGE = -105 => "Data too large",
+    /// No such entry.
+    ERR_NO_SUCH_ENTRY = -106 => "No such entry",
+    /// Invalid entry actions.
+    ERR_INVALID_ENTRY_ACTIONS = -107 => "Invalid entry actions",
+    /// Too many entries.
+    ERR_TOO_MANY_ENTRIES = -108 => "Too many entries",
+    /// No such key.
+    ERR_NO_SUCH_KEY = -109 => "No such key",
+    /// Invalid owners.
+    ERR_INVALID_OWNERS = -110 => "Invalid owners",
+    /// Invalid successor version.
+    ERR_INVALID_SUCCESSOR = -111 => "Invalid successor version",
+    /// Invalid operation.
+    ERR_INVALID_OPERATION = -112 => "Invalid operation",
+    /// Insufficient balance.
+    ERR_LOW_BALANCE = -113 => "Insufficient balance",
+    /// Network is full.
+    ERR_NETWORK_FULL = -114 => "Network is full",
+    /// Other network error.
+    ERR_NETWORK_OTHER = -115 => "Network error",
+    /// Invalid invitation.
+    ERR_INVALID_INVITATION = -116 => "Invalid invitation",
+    /// Invitation already claimed.
+    ERR_INVITATION_ALREADY_CLAIMED = -117 => "Invitation already claimed",
+
+    // IPC errors
+    /// Authentication denied.
+    ERR_AUTH_DENIED = -200 => "Authentication denied",
+    /// Containers access denied.
+    ERR_CONTAINERS_DENIED = -201 => "Containers access denied",
+    /// Invalid IPC message.
+    ERR_INVALID_MSG = -202 => "Invalid IPC message",
+    /// App is already authorised.
+    ERR_ALREADY_AUTHORISED = -203 => "App is already authorised",
+    /// Unknown app.
+    ERR_UNKNOWN_APP = -204 => "Unknown app",
+    /// Invalid string.
+    ERR_STRING_ERROR = -205 => "Invalid string",
+    /// Sharing of mutable data denied.
+    ERR_SHARE_MDATA_DENIED = -206 => "Sharing of mutable data denied",
+    /// Invalid owner of mutable data.
+    ERR_INVALID_OWNER = -207 => "Invalid owner of mutable data",
+    /// Request denied.
+    ERR_REQUEST_DENIED = -208 => "Request denied",
+    /// Unsupported IPC protocol version.
+    ERR_UNSUPPORTED_VERSION = -209 => "Unsupported IPC protocol version",
+    /// Request expired or replayed.
+    ERR_REQUEST_EXPIRED = -210 => "Request expired or replayed",
+
+    // NFS errors
+    /// File already exists.
+    ERR_FILE_EXISTS = -300 => "File already exists",
+    /// File not found.
+    ERR_FILE_NOT_FOUND = -301 => "File not found",
+    /// Invalid byte range.
+    ERR_INVALID_RANGE = -302 => "Invalid byte range",
+
+    // App errors
+    /// Container not found.
+    ERR_NO_SUCH_CONTAINER = -1002 => "Container not found",
+    /// Invalid CipherOpt handle.
+    ERR_INVALID_CIPHER_OPT_HANDLE = -1003 => "Invalid CipherOpt handle",
+    /// Invalid encrypt public key handle.
+    ERR_INVALID_ENCRYPT_PUB_KEY_HANDLE = -1004 => "Invalid encrypt public key handle",
+    /// Invalid MDataInfo handle.
+    ERR_INVALID_MDATA_INFO_HANDLE = -1005 => "Invalid MDataInfo handle",
+    /// Invalid MutableData entries handle.
+    ERR_INVALID_MDATA_ENTRIES_HANDLE = -1006 => "Invalid MutableData entries handle",
+    /// Invalid MutableData entry actions handle.
+    ERR_INVALID_MDATA_ENTRY_ACTIONS_HANDLE = -1007 => "Invalid MutableData entry actions handle",
+    /// Invalid MutableData permissions handle.
+    ERR_INVALID_MDATA_PERMISSIONS_HANDLE = -1008 => "Invalid MutableData permissions handle",
+    /// Invalid MutableData permission set handle.
+    ERR_INVALID_MDATA_PERMISSION_SET_HANDLE = -1009 =>
+        "Invalid MutableData permission set handle",
+    /// Invalid Self Encryptor handle.
+    ERR_INVALID_SELF_ENCRYPTOR_HANDLE = -1010 => "Invalid Self Encryptor handle",
+    /// Invalid sign public key handle.
+    ERR_INVALID_SIGN_PUB_KEY_HANDLE = -1011 => "Invalid sign public key handle",
+    /// Invalid Self Encryptor read offsets.
+    ERR_INVALID_SELF_ENCRYPTOR_READ_OFFSETS = -1012 => "Invalid Self Encryptor read offsets",
+    /// I/O error.
+    ERR_IO_ERROR = -1013 => "I/O error",
+    /// Invalid encrypt secret key handle.
+    ERR_INVALID_ENCRYPT_SEC_KEY_HANDLE = -1014 => "Invalid encrypt secret key handle",
+    /// Invalid file context handle.
+    ERR_INVALID_FILE_CONTEXT_HANDLE = -1015 => "Invalid file context handle",
+    /// Invalid file mode.
+    ERR_INVALID_FILE_MODE = -1016 => "Invalid file mode",
+    /// Invalid sign secret key handle.
+    ERR_INVALID_SIGN_SEC_KEY_HANDLE = -1017 => "Invalid sign secret key handle",
+
+    // Authenticator errors
+    /// Failed to create the standard account containers.
+    ERR_ACCOUNT_CONTAINERS_CREATION = -1100 => "Failed to create the account containers",
+    /// No such pending request.
+    ERR_NO_SUCH_PENDING_REQUEST = -1101 => "No such pending request",
+    /// Credentials don't match the logged in account.
+    ERR_INVALID_CREDENTIALS = -1102 => "Invalid account credentials",
+    /// Device has been revoked.
+    ERR_DEVICE_REVOKED = -1103 => "Device revoked",
+    /// Device not found.
+    ERR_NO_SUCH_DEVICE = -1104 => "Device not found",
+    /// Too many failed login attempts.
+    ERR_LOGIN_DELAYED = -1105 => "Too many failed login attempts",
+
+    // Generic errors
+    /// Unexpected error, probably a logic error.
+    ERR_UNEXPECTED = -2000 => "Unexpected error",
+    /// Panic caught at the FFI boundary.
+    ERR_UNEXPECTED_PANIC = -2001 => "Unexpected panic",
+    /// Null pointer passed where a valid one was required.
+    ERR_NULL_POINTER = -2002 => "Null pointer",
+}
+
+/// Returns the description of the given error code, or `None` if the code isn't
+/// registered.
+pub fn error_code_description(code: i32) -> Option<&'static str> {
+    error_code_description_c(code).map(|desc| &desc[..desc.len() - 1])
+}
+
+/// Returns a pointer to the static, nul-terminated description of the given
+/// error code, or null if the code isn't registered.
+pub fn error_code_description_ptr(code: i32) -> *const c_char {
+    error_code_description_c(code).map_or(0 as *const c_char, |desc| {
+        desc.as_ptr() as *const c_char
+    })
+}
+
+fn error_code_description_c(code: i32) -> Option<&'static str> {
+    REGISTRY.iter().find(|&&(c, _)| c == code).map(|&(_, desc)| desc)
+}
+
+/// Returns the error code of the given `CoreError`.
+pub fn core_error_code(err: &CoreError) -> i32 {
+    match *err {
+        CoreError::EncodeDecodeError(_) => ERR_ENCODE_DECODE_ERROR,
+        CoreError::AsymmetricDecipherFailure => ERR_ASYMMETRIC_DECIPHER_FAILURE,
+        CoreError::SymmetricDecipherFailure => ERR_SYMMETRIC_DECIPHER_FAILURE,
+        CoreError::ReceivedUnexpectedData => ERR_RECEIVED_UNEXPECTED_DATA,
+        CoreError::ReceivedUnexpectedEvent => ERR_RECEIVED_UNEXPECTED_EVENT,
+        CoreError::VersionCacheMiss => ERR_VERSION_CACHE_MISS,
+        CoreError::RootDirectoryExists => ERR_ROOT_DIRECTORY_EXISTS,
+        CoreError::RandomDataGenerationFailure => ERR_RANDOM_DATA_GENERATION_FAILURE,
+        CoreError::OperationForbidden => ERR_OPERATION_FORBIDDEN,
+        CoreError::RoutingError(_) => ERR_ROUTING_ERROR,
+        CoreError::RoutingInterfaceError(_) => ERR_ROUTING_INTERFACE_ERROR,
+        CoreError::RoutingClientError(ref err) => client_error_code(err),
+        CoreError::UnsupportedSaltSizeForPwHash => ERR_UNSUPPORTED_SALT_SIZE_FOR_PW_HASH,
+        CoreError::UnsuccessfulPwHash => ERR_UNSUCCESSFUL_PW_HASH,
+        CoreError::OperationAborted => ERR_OPERATION_ABORTED,
+        CoreError::MpidMessagingError(_) => ERR_MPID_MESSAGING_ERROR,
+        CoreError::SelfEncryption(_) => ERR_SELF_ENCRYPTION,
+        CoreError::RequestTimeout => ERR_REQUEST_TIMEOUT,
+        CoreError::ConfigError(_) => ERR_CONFIG_FILE,
+        CoreError::IoError(_) => ERR_IO,
+        CoreError::EntryTooLarge => ERR_ENTRY_TOO_LARGE,
+        CoreError::UserMetadataTooLarge => ERR_USER_METADATA_TOO_LARGE,
+        CoreError::UnsupportedFormatVersion(_) => ERR_UNSUPPORTED_FORMAT_VERSION,
+        CoreError::MissingOwnerSignatures => ERR_MISSING_OWNER_SIGNATURES,
+        CoreError::EntryAccessDenied(_) => ERR_ENTRY_ACCESS_DENIED,
+        CoreError::NotificationTooLarge => ERR_NOTIFICATION_TOO_LARGE,
+        CoreError::Unexpected(_) => ERR_UNEXPECTED,
+    }
+}
+
+/// Returns the error code of the given routing `ClientError`.
+pub fn client_error_code(err: &ClientError) -> i32 {
+    match *err {
+        ClientError::AccessDenied => ERR_ACCESS_DENIED,
+        ClientError::NoSuchAccount => ERR_NO_SUCH_ACCOUNT,
+        ClientError::AccountExists => ERR_ACCOUNT_EXISTS,
+        ClientError::NoSuchData => ERR_NO_SUCH_DATA,
+        ClientError::DataExists => ERR_DATA_EXISTS,
+        ClientError::DataTooLarge => ERR_DATA_TOO_LARGE,
+        ClientError::NoSuchEntry => ERR_NO_SUCH_ENTRY,
+        ClientError::InvalidEntryActions(..) => ERR_INVALID_ENTRY_ACTIONS,
+        ClientError::TooManyEntries => ERR_TOO_MANY_ENTRIES,
+        ClientError::NoSuchKey => ERR_NO_SUCH_KEY,
+        ClientError::InvalidOwners => ERR_INVALID_OWNERS,
+        ClientError::InvalidSuccessor(..) => ERR_INVALID_SUCCESSOR,
+        ClientError::InvalidOperation => ERR_INVALID_OPERATION,
+        ClientError::LowBalance => ERR_LOW_BALANCE,
+        ClientError::NetworkFull => ERR_NETWORK_FULL,
+        ClientError::NetworkOther(_) => ERR_NETWORK_OTHER,
+        ClientError::InvalidInvitation => ERR_INVALID_INVITATION,
+        ClientError::InvitationAlreadyClaimed => ERR_INVITATION_ALREADY_CLAIMED,
+    }
+}
+
+/// Returns the error code of the given `IpcError`.
+pub fn ipc_error_code(err: &IpcError) -> i32 {
+    match *err {
+        IpcError::AuthDenied => ERR_AUTH_DENIED,
+        IpcError::ContainersDenied => ERR_CONTAINERS_DENIED,
+        IpcError::InvalidMsg => ERR_INVALID_MSG,
+        IpcError::EncodeDecodeError => ERR_ENCODE_DECODE_ERROR,
+        IpcError::AlreadyAuthorised => ERR_ALREADY_AUTHORISED,
+        IpcError::UnknownApp => ERR_UNKNOWN_APP,
+        IpcError::Unexpected(_) => ERR_UNEXPECTED,
+        IpcError::StringError(_) => ERR_STRING_ERROR,
+        IpcError::ShareMDataDenied => ERR_SHARE_MDATA_DENIED,
+        IpcError::InvalidOwner(..) => ERR_INVALID_OWNER,
+        IpcError::RequestDenied => ERR_REQUEST_DENIED,
+        IpcError::UnsupportedVersion { .. } => ERR_UNSUPPORTED_VERSION,
+        IpcError::RequestExpired => ERR_REQUEST_EXPIRED,
+    }
+}
+
+/// Returns the error code of the given `NfsError`.
+pub fn nfs_error_code(err: &NfsError) -> i32 {
+    match *err {
+        NfsError::CoreError(ref err) => core_error_code(err),
+        NfsError::FileExists => ERR_FILE_EXISTS,
+        NfsError::FileNotFound => ERR_FILE_NOT_FOUND,
+        NfsError::InvalidRange => ERR_INVALID_RANGE,
+        NfsError::EncodeDecodeError(_) => ERR_ENCODE_DECODE_ERROR,
+        NfsError::SelfEncryption(_) => ERR_SELF_ENCRYPTION,
+        NfsError::Unexpected(_) => ERR_UNEXPECTED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::ffi::CStr;
+
+    // Test that no two errors share a code and every code has a description.
+    #[test]
+    fn registry() {
+        let mut codes = HashSet::new();
+
+        for &(code, desc) in REGISTRY {
+            assert!(codes.insert(code), "Duplicate error code {}", code);
+            assert!(code < 0);
+            assert!(desc.len() > 1);
+            assert!(desc.ends_with('\0'));
+        }
+
+        assert_eq!(error_code_description(ERR_NULL_POINTER), Some("Null pointer"));
+        assert_eq!(error_code_description(0), None);
+
+        let desc = unsafe { CStr::from_ptr(error_code_description_ptr(ERR_FILE_NOT_FOUND)) };
+        assert_eq!(unwrap!(desc.to_str()), "File not found");
+        assert!(error_code_description_ptr(1).is_null());
+    }
+}
@@ -15,6 +15,7 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use ffi::arrays::XorNameArray;
 use ffi_utils::ReprC;
 use ffi_utils::callback::CallbackArgs;
 use ipc::req::permission_set_into_repr_c;
@@ -124,7 +125,7 @@ pub struct AppExchangeInfo {
     /// UTF-8 encoded id
     pub id: *const c_char,
 
-    /// Reserved by the frontend
+    /// UTF-8 encoded scope of the authorisation
     ///
     /// null if not present
     pub scope: *const c_char,
@@ -134,6 +135,33 @@ pub struct AppExchangeInfo {
 
     /// UTF-8 encoded application provider/vendor (e.g. MaidSafe)
     pub vendor: *const c_char,
+
+    /// UTF-8 encoded description of the application
+    ///
+    /// null if not present
+    pub description: *const c_char,
+
+    /// UTF-8 encoded URL of the application's homepage
+    ///
+    /// null if not present
+    pub homepage: *const c_char,
+
+    /// Pointer to the icon image data
+    ///
+    /// null if there's no icon or it's stored as `ImmutableData`
+    pub icon_ptr: *mut u8,
+
+    /// Size of the icon image data
+    pub icon_len: usize,
+
+    /// Capacity of the icon image data. Internal field required for the
+    /// Rust allocator.
+    pub icon_cap: usize,
+
+    /// Name of the `ImmutableData` holding the icon image data
+    ///
+    /// null if not present
+    pub icon_name: *const XorNameArray,
 }
 
 impl Drop for AppExchangeInfo {
@@ -146,6 +174,18 @@ impl Drop for AppExchangeInfo {
             }
             let _ = CString::from_raw(self.name as *mut _);
             let _ = CString::from_raw(self.vendor as *mut _);
+            if !self.description.is_null() {
+                let _ = CString::from_raw(self.description as *mut _);
+            }
+            if !self.homepage.is_null() {
+                let _ = CString::from_raw(self.homepage as *mut _);
+            }
+            if !self.icon_ptr.is_null() {
+                let _ = Vec::from_raw_parts(self.icon_ptr, self.icon_len, self.icon_cap);
+            }
+            if !self.icon_name.is_null() {
+                let _ = Box::from_raw(self.icon_name as *mut XorNameArray);
+            }
         }
     }
 }
@@ -204,3 +244,30 @@ pub struct ShareMData {
     /// The permissions being requested.
     pub perms: PermissionSet,
 }
+
+/// Represents a combined request for authorisation, container permissions and
+/// shared access to mutable data, to be granted or denied as a whole.
+#[repr(C)]
+pub struct CombinedReq {
+    /// Authorisation request, also carrying the requested container permissions
+    pub auth: AuthReq,
+    /// List of MD names & type tags and permissions that need to be shared
+    pub mdata: *const ShareMData,
+    /// Length of the mdata array
+    pub mdata_len: usize,
+    /// Capacity of the mdata vec - internal implementation detail
+    pub mdata_cap: usize,
+}
+
+impl Drop for CombinedReq {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Vec::from_raw_parts(
+                self.mdata as *mut ShareMData,
+                self.mdata_len,
+                self.mdata_cap,
+            );
+        }
+    }
+}
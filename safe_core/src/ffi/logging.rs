@@ -0,0 +1,156 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Forwarding of log records to a callback provided by the host application.
+
+use errors::CoreError;
+use ffi_utils::OpaqueCtx;
+use log::{self, Log, Metadata, Record};
+use std::ffi::CString;
+use std::os::raw::{c_char, c_void};
+use utils::logging::LogLevels;
+
+/// Callback receiving log records: user data, level (1 = error, 2 = warn,
+/// 3 = info, 4 = debug, 5 = trace), module path and the formatted message.
+/// The strings are only valid for the duration of the call.
+pub type LogCallback = extern "C" fn(user_data: *mut c_void,
+                                     level: u32,
+                                     module: *const c_char,
+                                     message: *const c_char);
+
+/// Installs a logger passing every record allowed by `levels` to `o_log`.
+/// The callback can be called from any thread.
+///
+/// Only one logger can be installed per process, so this fails if logging
+/// has already been initialised, e.g. to a file.
+pub fn init_callback_logger(
+    levels: LogLevels,
+    user_data: *mut c_void,
+    o_log: LogCallback,
+) -> Result<(), CoreError> {
+    let max_level = levels.max_level();
+    let logger = CallbackLogger {
+        levels,
+        user_data: OpaqueCtx(user_data),
+        o_log,
+    };
+
+    // The logger has to live for the rest of the program.
+    let logger: &'static CallbackLogger = unsafe { &*Box::into_raw(Box::new(logger)) };
+    log::set_logger(logger).map_err(|_| {
+        CoreError::Unexpected("Logging has already been initialised".to_owned())
+    })?;
+    log::set_max_level(max_level);
+
+    Ok(())
+}
+
+struct CallbackLogger {
+    levels: LogLevels,
+    user_data: OpaqueCtx,
+    o_log: LogCallback,
+}
+
+// The host guarantees the callback and its user data can be used from any thread.
+unsafe impl Sync for CallbackLogger {}
+
+impl Log for CallbackLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        metadata.level() <= self.levels.level_for(metadata.target())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+
+        let module = record.module_path().unwrap_or_else(|| record.target());
+        let module = CString::new(module.replace('\0', "")).unwrap_or_default();
+        let message = format!("{}", record.args());
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+
+        (self.o_log)(
+            self.user_data.0,
+            record.level() as u32,
+            module.as_ptr(),
+            message.as_ptr(),
+        );
+    }
+
+    fn flush(&self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use log::Level;
+    use std::ffi::CStr;
+    use std::str::FromStr;
+    use std::sync::Mutex;
+
+    // Test that records are filtered by level and passed to the callback.
+    #[test]
+    fn forwards_records() {
+        let lines: Mutex<Vec<(u32, String, String)>> = Mutex::new(Vec::new());
+        let lines_ptr: *const _ = &lines;
+        let logger = CallbackLogger {
+            levels: unwrap!(LogLevels::from_str("error,safe_core=info")),
+            user_data: OpaqueCtx(lines_ptr as *mut c_void),
+            o_log: log_cb,
+        };
+
+        let emit = |level, target: &str, msg: &str| {
+            logger.log(
+                &Record::builder()
+                    .level(level)
+                    .target(target)
+                    .module_path(Some(target))
+                    .args(format_args!("{}", msg))
+                    .build(),
+            )
+        };
+
+        emit(Level::Info, "safe_core::client", "connected");
+        emit(Level::Debug, "safe_core::client", "too verbose");
+        emit(Level::Warn, "routing", "also too verbose");
+        emit(Level::Error, "routing", "failed");
+
+        assert_eq!(
+            *unwrap!(lines.lock()),
+            vec![
+                (3, "safe_core::client".to_owned(), "connected".to_owned()),
+                (1, "routing".to_owned(), "failed".to_owned()),
+            ]
+        );
+
+        extern "C" fn log_cb(
+            user_data: *mut c_void,
+            level: u32,
+            module: *const c_char,
+            message: *const c_char,
+        ) {
+            unsafe {
+                let lines = user_data as *const Mutex<Vec<(u32, String, String)>>;
+                unwrap!((*lines).lock()).push((
+                    level,
+                    unwrap!(CStr::from_ptr(module).to_str()).to_owned(),
+                    unwrap!(CStr::from_ptr(message).to_str()).to_owned(),
+                ));
+            }
+        }
+    }
+}
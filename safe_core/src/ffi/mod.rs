@@ -19,16 +19,22 @@
 
 #![allow(unsafe_code)]
 
+/// Registry of FFI error codes.
+pub mod error_codes;
 /// IPC utilities.
 pub mod ipc;
+/// Logging to a host callback.
+pub mod logging;
 /// NFS API.
 pub mod nfs;
 /// Type definitions for arrays that are FFI input params.
 pub mod arrays;
 
 use self::arrays::*;
+use client::{NetworkMode, network_mode, set_network_mode};
 use errors::CoreError;
 use ffi_utils::ReprC;
+use std::os::raw::c_char;
 
 /// Represents the FFI-safe account info.
 #[repr(C)]
@@ -76,26 +82,75 @@ pub struct MDataInfo {
     pub new_enc_nonce: SymNonce,
 }
 
-/// Returns true if this crate was compiled against mock-routing.
+/// FFI-safe statistics of the traffic between a client and the network.
+#[repr(C)]
+pub struct NetworkStats {
+    /// Bytes sent to the network.
+    pub bytes_sent: u64,
+    /// Bytes received from the network.
+    pub bytes_received: u64,
+    /// Total number of requests sent.
+    pub requests_sent: u64,
+    /// Total number of requests which failed.
+    pub requests_failed: u64,
+    /// Smoothed round trip time in milliseconds, zero if no response has been received yet.
+    pub rtt_ms: u64,
+    /// Request counts by kind of request.
+    pub requests: *const RequestStats,
+    /// Number of elements in `requests`.
+    pub requests_len: usize,
+}
+
+/// FFI-safe number of requests of one kind.
+#[repr(C)]
+pub struct RequestStats {
+    /// Kind of the request, e.g. "GetIData".
+    pub kind: *const c_char,
+    /// Requests sent, including retries.
+    pub sent: u64,
+    /// Requests which failed.
+    pub failed: u64,
+}
+
+/// Returns true if this crate was compiled with the mock vault, so the clients can be switched
+/// to it with `set_mock_network`.
 #[no_mangle]
 pub extern "C" fn is_mock_build() -> bool {
-    cfg!(feature = "use-mock-routing")
+    cfg!(feature = "mock-vault")
+}
+
+/// Returns true if the clients connect to the mock vault rather than the live network.
+#[no_mangle]
+pub extern "C" fn is_mock_network() -> bool {
+    network_mode() == NetworkMode::Mock
+}
+
+/// Selects whether the clients created from now on connect to the mock vault or the live
+/// network. Returns false if the mock vault is requested but this crate was built without it.
+#[no_mangle]
+pub extern "C" fn set_mock_network(enabled: bool) -> bool {
+    let mode = if enabled {
+        NetworkMode::Mock
+    } else {
+        NetworkMode::Live
+    };
+    set_network_mode(mode).is_ok()
 }
 
 #[cfg(test)]
 mod tests {
     use ffi::is_mock_build;
 
-    // Test `is_mock_build` when compiled against mock-routing.
+    // Test `is_mock_build` when compiled with the mock vault.
     #[test]
-    #[cfg(feature = "use-mock-routing")]
+    #[cfg(feature = "mock-vault")]
     fn test_mock_build() {
         assert_eq!(is_mock_build(), true);
     }
 
-    // Test `is_mock_build` when not compiled against mock-routing.
+    // Test `is_mock_build` when not compiled with the mock vault.
     #[test]
-    #[cfg(not(feature = "use-mock-routing"))]
+    #[cfg(not(feature = "mock-vault"))]
     fn test_not_mock_build() {
         assert_eq!(is_mock_build(), false);
     }
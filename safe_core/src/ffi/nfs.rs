@@ -16,6 +16,8 @@
 // relating to use of the SAFE Network Software.
 
 use arrays::XorNameArray;
+use std::ffi::CString;
+use std::os::raw::c_char;
 
 /// FFI-wrapper for `File`.
 #[repr(C)]
@@ -52,3 +54,70 @@ impl Drop for File {
         };
     }
 }
+
+/// FFI-wrapper for `DirEntry`.
+#[repr(C)]
+pub struct DirEntry {
+    /// UTF-8 encoded name of the entry.
+    pub name: *const c_char,
+    /// Whether the entry is a sub-directory rather than a file.
+    pub is_dir: bool,
+    /// File size in bytes, 0 for sub-directories.
+    pub size: u64,
+    /// Creation time in milliseconds since the Unix epoch, 0 for sub-directories.
+    pub created_ms: i64,
+    /// Modification time in milliseconds since the Unix epoch, 0 for sub-directories.
+    pub modified_ms: i64,
+    /// Creation time in RFC 3339 format, null for sub-directories.
+    pub created: *const c_char,
+    /// Modification time in RFC 3339 format, null for sub-directories.
+    pub modified: *const c_char,
+    /// Pointer to the user metadata of the file.
+    pub user_metadata_ptr: *mut u8,
+    /// Size of the user metadata.
+    pub user_metadata_len: usize,
+    /// Capacity of the user metadata (internal field).
+    pub user_metadata_cap: usize,
+}
+
+impl Drop for DirEntry {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = CString::from_raw(self.name as *mut _);
+            if !self.created.is_null() {
+                let _ = CString::from_raw(self.created as *mut _);
+            }
+            if !self.modified.is_null() {
+                let _ = CString::from_raw(self.modified as *mut _);
+            }
+            let _ = Vec::from_raw_parts(
+                self.user_metadata_ptr,
+                self.user_metadata_len,
+                self.user_metadata_cap,
+            );
+        }
+    }
+}
+
+/// FFI-wrapper for `DirTreeNode`.
+#[repr(C)]
+pub struct DirTreeNode {
+    /// The file or sub-directory.
+    pub entry: DirEntry,
+    /// Pointer to the entries of the sub-directory.
+    pub children: *mut DirTreeNode,
+    /// Number of entries of the sub-directory.
+    pub children_len: usize,
+    /// Capacity of the entries (internal field).
+    pub children_cap: usize,
+}
+
+impl Drop for DirTreeNode {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        unsafe {
+            let _ = Vec::from_raw_parts(self.children, self.children_len, self.children_cap);
+        }
+    }
+}
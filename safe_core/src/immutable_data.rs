@@ -20,7 +20,8 @@ use crypto::shared_secretbox;
 use event_loop::CoreFuture;
 use futures::Future;
 use maidsafe_utilities::serialisation::{deserialise, serialise};
-use routing::{ImmutableData, XorName};
+use routing::{ImmutableData, XOR_NAME_LEN, XorName};
+use std::collections::BTreeSet;
 use self_encryption::{DataMap, SelfEncryptor};
 use self_encryption_storage::SelfEncryptionStorage;
 use utils::{self, FutureExt};
@@ -109,6 +110,63 @@ pub fn get_value<T: 'static>(
         .into_box()
 }
 
+/// Same as `get_value`, but also returns the names of all the `ImmutableData` the value is
+/// spread across, `name` included.
+pub fn get_value_with_chunks<T: 'static>(
+    client: &Client<T>,
+    name: &XorName,
+    decryption_key: Option<shared_secretbox::Key>,
+) -> Box<CoreFuture<(Vec<u8>, BTreeSet<XorName>)>> {
+    let client2 = client.clone();
+    let client3 = client.clone();
+
+    client
+        .get_idata(*name)
+        .and_then(move |data| {
+            let chunks = btree_set![*data.name()];
+            unpack_with_chunks(client2, &data, chunks)
+        })
+        .and_then(move |(value, mut chunks)| {
+            let data_map: DataMap = if let Some(key) = decryption_key {
+                let plain_text = utils::symmetric_decrypt(&value, &key)?;
+                deserialise(&plain_text)?
+            } else {
+                deserialise(&value)?
+            };
+            chunks.extend(chunk_names(&data_map));
+
+            let storage = SelfEncryptionStorage::new(client3);
+            Ok((SelfEncryptor::new(storage, data_map)?, chunks))
+        })
+        .and_then(|(self_encryptor, chunks)| {
+            let length = self_encryptor.len();
+            self_encryptor
+                .read(0, length)
+                .map(move |value| (value, chunks))
+                .map_err(From::from)
+        })
+        .into_box()
+}
+
+/// Names of the chunks the data map points to.
+pub fn chunk_names(data_map: &DataMap) -> Vec<XorName> {
+    match *data_map {
+        DataMap::Chunks(ref chunks) => {
+            chunks
+                .iter()
+                .filter(|chunk| chunk.hash.len() == XOR_NAME_LEN)
+                .map(|chunk| {
+                    let mut name = [0; XOR_NAME_LEN];
+                    name.copy_from_slice(&chunk.hash);
+                    XorName(name)
+                })
+                .collect()
+        }
+        DataMap::Content(_) |
+        DataMap::None => Vec::new(),
+    }
+}
+
 // TODO: consider rewriting these two function to not use recursion.
 
 fn pack<T: 'static>(client: Client<T>, value: Vec<u8>) -> Box<CoreFuture<ImmutableData>> {
@@ -151,10 +209,37 @@ fn unpack<T: 'static>(client: Client<T>, data: &ImmutableData) -> Box<CoreFuture
     }
 }
 
+// Same as `unpack`, but also collects the names of the chunks the packed layers are stored in.
+fn unpack_with_chunks<T: 'static>(
+    client: Client<T>,
+    data: &ImmutableData,
+    mut chunks: BTreeSet<XorName>,
+) -> Box<CoreFuture<(Vec<u8>, BTreeSet<XorName>)>> {
+    match fry!(deserialise(data.value())) {
+        DataTypeEncoding::Serialised(value) => ok!((value, chunks)),
+        DataTypeEncoding::DataMap(data_map) => {
+            chunks.extend(chunk_names(&data_map));
+
+            let storage = SelfEncryptionStorage::new(client.clone());
+            let self_encryptor = fry!(SelfEncryptor::new(storage, data_map));
+            let length = self_encryptor.len();
+            self_encryptor
+                .read(0, length)
+                .map_err(From::from)
+                .and_then(move |serialised_data| {
+                    let data = fry!(deserialise(&serialised_data));
+                    unpack_with_chunks(client, &data, chunks)
+                })
+                .into_box()
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use futures::Future;
+    use futures::future;
     use utils;
     use utils::test_utils::{finish, random_client};
 
@@ -183,6 +268,46 @@ mod tests {
         create_and_retrieve(10 * 1024 * 1024)
     }
 
+    // Test that all the chunks a value is spread across are reported, through the packed
+    // layers too.
+    #[test]
+    fn get_value_with_chunks_reports_chunks() {
+        let value = unwrap!(utils::generate_random_vector(2 * 1024 * 1024));
+        let key = shared_secretbox::gen_key();
+
+        random_client(move |client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let value2 = value.clone();
+
+            create(client, &value, Some(key.clone()))
+                .then(move |res| {
+                    let data = unwrap!(res);
+                    let name = *data.name();
+                    client2.put_idata(data).map(move |_| name)
+                })
+                .then(move |res| {
+                    let name = unwrap!(res);
+                    get_value_with_chunks(&client3, &name, Some(key))
+                        .map(move |(value, chunks)| (name, value, chunks))
+                })
+                .then(move |res| {
+                    let (name, value, chunks) = unwrap!(res);
+                    assert_eq!(value, value2);
+                    assert!(chunks.contains(&name));
+                    assert!(chunks.len() > 3);
+
+                    let fetches: Vec<_> = chunks
+                        .into_iter()
+                        .map(|chunk| client4.get_idata(chunk))
+                        .collect();
+                    future::join_all(fetches)
+                })
+                .map(|_| ())
+        })
+    }
+
     fn create_and_retrieve(size: usize) {
         let value = unwrap!(utils::generate_random_vector(size));
 
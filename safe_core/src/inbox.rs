@@ -0,0 +1,213 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Inboxes.
+//!
+//! The network has no appendable data anymore, so an inbox is a `MutableData` (with the
+//! `INBOX_TAG` type tag) into which anyone may insert but only the owner may delete. Every
+//! message is a separate entry, stored under a random key and encrypted with a sealed box
+//! for the recipient's public encryption key, so only the recipient can read it.
+//!
+//! There is no migration from appendable data: routing no longer defines those types, so
+//! existing `PubAppendableData` or `PrivAppendableData` can't be fetched or decoded by this
+//! crate. Apps holding such data have to re-send its items to a new inbox using an older
+//! client.
+
+use client::{Client, KeyOp, MDataInfo};
+use crypto::shared_box;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use ipc::now_secs;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{Action, ClientError, EntryActions, MutableData, PermissionSet, User};
+use rust_sodium::crypto::{box_, sealedbox};
+use utils::{self, FutureExt};
+use INBOX_TAG;
+
+/// Length of the keys messages are stored under.
+pub const MESSAGE_ID_LEN: usize = 32;
+
+/// Message stored in an inbox.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    /// Time the message was sent at, in seconds since the Unix epoch, as claimed by the sender.
+    pub sent_at: u64,
+    /// Content of the message.
+    pub content: Vec<u8>,
+}
+
+/// Creates a new empty inbox owned by the client and returns its `MDataInfo`.
+pub fn create<T: 'static>(client: &Client<T>) -> Box<CoreFuture<MDataInfo>> {
+    let owner_key = fry!(client.owner_key());
+    let sign_key = fry!(client.public_signing_key());
+    let inbox = fry!(MDataInfo::random_public(INBOX_TAG));
+
+    let inbox_md = fry!(MutableData::new(
+        inbox.name,
+        inbox.type_tag,
+        btree_map![
+            User::Anyone => PermissionSet::new().allow(Action::Insert),
+            User::Key(sign_key) => PermissionSet::new()
+                .allow(Action::Insert)
+                .allow(Action::Delete)
+                .allow(Action::ManagePermissions),
+        ],
+        btree_map![],
+        btree_set![owner_key],
+    ));
+
+    client.put_mdata(inbox_md).map(move |_| inbox).into_box()
+}
+
+/// Sends `content` to the inbox, encrypted for `recipient`.
+/// Returns the ID of the new message.
+pub fn send<T: 'static>(
+    client: &Client<T>,
+    inbox: &MDataInfo,
+    recipient: &box_::PublicKey,
+    content: Vec<u8>,
+) -> Box<CoreFuture<Vec<u8>>> {
+    let id = fry!(utils::generate_random_vector::<u8>(MESSAGE_ID_LEN));
+    let message = Message {
+        sent_at: now_secs(),
+        content: content,
+    };
+    let ciphertext = sealedbox::seal(&fry!(serialise(&message)), recipient);
+
+    client
+        .mutate_mdata_entries(
+            inbox.name,
+            inbox.type_tag,
+            EntryActions::new().ins(id.clone(), ciphertext, 0).into(),
+        )
+        .map(move |_| id)
+        .into_box()
+}
+
+/// Lists messages in the inbox, oldest first, together with their IDs.
+/// Entries which can't be decrypted with the given keys are skipped.
+pub fn list<T: 'static>(
+    client: &Client<T>,
+    inbox: &MDataInfo,
+    pk: &box_::PublicKey,
+    sk: &shared_box::SecretKey,
+) -> Box<CoreFuture<Vec<(Vec<u8>, Message)>>> {
+    let pk = *pk;
+    let sk = sk.clone();
+
+    if client.public_encryption_key().ok() == Some(pk) {
+        client.record_key_usage(KeyOp::Decrypt, Some(inbox.name));
+    }
+
+    client
+        .list_mdata_entries(inbox.name, inbox.type_tag)
+        .map(move |entries| {
+            let mut messages: Vec<_> = entries
+                .into_iter()
+                .filter_map(|(id, value)| {
+                    // Empty entry means the message has been deleted.
+                    if value.content.is_empty() {
+                        return None;
+                    }
+                    sealedbox::open(&value.content, &pk, &sk)
+                        .ok()
+                        .and_then(|plaintext| deserialise::<Message>(&plaintext).ok())
+                        .map(|message| (id, message))
+                })
+                .collect();
+
+            messages.sort_by(|a, b| a.1.sent_at.cmp(&b.1.sent_at));
+            messages
+        })
+        .into_box()
+}
+
+/// Deletes the message with the given ID from the inbox.
+pub fn delete<T: 'static>(
+    client: &Client<T>,
+    inbox: &MDataInfo,
+    id: Vec<u8>,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let name = inbox.name;
+    let type_tag = inbox.type_tag;
+
+    client
+        .get_mdata_value(name, type_tag, id.clone())
+        .and_then(move |value| {
+            if value.content.is_empty() {
+                return err!(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            c2.mutate_mdata_entries(
+                name,
+                type_tag,
+                EntryActions::new().del(id, value.entry_version + 1).into(),
+            )
+        })
+        .into_box()
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::test_utils::random_client;
+
+    // Test sending, listing and deleting messages.
+    #[test]
+    fn send_list_delete() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+
+            let (pk, sk) = unwrap!(client.encryption_keypair());
+            let (other_pk, _) = shared_box::gen_keypair();
+
+            create(client)
+                .then(move |res| {
+                    let inbox = unwrap!(res);
+                    send(&c2, &inbox, &pk, b"hello".to_vec()).map(move |id| (inbox, id))
+                })
+                .then(move |res| {
+                    let (inbox, id) = unwrap!(res);
+                    // Not readable with our keys, so it must not be listed.
+                    send(&c3, &inbox, &other_pk, b"secret".to_vec()).map(move |_| (inbox, id))
+                })
+                .then(move |res| {
+                    let (inbox, id) = unwrap!(res);
+                    list(&c4, &inbox, &pk, &sk).map(move |messages| (inbox, id, messages, pk, sk))
+                })
+                .then(move |res| {
+                    let (inbox, id, messages, pk, sk) = unwrap!(res);
+                    assert_eq!(messages.len(), 1);
+                    assert_eq!(messages[0].0, id);
+                    assert_eq!(messages[0].1.content, b"hello");
+
+                    delete(&c5, &inbox, id).map(move |_| (inbox, pk, sk))
+                })
+                .then(move |res| {
+                    let (inbox, pk, sk) = unwrap!(res);
+                    list(&c6, &inbox, &pk, &sk)
+                })
+                .map(|messages| assert!(messages.is_empty()))
+        });
+    }
+}
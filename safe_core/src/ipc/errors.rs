@@ -47,6 +47,18 @@ pub enum IpcError {
     InvalidOwner(Vec<(XorName, u64)>),
     /// Unexpected error
     Unexpected(String),
+    // Variants are serialised by index, so new ones go last.
+    /// Too many requests from the app; it should back off before trying again
+    RequestDenied,
+    /// The message was encoded with an unsupported version of the IPC protocol
+    UnsupportedVersion {
+        /// Version the message was encoded with
+        requested: u32,
+        /// Range of supported versions (inclusive)
+        supported: (u32, u32),
+    },
+    /// The request has expired or has already been received before
+    RequestExpired,
 }
 
 impl<T: 'static> From<SendError<T>> for IpcError {
@@ -0,0 +1,148 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Layouts of IPC messages encoded with versions of the protocol older than
+//! `IPC_APP_INFO_PROTOCOL_VERSION`, in which `AppExchangeInfo` carried no display
+//! metadata. They're only ever decoded, and converted to the current types.
+
+use super::{IpcError, IpcMsg};
+use super::req::{AppExchangeInfo, AuthReq, CombinedReq, ContainerPermissions, ContainersReq,
+                 IpcReq, ShareMData, ShareMDataReq};
+use super::resp::IpcResp;
+use std::collections::HashMap;
+
+#[derive(Deserialize)]
+pub enum IpcMsgV0 {
+    Req { req_id: u32, req: IpcReqV0 },
+    Resp { req_id: u32, resp: IpcResp },
+    Revoked { app_id: String },
+    Err(IpcError),
+}
+
+#[derive(Deserialize)]
+pub enum IpcReqV0 {
+    Auth(AuthReqV0),
+    Containers(ContainersReqV0),
+    Unregistered(Vec<u8>),
+    ShareMData(ShareMDataReqV0),
+    Combined(CombinedReqV0),
+}
+
+#[derive(Deserialize)]
+pub struct AppExchangeInfoV0 {
+    id: String,
+    scope: Option<String>,
+    name: String,
+    vendor: String,
+}
+
+#[derive(Deserialize)]
+pub struct AuthReqV0 {
+    app: AppExchangeInfoV0,
+    app_container: bool,
+    containers: HashMap<String, ContainerPermissions>,
+}
+
+#[derive(Deserialize)]
+pub struct ContainersReqV0 {
+    app: AppExchangeInfoV0,
+    containers: HashMap<String, ContainerPermissions>,
+}
+
+#[derive(Deserialize)]
+pub struct ShareMDataReqV0 {
+    app: AppExchangeInfoV0,
+    mdata: Vec<ShareMData>,
+}
+
+#[derive(Deserialize)]
+pub struct CombinedReqV0 {
+    auth: AuthReqV0,
+    mdata: Vec<ShareMData>,
+}
+
+impl From<IpcMsgV0> for IpcMsg {
+    fn from(msg: IpcMsgV0) -> Self {
+        match msg {
+            IpcMsgV0::Req { req_id, req } => {
+                IpcMsg::Req {
+                    req_id: req_id,
+                    req: req.into(),
+                }
+            }
+            IpcMsgV0::Resp { req_id, resp } => {
+                IpcMsg::Resp {
+                    req_id: req_id,
+                    resp: resp,
+                }
+            }
+            IpcMsgV0::Revoked { app_id } => IpcMsg::Revoked { app_id: app_id },
+            IpcMsgV0::Err(error) => IpcMsg::Err(error),
+        }
+    }
+}
+
+impl From<IpcReqV0> for IpcReq {
+    fn from(req: IpcReqV0) -> Self {
+        match req {
+            IpcReqV0::Auth(req) => IpcReq::Auth(req.into()),
+            IpcReqV0::Containers(req) => {
+                IpcReq::Containers(ContainersReq {
+                    app: req.app.into(),
+                    containers: req.containers,
+                })
+            }
+            IpcReqV0::Unregistered(extra_data) => IpcReq::Unregistered(extra_data),
+            IpcReqV0::ShareMData(req) => {
+                IpcReq::ShareMData(ShareMDataReq {
+                    app: req.app.into(),
+                    mdata: req.mdata,
+                })
+            }
+            IpcReqV0::Combined(req) => {
+                IpcReq::Combined(CombinedReq {
+                    auth: req.auth.into(),
+                    mdata: req.mdata,
+                })
+            }
+        }
+    }
+}
+
+impl From<AuthReqV0> for AuthReq {
+    fn from(req: AuthReqV0) -> Self {
+        AuthReq {
+            app: req.app.into(),
+            app_container: req.app_container,
+            containers: req.containers,
+        }
+    }
+}
+
+impl From<AppExchangeInfoV0> for AppExchangeInfo {
+    fn from(info: AppExchangeInfoV0) -> Self {
+        AppExchangeInfo {
+            id: info.id,
+            scope: info.scope,
+            name: info.name,
+            vendor: info.vendor,
+            description: None,
+            homepage: None,
+            icon: None,
+        }
+    }
+}
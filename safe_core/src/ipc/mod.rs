@@ -19,20 +19,29 @@
 pub mod req;
 /// Response module
 pub mod resp;
+/// URI module
+pub mod uri;
+/// Local socket transport
+pub mod socket;
 
 mod errors;
+mod legacy;
 
 pub use self::errors::IpcError;
-pub use self::req::{AppExchangeInfo, AuthReq, ContainersReq, IpcReq, Permission, ShareMData,
-                    ShareMDataReq};
-pub use self::resp::{AccessContInfo, AccessContainerEntry, AppKeys, AuthGranted, IpcResp,
-                     access_container_enc_key};
+pub use self::req::{AppExchangeInfo, AuthReq, CombinedReq, ContainersReq, IpcReq, Permission,
+                    ShareMData, ShareMDataReq};
+pub use self::resp::{ACCESS_CONTAINER_ENTRY_FORMAT, AccessContInfo, AccessContainerEntry,
+                     AppKeys, AuthGranted, IpcResp, access_container_enc_key};
 
+use self::legacy::IpcMsgV0;
 use ffi_utils::{base64_decode, base64_encode};
 use maidsafe_utilities::serialisation::{deserialise, serialise};
 use rand::{self, Rng};
 pub use routing::BootstrapConfig;
+use rust_sodium::crypto::{auth, secretbox};
+use std::time::{SystemTime, UNIX_EPOCH};
 use std::u32;
+use tiny_keccak::sha3_256;
 
 /// IPC message
 #[cfg_attr(feature = "cargo-clippy", allow(large_enum_variant))]
@@ -61,14 +70,254 @@ pub enum IpcMsg {
     Err(IpcError),
 }
 
+/// Version of the IPC protocol used to encode messages.
+pub const IPC_PROTOCOL_VERSION: u32 = 4;
+/// Oldest version of the IPC protocol which can still be decoded. Version 0 stands
+/// for messages from before the protocol was versioned.
+pub const IPC_MIN_PROTOCOL_VERSION: u32 = 0;
+/// First version of the IPC protocol in which requests carry a `ReqStamp`.
+pub const IPC_STAMPED_PROTOCOL_VERSION: u32 = 2;
+/// First version of the IPC protocol in which `AppExchangeInfo` carries the description,
+/// homepage and icon of the app.
+pub const IPC_APP_INFO_PROTOCOL_VERSION: u32 = 3;
+/// First version of the IPC protocol in which requests can carry a `ReqMac`.
+pub const IPC_MAC_PROTOCOL_VERSION: u32 = 4;
+
+/// Number of seconds after which an IPC request expires.
+pub const IPC_REQ_EXPIRY_SECS: u64 = 10 * 60;
+
+// Marks versioned messages. Unversioned messages start with the serialised
+// `IpcMsg` variant index instead, which is always much smaller.
+const VERSIONED_MSG_MAGIC: u32 = 0x5643_5049;
+
+/// Protects an IPC request from being replayed: the authenticator accepts every
+/// nonce only once, and only until the request expires.
+#[derive(Clone, Copy, Serialize, Deserialize, Debug, Eq, PartialEq)]
+pub struct ReqStamp {
+    /// Random nonce
+    pub nonce: u64,
+    /// Time (in seconds since the Unix epoch) after which the request expires
+    pub expires_at: u64,
+}
+
+impl ReqStamp {
+    /// Generate a stamp with a random nonce, expiring in `IPC_REQ_EXPIRY_SECS`.
+    pub fn new() -> Self {
+        ReqStamp {
+            nonce: rand::random(),
+            expires_at: now_secs() + IPC_REQ_EXPIRY_SECS,
+        }
+    }
+
+    /// Returns `true` if the request has expired at the given time.
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.expires_at <= now
+    }
+}
+
+impl Default for ReqStamp {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// MAC of a request and its stamp, keyed with the symmetric encryption key of
+/// the app sending it. It proves the request comes from the app, so a captured
+/// request can't be given a fresh stamp and replayed.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReqMac {
+    tag: [u8; auth::TAGBYTES],
+    body: Vec<u8>,
+}
+
+impl ReqMac {
+    /// Returns `true` if the request was authenticated with the given key.
+    pub fn verify(&self, enc_key: &secretbox::Key) -> bool {
+        auth::verify(&auth::Tag(self.tag), &self.body, &mac_key(enc_key))
+    }
+}
+
+// Derives the MAC key from the app's encryption key, so that the same key
+// material isn't used with two different primitives.
+fn mac_key(enc_key: &secretbox::Key) -> auth::Key {
+    auth::Key(sha3_256(&[&b"safe-ipc-req-mac"[..], &enc_key.0[..]].concat()))
+}
+
+/// An IPC message along with the details of how it was encoded.
+#[derive(Debug)]
+pub struct DecodedMsg {
+    /// The message
+    pub msg: IpcMsg,
+    /// Version of the protocol the message was encoded with
+    pub version: u32,
+    /// Stamp of the request. `None` for responses and for requests encoded
+    /// with protocol versions older than `IPC_STAMPED_PROTOCOL_VERSION`.
+    pub stamp: Option<ReqStamp>,
+    /// MAC of the request. `None` if the app encoded it without its keys.
+    pub mac: Option<ReqMac>,
+}
+
+/// Returns the current time in seconds since the Unix epoch.
+pub fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
 /// Encode `IpcMsg` into string, using base64 encoding.
+/// Requests are stamped with a fresh `ReqStamp`.
 pub fn encode_msg(msg: &IpcMsg) -> Result<String, IpcError> {
-    Ok(base64_encode(&serialise(msg)?))
+    encode(msg, None, IPC_PROTOCOL_VERSION)
+}
+
+/// Encode `IpcMsg` like `encode_msg`, additionally authenticating requests with
+/// a `ReqMac` keyed with the symmetric encryption key of the app.
+pub fn encode_msg_with_mac(msg: &IpcMsg, enc_key: &secretbox::Key) -> Result<String, IpcError> {
+    encode(msg, Some(enc_key), IPC_PROTOCOL_VERSION)
+}
+
+/// Encode `IpcMsg` with the given version of the protocol, e.g. a response, so that
+/// it can be decoded by an app which sent its request with that version. Requests
+/// are stamped like with `encode_msg`, and can't be encoded with versions older than
+/// `IPC_APP_INFO_PROTOCOL_VERSION`. Unsupported versions fail with
+/// `IpcError::UnsupportedVersion`.
+pub fn encode_msg_with_version(msg: &IpcMsg, version: u32) -> Result<String, IpcError> {
+    encode(msg, None, version)
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(absurd_extreme_comparisons))]
+fn encode(
+    msg: &IpcMsg,
+    enc_key: Option<&secretbox::Key>,
+    version: u32,
+) -> Result<String, IpcError> {
+    let is_req = match *msg {
+        IpcMsg::Req { .. } => true,
+        _ => false,
+    };
+    // Only requests changed their layout over the versions, responses just their envelope.
+    let min_version = if is_req {
+        IPC_APP_INFO_PROTOCOL_VERSION
+    } else {
+        IPC_MIN_PROTOCOL_VERSION
+    };
+    if version < min_version || version > IPC_PROTOCOL_VERSION {
+        return Err(IpcError::UnsupportedVersion {
+            requested: version,
+            supported: (min_version, IPC_PROTOCOL_VERSION),
+        });
+    }
+
+    let stamp = if is_req { Some(ReqStamp::new()) } else { None };
+    let payload = if version < IPC_STAMPED_PROTOCOL_VERSION {
+        serialise(msg)?
+    } else if version < IPC_MAC_PROTOCOL_VERSION {
+        serialise(&(stamp, msg))?
+    } else {
+        // The MAC is computed over the serialised bytes, as re-serialising
+        // a decoded request isn't guaranteed to reproduce them.
+        let body = serialise(&(stamp, msg))?;
+        let tag = match (stamp, enc_key) {
+            (Some(_), Some(enc_key)) => Some(auth::authenticate(&body, &mac_key(enc_key)).0),
+            _ => None,
+        };
+        serialise(&(tag, body))?
+    };
+
+    if version == 0 {
+        return Ok(base64_encode(&payload));
+    }
+
+    let mut encoded = Vec::new();
+    encoded.extend_from_slice(&u32_to_le_bytes(VERSIONED_MSG_MAGIC));
+    encoded.extend_from_slice(&u32_to_le_bytes(version));
+    encoded.extend_from_slice(&payload);
+    Ok(base64_encode(&encoded))
 }
 
 /// Decode `IpcMsg` encoded with base64 encoding.
+/// Fails with `IpcError::UnsupportedVersion` if the message was encoded with
+/// a version of the protocol outside of the supported range.
 pub fn decode_msg(encoded: &str) -> Result<IpcMsg, IpcError> {
-    Ok(deserialise(&base64_decode(encoded)?)?)
+    decode(encoded).map(|decoded| decoded.msg)
+}
+
+/// Decode `IpcMsg` encoded with base64 encoding, also returning the version of
+/// the protocol it was encoded with.
+pub fn decode_msg_with_version(encoded: &str) -> Result<(IpcMsg, u32), IpcError> {
+    decode(encoded).map(|decoded| (decoded.msg, decoded.version))
+}
+
+/// Decode `IpcMsg` encoded with base64 encoding, also returning the stamp of
+/// the request. The stamp is `None` for responses and for requests encoded
+/// with protocol versions older than `IPC_STAMPED_PROTOCOL_VERSION`.
+pub fn decode_msg_with_stamp(encoded: &str) -> Result<(IpcMsg, Option<ReqStamp>), IpcError> {
+    decode(encoded).map(|decoded| (decoded.msg, decoded.stamp))
+}
+
+/// Decode `IpcMsg` encoded with base64 encoding, along with the version of the
+/// protocol, the stamp and the MAC it was encoded with.
+pub fn decode_msg_with_details(encoded: &str) -> Result<DecodedMsg, IpcError> {
+    decode(encoded)
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(absurd_extreme_comparisons))]
+fn decode(encoded: &str) -> Result<DecodedMsg, IpcError> {
+    let decoded = base64_decode(encoded)?;
+
+    let (version, payload) = if decoded.len() >= 8 &&
+        u32_from_le_bytes(&decoded[..4]) == VERSIONED_MSG_MAGIC
+    {
+        (u32_from_le_bytes(&decoded[4..8]), &decoded[8..])
+    } else {
+        (0, &decoded[..])
+    };
+
+    if version < IPC_MIN_PROTOCOL_VERSION || version > IPC_PROTOCOL_VERSION {
+        return Err(IpcError::UnsupportedVersion {
+            requested: version,
+            supported: (IPC_MIN_PROTOCOL_VERSION, IPC_PROTOCOL_VERSION),
+        });
+    }
+
+    let (msg, stamp, mac) = if version < IPC_STAMPED_PROTOCOL_VERSION {
+        let msg: IpcMsgV0 = deserialise(payload)?;
+        (msg.into(), None, None)
+    } else if version < IPC_APP_INFO_PROTOCOL_VERSION {
+        let (stamp, msg): (_, IpcMsgV0) = deserialise(payload)?;
+        (msg.into(), stamp, None)
+    } else if version < IPC_MAC_PROTOCOL_VERSION {
+        let (stamp, msg) = deserialise(payload)?;
+        (msg, stamp, None)
+    } else {
+        let (tag, body): (Option<[u8; auth::TAGBYTES]>, Vec<u8>) = deserialise(payload)?;
+        let (stamp, msg) = deserialise(&body)?;
+        (msg, stamp, tag.map(|tag| ReqMac { tag, body }))
+    };
+
+    Ok(DecodedMsg {
+        msg,
+        version,
+        stamp,
+        mac,
+    })
+}
+
+fn u32_to_le_bytes(value: u32) -> [u8; 4] {
+    [
+        value as u8,
+        (value >> 8) as u8,
+        (value >> 16) as u8,
+        (value >> 24) as u8,
+    ]
+}
+
+fn u32_from_le_bytes(bytes: &[u8]) -> u32 {
+    bytes.iter().rev().fold(
+        0,
+        |value, byte| (value << 8) | (*byte as u32),
+    )
 }
 
 /// Generate unique request ID.
@@ -76,3 +325,222 @@ pub fn gen_req_id() -> u32 {
     // Generate the number in range 1..MAX inclusive.
     rand::thread_rng().gen_range(0, u32::MAX) + 1
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ipc::req::ContainerPermissions;
+    use std::collections::HashMap;
+
+    // Test that messages are decoded with the version they were encoded with.
+    #[test]
+    fn versioned_msg() {
+        let msg = IpcMsg::Revoked { app_id: "app".to_owned() };
+
+        let encoded = unwrap!(encode_msg(&msg));
+        let (decoded, version) = unwrap!(decode_msg_with_version(&encoded));
+        assert_eq!(decoded, msg);
+        assert_eq!(version, IPC_PROTOCOL_VERSION);
+    }
+
+    // Test that responses can be encoded with any supported version, and requests
+    // only with versions in which they have the current layout.
+    #[test]
+    fn resp_with_version() {
+        let resp = IpcMsg::Resp {
+            req_id: gen_req_id(),
+            resp: IpcResp::Unregistered(Err(IpcError::AuthDenied)),
+        };
+
+        for version in IPC_MIN_PROTOCOL_VERSION..(IPC_PROTOCOL_VERSION + 1) {
+            let encoded = unwrap!(encode_msg_with_version(&resp, version));
+            let (decoded, decoded_version) = unwrap!(decode_msg_with_version(&encoded));
+            assert_eq!(decoded, resp);
+            assert_eq!(decoded_version, version);
+        }
+
+        let req = IpcMsg::Req {
+            req_id: gen_req_id(),
+            req: IpcReq::Unregistered(Vec::new()),
+        };
+        let encoded = unwrap!(encode_msg_with_version(&req, IPC_APP_INFO_PROTOCOL_VERSION));
+        let decoded = unwrap!(decode_msg_with_details(&encoded));
+        assert_eq!(decoded.msg, req);
+        assert_eq!(decoded.version, IPC_APP_INFO_PROTOCOL_VERSION);
+        assert!(decoded.stamp.is_some());
+
+        match encode_msg_with_version(&req, IPC_STAMPED_PROTOCOL_VERSION) {
+            Err(IpcError::UnsupportedVersion { .. }) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+        match encode_msg_with_version(&resp, IPC_PROTOCOL_VERSION + 1) {
+            Err(IpcError::UnsupportedVersion { .. }) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
+    // Test that messages encoded before the protocol was versioned are still decoded.
+    #[test]
+    fn unversioned_msg() {
+        let msg = IpcMsg::Revoked { app_id: "app".to_owned() };
+
+        let encoded = base64_encode(&unwrap!(serialise(&msg)));
+        let (decoded, version) = unwrap!(decode_msg_with_version(&encoded));
+        assert_eq!(decoded, msg);
+        assert_eq!(version, 0);
+    }
+
+    // Test that requests are stamped and responses are not.
+    #[test]
+    fn stamped_msg() {
+        let req = IpcMsg::Req {
+            req_id: gen_req_id(),
+            req: IpcReq::Unregistered(Vec::new()),
+        };
+
+        let (decoded, stamp) = unwrap!(decode_msg_with_stamp(&unwrap!(encode_msg(&req))));
+        assert_eq!(decoded, req);
+        let stamp = unwrap!(stamp);
+        assert!(!stamp.is_expired(now_secs()));
+        assert!(stamp.is_expired(now_secs() + IPC_REQ_EXPIRY_SECS + 1));
+
+        // Every encoding gets a new nonce.
+        let (_, stamp2) = unwrap!(decode_msg_with_stamp(&unwrap!(encode_msg(&req))));
+        assert_ne!(stamp.nonce, unwrap!(stamp2).nonce);
+
+        let resp = IpcMsg::Revoked { app_id: "app".to_owned() };
+        let (_, stamp) = unwrap!(decode_msg_with_stamp(&unwrap!(encode_msg(&resp))));
+        assert!(stamp.is_none());
+    }
+
+    // Test that MACs of requests only verify with the key of the app which
+    // encoded them, and that the stamp can't be replaced.
+    #[test]
+    fn req_mac() {
+        let req = IpcMsg::Req {
+            req_id: gen_req_id(),
+            req: IpcReq::Unregistered(Vec::new()),
+        };
+        let enc_key = secretbox::gen_key();
+
+        let decoded = unwrap!(decode_msg_with_details(&unwrap!(encode_msg_with_mac(&req, &enc_key))));
+        assert_eq!(decoded.msg, req);
+        assert_eq!(decoded.version, IPC_PROTOCOL_VERSION);
+        let mac = unwrap!(decoded.mac);
+        assert!(mac.verify(&enc_key));
+        assert!(!mac.verify(&secretbox::gen_key()));
+
+        // A fresh stamp invalidates the MAC.
+        let restamped = ReqMac {
+            body: unwrap!(serialise(&(Some(ReqStamp::new()), &req))),
+            ..mac
+        };
+        assert!(!restamped.verify(&enc_key));
+
+        // Requests encoded without a key carry no MAC.
+        let decoded = unwrap!(decode_msg_with_details(&unwrap!(encode_msg(&req))));
+        assert!(decoded.mac.is_none());
+    }
+
+    // Test that requests from apps predating the display metadata in `AppExchangeInfo`
+    // are still decoded.
+    #[test]
+    fn app_info_v0() {
+        #[derive(Serialize)]
+        struct OldAppExchangeInfo {
+            id: String,
+            scope: Option<String>,
+            name: String,
+            vendor: String,
+        }
+
+        #[derive(Serialize)]
+        struct OldAuthReq {
+            app: OldAppExchangeInfo,
+            app_container: bool,
+            containers: HashMap<String, ContainerPermissions>,
+        }
+
+        #[derive(Serialize)]
+        enum OldIpcReq {
+            Auth(OldAuthReq),
+        }
+
+        #[derive(Serialize)]
+        enum OldIpcMsg {
+            Req { req_id: u32, req: OldIpcReq },
+        }
+
+        let mut containers = HashMap::new();
+        let _ = containers.insert("photos".to_owned(), btree_set![Permission::Read]);
+        let old_msg = OldIpcMsg::Req {
+            req_id: 1,
+            req: OldIpcReq::Auth(OldAuthReq {
+                app: OldAppExchangeInfo {
+                    id: "app".to_owned(),
+                    scope: None,
+                    name: "App".to_owned(),
+                    vendor: "Vendor".to_owned(),
+                },
+                app_container: true,
+                containers: containers.clone(),
+            }),
+        };
+        let stamp = ReqStamp::new();
+
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&u32_to_le_bytes(VERSIONED_MSG_MAGIC));
+        encoded.extend_from_slice(&u32_to_le_bytes(IPC_APP_INFO_PROTOCOL_VERSION - 1));
+        encoded.extend_from_slice(&unwrap!(serialise(&(Some(stamp), old_msg))));
+
+        let (msg, decoded_stamp) = unwrap!(decode_msg_with_stamp(&base64_encode(&encoded)));
+        assert_eq!(decoded_stamp, Some(stamp));
+        assert_eq!(
+            msg,
+            IpcMsg::Req {
+                req_id: 1,
+                req: IpcReq::Auth(AuthReq {
+                    app: AppExchangeInfo {
+                        id: "app".to_owned(),
+                        scope: None,
+                        name: "App".to_owned(),
+                        vendor: "Vendor".to_owned(),
+                        description: None,
+                        homepage: None,
+                        icon: None,
+                    },
+                    app_container: true,
+                    containers: containers,
+                }),
+            }
+        );
+    }
+
+    // Test that errors keep the serialised variant indices older peers know them by.
+    #[test]
+    fn error_variant_indices() {
+        let encoded = unwrap!(serialise(&IpcError::Unexpected("error".to_owned())));
+        assert_eq!(&encoded[..4], &u32_to_le_bytes(9));
+        let encoded = unwrap!(serialise(&IpcError::RequestDenied));
+        assert_eq!(&encoded[..4], &u32_to_le_bytes(10));
+    }
+
+    // Test that messages from a newer version of the protocol are rejected.
+    #[test]
+    fn unsupported_version() {
+        let msg = IpcMsg::Revoked { app_id: "app".to_owned() };
+
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&u32_to_le_bytes(VERSIONED_MSG_MAGIC));
+        encoded.extend_from_slice(&u32_to_le_bytes(IPC_PROTOCOL_VERSION + 1));
+        encoded.extend_from_slice(&unwrap!(serialise(&msg)));
+
+        match decode_msg(&base64_encode(&encoded)) {
+            Err(IpcError::UnsupportedVersion { requested, supported }) => {
+                assert_eq!(requested, IPC_PROTOCOL_VERSION + 1);
+                assert_eq!(supported, (IPC_MIN_PROTOCOL_VERSION, IPC_PROTOCOL_VERSION));
+            }
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
@@ -0,0 +1,83 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use super::{AuthReq, ShareMData, ShareMDataReq};
+use ffi::ipc::req as ffi;
+use ffi_utils::{ReprC, vec_into_raw_parts};
+use ipc::errors::IpcError;
+use std::slice;
+
+/// Represents a request for authorisation together with shared access to
+/// mutable data. The app container and container permissions are requested as
+/// part of `auth`. The user is prompted once, and the request is granted or
+/// denied as a whole.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct CombinedReq {
+    /// Authorisation request
+    pub auth: AuthReq,
+    /// List of MD names & type tags and permissions that need to be shared
+    pub mdata: Vec<ShareMData>,
+}
+
+impl CombinedReq {
+    /// Returns the part of the request asking for shared access to mutable data.
+    pub fn share_mdata_req(&self) -> ShareMDataReq {
+        ShareMDataReq {
+            app: self.auth.app.clone(),
+            mdata: self.mdata.clone(),
+        }
+    }
+
+    /// Consumes the object and returns the FFI counterpart.
+    ///
+    /// You're now responsible for freeing the subobjects memory once you're
+    /// done.
+    pub fn into_repr_c(self) -> Result<ffi::CombinedReq, IpcError> {
+        let mdata_repr_c: Vec<_> = self.mdata
+            .into_iter()
+            .map(|md| md.into_repr_c())
+            .collect::<Result<_, _>>()?;
+
+        let (mdata, mdata_len, mdata_cap) = vec_into_raw_parts(mdata_repr_c);
+
+        Ok(ffi::CombinedReq {
+            auth: self.auth.into_repr_c()?,
+            mdata,
+            mdata_len,
+            mdata_cap,
+        })
+    }
+}
+
+impl ReprC for CombinedReq {
+    type C = *const ffi::CombinedReq;
+    type Error = IpcError;
+
+    /// Constructs the object from the FFI counterpart.
+    unsafe fn clone_from_repr_c(raw: *const ffi::CombinedReq) -> Result<Self, IpcError> {
+        Ok(CombinedReq {
+            auth: AuthReq::clone_from_repr_c(&(*raw).auth)?,
+            mdata: {
+                let mdata = slice::from_raw_parts((*raw).mdata, (*raw).mdata_len);
+                mdata
+                    .into_iter()
+                    .map(|c| ShareMData::clone_from_repr_c(c))
+                    .collect::<Result<_, _>>()?
+            },
+        })
+    }
+}
@@ -18,22 +18,26 @@
 #![allow(unsafe_code)]
 
 mod auth;
+mod combined;
 mod containers;
 mod share_mdata;
 
 pub use self::auth::AuthReq;
+pub use self::combined::CombinedReq;
 pub use self::containers::ContainersReq;
 pub use self::share_mdata::{ShareMData, ShareMDataReq};
 
 use ffi::ipc::req::{AppExchangeInfo as FfiAppExchangeInfo,
                     ContainerPermissions as FfiContainerPermissions,
                     PermissionSet as FfiPermissionSet};
-use ffi_utils::{ReprC, StringError, from_c_str};
+use ffi::arrays::XorNameArray;
+use ffi_utils::{ReprC, StringError, from_c_str, vec_into_raw_parts};
 use ipc::errors::IpcError;
-use routing::{Action, PermissionSet};
+use routing::{Action, PermissionSet, XorName};
 use std::{ptr, slice};
 use std::collections::{BTreeSet, HashMap};
 use std::ffi::{CString, NulError};
+use std::os::raw::c_char;
 
 /// Permission enum - use for internal storage only
 #[repr(C)]
@@ -68,6 +72,8 @@ pub enum IpcReq {
     Unregistered(Vec<u8>),
     /// Share mutable data.
     ShareMData(ShareMDataReq),
+    /// Authentication together with shared access to mutable data.
+    Combined(CombinedReq),
 }
 
 /// Consumes the object and returns the wrapped raw pointer.
@@ -230,20 +236,68 @@ pub fn permission_set_clone_from_repr_c(
     Ok(pm)
 }
 
+/// Separates the app ID from the scope in scoped app IDs.
+pub const SCOPE_SEPARATOR: char = '#';
+
+/// Scoped identifier of the app `id` authorised with `scope`, as returned by
+/// `AppExchangeInfo::scoped_id`.
+pub fn scoped_app_id(id: &str, scope: Option<&str>) -> String {
+    match scope {
+        Some(scope) => format!("{}{}{}", id, SCOPE_SEPARATOR, scope),
+        None => id.to_owned(),
+    }
+}
+
 /// Represents an application ID in the process of asking permissions
 #[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
 pub struct AppExchangeInfo {
     /// The ID. It must be unique.
     pub id: String,
-    /// Reserved by the frontend.
+    /// Scope of the authorisation, e.g. "work" or "personal". Each scope of an app is
+    /// registered separately, see `scoped_id`.
     pub scope: Option<String>,
     /// The application friendly-name.
     pub name: String,
     /// The application provider/vendor (e.g. MaidSafe)
     pub vendor: String,
+    /// Short description of the app, for the authenticator to display.
+    pub description: Option<String>,
+    /// URL of the app's homepage.
+    pub homepage: Option<String>,
+    /// Icon of the app. Requests travel in URIs, so larger images are better stored as
+    /// `ImmutableData`.
+    pub icon: Option<AppIcon>,
+}
+
+/// Icon of an app.
+#[derive(Clone, Eq, PartialEq, Serialize, Deserialize, Debug)]
+pub enum AppIcon {
+    /// The image data (PNG, SVG, ...).
+    Data(Vec<u8>),
+    /// Name of the `ImmutableData` holding the image data.
+    ImmutableData(XorName),
 }
 
 impl AppExchangeInfo {
+    /// Identifier the authenticator registers the app under: the ID for unscoped apps, or
+    /// the ID and the scope joined by `SCOPE_SEPARATOR`. Every scope has keys, an access
+    /// container entry and an app container of its own, so the data of one scope isn't
+    /// accessible from another.
+    pub fn scoped_id(&self) -> String {
+        scoped_app_id(&self.id, self.scope.as_ref().map(String::as_str))
+    }
+
+    /// Fails with `InvalidMsg` if the ID or the scope contain `SCOPE_SEPARATOR`, which would
+    /// make the scoped ID ambiguous: ID "a#b" with scope "c" would be registered as the same
+    /// app as ID "a" with scope "b#c".
+    pub fn validate(&self) -> Result<(), IpcError> {
+        let scope = self.scope.as_ref().map_or("", String::as_str);
+        if self.id.contains(SCOPE_SEPARATOR) || scope.contains(SCOPE_SEPARATOR) {
+            return Err(IpcError::InvalidMsg);
+        }
+        Ok(())
+    }
+
     /// Consumes the object and returns the wrapped raw pointer.
     ///
     /// You're now responsible for freeing this memory once you're done.
@@ -253,17 +307,34 @@ impl AppExchangeInfo {
             scope,
             name,
             vendor,
+            description,
+            homepage,
+            icon,
         } = self;
 
+        let (icon_ptr, icon_len, icon_cap, icon_name) = match icon {
+            Some(AppIcon::Data(data)) => {
+                let (ptr, len, cap) = vec_into_raw_parts(data);
+                (ptr, len, cap, ptr::null())
+            }
+            Some(AppIcon::ImmutableData(name)) => {
+                let name: *const XorNameArray = Box::into_raw(Box::new(name.0));
+                (ptr::null_mut(), 0, 0, name)
+            }
+            None => (ptr::null_mut(), 0, 0, ptr::null()),
+        };
+
         Ok(FfiAppExchangeInfo {
             id: CString::new(id).map_err(StringError::from)?.into_raw(),
-            scope: if let Some(scope) = scope {
-                CString::new(scope).map_err(StringError::from)?.into_raw()
-            } else {
-                ptr::null()
-            },
+            scope: opt_string_into_raw(scope)?,
             name: CString::new(name).map_err(StringError::from)?.into_raw(),
             vendor: CString::new(vendor).map_err(StringError::from)?.into_raw(),
+            description: opt_string_into_raw(description)?,
+            homepage: opt_string_into_raw(homepage)?,
+            icon_ptr: icon_ptr,
+            icon_len: icon_len,
+            icon_cap: icon_cap,
+            icon_name: icon_name,
         })
     }
 }
@@ -277,25 +348,49 @@ impl ReprC for AppExchangeInfo {
     /// After calling this function, the raw pointer is owned by the resulting
     /// object.
     unsafe fn clone_from_repr_c(raw: *const FfiAppExchangeInfo) -> Result<Self, IpcError> {
+        let icon = if !(*raw).icon_name.is_null() {
+            Some(AppIcon::ImmutableData(XorName(*(*raw).icon_name)))
+        } else if !(*raw).icon_ptr.is_null() {
+            let data = slice::from_raw_parts((*raw).icon_ptr, (*raw).icon_len).to_vec();
+            Some(AppIcon::Data(data))
+        } else {
+            None
+        };
+
         Ok(AppExchangeInfo {
             id: from_c_str((*raw).id).map_err(StringError::from)?,
-            scope: if (*raw).scope.is_null() {
-                None
-            } else {
-                Some(from_c_str((*raw).scope).map_err(StringError::from)?)
-            },
+            scope: opt_string_clone_from_raw((*raw).scope)?,
             name: from_c_str((*raw).name).map_err(StringError::from)?,
             vendor: from_c_str((*raw).vendor).map_err(StringError::from)?,
+            description: opt_string_clone_from_raw((*raw).description)?,
+            homepage: opt_string_clone_from_raw((*raw).homepage)?,
+            icon,
         })
     }
 }
 
+fn opt_string_into_raw(string: Option<String>) -> Result<*const c_char, IpcError> {
+    match string {
+        Some(string) => Ok(CString::new(string).map_err(StringError::from)?.into_raw()),
+        None => Ok(ptr::null()),
+    }
+}
+
+unsafe fn opt_string_clone_from_raw(raw: *const c_char) -> Result<Option<String>, IpcError> {
+    if raw.is_null() {
+        Ok(None)
+    } else {
+        Ok(Some(from_c_str(raw).map_err(StringError::from)?))
+    }
+}
+
 #[cfg(test)]
 #[allow(unsafe_code)]
 mod tests {
     use super::*;
     use ffi::ipc::req::PermissionSet as FfiPermissionSet;
     use ffi_utils::ReprC;
+    use rand;
     use std::collections::HashMap;
     use std::ffi::CStr;
 
@@ -369,6 +464,9 @@ mod tests {
             scope: Some("hi".to_string()),
             name: "bubi".to_string(),
             vendor: "hey girl".to_string(),
+            description: Some("does things".to_string()),
+            homepage: None,
+            icon: Some(AppIcon::Data(vec![1, 2, 3])),
         };
 
         let ffi_a = unwrap!(a.into_repr_c());
@@ -386,8 +484,13 @@ mod tests {
         assert_eq!(a.scope, Some("hi".to_string()));
         assert_eq!(a.name, "bubi");
         assert_eq!(a.vendor, "hey girl");
+        assert_eq!(a.description, Some("does things".to_string()));
+        assert_eq!(a.homepage, None);
+        assert_eq!(a.icon, Some(AppIcon::Data(vec![1, 2, 3])));
 
         a.scope = None;
+        let icon_name = rand::random();
+        a.icon = Some(AppIcon::ImmutableData(icon_name));
 
         let ffi_a = unwrap!(a.into_repr_c());
 
@@ -396,7 +499,13 @@ mod tests {
             assert!(ffi_a.scope.is_null());
             assert_eq!(unwrap!(CStr::from_ptr(ffi_a.name).to_str()), "bubi");
             assert_eq!(unwrap!(CStr::from_ptr(ffi_a.vendor).to_str()), "hey girl");
+            assert!(ffi_a.homepage.is_null());
+            assert!(ffi_a.icon_ptr.is_null());
+            assert_eq!(*ffi_a.icon_name, icon_name.0);
         }
+
+        let a = unsafe { unwrap!(AppExchangeInfo::clone_from_repr_c(&ffi_a)) };
+        assert_eq!(a.icon, Some(AppIcon::ImmutableData(icon_name)));
     }
 
     // Test converting an `AuthReq` object to its FFI representation and back again.
@@ -407,6 +516,9 @@ mod tests {
             scope: Some("2".to_string()),
             name: "3".to_string(),
             vendor: "4".to_string(),
+            description: None,
+            homepage: None,
+            icon: None,
         };
 
         let a = AuthReq {
@@ -438,6 +550,9 @@ mod tests {
             scope: Some("2".to_string()),
             name: "3".to_string(),
             vendor: "4".to_string(),
+            description: None,
+            homepage: None,
+            icon: None,
         };
 
         let a = ContainersReq {
@@ -457,4 +572,48 @@ mod tests {
         assert_eq!(a.app.vendor, "4");
         assert_eq!(a.containers.len(), 0);
     }
+
+    // Test converting a `CombinedReq` object to its FFI representation and back again.
+    #[test]
+    fn combined_req() {
+        let app = AppExchangeInfo {
+            id: "1".to_string(),
+            scope: None,
+            name: "3".to_string(),
+            vendor: "4".to_string(),
+            description: None,
+            homepage: None,
+            icon: None,
+        };
+
+        let mut cp = HashMap::new();
+        let _ = cp.insert("_documents".to_string(), btree_set![Permission::Insert]);
+
+        let a = CombinedReq {
+            auth: AuthReq {
+                app: app,
+                app_container: true,
+                containers: cp,
+            },
+            mdata: vec![
+                ShareMData {
+                    type_tag: 15_000,
+                    name: rand::random(),
+                    perms: PermissionSet::new().allow(Action::Insert),
+                },
+            ],
+        };
+
+        let ffi = unwrap!(a.clone().into_repr_c());
+
+        assert_eq!(ffi.auth.containers_len, 1);
+        assert_eq!(ffi.mdata_len, 1);
+
+        let b = unsafe { unwrap!(CombinedReq::clone_from_repr_c(&ffi)) };
+        assert_eq!(a, b);
+
+        let share_req = b.share_mdata_req();
+        assert_eq!(share_req.app, a.auth.app);
+        assert_eq!(share_req.mdata, a.mdata);
+    }
 }
@@ -35,6 +35,7 @@ use std::ffi::{CString, NulError};
 use std::ptr;
 use std::slice;
 use tiny_keccak::sha3_256;
+use utils::migration::Format;
 
 /// Entry key under which the metadata are stored.
 #[no_mangle]
@@ -57,6 +58,8 @@ pub enum IpcResp {
     Unregistered(Result<BootstrapConfig, IpcError>),
     /// Share mutable data.
     ShareMData(Result<(), IpcError>),
+    /// Combined authentication and shared access to mutable data.
+    Combined(Result<AuthGranted, IpcError>),
 }
 
 /// It represents the authentication response.
@@ -203,6 +206,13 @@ impl ReprC for AppKeys {
 /// Represents an entry for a single app in the access container
 pub type AccessContainerEntry = HashMap<String, (MDataInfo, ContainerPermissions)>;
 
+/// Format of the serialised `AccessContainerEntry`, applied before encryption. Read by both
+/// the authenticator and the app the entry belongs to.
+pub const ACCESS_CONTAINER_ENTRY_FORMAT: Format = Format {
+    header: b"SAFE-ACE",
+    migrations: &[],
+};
+
 /// Convert `AccessContainerEntry` to FFI representation.
 pub fn access_container_entry_into_repr_c(
     entry: AccessContainerEntry,
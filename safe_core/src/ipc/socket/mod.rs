@@ -0,0 +1,389 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Transport for IPC messages over a local socket - a Unix domain socket, or a
+//! named pipe on Windows - for apps and the authenticator running on the same
+//! machine. This avoids the round-trip of the messages through system URIs.
+//!
+//! Every message is sent as a frame: its length as a little-endian `u32`,
+//! followed by the encoded message itself. A connection carries exactly one
+//! request and the corresponding response.
+
+#[cfg(unix)]
+mod unix;
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod windows;
+
+#[cfg(unix)]
+use self::unix as sys;
+#[cfg(windows)]
+use self::windows as sys;
+
+use maidsafe_utilities::thread::{self, Joiner};
+use std::collections::HashMap;
+use std::io::{self, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread as std_thread;
+use std::time::Duration;
+
+/// Name of the socket the authenticator listens on by default.
+pub const DEFAULT_SOCKET_NAME: &'static str = "safe_authenticator";
+
+// Frames longer than this are rejected, so a misbehaving peer can't make us
+// allocate arbitrary amounts of memory.
+const MAX_FRAME_LEN: usize = 1024 * 1024;
+
+// Connections which don't send their request, or don't take the response, within
+// this time are dropped.
+const IO_TIMEOUT_SECS: u64 = 10;
+
+// Connections beyond this many, which haven't sent their request yet, are dropped
+// straight away.
+const MAX_PENDING_READS: usize = 16;
+
+// How often the listener checks whether it's been stopped while waiting for
+// connections.
+const ACCEPT_POLL_INTERVAL_MS: u64 = 50;
+
+/// Returns the path of the socket the authenticator listens on by default.
+/// On Unix, it's in a directory accessible only to the current user.
+pub fn default_socket_path() -> io::Result<PathBuf> {
+    socket_path(DEFAULT_SOCKET_NAME)
+}
+
+/// Returns the path of a socket with the given name, private to the current
+/// user.
+pub fn socket_path(name: &str) -> io::Result<PathBuf> {
+    sys::socket_path(name)
+}
+
+/// Sends an encoded IPC message to the authenticator listening on `path` and
+/// blocks until it responds.
+pub fn send_msg(path: &Path, msg: &str) -> io::Result<String> {
+    let mut stream = sys::connect(path)?;
+    write_frame(&mut stream, msg)?;
+    read_frame(&mut stream)
+}
+
+/// Listens for IPC messages on a local socket. Received messages are passed to
+/// a handler together with an id of the connection they came from, which is
+/// then used to respond to them. The listener stops when dropped.
+pub struct IpcListener {
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+    connections: Arc<Mutex<HashMap<u64, sys::Stream>>>,
+    _joiner: Joiner,
+}
+
+impl IpcListener {
+    /// Starts listening on `path`. A stale socket left over at the path is
+    /// replaced, but starting fails if another listener is still using it.
+    /// Every connection is read on its own thread, so the handler may be called
+    /// from several threads, one at a time.
+    pub fn start<F>(path: PathBuf, handler: F) -> io::Result<Self>
+    where
+        F: Fn(u64, String) + Send + 'static,
+    {
+        let mut listener = sys::Listener::bind(&path)?;
+        let stopped = Arc::new(AtomicBool::new(false));
+        let connections = Arc::new(Mutex::new(HashMap::new()));
+        let handler = Arc::new(Mutex::new(handler));
+        let pending_reads = Arc::new(AtomicUsize::new(0));
+
+        let stopped2 = Arc::clone(&stopped);
+        let connections2 = Arc::clone(&connections);
+
+        let joiner = thread::named("IPC Listener", move || {
+            let mut next_id = 0;
+
+            while !stopped2.load(Ordering::SeqCst) {
+                let stream = match listener.accept() {
+                    Ok(stream) => stream,
+                    Err(ref error) if error.kind() == io::ErrorKind::WouldBlock => {
+                        std_thread::sleep(Duration::from_millis(ACCEPT_POLL_INTERVAL_MS));
+                        continue;
+                    }
+                    Err(error) => {
+                        debug!("Failed to accept IPC connection: {:?}", error);
+                        std_thread::sleep(Duration::from_millis(ACCEPT_POLL_INTERVAL_MS));
+                        continue;
+                    }
+                };
+                if stopped2.load(Ordering::SeqCst) {
+                    break;
+                }
+
+                if pending_reads.fetch_add(1, Ordering::SeqCst) >= MAX_PENDING_READS {
+                    let _ = pending_reads.fetch_sub(1, Ordering::SeqCst);
+                    debug!("Too many pending IPC connections; dropping a new one.");
+                    continue;
+                }
+
+                let id = next_id;
+                next_id += 1;
+
+                let connection = Connection {
+                    id,
+                    stopped: Arc::clone(&stopped2),
+                    connections: Arc::clone(&connections2),
+                    handler: Arc::clone(&handler),
+                    pending_reads: Arc::clone(&pending_reads),
+                };
+                let res = std_thread::Builder::new()
+                    .name("IPC Connection".to_owned())
+                    .spawn(move || connection.run(stream));
+                if let Err(error) = res {
+                    debug!("Failed to spawn IPC connection thread: {:?}", error);
+                }
+            }
+        });
+
+        Ok(IpcListener {
+            path,
+            stopped,
+            connections,
+            _joiner: joiner,
+        })
+    }
+
+    /// Sends the response to the message received over the given connection
+    /// and closes it.
+    pub fn respond(&self, connection_id: u64, resp: &str) -> io::Result<()> {
+        let stream = self.connections
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "Poisoned lock"))?
+            .remove(&connection_id);
+
+        match stream {
+            Some(mut stream) => write_frame(&mut stream, resp),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "No such IPC connection",
+            )),
+        }
+    }
+}
+
+impl Drop for IpcListener {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        sys::wake(&self.path);
+
+        if let Ok(mut connections) = self.connections.lock() {
+            connections.clear();
+        }
+    }
+}
+
+// A connection being read on its own thread.
+struct Connection<F> {
+    id: u64,
+    stopped: Arc<AtomicBool>,
+    connections: Arc<Mutex<HashMap<u64, sys::Stream>>>,
+    handler: Arc<Mutex<F>>,
+    pending_reads: Arc<AtomicUsize>,
+}
+
+impl<F: Fn(u64, String)> Connection<F> {
+    fn run(self, mut stream: sys::Stream) {
+        let msg = sys::set_timeout(&mut stream, Duration::from_secs(IO_TIMEOUT_SECS))
+            .and_then(|()| read_frame(&mut stream));
+        let _ = self.pending_reads.fetch_sub(1, Ordering::SeqCst);
+
+        let msg = match msg {
+            Ok(msg) => msg,
+            Err(error) => {
+                debug!("Failed to read IPC message: {:?}", error);
+                return;
+            }
+        };
+
+        if self.stopped.load(Ordering::SeqCst) {
+            return;
+        }
+
+        match self.connections.lock() {
+            Ok(mut connections) => {
+                let _ = connections.insert(self.id, stream);
+            }
+            Err(_) => return,
+        }
+
+        if let Ok(handler) = self.handler.lock() {
+            handler(self.id, msg);
+        }
+    }
+}
+
+fn write_frame<W: Write>(writer: &mut W, msg: &str) -> io::Result<()> {
+    let len = msg.len();
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "IPC message too long",
+        ));
+    }
+
+    writer.write_all(
+        &[
+            len as u8,
+            (len >> 8) as u8,
+            (len >> 16) as u8,
+            (len >> 24) as u8,
+        ],
+    )?;
+    writer.write_all(msg.as_bytes())?;
+    writer.flush()
+}
+
+fn read_frame<R: Read>(reader: &mut R) -> io::Result<String> {
+    let mut len_bytes = [0; 4];
+    reader.read_exact(&mut len_bytes)?;
+
+    let len = len_bytes.iter().rev().fold(
+        0,
+        |len, byte| (len << 8) | (*byte as usize),
+    );
+    if len > MAX_FRAME_LEN {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "IPC message too long",
+        ));
+    }
+
+    let mut msg = vec![0; len];
+    reader.read_exact(&mut msg)?;
+
+    String::from_utf8(msg).map_err(|error| {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use std::io::Cursor;
+    use std::sync::mpsc;
+
+    // Test that frames are read back the same as they were written.
+    #[test]
+    fn framing() {
+        let mut buffer = Vec::new();
+        unwrap!(write_frame(&mut buffer, "hello"));
+        unwrap!(write_frame(&mut buffer, ""));
+        assert_eq!(buffer.len(), 4 + 5 + 4);
+
+        let mut reader = Cursor::new(buffer);
+        assert_eq!(unwrap!(read_frame(&mut reader)), "hello");
+        assert_eq!(unwrap!(read_frame(&mut reader)), "");
+        assert!(read_frame(&mut reader).is_err());
+
+        let mut reader = Cursor::new(vec![0xff, 0xff, 0xff, 0xff]);
+        assert!(read_frame(&mut reader).is_err());
+    }
+
+    // Test sending a message to a listener and receiving its response.
+    #[test]
+    fn request_response() {
+        let path = unwrap!(socket_path(&format!("safe_core_ipc_{}", rand::random::<u64>())));
+
+        let (tx, rx) = mpsc::channel();
+        let listener = unwrap!(IpcListener::start(
+            path.clone(),
+            move |id, msg| unwrap!(tx.send((id, msg))),
+        ));
+
+        let path2 = path.clone();
+        let client = ::std::thread::spawn(move || unwrap!(send_msg(&path2, "request")));
+
+        let (id, msg) = unwrap!(rx.recv());
+        assert_eq!(msg, "request");
+        unwrap!(listener.respond(id, "response"));
+        assert!(listener.respond(id, "response").is_err());
+
+        assert_eq!(unwrap!(client.join()), "response");
+
+        drop(listener);
+        if cfg!(unix) {
+            assert!(!path.exists());
+        }
+    }
+
+    // Test that a connection which doesn't send anything doesn't hold up others.
+    // 1. Connect to the listener without sending a request.
+    // 2. A request over another connection should still be received.
+    // 3. Dropping the listener shouldn't wait for the idle connection.
+    #[test]
+    fn idle_connection() {
+        let path = unwrap!(socket_path(&format!("safe_core_ipc_{}", rand::random::<u64>())));
+
+        let (tx, rx) = mpsc::channel();
+        let listener = unwrap!(IpcListener::start(
+            path.clone(),
+            move |id, msg| unwrap!(tx.send((id, msg))),
+        ));
+
+        let _idle = unwrap!(sys::connect(&path));
+
+        let path2 = path.clone();
+        let client = ::std::thread::spawn(move || unwrap!(send_msg(&path2, "request")));
+
+        let (id, msg) = unwrap!(rx.recv_timeout(Duration::from_secs(5)));
+        assert_eq!(msg, "request");
+        unwrap!(listener.respond(id, "response"));
+        assert_eq!(unwrap!(client.join()), "response");
+
+        drop(listener);
+    }
+
+    // Test that a stale socket is replaced, but one which is in use is not.
+    #[cfg(unix)]
+    #[test]
+    fn stale_socket() {
+        use std::os::unix::net::UnixListener;
+
+        let path = unwrap!(socket_path(&format!("safe_core_ipc_{}", rand::random::<u64>())));
+        drop(unwrap!(UnixListener::bind(&path)));
+        assert!(path.exists());
+
+        let listener = unwrap!(IpcListener::start(path.clone(), |_, _| ()));
+
+        match IpcListener::start(path.clone(), |_, _| ()) {
+            Err(ref error) if error.kind() == io::ErrorKind::AddrInUse => (),
+            Err(error) => panic!("Unexpected {:?}", error),
+            Ok(_) => panic!("Unexpected success"),
+        }
+
+        drop(listener);
+        assert!(!path.exists());
+    }
+
+    // Test that sockets are placed in a directory private to the user.
+    #[cfg(unix)]
+    #[test]
+    fn private_socket_dir() {
+        use std::os::unix::fs::MetadataExt;
+
+        let path = unwrap!(default_socket_path());
+        let metadata = unwrap!(::std::fs::metadata(unwrap!(path.parent())));
+        assert_eq!(metadata.mode() & 0o077, 0);
+    }
+}
@@ -0,0 +1,137 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Unix domain socket implementation of the local IPC transport.
+
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, MetadataExt};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+pub type Stream = UnixStream;
+
+pub fn socket_path(name: &str) -> io::Result<PathBuf> {
+    Ok(runtime_dir()?.join(format!("{}.sock", name)))
+}
+
+pub fn connect(path: &Path) -> io::Result<Stream> {
+    UnixStream::connect(path)
+}
+
+pub fn set_timeout(stream: &mut Stream, timeout: Duration) -> io::Result<()> {
+    stream.set_read_timeout(Some(timeout))?;
+    stream.set_write_timeout(Some(timeout))
+}
+
+// The accept loop polls the listener, so there's nothing to wake up.
+pub fn wake(_path: &Path) {}
+
+pub struct Listener {
+    inner: UnixListener,
+    path: PathBuf,
+}
+
+impl Listener {
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        let inner = match UnixListener::bind(path) {
+            Ok(inner) => inner,
+            Err(ref error) if error.kind() == io::ErrorKind::AddrInUse => {
+                remove_stale_socket(path)?;
+                UnixListener::bind(path)?
+            }
+            Err(error) => return Err(error),
+        };
+        inner.set_nonblocking(true)?;
+
+        Ok(Listener {
+            inner,
+            path: path.to_path_buf(),
+        })
+    }
+
+    pub fn accept(&mut self) -> io::Result<Stream> {
+        let (stream, _) = self.inner.accept()?;
+        // Accepted sockets inherit the non-blocking mode on some platforms.
+        stream.set_nonblocking(false)?;
+        Ok(stream)
+    }
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        if let Err(error) = fs::remove_file(&self.path) {
+            debug!("Failed to remove IPC socket: {:?}", error);
+        }
+    }
+}
+
+// Removes a socket at `path` nobody listens on anymore. Fails if the socket is
+// still in use or if there's something else than a socket at `path`.
+fn remove_stale_socket(path: &Path) -> io::Result<()> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            io::ErrorKind::AddrInUse,
+            "Another IPC listener is running",
+        ));
+    }
+    if !fs::symlink_metadata(path)?.file_type().is_socket() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "IPC socket path is taken",
+        ));
+    }
+
+    fs::remove_file(path)
+}
+
+// Returns the directory for the sockets of the current user: `XDG_RUNTIME_DIR`
+// if it's set, otherwise a directory in the temp dir which is created if needed.
+// Either has to be owned by the user and inaccessible to anyone else.
+#[allow(unsafe_code)]
+fn runtime_dir() -> io::Result<PathBuf> {
+    let uid = unsafe { getuid() };
+
+    let dir = match env::var_os("XDG_RUNTIME_DIR") {
+        Some(ref dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => {
+            let dir = env::temp_dir().join(format!("safe-{}", uid));
+            match DirBuilder::new().mode(0o700).create(&dir) {
+                Ok(()) => (),
+                Err(ref error) if error.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(error) => return Err(error),
+            }
+            dir
+        }
+    };
+
+    let metadata = fs::symlink_metadata(&dir)?;
+    if !metadata.is_dir() || metadata.uid() != uid || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("IPC socket directory {:?} isn't private", dir),
+        ));
+    }
+
+    Ok(dir)
+}
+
+extern "C" {
+    fn getuid() -> u32;
+}
@@ -0,0 +1,391 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Named pipe implementation of the local IPC transport. Pipes are created with
+//! the default security descriptor, which grants write access only to the
+//! user who created them, and reject remote clients.
+
+use std::env;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::os::raw::c_void;
+use std::os::windows::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::Duration;
+
+const PIPE_PREFIX: &'static str = r"\\.\pipe\";
+
+pub fn socket_path(name: &str) -> io::Result<PathBuf> {
+    let user = env::var("USERNAME").map_err(|_| {
+        io::Error::new(io::ErrorKind::NotFound, "Unknown user name")
+    })?;
+    Ok(PathBuf::from(format!("{}{}-{}", PIPE_PREFIX, name, user)))
+}
+
+pub fn connect(path: &Path) -> io::Result<Stream> {
+    let name = wide(path);
+
+    loop {
+        let handle = unsafe {
+            CreateFileW(
+                name.as_ptr(),
+                GENERIC_READ | GENERIC_WRITE,
+                0,
+                ptr::null_mut(),
+                OPEN_EXISTING,
+                0,
+                ptr::null_mut(),
+            )
+        };
+        if handle != INVALID_HANDLE_VALUE {
+            return Ok(Stream::new(Handle(handle), None));
+        }
+
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() != Some(ERROR_PIPE_BUSY) {
+            return Err(error);
+        }
+        // All instances of the pipe are busy; wait for one to become available.
+        if unsafe { WaitNamedPipeW(name.as_ptr(), NMPWAIT_USE_DEFAULT_WAIT) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+    }
+}
+
+pub fn set_timeout(stream: &mut Stream, timeout: Duration) -> io::Result<()> {
+    let millis = timeout.as_secs() * 1000 + u64::from(timeout.subsec_nanos() / 1_000_000);
+    stream.timeout = if millis < u64::from(INFINITE) {
+        millis as DWORD
+    } else {
+        INFINITE - 1
+    };
+    Ok(())
+}
+
+// Wakes up the accept loop blocked waiting for a connection.
+pub fn wake(path: &Path) {
+    if let Err(error) = connect(path) {
+        debug!("Failed to stop IPC listener: {:?}", error);
+    }
+}
+
+pub struct Listener {
+    name: Vec<u16>,
+    // The pipe instance waiting for the next connection. There's always one, so
+    // the pipe name can't be taken over while the listener is running.
+    next: Handle,
+}
+
+impl Listener {
+    pub fn bind(path: &Path) -> io::Result<Self> {
+        let name = wide(path);
+        let next = create_instance(&name, true).map_err(|error| {
+            if error.raw_os_error() == Some(ERROR_ACCESS_DENIED) {
+                io::Error::new(io::ErrorKind::AddrInUse, "Another IPC listener is running")
+            } else {
+                error
+            }
+        })?;
+
+        Ok(Listener { name, next })
+    }
+
+    pub fn accept(&mut self) -> io::Result<Stream> {
+        let event = create_event()?;
+        let mut overlapped = Overlapped::new(&event);
+
+        if unsafe { ConnectNamedPipe(self.next.0, &mut overlapped) } == FALSE {
+            let error = io::Error::last_os_error();
+            match error.raw_os_error() {
+                Some(ERROR_PIPE_CONNECTED) => (),
+                Some(ERROR_IO_PENDING) => {
+                    let _ = wait_overlapped(&self.next, &mut overlapped, INFINITE)?;
+                }
+                _ => return Err(error),
+            }
+        }
+
+        let next = create_instance(&self.name, false)?;
+        let handle = mem::replace(&mut self.next, next);
+        Ok(Stream::new(handle, Some(event)))
+    }
+}
+
+/// A connected pipe. Pipes accepted by the listener are overlapped, so their
+/// operations can time out.
+pub struct Stream {
+    handle: Handle,
+    event: Option<Handle>,
+    timeout: DWORD,
+}
+
+impl Stream {
+    fn new(handle: Handle, event: Option<Handle>) -> Self {
+        Stream {
+            handle,
+            event,
+            timeout: INFINITE,
+        }
+    }
+
+    fn io<F>(&self, op: F) -> io::Result<usize>
+    where
+        F: FnOnce(HANDLE, *mut DWORD, *mut Overlapped) -> BOOL,
+    {
+        let mut transferred: DWORD = 0;
+
+        let event = match self.event {
+            Some(ref event) => event,
+            None => {
+                if op(self.handle.0, &mut transferred as *mut _, ptr::null_mut()) == FALSE {
+                    return Err(io::Error::last_os_error());
+                }
+                return Ok(transferred as usize);
+            }
+        };
+
+        let mut overlapped = Overlapped::new(event);
+        if op(self.handle.0, &mut transferred as *mut _, &mut overlapped as *mut _) == FALSE {
+            let error = io::Error::last_os_error();
+            if error.raw_os_error() != Some(ERROR_IO_PENDING) {
+                return Err(error);
+            }
+        }
+
+        wait_overlapped(&self.handle, &mut overlapped, self.timeout)
+    }
+}
+
+impl Read for Stream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf.len() as DWORD;
+        let res = self.io(|handle, read, overlapped| unsafe {
+            ReadFile(handle, buf.as_mut_ptr() as *mut c_void, len, read, overlapped)
+        });
+
+        match res {
+            // The other end has closed the pipe.
+            Err(ref error) if error.raw_os_error() == Some(ERROR_BROKEN_PIPE) => Ok(0),
+            res => res,
+        }
+    }
+}
+
+impl Write for Stream {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let len = buf.len() as DWORD;
+        self.io(|handle, written, overlapped| unsafe {
+            WriteFile(handle, buf.as_ptr() as *const c_void, len, written, overlapped)
+        })
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        // Blocks until the other end has read everything, so the data isn't lost
+        // when the pipe is closed right after.
+        if unsafe { FlushFileBuffers(self.handle.0) } == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+// Owned Win32 handle, closed when dropped.
+struct Handle(HANDLE);
+
+unsafe impl Send for Handle {}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let _ = unsafe { CloseHandle(self.0) };
+    }
+}
+
+fn create_instance(name: &[u16], first: bool) -> io::Result<Handle> {
+    let mut open_mode = PIPE_ACCESS_DUPLEX | FILE_FLAG_OVERLAPPED;
+    if first {
+        open_mode |= FILE_FLAG_FIRST_PIPE_INSTANCE;
+    }
+
+    let handle = unsafe {
+        CreateNamedPipeW(
+            name.as_ptr(),
+            open_mode,
+            PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_UNLIMITED_INSTANCES,
+            BUFFER_SIZE,
+            BUFFER_SIZE,
+            0,
+            ptr::null_mut(),
+        )
+    };
+    if handle == INVALID_HANDLE_VALUE {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Handle(handle))
+}
+
+fn create_event() -> io::Result<Handle> {
+    let handle = unsafe { CreateEventW(ptr::null_mut(), TRUE, FALSE, ptr::null()) };
+    if handle.is_null() {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(Handle(handle))
+}
+
+// Waits for an overlapped operation to complete. If it doesn't within `timeout`
+// milliseconds, it's cancelled.
+fn wait_overlapped(
+    handle: &Handle,
+    overlapped: &mut Overlapped,
+    timeout: DWORD,
+) -> io::Result<usize> {
+    let mut transferred = 0;
+
+    unsafe {
+        if WaitForSingleObject(overlapped.event, timeout) != WAIT_OBJECT_0 {
+            let _ = CancelIoEx(handle.0, overlapped);
+            // The buffers are in use until the cancellation completes.
+            let _ = GetOverlappedResult(handle.0, overlapped, &mut transferred, TRUE);
+            return Err(io::Error::new(
+                io::ErrorKind::TimedOut,
+                "IPC pipe operation timed out",
+            ));
+        }
+
+        if GetOverlappedResult(handle.0, overlapped, &mut transferred, FALSE) == FALSE {
+            return Err(io::Error::last_os_error());
+        }
+    }
+
+    Ok(transferred as usize)
+}
+
+fn wide(path: &Path) -> Vec<u16> {
+    path.as_os_str().encode_wide().chain(Some(0)).collect()
+}
+
+type BOOL = i32;
+type DWORD = u32;
+type HANDLE = *mut c_void;
+
+const FALSE: BOOL = 0;
+const TRUE: BOOL = 1;
+const INVALID_HANDLE_VALUE: HANDLE = !0usize as HANDLE;
+const INFINITE: DWORD = 0xFFFF_FFFF;
+const WAIT_OBJECT_0: DWORD = 0;
+
+const GENERIC_READ: DWORD = 0x8000_0000;
+const GENERIC_WRITE: DWORD = 0x4000_0000;
+const OPEN_EXISTING: DWORD = 3;
+
+const PIPE_ACCESS_DUPLEX: DWORD = 0x0000_0003;
+const FILE_FLAG_FIRST_PIPE_INSTANCE: DWORD = 0x0008_0000;
+const FILE_FLAG_OVERLAPPED: DWORD = 0x4000_0000;
+const PIPE_TYPE_BYTE: DWORD = 0;
+const PIPE_READMODE_BYTE: DWORD = 0;
+const PIPE_WAIT: DWORD = 0;
+const PIPE_REJECT_REMOTE_CLIENTS: DWORD = 0x0000_0008;
+const PIPE_UNLIMITED_INSTANCES: DWORD = 255;
+const NMPWAIT_USE_DEFAULT_WAIT: DWORD = 0;
+const BUFFER_SIZE: DWORD = 64 * 1024;
+
+const ERROR_ACCESS_DENIED: i32 = 5;
+const ERROR_BROKEN_PIPE: i32 = 109;
+const ERROR_PIPE_BUSY: i32 = 231;
+const ERROR_PIPE_CONNECTED: i32 = 535;
+const ERROR_IO_PENDING: i32 = 997;
+
+// The fields are only read by the system.
+#[allow(dead_code)]
+#[repr(C)]
+struct Overlapped {
+    internal: usize,
+    internal_high: usize,
+    offset: DWORD,
+    offset_high: DWORD,
+    event: HANDLE,
+}
+
+impl Overlapped {
+    fn new(event: &Handle) -> Self {
+        Overlapped {
+            internal: 0,
+            internal_high: 0,
+            offset: 0,
+            offset_high: 0,
+            event: event.0,
+        }
+    }
+}
+
+#[link(name = "kernel32")]
+extern "system" {
+    fn CreateNamedPipeW(
+        name: *const u16,
+        open_mode: DWORD,
+        pipe_mode: DWORD,
+        max_instances: DWORD,
+        out_buffer_size: DWORD,
+        in_buffer_size: DWORD,
+        default_timeout: DWORD,
+        security_attributes: *mut c_void,
+    ) -> HANDLE;
+    fn ConnectNamedPipe(pipe: HANDLE, overlapped: *mut Overlapped) -> BOOL;
+    fn WaitNamedPipeW(name: *const u16, timeout: DWORD) -> BOOL;
+    fn CreateFileW(
+        name: *const u16,
+        desired_access: DWORD,
+        share_mode: DWORD,
+        security_attributes: *mut c_void,
+        creation_disposition: DWORD,
+        flags_and_attributes: DWORD,
+        template_file: HANDLE,
+    ) -> HANDLE;
+    fn ReadFile(
+        file: HANDLE,
+        buffer: *mut c_void,
+        len: DWORD,
+        read: *mut DWORD,
+        overlapped: *mut Overlapped,
+    ) -> BOOL;
+    fn WriteFile(
+        file: HANDLE,
+        buffer: *const c_void,
+        len: DWORD,
+        written: *mut DWORD,
+        overlapped: *mut Overlapped,
+    ) -> BOOL;
+    fn FlushFileBuffers(file: HANDLE) -> BOOL;
+    fn CloseHandle(handle: HANDLE) -> BOOL;
+    fn CreateEventW(
+        security_attributes: *mut c_void,
+        manual_reset: BOOL,
+        initial_state: BOOL,
+        name: *const u16,
+    ) -> HANDLE;
+    fn WaitForSingleObject(handle: HANDLE, millis: DWORD) -> DWORD;
+    fn GetOverlappedResult(
+        file: HANDLE,
+        overlapped: *mut Overlapped,
+        transferred: *mut DWORD,
+        wait: BOOL,
+    ) -> BOOL;
+    fn CancelIoEx(file: HANDLE, overlapped: *mut Overlapped) -> BOOL;
+}
@@ -0,0 +1,126 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Helpers for carrying IPC messages in system URIs, e.g. `safe-auth://<payload>`.
+
+use super::{IpcError, IpcMsg, decode_msg, encode_msg};
+
+/// Scheme of the URIs used to send requests to the authenticator.
+pub const AUTH_URI_SCHEME: &'static str = "safe-auth";
+
+const SCHEME_PREFIX: &'static str = "safe-";
+
+/// Returns the scheme under which the app with the given id receives responses
+/// from the authenticator. App ids can contain characters not valid in URI
+/// schemes, so the id is hex encoded.
+pub fn app_uri_scheme(app_id: &str) -> String {
+    let mut scheme = SCHEME_PREFIX.to_owned();
+    for byte in app_id.as_bytes() {
+        scheme.push_str(&format!("{:02x}", byte));
+    }
+    scheme
+}
+
+/// Wraps an already encoded IPC message into a URI for the authenticator.
+pub fn to_auth_uri(payload: &str) -> String {
+    format!("{}://{}", AUTH_URI_SCHEME, payload)
+}
+
+/// Wraps an already encoded IPC message into a URI for the app with the given id.
+pub fn to_app_uri(app_id: &str, payload: &str) -> String {
+    format!("{}://{}", app_uri_scheme(app_id), payload)
+}
+
+/// Encodes `IpcMsg` into a URI for the authenticator.
+pub fn encode_auth_uri(msg: &IpcMsg) -> Result<String, IpcError> {
+    Ok(to_auth_uri(&encode_msg(msg)?))
+}
+
+/// Encodes `IpcMsg` into a URI for the app with the given id.
+pub fn encode_app_uri(app_id: &str, msg: &IpcMsg) -> Result<String, IpcError> {
+    Ok(to_app_uri(app_id, &encode_msg(msg)?))
+}
+
+/// Splits an IPC URI into its scheme and encoded payload. Both the
+/// `scheme://payload` and the `scheme:payload` forms are accepted, as some
+/// platforms drop or add slashes when passing URIs around.
+pub fn parse_uri(uri: &str) -> Result<(&str, &str), IpcError> {
+    let colon = uri.find(':').ok_or(IpcError::InvalidMsg)?;
+    let scheme = &uri[..colon];
+    if !scheme.starts_with(SCHEME_PREFIX) || scheme.len() == SCHEME_PREFIX.len() {
+        return Err(IpcError::InvalidMsg);
+    }
+
+    let payload = uri[colon + 1..].trim_left_matches('/').trim_right_matches(
+        '/',
+    );
+    if payload.is_empty() {
+        return Err(IpcError::InvalidMsg);
+    }
+
+    Ok((scheme, payload))
+}
+
+/// Decodes `IpcMsg` from a URI, regardless of its scheme.
+pub fn decode_uri(uri: &str) -> Result<IpcMsg, IpcError> {
+    let (_, payload) = parse_uri(uri)?;
+    decode_msg(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test wrapping messages into URIs and parsing them back.
+    #[test]
+    fn round_trip() {
+        let msg = IpcMsg::Revoked { app_id: "net.maidsafe.test".to_owned() };
+
+        let uri = unwrap!(encode_auth_uri(&msg));
+        assert!(uri.starts_with("safe-auth://"));
+        assert_eq!(unwrap!(decode_uri(&uri)), msg);
+
+        let uri = unwrap!(encode_app_uri("net.maidsafe.test", &msg));
+        let (scheme, _) = unwrap!(parse_uri(&uri));
+        assert_eq!(scheme, app_uri_scheme("net.maidsafe.test"));
+        assert_eq!(unwrap!(decode_uri(&uri)), msg);
+    }
+
+    // Test that app schemes only contain characters valid in URI schemes.
+    #[test]
+    fn app_scheme() {
+        let scheme = app_uri_scheme("Ünïcode app_id/1");
+        assert!(scheme.starts_with("safe-"));
+        assert!(scheme.chars().all(|c| match c {
+            'a'...'z' | '0'...'9' | '-' => true,
+            _ => false,
+        }));
+    }
+
+    // Test parsing the different URI forms.
+    #[test]
+    fn parse() {
+        assert_eq!(unwrap!(parse_uri("safe-auth://abc")), ("safe-auth", "abc"));
+        assert_eq!(unwrap!(parse_uri("safe-auth:abc")), ("safe-auth", "abc"));
+        assert_eq!(unwrap!(parse_uri("safe-auth://abc/")), ("safe-auth", "abc"));
+
+        assert_eq!(parse_uri("abc"), Err(IpcError::InvalidMsg));
+        assert_eq!(parse_uri("http://abc"), Err(IpcError::InvalidMsg));
+        assert_eq!(parse_uri("safe-://abc"), Err(IpcError::InvalidMsg));
+        assert_eq!(parse_uri("safe-auth://"), Err(IpcError::InvalidMsg));
+    }
+}
@@ -0,0 +1,172 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Public key directory.
+//!
+//! The owner of a public name can publish their public signing and encryption keys in the
+//! public ID `MutableData` of that name, under the `PUBLIC_KEYS_ENTRY_KEY` entry. Anyone who
+//! knows the public name can then look the keys up to encrypt messages for the owner or to
+//! verify their signatures, without exchanging the keys out of band.
+
+use client::Client;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryActions};
+use rust_sodium::crypto::{box_, sign};
+use utils::FutureExt;
+use {PUBLIC_ID_TAG, public_id_name};
+
+/// Key of the public ID entry holding the published keys.
+pub const PUBLIC_KEYS_ENTRY_KEY: &[u8] = b"_publicKeys";
+
+/// Public keys published under a public name.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct PublicKeys {
+    /// Public signing key.
+    pub sign_key: sign::PublicKey,
+    /// Public encryption key.
+    pub enc_key: box_::PublicKey,
+}
+
+impl PublicKeys {
+    /// Public keys of the given client.
+    pub fn of<T: 'static>(client: &Client<T>) -> Result<Self, CoreError> {
+        Ok(PublicKeys {
+            sign_key: client.public_signing_key()?,
+            enc_key: client.public_encryption_key()?,
+        })
+    }
+}
+
+/// Publishes `keys` under `public_name`, replacing the keys published before, if any.
+/// The public name has to be registered (see `dns::register`) and owned by the client.
+pub fn publish_keys<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+    keys: &PublicKeys,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let name = public_id_name(public_name);
+    let key = PUBLIC_KEYS_ENTRY_KEY.to_vec();
+    let content = fry!(serialise(keys));
+
+    client
+        .get_mdata_value(name, PUBLIC_ID_TAG, key.clone())
+        .then(move |res| match res {
+            Ok(value) => Ok(EntryActions::new().update(key, content, value.entry_version + 1)),
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                Ok(EntryActions::new().ins(key, content, 0))
+            }
+            Err(error) => Err(error),
+        })
+        .and_then(move |actions| c2.mutate_mdata_entries(name, PUBLIC_ID_TAG, actions.into()))
+        .into_box()
+}
+
+/// Looks up the keys published under `public_name`.
+/// Fails with `NoSuchData` if the public name isn't registered and with `NoSuchEntry`
+/// if its owner hasn't published any keys.
+pub fn lookup_keys<T: 'static>(
+    client: &Client<T>,
+    public_name: &str,
+) -> Box<CoreFuture<PublicKeys>> {
+    client
+        .get_mdata_value(
+            public_id_name(public_name),
+            PUBLIC_ID_TAG,
+            PUBLIC_KEYS_ENTRY_KEY.to_vec(),
+        )
+        .and_then(|value| {
+            // Empty entry means the keys have been withdrawn.
+            if value.content.is_empty() {
+                return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            Ok(deserialise(&value.content)?)
+        })
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use dns;
+    use utils::generate_random_string;
+    use utils::test_utils::random_client;
+
+    // Test publishing keys under a public name and looking them up from another account.
+    #[test]
+    fn publish_and_lookup() {
+        let public_name = unwrap!(generate_random_string(10));
+        let public_name2 = public_name.clone();
+
+        let keys = random_client(move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+
+            let keys = unwrap!(PublicKeys::of(client));
+
+            dns::register(client, &public_name)
+                .then(move |res| {
+                    let _ = unwrap!(res);
+                    lookup_keys(&c2, &public_name).then(move |res| {
+                        match res {
+                            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => (),
+                            res => panic!("Unexpected {:?}", res),
+                        }
+                        Ok::<_, CoreError>(public_name)
+                    })
+                })
+                .then(move |res| {
+                    let public_name = unwrap!(res);
+                    // Publishing twice replaces the keys.
+                    let (enc_key, _) = box_::gen_keypair();
+                    let old_keys = PublicKeys {
+                        sign_key: keys.sign_key,
+                        enc_key: enc_key,
+                    };
+                    publish_keys(&c3, &public_name, &old_keys).map(move |_| public_name)
+                })
+                .then(move |res| {
+                    let public_name = unwrap!(res);
+                    publish_keys(&c4, &public_name, &keys)
+                })
+                .map(move |_| keys)
+        });
+
+        random_client(move |client| {
+            lookup_keys(client, &public_name2).map(move |found| assert_eq!(found, keys))
+        });
+    }
+
+    // Test looking up keys of a public name nobody has registered.
+    #[test]
+    fn lookup_unregistered() {
+        let public_name = unwrap!(generate_random_string(10));
+
+        random_client(move |client| {
+            lookup_keys(client, &public_name).then(|res| -> Result<_, CoreError> {
+                match res {
+                    Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => (),
+                    res => panic!("Unexpected {:?}", res),
+                }
+                Ok(())
+            })
+        });
+    }
+}
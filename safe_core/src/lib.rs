@@ -27,7 +27,7 @@
 //! ```
 //!
 //! If set, switch off mutations limit in mock-vault. If `safe_core` is built with
-//! `--features=use-mock-routing`, then setting this option will allow an unlimited number of
+//! `--features=mock-vault`, then setting this option will allow an unlimited number of
 //! mutations. `safe_core` does not need to be rebuilt for this to take effect.
 //!
 //! ```ignore
@@ -35,7 +35,7 @@
 //! ```
 //!
 //! If set, use memory store instead of file store in mock-vault. If `safe_core` is built with
-//! `--features=use-mock-routing`, then setting this option will use mock-vault's memory store,
+//! `--features=mock-vault`, then setting this option will use mock-vault's memory store,
 //! which is faster than reading/writing to disk. `safe_core` does not need to be rebuilt for this
 //! to take effect.
 //!
@@ -46,6 +46,17 @@
 //! If this is set and file storage is being used (`mock_in_memory_storage` is `false`), use this as
 //! the path for mock-vault.
 //!
+//! ```ignore
+//! SAFE_TEST_NETWORK
+//! ```
+//!
+//! Selects the network the clients connect to: `live` for the network given by the bootstrap
+//! config, `mock` for the mock-vault, which is only available if `safe_core` is built with
+//! `--features=mock-vault` (implied by `use-mock-routing`). Builds with `use-mock-routing` default
+//! to `mock`, all the others to `live`. Tests relying on mock-only features, such as request hooks,
+//! are skipped on the live network. Test accounts are created with the invitation in
+//! `SAFE_TEST_INVITATION`, if set. Calling `set_network_mode` overrides this option.
+//!
 //! # Config
 //!
 //! You can create a config file with custom options following the example in `sample_config/`. The
@@ -56,7 +67,7 @@
 //! ```
 //!
 //! If true, switch off mutations limit in mock-vault. If `safe_core` is built with
-//! `--features=use-mock-routing`, then setting this option will allow an unlimited number of
+//! `--features=mock-vault`, then setting this option will allow an unlimited number of
 //! mutations. `safe_core` does not need to be rebuilt for this to take effect. The default value is
 //! false.
 //!
@@ -65,7 +76,7 @@
 //! ```
 //!
 //! If true, use memory store instead of file store in mock-vault. If `safe_core` is built with
-//! `--features=use-mock-routing`, then setting this option will use mock-vault's memory store,
+//! `--features=mock-vault`, then setting this option will use mock-vault's memory store,
 //! which is faster than reading/writing to disk. `safe_core` does not need to be rebuilt for this
 //! to take effect. The default value is false.
 //!
@@ -75,6 +86,21 @@
 //!
 //! If this variable is set and file storage is being used (`mock_in_memory_storage` is `false`),
 //! use this as the path for mock-vault.
+//!
+//! ```ignore
+//! network_mode
+//! ```
+//!
+//! Either `"mock"` or `"live"`, same as the `SAFE_TEST_NETWORK` environment variable.
+//!
+//! # Client settings
+//!
+//! Timeouts, cache sizes, concurrency limits and the default log levels of the clients are
+//! described by `config_handler::ClientConfig`. Each setting can be given in the `client` section
+//! of the config file, in a `SAFE_CLIENT_<SETTING>` environment variable (e.g.
+//! `SAFE_CLIENT_REQUEST_TIMEOUT_SECS`) and programmatically with
+//! `config_handler::set_client_config_overrides`, each source taking precedence over the previous
+//! one. The settings are read when a client is created.
 
 #![doc(html_logo_url =
            "https://raw.githubusercontent.com/maidsafe/QA/master/Images/maidsafe_logo.png",
@@ -101,31 +127,42 @@
                                          option_unwrap_used))]
 #![cfg_attr(feature="cargo-clippy", allow(implicit_hasher, too_many_arguments, use_debug))]
 
+// `fast-kdf` weakens the password key derivation of every account, so it has to be
+// enabled explicitly, and only for tests.
+#[cfg(all(feature = "fast-kdf", not(any(test, feature = "testing"))))]
+compile_error!("The `fast-kdf` feature is only meant for tests");
+
+extern crate argon2;
 extern crate base64;
 extern crate chrono;
 extern crate config_file_handler;
 extern crate ffi_utils;
-#[cfg(feature = "use-mock-routing")]
+extern crate flate2;
+#[cfg(feature = "mock-vault")]
 extern crate fs2;
 extern crate futures;
-#[cfg(feature = "use-mock-routing")]
 #[macro_use]
 extern crate lazy_static;
 #[macro_use]
 extern crate log;
 extern crate lru_cache;
 extern crate maidsafe_utilities;
+#[cfg(all(test, feature = "mock-vault"))]
+extern crate quickcheck;
 extern crate rand;
 extern crate routing;
 extern crate serde;
+extern crate serde_cbor;
 #[macro_use]
 extern crate serde_derive;
-#[cfg(test)]
+#[cfg(any(test, feature = "testing"))]
 extern crate serde_json;
 extern crate rust_sodium;
 extern crate self_encryption;
 extern crate tiny_keccak;
 extern crate tokio_core;
+#[cfg(all(feature = "mock-vault", any(test, feature = "testing")))]
+extern crate toml;
 #[macro_use]
 extern crate unwrap;
 
@@ -141,40 +178,106 @@ pub use ffi::nfs::*;
 #[macro_use]
 pub mod utils;
 
+/// Append-only logs.
+pub mod append_log;
+/// Sharded key-value store spanning multiple `MutableData`.
+pub mod big_map;
+/// Address book shared between apps.
+pub mod contacts;
 /// Config file handling.
 pub mod config_handler;
+/// Conflict-free replicated data types stored in `MutableData` entries.
+pub mod crdt;
 /// Cryptographic utilities.
 pub mod crypto;
+/// Estimates of the mutations operations consume.
+pub mod cost;
+/// Access control for individual `MutableData` entries.
+pub mod entry_acl;
+/// Public names and their services.
+pub mod dns;
 /// Event loop handling.
 pub mod event_loop;
 /// Utilities for handling `ImmutableData`.
 pub mod immutable_data;
+/// Inboxes for receiving encrypted messages.
+pub mod inbox;
 /// Inter-Process Communication utilities.
 pub mod ipc;
+/// Public keys published under public names.
+pub mod key_directory;
+/// Client-side checks of data size limits.
+pub mod limits;
+/// `MutableData` values which overflow into `ImmutableData`.
+pub mod mdata_value;
+/// `MutableData` owned jointly by several keys, with advisory (client-side) multi-signature
+/// agreement on owner-level requests.
+pub mod multisig;
 /// NFS utilities.
 pub mod nfs;
+/// Detection of orphaned `ImmutableData`.
+pub mod orphans;
+/// Client-side search indices.
+pub mod search;
 /// Implements the Self Encryption storage trait.
 pub mod self_encryption_storage;
+/// Read-only snapshots of `MutableData`.
+pub mod snapshot;
+/// Notification topics.
+pub mod topic;
+/// Ledger of `ImmutableData` uploads.
+pub mod upload_ledger;
+/// Fetching of `safe://` URLs.
+pub mod web;
 
 mod client;
 mod errors;
 mod event;
 
-pub use self::client::{Client, ClientKeys, MDataInfo, mdata_info, recovery};
-#[cfg(feature = "use-mock-routing")]
+pub use self::client::{Client, ClientKeys, Fetched, KeyOp, KeyStore, MDataInfo,
+                       MemoryKeyStore, NetworkMode, NetworkStats, Priority, RequestCounts,
+                       SchedulerLimits, mdata_info, network_mode, recovery, set_network_mode};
+#[cfg(feature = "use-host-routing")]
+pub use self::client::host_routing;
+#[cfg(feature = "mock-vault")]
 pub use self::client::{MockRouting, mock_vault_path};
+#[cfg(all(feature = "mock-vault", any(test, feature = "testing")))]
+pub use self::client::mock_fixture;
 pub use self::errors::CoreError;
 pub use self::event::{CoreEvent, NetworkEvent, NetworkRx, NetworkTx};
 pub use self::event_loop::{CoreFuture, CoreMsg, CoreMsgRx, CoreMsgTx};
 pub use self::self_encryption_storage::{SelfEncryptionStorage, SelfEncryptionStorageError};
 pub use self::utils::FutureExt;
+use routing::XorName;
+use tiny_keccak::sha3_256;
 
 /// All Maidsafe tagging should positive-offset from this.
 pub const MAIDSAFE_TAG: u64 = 5_483_000;
 /// `MutableData` type tag for a directory.
 pub const DIR_TAG: u64 = 15_000;
+/// `MutableData` type tag for a public ID.
+pub const PUBLIC_ID_TAG: u64 = 15_001;
+/// `MutableData` type tag for the services container of a public ID.
+pub const SERVICES_TAG: u64 = 15_002;
+/// `MutableData` type tag for an inbox.
+pub const INBOX_TAG: u64 = 15_003;
+/// `MutableData` type tag for a notification topic.
+pub const TOPIC_TAG: u64 = 15_004;
+/// `MutableData` type tag for the index and shards of a big map.
+pub const BIG_MAP_TAG: u64 = 15_005;
+/// `MutableData` type tag for an append-only log.
+pub const APPEND_LOG_TAG: u64 = 15_006;
+/// `MutableData` type tag for the backup copy of the account packet.
+pub const SESSION_PACKET_BACKUP_TAG: u64 = 15_008;
+/// `MutableData` type tag for a registry of revocable file shares.
+pub const SHARE_REGISTRY_TAG: u64 = 15_009;
 
 /// Gets name of the dedicated container of the given app.
 pub fn app_container_name(app_id: &str) -> String {
     format!("apps/{}", app_id)
 }
+
+/// Gets the network name of the public ID `MutableData` for the given public name.
+pub fn public_id_name(public_name: &str) -> XorName {
+    XorName(sha3_256(public_name.as_bytes()))
+}
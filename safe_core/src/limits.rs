@@ -0,0 +1,167 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Client-side checks of the size limits enforced by the vaults.
+//!
+//! Requests which would be rejected by the network for their size fail locally instead,
+//! without a round trip. Limits also enforced by the vaults fail with the same
+//! `ClientError` the vaults would return (`TooManyEntries`, `DataTooLarge`), the others
+//! with a specific `CoreError`.
+
+use errors::CoreError;
+use maidsafe_utilities::serialisation::serialised_size;
+use routing::{ClientError, EntryAction, MAX_MUTABLE_DATA_ENTRIES, MAX_MUTABLE_DATA_SIZE_IN_BYTES,
+              MutableData};
+use std::collections::BTreeMap;
+
+/// Maximum number of entries in a `MutableData`.
+pub const MAX_MDATA_ENTRIES: u64 = MAX_MUTABLE_DATA_ENTRIES;
+/// Maximum size of a serialised `MutableData`, in bytes.
+pub const MAX_MDATA_SIZE: u64 = MAX_MUTABLE_DATA_SIZE_IN_BYTES;
+/// Maximum size of a single entry (key and content), in bytes.
+pub const MAX_ENTRY_SIZE: u64 = MAX_MUTABLE_DATA_SIZE_IN_BYTES;
+/// Maximum size of the user metadata of a file, in bytes.
+///
+/// A file is stored as an entry of its parent directory, and all the files of a directory
+/// share its `MAX_MDATA_SIZE`. The metadata of one file is therefore capped at 1/64th of
+/// that (16 KiB with routing's 1 MiB limit), leaving room for its data map and for the
+/// other files of the directory.
+pub const MAX_USER_METADATA_SIZE: usize = (MAX_MDATA_SIZE / USER_METADATA_SHARE) as usize;
+
+const USER_METADATA_SHARE: u64 = 64;
+
+/// Checks the entry count, entry sizes and total size of the `MutableData`.
+pub fn check_mdata(data: &MutableData) -> Result<(), CoreError> {
+    let entries = data.entries();
+    if entries.len() as u64 > MAX_MDATA_ENTRIES {
+        return Err(CoreError::RoutingClientError(ClientError::TooManyEntries));
+    }
+
+    for (key, value) in entries {
+        check_entry(key, &value.content)?;
+    }
+
+    if serialised_size(data) > MAX_MDATA_SIZE {
+        return Err(CoreError::RoutingClientError(ClientError::DataTooLarge));
+    }
+
+    Ok(())
+}
+
+/// Checks the entries inserted or updated by the actions. The number of insertions is
+/// only checked against the limit on its own, as the current number of entries in the
+/// `MutableData` isn't known locally.
+pub fn check_entry_actions(actions: &BTreeMap<Vec<u8>, EntryAction>) -> Result<(), CoreError> {
+    let mut inserts = 0;
+
+    for (key, action) in actions {
+        match *action {
+            EntryAction::Ins(ref value) => {
+                inserts += 1;
+                check_entry(key, &value.content)?;
+            }
+            EntryAction::Update(ref value) => check_entry(key, &value.content)?,
+            EntryAction::Del(_) => (),
+        }
+    }
+
+    if inserts > MAX_MDATA_ENTRIES {
+        return Err(CoreError::RoutingClientError(ClientError::TooManyEntries));
+    }
+
+    Ok(())
+}
+
+/// Checks the size of a single entry.
+pub fn check_entry(key: &[u8], content: &[u8]) -> Result<(), CoreError> {
+    if (key.len() + content.len()) as u64 > MAX_ENTRY_SIZE {
+        Err(CoreError::EntryTooLarge)
+    } else {
+        Ok(())
+    }
+}
+
+/// Checks the size of file user metadata.
+pub fn check_user_metadata(metadata: &[u8]) -> Result<(), CoreError> {
+    if metadata.len() > MAX_USER_METADATA_SIZE {
+        Err(CoreError::UserMetadataTooLarge)
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use routing::{EntryActions, Value, XorName};
+
+    // Test that `MutableData` within the limits passes.
+    #[test]
+    fn mdata_within_limits() {
+        let name: XorName = ::rand::random();
+        let entries = btree_map![
+            b"key".to_vec() => Value { content: vec![0; 10], entry_version: 0 }
+        ];
+        let data = unwrap!(MutableData::new(name, 0, btree_map![], entries, btree_set![]));
+        unwrap!(check_mdata(&data));
+    }
+
+    // Test that actions exceeding the limits are rejected.
+    #[test]
+    fn entry_action_limits() {
+        let key = b"key".to_vec();
+        let large = vec![0; MAX_ENTRY_SIZE as usize];
+
+        let actions = EntryActions::new().ins(key.clone(), large.clone(), 0).into();
+        match check_entry_actions(&actions) {
+            Err(CoreError::EntryTooLarge) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let actions = EntryActions::new().update(key.clone(), large, 1).into();
+        match check_entry_actions(&actions) {
+            Err(CoreError::EntryTooLarge) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        let actions = EntryActions::new()
+            .ins(key.clone(), vec![0; 10], 0)
+            .del(b"other".to_vec(), 1)
+            .into();
+        unwrap!(check_entry_actions(&actions));
+
+        let actions = (0..MAX_MDATA_ENTRIES + 1)
+            .map(|i| {
+                let value = Value {
+                    content: vec![0; 10],
+                    entry_version: 0,
+                };
+                (format!("{}", i).into_bytes(), EntryAction::Ins(value))
+            })
+            .collect();
+        match check_entry_actions(&actions) {
+            Err(CoreError::RoutingClientError(ClientError::TooManyEntries)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        unwrap!(check_user_metadata(&[0; MAX_USER_METADATA_SIZE]));
+        match check_user_metadata(&[0; MAX_USER_METADATA_SIZE + 1]) {
+            Err(CoreError::UserMetadataTooLarge) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
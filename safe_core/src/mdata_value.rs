@@ -0,0 +1,227 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! `MutableData` values which overflow into `ImmutableData`.
+//!
+//! Values written through this module are wrapped: values up to `INLINE_VALUE_MAX_LEN`
+//! bytes are stored in the entry itself, larger ones are stored as `ImmutableData` and the
+//! entry only holds its name. Reading through this module resolves both transparently, so
+//! an entry never hits `DataTooLarge` just because its value grew. Wrapped values are not
+//! readable as raw bytes, so all readers of such entries have to use this module.
+
+use client::{Client, MDataInfo};
+use crypto::shared_secretbox;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use immutable_data;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{ClientError, EntryActions, Value, XorName};
+use utils::FutureExt;
+
+/// Maximum length of a value stored directly in the `MutableData` entry.
+pub const INLINE_VALUE_MAX_LEN: usize = 16 * 1024;
+
+#[derive(Serialize, Deserialize)]
+enum WrappedValue {
+    Inline(Vec<u8>),
+    ImmutableData(XorName),
+}
+
+/// Wraps `content` for storing in a `MutableData` entry, putting it to the network
+/// as `ImmutableData` if it's too large. The `ImmutableData` is encrypted with
+/// `encryption_key`, if given.
+pub fn wrap<T: 'static>(
+    client: &Client<T>,
+    content: Vec<u8>,
+    encryption_key: Option<shared_secretbox::Key>,
+) -> Box<CoreFuture<Vec<u8>>> {
+    if content.len() <= INLINE_VALUE_MAX_LEN {
+        return ok!(fry!(serialise(&WrappedValue::Inline(content))));
+    }
+
+    let c2 = client.clone();
+
+    immutable_data::create(client, &content, encryption_key)
+        .and_then(move |data| {
+            let name = *data.name();
+            c2.put_idata(data).map(move |_| name)
+        })
+        .and_then(|name| Ok(serialise(&WrappedValue::ImmutableData(name))?))
+        .into_box()
+}
+
+/// Resolves a value wrapped by `wrap` into its content.
+pub fn unwrap<T: 'static>(
+    client: &Client<T>,
+    wrapped: &[u8],
+    decryption_key: Option<shared_secretbox::Key>,
+) -> Box<CoreFuture<Vec<u8>>> {
+    match fry!(deserialise(wrapped)) {
+        WrappedValue::Inline(content) => ok!(content),
+        WrappedValue::ImmutableData(name) => {
+            immutable_data::get_value(client, &name, decryption_key)
+        }
+    }
+}
+
+/// Name of the `ImmutableData` a value wrapped by `wrap` is stored in, or `None` if the value
+/// is stored inline or isn't a wrapped value at all.
+pub fn idata_name(wrapped: &[u8]) -> Option<XorName> {
+    match deserialise(wrapped) {
+        Ok(WrappedValue::ImmutableData(name)) => Some(name),
+        Ok(WrappedValue::Inline(_)) | Err(_) => None,
+    }
+}
+
+/// Stores `content` under `key` in the `MutableData`, replacing the current value if
+/// there is one. The key and the value are encrypted according to `info`.
+pub fn put<T: 'static>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    key: &[u8],
+    content: Vec<u8>,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let info = info.clone();
+    let key = fry!(info.enc_entry_key(key));
+
+    wrap(client, content, info.enc_key().cloned())
+        .and_then(move |wrapped| {
+            let value = info.enc_entry_value(&wrapped)?;
+            Ok((info, value))
+        })
+        .and_then(move |(info, value)| {
+            c2.get_mdata_value(info.name, info.type_tag, key.clone())
+                .then(move |res| {
+                    let actions = match res {
+                        Ok(current) => {
+                            EntryActions::new().update(key, value, current.entry_version + 1)
+                        }
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                            EntryActions::new().ins(key, value, 0)
+                        }
+                        Err(error) => return Err(error),
+                    };
+                    Ok((info, actions))
+                })
+        })
+        .and_then(move |(info, actions)| {
+            c3.mutate_mdata_entries(info.name, info.type_tag, actions.into())
+        })
+        .into_box()
+}
+
+/// Gets the value stored under `key` in the `MutableData` by `put`, resolving its
+/// content if it has overflowed into `ImmutableData`.
+pub fn get<T: 'static>(
+    client: &Client<T>,
+    info: &MDataInfo,
+    key: &[u8],
+) -> Box<CoreFuture<Value>> {
+    let c2 = client.clone();
+    let info = info.clone();
+    let key = fry!(info.enc_entry_key(key));
+
+    client
+        .get_mdata_value(info.name, info.type_tag, key)
+        .and_then(move |value| {
+            // Empty entry means the value has been deleted.
+            if value.content.is_empty() {
+                return Err(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            let wrapped = info.decrypt(&value.content)?;
+            Ok((info, wrapped, value.entry_version))
+        })
+        .and_then(move |(info, wrapped, entry_version)| {
+            unwrap(&c2, &wrapped, info.enc_key().cloned()).map(move |content| {
+                Value {
+                    content,
+                    entry_version,
+                }
+            })
+        })
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use routing::MutableData;
+    use utils::generate_random_vector;
+    use utils::test_utils::random_client;
+
+    // Test that small values are stored inline and large ones overflow.
+    #[test]
+    fn inline_and_overflow() {
+        for &private in &[false, true] {
+            random_client(move |client| {
+                let c2 = client.clone();
+                let c3 = client.clone();
+                let c4 = client.clone();
+                let c5 = client.clone();
+                let c6 = client.clone();
+
+                let info = if private {
+                    unwrap!(MDataInfo::random_private(DIR_TAG))
+                } else {
+                    unwrap!(MDataInfo::random_public(DIR_TAG))
+                };
+                let large = unwrap!(generate_random_vector::<u8>(INLINE_VALUE_MAX_LEN + 1));
+                let large2 = large.clone();
+
+                let owner_key = unwrap!(client.owner_key());
+                let md = unwrap!(MutableData::new(
+                    info.name,
+                    info.type_tag,
+                    btree_map![],
+                    btree_map![],
+                    btree_set![owner_key],
+                ));
+
+                client
+                    .put_mdata(md)
+                    .and_then(move |_| {
+                        put(&c2, &info, b"small", b"value".to_vec()).map(move |_| info)
+                    })
+                    .and_then(move |info| {
+                        put(&c3, &info, b"large", large).map(move |_| info)
+                    })
+                    .and_then(move |info| {
+                        let key = unwrap!(info.enc_entry_key(b"large"));
+                        c4.get_mdata_value(info.name, info.type_tag, key)
+                            .map(move |raw| (info, raw))
+                    })
+                    .and_then(move |(info, raw)| {
+                        // Only the pointer is stored in the entry.
+                        assert!(raw.content.len() < INLINE_VALUE_MAX_LEN);
+                        get(&c5, &info, b"large").map(move |value| (info, value))
+                    })
+                    .and_then(move |(info, value)| {
+                        assert_eq!(value.content, large2);
+                        get(&c6, &info, b"small")
+                    })
+                    .map(|value| {
+                        assert_eq!(value.content, b"value");
+                        assert_eq!(value.entry_version, 0);
+                    })
+            });
+        }
+    }
+}
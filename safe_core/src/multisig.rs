@@ -0,0 +1,266 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Shared custody of `MutableData`.
+//!
+//! A `MutableData` can list several owner keys, e.g. those of two identities holding the data
+//! jointly. The network authorises owner-level requests (managing permissions and transferring
+//! ownership) from any single owner, so the co-owners agree on such a request off-network: it
+//! is described by an `OwnerRequest`, passed around in its serialised form, signed by every
+//! owner and only submitted once the signatures of all the other owners are attached.
+//!
+//! The signatures are an agreement between co-operating owners, not an access control: they're
+//! only checked by `submit`, and the network doesn't see them. Any single owner can still
+//! mutate the data directly, without the consent of the others, so only share ownership with
+//! keys trusted to follow the protocol.
+
+use client::Client;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use maidsafe_utilities::serialisation::serialise;
+use routing::{MutableData, PermissionSet, User, Value, XorName};
+use rust_sodium::crypto::sign;
+use std::collections::{BTreeMap, BTreeSet};
+use utils::FutureExt;
+
+/// Owner-level operation on a `MutableData`.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub enum OwnerOp {
+    /// Set the permissions of `user`.
+    SetUserPermissions {
+        /// User whose permissions are set.
+        user: User,
+        /// New permissions of the user.
+        permissions: PermissionSet,
+    },
+    /// Delete the permissions of `user`.
+    DelUserPermissions {
+        /// User whose permissions are deleted.
+        user: User,
+    },
+    /// Transfer the data to a single new owner.
+    ChangeOwner {
+        /// Key of the new owner.
+        new_owner: sign::PublicKey,
+    },
+}
+
+/// Owner-level operation together with the signatures of the owners agreeing to it.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct OwnerRequest {
+    /// Name of the data.
+    pub name: XorName,
+    /// Type tag of the data.
+    pub tag: u64,
+    /// Version the operation is valid for (current version of the data + 1).
+    pub version: u64,
+    /// The operation itself.
+    pub op: OwnerOp,
+    signatures: BTreeMap<sign::PublicKey, sign::Signature>,
+}
+
+impl OwnerRequest {
+    /// Creates a request which is not signed by anyone yet.
+    pub fn new(name: XorName, tag: u64, version: u64, op: OwnerOp) -> Self {
+        OwnerRequest {
+            name,
+            tag,
+            version,
+            op,
+            signatures: BTreeMap::new(),
+        }
+    }
+
+    /// Bytes the owners sign.
+    pub fn payload(&self) -> Result<Vec<u8>, CoreError> {
+        Ok(serialise(&(&self.name, self.tag, self.version, &self.op))?)
+    }
+
+    /// Signs the request with the given owner key, replacing any previous signature by it.
+    pub fn sign(
+        &mut self,
+        public_key: sign::PublicKey,
+        secret_key: &sign::SecretKey,
+    ) -> Result<(), CoreError> {
+        let signature = sign::sign_detached(&self.payload()?, secret_key);
+        self.attach(public_key, signature)
+    }
+
+    /// Attaches a signature made by the owner of `public_key` elsewhere.
+    /// Fails if the signature doesn't match the request.
+    pub fn attach(
+        &mut self,
+        public_key: sign::PublicKey,
+        signature: sign::Signature,
+    ) -> Result<(), CoreError> {
+        if !sign::verify_detached(&signature, &self.payload()?, &public_key) {
+            return Err(CoreError::from("Invalid owner signature"));
+        }
+
+        let _ = self.signatures.insert(public_key, signature);
+        Ok(())
+    }
+
+    /// Keys which have signed the request.
+    pub fn signers(&self) -> BTreeSet<sign::PublicKey> {
+        self.signatures.keys().cloned().collect()
+    }
+
+    /// Owners from `owners` whose signature is still needed before `submitter` can send the
+    /// request. The submitter's own consent is implied by sending it.
+    pub fn missing_signers(
+        &self,
+        owners: &BTreeSet<sign::PublicKey>,
+        submitter: &sign::PublicKey,
+    ) -> BTreeSet<sign::PublicKey> {
+        owners
+            .iter()
+            .filter(|owner| *owner != submitter && !self.signatures.contains_key(owner))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Puts a new `MutableData` owned jointly by the client's owner key and `co_owners`.
+pub fn put_mdata<T: 'static>(
+    client: &Client<T>,
+    name: XorName,
+    tag: u64,
+    permissions: BTreeMap<User, PermissionSet>,
+    entries: BTreeMap<Vec<u8>, Value>,
+    co_owners: BTreeSet<sign::PublicKey>,
+) -> Box<CoreFuture<()>> {
+    let mut owners = co_owners;
+    let _ = owners.insert(fry!(client.owner_key()));
+
+    let data = fry!(MutableData::new(name, tag, permissions, entries, owners).map_err(
+        CoreError::from,
+    ));
+    client.put_mdata(data)
+}
+
+/// Submits `request` after checking that every owner of the data other than the client has
+/// signed it. Fails with `MissingOwnerSignatures` otherwise.
+///
+/// The check is made by the client only; the network accepts owner-level requests from any
+/// single owner regardless of the signatures.
+pub fn submit<T: 'static>(client: &Client<T>, request: OwnerRequest) -> Box<CoreFuture<()>> {
+    let client2 = client.clone();
+    let submitter = fry!(client.owner_key());
+
+    client
+        .get_mdata_shell(request.name, request.tag)
+        .and_then(move |shell| {
+            if !shell.owners().contains(&submitter) {
+                return Err(CoreError::from("Client is not an owner of the data"));
+            }
+            if !request.missing_signers(shell.owners(), &submitter).is_empty() {
+                return Err(CoreError::MissingOwnerSignatures);
+            }
+            Ok(request)
+        })
+        .and_then(move |request| {
+            let OwnerRequest {
+                name, tag, version, op, ..
+            } = request;
+
+            match op {
+                OwnerOp::SetUserPermissions { user, permissions } => {
+                    client2.set_mdata_user_permissions(name, tag, user, permissions, version)
+                }
+                OwnerOp::DelUserPermissions { user } => {
+                    client2.del_mdata_user_permissions(name, tag, user, version)
+                }
+                OwnerOp::ChangeOwner { new_owner } => {
+                    client2.change_mdata_owner(name, tag, new_owner, version)
+                }
+            }
+        })
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand;
+    use routing::Action;
+    use utils::test_utils::random_client;
+
+    // Data owned by the client and a second identity can only have its permissions changed
+    // once the second owner has signed the request.
+    #[test]
+    fn shared_custody() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+
+            let (co_owner, co_owner_sk) = sign::gen_keypair();
+            let (user, _) = sign::gen_keypair();
+            let name = rand::random();
+            let tag = 15_000;
+
+            let request = OwnerRequest::new(
+                name,
+                tag,
+                1,
+                OwnerOp::SetUserPermissions {
+                    user: User::Key(user),
+                    permissions: PermissionSet::new().allow(Action::Insert),
+                },
+            );
+            let mut signed = request.clone();
+            unwrap!(signed.sign(co_owner, &co_owner_sk));
+
+            // A signature by someone else is refused.
+            let (other, _) = sign::gen_keypair();
+            let mut forged = request.clone();
+            let signature = sign::sign_detached(&unwrap!(request.payload()), &co_owner_sk);
+            assert!(forged.attach(other, signature).is_err());
+
+            put_mdata(
+                client,
+                name,
+                tag,
+                Default::default(),
+                Default::default(),
+                btree_set![co_owner],
+            ).then(move |res| {
+                    unwrap!(res);
+                    submit(&client2, request)
+                })
+                .then(move |res| {
+                    match res {
+                        Err(CoreError::MissingOwnerSignatures) => (),
+                        x => panic!("Unexpected {:?}", x),
+                    }
+                    submit(&client3, signed)
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    client4.list_mdata_user_permissions(name, tag, User::Key(user))
+                })
+                .then(|res| -> Result<_, CoreError> {
+                    let permissions = unwrap!(res);
+                    assert_eq!(permissions.is_allowed(Action::Insert), Some(true));
+                    assert_eq!(permissions.is_allowed(Action::Delete), None);
+                    Ok(())
+                })
+        })
+    }
+}
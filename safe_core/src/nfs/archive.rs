@@ -0,0 +1,170 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Archives packing many files into a single self-encrypted blob.
+//!
+//! Publishing thousands of tiny files one by one costs a data map `ImmutableData` and several
+//! round trips per file. An archive is instead written as the content of a single `File`: a
+//! little-endian `u64` holding the length of the serialised index, the index itself (mapping
+//! paths to ranges of the data section) and the data section with the contents of all the
+//! files. As the blob is self-encrypted, extracting a single file only fetches the chunks
+//! spanning the index and that file.
+
+use client::Client;
+use crypto::shared_secretbox;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use nfs::{File, Mode, NfsError, NfsFuture, Reader, file_helper};
+use std::collections::BTreeMap;
+use utils::FutureExt;
+
+const INDEX_LEN_SIZE: u64 = 8;
+
+// Offset into the data section and length of a file.
+type Index = BTreeMap<String, (u64, u64)>;
+
+/// Collects files to be packed into an archive.
+#[derive(Default)]
+pub struct ArchiveWriter {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl ArchiveWriter {
+    /// Create an empty archive writer.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Add a file to the archive, replacing a file previously added under the same path.
+    pub fn add(&mut self, path: &str, content: Vec<u8>) {
+        let _ = self.files.insert(path.to_owned(), content);
+    }
+
+    /// Pack the files and store the archive on the network. The returned `File`
+    /// can be inserted into a directory like any other file.
+    pub fn finish<T: 'static>(
+        self,
+        client: &Client<T>,
+        encryption_key: Option<shared_secretbox::Key>,
+    ) -> Box<NfsFuture<File>> {
+        let mut index = Index::new();
+        let mut offset = 0;
+        for (path, content) in &self.files {
+            let _ = index.insert(path.clone(), (offset, content.len() as u64));
+            offset += content.len() as u64;
+        }
+
+        let index = fry!(serialise(&index));
+        let mut blob = Vec::with_capacity(INDEX_LEN_SIZE as usize + index.len() + offset as usize);
+        blob.extend_from_slice(&encode_len(index.len() as u64));
+        blob.extend_from_slice(&index);
+        for content in self.files.values() {
+            blob.extend_from_slice(content);
+        }
+
+        file_helper::write(
+            client.clone(),
+            File::new(Vec::new()),
+            Mode::Overwrite,
+            encryption_key,
+        ).and_then(move |writer| writer.write(&blob).map(move |_| writer))
+            .and_then(|writer| writer.close())
+            .into_box()
+    }
+}
+
+/// Archive opened for reading.
+pub struct Archive<T> {
+    reader: Reader<T>,
+    index: Index,
+    data_offset: u64,
+}
+
+impl<T: 'static> Archive<T> {
+    /// Open an archive stored in `file`. Only the index is fetched.
+    pub fn open(
+        client: &Client<T>,
+        file: &File,
+        encryption_key: Option<shared_secretbox::Key>,
+    ) -> Box<NfsFuture<Archive<T>>> {
+        file_helper::read(client.clone(), file, encryption_key)
+            .and_then(|reader| {
+                if reader.size() < INDEX_LEN_SIZE {
+                    return err!(NfsError::from("Invalid archive"));
+                }
+                reader
+                    .read(0, INDEX_LEN_SIZE)
+                    .map(move |len| (reader, decode_len(&len)))
+                    .into_box()
+            })
+            .and_then(|(reader, index_len)| {
+                // The length comes from the file, so it mustn't be trusted to be in range.
+                let data_offset = match INDEX_LEN_SIZE.checked_add(index_len) {
+                    Some(data_offset) if data_offset <= reader.size() => data_offset,
+                    _ => return err!(NfsError::from("Invalid archive")),
+                };
+                reader
+                    .read(INDEX_LEN_SIZE, index_len)
+                    .and_then(move |index| {
+                        Ok(Archive {
+                            reader,
+                            index: deserialise(&index)?,
+                            data_offset,
+                        })
+                    })
+                    .into_box()
+            })
+            .into_box()
+    }
+
+    /// Paths of all the files in the archive, sorted.
+    pub fn paths(&self) -> Vec<&str> {
+        self.index.keys().map(|path| path.as_str()).collect()
+    }
+
+    /// Size of the file at `path`, if there is one.
+    pub fn file_size(&self, path: &str) -> Option<u64> {
+        self.index.get(path).map(|&(_, len)| len)
+    }
+
+    /// Extract the file at `path`.
+    /// Fails with `FileNotFound` if the archive has no such file.
+    pub fn extract(&self, path: &str) -> Box<NfsFuture<Vec<u8>>> {
+        match self.index.get(path) {
+            Some(&(offset, len)) => {
+                let start = self.data_offset.checked_add(offset);
+                match start.and_then(|start| start.checked_add(len).map(|end| (start, end))) {
+                    Some((start, end)) if end <= self.reader.size() => self.reader.read(start, len),
+                    _ => err!(NfsError::from("Invalid archive")),
+                }
+            }
+            None => err!(NfsError::FileNotFound),
+        }
+    }
+}
+
+fn encode_len(len: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (len >> (8 * i)) as u8;
+    }
+    bytes
+}
+
+fn decode_len(bytes: &[u8]) -> u64 {
+    bytes.iter().rev().fold(0, |len, &byte| (len << 8) | u64::from(byte))
+}
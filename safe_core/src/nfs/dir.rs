@@ -15,13 +15,206 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use client::{Client, MDataInfo};
+use chrono::{DateTime, Utc};
+use client::{Client, Fetched, MDataInfo};
 use errors::CoreError;
-use futures::Future;
-use nfs::{NfsError, NfsFuture};
-use routing::{ClientError, MutableData, PermissionSet, User, Value};
-use std::collections::BTreeMap;
+use ffi::nfs::{DirEntry as FfiDirEntry, DirTreeNode as FfiDirTreeNode};
+use ffi_utils::{ReprC, vec_into_raw_parts};
+use futures::{Future, Stream};
+use futures::future::{self, Loop};
+use futures::stream;
+use limits;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use nfs::{Entry, File, NfsError, NfsFuture, decode_entry};
+use nfs::journal::JOURNAL_ENTRY_KEY;
+use routing::{Action, ClientError, EntryActions, MutableData, PermissionSet, User, Value};
+use rust_sodium::crypto::sign;
+use std::collections::{BTreeMap, HashMap};
+use std::ffi::{CStr, CString};
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
 use utils::FutureExt;
+use utils::wire_format::{Cbor, WireFormat};
+
+/// Key of the entry holding the metadata of the directory itself. Starts with a nul byte,
+/// like the journal key, so it can't clash with the name of a file or sub-directory.
+pub const DIR_METADATA_ENTRY_KEY: &'static [u8] = b"\0metadata";
+
+/// Metadata of a directory, as stored by `update_dir_metadata`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DirMetadata {
+    /// Time the metadata was last updated.
+    pub modified: DateTime<Utc>,
+    /// User metadata of the directory.
+    pub user_metadata: Vec<u8>,
+}
+
+/// Listing of a directory, as returned by `get_dir`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct GetDirResponse {
+    /// Files and sub-directories, ordered by name.
+    pub entries: Vec<DirEntry>,
+}
+
+impl GetDirResponse {
+    /// Encode the listing as CBOR, each entry being a map keyed by the field names of
+    /// `DirEntry`.
+    pub fn to_cbor(&self) -> Result<Vec<u8>, CoreError> {
+        Cbor::serialise(self)
+    }
+}
+
+/// File or sub-directory in a `GetDirResponse`. Sub-directories don't record any times.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DirEntry {
+    /// Name of the entry.
+    pub name: String,
+    /// Whether the entry is a sub-directory rather than a file.
+    pub is_dir: bool,
+    /// File size in bytes, 0 for sub-directories.
+    pub size: u64,
+    /// Creation time in milliseconds since the Unix epoch.
+    pub created_ms: Option<i64>,
+    /// Modification time in milliseconds since the Unix epoch.
+    pub modified_ms: Option<i64>,
+    /// Creation time in RFC 3339 format.
+    pub created: Option<String>,
+    /// Modification time in RFC 3339 format.
+    pub modified: Option<String>,
+    /// User metadata of the file.
+    pub user_metadata: Vec<u8>,
+}
+
+impl DirEntry {
+    fn dir(name: String) -> Self {
+        DirEntry {
+            name: name,
+            is_dir: true,
+            size: 0,
+            created_ms: None,
+            modified_ms: None,
+            created: None,
+            modified: None,
+            user_metadata: Vec::new(),
+        }
+    }
+
+    fn file(name: String, file: &File) -> Self {
+        DirEntry {
+            name: name,
+            is_dir: false,
+            size: file.size(),
+            created_ms: Some(millis(file.created_time())),
+            modified_ms: Some(millis(file.modified_time())),
+            created: Some(file.created_time().to_rfc3339()),
+            modified: Some(file.modified_time().to_rfc3339()),
+            user_metadata: file.user_metadata().to_vec(),
+        }
+    }
+
+    /// Construct FFI wrapper for the native rust `DirEntry`, consuming the entry.
+    pub fn into_repr_c(self) -> Result<FfiDirEntry, NfsError> {
+        let (user_metadata_ptr, user_metadata_len, user_metadata_cap) =
+            vec_into_raw_parts(self.user_metadata);
+
+        Ok(FfiDirEntry {
+            name: c_string(self.name)?.into_raw(),
+            is_dir: self.is_dir,
+            size: self.size,
+            created_ms: self.created_ms.unwrap_or(0),
+            modified_ms: self.modified_ms.unwrap_or(0),
+            created: match self.created {
+                Some(created) => c_string(created)?.into_raw(),
+                None => ptr::null(),
+            },
+            modified: match self.modified {
+                Some(modified) => c_string(modified)?.into_raw(),
+                None => ptr::null(),
+            },
+            user_metadata_ptr: user_metadata_ptr,
+            user_metadata_len: user_metadata_len,
+            user_metadata_cap: user_metadata_cap,
+        })
+    }
+}
+
+impl ReprC for DirEntry {
+    type C = *const FfiDirEntry;
+    type Error = NfsError;
+
+    #[allow(unsafe_code)]
+    unsafe fn clone_from_repr_c(repr_c: Self::C) -> Result<Self, Self::Error> {
+        let repr_c = &*repr_c;
+        let name = c_str(repr_c.name)?.ok_or_else(
+            || NfsError::from("Missing entry name"),
+        )?;
+
+        Ok(DirEntry {
+            name: name,
+            is_dir: repr_c.is_dir,
+            size: repr_c.size,
+            created_ms: if repr_c.is_dir { None } else { Some(repr_c.created_ms) },
+            modified_ms: if repr_c.is_dir { None } else { Some(repr_c.modified_ms) },
+            created: c_str(repr_c.created)?,
+            modified: c_str(repr_c.modified)?,
+            user_metadata: slice::from_raw_parts(
+                repr_c.user_metadata_ptr,
+                repr_c.user_metadata_len,
+            ).to_vec(),
+        })
+    }
+}
+
+/// Maximum number of directories listed at once by `fetch_tree`.
+pub const FETCH_TREE_CONCURRENCY: usize = 8;
+
+/// File or sub-directory in a tree fetched by `fetch_tree`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DirTreeNode {
+    /// The file or sub-directory.
+    pub entry: DirEntry,
+    /// Entries of the sub-directory. Empty for files and for sub-directories at the depth
+    /// limit.
+    pub children: Vec<DirTreeNode>,
+}
+
+impl DirTreeNode {
+    /// Construct FFI wrapper for the native rust `DirTreeNode`, consuming the node.
+    pub fn into_repr_c(self) -> Result<FfiDirTreeNode, NfsError> {
+        let children = self.children
+            .into_iter()
+            .map(DirTreeNode::into_repr_c)
+            .collect::<Result<_, _>>()?;
+        let (children, children_len, children_cap) = vec_into_raw_parts(children);
+
+        Ok(FfiDirTreeNode {
+            entry: self.entry.into_repr_c()?,
+            children: children,
+            children_len: children_len,
+            children_cap: children_cap,
+        })
+    }
+}
+
+impl ReprC for DirTreeNode {
+    type C = *const FfiDirTreeNode;
+    type Error = NfsError;
+
+    #[allow(unsafe_code)]
+    unsafe fn clone_from_repr_c(repr_c: Self::C) -> Result<Self, Self::Error> {
+        let repr_c = &*repr_c;
+        let children = slice::from_raw_parts(repr_c.children, repr_c.children_len)
+            .iter()
+            .map(|child| DirTreeNode::clone_from_repr_c(child))
+            .collect::<Result<_, _>>()?;
+
+        Ok(DirTreeNode {
+            entry: DirEntry::clone_from_repr_c(&repr_c.entry)?,
+            children: children,
+        })
+    }
+}
 
 /// Create a new directory based on the provided `MDataInfo`
 pub fn create_dir<T: 'static>(
@@ -48,3 +241,406 @@ pub fn create_dir<T: 'static>(
         .map_err(NfsError::from)
         .into_box()
 }
+
+/// Create the dedicated container of an app (see `app_container_name`) based on the
+/// `MDataInfo` reserved for it by the authenticator, giving the app with the given key full
+/// access to it. The container is created on its first use rather than when the app is
+/// authorised, so it may not exist yet even though it's listed in the access container.
+pub fn create_app_container<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+    app_sign_pk: sign::PublicKey,
+) -> Box<NfsFuture<()>> {
+    create_dir(
+        client,
+        dir,
+        btree_map![],
+        btree_map![User::Key(app_sign_pk) => PermissionSet::new()
+            .allow(Action::Insert)
+            .allow(Action::Update)
+            .allow(Action::Delete)
+            .allow(Action::ManagePermissions)],
+    )
+}
+
+/// Fetch the decrypted entries of a directory, leaving out deleted ones. Entries holding
+/// the `MDataInfo` of a sub-directory get the sub-directory listed in the background, if
+/// enabled by `Client::set_prefetch_limit`, so that navigating into it is instant.
+/// Listing never modifies the directory: the results of an operation which is still in
+/// progress, or has been interrupted, are left out until it's settled, see `journal`.
+pub fn list_dir<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+) -> Box<NfsFuture<BTreeMap<Vec<u8>, Value>>> {
+    list_dir_versioned(client, dir)
+        .map(|(_, entries)| entries)
+        .into_box()
+}
+
+// Same as `list_dir`, but also returns the `entries_version` of the listed entries.
+fn list_dir_versioned<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+) -> Box<NfsFuture<(u64, BTreeMap<Vec<u8>, Value>)>> {
+    let client2 = client.clone();
+    let dir = dir.clone();
+
+    client
+        .list_mdata_entries(dir.name, dir.type_tag)
+        .map_err(NfsError::from)
+        .and_then(move |entries| decrypt_listing(&client2, &dir, entries))
+        .into_box()
+}
+
+// Decrypts the entries listed from `dir`.
+fn decrypt_listing<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+    entries: BTreeMap<Vec<u8>, Value>,
+) -> Box<NfsFuture<(u64, BTreeMap<Vec<u8>, Value>)>> {
+    let version = entries_version(&entries);
+    let mut output = BTreeMap::new();
+
+    for (key, value) in entries {
+        if value.content.is_empty() {
+            continue;
+        }
+        let key = fry!(dir.decrypt(&key));
+        if key == JOURNAL_ENTRY_KEY {
+            debug!("Listing a directory with a journaled operation pending");
+            continue;
+        }
+        if key == DIR_METADATA_ENTRY_KEY {
+            continue;
+        }
+        let value = Value {
+            content: fry!(dir.decrypt(&value.content)),
+            entry_version: value.entry_version,
+        };
+        let _ = output.insert(key, value);
+    }
+
+    let sub_dirs = output
+        .values()
+        .filter_map(|value| decode_entry(&value.content).ok().and_then(Entry::into_dir))
+        .map(|info| (info.name, info.type_tag))
+        .collect();
+    client.prefetch_mdata_entries(sub_dirs);
+
+    ok!((version, output))
+}
+
+// Version of a directory's contents, changing with every mutation of its entries: as
+// deleted entries are kept with their version bumped, no mutation can leave the sum of
+// entry versions unchanged. Never 0, so that 0 can stand for "unknown".
+fn entries_version(entries: &BTreeMap<Vec<u8>, Value>) -> u64 {
+    entries.values().fold(1, |version, value| {
+        version + value.entry_version + 1
+    })
+}
+
+/// Fetch the listing of a directory, describing its files and sub-directories. Entries
+/// which are neither are left out.
+///
+/// Only the entries themselves are decrypted. Data maps of the files are neither fetched nor
+/// decrypted until the files are opened with `file_helper::read` or `file_helper::write`.
+pub fn get_dir<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+) -> Box<NfsFuture<GetDirResponse>> {
+    list_entries(client, dir)
+        .map(|entries| {
+            GetDirResponse { entries: entries.into_iter().map(|(entry, _)| entry).collect() }
+        })
+        .into_box()
+}
+
+/// Same as `get_dir`, unless the directory is still at `known_version`, as returned by a
+/// previous call, in which case `Fetched::NotModified` is returned. Pass 0 to always get the
+/// listing.
+///
+/// The network keeps no version of the entries as a whole, so the entries still have to be
+/// fetched to find out, but unchanged ones are neither decrypted nor parsed.
+pub fn get_dir_if_changed<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+    known_version: u64,
+) -> Box<NfsFuture<Fetched<GetDirResponse>>> {
+    let client2 = client.clone();
+    let dir = dir.clone();
+
+    client
+        .list_mdata_entries(dir.name, dir.type_tag)
+        .map_err(NfsError::from)
+        .and_then(move |entries| {
+            if entries_version(&entries) == known_version {
+                return ok!(Fetched::NotModified);
+            }
+            decrypt_listing(&client2, &dir, entries)
+                .map(|(version, entries)| {
+                    let entries = entries
+                        .into_iter()
+                        .filter_map(|(key, value)| dir_entry(key, &value.content))
+                        .map(|(entry, _)| entry)
+                        .collect();
+                    Fetched::Modified {
+                        version: version,
+                        data: GetDirResponse { entries: entries },
+                    }
+                })
+                .into_box()
+        })
+        .into_box()
+}
+
+/// Same as `get_dir`, but calls `f` with every entry as soon as it's been decrypted, instead
+/// of collecting the whole listing first, so the first entries can be shown while the rest
+/// of a big directory is still being decrypted. Entries come in no particular order. An
+/// error returned by `f` stops the listing.
+pub fn for_each_dir_entry<T, F>(client: &Client<T>, dir: &MDataInfo, mut f: F) -> Box<NfsFuture<()>>
+where
+    T: 'static,
+    F: FnMut(DirEntry) -> Result<(), NfsError> + 'static,
+{
+    let client2 = client.clone();
+    let dir = dir.clone();
+
+    client
+        .list_mdata_entries(dir.name, dir.type_tag)
+        .map_err(NfsError::from)
+        .and_then(move |entries| {
+            let mut sub_dirs = Vec::new();
+            for (key, value) in entries {
+                if value.content.is_empty() {
+                    continue;
+                }
+                let key = fry!(dir.decrypt(&key));
+                if key == JOURNAL_ENTRY_KEY || key == DIR_METADATA_ENTRY_KEY {
+                    continue;
+                }
+                if let Some((entry, sub_dir)) = dir_entry(key, &fry!(dir.decrypt(&value.content))) {
+                    fry!(f(entry));
+                    if let Some(sub_dir) = sub_dir {
+                        sub_dirs.push((sub_dir.name, sub_dir.type_tag));
+                    }
+                }
+            }
+            client2.prefetch_mdata_entries(sub_dirs);
+
+            ok!(())
+        })
+        .into_box()
+}
+
+/// Resolve `path`, a `/`-separated list of sub-directory names, starting from `root`.
+/// Empty components are skipped, so an empty path resolves to `root`.
+pub fn resolve_dir<T: 'static>(
+    client: &Client<T>,
+    root: &MDataInfo,
+    path: &str,
+) -> Box<NfsFuture<MDataInfo>> {
+    let names: Vec<String> = path.split('/')
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+        .collect();
+    let client = client.clone();
+
+    stream::iter_ok(names)
+        .fold(root.clone(), move |dir, name| {
+            let key = fry!(dir.enc_entry_key(name.as_bytes()));
+            client
+                .get_mdata_value(dir.name, dir.type_tag, key)
+                .map_err(|err| match err {
+                    CoreError::RoutingClientError(ClientError::NoSuchEntry) => {
+                        NfsError::FileNotFound
+                    }
+                    err => NfsError::from(err),
+                })
+                .and_then(move |value| {
+                    if value.content.is_empty() {
+                        return Err(NfsError::FileNotFound);
+                    }
+                    decode_entry(&dir.decrypt(&value.content)?)
+                        .ok()
+                        .and_then(Entry::into_dir)
+                        .ok_or_else(|| {
+                            NfsError::Unexpected(format!("'{}' is not a directory", name))
+                        })
+                })
+                .into_box()
+        })
+        .into_box()
+}
+
+/// Fetch the metadata of the directory, or `None` if none has been set yet.
+pub fn fetch_dir_metadata<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+) -> Box<NfsFuture<Option<DirMetadata>>> {
+    let key = fry!(dir.enc_entry_key(DIR_METADATA_ENTRY_KEY));
+    let dir = dir.clone();
+
+    client
+        .get_mdata_value(dir.name, dir.type_tag, key)
+        .then(move |res| -> Result<_, NfsError> {
+            match res {
+                Ok(ref value) if value.content.is_empty() => Ok(None),
+                Ok(value) => Ok(Some(deserialise(&dir.decrypt(&value.content)?)?)),
+                Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => Ok(None),
+                Err(err) => Err(NfsError::from(err)),
+            }
+        })
+        .into_box()
+}
+
+/// Replace the user metadata of the directory and set its modification time to now,
+/// leaving its files and sub-directories untouched. Returns the stored metadata.
+pub fn update_dir_metadata<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+    user_metadata: Vec<u8>,
+) -> Box<NfsFuture<DirMetadata>> {
+    fry!(limits::check_user_metadata(&user_metadata));
+
+    let metadata = DirMetadata {
+        modified: Utc::now(),
+        user_metadata: user_metadata,
+    };
+    let key = fry!(dir.enc_entry_key(DIR_METADATA_ENTRY_KEY));
+    let encoded = fry!(serialise(&metadata));
+    let content = fry!(dir.enc_entry_value(&encoded));
+    let client2 = client.clone();
+    let dir = dir.clone();
+
+    client
+        .get_mdata_value(dir.name, dir.type_tag, key.clone())
+        .then(move |res| match res {
+            Ok(value) => Ok(EntryActions::new().update(key, content, value.entry_version + 1)),
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => {
+                Ok(EntryActions::new().ins(key, content, 0))
+            }
+            Err(err) => Err(err),
+        })
+        .and_then(move |actions| {
+            client2.mutate_mdata_entries(dir.name, dir.type_tag, actions.into())
+        })
+        .map(move |_| metadata)
+        .map_err(NfsError::from)
+        .into_box()
+}
+
+/// Fetch the entries of `dir` and of its sub-directories, recursively, down to `depth`
+/// levels: 1 lists `dir` only, 2 its sub-directories too, and so on. The directories of
+/// each level are listed with up to `FETCH_TREE_CONCURRENCY` requests at once.
+pub fn fetch_tree<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+    depth: usize,
+) -> Box<NfsFuture<Vec<DirTreeNode>>> {
+    let client = client.clone();
+    // Entries fetched so far along with the index of their parent, `None` for the entries
+    // of `dir`. Parents always come before their children.
+    let nodes: Vec<(Option<usize>, DirEntry)> = Vec::new();
+    // Directories to list on the next level.
+    let pending = vec![(None, dir.clone())];
+
+    future::loop_fn((nodes, pending, depth), move |(mut nodes, pending, depth)| {
+        if depth == 0 || pending.is_empty() {
+            return ok!(Loop::Break(nodes));
+        }
+
+        let client2 = client.clone();
+        stream::iter_ok(pending)
+            .map(move |(parent, dir)| {
+                list_entries(&client2, &dir).map(move |entries| (parent, entries))
+            })
+            .buffered(FETCH_TREE_CONCURRENCY)
+            .collect()
+            .map(move |listings| {
+                let mut pending = Vec::new();
+                for (parent, entries) in listings {
+                    for (entry, sub_dir) in entries {
+                        if let Some(sub_dir) = sub_dir {
+                            pending.push((Some(nodes.len()), sub_dir));
+                        }
+                        nodes.push((parent, entry));
+                    }
+                }
+                Loop::Continue((nodes, pending, depth - 1))
+            })
+            .into_box()
+    }).map(build_tree)
+        .into_box()
+}
+
+// Lists the files and sub-directories of `dir`, along with the `MDataInfo` of the latter.
+fn list_entries<T: 'static>(
+    client: &Client<T>,
+    dir: &MDataInfo,
+) -> Box<NfsFuture<Vec<(DirEntry, Option<MDataInfo>)>>> {
+    list_dir(client, dir)
+        .map(|entries| {
+            entries
+                .into_iter()
+                .filter_map(|(key, value)| dir_entry(key, &value.content))
+                .collect()
+        })
+        .into_box()
+}
+
+// Describes the decrypted directory entry, if it's a file or a sub-directory.
+fn dir_entry(key: Vec<u8>, content: &[u8]) -> Option<(DirEntry, Option<MDataInfo>)> {
+    let name = match String::from_utf8(key) {
+        Ok(name) => name,
+        Err(_) => return None,
+    };
+    match decode_entry(content) {
+        Ok(Entry::Dir(sub_dir)) => Some((DirEntry::dir(name), Some(sub_dir))),
+        Ok(Entry::File(file)) => Some((DirEntry::file(name, &file), None)),
+        Err(_) => None,
+    }
+}
+
+// Nests the flat list of entries gathered by `fetch_tree`.
+fn build_tree(nodes: Vec<(Option<usize>, DirEntry)>) -> Vec<DirTreeNode> {
+    let mut children: HashMap<usize, Vec<DirTreeNode>> = HashMap::new();
+    let mut roots = Vec::new();
+
+    // Going backwards, the children of every entry are complete by the time it's reached,
+    // but in reverse order.
+    for (index, (parent, entry)) in nodes.into_iter().enumerate().rev() {
+        let mut node_children = children.remove(&index).unwrap_or_default();
+        node_children.reverse();
+        let node = DirTreeNode {
+            entry: entry,
+            children: node_children,
+        };
+
+        match parent {
+            Some(parent) => children.entry(parent).or_insert_with(Vec::new).push(node),
+            None => roots.push(node),
+        }
+    }
+
+    roots.reverse();
+    roots
+}
+
+fn millis(time: &DateTime<Utc>) -> i64 {
+    time.timestamp() * 1000 + i64::from(time.timestamp_subsec_millis())
+}
+
+fn c_string(string: String) -> Result<CString, NfsError> {
+    CString::new(string).map_err(|_| NfsError::from("String contains a nul byte"))
+}
+
+#[allow(unsafe_code)]
+unsafe fn c_str(ptr: *const c_char) -> Result<Option<String>, NfsError> {
+    if ptr.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(|string| Some(string.to_owned()))
+        .map_err(|_| NfsError::from("String is not valid UTF-8"))
+}
@@ -0,0 +1,134 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Encoding of directory entries.
+//!
+//! The value of a directory entry is either a `File` or the `MDataInfo` of a sub-directory.
+//! Each kind is written with a `Format` of its own, whose header tells it apart from the other.
+//! Entries written before the headers were introduced are version 0 of both formats and are
+//! recognised by the type they deserialise to, as they used to be.
+
+use client::MDataInfo;
+use errors::CoreError;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use nfs::File;
+use utils::migration::Format;
+
+/// Format of the entries holding a file.
+pub const FILE_ENTRY_FORMAT: Format = Format {
+    header: b"SAFE-FIL",
+    migrations: &[add_header],
+};
+
+/// Format of the entries holding a sub-directory.
+pub const DIR_ENTRY_FORMAT: Format = Format {
+    header: b"SAFE-DIR",
+    migrations: &[add_header],
+};
+
+/// Decoded value of a directory entry.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Entry {
+    /// A file.
+    File(File),
+    /// A sub-directory.
+    Dir(MDataInfo),
+}
+
+impl Entry {
+    /// Returns the file, or `None` if the entry is a sub-directory.
+    pub fn into_file(self) -> Option<File> {
+        match self {
+            Entry::File(file) => Some(file),
+            Entry::Dir(_) => None,
+        }
+    }
+
+    /// Returns the sub-directory, or `None` if the entry is a file.
+    pub fn into_dir(self) -> Option<MDataInfo> {
+        match self {
+            Entry::Dir(dir) => Some(dir),
+            Entry::File(_) => None,
+        }
+    }
+}
+
+/// Encodes a file as the value of a directory entry, before encryption.
+pub fn encode_file(file: &File) -> Result<Vec<u8>, CoreError> {
+    Ok(FILE_ENTRY_FORMAT.encode(&serialise(file)?))
+}
+
+/// Encodes a sub-directory as the value of a directory entry, before encryption.
+pub fn encode_dir(dir: &MDataInfo) -> Result<Vec<u8>, CoreError> {
+    Ok(DIR_ENTRY_FORMAT.encode(&serialise(dir)?))
+}
+
+/// Decodes the decrypted value of a directory entry.
+pub fn decode_entry(content: &[u8]) -> Result<Entry, CoreError> {
+    if content.starts_with(DIR_ENTRY_FORMAT.header) {
+        let (payload, _) = DIR_ENTRY_FORMAT.decode(content)?;
+        return Ok(Entry::Dir(deserialise(&payload)?));
+    }
+    if content.starts_with(FILE_ENTRY_FORMAT.header) {
+        let (payload, _) = FILE_ENTRY_FORMAT.decode(content)?;
+        return Ok(Entry::File(deserialise(&payload)?));
+    }
+
+    match deserialise::<MDataInfo>(content) {
+        Ok(dir) => Ok(Entry::Dir(dir)),
+        Err(_) => Ok(Entry::File(deserialise(content)?)),
+    }
+}
+
+// Version 1 only adds the header, which leaves the payload as it was.
+fn add_header(payload: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+
+    // Test that entries are decoded to the kind they were encoded as.
+    #[test]
+    fn encode_decode() {
+        let dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+        let encoded = unwrap!(encode_dir(&dir));
+        assert!(encoded.starts_with(DIR_ENTRY_FORMAT.header));
+        assert_eq!(unwrap!(decode_entry(&encoded)), Entry::Dir(dir));
+
+        let file = File::new(vec![1, 2, 3]);
+        let encoded = unwrap!(encode_file(&file));
+        assert!(encoded.starts_with(FILE_ENTRY_FORMAT.header));
+        assert_eq!(unwrap!(decode_entry(&encoded)), Entry::File(file));
+
+        assert!(decode_entry(&[]).is_err());
+    }
+
+    // Test that entries written without a header are still recognised.
+    #[test]
+    fn decode_untagged() {
+        let dir = unwrap!(MDataInfo::random_public(DIR_TAG));
+        let decoded = unwrap!(decode_entry(&unwrap!(serialise(&dir))));
+        assert_eq!(decoded.into_dir(), Some(dir));
+
+        let file = File::new(Vec::new());
+        let decoded = unwrap!(decode_entry(&unwrap!(serialise(&file))));
+        assert_eq!(decoded.into_file(), Some(file));
+    }
+}
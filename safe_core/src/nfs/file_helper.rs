@@ -15,12 +15,13 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+use chrono::Utc;
 use client::{Client, MDataInfo};
 use crypto::shared_secretbox;
 use errors::CoreError;
 use futures::{Future, IntoFuture};
-use maidsafe_utilities::serialisation::{deserialise, serialise};
-use nfs::{File, Mode, NfsError, NfsFuture, Reader, Writer};
+use limits;
+use nfs::{File, Mode, NfsError, NfsFuture, Reader, Writer, decode_entry, encode_file};
 use routing::{ClientError, EntryActions};
 use self_encryption_storage::SelfEncryptionStorage;
 use utils::FutureExt;
@@ -39,9 +40,9 @@ where
     let name = name.as_ref();
     trace!("Inserting file with name '{}'", name);
 
-    serialise(&file)
-        .map_err(From::from)
+    encode_file(file)
         .and_then(|encoded| {
+            limits::check_user_metadata(file.user_metadata())?;
             let key = parent.enc_entry_key(name.as_bytes())?;
             let value = parent.enc_entry_value(&encoded)?;
 
@@ -75,7 +76,9 @@ where
         })
         .and_then(move |(value, parent)| {
             let plaintext = parent.decrypt(&value.content)?;
-            let file = deserialise(&plaintext)?;
+            let file = decode_entry(&plaintext)?.into_file().ok_or_else(|| {
+                CoreError::from("Directory entry is not a file")
+            })?;
             Ok((value.entry_version, file))
         })
         .map_err(convert_error)
@@ -142,9 +145,9 @@ where
 
     let client2 = client.clone();
 
-    serialise(&file)
-        .map_err(From::from)
+    encode_file(file)
         .and_then(|encoded| {
+            limits::check_user_metadata(file.user_metadata())?;
             let key = parent.enc_entry_key(name.as_bytes())?;
             let content = parent.enc_entry_value(&encoded)?;
 
@@ -170,6 +173,32 @@ where
         .into_box()
 }
 
+/// Replaces the user metadata of the file and sets its modification time to now. The
+/// contents of the file are neither read nor rewritten.
+/// Returns the updated file together with its new version.
+pub fn update_metadata<S, T>(
+    client: Client<T>,
+    parent: MDataInfo,
+    name: S,
+    user_metadata: Vec<u8>,
+) -> Box<NfsFuture<(u64, File)>>
+where
+    S: Into<String>,
+    T: 'static,
+{
+    let name = name.into();
+    trace!("Updating metadata of file with name '{}'", name);
+
+    fetch(client.clone(), parent.clone(), name.clone())
+        .and_then(move |(version, mut file)| {
+            file.set_user_metadata(user_metadata);
+            file.set_modified_time(Utc::now());
+
+            update(client, parent, name, &file, version + 1).map(move |_| (version + 1, file))
+        })
+        .into_box()
+}
+
 /// Helper function to Update content of a file in a directory. A writer
 /// object is returned, through which the data for the file can be written to
 /// the network. The file is actually saved in the directory listing only after
@@ -0,0 +1,343 @@
+// Copyright 2016 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Journal of multi-step NFS operations. Before an operation touching several
+//! pieces of data starts, it is recorded in a reserved entry of the directory it
+//! modifies. The last step clears the entry in the same mutation that links the
+//! result into the directory, so a non-empty journal means the operation is in
+//! progress or was interrupted. Listing the directory leaves its result out until
+//! then. `recover` completes an interrupted operation if all the data it needs is
+//! in place, or rolls it back otherwise. As it mutates the directory, it's only run
+//! by the next journaled operation on it, which bumps the journal's version, or
+//! explicitly by the caller, never by reads.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use nfs::{NfsError, NfsFuture, create_dir, encode_dir};
+use routing::{ClientError, EntryActions, PermissionSet, User};
+use std::collections::BTreeMap;
+use utils::FutureExt;
+
+/// Key of the directory entry holding the journal. The leading nul byte keeps
+/// it from clashing with the names of files and sub-directories.
+pub const JOURNAL_ENTRY_KEY: &'static [u8] = b"\0journal";
+
+/// Multi-step operation recorded in the journal of the directory it modifies.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum JournalOp {
+    /// Creation of the sub-directory `dir`, linked into the parent under `name`.
+    CreateDir {
+        /// Name of the sub-directory in the parent.
+        name: String,
+        /// The sub-directory.
+        dir: MDataInfo,
+    },
+}
+
+/// Create the sub-directory `dir` and link it into `parent` under `name`. The
+/// operation is journaled in `parent`, so that if it gets interrupted it's
+/// completed or rolled back by the next `recover` on `parent`.
+pub fn create_sub_dir<S, T>(
+    client: &Client<T>,
+    parent: &MDataInfo,
+    name: S,
+    dir: &MDataInfo,
+    perms: BTreeMap<User, PermissionSet>,
+) -> Box<NfsFuture<()>>
+where
+    S: Into<String>,
+    T: 'static,
+{
+    let name = name.into();
+    trace!("Creating sub-directory '{}'", name);
+
+    let journal_key = fry!(parent.enc_entry_key(JOURNAL_ENTRY_KEY));
+    let op = JournalOp::CreateDir {
+        name: name.clone(),
+        dir: dir.clone(),
+    };
+    let op = fry!(parent.enc_entry_value(&fry!(serialise(&op))));
+    let link_actions = fry!(link(EntryActions::new(), parent, &name, dir));
+
+    let client2 = client.clone();
+    let client3 = client.clone();
+    let client4 = client.clone();
+    let parent2 = parent.clone();
+    let parent3 = parent.clone();
+    let dir = dir.clone();
+    let journal_key2 = journal_key.clone();
+
+    recover(client, parent)
+        .and_then(move |version| {
+            let actions = match version {
+                Some(version) => EntryActions::new().update(journal_key, op, version + 1),
+                None => EntryActions::new().ins(journal_key, op, 0),
+            };
+            client2
+                .mutate_mdata_entries(parent2.name, parent2.type_tag, actions.into())
+                .map(move |_| version.map_or(0, |version| version + 1))
+                .map_err(NfsError::from)
+        })
+        .and_then(move |version| {
+            create_dir(&client3, &dir, BTreeMap::new(), perms).map(move |_| version)
+        })
+        .and_then(move |version| {
+            let actions = link_actions.update(journal_key2, Vec::new(), version + 1);
+            client4
+                .mutate_mdata_entries(parent3.name, parent3.type_tag, actions.into())
+                .map_err(NfsError::from)
+        })
+        .into_box()
+}
+
+/// Settle the operation left in the journal of `parent` by an interrupted
+/// multi-step operation, if any. Returns the version of the journal entry, or
+/// `None` if `parent` has never been journaled.
+pub fn recover<T: 'static>(client: &Client<T>, parent: &MDataInfo) -> Box<NfsFuture<Option<u64>>> {
+    let journal_key = fry!(parent.enc_entry_key(JOURNAL_ENTRY_KEY));
+    let client = client.clone();
+    let parent = parent.clone();
+
+    client
+        .get_mdata_value(parent.name, parent.type_tag, journal_key.clone())
+        .then(move |res| match res {
+            Ok(value) => {
+                if value.content.is_empty() {
+                    ok!(Some(value.entry_version))
+                } else {
+                    let op = fry!(parent.decrypt(&value.content));
+                    let op = fry!(deserialise(&op));
+                    settle(&client, &parent, journal_key, value.entry_version, op)
+                        .map(Some)
+                        .into_box()
+                }
+            }
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => ok!(None),
+            Err(err) => err!(err),
+        })
+        .into_box()
+}
+
+// Completes or rolls back `op` and clears the journal. Returns the new version
+// of the journal entry.
+fn settle<T: 'static>(
+    client: &Client<T>,
+    parent: &MDataInfo,
+    journal_key: Vec<u8>,
+    version: u64,
+    op: JournalOp,
+) -> Box<NfsFuture<u64>> {
+    let clear = move || EntryActions::new().update(journal_key.clone(), Vec::new(), version + 1);
+
+    match op {
+        JournalOp::CreateDir { name, dir } => {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let parent = parent.clone();
+
+            client
+                .get_mdata_version(dir.name, dir.type_tag)
+                .then(move |res| {
+                    let actions = match res {
+                        Ok(_) => {
+                            debug!("Completing interrupted creation of '{}'", name);
+                            link(clear(), &parent, &name, &dir)?
+                        }
+                        Err(CoreError::RoutingClientError(ClientError::NoSuchData)) => {
+                            debug!("Rolling back interrupted creation of '{}'", name);
+                            clear()
+                        }
+                        Err(err) => return Err(NfsError::from(err)),
+                    };
+
+                    Ok((actions, clear, parent))
+                })
+                .and_then(move |(actions, clear, parent)| {
+                    client2
+                        .mutate_mdata_entries(parent.name, parent.type_tag, actions.into())
+                        .or_else(move |err| match err {
+                            // The name has been taken in the meantime, so there's
+                            // nothing left to complete.
+                            CoreError::RoutingClientError(
+                                ClientError::InvalidEntryActions(_)
+                            ) => {
+                                client3.mutate_mdata_entries(
+                                    parent.name,
+                                    parent.type_tag,
+                                    clear().into(),
+                                )
+                            }
+                            err => err!(err),
+                        })
+                        .map_err(NfsError::from)
+                })
+                .map(move |_| version + 1)
+                .into_box()
+        }
+    }
+}
+
+// Adds the action inserting `dir` into `parent` under `name`.
+fn link(
+    actions: EntryActions,
+    parent: &MDataInfo,
+    name: &str,
+    dir: &MDataInfo,
+) -> Result<EntryActions, NfsError> {
+    let key = parent.enc_entry_key(name.as_bytes())?;
+    let value = parent.enc_entry_value(&encode_dir(dir)?)?;
+    Ok(actions.ins(key, value, 0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use nfs::{decode_entry, list_dir};
+    use utils::test_utils::random_client;
+
+    // Test creating a sub-directory through the journal.
+    #[test]
+    fn create_sub_dir_through_journal() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let parent = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let parent2 = parent.clone();
+            let parent3 = parent.clone();
+            let dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let dir2 = dir.clone();
+
+            create_dir(client, &parent, btree_map![], btree_map![])
+                .then(move |res| {
+                    unwrap!(res);
+                    create_sub_dir(&client2, &parent2, "sub", &dir, btree_map![])
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    list_dir(&client3, &parent3)
+                })
+                .map(move |entries| {
+                    assert_eq!(entries.len(), 1);
+                    let value = unwrap!(entries.get(&b"sub".to_vec()));
+                    let listed = unwrap!(unwrap!(decode_entry(&value.content)).into_dir());
+                    assert_eq!(listed, dir2);
+                })
+        })
+    }
+
+    // Test recovering interrupted operations. One whose data is in place is
+    // completed, the other one is rolled back.
+    #[test]
+    fn recover_interrupted() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+            let client6 = client.clone();
+            let client7 = client.clone();
+            let client8 = client.clone();
+            let client9 = client.clone();
+            let parent = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let parent2 = parent.clone();
+            let parent3 = parent.clone();
+            let parent4 = parent.clone();
+            let parent5 = parent.clone();
+            let parent6 = parent.clone();
+            let parent7 = parent.clone();
+            let parent8 = parent.clone();
+            let dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let dir2 = dir.clone();
+            let ghost = unwrap!(MDataInfo::random_private(DIR_TAG));
+
+            let journal_key = unwrap!(parent.enc_entry_key(JOURNAL_ENTRY_KEY));
+            let journal_key2 = journal_key.clone();
+            let completed = JournalOp::CreateDir {
+                name: "sub".to_owned(),
+                dir: dir.clone(),
+            };
+            let completed = unwrap!(parent.enc_entry_value(&unwrap!(serialise(&completed))));
+            let rolled_back = JournalOp::CreateDir {
+                name: "ghost".to_owned(),
+                dir: ghost,
+            };
+            let rolled_back = unwrap!(parent.enc_entry_value(&unwrap!(serialise(&rolled_back))));
+
+            create_dir(client, &parent, btree_map![], btree_map![])
+                .then(move |res| {
+                    unwrap!(res);
+                    create_dir(&client2, &dir, btree_map![], btree_map![])
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    // Interrupted after the sub-directory has been created.
+                    client3
+                        .mutate_mdata_entries(
+                            parent2.name,
+                            parent2.type_tag,
+                            EntryActions::new().ins(journal_key, completed, 0).into(),
+                        )
+                        .map_err(NfsError::from)
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    // Listing doesn't settle the operation.
+                    list_dir(&client4, &parent3)
+                })
+                .then(move |res| {
+                    assert!(unwrap!(res).is_empty());
+                    recover(&client8, &parent7)
+                })
+                .then(move |res| {
+                    assert_eq!(unwrap!(res), Some(1));
+                    list_dir(&client9, &parent8)
+                })
+                .then(move |res| {
+                    let entries = unwrap!(res);
+                    assert_eq!(entries.len(), 1);
+                    let value = unwrap!(entries.get(&b"sub".to_vec()));
+                    let listed = unwrap!(unwrap!(decode_entry(&value.content)).into_dir());
+                    assert_eq!(listed, dir2);
+
+                    // Interrupted before the sub-directory has been created.
+                    client5
+                        .mutate_mdata_entries(
+                            parent4.name,
+                            parent4.type_tag,
+                            EntryActions::new().update(journal_key2, rolled_back, 2).into(),
+                        )
+                        .map_err(NfsError::from)
+                })
+                .then(move |res| {
+                    unwrap!(res);
+                    recover(&client6, &parent5)
+                })
+                .then(move |res| {
+                    assert_eq!(unwrap!(res), Some(3));
+                    list_dir(&client7, &parent6)
+                })
+                .map(move |entries| {
+                    assert_eq!(entries.len(), 1);
+                    assert!(entries.contains_key(&b"sub".to_vec()));
+                    assert!(!entries.contains_key(&b"ghost".to_vec()));
+                })
+        })
+    }
+}
@@ -0,0 +1,267 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Read-only access to NFS data written by releases predating `MutableData`.
+//!
+//! Those releases stored each directory as a `StructuredData` holding a serialised
+//! `DirectoryListing`, which inlines the metadata of the sub-directories and the files
+//! along with the `DataMap` of each file. The network no longer serves `StructuredData`, so
+//! it has to be supplied in its serialised form, e.g. from an export made by an older
+//! client. The chunks of the files are still `ImmutableData` and are fetched as usual.
+//!
+//! Listings of private directories were encrypted by the old client and have to be
+//! decrypted before being parsed.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use client::Client;
+use futures::Future;
+use maidsafe_utilities::serialisation::deserialise;
+use nfs::{File, NfsError, NfsFuture, Reader};
+use routing::XorName;
+use rust_sodium::crypto::sign;
+use self_encryption::{DataMap, SelfEncryptor};
+use self_encryption_storage::SelfEncryptionStorage;
+use std::collections::BTreeMap;
+use utils::FutureExt;
+
+/// `StructuredData` as serialised by earlier releases.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct StructuredData {
+    /// Type tag.
+    pub type_tag: u64,
+    /// Name, combined with the type tag into the network address.
+    pub identifier: XorName,
+    /// Serialised `DataTypeEncoding`.
+    pub data: Vec<u8>,
+    /// Owners before the last ownership transfer.
+    pub previous_owner_keys: Vec<sign::PublicKey>,
+    /// Version.
+    pub version: u64,
+    /// Current owners.
+    pub current_owner_keys: Vec<sign::PublicKey>,
+    /// Signatures of the previous owners.
+    pub previous_owner_signatures: Vec<sign::Signature>,
+}
+
+/// Content of a legacy `StructuredData`.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub enum DataTypeEncoding {
+    /// Content small enough to be stored inline.
+    Data(Vec<u8>),
+    /// Name of the `ImmutableData` holding the `DataMap` of the content.
+    MapName(XorName),
+}
+
+impl StructuredData {
+    /// Parse serialised `StructuredData`.
+    pub fn parse(serialised: &[u8]) -> Result<Self, NfsError> {
+        Ok(deserialise(serialised)?)
+    }
+
+    /// Get the content, fetching it from the network if it wasn't stored inline.
+    pub fn content<T: 'static>(&self, client: &Client<T>) -> Box<NfsFuture<Vec<u8>>> {
+        match fry!(deserialise(&self.data)) {
+            DataTypeEncoding::Data(content) => ok!(content),
+            DataTypeEncoding::MapName(name) => {
+                let client2 = client.clone();
+                client
+                    .get_idata(name)
+                    .map_err(NfsError::from)
+                    .and_then(move |data| {
+                        let data_map = deserialise(data.value())?;
+                        let storage = SelfEncryptionStorage::new(client2);
+                        Ok(SelfEncryptor::new(storage, data_map)?)
+                    })
+                    .and_then(|self_encryptor| {
+                        let length = self_encryptor.len();
+                        self_encryptor.read(0, length).map_err(From::from)
+                    })
+                    .into_box()
+            }
+        }
+    }
+}
+
+/// Point in time as serialised by earlier releases, following the layout of `libc::tm`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Tm {
+    /// Seconds after the minute.
+    pub tm_sec: i32,
+    /// Minutes after the hour.
+    pub tm_min: i32,
+    /// Hours after midnight.
+    pub tm_hour: i32,
+    /// Day of the month, starting at 1.
+    pub tm_mday: i32,
+    /// Month, starting at 0.
+    pub tm_mon: i32,
+    /// Years since 1900.
+    pub tm_year: i32,
+    /// Days since Sunday.
+    pub tm_wday: i32,
+    /// Days since January 1.
+    pub tm_yday: i32,
+    /// Daylight Saving Time flag.
+    pub tm_isdst: i32,
+    /// Offset from UTC in seconds.
+    pub tm_utcoff: i32,
+    /// Nanoseconds after the second.
+    pub tm_nsec: i32,
+}
+
+impl Tm {
+    /// Convert to UTC.
+    pub fn to_utc(self) -> Result<DateTime<Utc>, NfsError> {
+        let date = NaiveDate::from_ymd_opt(
+            1900 + self.tm_year,
+            (self.tm_mon + 1) as u32,
+            self.tm_mday as u32,
+        );
+        let naive = date.and_then(|date| {
+            date.and_hms_nano_opt(
+                self.tm_hour as u32,
+                self.tm_min as u32,
+                self.tm_sec as u32,
+                self.tm_nsec as u32,
+            )
+        }).ok_or_else(|| NfsError::from("Invalid date format"))?;
+
+        let naive = naive - Duration::seconds(i64::from(self.tm_utcoff));
+        Ok(DateTime::<Utc>::from_utc(naive, Utc))
+    }
+}
+
+/// Access level of a legacy directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum AccessLevel {
+    /// Listing encrypted by the owner.
+    Private,
+    /// Listing readable by anyone.
+    Public,
+}
+
+/// Address of a legacy directory.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DirectoryKey {
+    /// Name of the `StructuredData` holding the listing.
+    pub id: XorName,
+    /// Type tag of the `StructuredData` holding the listing.
+    pub type_tag: u64,
+    /// Whether previous versions of the listing are kept.
+    pub versioned: bool,
+    /// Access level.
+    pub access_level: AccessLevel,
+}
+
+/// Metadata of a legacy directory.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct DirectoryMetadata {
+    /// Address of the directory.
+    pub key: DirectoryKey,
+    /// Name.
+    pub name: String,
+    /// Time of creation.
+    pub created_time: Tm,
+    /// Time of the last modification.
+    pub modified_time: Tm,
+    /// User setteble custom metadata.
+    pub user_metadata: Vec<u8>,
+    /// Address of the parent directory, unless this is a root directory.
+    pub parent_dir_key: Option<DirectoryKey>,
+}
+
+/// Metadata of a legacy file.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FileMetadata {
+    /// Name.
+    pub name: String,
+    /// Size of the content.
+    pub size: u64,
+    /// Time of creation.
+    pub created_time: Tm,
+    /// Time of the last modification.
+    pub modified_time: Tm,
+    /// User setteble custom metadata.
+    pub user_metadata: Vec<u8>,
+}
+
+/// Legacy file, with the `DataMap` of its content inlined.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct LegacyFile {
+    /// Metadata.
+    pub metadata: FileMetadata,
+    /// `DataMap` of the content.
+    pub datamap: DataMap,
+}
+
+impl LegacyFile {
+    /// Convert the metadata into a `File`. As the content isn't referred to by the
+    /// `DataMap` name, it has to be read with `read` rather than `file_helper::read`.
+    pub fn to_file(&self) -> Result<File, NfsError> {
+        let mut file = File::new(self.metadata.user_metadata.clone());
+        file.set_size(self.metadata.size);
+        file.set_created_time(self.metadata.created_time.to_utc()?);
+        file.set_modified_time(self.metadata.modified_time.to_utc()?);
+        Ok(file)
+    }
+}
+
+/// Legacy directory listing.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+pub struct DirectoryListing {
+    /// Metadata of the directory.
+    pub metadata: DirectoryMetadata,
+    /// Metadata of the sub-directories.
+    pub sub_directories: Vec<DirectoryMetadata>,
+    /// Files.
+    pub files: Vec<LegacyFile>,
+}
+
+impl DirectoryListing {
+    /// Parse a serialised listing. Private listings have to be decrypted first.
+    pub fn parse(serialised: &[u8]) -> Result<Self, NfsError> {
+        Ok(deserialise(serialised)?)
+    }
+
+    /// Files keyed by name, converted by `LegacyFile::to_file`.
+    pub fn list_files(&self) -> Result<BTreeMap<String, File>, NfsError> {
+        self.files
+            .iter()
+            .map(|file| Ok((file.metadata.name.clone(), file.to_file()?)))
+            .collect()
+    }
+
+    /// Find a file by name.
+    pub fn find_file(&self, name: &str) -> Option<&LegacyFile> {
+        self.files.iter().find(|file| file.metadata.name == name)
+    }
+
+    /// Find a sub-directory by name.
+    pub fn find_sub_directory(&self, name: &str) -> Option<&DirectoryMetadata> {
+        self.sub_directories.iter().find(|dir| dir.name == name)
+    }
+}
+
+/// Get a `Reader` of the content of a legacy file.
+pub fn read<T: 'static>(client: Client<T>, file: &LegacyFile) -> Box<NfsFuture<Reader<T>>> {
+    trace!("Reading legacy file {:?}", file.metadata.name);
+    Reader::with_data_map(
+        client.clone(),
+        SelfEncryptionStorage::new(client),
+        file.datamap.clone(),
+    )
+}
@@ -15,19 +15,31 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+/// Archives packing many files into a single blob
+pub mod archive;
 /// `FileHelper` provides functions for CRUD on file
 pub mod file_helper;
+pub mod journal;
+pub mod legacy;
+/// Share tokens granting access to single files
+pub mod share;
 
 mod errors;
 mod data_map;
 mod dir;
+mod entry;
 mod file;
 mod reader;
 #[cfg(test)]
 mod tests;
 mod writer;
 
-pub use self::dir::create_dir;
+pub use self::dir::{DIR_METADATA_ENTRY_KEY, DirEntry, DirMetadata, DirTreeNode,
+                    FETCH_TREE_CONCURRENCY, GetDirResponse, create_app_container, create_dir,
+                    fetch_dir_metadata, fetch_tree, for_each_dir_entry, get_dir,
+                    get_dir_if_changed, list_dir, resolve_dir, update_dir_metadata};
+pub use self::entry::{DIR_ENTRY_FORMAT, Entry, FILE_ENTRY_FORMAT, decode_entry, encode_dir,
+                      encode_file};
 pub use self::errors::NfsError;
 pub use self::file::File;
 pub use self::reader::Reader;
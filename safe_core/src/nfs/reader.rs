@@ -15,20 +15,35 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
-use client::Client;
+use client::{Client, Priority};
 use crypto::shared_secretbox;
+use errors::CoreError;
 use futures::Future;
 use nfs::{File, NfsError, NfsFuture, data_map};
-use self_encryption::SelfEncryptor;
+use routing::{XOR_NAME_LEN, XorName};
+use self_encryption::{DataMap, SelfEncryptor};
 use self_encryption_storage::SelfEncryptionStorage;
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::rc::Rc;
 use utils::FutureExt;
+use utils::compression;
+
+/// Default number of chunks fetched ahead of the position being read.
+pub const DEFAULT_READ_AHEAD: usize = 4;
 
 /// Reader is used to read contents of a File. It can read in chunks if the
 /// file happens to be very large
-#[allow(dead_code)]
 pub struct Reader<T> {
     client: Client<T>,
     self_encryptor: SelfEncryptor<SelfEncryptionStorage<T>>,
+    // End offsets and names of the chunks of the file, in order.
+    chunks: Vec<(u64, XorName)>,
+    read_ahead: usize,
+    // Chunks being prefetched, so consecutive reads don't fetch them again.
+    prefetching: Rc<RefCell<HashSet<XorName>>>,
+    // Inflated content of a compressed file.
+    content: Option<Vec<u8>>,
 }
 
 impl<T: 'static> Reader<T> {
@@ -40,20 +55,69 @@ impl<T: 'static> Reader<T> {
         encryption_key: Option<shared_secretbox::Key>,
     ) -> Box<NfsFuture<Reader<T>>> {
         data_map::get(&client, file.data_map_name(), encryption_key)
-            .and_then(move |data_map| {
-                let self_encryptor = SelfEncryptor::new(storage, data_map)?;
+            .and_then(move |data_map| Reader::with_data_map(client, storage, data_map))
+            .into_box()
+    }
 
-                Ok(Reader {
-                    client: client,
-                    self_encryptor: self_encryptor,
-                })
+    /// Create a new instance of Reader for the content described by `data_map`
+    pub fn with_data_map(
+        client: Client<T>,
+        storage: SelfEncryptionStorage<T>,
+        data_map: DataMap,
+    ) -> Box<NfsFuture<Reader<T>>> {
+        let chunks = chunk_ends(&data_map);
+        let self_encryptor = fry!(SelfEncryptor::new(storage, data_map));
+
+        Reader::inflate(Reader {
+            client: client,
+            self_encryptor: self_encryptor,
+            chunks: chunks,
+            read_ahead: DEFAULT_READ_AHEAD,
+            prefetching: Rc::new(RefCell::new(HashSet::new())),
+            content: None,
+        })
+    }
+
+    // Detects compressed content by its header and, if found, inflates the whole of it, as
+    // compressed content can't be read from arbitrary positions.
+    fn inflate(mut reader: Reader<T>) -> Box<NfsFuture<Reader<T>>> {
+        let header_len = compression::HEADER.len() as u64;
+        let size = reader.self_encryptor.len();
+        if size < header_len {
+            return ok!(reader);
+        }
+
+        let header = reader.self_encryptor.read(0, header_len);
+        header
+            .map_err(From::from)
+            .and_then(move |header| if compression::is_compressed(&header) {
+                let content = reader.self_encryptor.read(0, size);
+                content
+                    .map_err(From::from)
+                    .and_then(move |content| {
+                        reader.content = Some(compression::decompress(&content)?);
+                        Ok(reader)
+                    })
+                    .into_box()
+            } else {
+                ok!(reader)
             })
             .into_box()
     }
 
+    /// Sets the number of chunks following each read which are fetched in the
+    /// background, so sequential reads don't stall between chunks. Zero turns
+    /// read-ahead off.
+    pub fn set_read_ahead(&mut self, chunks: usize) {
+        self.read_ahead = chunks;
+    }
+
     /// Returns the total size of the file/blob
     pub fn size(&self) -> u64 {
-        self.self_encryptor.len()
+        match self.content {
+            Some(ref content) => content.len() as u64,
+            None => self.self_encryptor.len(),
+        }
     }
 
     /// Read data from file/blob
@@ -72,10 +136,63 @@ impl<T: 'static> Reader<T> {
                 len = length,
                 pos = position
             );
-            self.self_encryptor
+            if let Some(ref content) = self.content {
+                let (start, end) = (position as usize, (position + length) as usize);
+                return ok!(content[start..end].to_vec());
+            }
+
+            let read = self.self_encryptor
                 .read(position, length)
                 .map_err(From::from)
-                .into_box()
+                .into_box();
+            self.prefetch(position + length);
+            read
+        }
+    }
+
+    // Fetches chunks following `position` into the client's cache in background jobs,
+    // where the self-encryptor finds them once it gets there.
+    fn prefetch(&self, position: u64) {
+        let first = self.chunks
+            .iter()
+            .position(|&(end, _)| end > position)
+            .unwrap_or_else(|| self.chunks.len());
+
+        for &(_, name) in self.chunks.iter().skip(first).take(self.read_ahead) {
+            if !self.prefetching.borrow_mut().insert(name) {
+                continue;
+            }
+
+            let client = self.client.clone();
+            let prefetching = Rc::clone(&self.prefetching);
+            let _ = self.client.schedule(Priority::High, move || {
+                client.get_idata(name).then(move |_| {
+                    let _ = prefetching.borrow_mut().remove(&name);
+                    Ok::<_, CoreError>(())
+                })
+            });
+        }
+    }
+}
+
+fn chunk_ends(data_map: &DataMap) -> Vec<(u64, XorName)> {
+    let mut end = 0;
+    match *data_map {
+        DataMap::Chunks(ref chunks) => {
+            chunks
+                .iter()
+                .filter_map(|chunk| {
+                    end += chunk.source_size;
+                    if chunk.hash.len() != XOR_NAME_LEN {
+                        return None;
+                    }
+                    let mut name = [0; XOR_NAME_LEN];
+                    name.copy_from_slice(&chunk.hash);
+                    Some((end, XorName(name)))
+                })
+                .collect()
         }
+        DataMap::Content(_) |
+        DataMap::None => Vec::new(),
     }
 }
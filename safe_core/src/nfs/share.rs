@@ -0,0 +1,373 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Share tokens for single private files.
+//!
+//! The data map of a file in a private directory is encrypted with the directory's key, so
+//! handing that key out would expose every file in the directory. Instead, sharing a file
+//! stores a copy of its data map encrypted with a fresh random key, and the token carries
+//! the file metadata pointing at that copy together with the key. The token can optionally
+//! be sealed with a password, in which case the key protecting it is derived using scrypt.
+//!
+//! Such a token grants access for as long as the chunks exist. A revocable token instead
+//! points at an entry of a share registry, a `MutableData` (with the `SHARE_REGISTRY_TAG`
+//! type tag) owned by the sharer, which holds the file metadata and key encrypted with a key
+//! only the token knows. Deleting the entry revokes the share, although anyone who opened
+//! the token before may have kept the key.
+
+use client::{Client, MDataInfo};
+use crypto::shared_secretbox;
+use errors::CoreError;
+use ffi_utils::{base64_decode, base64_encode};
+use futures::Future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use nfs::{File, NfsError, NfsFuture, Reader, data_map, file_helper};
+use routing::{Action, ClientError, EntryActions, MutableData, PermissionSet, User, XorName};
+use rust_sodium::crypto::{pwhash, secretbox};
+use std::collections::BTreeMap;
+use utils::{self, FutureExt};
+use SHARE_REGISTRY_TAG;
+
+/// Length of the IDs revocable shares are stored under.
+pub const SHARE_ID_LEN: usize = 32;
+
+/// scrypt cost of the password key derivation.
+#[cfg(not(feature = "fast-kdf"))]
+const SCRYPT_OPSLIMIT: pwhash::OpsLimit = pwhash::OPSLIMIT_INTERACTIVE;
+/// Lowest cost libsodium accepts, for builds explicitly opting into `fast-kdf`.
+#[cfg(feature = "fast-kdf")]
+const SCRYPT_OPSLIMIT: pwhash::OpsLimit = pwhash::OpsLimit(32_768);
+
+/// Revocable share, as listed to the owner of the registry.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Share {
+    /// Label given to the share when it was created.
+    pub label: String,
+    /// Metadata of the shared file.
+    pub file: File,
+}
+
+// File metadata and the key to decrypt its data map with.
+#[derive(Serialize, Deserialize)]
+struct ShareToken {
+    file: File,
+    key: shared_secretbox::Key,
+}
+
+#[derive(Serialize, Deserialize)]
+enum Pointer {
+    Direct(ShareToken),
+    Revocable {
+        registry: XorName,
+        type_tag: u64,
+        id: Vec<u8>,
+        key: shared_secretbox::Key,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
+enum Envelope {
+    Plain(Vec<u8>),
+    Sealed {
+        salt: [u8; pwhash::SALTBYTES],
+        cipher_text: Vec<u8>,
+    },
+}
+
+// Registry entry of a revocable share.
+#[derive(Serialize, Deserialize)]
+struct RegistryEntry {
+    // `Share` encrypted with the key of the registry.
+    share: Vec<u8>,
+    // `ShareToken` encrypted with the key carried by the token.
+    token: Vec<u8>,
+}
+
+/// Creates a share token granting read access to `file`, whose data map is encrypted with
+/// `encryption_key` (the key of the directory it's stored in, if private). If `password`
+/// is given, the token can't be opened without it.
+pub fn create_token<T: 'static>(
+    client: &Client<T>,
+    file: &File,
+    encryption_key: Option<shared_secretbox::Key>,
+    password: Option<&str>,
+) -> Box<NfsFuture<String>> {
+    let password = password.map(str::to_owned);
+
+    copy_data_map(client, file, encryption_key)
+        .and_then(move |token| {
+            seal(&Pointer::Direct(token), password.as_ref().map(String::as_str))
+        })
+        .into_box()
+}
+
+/// Creates an empty share registry owned by the client and returns its `MDataInfo`.
+pub fn create_registry<T: 'static>(client: &Client<T>) -> Box<NfsFuture<MDataInfo>> {
+    let owner_key = fry!(client.owner_key());
+    let sign_key = fry!(client.public_signing_key());
+    let registry = fry!(MDataInfo::random_private(SHARE_REGISTRY_TAG));
+
+    let registry_md = fry!(MutableData::new(
+        registry.name,
+        registry.type_tag,
+        btree_map![
+            User::Key(sign_key) => PermissionSet::new()
+                .allow(Action::Insert)
+                .allow(Action::Delete)
+                .allow(Action::ManagePermissions),
+        ],
+        btree_map![],
+        btree_set![owner_key],
+    ));
+
+    client
+        .put_mdata(registry_md)
+        .map_err(NfsError::from)
+        .map(move |_| registry)
+        .into_box()
+}
+
+/// Creates a share token granting read access to `file` until the share is revoked.
+/// The share is recorded in `registry` under `label`.
+/// Returns the ID of the share together with the token.
+pub fn create_revocable_token<T: 'static>(
+    client: &Client<T>,
+    registry: &MDataInfo,
+    label: &str,
+    file: &File,
+    encryption_key: Option<shared_secretbox::Key>,
+    password: Option<&str>,
+) -> Box<NfsFuture<(Vec<u8>, String)>> {
+    let c2 = client.clone();
+    let registry = registry.clone();
+    let label = label.to_owned();
+    let password = password.map(str::to_owned);
+    let id = fry!(utils::generate_random_vector::<u8>(SHARE_ID_LEN));
+    let key = shared_secretbox::gen_key();
+
+    copy_data_map(client, file, encryption_key)
+        .and_then(move |token| -> Result<_, NfsError> {
+            let share = Share {
+                label: label,
+                file: token.file.clone(),
+            };
+            let entry = RegistryEntry {
+                share: registry.enc_entry_value(&serialise(&share)?)?,
+                token: utils::symmetric_encrypt(&serialise(&token)?, &key, None)?,
+            };
+            let pointer = Pointer::Revocable {
+                registry: registry.name,
+                type_tag: registry.type_tag,
+                id: id.clone(),
+                key: key,
+            };
+            let token = seal(&pointer, password.as_ref().map(String::as_str))?;
+
+            Ok((registry, id, serialise(&entry)?, token))
+        })
+        .and_then(move |(registry, id, content, token)| {
+            c2.mutate_mdata_entries(
+                registry.name,
+                registry.type_tag,
+                EntryActions::new().ins(id.clone(), content, 0).into(),
+            ).map_err(NfsError::from)
+                .map(move |_| (id, token))
+        })
+        .into_box()
+}
+
+/// Revokes the share with the given ID.
+/// Fails with `NoSuchEntry` if there is no such share.
+pub fn revoke<T: 'static>(
+    client: &Client<T>,
+    registry: &MDataInfo,
+    id: Vec<u8>,
+) -> Box<NfsFuture<()>> {
+    let c2 = client.clone();
+    let name = registry.name;
+    let type_tag = registry.type_tag;
+
+    client
+        .get_mdata_value(name, type_tag, id.clone())
+        .and_then(move |value| {
+            if value.content.is_empty() {
+                return err!(CoreError::RoutingClientError(ClientError::NoSuchEntry));
+            }
+            c2.mutate_mdata_entries(
+                name,
+                type_tag,
+                EntryActions::new().del(id, value.entry_version + 1).into(),
+            )
+        })
+        .map_err(NfsError::from)
+        .into_box()
+}
+
+/// Lists the outstanding shares of the registry, keyed by their IDs.
+pub fn list_shares<T: 'static>(
+    client: &Client<T>,
+    registry: &MDataInfo,
+) -> Box<NfsFuture<BTreeMap<Vec<u8>, Share>>> {
+    let registry = registry.clone();
+
+    client
+        .list_mdata_entries(registry.name, registry.type_tag)
+        .map_err(NfsError::from)
+        .and_then(move |entries| -> Result<_, NfsError> {
+            let mut shares = BTreeMap::new();
+
+            for (id, value) in entries {
+                // Empty entry means the share has been revoked.
+                if value.content.is_empty() {
+                    continue;
+                }
+                let entry: RegistryEntry = deserialise(&value.content)?;
+                let share = deserialise(&registry.decrypt(&entry.share)?)?;
+                let _ = shares.insert(id, share);
+            }
+
+            Ok(shares)
+        })
+        .into_box()
+}
+
+/// Opens a share token, returning the shared file and the key to read it with.
+///
+/// Fails with `SymmetricDecipherFailure` if the token is password protected and the
+/// password is missing or wrong. A password passed for an unprotected token is ignored.
+/// Fails with `NoSuchEntry` if the token is revocable and the share has been revoked.
+pub fn open_token<T: 'static>(
+    client: &Client<T>,
+    token: &str,
+    password: Option<&str>,
+) -> Box<NfsFuture<(File, shared_secretbox::Key)>> {
+    match fry!(unseal(token, password)) {
+        Pointer::Direct(ShareToken { file, key }) => ok!((file, key)),
+        Pointer::Revocable {
+            registry,
+            type_tag,
+            id,
+            key,
+        } => {
+            client
+                .get_mdata_value(registry, type_tag, id)
+                .map_err(NfsError::from)
+                .and_then(move |value| {
+                    // Empty entry means the share has been revoked.
+                    if value.content.is_empty() {
+                        return Err(NfsError::from(
+                            CoreError::RoutingClientError(ClientError::NoSuchEntry),
+                        ));
+                    }
+                    let entry: RegistryEntry = deserialise(&value.content)?;
+                    let ShareToken { file, key } =
+                        deserialise(&utils::symmetric_decrypt(&entry.token, &key)?)?;
+                    Ok((file, key))
+                })
+                .into_box()
+        }
+    }
+}
+
+/// Opens a share token and returns a `Reader` of the shared file.
+pub fn read<T: 'static>(
+    client: Client<T>,
+    token: &str,
+    password: Option<&str>,
+) -> Box<NfsFuture<Reader<T>>> {
+    open_token(&client, token, password)
+        .and_then(move |(file, key)| file_helper::read(client, &file, Some(key)))
+        .into_box()
+}
+
+// Stores a copy of the data map of `file` encrypted with a fresh key.
+fn copy_data_map<T: 'static>(
+    client: &Client<T>,
+    file: &File,
+    encryption_key: Option<shared_secretbox::Key>,
+) -> Box<NfsFuture<ShareToken>> {
+    let c2 = client.clone();
+    let mut file = file.clone();
+    let key = shared_secretbox::gen_key();
+
+    data_map::get(client, file.data_map_name(), encryption_key)
+        .and_then(move |data_map| data_map::put(&c2, &data_map, Some(key.clone())))
+        .map(move |name| {
+            file.set_data_map_name(name);
+            ShareToken {
+                file: file,
+                key: key,
+            }
+        })
+        .into_box()
+}
+
+fn seal(pointer: &Pointer, password: Option<&str>) -> Result<String, NfsError> {
+    let pointer = serialise(pointer)?;
+    let envelope = match password {
+        Some(password) => {
+            let salt = pwhash::gen_salt();
+            let seal_key = derive_key(password.as_bytes(), &salt)?;
+            Envelope::Sealed {
+                salt: salt.0,
+                cipher_text: utils::symmetric_encrypt(&pointer, &seal_key, None)?,
+            }
+        }
+        None => Envelope::Plain(pointer),
+    };
+
+    Ok(base64_encode(&serialise(&envelope)?))
+}
+
+fn unseal(token: &str, password: Option<&str>) -> Result<Pointer, NfsError> {
+    let envelope = base64_decode(token).map_err(|_| NfsError::from("Invalid share token"))?;
+    let pointer = match deserialise(&envelope)? {
+        Envelope::Plain(pointer) => pointer,
+        Envelope::Sealed { salt, cipher_text } => {
+            let password = password.ok_or(CoreError::SymmetricDecipherFailure)?;
+            let seal_key = derive_key(password.as_bytes(), &pwhash::Salt(salt))?;
+            utils::symmetric_decrypt(&cipher_text, &seal_key)?
+        }
+    };
+
+    Ok(deserialise(&pointer)?)
+}
+
+fn derive_key(password: &[u8], salt: &pwhash::Salt) -> Result<secretbox::Key, CoreError> {
+    let mut key = secretbox::Key([0; secretbox::KEYBYTES]);
+    {
+        let secretbox::Key(ref mut key_bytes) = key;
+        let _ = pwhash::derive_key(
+            key_bytes,
+            password,
+            salt,
+            SCRYPT_OPSLIMIT,
+            pwhash::MEMLIMIT_INTERACTIVE,
+        ).map_err(|_| CoreError::UnsuccessfulPwHash)?;
+    }
+    Ok(key)
+}
+
+#[cfg(all(test, not(feature = "fast-kdf")))]
+mod tests {
+    use super::*;
+
+    // Test that builds not opting into `fast-kdf` use the full key derivation cost.
+    #[test]
+    fn production_kdf_params() {
+        assert_eq!(SCRYPT_OPSLIMIT.0, pwhash::OPSLIMIT_INTERACTIVE.0);
+    }
+}
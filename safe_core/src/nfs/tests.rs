@@ -16,15 +16,22 @@
 // relating to use of the SAFE Network Software.
 
 use DIR_TAG;
-use client::{Client, MDataInfo};
+use client::{Client, Fetched, MDataInfo};
+use client::mdata_info;
 use crypto::shared_secretbox;
 use errors::CoreError;
 use futures::Future;
 use futures::future::{self, Loop};
-use nfs::{File, Mode, NfsError, NfsFuture, create_dir, file_helper};
+use maidsafe_utilities::serialisation::serialise;
+use nfs::{File, Mode, NfsError, NfsFuture, create_dir, data_map, decode_entry, encode_dir,
+          fetch_dir_metadata, file_helper, get_dir, get_dir_if_changed, legacy, list_dir,
+          update_dir_metadata};
+use nfs::archive::{Archive, ArchiveWriter};
 use nfs::reader::Reader;
+use nfs::share;
 use nfs::writer::Writer;
 use rand::{self, Rng};
+use routing::{ClientError, Value};
 use rust_sodium::crypto::secretbox;
 use std;
 use utils::FutureExt;
@@ -700,3 +707,594 @@ fn encryption() {
             })
     })
 }
+
+// Test packing files into an archive and extracting them one by one.
+#[test]
+fn archive() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let key = shared_secretbox::gen_key();
+        let key2 = key.clone();
+
+        let mut writer = ArchiveWriter::new();
+        writer.add("index.html", b"<html></html>".to_vec());
+        writer.add("css/style.css", b"body {}".to_vec());
+        writer.add("empty", Vec::new());
+
+        writer
+            .finish(client, Some(key))
+            .then(move |res| {
+                let file = unwrap!(res);
+                Archive::open(&c2, &file, Some(key2))
+            })
+            .then(|res| {
+                let archive = unwrap!(res);
+                assert_eq!(archive.paths(), vec!["css/style.css", "empty", "index.html"]);
+                assert_eq!(archive.file_size("index.html"), Some(13));
+
+                match archive.extract("missing").wait() {
+                    Err(NfsError::FileNotFound) => (),
+                    Err(x) => panic!("Unexpected error {:?}", x),
+                    Ok(_) => panic!("Unexpected success"),
+                }
+
+                archive.extract("css/style.css").map(move |content| (archive, content))
+            })
+            .then(|res| {
+                let (archive, content) = unwrap!(res);
+                assert_eq!(content, b"body {}");
+                archive.extract("empty")
+            })
+            .map(|content| assert!(content.is_empty()))
+    })
+}
+
+// Test that opening an archive with an index length out of range fails.
+#[test]
+fn archive_invalid_index_len() {
+    random_client(|client| {
+        let c2 = client.clone();
+
+        // A little-endian index length which overflows when the size of the length is added.
+        let mut blob = vec![0xff; 8];
+        blob[0] = 0xfc;
+        blob.extend_from_slice(b"not an index");
+
+        file_helper::write(client.clone(), File::new(Vec::new()), Mode::Overwrite, None)
+            .then(move |res| {
+                let writer = unwrap!(res);
+                writer.write(&blob).and_then(move |_| writer.close())
+            })
+            .then(move |res| {
+                let file = unwrap!(res);
+                Archive::open(&c2, &file, None)
+            })
+            .then(|res| -> Result<_, NfsError> {
+                match res {
+                    Err(NfsError::Unexpected(..)) => (),
+                    Err(x) => panic!("Unexpected error {:?}", x),
+                    Ok(_) => panic!("Unexpected success"),
+                }
+                Ok(())
+            })
+    })
+}
+
+// Test sharing a file of a private directory through a password protected token.
+#[test]
+fn share_token() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+
+        create_test_file(client)
+            .then(move |res| {
+                let (dir, file) = unwrap!(res);
+                share::create_token(&c2, &file, dir.enc_key().cloned(), Some("pass"))
+                    .map(move |token| (dir, token))
+            })
+            .then(move |res| {
+                let (dir, token) = unwrap!(res);
+
+                match share::open_token(&c3, &token, None).wait() {
+                    Err(NfsError::CoreError(CoreError::SymmetricDecipherFailure)) => (),
+                    Err(x) => panic!("Unexpected error {:?}", x),
+                    Ok(_) => panic!("Unexpected success"),
+                }
+                match share::open_token(&c3, &token, Some("wrong")).wait() {
+                    Err(NfsError::CoreError(CoreError::SymmetricDecipherFailure)) => (),
+                    Err(x) => panic!("Unexpected error {:?}", x),
+                    Ok(_) => panic!("Unexpected success"),
+                }
+
+                // The token must not carry the key of the directory.
+                // Tokens which aren't revocable are opened without touching the network.
+                let (_, key) = unwrap!(share::open_token(&c3, &token, Some("pass")).wait());
+                assert!(Some(&key) != dir.enc_key());
+
+                share::read(c3, &token, Some("pass"))
+            })
+            .then(|res| {
+                let reader = unwrap!(res);
+                let size = reader.size();
+                reader.read(0, size)
+            })
+            .map(|content| assert_eq!(content, vec![0u8; ORIG_SIZE]))
+    })
+}
+
+// Test creating, listing and revoking revocable share tokens.
+#[test]
+fn share_token_revocable() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let c5 = client.clone();
+        let c6 = client.clone();
+        let c7 = client.clone();
+
+        create_test_file(client)
+            .then(move |res| {
+                let (dir, file) = unwrap!(res);
+                share::create_registry(&c2).map(move |registry| (dir, file, registry))
+            })
+            .then(move |res| {
+                let (dir, file, registry) = unwrap!(res);
+                share::create_revocable_token(
+                    &c3,
+                    &registry,
+                    "hello",
+                    &file,
+                    dir.enc_key().cloned(),
+                    None,
+                ).map(move |(id, token)| (registry, id, token))
+            })
+            .then(move |res| {
+                let (registry, id, token) = unwrap!(res);
+                share::list_shares(&c4, &registry).map(move |shares| (registry, id, token, shares))
+            })
+            .then(move |res| {
+                let (registry, id, token, shares) = unwrap!(res);
+                assert_eq!(shares.len(), 1);
+                assert_eq!(unwrap!(shares.get(&id)).label, "hello");
+                assert_eq!(unwrap!(shares.get(&id)).file.size(), ORIG_SIZE as u64);
+
+                share::read(c5.clone(), &token, None)
+                    .and_then(|reader| {
+                        let size = reader.size();
+                        reader.read(0, size)
+                    })
+                    .map(move |content| (registry, id, token, content))
+            })
+            .then(move |res| {
+                let (registry, id, token, content) = unwrap!(res);
+                assert_eq!(content, vec![0u8; ORIG_SIZE]);
+                share::revoke(&c6, &registry, id).map(move |_| (registry, token))
+            })
+            .then(move |res| {
+                let (registry, token) = unwrap!(res);
+                share::open_token(&c7, &token, None).then(move |res| {
+                    match res {
+                        Err(NfsError::CoreError(
+                            CoreError::RoutingClientError(ClientError::NoSuchEntry),
+                        )) => (),
+                        Err(x) => panic!("Unexpected error {:?}", x),
+                        Ok(_) => panic!("Unexpected success"),
+                    }
+                    share::list_shares(&c7, &registry)
+                })
+            })
+            .map(|shares| assert!(shares.is_empty()))
+    })
+}
+
+// Test reading a file spanning several chunks sequentially with read-ahead on.
+#[test]
+fn file_read_ahead() {
+    const STEP: u64 = 256 * 1024;
+
+    random_client(|client| {
+        let c2 = client.clone();
+        let content: Vec<u8> = rand::thread_rng().gen_iter().take(3 * 1024 * 1024).collect();
+        let content2 = content.clone();
+
+        file_helper::write(client.clone(), File::new(Vec::new()), Mode::Overwrite, None)
+            .then(move |res| {
+                let writer = unwrap!(res);
+                writer.write(&content).and_then(move |_| writer.close())
+            })
+            .then(move |res| {
+                let file = unwrap!(res);
+                file_helper::read(c2, &file, None)
+            })
+            .then(|res| {
+                let mut reader = unwrap!(res);
+                reader.set_read_ahead(2);
+
+                future::loop_fn((reader, Vec::new()), |(reader, mut result)| {
+                    let position = result.len() as u64;
+                    let len = STEP.min(reader.size() - position);
+
+                    reader.read(position, len).map(move |mut data| {
+                        result.append(&mut data);
+                        if (result.len() as u64) < reader.size() {
+                            Loop::Continue((reader, result))
+                        } else {
+                            Loop::Break(result)
+                        }
+                    })
+                })
+            })
+            .map(move |result| assert_eq!(result, content2))
+    })
+}
+
+// Test writing a compressed file, reading it back, and appending to it.
+#[test]
+fn file_compressed() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let content: Vec<u8> = b"{\"level\": \"info\", \"message\": \"hello\"}\n"
+            .iter()
+            .cycle()
+            .take(ORIG_SIZE * 10)
+            .cloned()
+            .collect();
+        let content2 = content.clone();
+
+        file_helper::write(
+            client.clone(),
+            File::new(Vec::new()),
+            Mode::OverwriteCompressed,
+            None,
+        ).then(move |res| {
+                let writer = unwrap!(res);
+                writer.write(&content).and_then(move |_| writer.close())
+            })
+            .then(move |res| {
+                let file = unwrap!(res);
+                // The recorded size is the one of the content before compression.
+                assert_eq!(file.size(), content2.len() as u64);
+
+                file_helper::read(c2, &file, None).map(move |reader| (reader, file, content2))
+            })
+            .then(move |res| {
+                let (reader, file, content) = unwrap!(res);
+                assert_eq!(reader.size(), content.len() as u64);
+
+                reader.read(0, reader.size()).map(
+                    move |data| {
+                        assert_eq!(data, content);
+                        (file, content)
+                    },
+                )
+            })
+            .then(move |res| {
+                let (file, content) = unwrap!(res);
+                file_helper::write(c3, file, Mode::Append, None)
+                    .and_then(|writer| {
+                        writer.write(&[1u8; APPEND_SIZE]).and_then(move |_| writer.close())
+                    })
+                    .map(move |file| (file, content))
+            })
+            .then(move |res| {
+                let (file, content) = unwrap!(res);
+                assert_eq!(file.size(), (content.len() + APPEND_SIZE) as u64);
+
+                file_helper::read(c4, &file, None).map(move |reader| (reader, content))
+            })
+            .then(|res| {
+                let (reader, mut content) = unwrap!(res);
+                content.extend_from_slice(&[1u8; APPEND_SIZE]);
+                assert_eq!(reader.size(), content.len() as u64);
+
+                reader.read(0, reader.size()).map(
+                    move |data| assert_eq!(data, content),
+                )
+            })
+    })
+}
+
+// Test listing a directory with a sub-directory, which gets prefetched.
+#[test]
+fn dir_list_with_prefetch() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let root = unwrap!(MDataInfo::random_private(DIR_TAG));
+        let root2 = root.clone();
+        let sub_dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+        let sub_dir2 = sub_dir.clone();
+
+        let entries = btree_map![
+            b"sub".to_vec() => Value { content: unwrap!(encode_dir(&sub_dir)), entry_version: 0 }
+        ];
+        let entries = unwrap!(mdata_info::encrypt_entries(&root, &entries));
+
+        client.set_prefetch_limit(2);
+        create_dir(client, &sub_dir, btree_map![], btree_map![])
+            .then(move |res| {
+                unwrap!(res);
+                create_dir(&c2, &root, entries, btree_map![])
+            })
+            .then(move |res| {
+                unwrap!(res);
+                list_dir(&c3, &root2)
+            })
+            .map(move |entries| {
+                assert_eq!(entries.len(), 1);
+                let value = unwrap!(entries.get(&b"sub".to_vec()));
+                let listed = unwrap!(unwrap!(decode_entry(&value.content)).into_dir());
+                assert_eq!(listed, sub_dir2);
+            })
+    })
+}
+
+// Test that listing a directory doesn't fetch the data maps of its files, and that
+// opening a file does.
+#[test]
+fn dir_list_without_data_maps() {
+    let dir = random_client(|client| create_test_file(client).map(|(dir, _)| dir));
+
+    // Use a different client, so no data map can be cached.
+    random_client(move |client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let c5 = client.clone();
+        let dir2 = dir.clone();
+
+        get_dir(client, &dir)
+            .then(move |res| {
+                let listing = unwrap!(res);
+                assert_eq!(listing.entries.len(), 1);
+                assert_eq!(listing.entries[0].size, ORIG_SIZE as u64);
+                assert!(!c2.network_stats().requests.contains_key("GetIData"));
+
+                file_helper::fetch(c3, dir2.clone(), "hello.txt").map(move |res| (dir2, res))
+            })
+            .then(move |res| {
+                let (dir, (_, file)) = unwrap!(res);
+                assert!(!c4.network_stats().requests.contains_key("GetIData"));
+                file_helper::read(c4, &file, dir.enc_key().cloned())
+            })
+            .map(move |_| {
+                assert!(c5.network_stats().requests["GetIData"].sent > 0);
+            })
+    })
+}
+
+// Updating the metadata of a file keeps its contents.
+#[test]
+fn file_update_metadata() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+
+        create_test_file(client)
+            .then(move |res| {
+                let (dir, orig_file) = unwrap!(res);
+
+                file_helper::update_metadata(c2, dir.clone(), "hello.txt", b"tags".to_vec())
+                    .map(move |res| (dir, orig_file, res))
+            })
+            .then(move |res| {
+                let (dir, orig_file, (version, file)) = unwrap!(res);
+                assert_eq!(version, 1);
+                assert_eq!(file.user_metadata(), b"tags");
+                assert_eq!(file.data_map_name(), orig_file.data_map_name());
+                assert_eq!(file.size(), orig_file.size());
+                assert!(file.modified_time() >= orig_file.modified_time());
+
+                file_helper::fetch(c3, dir, "hello.txt")
+            })
+            .map(|(version, file)| {
+                assert_eq!(version, 1);
+                assert_eq!(file.user_metadata(), b"tags");
+            })
+    })
+}
+
+// Directory metadata can be set and replaced, and doesn't show up in listings.
+#[test]
+fn dir_update_metadata() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let c5 = client.clone();
+        let c6 = client.clone();
+
+        create_test_file(client)
+            .then(move |res| {
+                let (dir, _) = unwrap!(res);
+                fetch_dir_metadata(&c2, &dir).map(move |metadata| (dir, metadata))
+            })
+            .then(move |res| {
+                let (dir, metadata) = unwrap!(res);
+                assert!(metadata.is_none());
+
+                update_dir_metadata(&c3, &dir, b"first".to_vec()).map(move |_| dir)
+            })
+            .then(move |res| {
+                let dir = unwrap!(res);
+                update_dir_metadata(&c4, &dir, b"second".to_vec())
+                    .map(move |metadata| (dir, metadata))
+            })
+            .then(move |res| {
+                let (dir, stored) = unwrap!(res);
+                fetch_dir_metadata(&c5, &dir).map(move |metadata| (dir, stored, metadata))
+            })
+            .then(move |res| {
+                let (dir, stored, metadata) = unwrap!(res);
+                let metadata = unwrap!(metadata);
+                assert_eq!(metadata, stored);
+                assert_eq!(metadata.user_metadata, b"second");
+
+                get_dir(&c6, &dir)
+            })
+            .map(|listing| {
+                assert_eq!(listing.entries.len(), 1);
+                assert_eq!(listing.entries[0].name, "hello.txt");
+            })
+    })
+}
+
+// A directory listing is returned only when the directory has changed since the known version.
+#[test]
+fn dir_fetch_if_changed() {
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+        let c5 = client.clone();
+
+        create_test_file(client)
+            .then(move |res| {
+                let (dir, file) = unwrap!(res);
+                get_dir_if_changed(&c2, &dir, 0).map(move |fetched| (dir, file, fetched))
+            })
+            .then(move |res| {
+                let (dir, file, fetched) = unwrap!(res);
+                let version = match fetched {
+                    Fetched::Modified { version, data } => {
+                        assert_eq!(data.entries.len(), 1);
+                        version
+                    }
+                    Fetched::NotModified => panic!("Unexpected NotModified"),
+                };
+
+                get_dir_if_changed(&c3, &dir, version)
+                    .map(move |fetched| (dir, file, version, fetched))
+            })
+            .then(move |res| {
+                let (dir, file, version, fetched) = unwrap!(res);
+                assert_eq!(fetched, Fetched::NotModified);
+
+                file_helper::insert(c4, dir.clone(), "world.txt", &file)
+                    .map(move |_| (dir, version))
+            })
+            .then(move |res| {
+                let (dir, version) = unwrap!(res);
+                get_dir_if_changed(&c5, &dir, version).map(move |fetched| (version, fetched))
+            })
+            .map(|(old_version, fetched)| match fetched {
+                Fetched::Modified { version, data } => {
+                    assert_ne!(version, old_version);
+                    assert_eq!(data.entries.len(), 2);
+                }
+                Fetched::NotModified => panic!("Unexpected NotModified"),
+            })
+    })
+}
+
+fn legacy_tm(hour: i32) -> legacy::Tm {
+    legacy::Tm {
+        tm_sec: 45,
+        tm_min: 30,
+        tm_hour: hour,
+        tm_mday: 15,
+        tm_mon: 2,
+        tm_year: 117,
+        tm_wday: 3,
+        tm_yday: 73,
+        tm_isdst: 0,
+        tm_utcoff: 3600,
+        tm_nsec: 0,
+    }
+}
+
+fn legacy_dir(name: &str, parent: Option<legacy::DirectoryKey>) -> legacy::DirectoryMetadata {
+    legacy::DirectoryMetadata {
+        key: legacy::DirectoryKey {
+            id: rand::random(),
+            type_tag: 15_000,
+            versioned: false,
+            access_level: legacy::AccessLevel::Public,
+        },
+        name: name.to_owned(),
+        created_time: legacy_tm(12),
+        modified_time: legacy_tm(13),
+        user_metadata: Vec::new(),
+        parent_dir_key: parent,
+    }
+}
+
+// Test reading a directory and a file written by releases predating `MutableData`.
+#[test]
+fn legacy_listing() {
+    const CONTENT: &[u8] = b"written by an older client";
+
+    random_client(|client| {
+        let c2 = client.clone();
+        let c3 = client.clone();
+        let c4 = client.clone();
+
+        file_helper::write(client.clone(), File::new(Vec::new()), Mode::Overwrite, None)
+            .then(move |res| {
+                let writer = unwrap!(res);
+                writer.write(CONTENT).and_then(move |_| writer.close())
+            })
+            .then(move |res| {
+                let file = unwrap!(res);
+                data_map::get(&c2, file.data_map_name(), None)
+            })
+            .then(move |res| {
+                let root = legacy_dir("root", None);
+                let sub_dir = legacy_dir("docs", Some(root.key.clone()));
+                let listing = legacy::DirectoryListing {
+                    metadata: root,
+                    sub_directories: vec![sub_dir],
+                    files: vec![
+                        legacy::LegacyFile {
+                            metadata: legacy::FileMetadata {
+                                name: "file.txt".to_owned(),
+                                size: CONTENT.len() as u64,
+                                created_time: legacy_tm(12),
+                                modified_time: legacy_tm(13),
+                                user_metadata: b"text/plain".to_vec(),
+                            },
+                            datamap: unwrap!(res),
+                        },
+                    ],
+                };
+
+                let content = unwrap!(serialise(&listing));
+                let data = legacy::StructuredData {
+                    type_tag: listing.metadata.key.type_tag,
+                    identifier: listing.metadata.key.id,
+                    data: unwrap!(serialise(&legacy::DataTypeEncoding::Data(content))),
+                    previous_owner_keys: Vec::new(),
+                    version: 3,
+                    current_owner_keys: Vec::new(),
+                    previous_owner_signatures: Vec::new(),
+                };
+
+                let data = unwrap!(legacy::StructuredData::parse(&unwrap!(serialise(&data))));
+                data.content(&c3)
+            })
+            .then(move |res| {
+                let listing = unwrap!(legacy::DirectoryListing::parse(&unwrap!(res)));
+                let docs = unwrap!(listing.find_sub_directory("docs"));
+                assert_eq!(docs.parent_dir_key, Some(listing.metadata.key.clone()));
+
+                let files = unwrap!(listing.list_files());
+                assert_eq!(files.len(), 1);
+                let file = unwrap!(files.get("file.txt"));
+                assert_eq!(file.size(), CONTENT.len() as u64);
+                assert_eq!(file.user_metadata(), b"text/plain");
+                assert_eq!(file.created_time().timestamp(), 1_489_577_445);
+                assert_eq!(file.modified_time().timestamp(), 1_489_581_045);
+
+                legacy::read(c4, unwrap!(listing.find_file("file.txt")))
+            })
+            .then(|res| {
+                let reader = unwrap!(res);
+                reader.read(0, reader.size())
+            })
+            .map(|content| assert_eq!(content, CONTENT))
+    })
+}
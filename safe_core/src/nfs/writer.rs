@@ -18,11 +18,15 @@
 use chrono::Utc;
 use client::Client;
 use crypto::shared_secretbox;
+use errors::CoreError;
 use futures::Future;
 use nfs::{File, NfsFuture, data_map};
+use routing::ClientError;
 use self_encryption::SequentialEncryptor;
 use self_encryption_storage::SelfEncryptionStorage;
+use std::cell::{Cell, RefCell};
 use utils::FutureExt;
+use utils::compression::{self, Compressor};
 
 /// Mode of the writer
 #[derive(Clone, Copy, Debug)]
@@ -31,6 +35,9 @@ pub enum Mode {
     Overwrite,
     /// Will append content to the existing data
     Append,
+    /// Will create new data, compressed before it gets encrypted. Readers inflate it
+    /// transparently. Suits highly compressible content such as text, JSON or logs.
+    OverwriteCompressed,
 }
 
 /// Writer is used to write contents to a File and especially in chunks if the
@@ -40,6 +47,9 @@ pub struct Writer<T> {
     file: File,
     self_encryptor: SequentialEncryptor<SelfEncryptionStorage<T>>,
     encryption_key: Option<shared_secretbox::Key>,
+    compressor: RefCell<Option<Compressor>>,
+    // Size of the content before compression.
+    size: Cell<u64>,
 }
 
 impl<T: 'static> Writer<T> {
@@ -57,7 +67,17 @@ impl<T: 'static> Writer<T> {
                     .map(Some)
                     .into_box()
             }
-            Mode::Overwrite => ok!(None),
+            Mode::Overwrite |
+            Mode::OverwriteCompressed => ok!(None),
+        };
+        let compressor = match mode {
+            Mode::OverwriteCompressed => Some(Compressor::new()),
+            Mode::Overwrite | Mode::Append => None,
+        };
+        let size = match mode {
+            Mode::Append => file.size(),
+            Mode::Overwrite |
+            Mode::OverwriteCompressed => 0,
         };
         let client = client.clone();
         fut.and_then(move |data_map| {
@@ -68,6 +88,8 @@ impl<T: 'static> Writer<T> {
                     file,
                     self_encryptor,
                     encryption_key,
+                    compressor: RefCell::new(compressor),
+                    size: Cell::new(size),
                 }
             })
             .map_err(From::from)
@@ -80,6 +102,21 @@ impl<T: 'static> Writer<T> {
             "Writer writing file data of size {} into self-encryptor.",
             data.len()
         );
+        self.size.set(self.size.get() + data.len() as u64);
+
+        let compressed;
+        let data = match *self.compressor.borrow_mut() {
+            Some(ref mut compressor) => {
+                // Readers couldn't inflate the content past this size.
+                if self.size.get() > compression::MAX_INFLATED_SIZE {
+                    return err!(CoreError::RoutingClientError(ClientError::DataTooLarge));
+                }
+                compressed = fry!(compressor.write(data));
+                &compressed[..]
+            }
+            None => data,
+        };
+
         self.self_encryptor
             .write(data)
             .map_err(From::from)
@@ -93,16 +130,28 @@ impl<T: 'static> Writer<T> {
         trace!("Writer induced self-encryptor close.");
 
         let mut file = self.file;
-        let size = self.self_encryptor.len();
         let client = self.client;
         let encryption_key = self.encryption_key;
+        let self_encryptor = self.self_encryptor;
+        let size = self.size.get();
 
-        self.self_encryptor
-            .close()
-            .map_err(From::from)
-            .and_then(move |(data_map, _)| {
-                data_map::put(&client, &data_map, encryption_key)
+        let tail = match self.compressor.into_inner() {
+            Some(compressor) => fry!(compressor.finish()),
+            None => Vec::new(),
+        };
+        let write_tail = if tail.is_empty() {
+            ok!(())
+        } else {
+            self_encryptor.write(&tail).map_err(From::from).into_box()
+        };
+
+        write_tail
+            .and_then(move |()| {
+                self_encryptor.close().map(|(data_map, _)| data_map).map_err(
+                    From::from,
+                )
             })
+            .and_then(move |data_map| data_map::put(&client, &data_map, encryption_key))
             .map(move |data_map_name| {
                 file.set_data_map_name(data_map_name);
                 file.set_modified_time(Utc::now());
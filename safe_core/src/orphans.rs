@@ -0,0 +1,265 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Detection of orphaned `ImmutableData`.
+//!
+//! Uploads which fail or get abandoned before the data is linked from anywhere leave their
+//! chunks behind. Given the names of the chunks uploaded by the account, e.g. as recorded in
+//! its `upload_ledger`, `find_orphans` walks the `MutableData` reachable from the given roots,
+//! usually the containers of the account, and reports the chunks nothing refers to. The network has no way of deleting
+//! `ImmutableData`, so orphans can only be reported, e.g. to account for the storage they
+//! take up.
+//!
+//! Recognised references are files, sub-directories, values wrapped by `mdata_value` and
+//! values consisting of just the name of the data. Chunks referred to in any other way, e.g.
+//! through share tokens handed out, are reported as orphans too, so the result is a list of
+//! candidates to review rather than of chunks safe to forget.
+
+use client::{Client, MDataInfo};
+use crypto::shared_secretbox;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use futures::future::{self, Loop};
+use immutable_data;
+use maidsafe_utilities::serialisation::deserialise;
+use mdata_value;
+use nfs::{Entry, decode_entry};
+use routing::{ClientError, XOR_NAME_LEN, XorName};
+use self_encryption::DataMap;
+use std::collections::BTreeSet;
+use utils::FutureExt;
+
+/// Returns those of the `uploaded` chunks which aren't referred to from `roots`, nor from any
+/// `MutableData` reachable from them.
+pub fn find_orphans<T: 'static>(
+    client: &Client<T>,
+    roots: Vec<MDataInfo>,
+    uploaded: BTreeSet<XorName>,
+) -> Box<CoreFuture<BTreeSet<XorName>>> {
+    referenced_chunks(client, roots)
+        .map(move |referenced| uploaded.difference(&referenced).cloned().collect())
+        .into_box()
+}
+
+/// Returns the names of all the `ImmutableData` referred to from `roots` and from the
+/// `MutableData` reachable from them, including the chunks the referred data is spread
+/// across. Entries which can't be decrypted with the `MDataInfo` they're reached through are
+/// skipped. Failing to fetch the referred data for any reason other than it not existing or
+/// not being decodable fails the whole walk.
+pub fn referenced_chunks<T: 'static>(
+    client: &Client<T>,
+    roots: Vec<MDataInfo>,
+) -> Box<CoreFuture<BTreeSet<XorName>>> {
+    let client = client.clone();
+
+    future::loop_fn(
+        (roots, BTreeSet::new(), BTreeSet::new()),
+        move |(mut pending, mut visited, mut referenced)| {
+            let info = match pending.pop() {
+                Some(info) => info,
+                None => return ok!(Loop::Break(referenced)),
+            };
+            if !visited.insert((info.name, info.type_tag)) {
+                return ok!(Loop::Continue((pending, visited, referenced)));
+            }
+
+            let client2 = client.clone();
+
+            client
+                .list_mdata_entries(info.name, info.type_tag)
+                .and_then(move |entries| {
+                    let mut follow = Vec::new();
+
+                    for value in entries.values() {
+                        if value.content.is_empty() {
+                            continue;
+                        }
+                        let content = match info.decrypt(&value.content) {
+                            Ok(content) => content,
+                            Err(_) => continue,
+                        };
+
+                        match decode_entry(&content) {
+                            Ok(Entry::Dir(sub_info)) => pending.push(sub_info),
+                            Ok(Entry::File(file)) => {
+                                follow.push(file_chunks(
+                                    &client2,
+                                    *file.data_map_name(),
+                                    info.enc_key().cloned(),
+                                ))
+                            }
+                            Err(_) => {
+                                if let Some(name) = name_of(&content) {
+                                    let key = info.enc_key().cloned();
+                                    follow.push(value_chunks(&client2, name, key));
+                                }
+                            }
+                        }
+                    }
+
+                    future::join_all(follow).map(move |chunks| {
+                        for chunks in chunks {
+                            referenced.extend(chunks);
+                        }
+                        Loop::Continue((pending, visited, referenced))
+                    })
+                })
+                .into_box()
+        },
+    ).into_box()
+}
+
+// Name of the `ImmutableData` an entry value refers to, if it's a wrapped value stored as
+// `ImmutableData` or consists of nothing but a name.
+fn name_of(content: &[u8]) -> Option<XorName> {
+    if content.len() == XOR_NAME_LEN {
+        deserialise(content).ok()
+    } else {
+        mdata_value::idata_name(content)
+    }
+}
+
+// Chunks of the data map of a file and of the file contents. If the data map doesn't exist
+// or can't be decoded, only its name is returned.
+fn file_chunks<T: 'static>(
+    client: &Client<T>,
+    data_map_name: XorName,
+    encryption_key: Option<shared_secretbox::Key>,
+) -> Box<CoreFuture<BTreeSet<XorName>>> {
+    immutable_data::get_value_with_chunks(client, &data_map_name, encryption_key)
+        .map(move |(value, mut chunks)| {
+            if let Ok(data_map) = deserialise::<DataMap>(&value) {
+                chunks.extend(immutable_data::chunk_names(&data_map));
+            }
+            chunks
+        })
+        .or_else(move |error| if is_unreadable(&error) {
+            Ok(btree_set![data_map_name])
+        } else {
+            Err(error)
+        })
+        .into_box()
+}
+
+// Chunks of a value stored with `immutable_data::create`. If the value doesn't exist or can't
+// be decoded, only its name is returned.
+fn value_chunks<T: 'static>(
+    client: &Client<T>,
+    name: XorName,
+    encryption_key: Option<shared_secretbox::Key>,
+) -> Box<CoreFuture<BTreeSet<XorName>>> {
+    immutable_data::get_value_with_chunks(client, &name, encryption_key)
+        .map(|(_, chunks)| chunks)
+        .or_else(move |error| if is_unreadable(&error) {
+            Ok(btree_set![name])
+        } else {
+            Err(error)
+        })
+        .into_box()
+}
+
+// Whether the error means the data doesn't exist or can't be decoded. Other errors, e.g.
+// timeouts, say nothing about the chunks of the data, so they have to be propagated lest
+// chunks in use get reported as orphans.
+fn is_unreadable(error: &CoreError) -> bool {
+    match *error {
+        CoreError::RoutingClientError(ClientError::NoSuchData) |
+        CoreError::EncodeDecodeError(_) |
+        CoreError::SymmetricDecipherFailure => true,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use nfs::{File, Mode, create_dir, file_helper, journal};
+    use utils::test_utils::random_client;
+
+    // Test that chunks of files in sub-directories are referenced and the others reported.
+    #[test]
+    fn find_orphans_in_dir_tree() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+            let c8 = client.clone();
+
+            let root = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let sub_dir = unwrap!(MDataInfo::random_private(DIR_TAG));
+            let root2 = root.clone();
+            let sub_dir2 = sub_dir.clone();
+
+            create_dir(client, &root, btree_map![], btree_map![])
+                .then(move |res| {
+                    unwrap!(res);
+                    journal::create_sub_dir(&c2, &root, "sub", &sub_dir, btree_map![])
+                        .map(move |_| (root, sub_dir))
+                })
+                .then(move |res| {
+                    let (root, sub_dir) = unwrap!(res);
+                    file_helper::write(
+                        c3,
+                        File::new(Vec::new()),
+                        Mode::Overwrite,
+                        sub_dir.enc_key().cloned(),
+                    ).map(move |writer| (root, sub_dir, writer))
+                })
+                .then(move |res| {
+                    let (root, sub_dir, writer) = unwrap!(res);
+                    writer
+                        .write(&[1u8; 4096])
+                        .and_then(move |_| writer.close())
+                        .map(move |file| (root, sub_dir, file))
+                })
+                .then(move |res| {
+                    let (root, sub_dir, file) = unwrap!(res);
+                    file_helper::insert(c4, sub_dir, "file.txt", &file).map(move |_| (root, file))
+                })
+                .then(move |res| {
+                    let (root, file) = unwrap!(res);
+                    // Uploaded, but never linked from anywhere.
+                    immutable_data::create(&c5, &[2u8; 4096], None)
+                        .and_then(move |data| {
+                            let name = *data.name();
+                            c6.put_idata(data).map(move |_| name)
+                        })
+                        .map(move |orphan| (root, file, orphan))
+                })
+                .then(move |res| {
+                    let (root, file, orphan) = unwrap!(res);
+                    referenced_chunks(&c7, vec![root]).map(move |chunks| (file, orphan, chunks))
+                })
+                .then(move |res| {
+                    let (file, orphan, chunks) = unwrap!(res);
+                    assert!(chunks.contains(file.data_map_name()));
+                    assert!(!chunks.contains(&orphan));
+
+                    let mut uploaded = chunks.clone();
+                    let _ = uploaded.insert(orphan);
+
+                    find_orphans(&c8, vec![root2, sub_dir2], uploaded)
+                        .map(move |orphans| (orphan, orphans))
+                })
+                .map(|(orphan, orphans)| assert_eq!(orphans, btree_set![orphan]))
+        })
+    }
+}
@@ -0,0 +1,256 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Client-side search indices.
+//!
+//! An index is a private big map holding an inverted index from tokens to the references of
+//! the entries they occur in, plus the tokens each reference is currently indexed under so
+//! it can be removed again. Every token and every reference has an entry of its own, which
+//! the big map spreads over as many shards as the index needs, so it isn't limited by the
+//! capacity of a single `MutableData`. References are opaque to the index - they can be e.g.
+//! NFS paths or `MutableData` entry keys - and it's up to the app to feed the index whenever
+//! it changes the data being indexed.
+
+use big_map;
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::{Future, Stream, stream};
+use futures::future;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::ClientError;
+use std::collections::BTreeSet;
+use utils::FutureExt;
+
+// Keys of the entries mapping a token to references.
+const TOKEN_KEY_PREFIX: &[u8] = b"token:";
+// Keys of the entries mapping a reference to tokens.
+const REF_KEY_PREFIX: &[u8] = b"ref:";
+
+/// Creates a new empty index and returns its `MDataInfo`.
+pub fn create<T: 'static>(client: &Client<T>) -> Box<CoreFuture<MDataInfo>> {
+    big_map::create(client, true)
+}
+
+/// Splits text into the tokens it's indexed or searched by: lowercase runs of
+/// alphanumeric characters.
+pub fn tokenize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|token| !token.is_empty())
+        .map(|token| token.to_lowercase())
+        .collect()
+}
+
+/// Indexes `text` under `entry_ref`, replacing whatever was indexed under it before.
+/// The entries affected are updated one by one; if that's interrupted, indexing the same
+/// reference again completes it.
+pub fn index_add<T: 'static>(
+    client: &Client<T>,
+    index: &MDataInfo,
+    entry_ref: Vec<u8>,
+    text: &str,
+) -> Box<CoreFuture<()>> {
+    let tokens = tokenize(text).into_iter().map(String::into_bytes).collect();
+    set_tokens(client, index, entry_ref, tokens)
+}
+
+/// Removes `entry_ref` from the index.
+pub fn index_remove<T: 'static>(
+    client: &Client<T>,
+    index: &MDataInfo,
+    entry_ref: Vec<u8>,
+) -> Box<CoreFuture<()>> {
+    set_tokens(client, index, entry_ref, BTreeSet::new())
+}
+
+/// Returns references of the entries containing all the tokens of `query`.
+pub fn search<T: 'static>(
+    client: &Client<T>,
+    index: &MDataInfo,
+    query: &str,
+) -> Box<CoreFuture<BTreeSet<Vec<u8>>>> {
+    let fetches: Vec<_> = tokenize(query)
+        .into_iter()
+        .map(|token| {
+            fetch_set(client, index, &entry_key(TOKEN_KEY_PREFIX, token.as_bytes()))
+        })
+        .collect();
+
+    future::join_all(fetches)
+        .map(|sets| {
+            let mut sets = sets.into_iter();
+            let first = sets.next().unwrap_or_default();
+            sets.fold(first, |acc, refs| acc.intersection(&refs).cloned().collect())
+        })
+        .into_box()
+}
+
+// Makes `entry_ref` indexed under exactly `tokens`. The entries of the tokens are updated
+// before that of the reference, which keeps listing the old tokens until the very end, so
+// whichever tokens an interrupted update leaves stale are updated again by the next one.
+fn set_tokens<T: 'static>(
+    client: &Client<T>,
+    index: &MDataInfo,
+    entry_ref: Vec<u8>,
+    tokens: BTreeSet<Vec<u8>>,
+) -> Box<CoreFuture<()>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+    let index = index.clone();
+    let ref_key = entry_key(REF_KEY_PREFIX, &entry_ref);
+
+    fetch_set(client, &index, &ref_key)
+        .and_then(move |old_tokens| {
+            let fetches: Vec<_> = old_tokens
+                .union(&tokens)
+                .cloned()
+                .map(|token| {
+                    let key = entry_key(TOKEN_KEY_PREFIX, &token);
+                    fetch_set(&c2, &index, &key).map(move |refs| (token, key, refs))
+                })
+                .collect();
+
+            future::join_all(fetches).map(move |token_sets| {
+                (index, ref_key, entry_ref, old_tokens, tokens, token_sets)
+            })
+        })
+        .and_then(move |(index, ref_key, entry_ref, old_tokens, tokens, token_sets)| {
+            let mut updates = Vec::new();
+            for (token, key, mut refs) in token_sets {
+                let changed = if tokens.contains(&token) {
+                    refs.insert(entry_ref.clone())
+                } else {
+                    refs.remove(&entry_ref)
+                };
+                if changed {
+                    updates.push((key, refs));
+                }
+            }
+            if tokens != old_tokens {
+                updates.push((ref_key, tokens));
+            }
+
+            // One at a time, as updates of the same shard could otherwise split it twice.
+            stream::iter_ok::<_, CoreError>(updates)
+                .for_each(move |(key, set)| store_set(&c3, &index, &key, &set))
+        })
+        .into_box()
+}
+
+// Fetches the set stored under `key`, or an empty set if there's no such entry.
+fn fetch_set<T: 'static>(
+    client: &Client<T>,
+    index: &MDataInfo,
+    key: &[u8],
+) -> Box<CoreFuture<BTreeSet<Vec<u8>>>> {
+    big_map::get(client, index, key)
+        .then(|res| match res {
+            Ok(value) => Ok(deserialise(&value.content)?),
+            Err(CoreError::RoutingClientError(ClientError::NoSuchEntry)) => Ok(BTreeSet::new()),
+            Err(error) => Err(error),
+        })
+        .into_box()
+}
+
+// Stores the set under `key`, deleting the entry if the set is empty.
+fn store_set<T: 'static>(
+    client: &Client<T>,
+    index: &MDataInfo,
+    key: &[u8],
+    set: &BTreeSet<Vec<u8>>,
+) -> Box<CoreFuture<()>> {
+    if !set.is_empty() {
+        let value = fry!(serialise(set));
+        return big_map::put(client, index, key, &value);
+    }
+
+    big_map::delete(client, index, key)
+        .or_else(|error| match error {
+            CoreError::RoutingClientError(ClientError::NoSuchEntry) => Ok(()),
+            error => Err(error),
+        })
+        .into_box()
+}
+
+fn entry_key(prefix: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut key = prefix.to_vec();
+    key.extend_from_slice(data);
+    key
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use utils::test_utils::random_client;
+
+    // Test splitting text into tokens.
+    #[test]
+    fn tokens() {
+        let expected = btree_set!["hello".to_string(), "safe".to_string(), "world".to_string()];
+        assert_eq!(tokenize("Hello, SAFE-world! hello"), expected);
+        assert!(tokenize(" ,. ").is_empty());
+    }
+
+    // Test adding, replacing and removing indexed entries.
+    #[test]
+    fn add_search_remove() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+            let c8 = client.clone();
+
+            create(client)
+                .then(move |res| {
+                    let index = unwrap!(res);
+                    index_add(&c2, &index, b"doc1".to_vec(), "Hello world").map(move |_| index)
+                })
+                .then(move |res| {
+                    let index = unwrap!(res);
+                    index_add(&c3, &index, b"doc2".to_vec(), "hello there").map(move |_| index)
+                })
+                .then(move |res| {
+                    let index = unwrap!(res);
+                    search(&c4, &index, "HELLO world").map(move |refs| (index, refs))
+                })
+                .then(move |res| {
+                    let (index, refs) = unwrap!(res);
+                    assert_eq!(refs, btree_set![b"doc1".to_vec()]);
+
+                    index_add(&c5, &index, b"doc1".to_vec(), "goodbye").map(move |_| index)
+                })
+                .then(move |res| {
+                    let index = unwrap!(res);
+                    search(&c6, &index, "hello").map(move |refs| (index, refs))
+                })
+                .then(move |res| {
+                    let (index, refs) = unwrap!(res);
+                    assert_eq!(refs, btree_set![b"doc2".to_vec()]);
+
+                    index_remove(&c7, &index, b"doc2".to_vec()).map(move |_| index)
+                })
+                .then(move |res| {
+                    let index = unwrap!(res);
+                    search(&c8, &index, "hello")
+                })
+                .map(|refs| assert!(refs.is_empty()))
+        });
+    }
+}
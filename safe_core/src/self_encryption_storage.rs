@@ -17,21 +17,38 @@
 
 use super::{Client, CoreError, FutureExt};
 use futures::{self, Future};
+use futures::unsync::oneshot;
 use routing::{ImmutableData, XOR_NAME_LEN, XorName};
 use self_encryption::{Storage, StorageError};
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
+use std::rc::Rc;
+use utils::buffer_pool;
 
 /// Network storage is the concrete type which self-encryption crate will use
 /// to put or get data from the network
 pub struct SelfEncryptionStorage<T> {
     client: Client<T>,
+    put_window: Rc<PutWindow>,
 }
 
 impl<T> SelfEncryptionStorage<T> {
-    /// Create a new SelfEncryptionStorage instance
+    /// Create a new SelfEncryptionStorage instance, keeping at most as many chunk PUTs in
+    /// flight as the client's `put_window` setting allows.
     pub fn new(client: Client<T>) -> Self {
-        SelfEncryptionStorage { client: client }
+        let put_window = client.put_window();
+        Self::with_put_window(client, put_window)
+    }
+
+    /// Create a new SelfEncryptionStorage instance which dispatches chunk PUTs
+    /// concurrently, but keeps at most `put_window` of them in flight at once.
+    pub fn with_put_window(client: Client<T>, put_window: usize) -> Self {
+        SelfEncryptionStorage {
+            client: client,
+            put_window: PutWindow::new(put_window),
+        }
     }
 }
 
@@ -55,15 +72,76 @@ impl<T: 'static> Storage for SelfEncryptionStorage<T> {
 
         self.client
             .get_idata(name)
-            .map(|data| data.value().clone())
+            .map(|data| buffer_pool::copy(data.value()))
             .map_err(From::from)
             .into_box()
     }
 
     fn put(&mut self, _: Vec<u8>, data: Vec<u8>) -> Box<Future<Item = (), Error = Self::Error>> {
         trace!("Self encrypt invoked PutIData.");
+        let client = self.client.clone();
         let data = ImmutableData::new(data);
-        self.client.put_idata(data).map_err(From::from).into_box()
+
+        PutWindow::acquire(&self.put_window)
+            .and_then(move |permit| {
+                client.put_idata(data).then(move |res| {
+                    drop(permit);
+                    res
+                })
+            })
+            .map_err(From::from)
+            .into_box()
+    }
+}
+
+// Limits the number of PUTs in flight. Every PUT holds a `Permit` while in
+// flight; PUTs beyond the limit wait in a queue for one to be dropped.
+struct PutWindow {
+    limit: usize,
+    in_flight: Cell<usize>,
+    waiting: RefCell<VecDeque<oneshot::Sender<Permit>>>,
+}
+
+impl PutWindow {
+    fn new(limit: usize) -> Rc<Self> {
+        Rc::new(PutWindow {
+            limit: limit.max(1),
+            in_flight: Cell::new(0),
+            waiting: RefCell::new(VecDeque::new()),
+        })
+    }
+
+    fn acquire(window: &Rc<Self>) -> Box<Future<Item = Permit, Error = CoreError>> {
+        if window.in_flight.get() < window.limit {
+            window.in_flight.set(window.in_flight.get() + 1);
+            return ok!(Permit(Rc::clone(window)));
+        }
+
+        let (tx, rx) = oneshot::channel();
+        window.waiting.borrow_mut().push_back(tx);
+        rx.map_err(|_| CoreError::Unexpected("PUT window dropped".to_owned()))
+            .into_box()
+    }
+
+    // Passes the slot of a dropped permit on to the first waiting PUT.
+    fn release(window: &Rc<Self>) {
+        let tx = window.waiting.borrow_mut().pop_front();
+        match tx {
+            // If the PUT is gone, dropping the returned permit passes the slot
+            // on to the next one.
+            Some(tx) => {
+                let _ = tx.send(Permit(Rc::clone(window)));
+            }
+            None => window.in_flight.set(window.in_flight.get() - 1),
+        }
+    }
+}
+
+struct Permit(Rc<PutWindow>);
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        PutWindow::release(&self.0);
     }
 }
 
@@ -94,3 +172,35 @@ impl From<CoreError> for SelfEncryptionStorageError {
 }
 
 impl StorageError for SelfEncryptionStorageError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tokio_core::reactor::Core;
+
+    // Test that at most `limit` permits are handed out at once.
+    #[test]
+    fn put_window() {
+        let mut core = unwrap!(Core::new());
+        let window = PutWindow::new(2);
+
+        let res: Result<_, ()> = core.run(future::lazy(move || {
+            let first = unwrap!(PutWindow::acquire(&window).wait());
+            let _second = unwrap!(PutWindow::acquire(&window).wait());
+
+            let mut third = PutWindow::acquire(&window);
+            let mut fourth = PutWindow::acquire(&window);
+            assert!(unwrap!(third.poll()).is_not_ready());
+
+            // A waiting PUT which is gone must not take the slot.
+            drop(third);
+            drop(first);
+            assert!(unwrap!(fourth.poll()).is_ready());
+            assert_eq!(window.in_flight.get(), 2);
+
+            Ok(())
+        }));
+        unwrap!(res);
+    }
+}
@@ -0,0 +1,438 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Read-only snapshots of `MutableData`.
+//!
+//! A snapshot captures the whole `MutableData` with a single request, so the version,
+//! permissions and entries it holds are consistent with each other. It's stored as
+//! `ImmutableData`, which makes it suitable for point-in-time backups and audit copies.
+//! Entries are kept as they're stored on the network, so those of private data stay
+//! encrypted and the snapshot itself is encrypted with the same key.
+//!
+//! Each snapshot records an identifier of the key it's sealed with, so it can still be
+//! opened while the `MutableData` is being re-encrypted. Snapshots aren't re-encrypted
+//! along with the data though: use `reseal` on each of them between
+//! `MDataInfo::start_new_enc_info` and `MDataInfo::commit_new_enc_info`, otherwise they
+//! can't be opened with the committed `MDataInfo` anymore.
+
+use client::{Client, LazyEntries, MDataInfo};
+use client::mdata_info;
+use crypto::shared_secretbox;
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::Future;
+use immutable_data;
+use ipc::now_secs;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{MutableData, PermissionSet, User, Value, XorName};
+use rust_sodium::crypto::{secretbox, sign};
+use std::collections::{BTreeMap, BTreeSet};
+use tiny_keccak::sha3_256;
+use utils::{self, FutureExt};
+
+// Snapshot as it's stored on the network. `key_id` identifies the key `content` is
+// sealed with and is `None` for snapshots of public data, whose content isn't encrypted.
+#[derive(Serialize, Deserialize)]
+struct SealedSnapshot {
+    key_id: Option<[u8; 32]>,
+    content: Vec<u8>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    name: XorName,
+    type_tag: u64,
+    version: u64,
+    taken_at: u64,
+    owners: BTreeSet<sign::PublicKey>,
+    permissions: BTreeMap<User, PermissionSet>,
+    entries: BTreeMap<Vec<u8>, Value>,
+}
+
+/// Read-only view of a snapshot.
+#[derive(Clone, Debug)]
+pub struct SnapshotReader {
+    name: XorName,
+    type_tag: u64,
+    version: u64,
+    taken_at: u64,
+    owners: BTreeSet<sign::PublicKey>,
+    permissions: BTreeMap<User, PermissionSet>,
+    entries: LazyEntries,
+}
+
+impl SnapshotReader {
+    /// Name of the `MutableData` the snapshot was taken of.
+    pub fn name(&self) -> &XorName {
+        &self.name
+    }
+
+    /// Type tag of the `MutableData` the snapshot was taken of.
+    pub fn type_tag(&self) -> u64 {
+        self.type_tag
+    }
+
+    /// Version of the `MutableData` shell at the time of the snapshot.
+    pub fn version(&self) -> u64 {
+        self.version
+    }
+
+    /// Time the snapshot was taken at, in seconds since the Unix epoch.
+    pub fn taken_at(&self) -> u64 {
+        self.taken_at
+    }
+
+    /// Owners of the `MutableData` at the time of the snapshot.
+    pub fn owners(&self) -> &BTreeSet<sign::PublicKey> {
+        &self.owners
+    }
+
+    /// Permissions of the `MutableData` at the time of the snapshot.
+    pub fn permissions(&self) -> &BTreeMap<User, PermissionSet> {
+        &self.permissions
+    }
+
+    /// Returns the decrypted value of the entry with the given plain text key, as it was
+    /// at the time of the snapshot.
+    pub fn get(&self, key: &[u8]) -> Result<Option<Value>, CoreError> {
+        self.entries.get(key)
+    }
+
+    /// Entries of the snapshot, decrypted only as they're accessed.
+    pub fn entries(&self) -> &LazyEntries {
+        &self.entries
+    }
+}
+
+/// Takes a snapshot of the `MutableData` described by `info` and stores it on the network.
+/// Returns the name of the `ImmutableData` holding it.
+pub fn take<T: 'static>(client: &Client<T>, info: &MDataInfo) -> Box<CoreFuture<XorName>> {
+    let client2 = client.clone();
+    let info = info.clone();
+
+    client
+        .get_mdata(info.name, info.type_tag)
+        .and_then(move |data| {
+            let content = fry!(serialise(&from_mdata(data)));
+            store(&client2, &content, info.enc_info.as_ref())
+        })
+        .into_box()
+}
+
+/// Fetches the snapshot stored under `name`. `info` has to describe the same
+/// `MutableData` the snapshot was taken of, with either its current or its new
+/// encryption info matching the key the snapshot is sealed with.
+pub fn open<T: 'static>(
+    client: &Client<T>,
+    name: &XorName,
+    info: &MDataInfo,
+) -> Box<CoreFuture<SnapshotReader>> {
+    let info = info.clone();
+
+    fetch(client, name, &info)
+        .map(move |(snapshot, sealed_with)| {
+            SnapshotReader {
+                name: snapshot.name,
+                type_tag: snapshot.type_tag,
+                version: snapshot.version,
+                taken_at: snapshot.taken_at,
+                owners: snapshot.owners,
+                permissions: snapshot.permissions,
+                entries: LazyEntries::new(sealed_with, snapshot.entries),
+            }
+        })
+        .into_box()
+}
+
+/// Re-encrypts the snapshot stored under `name` with the new encryption info of `info`,
+/// which has to be set with `MDataInfo::start_new_enc_info` beforehand. Returns the name
+/// of the `ImmutableData` holding the re-encrypted snapshot, which is `name` itself if
+/// the snapshot is already sealed with the new key. The old snapshot is left in place.
+pub fn reseal<T: 'static>(
+    client: &Client<T>,
+    name: &XorName,
+    info: &MDataInfo,
+) -> Box<CoreFuture<XorName>> {
+    let new_info = match info.new_enc_info {
+        Some(ref new_enc_info) => with_enc_info(info, new_enc_info),
+        None => return err!(CoreError::from("No new encryption info to reseal with")),
+    };
+
+    let client2 = client.clone();
+    let name = *name;
+
+    fetch(client, &name, info)
+        .and_then(move |(mut snapshot, sealed_with)| {
+            if sealed_with == new_info {
+                return ok!(name);
+            }
+
+            let entries = fry!(mdata_info::decrypt_entries(&sealed_with, &snapshot.entries));
+            snapshot.entries = fry!(mdata_info::encrypt_entries(&new_info, &entries));
+
+            let content = fry!(serialise(&snapshot));
+            store(&client2, &content, new_info.enc_info.as_ref())
+        })
+        .into_box()
+}
+
+// Seals `content` with `enc_info` and puts the result on the network.
+fn store<T: 'static>(
+    client: &Client<T>,
+    content: &[u8],
+    enc_info: Option<&(shared_secretbox::Key, secretbox::Nonce)>,
+) -> Box<CoreFuture<XorName>> {
+    let sealed = match enc_info {
+        Some(&(ref key, _)) => SealedSnapshot {
+            key_id: Some(key_id(key)),
+            content: fry!(utils::symmetric_encrypt(content, key, None)),
+        },
+        None => SealedSnapshot {
+            key_id: None,
+            content: content.to_vec(),
+        },
+    };
+    let encoded = fry!(serialise(&sealed));
+    let client2 = client.clone();
+
+    immutable_data::create(client, &encoded, None)
+        .and_then(move |data| {
+            let name = *data.name();
+            client2.put_idata(data).map(move |_| name)
+        })
+        .into_box()
+}
+
+// Fetches and unseals the snapshot stored under `name`. Returns it along with the
+// `MDataInfo` holding only the encryption info it's sealed with, which its entries are
+// encrypted with too.
+fn fetch<T: 'static>(
+    client: &Client<T>,
+    name: &XorName,
+    info: &MDataInfo,
+) -> Box<CoreFuture<(Snapshot, MDataInfo)>> {
+    let info = info.clone();
+
+    immutable_data::get_value(client, name, None)
+        .and_then(move |encoded| {
+            let sealed: SealedSnapshot = deserialise(&encoded)?;
+            let sealed_with = sealing_info(&info, sealed.key_id.as_ref())?;
+
+            let content = match sealed_with.enc_key() {
+                Some(key) => utils::symmetric_decrypt(&sealed.content, key)?,
+                None => sealed.content,
+            };
+
+            let snapshot: Snapshot = deserialise(&content)?;
+            if snapshot.name != info.name || snapshot.type_tag != info.type_tag {
+                return Err(CoreError::from("Snapshot of a different MutableData"));
+            }
+
+            Ok((snapshot, sealed_with))
+        })
+        .into_box()
+}
+
+// Picks the encryption info of `info` whose key has the given id.
+fn sealing_info(info: &MDataInfo, id: Option<&[u8; 32]>) -> Result<MDataInfo, CoreError> {
+    let id = match id {
+        Some(id) => id,
+        None if info.enc_info.is_none() => return Ok(info.clone()),
+        None => return Err(CoreError::from("Snapshot of public data")),
+    };
+
+    info.enc_info
+        .iter()
+        .chain(info.new_enc_info.iter())
+        .find(|&&(ref key, _)| key_id(key) == *id)
+        .map(|enc_info| with_enc_info(info, enc_info))
+        .ok_or_else(|| CoreError::from("Snapshot sealed with an unknown key"))
+}
+
+fn with_enc_info(
+    info: &MDataInfo,
+    enc_info: &(shared_secretbox::Key, secretbox::Nonce),
+) -> MDataInfo {
+    MDataInfo {
+        enc_info: Some(enc_info.clone()),
+        new_enc_info: None,
+        ..info.clone()
+    }
+}
+
+fn key_id(key: &shared_secretbox::Key) -> [u8; 32] {
+    sha3_256(&[&b"snapshot key id"[..], &(**key).0[..]].concat())
+}
+
+fn from_mdata(data: MutableData) -> Snapshot {
+    Snapshot {
+        name: *data.name(),
+        type_tag: data.tag(),
+        version: data.version(),
+        taken_at: now_secs(),
+        owners: data.owners().clone(),
+        permissions: data.permissions().clone(),
+        entries: data.entries().clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::mdata_info;
+    use routing::EntryActions;
+    use utils::test_utils::random_client;
+
+    // Test that a snapshot keeps serving the entries it was taken with.
+    #[test]
+    fn point_in_time() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+
+            let info = unwrap!(MDataInfo::random_private(15_002));
+            let info2 = info.clone();
+            let info3 = info.clone();
+            let info4 = info.clone();
+
+            let entries = btree_map![
+                b"key".to_vec() => Value { content: b"old".to_vec(), entry_version: 0 }
+            ];
+            let entries = unwrap!(mdata_info::encrypt_entries(&info, &entries));
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                info.name,
+                info.type_tag,
+                Default::default(),
+                entries,
+                owners,
+            ));
+
+            client
+                .put_mdata(data)
+                .then(move |res| {
+                    unwrap!(res);
+                    take(&client2, &info2)
+                })
+                .then(move |res| {
+                    let snapshot_name = unwrap!(res);
+                    let key = unwrap!(info3.enc_entry_key(b"key"));
+                    let value = unwrap!(info3.enc_entry_value(b"new"));
+                    let actions = EntryActions::new().update(key, value, 1);
+
+                    client3
+                        .mutate_mdata_entries(info3.name, info3.type_tag, actions.into())
+                        .map(move |_| snapshot_name)
+                })
+                .then(move |res| {
+                    let snapshot_name = unwrap!(res);
+                    open(&client4, &snapshot_name, &info4)
+                })
+                .then(|res| {
+                    let reader = unwrap!(res);
+                    assert_eq!(reader.version(), 0);
+                    assert_eq!(reader.entries().len(), 1);
+
+                    let value = unwrap!(unwrap!(reader.get(b"key")));
+                    assert_eq!(value.content, b"old");
+                    assert_eq!(value.entry_version, 0);
+                    assert!(unwrap!(reader.get(b"missing")).is_none());
+
+                    Ok::<_, CoreError>(())
+                })
+        });
+    }
+
+    // Test that a snapshot can be opened during re-encryption of the data and, once
+    // resealed, after it.
+    #[test]
+    fn reseal_after_rotation() {
+        random_client(|client| {
+            let client2 = client.clone();
+            let client3 = client.clone();
+            let client4 = client.clone();
+            let client5 = client.clone();
+            let client6 = client.clone();
+
+            let mut info = unwrap!(MDataInfo::random_private(15_002));
+            let entries = btree_map![
+                b"key".to_vec() => Value { content: b"value".to_vec(), entry_version: 0 }
+            ];
+            let entries = unwrap!(mdata_info::encrypt_entries(&info, &entries));
+            let owners = btree_set![unwrap!(client.owner_key())];
+            let data = unwrap!(MutableData::new(
+                info.name,
+                info.type_tag,
+                Default::default(),
+                entries,
+                owners,
+            ));
+
+            let old_info = info.clone();
+            info.start_new_enc_info();
+            let rotating_info = info.clone();
+            let rotating_info2 = info.clone();
+            info.commit_new_enc_info();
+            let new_info = info.clone();
+            let new_info2 = info.clone();
+
+            client
+                .put_mdata(data)
+                .then(move |res| {
+                    unwrap!(res);
+                    take(&client2, &old_info)
+                })
+                .then(move |res| {
+                    let snapshot_name = unwrap!(res);
+                    open(&client3, &snapshot_name, &rotating_info)
+                        .map(move |reader| (snapshot_name, reader))
+                })
+                .then(move |res| {
+                    let (snapshot_name, reader) = unwrap!(res);
+                    let value = unwrap!(unwrap!(reader.get(b"key")));
+                    assert_eq!(value.content, b"value");
+
+                    reseal(&client4, &snapshot_name, &rotating_info2)
+                        .map(move |resealed_name| (snapshot_name, resealed_name))
+                })
+                .then(move |res| {
+                    let (snapshot_name, resealed_name) = unwrap!(res);
+                    assert_ne!(snapshot_name, resealed_name);
+
+                    open(&client5, &snapshot_name, &new_info)
+                        .then(move |res| {
+                            match res {
+                                Err(CoreError::Unexpected(..)) => (),
+                                Err(error) => panic!("Unexpected error {:?}", error),
+                                Ok(_) => panic!("Opened with an unknown key"),
+                            }
+
+                            open(&client6, &resealed_name, &new_info2)
+                        })
+                })
+                .then(|res| {
+                    let reader = unwrap!(res);
+                    let value = unwrap!(unwrap!(reader.get(b"key")));
+                    assert_eq!(value.content, b"value");
+                    assert_eq!(value.entry_version, 0);
+
+                    Ok::<_, CoreError>(())
+                })
+        });
+    }
+}
@@ -0,0 +1,336 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Notification topics.
+//!
+//! A topic is a public `MutableData` (with the `TOPIC_TAG` type tag) to which its owner
+//! publishes small notifications. Every notification is stored under its sequence number
+//! and the `HEAD_KEY` entry holds the sequence number of the latest one, so subscribers
+//! only need to fetch that single entry to find out whether anything new was published.
+
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::{Future, IntoFuture};
+use futures::future::{self, Loop};
+use futures::sync::oneshot;
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use routing::{Action, EntryActions, MutableData, PermissionSet, User, Value};
+use std::time::Duration;
+use tokio_core::reactor::Timeout;
+use utils::FutureExt;
+use TOPIC_TAG;
+
+/// Key of the entry holding the sequence number of the latest notification.
+pub const HEAD_KEY: &[u8] = b"_head";
+
+/// Maximum size of a single notification, in bytes. Notifications are meant to be small
+/// pointers to data stored elsewhere, and the limit keeps a topic from filling up the
+/// `MutableData` with just a few of them.
+pub const MAX_NOTIFICATION_LEN: usize = 1024;
+
+// Up to this many new notifications are fetched one by one; larger gaps are
+// fetched by listing all entries of the topic.
+const FETCH_BATCH_LEN: u64 = 16;
+
+/// Keeps a subscription created by `subscribe` alive. Dropping it cancels the
+/// subscription.
+pub struct Subscription {
+    _cancel: oneshot::Sender<()>,
+}
+
+/// Creates a new topic owned by the client and returns its `MDataInfo`.
+pub fn create<T: 'static>(client: &Client<T>) -> Box<CoreFuture<MDataInfo>> {
+    let owner_key = fry!(client.owner_key());
+    let sign_key = fry!(client.public_signing_key());
+    let topic = fry!(MDataInfo::random_public(TOPIC_TAG));
+    let head = fry!(serialise(&0u64));
+
+    let topic_md = fry!(MutableData::new(
+        topic.name,
+        topic.type_tag,
+        btree_map![
+            User::Key(sign_key) => PermissionSet::new()
+                .allow(Action::Insert)
+                .allow(Action::Update),
+        ],
+        btree_map![HEAD_KEY.to_vec() => Value { content: head, entry_version: 0 }],
+        btree_set![owner_key],
+    ));
+
+    client.put_mdata(topic_md).map(move |_| topic).into_box()
+}
+
+/// Publishes a notification to the topic and returns its sequence number.
+///
+/// Fails with `NotificationTooLarge` if the payload is larger than `MAX_NOTIFICATION_LEN`,
+/// and with `InvalidSuccessor` if another notification is published
+/// concurrently, in which case the call can be retried.
+pub fn publish<T: 'static>(
+    client: &Client<T>,
+    topic: &MDataInfo,
+    payload: Vec<u8>,
+) -> Box<CoreFuture<u64>> {
+    if payload.len() > MAX_NOTIFICATION_LEN {
+        return err!(CoreError::NotificationTooLarge);
+    }
+
+    let c2 = client.clone();
+    let name = topic.name;
+    let type_tag = topic.type_tag;
+
+    client
+        .get_mdata_value(name, type_tag, HEAD_KEY.to_vec())
+        .and_then(move |value| {
+            let seq = deserialise::<u64>(&value.content)? + 1;
+            let actions = EntryActions::new().ins(seq_key(seq), payload, 0).update(
+                HEAD_KEY.to_vec(),
+                serialise(&seq)?,
+                value.entry_version + 1,
+            );
+            Ok((seq, actions))
+        })
+        .and_then(move |(seq, actions)| {
+            c2.mutate_mdata_entries(name, type_tag, actions.into())
+                .map(move |_| seq)
+        })
+        .into_box()
+}
+
+/// Gets the sequence number of the latest notification published to the topic,
+/// or 0 if there are none.
+pub fn head<T: 'static>(client: &Client<T>, topic: &MDataInfo) -> Box<CoreFuture<u64>> {
+    client
+        .get_mdata_value(topic.name, topic.type_tag, HEAD_KEY.to_vec())
+        .and_then(|value| Ok(deserialise(&value.content)?))
+        .into_box()
+}
+
+/// Fetches notifications published after the one with sequence number `since`.
+/// Returns the sequence number of the latest notification together with the
+/// new notifications, oldest first.
+pub fn fetch_since<T: 'static>(
+    client: &Client<T>,
+    topic: &MDataInfo,
+    since: u64,
+) -> Box<CoreFuture<(u64, Vec<(u64, Vec<u8>)>)>> {
+    let c2 = client.clone();
+    let name = topic.name;
+    let type_tag = topic.type_tag;
+
+    head(client, topic)
+        .and_then(move |head| if head <= since {
+            ok!((head, Vec::new()))
+        } else if head - since <= FETCH_BATCH_LEN {
+            let gets = (since + 1..head + 1).map(|seq| {
+                c2.get_mdata_value(name, type_tag, seq_key(seq))
+                    .map(move |value| (seq, value.content))
+            });
+            future::join_all(gets)
+                .map(move |notifications| (head, notifications))
+                .into_box()
+        } else {
+            c2.list_mdata_entries(name, type_tag)
+                .map(move |entries| {
+                    let mut notifications: Vec<_> = entries
+                        .into_iter()
+                        .filter_map(|(key, value)| {
+                            decode_seq_key(&key)
+                                .and_then(|seq| if seq > since && seq <= head {
+                                    Some((seq, value.content))
+                                } else {
+                                    None
+                                })
+                        })
+                        .collect();
+                    notifications.sort_by_key(|&(seq, _)| seq);
+                    (head, notifications)
+                })
+                .into_box()
+        })
+        .into_box()
+}
+
+/// Subscribes to notifications published to the topic after the one with
+/// sequence number `since`. The topic is polled every `interval` and `notify` is
+/// called with the sequence number and payload of every new notification.
+///
+/// The returned future drives the subscription and has to be run on the client's
+/// event loop. It completes once the returned `Subscription` is dropped.
+pub fn subscribe<T, F>(
+    client: &Client<T>,
+    topic: MDataInfo,
+    since: u64,
+    interval: Duration,
+    notify: F,
+) -> (Box<CoreFuture<()>>, Subscription)
+where
+    T: 'static,
+    F: FnMut(u64, Vec<u8>) + 'static,
+{
+    let (cancel_tx, cancel_rx) = oneshot::channel();
+    let client = client.clone();
+
+    let poll = future::loop_fn((since, notify), move |(since, mut notify)| {
+        let handle = client.el_handle();
+
+        fetch_since(&client, &topic, since).then(move |res| {
+            let since = match res {
+                Ok((head, notifications)) => {
+                    for (seq, payload) in notifications {
+                        notify(seq, payload);
+                    }
+                    head
+                }
+                Err(error) => {
+                    debug!("Failed to poll topic: {:?}", error);
+                    since
+                }
+            };
+
+            Timeout::new(interval, &handle)
+                .into_future()
+                .flatten()
+                .map_err(CoreError::from)
+                .map(move |_| Loop::<(), _>::Continue((since, notify)))
+        })
+    });
+
+    let future = poll.select2(cancel_rx).then(|_| Ok(())).into_box();
+    (future, Subscription { _cancel: cancel_tx })
+}
+
+fn seq_key(seq: u64) -> Vec<u8> {
+    (0..8).rev().map(|i| (seq >> (i * 8)) as u8).collect()
+}
+
+fn decode_seq_key(key: &[u8]) -> Option<u64> {
+    if key.len() != 8 {
+        return None;
+    }
+    Some(key.iter().fold(0, |seq, byte| (seq << 8) | u64::from(*byte)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use utils::test_utils::random_client;
+
+    // Test publishing and fetching notifications.
+    #[test]
+    fn publish_fetch() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+
+            create(client)
+                .then(move |res| {
+                    let topic = unwrap!(res);
+                    let (c2b, c2c) = (c2.clone(), c2.clone());
+                    let (topic2, topic3) = (topic.clone(), topic.clone());
+
+                    publish(&c2, &topic, vec![0])
+                        .and_then(move |_| publish(&c2b, &topic2, vec![1]))
+                        .and_then(move |_| publish(&c2c, &topic3, vec![2]))
+                        .map(move |seq| (topic, seq))
+                })
+                .then(move |res| {
+                    let (topic, seq) = unwrap!(res);
+                    assert_eq!(seq, 3);
+                    fetch_since(&c3, &topic, 1).map(move |res| (topic, res))
+                })
+                .then(move |res| {
+                    let (topic, (head, notifications)) = unwrap!(res);
+                    assert_eq!(head, 3);
+                    assert_eq!(notifications, vec![(2, vec![1]), (3, vec![2])]);
+
+                    fetch_since(&c4, &topic, 3).map(move |res| (topic, res))
+                })
+                .then(move |res| {
+                    let (topic, (head, notifications)) = unwrap!(res);
+                    assert_eq!(head, 3);
+                    assert!(notifications.is_empty());
+
+                    publish(&c5, &topic, vec![0; MAX_NOTIFICATION_LEN + 1])
+                })
+                .then(|res| -> Result<_, CoreError> {
+                    match res {
+                        Err(CoreError::NotificationTooLarge) => (),
+                        res => panic!("Unexpected {:?}", res),
+                    }
+                    Ok(())
+                })
+        });
+    }
+
+    // Test that subscribers get notified about new notifications.
+    #[test]
+    fn subscribe_notify() {
+        random_client(|client| {
+            let c2 = client.clone();
+
+            create(client).and_then(move |topic| {
+                let received = Rc::new(RefCell::new(Vec::new()));
+                let received2 = Rc::clone(&received);
+
+                let (poll, subscription) = subscribe(
+                    &c2,
+                    topic.clone(),
+                    0,
+                    Duration::from_millis(10),
+                    move |seq, payload| received2.borrow_mut().push((seq, payload)),
+                );
+                c2.el_handle().spawn(poll.map_err(|_| ()));
+
+                let handle = c2.el_handle();
+
+                publish(&c2, &topic, b"ping".to_vec())
+                    .and_then(move |_| {
+                        future::loop_fn(received, move |received| {
+                            Timeout::new(Duration::from_millis(10), &handle)
+                                .into_future()
+                                .flatten()
+                                .map_err(CoreError::from)
+                                .map(move |_| if received.borrow().is_empty() {
+                                    Loop::Continue(received)
+                                } else {
+                                    Loop::Break(received)
+                                })
+                        })
+                    })
+                    .map(move |received| {
+                        drop(subscription);
+                        assert_eq!(*received.borrow(), vec![(1, b"ping".to_vec())]);
+                    })
+            })
+        });
+    }
+
+    // Test encoding of sequence numbers into entry keys.
+    #[test]
+    fn seq_keys() {
+        for seq in &[0, 1, 255, 256, u64::max_value()] {
+            assert_eq!(decode_seq_key(&seq_key(*seq)), Some(*seq));
+        }
+        assert!(seq_key(1) < seq_key(256));
+        assert_eq!(decode_seq_key(HEAD_KEY), None);
+    }
+}
@@ -0,0 +1,232 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+//! Ledger of `ImmutableData` uploads.
+//!
+//! The ledger is a private big map of batches of uploads, each holding the records of the
+//! uploaded data by name. Once it's set with `Client::set_upload_ledger`, every
+//! `ImmutableData` the client puts is recorded in it, together with its size, the time of the
+//! upload and the app which uploaded it, so users can see what takes up their storage
+//! allowance. Uploads are recorded by background jobs run through `Client::schedule` only
+//! once they've succeeded, and those succeeding while a job waits to start are recorded by
+//! it in the same batch, so recording costs one mutation per batch rather than one per
+//! upload. A failure to record a batch doesn't fail the uploads, so the ledger never lists
+//! data which didn't make it to the network, but it may miss some which did.
+
+use big_map;
+use client::{Client, MDataInfo};
+use errors::CoreError;
+use event_loop::CoreFuture;
+use futures::{Future, Stream, stream};
+use maidsafe_utilities::serialisation::{deserialise, serialise};
+use rand;
+use routing::{ClientError, XorName};
+use std::collections::BTreeMap;
+use utils::FutureExt;
+
+/// Maximum number of uploads recorded in a single batch. Larger batches are split.
+pub const MAX_BATCH_LEN: usize = 1000;
+
+/// Upload recorded in the ledger.
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
+pub struct Upload {
+    /// Size of the serialised `ImmutableData`, in bytes.
+    pub size: u64,
+    /// Time of the upload, in seconds since the Unix epoch.
+    pub uploaded_at: u64,
+    /// ID of the app which uploaded the data.
+    pub app_id: String,
+}
+
+/// Creates a new empty ledger and returns its `MDataInfo`.
+pub fn create<T: 'static>(client: &Client<T>) -> Box<CoreFuture<MDataInfo>> {
+    big_map::create(client, true)
+}
+
+/// Records the uploads of the `ImmutableData`s with the given names, as a single batch of up
+/// to `MAX_BATCH_LEN` uploads. Records of later uploads of the same data take precedence.
+pub fn record<T: 'static>(
+    client: &Client<T>,
+    ledger: &MDataInfo,
+    uploads: BTreeMap<XorName, Upload>,
+) -> Box<CoreFuture<()>> {
+    let mut batches = Vec::new();
+    let mut batch = BTreeMap::new();
+    for (name, upload) in uploads {
+        let _ = batch.insert(name, upload);
+        if batch.len() == MAX_BATCH_LEN {
+            batches.push(fry!(serialise(&batch)));
+            batch.clear();
+        }
+    }
+    if !batch.is_empty() {
+        batches.push(fry!(serialise(&batch)));
+    }
+
+    let client = client.clone();
+    let ledger = ledger.clone();
+
+    stream::iter_ok::<_, CoreError>(batches)
+        .for_each(move |batch| {
+            let id: [u8; 32] = rand::random();
+            big_map::put(&client, &ledger, &id, &batch)
+        })
+        .into_box()
+}
+
+/// Gets the record of the upload of the `ImmutableData` with the given name.
+/// Fails with `NoSuchEntry` if there is none.
+pub fn get<T: 'static>(
+    client: &Client<T>,
+    ledger: &MDataInfo,
+    name: &XorName,
+) -> Box<CoreFuture<Upload>> {
+    let name = *name;
+
+    list(client, ledger)
+        .and_then(move |mut uploads| {
+            uploads.remove(&name).ok_or_else(|| {
+                CoreError::RoutingClientError(ClientError::NoSuchEntry)
+            })
+        })
+        .into_box()
+}
+
+/// Lists all the uploads recorded in the ledger, by the names of the uploaded data.
+pub fn list<T: 'static>(
+    client: &Client<T>,
+    ledger: &MDataInfo,
+) -> Box<CoreFuture<BTreeMap<XorName, Upload>>> {
+    big_map::list(client, ledger)
+        .map(|batches| {
+            let mut uploads = BTreeMap::new();
+            for value in batches.values() {
+                let batch: BTreeMap<XorName, Upload> = match deserialise(&value.content) {
+                    Ok(batch) => batch,
+                    Err(_) => continue,
+                };
+                for (name, upload) in batch {
+                    let later = uploads.get(&name).map_or(true, |current: &Upload| {
+                        current.uploaded_at <= upload.uploaded_at
+                    });
+                    if later {
+                        let _ = uploads.insert(name, upload);
+                    }
+                }
+            }
+            uploads
+        })
+        .into_box()
+}
+
+/// Sums up the sizes of the recorded uploads per app.
+pub fn usage_by_app<T: 'static>(
+    client: &Client<T>,
+    ledger: &MDataInfo,
+) -> Box<CoreFuture<BTreeMap<String, u64>>> {
+    list(client, ledger)
+        .map(|uploads| {
+            uploads.into_iter().fold(BTreeMap::new(), |mut usage, (_, upload)| {
+                *usage.entry(upload.app_id).or_insert(0) += upload.size;
+                usage
+            })
+        })
+        .into_box()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use client::Priority;
+    use errors::CoreError;
+    use futures::unsync::oneshot;
+    use immutable_data;
+    use maidsafe_utilities::serialisation::serialised_size;
+    use utils::test_utils::random_client;
+
+    // Test that uploads are recorded once a ledger is set, and only then, and that those
+    // succeeding together are recorded in a single batch.
+    #[test]
+    fn record_uploads() {
+        random_client(|client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+            let c8 = client.clone();
+
+            // Uploaded before the ledger is set, so not recorded.
+            immutable_data::create(client, b"unrecorded", None)
+                .and_then(move |data| c2.put_idata(data))
+                .and_then(move |()| create(&c3))
+                .then(move |res| {
+                    let ledger = unwrap!(res);
+                    c4.set_upload_ledger(Some((ledger.clone(), "test-app".to_owned())));
+
+                    // Hold the background jobs until both uploads have succeeded.
+                    let (tx, rx) = oneshot::channel::<()>();
+                    let _ = c4.schedule(Priority::Low, move || {
+                        rx.map_err(|_| CoreError::from("Hold released"))
+                    });
+
+                    let first = immutable_data::create(&c4, b"recorded", None);
+                    let second = immutable_data::create(&c4, b"recorded too", None);
+                    first
+                        .join(second)
+                        .and_then(move |(first, second)| {
+                            let names = vec![*first.name(), *second.name()];
+                            let size = serialised_size(&first);
+                            let c5b = c5.clone();
+                            // The uploads are recorded by a background job; the one scheduled
+                            // after it runs once it's done.
+                            c5.put_idata(first)
+                                .join(c5.put_idata(second))
+                                .and_then(move |_| {
+                                    unwrap!(tx.send(()));
+                                    c5b.schedule(Priority::Low, || Ok::<_, CoreError>(()))
+                                })
+                                .map(move |()| (ledger, names, size))
+                        })
+                })
+                .then(move |res| {
+                    let (ledger, names, size) = unwrap!(res);
+                    big_map::list(&c6, &ledger).map(move |batches| {
+                        assert_eq!(batches.len(), 1);
+                        (ledger, names, size)
+                    })
+                })
+                .then(move |res| {
+                    let (ledger, names, size) = unwrap!(res);
+                    list(&c7, &ledger).map(move |uploads| (ledger, names, size, uploads))
+                })
+                .then(move |res| {
+                    let (ledger, names, size, uploads) = unwrap!(res);
+                    assert_eq!(uploads.len(), 2);
+                    assert_eq!(uploads[&names[0]].size, size);
+                    assert_eq!(uploads[&names[0]].app_id, "test-app");
+                    assert!(uploads.contains_key(&names[1]));
+
+                    usage_by_app(&c8, &ledger)
+                })
+                .map(|usage| {
+                    assert_eq!(usage.len(), 1);
+                    assert!(usage["test-app"] > 0);
+                })
+        })
+    }
+}
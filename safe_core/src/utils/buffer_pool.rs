@@ -0,0 +1,114 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Pool of reusable byte buffers, so that large transfers don't allocate and free a
+//! chunk-sized buffer for every chunk they go through. The pool is shared by all threads,
+//! as buffers filled on the event loop thread are often released by FFI callers and
+//! vice versa.
+
+use self_encryption::MAX_CHUNK_SIZE;
+use std::sync::Mutex;
+
+/// Buffers with a smaller capacity aren't worth pooling.
+pub const MIN_POOLED_CAPACITY: usize = 64 * 1024;
+/// Buffers with a bigger capacity, e.g. those holding whole files, are freed rather than
+/// kept around in the pool.
+pub const MAX_POOLED_CAPACITY: usize = MAX_CHUNK_SIZE as usize;
+/// Maximum number of buffers kept in the pool.
+pub const MAX_POOLED_BUFFERS: usize = 16;
+
+lazy_static! {
+    static ref POOL: Mutex<Vec<Vec<u8>>> = Mutex::new(Vec::new());
+}
+
+/// Returns an empty buffer with at least the given capacity, reusing a pooled one when
+/// there's one big enough.
+pub fn take(capacity: usize) -> Vec<u8> {
+    if is_poolable(capacity) {
+        if let Ok(mut pool) = POOL.lock() {
+            if let Some(index) = pool.iter().position(|buffer| buffer.capacity() >= capacity) {
+                return pool.swap_remove(index);
+            }
+        }
+    }
+
+    Vec::with_capacity(capacity)
+}
+
+/// Returns a buffer filled with a copy of `data`.
+pub fn copy(data: &[u8]) -> Vec<u8> {
+    let mut buffer = take(data.len());
+    buffer.extend_from_slice(data);
+    buffer
+}
+
+/// Hands a buffer that is no longer needed back to the pool. Its content is wiped first,
+/// as it might be plain text of private data.
+pub fn recycle(mut buffer: Vec<u8>) {
+    if !is_poolable(buffer.capacity()) {
+        return;
+    }
+
+    for byte in &mut buffer {
+        *byte = 0;
+    }
+    buffer.clear();
+
+    if let Ok(mut pool) = POOL.lock() {
+        if pool.len() < MAX_POOLED_BUFFERS {
+            pool.push(buffer);
+        }
+    }
+}
+
+fn is_poolable(capacity: usize) -> bool {
+    capacity >= MIN_POOLED_CAPACITY && capacity <= MAX_POOLED_CAPACITY
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::PoisonError;
+
+    #[test]
+    fn reuse() {
+        recycle(copy(&[1; MIN_POOLED_CAPACITY]));
+
+        // Other tests use the pool concurrently, so the very same buffer isn't guaranteed
+        // to come back, but whichever does has to be big enough and empty.
+        let buffer = take(MIN_POOLED_CAPACITY);
+        assert!(buffer.capacity() >= MIN_POOLED_CAPACITY);
+        assert!(buffer.is_empty());
+
+        // Small buffers bypass the pool.
+        let small = take(16);
+        assert!(small.capacity() >= 16);
+        recycle(small);
+    }
+
+    #[test]
+    fn big_buffers_bypass_pool() {
+        let big = Vec::with_capacity(MAX_POOLED_CAPACITY + 1);
+        recycle(big);
+
+        // No other test recycles buffers this big, so none can be in the pool.
+        let pool = POOL.lock().unwrap_or_else(PoisonError::into_inner);
+        assert!(pool.iter().all(
+            |buffer| buffer.capacity() <= MAX_POOLED_CAPACITY,
+        ));
+    }
+}
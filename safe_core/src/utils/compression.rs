@@ -0,0 +1,165 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Deflate compression of content before it gets encrypted.
+//!
+//! Compressed content starts with `HEADER`, so readers can detect it and inflate it
+//! transparently. Anything following the end of the deflate stream (e.g. data appended to a
+//! compressed file later on) is passed through unchanged. Inflated content is capped at
+//! `MAX_INFLATED_SIZE`, so a small malicious input can't exhaust the memory.
+
+use errors::CoreError;
+use flate2::Compression;
+use flate2::bufread::DeflateDecoder;
+use flate2::write::DeflateEncoder;
+use routing::ClientError;
+use std::io::{Read, Write};
+use std::mem;
+
+/// Marks the start of compressed content.
+pub const HEADER: &'static [u8] = b"\0SAFE-DEFLATE\0";
+/// Maximum size of inflated content, in bytes.
+pub const MAX_INFLATED_SIZE: u64 = 1024 * 1024 * 1024;
+
+/// Returns `true` if `data` starts with the compression header.
+pub fn is_compressed(data: &[u8]) -> bool {
+    data.starts_with(HEADER)
+}
+
+/// Compresses `data`, prefixing the result with the compression header.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, CoreError> {
+    let mut compressor = Compressor::new();
+    let mut output = compressor.write(data)?;
+    output.extend_from_slice(&compressor.finish()?);
+    Ok(output)
+}
+
+/// Inflates `data` if it starts with the compression header, otherwise returns it as is.
+/// Fails with `DataTooLarge` if the result would exceed `MAX_INFLATED_SIZE`.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>, CoreError> {
+    decompress_with_limit(data, MAX_INFLATED_SIZE)
+}
+
+fn decompress_with_limit(data: &[u8], max_size: u64) -> Result<Vec<u8>, CoreError> {
+    if !is_compressed(data) {
+        return Ok(data.to_vec());
+    }
+
+    let mut decoder = DeflateDecoder::new(&data[HEADER.len()..]);
+    let mut output = Vec::new();
+    let _ = (&mut decoder).take(max_size + 1).read_to_end(
+        &mut output,
+    )?;
+    let trailing = decoder.into_inner();
+    if (output.len() + trailing.len()) as u64 > max_size {
+        return Err(CoreError::RoutingClientError(ClientError::DataTooLarge));
+    }
+    output.extend_from_slice(trailing);
+
+    Ok(output)
+}
+
+/// Streaming compressor, for content written in several pieces.
+pub struct Compressor {
+    encoder: DeflateEncoder<Vec<u8>>,
+}
+
+impl Compressor {
+    /// Creates a compressor with the header already queued for output.
+    pub fn new() -> Self {
+        Compressor { encoder: DeflateEncoder::new(HEADER.to_vec(), Compression::default()) }
+    }
+
+    /// Feeds `data` to the compressor and returns the compressed output produced so far.
+    pub fn write(&mut self, data: &[u8]) -> Result<Vec<u8>, CoreError> {
+        self.encoder.write_all(data)?;
+        Ok(mem::replace(self.encoder.get_mut(), Vec::new()))
+    }
+
+    /// Flushes the remaining compressed output.
+    pub fn finish(self) -> Result<Vec<u8>, CoreError> {
+        Ok(self.encoder.finish()?)
+    }
+}
+
+impl Default for Compressor {
+    fn default() -> Self {
+        Compressor::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let data = repeat(b"{\"key\": \"value\"}", 100);
+        let compressed = unwrap!(compress(&data));
+
+        assert!(is_compressed(&compressed));
+        assert!(compressed.len() < data.len());
+        assert_eq!(unwrap!(decompress(&compressed)), data);
+    }
+
+    #[test]
+    fn streaming() {
+        let data = repeat(b"log line\n", 100);
+        let mut compressor = Compressor::new();
+        let mut compressed = Vec::new();
+
+        for piece in data.chunks(64) {
+            compressed.extend_from_slice(&unwrap!(compressor.write(piece)));
+        }
+        compressed.extend_from_slice(&unwrap!(compressor.finish()));
+
+        assert_eq!(unwrap!(decompress(&compressed)), data);
+    }
+
+    #[test]
+    fn uncompressed_and_trailing_data() {
+        let data = b"plain content".to_vec();
+        assert_eq!(unwrap!(decompress(&data)), data);
+
+        let mut compressed = unwrap!(compress(b"compressed "));
+        compressed.extend_from_slice(b"appended");
+        assert_eq!(unwrap!(decompress(&compressed)), b"compressed appended".to_vec());
+    }
+
+    #[test]
+    fn inflated_size_cap() {
+        let compressed = unwrap!(compress(&[0; 1000]));
+        assert_eq!(unwrap!(decompress_with_limit(&compressed, 1000)).len(), 1000);
+
+        match decompress_with_limit(&compressed, 999) {
+            Err(CoreError::RoutingClientError(ClientError::DataTooLarge)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        // Trailing data counts towards the limit too.
+        let mut compressed = compressed;
+        compressed.push(0);
+        match decompress_with_limit(&compressed, 1000) {
+            Err(CoreError::RoutingClientError(ClientError::DataTooLarge)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+
+    fn repeat(data: &[u8], times: usize) -> Vec<u8> {
+        data.iter().cycle().take(data.len() * times).cloned().collect()
+    }
+}
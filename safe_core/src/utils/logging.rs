@@ -0,0 +1,191 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Logger configuration with per-module log levels.
+
+use config_file_handler::FileHandler;
+use errors::CoreError;
+use log::LevelFilter;
+use std::fs::File;
+use std::io::Write;
+use std::str::FromStr;
+
+/// Name of the log output file used when the host doesn't override it.
+pub const DEFAULT_LOG_FILE_NAME: &str = "Client.log";
+
+/// Log levels for the root logger and individual modules.
+#[derive(Debug, Eq, PartialEq)]
+pub struct LogLevels {
+    /// Level applying to all modules without an explicit level.
+    pub root: LevelFilter,
+    /// Levels of individual modules, e.g. `("safe_core", LevelFilter::Debug)`.
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+impl LogLevels {
+    /// Returns the level for the given module, using the most specific module
+    /// level which applies, or the root level if there's none.
+    pub fn level_for(&self, target: &str) -> LevelFilter {
+        self.modules
+            .iter()
+            .filter(|&&(ref module, _)| {
+                target == module || target.starts_with(&format!("{}::", module))
+            })
+            .max_by_key(|&&(ref module, _)| module.len())
+            .map_or(self.root, |&(_, level)| level)
+    }
+
+    /// Returns the most verbose of all the levels.
+    pub fn max_level(&self) -> LevelFilter {
+        self.modules.iter().map(|&(_, level)| level).fold(
+            self.root,
+            |max, level| if level > max { level } else { max },
+        )
+    }
+}
+
+impl FromStr for LogLevels {
+    type Err = CoreError;
+
+    /// Parses a comma-separated list of `level` or `module=level` items,
+    /// e.g. `"warn,safe_core=debug,routing=error"`. The root level defaults
+    /// to `error` if it's not given.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut levels = LogLevels {
+            root: LevelFilter::Error,
+            modules: Vec::new(),
+        };
+
+        for item in spec.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let mut parts = item.splitn(2, '=');
+            let first = parts.next().unwrap_or("");
+
+            match parts.next() {
+                Some(level) => {
+                    let module = first.trim();
+                    if module.is_empty() ||
+                        !module.chars().all(|c| c.is_alphanumeric() || c == '_' || c == ':')
+                    {
+                        return Err(CoreError::Unexpected(
+                            format!("Invalid module name in log spec: {}", item),
+                        ));
+                    }
+                    levels.modules.push((module.to_owned(), parse_level(level)?));
+                }
+                None => levels.root = parse_level(first)?,
+            }
+        }
+
+        Ok(levels)
+    }
+}
+
+/// Writes a `log.toml` configuring an async file logger with the given levels, so
+/// it's picked up by the next call to `maidsafe_utilities::log::init`.
+/// This replaces any existing `log.toml` in the config location.
+pub fn write_log_config(levels: &LogLevels, output_file_name: &str) -> Result<(), CoreError> {
+    let fh = FileHandler::<()>::new("log.toml", true)?;
+    let mut file = File::create(fh.path())?;
+    file.write_all(log_config(levels, output_file_name).as_bytes())?;
+    Ok(())
+}
+
+fn log_config(levels: &LogLevels, output_file_name: &str) -> String {
+    let mut config = format!(
+        "[appenders.async_file]\n\
+         kind = \"async_file\"\n\
+         output_file_name = {:?}\n\
+         file_timestamp = false\n\
+         append = false\n\
+         \n\
+         [root]\n\
+         level = \"{}\"\n\
+         appenders = [\"async_file\"]\n",
+        output_file_name,
+        level_name(levels.root)
+    );
+
+    for &(ref module, level) in &levels.modules {
+        config.push_str(&format!(
+            "\n[loggers.\"{}\"]\nlevel = \"{}\"\n",
+            module,
+            level_name(level)
+        ));
+    }
+
+    config
+}
+
+fn parse_level(level: &str) -> Result<LevelFilter, CoreError> {
+    LevelFilter::from_str(level.trim()).map_err(|_| {
+        CoreError::Unexpected(format!("Invalid log level: {}", level))
+    })
+}
+
+fn level_name(level: LevelFilter) -> String {
+    format!("{}", level).to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Test parsing of log level specs.
+    #[test]
+    fn parse_spec() {
+        let levels = unwrap!(LogLevels::from_str("warn, safe_core=debug,routing::core=off"));
+        assert_eq!(levels.root, LevelFilter::Warn);
+        assert_eq!(
+            levels.modules,
+            vec![
+                ("safe_core".to_owned(), LevelFilter::Debug),
+                ("routing::core".to_owned(), LevelFilter::Off),
+            ]
+        );
+
+        let levels = unwrap!(LogLevels::from_str("safe_app=trace"));
+        assert_eq!(levels.root, LevelFilter::Error);
+
+        assert!(LogLevels::from_str("loud").is_err());
+        assert!(LogLevels::from_str("safe_core=loud").is_err());
+        assert!(LogLevels::from_str("\"]\nx=debug").is_err());
+    }
+
+    // Test looking up the level of a module.
+    #[test]
+    fn level_for() {
+        let levels = unwrap!(LogLevels::from_str("warn,safe_core=debug,safe_core::nfs=trace"));
+
+        assert_eq!(levels.level_for("routing"), LevelFilter::Warn);
+        assert_eq!(levels.level_for("safe_core"), LevelFilter::Debug);
+        assert_eq!(levels.level_for("safe_core::client"), LevelFilter::Debug);
+        assert_eq!(levels.level_for("safe_core::nfs::dir"), LevelFilter::Trace);
+        assert_eq!(levels.level_for("safe_core_extra"), LevelFilter::Warn);
+        assert_eq!(levels.max_level(), LevelFilter::Trace);
+    }
+
+    // Test that every module gets its own logger section.
+    #[test]
+    fn config() {
+        let levels = unwrap!(LogLevels::from_str("info,safe_core=debug"));
+        let config = log_config(&levels, "out.log");
+
+        assert!(config.contains("output_file_name = \"out.log\""));
+        assert!(config.contains("[root]\nlevel = \"info\""));
+        assert!(config.contains("[loggers.\"safe_core\"]\nlevel = \"debug\""));
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Versioning of persisted formats.
+//!
+//! Data written by `Format::encode` starts with the format's header followed by its version.
+//! `Format::decode` upgrades older data by running it through the remaining migrations, so
+//! a changed layout only needs a new migration appended to the list instead of breaking
+//! existing accounts. Version 0 is written without the header, which keeps it readable by
+//! clients predating versioning, so data lacking the header is treated as version 0.
+
+use errors::CoreError;
+
+/// Upgrades a payload from one version to the next.
+pub type Migration = fn(Vec<u8>) -> Result<Vec<u8>, CoreError>;
+
+/// Versioned format of a persisted structure.
+pub struct Format {
+    /// Marks versioned data.
+    pub header: &'static [u8],
+    /// Migrations in order. The one at index `n` upgrades version `n` to `n + 1`, which makes
+    /// the current version equal to the number of migrations.
+    pub migrations: &'static [Migration],
+}
+
+impl Format {
+    /// Current version of the format.
+    pub fn version(&self) -> u64 {
+        self.migrations.len() as u64
+    }
+
+    /// Prefixes `payload` with the header and the current version.
+    pub fn encode(&self, payload: &[u8]) -> Vec<u8> {
+        if self.migrations.is_empty() {
+            return payload.to_vec();
+        }
+
+        let mut output = Vec::with_capacity(self.header.len() + 8 + payload.len());
+        output.extend_from_slice(self.header);
+        output.extend_from_slice(&u64_to_bytes(self.version()));
+        output.extend_from_slice(payload);
+        output
+    }
+
+    /// Returns the payload of `data` upgraded to the current version, along with whether any
+    /// migration had to run. Fails if the data comes from a newer client.
+    pub fn decode(&self, data: &[u8]) -> Result<(Vec<u8>, bool), CoreError> {
+        let (version, payload) = self.split(data)?;
+        if version > self.version() {
+            return Err(CoreError::UnsupportedFormatVersion(version));
+        }
+
+        let mut payload = payload.to_vec();
+        for migration in &self.migrations[version as usize..] {
+            payload = migration(payload)?;
+        }
+
+        Ok((payload, version < self.version()))
+    }
+
+    fn split<'a>(&self, data: &'a [u8]) -> Result<(u64, &'a [u8]), CoreError> {
+        if !data.starts_with(self.header) {
+            return Ok((0, data));
+        }
+
+        let data = &data[self.header.len()..];
+        if data.len() < 8 {
+            return Err(CoreError::from("Truncated format version"));
+        }
+
+        Ok((bytes_to_u64(&data[..8]), &data[8..]))
+    }
+}
+
+fn u64_to_bytes(value: u64) -> [u8; 8] {
+    let mut bytes = [0; 8];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = (value >> (8 * (7 - i))) as u8;
+    }
+    bytes
+}
+
+fn bytes_to_u64(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0, |value, &byte| (value << 8) | u64::from(byte))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn append_one(mut payload: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+        payload.push(1);
+        Ok(payload)
+    }
+
+    fn append_two(mut payload: Vec<u8>) -> Result<Vec<u8>, CoreError> {
+        payload.push(2);
+        Ok(payload)
+    }
+
+    const V2: Format = Format {
+        header: b"TEST-FMT",
+        migrations: &[append_one, append_two],
+    };
+
+    // Test that current data round-trips without migrations.
+    #[test]
+    fn current_version() {
+        let encoded = V2.encode(&[7, 7]);
+        let (decoded, migrated) = unwrap!(V2.decode(&encoded));
+        assert_eq!(decoded, vec![7, 7]);
+        assert!(!migrated);
+
+        // Version 0 stays unversioned.
+        let v0 = Format {
+            header: V2.header,
+            migrations: &[],
+        };
+        assert_eq!(v0.encode(&[7]), vec![7]);
+        assert_eq!(unwrap!(v0.decode(&[7])), (vec![7], false));
+    }
+
+    // Test that older data runs through the remaining migrations in order.
+    #[test]
+    fn older_versions() {
+        // Unversioned data.
+        let (decoded, migrated) = unwrap!(V2.decode(&[7]));
+        assert_eq!(decoded, vec![7, 1, 2]);
+        assert!(migrated);
+
+        // Data written by a client knowing only the first migration.
+        let v1 = Format {
+            header: V2.header,
+            migrations: &[append_one],
+        };
+        let (decoded, migrated) = unwrap!(V2.decode(&v1.encode(&[7, 1])));
+        assert_eq!(decoded, vec![7, 1, 2]);
+        assert!(migrated);
+    }
+
+    // Test that data from a newer client is rejected.
+    #[test]
+    fn newer_version() {
+        let v3 = Format {
+            header: V2.header,
+            migrations: &[append_one, append_two, append_two],
+        };
+
+        match V2.decode(&v3.encode(&[7])) {
+            Err(CoreError::UnsupportedFormatVersion(3)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+
+        match V2.decode(&V2.header[..]) {
+            Err(CoreError::Unexpected(_)) => (),
+            x => panic!("Unexpected {:?}", x),
+        }
+    }
+}
@@ -18,9 +18,22 @@
 #[macro_use]
 mod futures;
 
+/// Pool of reusable chunk-sized buffers.
+pub mod buffer_pool;
+/// Compression of content before encryption.
+pub mod compression;
+/// Logger configuration.
+pub mod logging;
+/// Versioning and migration of persisted formats.
+pub mod migration;
+/// Containers wiping secrets from memory when dropped.
+pub mod secret;
 /// Common utility functions for writing test cases
 #[cfg(any(test, feature = "testing"))]
+#[macro_use]
 pub mod test_utils;
+/// Compile-time selectable serialisation format.
+pub mod wire_format;
 
 pub use self::futures::FutureExt;
 use errors::CoreError;
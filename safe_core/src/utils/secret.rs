@@ -0,0 +1,303 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Containers for secrets such as passwords and derived keys, which overwrite their content
+//! with zeros when dropped, so the secrets don't linger in freed memory.
+//!
+//! Keys from `rust_sodium` already wipe themselves, so these are for the raw bytes and
+//! strings secrets pass through on their way in and out of those keys. Long-lived keys can
+//! additionally be kept out of swap with `MemoryLock`.
+
+use rust_sodium::utils::memzero;
+use std::collections::HashMap;
+use std::fmt;
+use std::mem;
+use std::ops::Deref;
+use std::sync::{Mutex, PoisonError};
+
+lazy_static! {
+    // Number of live `MemoryLock`s covering each locked page, by page address.
+    static ref LOCKED_PAGES: Mutex<HashMap<usize, usize>> = Mutex::new(HashMap::new());
+}
+
+/// Bytes which get wiped when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretBytes(Vec<u8>);
+
+impl Deref for SecretBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl From<Vec<u8>> for SecretBytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        SecretBytes(bytes)
+    }
+}
+
+impl Drop for SecretBytes {
+    fn drop(&mut self) {
+        memzero(&mut self.0);
+    }
+}
+
+impl fmt::Debug for SecretBytes {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "SecretBytes(..)")
+    }
+}
+
+/// String which gets wiped when dropped.
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretString(String);
+
+impl Deref for SecretString {
+    type Target = str;
+
+    fn deref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<String> for SecretString {
+    fn from(string: String) -> Self {
+        SecretString(string)
+    }
+}
+
+impl From<SecretString> for String {
+    /// Moves the string out, leaving the wiping to whoever receives it.
+    fn from(mut secret: SecretString) -> Self {
+        mem::replace(&mut secret.0, String::new())
+    }
+}
+
+impl Drop for SecretString {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        // Zeros are valid UTF-8, so the string stays valid.
+        unsafe { memzero(self.0.as_mut_vec()) }
+    }
+}
+
+impl fmt::Debug for SecretString {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        write!(formatter, "SecretString(..)")
+    }
+}
+
+/// Lock of the memory a value occupies into RAM, keeping it out of swap. The memory is
+/// unlocked, but not wiped, when the lock is dropped, so the lock must not outlive the value.
+///
+/// Locks are per page, so locks of values sharing a page are counted, and a page is only
+/// unlocked once the last lock covering it is dropped.
+#[derive(Debug)]
+pub struct MemoryLock {
+    // Page-aligned range of the locked pages.
+    start: usize,
+    end: usize,
+}
+
+impl MemoryLock {
+    /// Locks the memory of `value`. Returns `None` if the platform denies locking, e.g.
+    /// because the process exceeded its limit of locked memory, in which case `value` is
+    /// left as it was.
+    #[allow(unsafe_code)]
+    pub fn new<T: ?Sized>(value: &T) -> Option<Self> {
+        let len = mem::size_of_val(value);
+        if len == 0 {
+            return None;
+        }
+
+        let addr = value as *const T as *const u8 as usize;
+        let page_size = sys::page_size();
+        let start = addr - addr % page_size;
+        let end = (addr + len + page_size - 1) / page_size * page_size;
+
+        // Held across the system call, so no page gets unlocked by a concurrent drop
+        // before it's counted.
+        let mut pages = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+        if unsafe { sys::lock(start, end - start) } {
+            for page in (start / page_size..end / page_size).map(|i| i * page_size) {
+                *pages.entry(page).or_insert(0) += 1;
+            }
+            Some(MemoryLock { start, end })
+        } else {
+            warn!("Couldn't lock {} bytes of memory; secrets may get swapped out.", len);
+            None
+        }
+    }
+}
+
+impl Drop for MemoryLock {
+    #[allow(unsafe_code)]
+    fn drop(&mut self) {
+        let page_size = sys::page_size();
+        let mut pages = LOCKED_PAGES.lock().unwrap_or_else(PoisonError::into_inner);
+
+        for page in (self.start / page_size..self.end / page_size).map(|i| i * page_size) {
+            let unused = match pages.get_mut(&page) {
+                Some(count) => {
+                    *count -= 1;
+                    *count == 0
+                }
+                None => false,
+            };
+            if unused {
+                let _ = pages.remove(&page);
+                let _ = unsafe { sys::unlock(page, page_size) };
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+#[allow(unsafe_code)]
+mod sys {
+    use std::os::raw::{c_int, c_void};
+
+    extern "C" {
+        fn getpagesize() -> c_int;
+        fn mlock(addr: *const c_void, len: usize) -> c_int;
+        fn munlock(addr: *const c_void, len: usize) -> c_int;
+    }
+
+    pub fn page_size() -> usize {
+        unsafe { getpagesize() as usize }
+    }
+
+    pub unsafe fn lock(addr: usize, len: usize) -> bool {
+        mlock(addr as *const c_void, len) == 0
+    }
+
+    pub unsafe fn unlock(addr: usize, len: usize) -> bool {
+        munlock(addr as *const c_void, len) == 0
+    }
+}
+
+#[cfg(windows)]
+#[allow(unsafe_code)]
+mod sys {
+    use std::mem;
+    use std::os::raw::{c_int, c_void};
+
+    #[repr(C)]
+    struct SystemInfo {
+        processor_architecture: u16,
+        reserved: u16,
+        page_size: u32,
+        minimum_application_address: *mut c_void,
+        maximum_application_address: *mut c_void,
+        active_processor_mask: usize,
+        number_of_processors: u32,
+        processor_type: u32,
+        allocation_granularity: u32,
+        processor_level: u16,
+        processor_revision: u16,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn GetSystemInfo(info: *mut SystemInfo);
+        fn VirtualLock(addr: *mut c_void, len: usize) -> c_int;
+        fn VirtualUnlock(addr: *mut c_void, len: usize) -> c_int;
+    }
+
+    pub fn page_size() -> usize {
+        unsafe {
+            let mut info: SystemInfo = mem::zeroed();
+            GetSystemInfo(&mut info);
+            info.page_size as usize
+        }
+    }
+
+    pub unsafe fn lock(addr: usize, len: usize) -> bool {
+        VirtualLock(addr as *mut c_void, len) != 0
+    }
+
+    pub unsafe fn unlock(addr: usize, len: usize) -> bool {
+        VirtualUnlock(addr as *mut c_void, len) != 0
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod sys {
+    pub fn page_size() -> usize {
+        4096
+    }
+
+    pub unsafe fn lock(_addr: usize, _len: usize) -> bool {
+        false
+    }
+
+    pub unsafe fn unlock(_addr: usize, _len: usize) -> bool {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn deref() {
+        let bytes = SecretBytes::from(vec![1, 2, 3]);
+        assert_eq!(&*bytes, &[1, 2, 3]);
+
+        let string = SecretString::from("password".to_owned());
+        assert_eq!(&*string, "password");
+        assert_eq!(format!("{:?}", string), "SecretString(..)");
+    }
+
+    #[test]
+    fn memory_lock() {
+        let secret = SecretBytes::from(vec![7; 64]);
+
+        // Locking may be denied in restricted environments, which mustn't be fatal.
+        let lock = MemoryLock::new(&*secret);
+        drop(lock);
+        assert_eq!(&*secret, &[7; 64][..]);
+
+        assert!(MemoryLock::new(&[0u8; 0][..]).is_none());
+    }
+
+    #[test]
+    fn memory_lock_shared_page() {
+        let secrets = SecretBytes::from(vec![7; 64]);
+        let page_size = sys::page_size();
+        let page = &*secrets as *const [u8] as *const u8 as usize / page_size * page_size;
+
+        let first = MemoryLock::new(&secrets[..32]);
+        let second = MemoryLock::new(&secrets[32..]);
+        if first.is_none() || second.is_none() {
+            // Locking is denied in this environment.
+            return;
+        }
+
+        // Other tests may lock the same page concurrently, so counts are lower bounds.
+        let count = || unwrap!(LOCKED_PAGES.lock()).get(&page).cloned().unwrap_or(0);
+        assert!(count() >= 2);
+
+        // The page stays locked for the remaining lock.
+        drop(first);
+        assert!(count() >= 1);
+        drop(second);
+    }
+}
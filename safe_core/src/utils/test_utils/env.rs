@@ -0,0 +1,87 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+use NetworkMode;
+use utils;
+
+/// Network the tests are run against.
+///
+/// Follows the network mode of the clients, so builds with the `use-mock-routing`
+/// feature run against the mock vault, unless the `SAFE_TEST_NETWORK` env var is
+/// set to `live`, in which case they connect to the network configured by the
+/// bootstrap config, same as the builds without the mock vault.
+pub trait TestEnv: Sync {
+    /// Name of the network, for test output.
+    fn name(&self) -> &'static str;
+
+    /// Returns `true` for the mock network, which supports request hooks,
+    /// network limits and simulated disconnects.
+    fn is_mock(&self) -> bool;
+
+    /// Returns the invitation to create test accounts with.
+    fn invitation(&self) -> String;
+}
+
+/// In-process mock vault.
+pub struct MockNetwork;
+
+impl TestEnv for MockNetwork {
+    fn name(&self) -> &'static str {
+        "mock"
+    }
+
+    fn is_mock(&self) -> bool {
+        true
+    }
+
+    fn invitation(&self) -> String {
+        unwrap!(utils::generate_random_string(10))
+    }
+}
+
+/// Live network, e.g. a local test network.
+pub struct LiveNetwork;
+
+impl TestEnv for LiveNetwork {
+    fn name(&self) -> &'static str {
+        "live"
+    }
+
+    fn is_mock(&self) -> bool {
+        false
+    }
+
+    /// Uses the `SAFE_TEST_INVITATION` env var, if set. Otherwise returns a
+    /// random invitation, which only works with networks not requiring one.
+    fn invitation(&self) -> String {
+        ::std::env::var("SAFE_TEST_INVITATION")
+            .unwrap_or_else(|_| unwrap!(utils::generate_random_string(10)))
+    }
+}
+
+/// Returns the network the tests are run against.
+pub fn test_env() -> &'static TestEnv {
+    if use_live_network() {
+        &LiveNetwork
+    } else {
+        &MockNetwork
+    }
+}
+
+fn use_live_network() -> bool {
+    ::client::network_mode() == NetworkMode::Live
+}
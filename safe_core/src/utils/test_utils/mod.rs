@@ -15,9 +15,11 @@
 // Please review the Licences for the specific language governing permissions and limitations
 // relating to use of the SAFE Network Software.
 
+mod env;
 #[cfg(feature = "use-mock-routing")]
 mod sync;
 
+pub use self::env::{LiveNetwork, MockNetwork, TestEnv, test_env};
 #[cfg(feature = "use-mock-routing")]
 pub use self::sync::Synchronizer;
 use Client;
@@ -84,7 +86,7 @@ where
     let c = |el_h, core_tx, net_tx| {
         let acc_locator = unwrap!(utils::generate_random_string(10));
         let acc_password = unwrap!(utils::generate_random_string(10));
-        let invitation = unwrap!(utils::generate_random_string(10));
+        let invitation = test_env().invitation();
         Client::registered(
             &acc_locator,
             &acc_password,
@@ -164,6 +166,20 @@ where
     unwrap!(result_rx.recv())
 }
 
+/// Returns early from a test relying on mock routing features, such as request
+/// hooks, if the tests run against a live network. The calling crate has to import
+/// the `log` macros.
+#[macro_export]
+macro_rules! skip_unless_mock {
+    () => {
+        if !$crate::utils::test_utils::test_env().is_mock() {
+            debug!("Skipped: not supported by the {} network",
+                   $crate::utils::test_utils::test_env().name());
+            return;
+        }
+    }
+}
+
 /// Convenience for creating a blank runner.
 pub fn finish() -> Result<(), ()> {
     Ok(())
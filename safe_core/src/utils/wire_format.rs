@@ -0,0 +1,158 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Serialisation of structures other clients need to read, such as the entries of the
+//! access container.
+//!
+//! The format written is chosen at compile time. By default it's the `maidsafe_utilities`
+//! serialisation used everywhere else, while the `wire-cbor` feature switches to CBOR, which
+//! has decoders in most languages. Every value is tagged with the format it was written in,
+//! so clients sharing an account can read each other's data whichever format they write.
+
+use errors::CoreError;
+use maidsafe_utilities::serialisation;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+
+/// Serialisation format.
+pub trait WireFormat {
+    /// Serialise `data`.
+    fn serialise<T: Serialize>(data: &T) -> Result<Vec<u8>, CoreError>;
+
+    /// Deserialise data serialised by `serialise`.
+    fn deserialise<T: DeserializeOwned>(encoded: &[u8]) -> Result<T, CoreError>;
+}
+
+/// Format of `maidsafe_utilities::serialisation`.
+pub struct MaidsafeUtilities;
+
+impl WireFormat for MaidsafeUtilities {
+    fn serialise<T: Serialize>(data: &T) -> Result<Vec<u8>, CoreError> {
+        Ok(serialisation::serialise(data)?)
+    }
+
+    fn deserialise<T: DeserializeOwned>(encoded: &[u8]) -> Result<T, CoreError> {
+        Ok(serialisation::deserialise(encoded)?)
+    }
+}
+
+/// CBOR (RFC 7049).
+pub struct Cbor;
+
+impl WireFormat for Cbor {
+    fn serialise<T: Serialize>(data: &T) -> Result<Vec<u8>, CoreError> {
+        serde_cbor::to_vec(data).map_err(|error| {
+            CoreError::Unexpected(format!("CBOR serialisation failed: {}", error))
+        })
+    }
+
+    fn deserialise<T: DeserializeOwned>(encoded: &[u8]) -> Result<T, CoreError> {
+        serde_cbor::from_slice(encoded).map_err(|error| {
+            CoreError::Unexpected(format!("CBOR deserialisation failed: {}", error))
+        })
+    }
+}
+
+/// Format selected at compile time.
+#[cfg(not(feature = "wire-cbor"))]
+pub type Selected = MaidsafeUtilities;
+/// Format selected at compile time.
+#[cfg(feature = "wire-cbor")]
+pub type Selected = Cbor;
+
+// Prefixes values serialised by `serialise`. It's followed by the id of the format.
+const TAG: &[u8] = b"SAFE-WIRE";
+
+const MAIDSAFE_UTILITIES_ID: u8 = 0;
+const CBOR_ID: u8 = 1;
+
+#[cfg(not(feature = "wire-cbor"))]
+const SELECTED_ID: u8 = MAIDSAFE_UTILITIES_ID;
+#[cfg(feature = "wire-cbor")]
+const SELECTED_ID: u8 = CBOR_ID;
+
+/// Serialise `data` with the selected format, tagged with the id of the format.
+pub fn serialise<T: Serialize>(data: &T) -> Result<Vec<u8>, CoreError> {
+    let mut encoded = TAG.to_vec();
+    encoded.push(SELECTED_ID);
+    encoded.extend_from_slice(&Selected::serialise(data)?);
+    Ok(encoded)
+}
+
+/// Deserialise data serialised by `serialise` in any of the formats. Untagged data
+/// predates the tags and is tried with `maidsafe_utilities`, then with CBOR.
+pub fn deserialise<T: DeserializeOwned>(encoded: &[u8]) -> Result<T, CoreError> {
+    if encoded.len() > TAG.len() && encoded.starts_with(TAG) {
+        let payload = &encoded[TAG.len() + 1..];
+        match encoded[TAG.len()] {
+            MAIDSAFE_UTILITIES_ID => MaidsafeUtilities::deserialise(payload),
+            CBOR_ID => Cbor::deserialise(payload),
+            id => Err(CoreError::Unexpected(format!("Unknown wire format: {}", id))),
+        }
+    } else {
+        MaidsafeUtilities::deserialise(encoded).or_else(|_| Cbor::deserialise(encoded))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    fn round_trip<F: WireFormat>() {
+        let data: BTreeMap<String, Vec<u8>> = btree_map!["key".to_string() => vec![1, 2, 3]];
+        let encoded = unwrap!(F::serialise(&data));
+        let decoded: BTreeMap<String, Vec<u8>> = unwrap!(F::deserialise(&encoded));
+        assert_eq!(decoded, data);
+    }
+
+    // Test that the formats round-trip.
+    #[test]
+    fn formats() {
+        round_trip::<MaidsafeUtilities>();
+        round_trip::<Selected>();
+    }
+
+    #[test]
+    fn cbor() {
+        round_trip::<Cbor>();
+    }
+
+    // Test that values are read whichever format they were written in.
+    #[test]
+    fn mixed_formats() {
+        let data: BTreeMap<String, Vec<u8>> = btree_map!["key".to_string() => vec![1, 2, 3]];
+
+        let decoded: BTreeMap<String, Vec<u8>> = unwrap!(deserialise(&unwrap!(serialise(&data))));
+        assert_eq!(decoded, data);
+
+        let mut cbor = TAG.to_vec();
+        cbor.push(CBOR_ID);
+        cbor.extend_from_slice(&unwrap!(Cbor::serialise(&data)));
+        let decoded: BTreeMap<String, Vec<u8>> = unwrap!(deserialise(&cbor));
+        assert_eq!(decoded, data);
+
+        // Untagged values written before the tags were introduced.
+        let legacy = unwrap!(MaidsafeUtilities::serialise(&data));
+        let decoded: BTreeMap<String, Vec<u8>> = unwrap!(deserialise(&legacy));
+        assert_eq!(decoded, data);
+
+        let legacy = unwrap!(Cbor::serialise(&data));
+        let decoded: BTreeMap<String, Vec<u8>> = unwrap!(deserialise(&legacy));
+        assert_eq!(decoded, data);
+    }
+}
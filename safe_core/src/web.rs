@@ -0,0 +1,268 @@
+// Copyright 2017 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under (1) the MaidSafe.net Commercial License,
+// version 1.0 or later, or (2) The General Public License (GPL), version 3, depending on which
+// licence you accepted on initial access to the Software (the "Licences").
+//
+// By contributing code to the SAFE Network Software, or to this project generally, you agree to be
+// bound by the terms of the MaidSafe Contributor Agreement.  This, along with the Licenses can be
+// found in the root directory of this project at LICENSE, COPYING and CONTRIBUTOR.
+//
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied.
+//
+// Please review the Licences for the specific language governing permissions and limitations
+// relating to use of the SAFE Network Software.
+
+//! Fetching of `safe://` URLs.
+//!
+//! A URL has the form `safe://[service.]public_name[/path]`. The host part is resolved
+//! through the public name's services (`www` if no service is given) and the path is then
+//! looked up as a file in the resolved directory. Directory paths (empty or ending with `/`)
+//! and paths not matching any file fall back to their `index.html`.
+
+use client::Client;
+use dns;
+use futures::{Future, IntoFuture};
+use nfs::{NfsError, NfsFuture, file_helper};
+use utils::FutureExt;
+
+/// Scheme of the URLs handled by `fetch`.
+pub const SAFE_URL_SCHEME: &str = "safe://";
+
+/// File served for directory paths.
+pub const INDEX_FILE: &str = "index.html";
+
+/// MIME type of files with unknown extensions.
+pub const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// Parsed `safe://` URL.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SafeUrl {
+    /// Public name the URL belongs to.
+    pub public_name: String,
+    /// Service of the public name.
+    pub service: String,
+    /// Path of the file within the service directory, without the leading `/`.
+    pub path: String,
+}
+
+impl SafeUrl {
+    /// Parses a `safe://[service.]public_name[/path]` URL. Query and fragment parts
+    /// are ignored.
+    pub fn parse(url: &str) -> Result<Self, NfsError> {
+        let invalid = || NfsError::Unexpected(format!("Invalid safe:// URL: {}", url));
+
+        if !url.starts_with(SAFE_URL_SCHEME) {
+            return Err(invalid());
+        }
+        let rest = &url[SAFE_URL_SCHEME.len()..];
+        let rest = match rest.find(|c| c == '?' || c == '#') {
+            Some(index) => &rest[..index],
+            None => rest,
+        };
+
+        let (host, path) = match rest.find('/') {
+            Some(index) => (&rest[..index], &rest[index + 1..]),
+            None => (rest, ""),
+        };
+        let (service, public_name) = match host.rfind('.') {
+            Some(index) => (&host[..index], &host[index + 1..]),
+            None => (dns::DEFAULT_SERVICE, host),
+        };
+
+        if public_name.is_empty() || service.is_empty() {
+            return Err(invalid());
+        }
+
+        Ok(SafeUrl {
+            public_name: public_name.to_owned(),
+            service: service.to_owned(),
+            path: path.to_owned(),
+        })
+    }
+}
+
+/// Fetches the file at the given `safe://` URL.
+/// Returns the file content together with its MIME type.
+pub fn fetch<T: 'static>(client: &Client<T>, url: &str) -> Box<NfsFuture<(Vec<u8>, String)>> {
+    let c2 = client.clone();
+    let c3 = client.clone();
+
+    SafeUrl::parse(url)
+        .into_future()
+        .and_then(move |url| {
+            dns::resolve(&c2, &url.public_name, &url.service)
+                .map_err(NfsError::from)
+                .map(move |dir| (dir, url.path))
+        })
+        .and_then(move |(dir, path)| {
+            let name = if path.is_empty() || path.ends_with('/') {
+                format!("{}{}", path, INDEX_FILE)
+            } else {
+                path
+            };
+            let index = format!("{}/{}", name, INDEX_FILE);
+            let c4 = c3.clone();
+
+            file_helper::fetch(c3.clone(), dir.clone(), name.clone())
+                .map({
+                    let name = name.clone();
+                    move |(_, file)| (file, name)
+                })
+                .or_else(move |err| match err {
+                    NfsError::FileNotFound if !name.ends_with(INDEX_FILE) => {
+                        file_helper::fetch(c3, dir, index.clone())
+                            .map(move |(_, file)| (file, index))
+                            .into_box()
+                    }
+                    err => err!(err),
+                })
+                .map(move |(file, name)| (file, name, c4))
+        })
+        .and_then(|(file, name, client)| {
+            file_helper::read(client, &file, None)
+                .and_then(|reader| reader.read(0, reader.size()))
+                .map(move |content| (content, mime_type(&name).to_owned()))
+        })
+        .into_box()
+}
+
+/// Guesses the MIME type of a file from its name.
+pub fn mime_type(name: &str) -> &'static str {
+    let extension = match name.rfind('.') {
+        Some(index) if index > name.rfind('/').map_or(0, |slash| slash + 1) => {
+            name[index + 1..].to_lowercase()
+        }
+        _ => return DEFAULT_MIME_TYPE,
+    };
+
+    match extension.as_str() {
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "js" => "application/javascript",
+        "json" => "application/json",
+        "txt" => "text/plain",
+        "xml" => "application/xml",
+        "svg" => "image/svg+xml",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "ico" => "image/x-icon",
+        "webp" => "image/webp",
+        "pdf" => "application/pdf",
+        "mp3" => "audio/mpeg",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "woff" => "font/woff",
+        "woff2" => "font/woff2",
+        "wasm" => "application/wasm",
+        _ => DEFAULT_MIME_TYPE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DIR_TAG;
+    use client::MDataInfo;
+    use nfs::{File, Mode, create_dir};
+    use utils::generate_random_string;
+    use utils::test_utils::random_client;
+
+    fn put_file<T: 'static>(
+        client: &Client<T>,
+        dir: MDataInfo,
+        name: &'static str,
+        content: &'static [u8],
+    ) -> Box<NfsFuture<()>> {
+        let c2 = client.clone();
+
+        file_helper::write(client.clone(), File::new(Vec::new()), Mode::Overwrite, None)
+            .and_then(move |writer| writer.write(content).and_then(move |_| writer.close()))
+            .and_then(move |file| file_helper::insert(c2, dir, name, &file))
+            .into_box()
+    }
+
+    // Test fetching files of a public name's service through URLs.
+    #[test]
+    fn fetch_url() {
+        let public_name = unwrap!(generate_random_string(10));
+
+        random_client(move |client| {
+            let c2 = client.clone();
+            let c3 = client.clone();
+            let c4 = client.clone();
+            let c5 = client.clone();
+            let c6 = client.clone();
+            let c7 = client.clone();
+            let c8 = client.clone();
+
+            let dir = unwrap!(MDataInfo::random_public(DIR_TAG));
+            let dir2 = dir.clone();
+            let dir3 = dir.clone();
+            let dir4 = dir.clone();
+            let name = public_name.clone();
+
+            create_dir(client, &dir, btree_map![], btree_map![])
+                .and_then(move |_| put_file(&c2, dir2, "index.html", b"<html></html>"))
+                .and_then(move |_| put_file(&c3, dir3, "docs/index.html", b"docs"))
+                .and_then(move |_| put_file(&c4, dir4, "docs/style.css", b"body {}"))
+                .and_then(move |_| {
+                    dns::register(&c5, &name)
+                        .and_then(move |_| dns::set_service(&c5, &name, "blog", &dir))
+                        .map_err(NfsError::from)
+                })
+                .and_then(move |_| {
+                    fetch(&c6, &format!("safe://blog.{}", public_name)).map(
+                        move |res| (res, public_name),
+                    )
+                })
+                .and_then(move |((content, mime), public_name)| {
+                    assert_eq!(content, b"<html></html>");
+                    assert_eq!(mime, "text/html");
+
+                    fetch(&c7, &format!("safe://blog.{}/docs/style.css", public_name))
+                        .map(move |res| (res, public_name))
+                })
+                .and_then(move |((content, mime), public_name)| {
+                    assert_eq!(content, b"body {}");
+                    assert_eq!(mime, "text/css");
+
+                    fetch(&c8, &format!("safe://blog.{}/docs", public_name))
+                })
+                .map(|(content, mime)| {
+                    assert_eq!(content, b"docs");
+                    assert_eq!(mime, "text/html");
+                })
+        });
+    }
+
+    // Test parsing of `safe://` URLs.
+    #[test]
+    fn parse_url() {
+        let url = unwrap!(SafeUrl::parse("safe://name"));
+        assert_eq!(url.public_name, "name");
+        assert_eq!(url.service, dns::DEFAULT_SERVICE);
+        assert_eq!(url.path, "");
+
+        let url = unwrap!(SafeUrl::parse("safe://blog.name/path/to/file.txt?q=1#top"));
+        assert_eq!(url.public_name, "name");
+        assert_eq!(url.service, "blog");
+        assert_eq!(url.path, "path/to/file.txt");
+
+        assert!(SafeUrl::parse("http://name").is_err());
+        assert!(SafeUrl::parse("safe://").is_err());
+        assert!(SafeUrl::parse("safe://blog./file").is_err());
+    }
+
+    // Test guessing of MIME types.
+    #[test]
+    fn mime_types() {
+        assert_eq!(mime_type("index.html"), "text/html");
+        assert_eq!(mime_type("img/LOGO.PNG"), "image/png");
+        assert_eq!(mime_type("dir.d/file"), DEFAULT_MIME_TYPE);
+        assert_eq!(mime_type(".hidden"), DEFAULT_MIME_TYPE);
+    }
+}
@@ -154,6 +154,9 @@ fn authorisation_and_revocation() {
         scope: None,
         name: app_id.clone(), // Use ID for name so the app is easier to find in Browser.
         vendor: unwrap!(utils::generate_readable_string(10)),
+        description: None,
+        homepage: None,
+        icon: None,
     };
 
     println!("Authorising app...");